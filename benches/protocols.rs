@@ -0,0 +1,150 @@
+use std::{env, thread, time::Duration};
+
+use rand::thread_rng;
+use shami_rs::{
+    math::mersenne61::Mersenne61,
+    mpc::{
+        bench::{run_benchmark, BenchConfig},
+        engine::Engine,
+    },
+    net::Network,
+};
+
+/// Configurations of the number of parties and the threshold of the benchmarks.
+const CONFIGURATIONS: [(usize, usize); 3] = [(3, 1), (5, 2), (7, 3)];
+
+/// Number of times that each benchmark is run, of which the median is reported.
+const ITERATIONS: usize = 5;
+
+/// Benchmark of a phase of the protocols.
+struct Scenario {
+    /// Name of the benchmark, by which it can be selected in the command line.
+    name: &'static str,
+    /// Phase of [`run_benchmark`] that is measured.
+    phase: &'static str,
+    /// Parameters of the benchmark.
+    config: BenchConfig,
+    /// Number of operations computed in the phase.
+    operations: usize,
+}
+
+/// Returns the benchmarks of the multiplication, one operation at a time and in a batch, of the
+/// opening of shared values, and of the encryption of shared values with MiMC, which is the
+/// reference circuit of the engine.
+fn scenarios() -> [Scenario; 4] {
+    [
+        Scenario {
+            name: "multiplication",
+            phase: "multiplication",
+            config: BenchConfig {
+                multiplications: 100,
+                openings: 0,
+                batch_size: Some(1),
+                mimc: 0,
+            },
+            operations: 100,
+        },
+        Scenario {
+            name: "batched multiplication",
+            phase: "multiplication",
+            config: BenchConfig {
+                multiplications: 10_000,
+                openings: 0,
+                batch_size: None,
+                mimc: 0,
+            },
+            operations: 10_000,
+        },
+        Scenario {
+            name: "opening",
+            phase: "opening",
+            config: BenchConfig {
+                multiplications: 0,
+                openings: 10_000,
+                batch_size: None,
+                mimc: 0,
+            },
+            operations: 10_000,
+        },
+        Scenario {
+            name: "mimc",
+            phase: "mimc",
+            config: BenchConfig {
+                multiplications: 0,
+                openings: 0,
+                batch_size: None,
+                mimc: 100,
+            },
+            operations: 100,
+        },
+    ]
+}
+
+/// Runs a benchmark with every party of an in-memory network and returns the time of the
+/// measured phase for the slowest party, which is the latency observed by the parties.
+fn run(scenario: &Scenario, n_parties: usize, threshold: usize) -> Duration {
+    let handles: Vec<_> = Network::create_local(n_parties)
+        .into_iter()
+        .map(|network| {
+            let config = scenario.config.clone();
+            let phase = scenario.phase;
+            thread::spawn(move || {
+                let report = run_benchmark::<Mersenne61, _>(
+                    &config,
+                    threshold,
+                    &mut thread_rng(),
+                    &mut Engine::new(network),
+                )
+                .unwrap();
+                report
+                    .phases
+                    .iter()
+                    .find(|report| report.name == phase)
+                    .map(|report| report.elapsed)
+                    .unwrap()
+            })
+        })
+        .collect();
+    handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .max()
+        .unwrap()
+}
+
+/// Runs the end-to-end benchmarks of the protocols over the in-memory network, printing the
+/// median latency and the throughput of each one. The benchmarks can be filtered by passing a
+/// part of their name, e.g. `cargo bench --bench protocols -- opening`.
+fn main() {
+    // Cargo passes `--bench` to the benchmarks, which is not a filter.
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    println!(
+        "{:<24}{:>4}{:>4}{:>8}{:>14}{:>14}{:>14}",
+        "benchmark", "n", "t", "ops", "median (ms)", "ms/op", "ops/s"
+    );
+    for scenario in scenarios() {
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !scenario.name.contains(filter.as_str()))
+        {
+            continue;
+        }
+        for (n_parties, threshold) in CONFIGURATIONS {
+            let mut times: Vec<Duration> = (0..ITERATIONS)
+                .map(|_| run(&scenario, n_parties, threshold))
+                .collect();
+            times.sort();
+            let median = times[ITERATIONS / 2].as_secs_f64();
+            println!(
+                "{:<24}{:>4}{:>4}{:>8}{:>14.3}{:>14.4}{:>14.0}",
+                scenario.name,
+                n_parties,
+                threshold,
+                scenario.operations,
+                median * 1000.0,
+                median * 1000.0 / scenario.operations as f64,
+                scenario.operations as f64 / median
+            );
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use aws_lc_rs::{
+    aead::{UnboundKey, AES_256_GCM},
+    digest::{self, SHA256},
+};
+use clap::{Parser, Subcommand};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use shami_rs::{
+    math::mersenne127::Mersenne127,
+    mpc::bytes::{reconstruct_bytes, share_bytes, ByteShare},
+};
+use zeroize::Zeroizing;
+
+/// Size in bytes of an AES-256 key.
+const KEY_SIZE: usize = 32;
+
+/// Prefix of the digest that identifies an escrowed key, so the fingerprint is not the plain
+/// digest of the key.
+const FINGERPRINT_DOMAIN: &[u8] = b"shami-rs key escrow fingerprint";
+
+/// Escrow of an AES-256 key among custodians: the key is Shamir-shared in a file per custodian,
+/// and any `threshold + 1` custodians can recover it later from their files.
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Shares an AES-256 key among the custodians, writing the file of each one.
+    Split {
+        /// Number of custodians.
+        #[arg(short = 'n', long)]
+        custodians: usize,
+        /// Maximum number of custodians that learn nothing about the key, so `threshold + 1`
+        /// custodians are needed to recover it.
+        #[arg(short, long)]
+        threshold: usize,
+        /// File with the key to escrow in hexadecimal. By default, a fresh key is generated.
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+        /// Directory where the file of each custodian is written.
+        #[arg(short, long)]
+        out_dir: PathBuf,
+    },
+    /// Recovers the key from the files of the custodians and prints it in hexadecimal.
+    Recover {
+        /// Files of the custodians. Corrupted files are skipped, and at least `threshold + 1`
+        /// correct files are needed.
+        files: Vec<PathBuf>,
+    },
+}
+
+/// Contents of the file of a custodian that are covered by its integrity tag.
+#[derive(Serialize, Deserialize)]
+struct Escrow {
+    /// Number of custodians among which the key is shared.
+    custodians: usize,
+    /// Degree of the shares of the key.
+    threshold: usize,
+    /// Fingerprint of the escrowed key, with which the recovered key is checked.
+    fingerprint: String,
+    /// Share of the key held by the custodian.
+    share: ByteShare<Mersenne127>,
+}
+
+impl Escrow {
+    /// Returns the integrity tag of the contents, which is the SHA-256 digest of their JSON.
+    fn tag(&self) -> anyhow::Result<String> {
+        Ok(to_hex(
+            digest::digest(&SHA256, &serde_json::to_vec(self)?).as_ref(),
+        ))
+    }
+}
+
+/// File of a custodian, with the integrity tag that tells a corrupted or edited file.
+#[derive(Serialize, Deserialize)]
+struct CustodianFile {
+    /// Contents of the file covered by the tag.
+    escrow: Escrow,
+    /// Integrity tag of the contents of the file.
+    tag: String,
+}
+
+fn main() -> anyhow::Result<()> {
+    match Cli::parse().command {
+        Command::Split {
+            custodians,
+            threshold,
+            key_file,
+            out_dir,
+        } => split(custodians, threshold, key_file.as_deref(), &out_dir),
+        Command::Recover { files } => recover(&files),
+    }
+}
+
+/// Shares the key among the custodians and writes the file of each one in the output directory.
+fn split(
+    custodians: usize,
+    threshold: usize,
+    key_file: Option<&Path>,
+    out_dir: &Path,
+) -> anyhow::Result<()> {
+    if threshold >= custodians {
+        anyhow::bail!(
+            "{custodians} custodians can not recover a key shared with threshold {threshold}"
+        );
+    }
+    let key = match key_file {
+        Some(path) => {
+            let contents = Zeroizing::new(fs::read_to_string(path)?);
+            let key = from_hex(contents.trim()).context("the key is not hexadecimal")?;
+            if key.len() != KEY_SIZE {
+                anyhow::bail!(
+                    "the key has {} bytes, but AES-256 keys have {KEY_SIZE}",
+                    key.len()
+                );
+            }
+            key
+        }
+        None => {
+            let mut key = Zeroizing::new(vec![0; KEY_SIZE]);
+            OsRng.fill_bytes(&mut key);
+            key
+        }
+    };
+
+    let fingerprint = fingerprint(&key);
+    fs::create_dir_all(out_dir)?;
+    for share in share_bytes(&key, custodians, threshold, &mut OsRng) {
+        let path = out_dir.join(format!("custodian_{}.json", share.party_id));
+        let escrow = Escrow {
+            custodians,
+            threshold,
+            fingerprint: fingerprint.clone(),
+            share,
+        };
+        let file = CustodianFile {
+            tag: escrow.tag()?,
+            escrow,
+        };
+        write_private(&path, &serde_json::to_string_pretty(&file)?)?;
+        println!("wrote {}", path.display());
+    }
+    println!("escrowed the key with fingerprint {fingerprint}");
+    Ok(())
+}
+
+/// Recovers the key from the files of the custodians, checking their integrity tags and the
+/// fingerprint of the recovered key.
+fn recover(files: &[PathBuf]) -> anyhow::Result<()> {
+    let mut escrows = Vec::with_capacity(files.len());
+    for path in files {
+        let file: CustodianFile = serde_json::from_str(&fs::read_to_string(path)?)
+            .with_context(|| format!("{} is not a custodian file", path.display()))?;
+        if file.escrow.tag()? != file.tag {
+            eprintln!(
+                "skipping {}: its integrity tag does not match",
+                path.display()
+            );
+            continue;
+        }
+        escrows.push(file.escrow);
+    }
+    let Some(first) = escrows.first() else {
+        anyhow::bail!("there are no correct custodian files");
+    };
+    let (threshold, expected) = (first.threshold, first.fingerprint.clone());
+    if escrows
+        .iter()
+        .any(|escrow| escrow.fingerprint != expected || escrow.threshold != threshold)
+    {
+        anyhow::bail!("the custodian files belong to different keys");
+    }
+    if escrows.len() <= threshold {
+        anyhow::bail!(
+            "{} correct custodian files were given, but {} are needed",
+            escrows.len(),
+            threshold + 1
+        );
+    }
+
+    let shares: Vec<_> = escrows.into_iter().map(|escrow| escrow.share).collect();
+    let key = reconstruct_bytes(&shares)?;
+    if fingerprint(&key) != expected {
+        anyhow::bail!("the recovered key does not match the fingerprint of the escrow");
+    }
+    UnboundKey::new(&AES_256_GCM, &key)
+        .map_err(|_| anyhow::anyhow!("the recovered key is not an AES-256 key"))?;
+    println!("{}", *Zeroizing::new(to_hex(&key)));
+    Ok(())
+}
+
+/// Returns the fingerprint of a key in hexadecimal.
+fn fingerprint(key: &[u8]) -> String {
+    let mut context = digest::Context::new(&SHA256);
+    context.update(FINGERPRINT_DOMAIN);
+    context.update(key);
+    to_hex(context.finish().as_ref())
+}
+
+/// Encodes bytes as a string of hexadecimal digits.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decodes a string of hexadecimal digits, or returns `None` if it is not one.
+fn from_hex(hex: &str) -> Option<Zeroizing<Vec<u8>>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(Zeroizing::new(bytes))
+}
+
+/// Writes a file that only the owner can read, since it holds a share of the key.
+fn write_private(path: &Path, contents: &str) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::{io::Write, os::unix::fs::OpenOptionsExt};
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(contents.as_bytes())?;
+    }
+    #[cfg(not(unix))]
+    fs::write(path, contents)?;
+    Ok(())
+}
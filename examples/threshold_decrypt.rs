@@ -0,0 +1,329 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use anyhow::Context;
+use aws_lc_rs::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use clap::{Parser, Subcommand};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use shami_rs::{
+    math::mersenne127::Mersenne127,
+    mpc::bytes::{reconstruct_bytes, share_bytes, ByteShare},
+    net::{Network, NetworkConfig},
+};
+use zeroize::Zeroizing;
+
+/// Size in bytes of an AES-256 key.
+const KEY_SIZE: usize = 32;
+
+/// Magic bytes at the start of an encrypted file, which are also authenticated with it.
+const MAGIC: &[u8; 8] = b"SHAMIENC";
+
+/// Hybrid encryption of files under a key shared among custodians. A file is encrypted with
+/// AES-256-GCM under a fresh key, whose shares are given to the custodians. To decrypt it, a
+/// requester asks the custodians for their shares, and each custodian only sends its share to
+/// the requester if it approves the request, so the key only exists again at the requester once
+/// `threshold + 1` custodians approve, and never at any custodian.
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Encrypts a file under a fresh key and writes the share of the key of each custodian.
+    Encrypt {
+        /// Number of custodians.
+        #[arg(short = 'n', long)]
+        custodians: usize,
+        /// Maximum number of custodians that learn nothing about the key, so `threshold + 1`
+        /// approvals are needed to decrypt.
+        #[arg(short, long)]
+        threshold: usize,
+        /// File to encrypt.
+        #[arg(long)]
+        input: PathBuf,
+        /// Encrypted file.
+        #[arg(long)]
+        output: PathBuf,
+        /// Directory where the share of each custodian is written.
+        #[arg(long)]
+        shares_dir: PathBuf,
+    },
+    /// Serves a decryption request as a custodian. The custodians have the IDs of their shares,
+    /// and the requester has the ID that follows the last custodian.
+    Custodian {
+        /// Network configuration of the custodian.
+        #[arg(long)]
+        net_config: PathBuf,
+        /// Share of the key held by the custodian.
+        #[arg(long)]
+        share: PathBuf,
+        /// Approve the request, sending the share to the requester.
+        #[arg(long)]
+        approve: bool,
+    },
+    /// Requests the decryption of a file from the custodians.
+    Request {
+        /// Network configuration of the requester.
+        #[arg(long)]
+        net_config: PathBuf,
+        /// Number of custodians.
+        #[arg(short = 'n', long)]
+        custodians: usize,
+        /// Encrypted file.
+        #[arg(long)]
+        input: PathBuf,
+        /// Decrypted file.
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Runs the custodians and the requester of a decryption in this process, each one in its
+    /// own thread, connected through an in-memory network.
+    Simulate {
+        /// Directory with the shares of the custodians.
+        #[arg(long)]
+        shares_dir: PathBuf,
+        /// Number of custodians.
+        #[arg(short = 'n', long)]
+        custodians: usize,
+        /// IDs of the custodians that approve the request, separated by commas.
+        #[arg(long, value_delimiter = ',')]
+        approve: Vec<usize>,
+        /// Encrypted file.
+        #[arg(long)]
+        input: PathBuf,
+        /// Decrypted file.
+        #[arg(long)]
+        output: PathBuf,
+    },
+}
+
+/// Share of the key of an encrypted file held by a custodian.
+#[derive(Serialize, Deserialize)]
+struct KeyShare {
+    /// Degree of the shares of the key.
+    threshold: usize,
+    /// Share of the key.
+    share: ByteShare<Mersenne127>,
+}
+
+/// Answer of a custodian to a decryption request.
+#[derive(Serialize, Deserialize)]
+enum Answer {
+    /// The custodian approves the request and sends its share of the key.
+    Approve(KeyShare),
+    /// The custodian refuses the request.
+    Refuse,
+}
+
+fn main() -> anyhow::Result<()> {
+    match Cli::parse().command {
+        Command::Encrypt {
+            custodians,
+            threshold,
+            input,
+            output,
+            shares_dir,
+        } => encrypt(custodians, threshold, &input, &output, &shares_dir),
+        Command::Custodian {
+            net_config,
+            share,
+            approve,
+        } => {
+            let share = read_share(&share)?;
+            let mut network =
+                Network::create(share.share.party_id, NetworkConfig::new(&net_config)?)?;
+            serve_custodian(&mut network, share, approve)
+        }
+        Command::Request {
+            net_config,
+            custodians,
+            input,
+            output,
+        } => {
+            let mut network = Network::create(custodians, NetworkConfig::new(&net_config)?)?;
+            let key = request_key(&mut network)?;
+            decrypt(&key, &input, &output)
+        }
+        Command::Simulate {
+            shares_dir,
+            custodians,
+            approve,
+            input,
+            output,
+        } => simulate(&shares_dir, custodians, &approve, &input, &output),
+    }
+}
+
+/// Encrypts a file under a fresh key, and shares the key among the custodians.
+fn encrypt(
+    custodians: usize,
+    threshold: usize,
+    input: &Path,
+    output: &Path,
+    shares_dir: &Path,
+) -> anyhow::Result<()> {
+    if threshold >= custodians {
+        anyhow::bail!(
+            "{custodians} custodians can not approve a key shared with threshold {threshold}"
+        );
+    }
+    let mut key = Zeroizing::new([0; KEY_SIZE]);
+    OsRng.fill_bytes(key.as_mut());
+    let mut nonce = [0; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut contents = fs::read(input)?;
+    sealing_key(key.as_ref())?
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(MAGIC),
+            &mut contents,
+        )
+        .map_err(|_| anyhow::anyhow!("the file could not be encrypted"))?;
+    fs::write(output, [MAGIC.as_slice(), &nonce, &contents].concat())?;
+
+    fs::create_dir_all(shares_dir)?;
+    for share in share_bytes(key.as_ref(), custodians, threshold, &mut OsRng) {
+        let path = shares_dir.join(format!("custodian_{}.json", share.party_id));
+        write_private(
+            &path,
+            &serde_json::to_string(&KeyShare { threshold, share })?,
+        )?;
+    }
+    println!(
+        "encrypted {} into {}, with the key shared among {custodians} custodians in {}",
+        input.display(),
+        output.display(),
+        shares_dir.display()
+    );
+    Ok(())
+}
+
+/// Decrypts a file with the reconstructed key.
+fn decrypt(key: &[u8], input: &Path, output: &Path) -> anyhow::Result<()> {
+    let contents = fs::read(input)?;
+    let header = MAGIC.len() + NONCE_LEN;
+    if contents.len() < header || !contents.starts_with(MAGIC) {
+        anyhow::bail!("{} is not an encrypted file", input.display());
+    }
+    // The unwrap is safe because the slice has the size of a nonce.
+    let nonce = contents[MAGIC.len()..header].try_into().unwrap();
+    let mut plaintext = Zeroizing::new(contents[header..].to_vec());
+    let len = sealing_key(key)?
+        .open_in_place(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(MAGIC),
+            &mut plaintext,
+        )
+        .map_err(|_| anyhow::anyhow!("the file was not encrypted under the shared key"))?
+        .len();
+    fs::write(output, &plaintext[..len])?;
+    println!("decrypted {} into {}", input.display(), output.display());
+    Ok(())
+}
+
+/// Returns the AES-256-GCM key of the given bytes.
+fn sealing_key(key: &[u8]) -> anyhow::Result<LessSafeKey> {
+    let key = UnboundKey::new(&AES_256_GCM, key)
+        .map_err(|_| anyhow::anyhow!("the key is not an AES-256 key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Answers the request of the requester, which is the party that follows the custodians. The
+/// share only leaves the custodian if it approves the request, and it is only sent to the
+/// requester, so no custodian ever holds the shares of the others.
+fn serve_custodian(network: &mut Network, share: KeyShare, approve: bool) -> anyhow::Result<()> {
+    let requester = network.n_parties() - 1;
+    let answer = if approve {
+        Answer::Approve(share)
+    } else {
+        Answer::Refuse
+    };
+    network.send_value(&answer, requester)?;
+    network.close()
+}
+
+/// Collects the answers of the custodians and reconstructs the key if at least `threshold + 1`
+/// of them approve the request.
+fn request_key(network: &mut Network) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let mut shares = Vec::new();
+    let mut threshold = None;
+    for custodian in 0..network.n_parties() - 1 {
+        match network.recv_value(custodian)? {
+            Answer::Approve(share) => {
+                if *threshold.get_or_insert(share.threshold) != share.threshold {
+                    anyhow::bail!("the custodians hold shares of different keys");
+                }
+                shares.push(share.share);
+            }
+            Answer::Refuse => println!("custodian {custodian} refused the request"),
+        }
+    }
+    network.close()?;
+    let Some(threshold) = threshold else {
+        anyhow::bail!("no custodian approved the request");
+    };
+    if shares.len() <= threshold {
+        anyhow::bail!(
+            "{} custodians approved the request, but {} approvals are needed",
+            shares.len(),
+            threshold + 1
+        );
+    }
+    Ok(reconstruct_bytes(&shares)?)
+}
+
+/// Runs the custodians and the requester of a decryption in threads of this process.
+fn simulate(
+    shares_dir: &Path,
+    custodians: usize,
+    approve: &[usize],
+    input: &Path,
+    output: &Path,
+) -> anyhow::Result<()> {
+    let mut networks = Network::create_local(custodians + 1);
+    let mut requester = networks.pop().context("there is no requester")?;
+    let handles: Vec<_> = networks
+        .into_iter()
+        .map(|mut network| {
+            let path = shares_dir.join(format!("custodian_{}.json", network.id()));
+            let approves = approve.contains(&network.id());
+            thread::spawn(move || serve_custodian(&mut network, read_share(&path)?, approves))
+        })
+        .collect();
+    let key = request_key(&mut requester);
+    for handle in handles {
+        handle.join().expect("a custodian panicked")?;
+    }
+    decrypt(&key?, input, output)
+}
+
+/// Reads the share of a custodian.
+fn read_share(path: &Path) -> anyhow::Result<KeyShare> {
+    serde_json::from_str(&Zeroizing::new(fs::read_to_string(path)?))
+        .with_context(|| format!("{} is not the share of a custodian", path.display()))
+}
+
+/// Writes a file that only the owner can read, since it holds a share of the key.
+fn write_private(path: &Path, contents: &str) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::{io::Write, os::unix::fs::OpenOptionsExt};
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(contents.as_bytes())?;
+    }
+    #[cfg(not(unix))]
+    fs::write(path, contents)?;
+    Ok(())
+}
@@ -7,18 +7,22 @@ cd certs
 # Create a self-signed root CA
 openssl req -x509 -sha256 -nodes -subj "/C=FI/CN=hdvanegasm" -days 1825 -newkey rsa:2048 -keyout rootCA.key -out rootCA.crt
 
-# Create file localhost.ext with the following content:
-cat <<'EOF' >localhost.ext
+n_certificates=$(expr $1 - 1)
+for i in $(seq 0 $n_certificates); do
+  # Create file party_p$i.ext with the following content. The DNS name `party$i` identifies the
+  # party when it connects to the other parties using mutual TLS.
+  cat <<EOF >"party_p$i.ext"
   authorityKeyIdentifier=keyid,issuer
   basicConstraints=CA:FALSE
   subjectAltName = @alt_names
   [alt_names]
   DNS.1 = server
-  IP.1 = 127.0.0.1 
+  DNS.2 = localhost
+  DNS.3 = party$i
+  IP.1 = 127.0.0.1
+  IP.2 = ::1
 EOF
 
-n_certificates=$(expr $1 - 1)
-for i in $(seq 0 $n_certificates); do
   # Create unencrypted private key and a CSR (certificate signing request)
   openssl req -newkey rsa:2048 -nodes -subj "/C=FI/CN=hdvanegasm" -keyout "priv_key_p$i.pem" -out "server_cert_p$i.csr"
 
@@ -27,5 +31,5 @@ for i in $(seq 0 $n_certificates); do
 
   # Sign the CSR (`cert.pem`) with the root CA certificate and private key
   # => this overwrites `cert.pem` because it gets signed
-  openssl x509 -req -CA rootCA.crt -CAkey rootCA.key -in "server_cert_p$i.csr" -out "server_cert_p$i.crt" -days 365 -CAcreateserial -extfile localhost.ext
+  openssl x509 -req -CA rootCA.crt -CAkey rootCA.key -in "server_cert_p$i.csr" -out "server_cert_p$i.crt" -days 365 -CAcreateserial -extfile "party_p$i.ext"
 done
@@ -0,0 +1,15 @@
+#!/bin/bash
+
+# Generates the static X25519 keys of the Noise transport for the given number of parties.
+mkdir -p keys
+cd keys
+
+n_keys=$(expr $1 - 1)
+for i in $(seq 0 $n_keys); do
+  # Create the private key in PEM format.
+  openssl genpkey -algorithm X25519 -out "noise_key_p$i.pem"
+
+  # The raw keys are the last 32 bytes of the DER encodings, which are written in hexadecimal.
+  openssl pkey -in "noise_key_p$i.pem" -outform DER | tail -c 32 | xxd -p -c 32 >"noise_key_p$i.hex"
+  openssl pkey -in "noise_key_p$i.pem" -pubout -outform DER | tail -c 32 | xxd -p -c 32 >"noise_pub_p$i.hex"
+done
@@ -0,0 +1,98 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use rand::rngs::OsRng;
+use shami_rs::{
+    mpc::{
+        beacon::{self, run_beacon_round, BeaconLog},
+        http::spawn_http_handler,
+    },
+    net::{Network, NetworkConfig},
+};
+
+/// Number of consecutive rounds that may fail before the beacon stops, e.g. because the
+/// connections with the other parties were lost.
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+/// Distributed randomness beacon: the parties run a round of commit-reveal coin tossing
+/// periodically, and every party publishes the beacon values with their transcripts through an
+/// HTTP API, at `GET /beacon/latest` and `GET /beacon/{round}`. Every party must run the beacon
+/// with the same interval.
+#[derive(Parser)]
+struct Cli {
+    /// ID of the party.
+    #[arg(short, long)]
+    id: usize,
+    /// Network configuration of the party.
+    #[arg(short, long)]
+    net_config: PathBuf,
+    /// Time between the starts of two rounds, in milliseconds.
+    #[arg(long, default_value_t = 10_000)]
+    interval_ms: u64,
+    /// Number of rounds after which the beacon stops. By default, it runs until it fails.
+    #[arg(long)]
+    rounds: Option<u32>,
+    /// Address at which the HTTP API listens. It should only be reachable by the applications
+    /// that consume the beacon.
+    #[arg(long)]
+    http: Option<SocketAddr>,
+    /// File to which the record of every beacon value is appended, one JSON object per line.
+    #[arg(long)]
+    transcript: Option<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let cli = Cli::parse();
+    let mut network = Network::create(cli.id, NetworkConfig::new(&cli.net_config)?)?;
+    let log = Arc::new(BeaconLog::new());
+    if let Some(address) = cli.http {
+        let log = Arc::clone(&log);
+        spawn_http_handler(address, move |method, target, _| {
+            beacon::route(&log, method, target)
+        })?;
+    }
+    let mut transcript = cli
+        .transcript
+        .as_ref()
+        .map(|path| OpenOptions::new().create(true).append(true).open(path))
+        .transpose()?;
+
+    let interval = Duration::from_millis(cli.interval_ms);
+    let start = Instant::now();
+    let mut failures = 0;
+    for round in 1..=cli.rounds.unwrap_or(u32::MAX) {
+        match run_beacon_round(round, &mut OsRng, &mut network) {
+            Ok(record) => {
+                failures = 0;
+                if let Some(transcript) = &mut transcript {
+                    writeln!(transcript, "{}", serde_json::to_string(&record)?)?;
+                    transcript.flush()?;
+                }
+                println!("{} {}", record.round, record.value);
+                log.push(record);
+            }
+            Err(err) => {
+                failures += 1;
+                log::warn!("round {round} of the beacon failed: {err:#}");
+                if failures == MAX_CONSECUTIVE_FAILURES {
+                    anyhow::bail!("{failures} consecutive rounds of the beacon failed");
+                }
+            }
+        }
+        // The rounds start at fixed times, so a slow round does not delay the following ones.
+        let next = interval * round;
+        if let Some(wait) = next.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+    }
+    network.close()
+}
@@ -0,0 +1,93 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+/// Allocator that delegates to the system allocator, but while a canary is armed, it looks for
+/// the canary in every block that is freed. A block that still contains the canary when it is
+/// freed means that a secret was left in memory. The blocks moved by a reallocation are not
+/// checked.
+struct CanaryAllocator;
+
+#[global_allocator]
+static ALLOCATOR: CanaryAllocator = CanaryAllocator;
+
+/// Canary for the tests, which is lower than the modulus of every field, so it can be the value
+/// of a field element.
+pub(crate) const CANARY: u64 = 0x1ca7_a11e_5ec2_e75a;
+
+/// Canary that is armed, or zero if none is.
+static ARMED_CANARY: AtomicU64 = AtomicU64::new(0);
+
+/// Number of freed blocks that contained the canary since it was armed.
+static FREED_COPIES: AtomicUsize = AtomicUsize::new(0);
+
+/// Lock that prevents two tests from arming a canary at the same time.
+static ARMED: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for CanaryAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let canary = ARMED_CANARY.load(Ordering::Relaxed);
+        if canary != 0 {
+            // The block is still allocated, so it can be read before it is handed back.
+            let block = std::slice::from_raw_parts(ptr, layout.size());
+            let canary = canary.to_le_bytes();
+            if block.windows(canary.len()).any(|window| window == canary) {
+                FREED_COPIES.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Runs a function while the given canary is armed and returns the number of blocks freed in
+/// the meantime that still contained its little-endian bytes. The canary must not be zero, and
+/// it should be unlikely to appear in the memory of the rest of tests.
+pub(crate) fn freed_copies<F: FnOnce()>(canary: u64, f: F) -> usize {
+    assert_ne!(canary, 0, "the canary must not be zero");
+    let _armed = ARMED.lock().unwrap_or_else(PoisonError::into_inner);
+    FREED_COPIES.store(0, Ordering::Relaxed);
+    ARMED_CANARY.store(canary, Ordering::Relaxed);
+    f();
+    ARMED_CANARY.store(0, Ordering::Relaxed);
+    FREED_COPIES.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hint::black_box;
+
+    use zeroize::Zeroize;
+
+    use super::{freed_copies, CANARY};
+
+    #[test]
+    fn detect_secrets_left_in_freed_memory() {
+        let leaked = freed_copies(CANARY, || {
+            let secret = black_box(CANARY.to_le_bytes().to_vec());
+            drop(secret);
+        });
+        assert_eq!(leaked, 1);
+        let wiped = freed_copies(CANARY, || {
+            let mut secret = black_box(CANARY.to_le_bytes().to_vec());
+            secret.zeroize();
+            drop(secret);
+        });
+        assert_eq!(wiped, 0);
+    }
+}
@@ -0,0 +1,420 @@
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+/// Encoding in the format of postcard, a compact format based on variable-length integers. It is
+/// implemented here rather than taken from the `postcard` crate, and checked against the vectors
+/// of its specification.
+pub mod postcard;
+/// Encoding in the wire format of Protocol Buffers, readable from other languages. It is
+/// implemented here on top of serde rather than generated by `prost`, and checked against the
+/// vectors of the encoding guide.
+pub mod protobuf;
+
+/// Possible errors that may appear while encoding or decoding a value.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CodecError {
+    /// The value reported an error while it was encoded or decoded.
+    #[error("{0}")]
+    Custom(String),
+
+    /// The bytes end in the middle of a value.
+    #[error("the bytes end in the middle of a value")]
+    UnexpectedEnd,
+
+    /// There are bytes left after the value.
+    #[error("there are {0} bytes left after the value")]
+    TrailingBytes(usize),
+
+    /// A variable-length integer takes more bytes than the largest integer.
+    #[error("a variable-length integer is too long")]
+    VarintOverflow,
+
+    /// An integer does not fit in the type of the value.
+    #[error("the integer {0} does not fit in the type of the value")]
+    IntegerOutOfRange(u128),
+
+    /// A byte that encodes a boolean or the presence of an option is neither zero nor one.
+    #[error("the byte {0} is not a valid tag of a boolean or an option")]
+    InvalidTag(u64),
+
+    /// A string is not valid UTF-8, or a character is not a single one.
+    #[error("a string is not valid UTF-8 or a character is not a single one")]
+    InvalidString,
+
+    /// A sequence or a map does not know its length before it is encoded.
+    #[error("the length of the sequences and the maps must be known in advance")]
+    UnknownLength,
+
+    /// The type of the value must be known to decode it, since the format is not
+    /// self-describing.
+    #[error("the format is not self-describing, so the type of the values must be known")]
+    NotSelfDescribing,
+
+    /// A field of a Protocol Buffers message has an unexpected wire type.
+    #[error("a field has the wire type {found}, but the wire type {expected} was expected")]
+    WireTypeMismatch { expected: u8, found: u8 },
+
+    /// A field of a Protocol Buffers message has a wire type that is not supported.
+    #[error("the wire type {0} is not supported")]
+    UnsupportedWireType(u8),
+}
+
+impl serde::ser::Error for CodecError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CodecError::Custom(msg.to_string())
+    }
+}
+
+impl serde::de::Error for CodecError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CodecError::Custom(msg.to_string())
+    }
+}
+
+/// Encoding of the values that the parties send to each other, such as the shares and the
+/// messages of the protocols. Every party must use the same codec.
+pub trait WireCodec {
+    /// Encodes a value into bytes.
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> anyhow::Result<Vec<u8>>;
+
+    /// Decodes a value of the type `T` from the bytes of [`WireCodec::encode`].
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T>;
+}
+
+/// Codec of bincode, which is the fastest one but whose format is only specified by the
+/// implementation in Rust.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+impl WireCodec for BincodeCodec {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// Codec of postcard, whose format is stable and documented, and takes fewer bytes for small
+/// integers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostcardCodec;
+
+impl WireCodec for PostcardCodec {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        Ok(postcard::to_vec(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+        Ok(postcard::from_slice(bytes)?)
+    }
+}
+
+/// Codec of the wire format of Protocol Buffers, whose messages can be decoded from other
+/// languages with the schemas described in [`protobuf`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtobufCodec;
+
+impl WireCodec for ProtobufCodec {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        Ok(protobuf::to_vec(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+        Ok(protobuf::from_slice(bytes)?)
+    }
+}
+
+/// Codec selected in the configuration of a network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    /// Encoding of bincode.
+    #[default]
+    Bincode,
+    /// Encoding of postcard.
+    Postcard,
+    /// Wire format of Protocol Buffers.
+    Protobuf,
+}
+
+impl Codec {
+    /// Returns the byte that identifies the codec in the encoded values, so a party that uses
+    /// another codec is told apart from a party that sends a corrupted value.
+    pub fn id(&self) -> u8 {
+        match self {
+            Codec::Bincode => 0,
+            Codec::Postcard => 1,
+            Codec::Protobuf => 2,
+        }
+    }
+
+    /// Returns the codec identified by the given byte, if any.
+    pub fn from_id(id: u8) -> Option<Self> {
+        [Codec::Bincode, Codec::Postcard, Codec::Protobuf]
+            .into_iter()
+            .find(|codec| codec.id() == id)
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Codec::Bincode => "bincode",
+            Codec::Postcard => "postcard",
+            Codec::Protobuf => "protobuf",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl WireCodec for Codec {
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Codec::Bincode => BincodeCodec.encode(value),
+            Codec::Postcard => PostcardCodec.encode(value),
+            Codec::Protobuf => ProtobufCodec.encode(value),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> anyhow::Result<T> {
+        match self {
+            Codec::Bincode => BincodeCodec.decode(bytes),
+            Codec::Postcard => PostcardCodec.decode(bytes),
+            Codec::Protobuf => ProtobufCodec.decode(bytes),
+        }
+    }
+}
+
+/// Appends an unsigned integer as a variable-length integer: seven bits per byte starting with
+/// the least significant ones, with the highest bit set in every byte but the last one.
+pub(crate) fn write_varint(output: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+/// Reads a variable-length integer written with [`write_varint`] from the start of the input,
+/// which is advanced past it.
+pub(crate) fn read_varint(input: &mut &[u8]) -> Result<u128, CodecError> {
+    let mut value = 0u128;
+    for shift in (0..u128::BITS).step_by(7) {
+        let (byte, rest) = input.split_first().ok_or(CodecError::UnexpectedEnd)?;
+        *input = rest;
+        let bits = u128::from(byte & 0x7f);
+        if bits << shift >> shift != bits {
+            return Err(CodecError::VarintOverflow);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(CodecError::VarintOverflow)
+}
+
+/// Maps a signed integer to an unsigned one so the integers of small magnitude, including the
+/// negative ones, take few bytes as variable-length integers.
+pub(crate) fn zigzag(value: i128) -> u128 {
+    ((value << 1) ^ (value >> 127)) as u128
+}
+
+/// Inverts [`zigzag`].
+pub(crate) fn unzigzag(value: u128) -> i128 {
+    (value >> 1) as i128 ^ -((value & 1) as i128)
+}
+
+/// Splits the first `len` bytes of the input, which is advanced past them.
+pub(crate) fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], CodecError> {
+    if input.len() < len {
+        return Err(CodecError::UnexpectedEnd);
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use serde::{Deserialize, Serialize};
+
+    use super::{read_varint, unzigzag, write_varint, zigzag, Codec, CodecError, WireCodec};
+    use crate::{
+        math::{mersenne127::Mersenne127, mersenne61::Mersenne61, p128::P128},
+        mpc::share::{BeaverTriple, ShamirShare},
+    };
+
+    /// Value that exercises every kind of type of serde.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Everything {
+        flag: bool,
+        small: u8,
+        negative: i32,
+        large: u128,
+        signed: i128,
+        ratio: f64,
+        letter: char,
+        name: String,
+        missing: Option<u64>,
+        present: Option<Vec<u16>>,
+        nested: Vec<Vec<u32>>,
+        optionals: Vec<Option<String>>,
+        tuple: (u8, String, ()),
+        map: BTreeMap<String, i64>,
+        enums: Vec<Shape>,
+        unit: (),
+    }
+
+    /// Enumeration with every kind of variant.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Point,
+        Circle(u64),
+        Segment(i8, i8),
+        Rectangle { width: u32, height: u32 },
+        Polygon(Vec<u8>),
+    }
+
+    fn everything() -> Everything {
+        Everything {
+            flag: true,
+            small: 200,
+            negative: -70000,
+            large: u128::MAX - 5,
+            signed: i128::MIN + 3,
+            ratio: -1.25,
+            letter: 'ñ',
+            name: "share".to_string(),
+            missing: None,
+            present: Some(vec![0, 1, 300]),
+            nested: vec![vec![], vec![1, 2], vec![u32::MAX]],
+            optionals: vec![None, Some(String::new()), Some("x".to_string())],
+            tuple: (7, String::new(), ()),
+            map: BTreeMap::from([("a".to_string(), -1), ("b".to_string(), i64::MAX)]),
+            enums: vec![
+                Shape::Point,
+                Shape::Circle(0),
+                Shape::Segment(-1, 1),
+                Shape::Rectangle {
+                    width: 3,
+                    height: 4,
+                },
+                Shape::Polygon(vec![]),
+                Shape::Polygon(vec![9, 8]),
+            ],
+            unit: (),
+        }
+    }
+
+    #[test]
+    fn variable_length_integers() {
+        for value in [0, 1, 127, 128, 300, u64::MAX as u128, u128::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value);
+            assert_eq!(read_varint(&mut bytes.as_slice()), Ok(value));
+        }
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, 300);
+        assert_eq!(bytes, [0xac, 0x02]);
+        assert_eq!(
+            read_varint(&mut [0xff; 20].as_slice()),
+            Err(CodecError::VarintOverflow)
+        );
+        assert_eq!(
+            read_varint(&mut [0x80].as_slice()),
+            Err(CodecError::UnexpectedEnd)
+        );
+        for value in [0, -1, 1, i128::MIN, i128::MAX] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+    }
+
+    #[test]
+    fn every_codec_roundtrips_every_type() {
+        for codec in [Codec::Bincode, Codec::Postcard, Codec::Protobuf] {
+            let value = everything();
+            let bytes = codec.encode(&value).unwrap();
+            assert_eq!(
+                codec.decode::<Everything>(&bytes).unwrap(),
+                value,
+                "{codec}"
+            );
+
+            let share = ShamirShare::new(Mersenne61::from(42), 2);
+            let bytes = codec.encode(&vec![share.clone(); 3]).unwrap();
+            assert_eq!(
+                codec
+                    .decode::<Vec<ShamirShare<Mersenne61>>>(&bytes)
+                    .unwrap(),
+                vec![share; 3],
+                "{codec}"
+            );
+            let triple = BeaverTriple {
+                a: ShamirShare::new(P128::from(1), 1),
+                b: ShamirShare::new(P128::from(2), 1),
+                c: ShamirShare::new(P128::from(3), 1),
+            };
+            let bytes = codec.encode(&triple).unwrap();
+            assert_eq!(codec.decode::<BeaverTriple<P128>>(&bytes).unwrap(), triple);
+            let bytes = codec.encode(&Some((String::from("x"), 5usize))).unwrap();
+            assert_eq!(
+                codec.decode::<Option<(String, usize)>>(&bytes).unwrap(),
+                Some((String::from("x"), 5))
+            );
+            let bytes = codec.encode(&Option::<(String, usize)>::None).unwrap();
+            assert_eq!(
+                codec.decode::<Option<(String, usize)>>(&bytes).unwrap(),
+                None
+            );
+            let bytes = codec.encode(&Mersenne127::from(u64::MAX)).unwrap();
+            assert_eq!(
+                codec.decode::<Mersenne127>(&bytes).unwrap(),
+                Mersenne127::from(u64::MAX)
+            );
+        }
+    }
+
+    #[test]
+    fn arbitrary_bytes_are_decoded_without_panicking() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for codec in [Codec::Bincode, Codec::Postcard, Codec::Protobuf] {
+            let bytes = codec.encode(&everything()).unwrap();
+            for len in 0..bytes.len() {
+                let decoded = codec.decode::<Everything>(&bytes[..len]);
+                // Protocol Buffers takes the missing fields at their defaults.
+                if codec != Codec::Protobuf {
+                    assert!(decoded.is_err(), "{codec} {len}");
+                }
+            }
+            let mut corrupted = bytes.clone();
+            for _ in 0..1000 {
+                let position = rng.gen_range(0..corrupted.len());
+                corrupted[position] = rng.gen();
+                let _ = codec.decode::<Everything>(&corrupted);
+                let noise: Vec<u8> = (0..rng.gen_range(0..64)).map(|_| rng.gen()).collect();
+                let _ = codec.decode::<Everything>(&noise);
+                let _ = codec.decode::<Vec<ShamirShare<Mersenne61>>>(&noise);
+            }
+        }
+    }
+
+    #[test]
+    fn codecs_are_identified() {
+        for codec in [Codec::Bincode, Codec::Postcard, Codec::Protobuf] {
+            assert_eq!(Codec::from_id(codec.id()), Some(codec));
+            let json = serde_json::to_string(&codec).unwrap();
+            assert_eq!(json, format!("\"{codec}\""));
+        }
+        assert_eq!(Codec::from_id(3), None);
+    }
+}
@@ -0,0 +1,1296 @@
+use std::collections::BTreeMap;
+
+use serde::{
+    de::{self, DeserializeSeed, IntoDeserializer, Visitor},
+    ser::{self, Serialize},
+};
+
+use super::{read_varint, take, unzigzag, write_varint, zigzag, CodecError};
+
+/// Encodes a value in the wire format of Protocol Buffers, with the following schema:
+///
+/// - A struct or a tuple is a message whose fields are numbered from one in the order in which
+///   they are declared. At the top level, any other value is the field 1 of a message.
+/// - The integers are `uint64` or `sint64`, except the ones of 128 bits, which are `bytes` with
+///   16 bytes in little-endian order. The booleans are `bool`, the floats `float` or `double`,
+///   and the strings and the characters `string`.
+/// - A sequence is a `repeated` field, packed if its elements are numbers, and a map is a
+///   `repeated` message with the key as field 1 and the value as field 2. An option is an
+///   `optional` field.
+/// - A variant without data is an `enum` with the index of the variant. Any other variant is a
+///   message with a `oneof` whose field `i + 1` holds the data of the variant `i`.
+/// - A sequence or an option inside a sequence, an option or a variant is wrapped as the field 1
+///   of a message, since the wire format can not nest them directly.
+///
+/// For instance, a `ShamirShare<Mersenne61>` is the message
+/// `message ShamirShare { uint64 degree = 1; uint64 value = 2; }`.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, CodecError> {
+    let mut output = Vec::new();
+    match value.serialize(NodeSerializer)? {
+        Node::Struct(fields) => write_fields(&mut output, &fields),
+        node => write_field(&mut output, 1, &node),
+    }
+    Ok(output)
+}
+
+/// Decodes a value encoded with [`to_vec`]. As in Protocol Buffers, a field that is missing takes
+/// its default value and a field that appears several times takes its last value.
+pub fn from_slice<'a, T: de::Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, CodecError> {
+    T::deserialize(TopDeserializer { bytes })
+}
+
+/// Wire types of the fields.
+const VARINT: u8 = 0;
+const FIXED64: u8 = 1;
+const LENGTH_DELIMITED: u8 = 2;
+const FIXED32: u8 = 5;
+
+/// Value of a field, which is built before it is written since the length of an embedded message
+/// precedes its fields.
+#[derive(Debug)]
+enum Node {
+    /// Variable-length integer that may be packed in a sequence.
+    Varint(u64),
+    /// Index of a variant without data, which is never packed so it is not confused with a
+    /// variant with data.
+    Enum(u32),
+    Fixed32(u32),
+    Fixed64(u64),
+    Bytes(Vec<u8>),
+    /// Embedded message with its numbered fields.
+    Message(Vec<(u32, Node)>),
+    /// Message of a struct or a tuple, which is not wrapped at the top level.
+    Struct(Vec<(u32, Node)>),
+    /// Elements of a sequence, none of which is a sequence or an option.
+    Repeated(Vec<Node>),
+    /// Value of an option, which is not a sequence or an option.
+    Optional(Option<Box<Node>>),
+}
+
+impl Node {
+    /// Wraps a sequence or an option as the field 1 of a message.
+    fn wrap(self) -> Self {
+        match self {
+            Node::Repeated(_) | Node::Optional(_) => Node::Message(vec![(1, self)]),
+            node => node,
+        }
+    }
+
+    /// Returns whether the node can be packed with others of its kind.
+    fn is_packable(&self) -> bool {
+        matches!(self, Node::Varint(_) | Node::Fixed32(_) | Node::Fixed64(_))
+    }
+
+    /// Appends the value of a scalar without its tag.
+    fn write_payload(&self, output: &mut Vec<u8>) {
+        match self {
+            Node::Varint(value) => write_varint(output, (*value).into()),
+            Node::Enum(value) => write_varint(output, (*value).into()),
+            Node::Fixed32(value) => output.extend_from_slice(&value.to_le_bytes()),
+            Node::Fixed64(value) => output.extend_from_slice(&value.to_le_bytes()),
+            Node::Bytes(bytes) => write_bytes(output, bytes),
+            Node::Message(fields) | Node::Struct(fields) => {
+                let mut message = Vec::new();
+                write_fields(&mut message, fields);
+                write_bytes(output, &message);
+            }
+            Node::Repeated(_) | Node::Optional(_) => unreachable!("they are written as fields"),
+        }
+    }
+
+    fn wire_type(&self) -> u8 {
+        match self {
+            Node::Varint(_) | Node::Enum(_) => VARINT,
+            Node::Fixed32(_) => FIXED32,
+            Node::Fixed64(_) => FIXED64,
+            _ => LENGTH_DELIMITED,
+        }
+    }
+}
+
+fn write_bytes(output: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(output, bytes.len() as u128);
+    output.extend_from_slice(bytes);
+}
+
+fn write_tag(output: &mut Vec<u8>, number: u32, wire_type: u8) {
+    write_varint(output, (u128::from(number) << 3) | u128::from(wire_type));
+}
+
+fn write_fields(output: &mut Vec<u8>, fields: &[(u32, Node)]) {
+    for (number, node) in fields {
+        write_field(output, *number, node);
+    }
+}
+
+fn write_field(output: &mut Vec<u8>, number: u32, node: &Node) {
+    match node {
+        Node::Repeated(items) if !items.is_empty() && items.iter().all(Node::is_packable) => {
+            let mut packed = Vec::new();
+            for item in items {
+                item.write_payload(&mut packed);
+            }
+            write_tag(output, number, LENGTH_DELIMITED);
+            write_bytes(output, &packed);
+        }
+        Node::Repeated(items) => {
+            for item in items {
+                write_field(output, number, item);
+            }
+        }
+        Node::Optional(value) => {
+            if let Some(value) = value {
+                write_field(output, number, value);
+            }
+        }
+        node => {
+            write_tag(output, number, node.wire_type());
+            node.write_payload(output);
+        }
+    }
+}
+
+/// Serializer that builds the node of a value.
+struct NodeSerializer;
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = Node;
+    type Error = CodecError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = FieldsSerializer;
+    type SerializeTupleStruct = FieldsSerializer;
+    type SerializeTupleVariant = FieldsSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = FieldsSerializer;
+    type SerializeStructVariant = FieldsSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, CodecError> {
+        Ok(Node::Varint(v.into()))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Node, CodecError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Node, CodecError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Node, CodecError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Node, CodecError> {
+        Ok(Node::Varint(zigzag(v.into()) as u64))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Node, CodecError> {
+        Ok(Node::Bytes(v.to_le_bytes().to_vec()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Node, CodecError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Node, CodecError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Node, CodecError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Node, CodecError> {
+        Ok(Node::Varint(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Node, CodecError> {
+        Ok(Node::Bytes(v.to_le_bytes().to_vec()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Node, CodecError> {
+        Ok(Node::Fixed32(v.to_bits()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Node, CodecError> {
+        Ok(Node::Fixed64(v.to_bits()))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Node, CodecError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Node, CodecError> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, CodecError> {
+        Ok(Node::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Node, CodecError> {
+        Ok(Node::Optional(None))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Node, CodecError> {
+        let node = value.serialize(NodeSerializer)?.wrap();
+        Ok(Node::Optional(Some(Box::new(node))))
+    }
+
+    fn serialize_unit(self) -> Result<Node, CodecError> {
+        Ok(Node::Message(Vec::new()))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node, CodecError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Node, CodecError> {
+        Ok(Node::Enum(variant_index))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Node, CodecError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<Node, CodecError> {
+        let node = value.serialize(NodeSerializer)?.wrap();
+        Ok(Node::Message(vec![(variant_index + 1, node)]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, CodecError> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<FieldsSerializer, CodecError> {
+        Ok(FieldsSerializer::new(len, None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<FieldsSerializer, CodecError> {
+        Ok(FieldsSerializer::new(len, None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<FieldsSerializer, CodecError> {
+        Ok(FieldsSerializer::new(len, Some(variant_index)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, CodecError> {
+        Ok(MapSerializer {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<FieldsSerializer, CodecError> {
+        Ok(FieldsSerializer::new(len, None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<FieldsSerializer, CodecError> {
+        Ok(FieldsSerializer::new(len, Some(variant_index)))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Serializer of the elements of a sequence.
+struct SeqSerializer {
+    items: Vec<Node>,
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Node;
+    type Error = CodecError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CodecError> {
+        self.items.push(value.serialize(NodeSerializer)?.wrap());
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, CodecError> {
+        Ok(Node::Repeated(self.items))
+    }
+}
+
+/// Serializer of the numbered fields of a struct, a tuple or a variant.
+struct FieldsSerializer {
+    fields: Vec<(u32, Node)>,
+    next_number: u32,
+    variant_index: Option<u32>,
+}
+
+impl FieldsSerializer {
+    fn new(len: usize, variant_index: Option<u32>) -> Self {
+        Self {
+            fields: Vec::with_capacity(len),
+            next_number: 1,
+            variant_index,
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CodecError> {
+        self.fields
+            .push((self.next_number, value.serialize(NodeSerializer)?));
+        self.next_number += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Node, CodecError> {
+        Ok(match self.variant_index {
+            Some(index) => Node::Message(vec![(index + 1, Node::Message(self.fields))]),
+            None => Node::Struct(self.fields),
+        })
+    }
+}
+
+impl ser::SerializeTuple for FieldsSerializer {
+    type Ok = Node;
+    type Error = CodecError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CodecError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, CodecError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for FieldsSerializer {
+    type Ok = Node;
+    type Error = CodecError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CodecError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, CodecError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for FieldsSerializer {
+    type Ok = Node;
+    type Error = CodecError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CodecError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, CodecError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for FieldsSerializer {
+    type Ok = Node;
+    type Error = CodecError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), CodecError> {
+        self.push(value)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), CodecError> {
+        // The skipped field keeps its number, so the next fields are not renumbered.
+        self.next_number += 1;
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, CodecError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for FieldsSerializer {
+    type Ok = Node;
+    type Error = CodecError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), CodecError> {
+        self.push(value)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), CodecError> {
+        self.next_number += 1;
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, CodecError> {
+        self.finish()
+    }
+}
+
+/// Serializer of the entries of a map.
+struct MapSerializer {
+    entries: Vec<Node>,
+    key: Option<Node>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Node;
+    type Error = CodecError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), CodecError> {
+        self.key = Some(key.serialize(NodeSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CodecError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| CodecError::Custom("a value of a map has no key".to_string()))?;
+        let value = value.serialize(NodeSerializer)?;
+        self.entries.push(Node::Message(vec![(1, key), (2, value)]));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, CodecError> {
+        Ok(Node::Repeated(self.entries))
+    }
+}
+
+/// Value of a field as read from the bytes of a message.
+#[derive(Debug, Clone, Copy)]
+enum Raw<'a> {
+    Varint(u128),
+    Fixed32(u32),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+}
+
+impl Raw<'_> {
+    fn wire_type(&self) -> u8 {
+        match self {
+            Raw::Varint(_) => VARINT,
+            Raw::Fixed32(_) => FIXED32,
+            Raw::Fixed64(_) => FIXED64,
+            Raw::Bytes(_) => LENGTH_DELIMITED,
+        }
+    }
+}
+
+/// Reads the fields of a message, grouping the values of each field in the order in which they
+/// appear.
+fn parse_message(mut input: &[u8]) -> Result<BTreeMap<u32, Vec<Raw<'_>>>, CodecError> {
+    let mut fields: BTreeMap<u32, Vec<Raw>> = BTreeMap::new();
+    while !input.is_empty() {
+        let tag = read_varint(&mut input)?;
+        let number = u32::try_from(tag >> 3).map_err(|_| CodecError::IntegerOutOfRange(tag))?;
+        let raw = match (tag & 7) as u8 {
+            VARINT => Raw::Varint(read_varint(&mut input)?),
+            FIXED64 => Raw::Fixed64(u64::from_le_bytes(take(&mut input, 8)?.try_into().unwrap())),
+            LENGTH_DELIMITED => {
+                let len = read_varint(&mut input)?;
+                let len = usize::try_from(len).map_err(|_| CodecError::IntegerOutOfRange(len))?;
+                Raw::Bytes(take(&mut input, len)?)
+            }
+            FIXED32 => Raw::Fixed32(u32::from_le_bytes(take(&mut input, 4)?.try_into().unwrap())),
+            wire_type => return Err(CodecError::UnsupportedWireType(wire_type)),
+        };
+        fields.entry(number).or_default().push(raw);
+    }
+    Ok(fields)
+}
+
+fn mismatch(expected: u8, raw: &Raw) -> CodecError {
+    CodecError::WireTypeMismatch {
+        expected,
+        found: raw.wire_type(),
+    }
+}
+
+/// Deserializer of the bytes of a whole value, which unwraps the values that are not structs or
+/// tuples from the field 1 of a message.
+struct TopDeserializer<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> TopDeserializer<'a> {
+    fn message(self) -> FieldDeserializer<'a, 'a> {
+        FieldDeserializer::new(vec![Raw::Bytes(self.bytes)])
+    }
+
+    fn field(self) -> Result<FieldDeserializer<'a, 'a>, CodecError> {
+        let mut fields = parse_message(self.bytes)?;
+        Ok(FieldDeserializer::new(
+            fields.remove(&1).unwrap_or_default(),
+        ))
+    }
+}
+
+/// Forwards the deserialization of the values that are not structs or tuples to the field 1.
+macro_rules! forward_to_field {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+                de::Deserializer::$method(self.field()?, visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for TopDeserializer<'de> {
+    type Error = CodecError;
+
+    forward_to_field! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32
+        deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char
+        deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_option deserialize_unit deserialize_seq deserialize_map
+        deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        self.field()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        self.message().deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        self.message().deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        self.message().deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        self.field()?.deserialize_enum(name, variants, visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Deserializer of the values of a field. The values of an element of a sequence hold a single
+/// value, or none when the element continues a packed run of numbers, which the sequence shares
+/// with its elements.
+struct FieldDeserializer<'a, 'b> {
+    raws: Vec<Raw<'a>>,
+    /// Whether a sequence or an option is wrapped in a message, as in the elements of a sequence,
+    /// the values of an option and the data of a variant.
+    wrapped: bool,
+    /// Rest of the packed run of numbers of the sequence of the element.
+    packed: Option<&'b mut &'a [u8]>,
+}
+
+impl<'a, 'b> FieldDeserializer<'a, 'b> {
+    fn new(raws: Vec<Raw<'a>>) -> Self {
+        Self {
+            raws,
+            wrapped: false,
+            packed: None,
+        }
+    }
+
+    fn wrapped(raws: Vec<Raw<'a>>) -> Self {
+        Self {
+            raws,
+            wrapped: true,
+            packed: None,
+        }
+    }
+
+    /// Returns the packed run of numbers to read the element from, starting it if the element
+    /// is the first one of a run.
+    fn packed(&mut self) -> Option<&mut &'a [u8]> {
+        let packed = self.packed.as_deref_mut()?;
+        if packed.is_empty() {
+            match self.raws.last() {
+                Some(Raw::Bytes(bytes)) => *packed = bytes,
+                _ => return None,
+            }
+        }
+        Some(packed)
+    }
+
+    fn varint(&mut self) -> Result<u128, CodecError> {
+        if let Some(packed) = self.packed() {
+            return read_varint(packed);
+        }
+        match self.raws.last() {
+            None => Ok(0),
+            Some(Raw::Varint(value)) => Ok(*value),
+            Some(raw) => Err(mismatch(VARINT, raw)),
+        }
+    }
+
+    fn fixed32(&mut self) -> Result<u32, CodecError> {
+        if let Some(packed) = self.packed() {
+            return Ok(u32::from_le_bytes(take(packed, 4)?.try_into().unwrap()));
+        }
+        match self.raws.last() {
+            None => Ok(0),
+            Some(Raw::Fixed32(value)) => Ok(*value),
+            Some(raw) => Err(mismatch(FIXED32, raw)),
+        }
+    }
+
+    fn fixed64(&mut self) -> Result<u64, CodecError> {
+        if let Some(packed) = self.packed() {
+            return Ok(u64::from_le_bytes(take(packed, 8)?.try_into().unwrap()));
+        }
+        match self.raws.last() {
+            None => Ok(0),
+            Some(Raw::Fixed64(value)) => Ok(*value),
+            Some(raw) => Err(mismatch(FIXED64, raw)),
+        }
+    }
+
+    fn bytes(&self) -> Result<&'a [u8], CodecError> {
+        match self.raws.last() {
+            None => Ok(&[]),
+            Some(Raw::Bytes(bytes)) => Ok(bytes),
+            Some(raw) => Err(mismatch(LENGTH_DELIMITED, raw)),
+        }
+    }
+
+    fn str(&self) -> Result<&'a str, CodecError> {
+        std::str::from_utf8(self.bytes()?).map_err(|_| CodecError::InvalidString)
+    }
+
+    fn unsigned<U: TryFrom<u128>>(&mut self) -> Result<U, CodecError> {
+        let value = self.varint()?;
+        U::try_from(value).map_err(|_| CodecError::IntegerOutOfRange(value))
+    }
+
+    fn signed<I: TryFrom<i128>>(&mut self) -> Result<I, CodecError> {
+        let value = self.varint()?;
+        I::try_from(unzigzag(value)).map_err(|_| CodecError::IntegerOutOfRange(value))
+    }
+
+    fn sixteen_bytes(&self) -> Result<[u8; 16], CodecError> {
+        match self.bytes()? {
+            [] => Ok([0; 16]),
+            bytes => bytes.try_into().map_err(|_| CodecError::UnexpectedEnd),
+        }
+    }
+
+    /// Returns the fields of the message of a struct, a tuple or a variant.
+    fn message(&self) -> Result<BTreeMap<u32, Vec<Raw<'a>>>, CodecError> {
+        parse_message(self.bytes()?)
+    }
+
+    /// Returns the values of a sequence or an option, unwrapping them if needed.
+    fn unwrapped(self) -> Result<Vec<Raw<'a>>, CodecError> {
+        if self.wrapped {
+            Ok(self.message()?.remove(&1).unwrap_or_default())
+        } else {
+            Ok(self.raws)
+        }
+    }
+}
+
+impl<'de, 'b> de::Deserializer<'de> for FieldDeserializer<'de, 'b> {
+    type Error = CodecError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, CodecError> {
+        Err(CodecError::NotSelfDescribing)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        match self.varint()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            tag => Err(CodecError::InvalidTag(tag as u64)),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_i8(self.signed()?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_i16(self.signed()?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_i32(self.signed()?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_i64(self.signed()?)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_i128(i128::from_le_bytes(self.sixteen_bytes()?))
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_u8(self.unsigned()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_u16(self.unsigned()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_u32(self.unsigned()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_u64(self.unsigned()?)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_u128(u128::from_le_bytes(self.sixteen_bytes()?))
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_f32(f32::from_bits(self.fixed32()?))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_f64(f64::from_bits(self.fixed64()?))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let mut chars = self.str()?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(CodecError::InvalidString),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_borrowed_str(self.str()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_borrowed_bytes(self.bytes()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let raws = self.unwrapped()?;
+        if raws.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(FieldDeserializer::wrapped(raws))
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_seq(Elements {
+            raws: self.unwrapped()?.into_iter(),
+            packed: &[],
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        visitor.visit_seq(Fields {
+            fields: self.message()?,
+            next_number: 1,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_map(Entries {
+            raws: self.unwrapped()?.into_iter(),
+            value: Vec::new(),
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        match self.raws.last() {
+            None => visitor.visit_enum(Variant {
+                index: 0,
+                raws: Vec::new(),
+            }),
+            Some(Raw::Varint(index)) => visitor.visit_enum(Variant {
+                index: u32::try_from(*index).map_err(|_| CodecError::IntegerOutOfRange(*index))?,
+                raws: Vec::new(),
+            }),
+            Some(_) => {
+                let mut fields = self.message()?;
+                let (number, raws) = fields.pop_last().ok_or_else(|| {
+                    CodecError::Custom("the message of a variant has no field".to_string())
+                })?;
+                visitor.visit_enum(Variant {
+                    index: number - 1,
+                    raws,
+                })
+            }
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, CodecError> {
+        Err(CodecError::NotSelfDescribing)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Access to the elements of a sequence.
+struct Elements<'a> {
+    raws: std::vec::IntoIter<Raw<'a>>,
+    packed: &'a [u8],
+}
+
+impl<'de> de::SeqAccess<'de> for Elements<'de> {
+    type Error = CodecError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, CodecError> {
+        // An element that continues a packed run has no value of its own.
+        let raws = if self.packed.is_empty() {
+            match self.raws.next() {
+                Some(raw) => vec![raw],
+                None => return Ok(None),
+            }
+        } else {
+            Vec::new()
+        };
+        seed.deserialize(FieldDeserializer {
+            raws,
+            wrapped: true,
+            packed: Some(&mut self.packed),
+        })
+        .map(Some)
+    }
+}
+
+/// Access to the fields of a struct or a tuple, in order of number.
+struct Fields<'a> {
+    fields: BTreeMap<u32, Vec<Raw<'a>>>,
+    next_number: u32,
+    remaining: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Fields<'de> {
+    type Error = CodecError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, CodecError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let raws = self.fields.remove(&self.next_number).unwrap_or_default();
+        self.next_number += 1;
+        seed.deserialize(FieldDeserializer::new(raws)).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Access to the entries of a map.
+struct Entries<'a> {
+    raws: std::vec::IntoIter<Raw<'a>>,
+    value: Vec<Raw<'a>>,
+}
+
+impl<'de> de::MapAccess<'de> for Entries<'de> {
+    type Error = CodecError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, CodecError> {
+        let Some(raw) = self.raws.next() else {
+            return Ok(None);
+        };
+        let mut entry = FieldDeserializer::new(vec![raw]).message()?;
+        self.value = entry.remove(&2).unwrap_or_default();
+        let key = entry.remove(&1).unwrap_or_default();
+        seed.deserialize(FieldDeserializer::new(key)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, CodecError> {
+        seed.deserialize(FieldDeserializer::new(std::mem::take(&mut self.value)))
+    }
+}
+
+/// Access to a variant of an enumeration and its data.
+struct Variant<'a> {
+    index: u32,
+    raws: Vec<Raw<'a>>,
+}
+
+impl<'de> de::EnumAccess<'de> for Variant<'de> {
+    type Error = CodecError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), CodecError> {
+        let variant = seed.deserialize(self.index.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Variant<'de> {
+    type Error = CodecError;
+
+    fn unit_variant(self) -> Result<(), CodecError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, CodecError> {
+        seed.deserialize(FieldDeserializer::wrapped(self.raws))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        de::Deserializer::deserialize_tuple(FieldDeserializer::wrapped(self.raws), len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        de::Deserializer::deserialize_tuple(
+            FieldDeserializer::wrapped(self.raws),
+            fields.len(),
+            visitor,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{from_slice, to_vec};
+    use crate::{codec::CodecError, math::mersenne61::Mersenne61, mpc::share::ShamirShare};
+
+    /// Messages of the examples of the encoding guide of Protocol Buffers.
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Test1 {
+        a: u64,
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Test2 {
+        a: u64,
+        b: String,
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Test3 {
+        c: Test1,
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Test4 {
+        d: Vec<u64>,
+    }
+
+    #[test]
+    fn known_encodings() {
+        let share = ShamirShare::new(Mersenne61::from(42), 1);
+        assert_eq!(to_vec(&share).unwrap(), [0x08, 0x01, 0x10, 0x2a]);
+        assert_eq!(
+            from_slice::<ShamirShare<Mersenne61>>(&[0x08, 0x01, 0x10, 0x2a]).unwrap(),
+            share
+        );
+        // A sequence of numbers at the top level is a packed field 1.
+        assert_eq!(
+            to_vec(&vec![1u64, 300]).unwrap(),
+            [0x0a, 0x03, 0x01, 0xac, 0x02]
+        );
+        // The strings are repeated instead of packed, and a signed integer is zigzagged.
+        assert_eq!(
+            to_vec(&(vec!["a", ""], -2i32)).unwrap(),
+            [0x0a, 0x01, b'a', 0x0a, 0x00, 0x10, 0x03]
+        );
+    }
+
+    /// Encodings given by the encoding guide of Protocol Buffers.
+    #[test]
+    fn conformance_vectors() {
+        // Variable-length integers.
+        let test1 = Test1 { a: 150 };
+        assert_eq!(to_vec(&test1).unwrap(), [0x08, 0x96, 0x01]);
+        assert_eq!(from_slice::<Test1>(&[0x08, 0x96, 0x01]), Ok(test1));
+        for (value, bytes) in [
+            (0u64, &[0x08, 0x00][..]),
+            (127, &[0x08, 0x7f]),
+            (128, &[0x08, 0x80, 0x01]),
+            (
+                u64::MAX,
+                &[
+                    0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01,
+                ],
+            ),
+        ] {
+            assert_eq!(to_vec(&value).unwrap(), bytes, "{value}");
+            assert_eq!(from_slice::<u64>(bytes), Ok(value));
+        }
+        // Signed integers are `sint64`, which are zigzagged.
+        for (value, bytes) in [
+            (0i64, &[0x08, 0x00][..]),
+            (-1, &[0x08, 0x01]),
+            (1, &[0x08, 0x02]),
+            (-2, &[0x08, 0x03]),
+            (i32::MIN.into(), &[0x08, 0xff, 0xff, 0xff, 0xff, 0x0f]),
+            (
+                i64::MIN,
+                &[
+                    0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01,
+                ],
+            ),
+        ] {
+            assert_eq!(to_vec(&value).unwrap(), bytes, "{value}");
+            assert_eq!(from_slice::<i64>(bytes), Ok(value));
+        }
+        // Length-delimited strings.
+        let test2 = Test2 {
+            a: 150,
+            b: "testing".to_string(),
+        };
+        let bytes = [
+            0x08, 0x96, 0x01, 0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g',
+        ];
+        assert_eq!(to_vec(&test2).unwrap(), bytes);
+        assert_eq!(from_slice::<Test2>(&bytes), Ok(test2));
+        // Embedded messages.
+        let test3 = Test3 {
+            c: Test1 { a: 150 },
+        };
+        assert_eq!(to_vec(&test3).unwrap(), [0x0a, 0x03, 0x08, 0x96, 0x01]);
+        assert_eq!(
+            from_slice::<Test3>(&[0x0a, 0x03, 0x08, 0x96, 0x01]),
+            Ok(test3)
+        );
+        // Packed repeated fields.
+        let test4 = Test4 {
+            d: vec![3, 270, 86942],
+        };
+        let bytes = [0x0a, 0x06, 0x03, 0x8e, 0x02, 0x9e, 0xa7, 0x05];
+        assert_eq!(to_vec(&test4).unwrap(), bytes);
+        assert_eq!(from_slice::<Test4>(&bytes), Ok(test4));
+    }
+
+    #[test]
+    fn truncated_and_oversized_bytes_are_rejected() {
+        let bytes = to_vec(&Test2 {
+            a: 150,
+            b: "testing".to_string(),
+        })
+        .unwrap();
+        for len in 0..bytes.len() {
+            let decoded = from_slice::<Test2>(&bytes[..len]);
+            // A message that ends between two fields is valid, with the missing ones at their
+            // defaults.
+            match len {
+                0 => assert_eq!(decoded, Ok(Test2::default())),
+                3 => assert_eq!(
+                    decoded,
+                    Ok(Test2 {
+                        a: 150,
+                        b: String::new()
+                    })
+                ),
+                _ => assert_eq!(decoded, Err(CodecError::UnexpectedEnd), "{len}"),
+            }
+        }
+        // A length larger than the input is not allocated.
+        let mut huge = vec![0x0a];
+        huge.extend([0xff; 9]);
+        huge.push(0x01);
+        assert_eq!(from_slice::<Test4>(&huge), Err(CodecError::UnexpectedEnd));
+        assert_eq!(from_slice::<String>(&huge), Err(CodecError::UnexpectedEnd));
+        let mut long = vec![0x08];
+        long.extend([0xff; 20]);
+        assert_eq!(from_slice::<u64>(&long), Err(CodecError::VarintOverflow));
+        assert_eq!(
+            from_slice::<u32>(&[0x08, 0x80, 0x80, 0x80, 0x80, 0x10]),
+            Err(CodecError::IntegerOutOfRange(1 << 32))
+        );
+    }
+
+    #[test]
+    fn missing_and_repeated_fields() {
+        // The degree is missing and the value appears twice.
+        let share: ShamirShare<Mersenne61> = from_slice(&[0x10, 0x01, 0x10, 0x07]).unwrap();
+        assert_eq!(share, ShamirShare::new(Mersenne61::from(7), 0));
+        // Unpacked numbers are read as well as packed ones.
+        assert_eq!(
+            from_slice::<Vec<u32>>(&[0x08, 0x05, 0x0a, 0x02, 0x06, 0x07]).unwrap(),
+            [5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn malformed_bytes_are_rejected() {
+        assert_eq!(
+            from_slice::<ShamirShare<Mersenne61>>(&[0x0b]),
+            Err(CodecError::UnsupportedWireType(3))
+        );
+        assert_eq!(
+            from_slice::<ShamirShare<Mersenne61>>(&[0x0a, 0x00]),
+            Err(CodecError::WireTypeMismatch {
+                expected: 0,
+                found: 2
+            })
+        );
+        assert_eq!(
+            from_slice::<ShamirShare<Mersenne61>>(&[0x0a, 0x05, 0x00]),
+            Err(CodecError::UnexpectedEnd)
+        );
+        assert_eq!(
+            from_slice::<u8>(&[0x08, 0x80, 0x02]),
+            Err(CodecError::IntegerOutOfRange(256))
+        );
+    }
+}
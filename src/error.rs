@@ -0,0 +1,277 @@
+use std::{error::Error as StdError, io};
+
+use thiserror::Error;
+
+use crate::{
+    codec::CodecError,
+    math::{fixed_point::FixedPointError, FieldError},
+    mpc::{
+        beacon::BeaconError, bytes::ByteSharingError, checkpoint::CheckpointError,
+        circuit::CircuitError, compare::ComparisonError, control::ControlError, daemon::JobError,
+        dealer::DealingError, expression::ExpressionError, input::InputError, merkle::MerkleError,
+        mpspdz::MpSpdzError, repl::ReplError, seed::SeedError, shadow::ShadowError,
+        share_file::ShareFileError, two_party::TwoPartyError, verification::VerificationError,
+        DegreeError, ReconstructionError, ThresholdError,
+    },
+    net::{
+        audit::AuditError, broadcast::BroadcastError, channel::ChannelError,
+        coordinator::CoordinatorError, deadline::DeadlineError, guard::GuardError,
+        identity::IdentityError, overrides::OverrideError, pinning::FingerprintError,
+        reconnect::ReconnectError, sequence::SequenceError, socks::ProxyError, stream::StreamError,
+        transcript::TranscriptError, value::ValueError, version::VersionError,
+        websocket::WebSocketError,
+    },
+};
+
+/// Error that stops the command-line tool. Each kind of failure exits with its own code, so the
+/// scripts that run the parties can tell, e.g., an unreachable peer from a peer that failed a
+/// security check.
+#[derive(Debug, Error)]
+pub enum ShamiError {
+    /// The arguments, the configuration files or the inputs of the party are not correct.
+    #[error("configuration error: {0:#}")]
+    Config(anyhow::Error),
+
+    /// A peer could not be reached or the connection with it was lost.
+    #[error("network error: {0:#}")]
+    Network(anyhow::Error),
+
+    /// The parties did not follow the protocol, e.g. a peer sent an unexpected message.
+    #[error("protocol error: {0:#}")]
+    Protocol(anyhow::Error),
+
+    /// A peer failed a security check, e.g. its certificate is not trusted or its messages are
+    /// not authentic.
+    #[error("security check failed: {0:#}")]
+    Security(anyhow::Error),
+
+    /// The operator interrupted the party.
+    #[error("the party was interrupted")]
+    Interrupted,
+}
+
+impl ShamiError {
+    /// Exit code of a configuration error, which matches the one of the errors in the arguments.
+    pub const CONFIG_EXIT_CODE: u8 = 2;
+    /// Exit code of a network error.
+    pub const NETWORK_EXIT_CODE: u8 = 3;
+    /// Exit code of a protocol error.
+    pub const PROTOCOL_EXIT_CODE: u8 = 4;
+    /// Exit code of a failed security check.
+    pub const SECURITY_EXIT_CODE: u8 = 5;
+    /// Exit code of an interrupted party, which follows the convention of the shells for SIGINT.
+    pub const INTERRUPTED_EXIT_CODE: u8 = 130;
+
+    /// Returns the code with which the process exits after the error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ShamiError::Config(_) => Self::CONFIG_EXIT_CODE,
+            ShamiError::Network(_) => Self::NETWORK_EXIT_CODE,
+            ShamiError::Protocol(_) => Self::PROTOCOL_EXIT_CODE,
+            ShamiError::Security(_) => Self::SECURITY_EXIT_CODE,
+            ShamiError::Interrupted => Self::INTERRUPTED_EXIT_CODE,
+        }
+    }
+}
+
+/// Kind of failure behind an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Config,
+    Network,
+    Protocol,
+    Security,
+    Interrupted,
+}
+
+impl From<anyhow::Error> for ShamiError {
+    /// Classifies an error by the first cause of its chain whose kind is known. An interruption
+    /// or a failed security check anywhere in the chain takes precedence, since they explain
+    /// the errors that follow them. Errors of unknown kind are protocol errors.
+    fn from(err: anyhow::Error) -> Self {
+        let kinds: Vec<Kind> = err.chain().filter_map(kind_of).collect();
+        let kind = [Kind::Interrupted, Kind::Security]
+            .into_iter()
+            .find(|kind| kinds.contains(kind))
+            .or(kinds.first().copied())
+            .unwrap_or(Kind::Protocol);
+        match kind {
+            Kind::Config => ShamiError::Config(err),
+            Kind::Network => ShamiError::Network(err),
+            Kind::Protocol => ShamiError::Protocol(err),
+            Kind::Security => ShamiError::Security(err),
+            Kind::Interrupted => ShamiError::Interrupted,
+        }
+    }
+}
+
+/// Returns the kind of failure of an error, if its type is known.
+fn kind_of(err: &(dyn StdError + 'static)) -> Option<Kind> {
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        // The errors of the transports are wrapped in I/O errors, so they are looked up first.
+        if let Some(kind) = err.get_ref().and_then(|inner| kind_of(inner)) {
+            return Some(kind);
+        }
+        return Some(match err.kind() {
+            io::ErrorKind::InvalidInput
+            | io::ErrorKind::NotFound
+            | io::ErrorKind::PermissionDenied => Kind::Config,
+            io::ErrorKind::InvalidData => Kind::Protocol,
+            _ => Kind::Network,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<ChannelError>() {
+        return Some(match err {
+            ChannelError::Interrupted => Kind::Interrupted,
+            _ => Kind::Network,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<CheckpointError>() {
+        return Some(match err {
+            CheckpointError::Diverged => Kind::Protocol,
+            _ => Kind::Config,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<VerificationError>() {
+        return Some(match err {
+            VerificationError::ProductsRejected(_) => Kind::Security,
+            _ => Kind::Config,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<TranscriptError>() {
+        return Some(match err {
+            TranscriptError::Diverged(_) => Kind::Protocol,
+            _ => Kind::Config,
+        });
+    }
+    #[cfg(feature = "noise")]
+    if let Some(err) = err.downcast_ref::<crate::net::noise::NoiseError>() {
+        return Some(match err {
+            crate::net::noise::NoiseError::InvalidKey => Kind::Config,
+            crate::net::noise::NoiseError::InvalidHandshakeMessage(_) => Kind::Protocol,
+            _ => Kind::Security,
+        });
+    }
+    #[cfg(feature = "udp")]
+    if err.is::<crate::net::udp::UdpError>() {
+        return Some(Kind::Network);
+    }
+
+    if err.is::<rustls::Error>()
+        || err.is::<webpki::Error>()
+        || err.is::<IdentityError>()
+        || err.is::<SequenceError>()
+        || err.is::<GuardError>()
+        || err.is::<AuditError>()
+        || err.is::<MerkleError>()
+        || err.is::<BeaconError>()
+        || err.is::<DealingError>()
+    {
+        Some(Kind::Security)
+    } else if err.is::<ThresholdError>()
+        || err.is::<ShareFileError>()
+        || err.is::<TwoPartyError>()
+        || err.is::<BroadcastError>()
+        || err.is::<InputError>()
+        || err.is::<SeedError>()
+        || err.is::<ExpressionError>()
+        || err.is::<CircuitError>()
+        || err.is::<ComparisonError>()
+        || err.is::<FixedPointError>()
+        || err.is::<FingerprintError>()
+        || err.is::<OverrideError>()
+        || err.is::<ControlError>()
+        || err.is::<MpSpdzError>()
+        || err.is::<VersionError>()
+        || err.is::<serde_json::Error>()
+    {
+        Some(Kind::Config)
+    } else if err.is::<CoordinatorError>()
+        || err.is::<ReconnectError>()
+        || err.is::<ProxyError>()
+        || err.is::<WebSocketError>()
+        || err.is::<DeadlineError>()
+    {
+        Some(Kind::Network)
+    } else if err.is::<ValueError>()
+        || err.is::<StreamError>()
+        || err.is::<ReconstructionError>()
+        || err.is::<DegreeError>()
+        || err.is::<ShadowError>()
+        || err.is::<FieldError>()
+        || err.is::<JobError>()
+        || err.is::<ReplError>()
+        || err.is::<bincode::Error>()
+        || err.is::<CodecError>()
+        || err.is::<ByteSharingError>()
+    {
+        Some(Kind::Protocol)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Error, ErrorKind},
+        time::Duration,
+    };
+
+    use anyhow::Context;
+
+    use super::ShamiError;
+    use crate::{
+        mpc::{seed::SeedError, ThresholdError},
+        net::{channel::ChannelError, identity::IdentityError, value::ValueError},
+    };
+
+    fn exit_code(err: anyhow::Error) -> u8 {
+        ShamiError::from(err).exit_code()
+    }
+
+    #[test]
+    fn classify_errors() {
+        let unreachable =
+            anyhow::Error::new(ChannelError::AttemptsExhausted(3)).context("connecting to party 1");
+        assert_eq!(exit_code(unreachable), ShamiError::NETWORK_EXIT_CODE);
+        let refused = Error::new(ErrorKind::ConnectionRefused, "connection refused");
+        assert_eq!(exit_code(refused.into()), ShamiError::NETWORK_EXIT_CODE);
+
+        let config = Error::new(ErrorKind::InvalidInput, "the timeout is not correct");
+        assert_eq!(exit_code(config.into()), ShamiError::CONFIG_EXIT_CODE);
+        let threshold = ThresholdError::TooManyCorruptions {
+            threshold: 3,
+            n_parties: 3,
+        };
+        assert_eq!(exit_code(threshold.into()), ShamiError::CONFIG_EXIT_CODE);
+        assert_eq!(
+            exit_code(SeedError::NotAllowed.into()),
+            ShamiError::CONFIG_EXIT_CODE
+        );
+
+        let mismatch = ValueError::TypeMismatch { expected: "u64" };
+        assert_eq!(exit_code(mismatch.into()), ShamiError::PROTOCOL_EXIT_CODE);
+        assert_eq!(
+            exit_code(anyhow::anyhow!("the parties obtained different results")),
+            ShamiError::PROTOCOL_EXIT_CODE
+        );
+
+        // A failed security check is reported even if it is wrapped in another error.
+        let forged = Error::new(ErrorKind::InvalidData, IdentityError::UnknownCertificate);
+        assert_eq!(
+            exit_code(anyhow::Error::new(forged).context("connecting to party 2")),
+            ShamiError::SECURITY_EXIT_CODE
+        );
+
+        let timeout = ChannelError::RecvTimeout(Duration::from_secs(1));
+        assert_eq!(exit_code(timeout.into()), ShamiError::NETWORK_EXIT_CODE);
+        let interrupted = Err::<(), _>(ChannelError::Interrupted)
+            .context("receiving the shares of the result")
+            .unwrap_err();
+        assert!(matches!(
+            ShamiError::from(interrupted),
+            ShamiError::Interrupted
+        ));
+    }
+}
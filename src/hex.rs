@@ -0,0 +1,20 @@
+/// Encodes bytes as a string of lowercase hexadecimal digits.
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decodes a string of hexadecimal digits into bytes. Returns `None` if the string has an odd
+/// number of digits or contains characters that are not hexadecimal digits.
+///
+/// The bytes are written into a buffer of the final size, so decoding a key does not leave copies
+/// of it in reallocated buffers.
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(hex.len() / 2);
+    for i in (0..hex.len()).step_by(2) {
+        bytes.push(u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()?);
+    }
+    Some(bytes)
+}
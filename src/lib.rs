@@ -0,0 +1,45 @@
+/// Allocator of the tests that detects secrets left in the memory that is freed.
+#[cfg(test)]
+pub(crate) mod canary;
+/// Encodings of the values that the parties send to each other.
+pub mod codec;
+/// Errors that stop the command-line tool and the exit codes of each kind of failure.
+#[cfg(feature = "native")]
+pub mod error;
+/// Encoding and decoding of hexadecimal strings, e.g. of the configuration and the command line.
+pub(crate) mod hex;
+/// Finite fields, polynomials over them and Lagrange interpolation.
+pub mod math;
+/// Locking of the buffers that hold secrets, so they are not swapped to disk or dumped.
+pub mod memory;
+/// Shamir secret sharing and the multi-party protocols built on top of it.
+pub mod mpc;
+/// Network that connects the parties of a protocol through secure channels.
+#[cfg(feature = "native")]
+pub mod net;
+/// Spans of the execution of the parties, which nest and are written in their logs.
+pub mod trace;
+/// Transport through which the protocols exchange values, implementable over any medium.
+pub mod transport;
+
+// The types and protocols that most applications need, re-exported at the root of the crate so
+// that embedding it does not depend on the layout of its modules.
+pub use math::{
+    dynamic::{DynField, FieldKind},
+    gf2k::GF2k,
+    mersenne127::Mersenne127,
+    mersenne61::Mersenne61,
+    p128::P128,
+    FiniteField, Polynomial, PrimeField,
+};
+#[cfg(feature = "native")]
+pub use mpc::run_open_protocol;
+pub use mpc::{
+    compute_shamir_share, compute_shamir_shares,
+    engine::Engine,
+    reconstruct_secret, run_batch_multiply_protocol, run_multiply_many, run_multiply_protocol,
+    share::{BeaverTriple, ShamirShare},
+};
+#[cfg(feature = "native")]
+pub use net::{Network, NetworkConfig};
+pub use transport::Transport;
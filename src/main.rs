@@ -1,119 +1,1333 @@
-mod math;
-mod mpc;
-mod net;
-
-use clap::Parser;
-use math::mersenne61::Mersenne61;
-use mpc::{reconstruct_secret, run_multiply_protocol, share::ShamirShare};
-use net::{Network, NetworkConfig, Packet};
-use std::{error::Error, path::Path};
+use clap::{Parser, Subcommand, ValueEnum};
+use rand::rngs::OsRng;
+use serde_json::Value;
+use shami_rs::{
+    error::ShamiError,
+    math::{
+        dynamic::FieldKind, fixed_point::Decimal, mersenne127::Mersenne127, mersenne61::Mersenne61,
+        p128::P128, FiniteField, PrimeField,
+    },
+    memory,
+    mpc::{
+        self,
+        bench::{self, BenchConfig},
+        circuit::Circuit,
+        control::{self, JobQueue},
+        daemon,
+        dealer::{self, Preprocessing, ShareFileFormat},
+        engine::Engine,
+        expression::Expression,
+        http, input,
+        mpspdz::{self, MpSpdzFormat},
+        parallel,
+        repl::Repl,
+        seed::{RandomnessSource, Seed, SeedError},
+        session::{self, Computation, Connection, Recovery},
+        share_file, simulation,
+        verification::Security,
+    },
+    net::{
+        audit,
+        check::{self, CheckOptions},
+        generate::{self, ConfigTemplate},
+        keys, log_context,
+        overrides::Override,
+        pinning::Fingerprint,
+        progress::Dashboard,
+        stats, Network,
+    },
+    trace,
+};
+use std::{
+    fs,
+    io::{self, ErrorKind, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
 
 /// Implementation of a node to execute a Shamir secret-sharing protocol.
 #[derive(Parser, Debug)]
 #[command(about)]
-struct Args {
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Format of the logs.
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = LogFormat::Text,
+        env = "SHAMI_LOG_FORMAT"
+    )]
+    log_format: LogFormat,
+    /// Finite field in which the secrets are shared. Every party of a session, and the `share`
+    /// and `reconstruct` commands, must use the same field.
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = Field::Mersenne61,
+        env = "SHAMI_FIELD"
+    )]
+    field: Field,
+    /// Allow the `--seed` option, which makes the randomness of the parties deterministic. This
+    /// is INSECURE, since anybody who knows the seed can recompute the shares, and it is only
+    /// intended for tests and bug reports.
+    #[arg(long, global = true)]
+    allow_insecure_seed: bool,
+    /// Lock the memory that holds the coefficients of the sharing polynomials and the shares of
+    /// the circuits, so it is not swapped to disk or written to core dumps. The limit of locked
+    /// memory of the process, e.g. `ulimit -l`, may need to be raised.
+    #[arg(long, global = true, env = "SHAMI_LOCK_MEMORY")]
+    lock_memory: bool,
+    /// Number of threads among which the local computations are split, such as the sharing of
+    /// many secrets, the recombination of the products or the writing of the files of the
+    /// parties. Zero uses one thread per core.
+    #[arg(long, global = true, default_value_t = 0, env = "SHAMI_THREADS")]
+    threads: usize,
+}
+
+impl Cli {
+    /// Checks that a seed is only given together with the flag that allows it.
+    fn check_seed(&self) -> anyhow::Result<()> {
+        if self.command.seed().is_some() && !self.allow_insecure_seed {
+            anyhow::bail!(SeedError::NotAllowed);
+        }
+        Ok(())
+    }
+}
+
+/// Finite fields in which the secrets can be shared.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// Integers modulo the Mersenne prime 2^61 - 1.
+    Mersenne61,
+    /// Integers modulo the Mersenne prime 2^127 - 1.
+    Mersenne127,
+    /// Integers modulo the prime 2^128 - 159.
+    P128,
+}
+
+impl From<Field> for FieldKind {
+    fn from(field: Field) -> Self {
+        match field {
+            Field::Mersenne61 => FieldKind::Mersenne61,
+            Field::Mersenne127 => FieldKind::Mersenne127,
+            Field::P128 => FieldKind::P128,
+        }
+    }
+}
+
+/// Formats of the files that hold the shares of the parties.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ShareFormat {
+    /// A JSON file `share_<i>.json` per party with its ID and its share.
+    Json,
+    /// The file `Transactions-P<i>.data` in which MP-SPDZ persists the shares of each party.
+    MpSpdz,
+}
+
+impl ShareFormat {
+    /// Returns the format of the share files, with elements of the given number of limbs in the
+    /// format of MP-SPDZ.
+    fn with_limbs(self, limbs: usize) -> ShareFileFormat {
+        match self {
+            ShareFormat::Json => ShareFileFormat::Json,
+            ShareFormat::MpSpdz => ShareFileFormat::MpSpdz { limbs },
+        }
+    }
+}
+
+/// Phases in which a computation is split so that the expensive preprocessing is done ahead of
+/// the inputs.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// Generate Beaver triples with the other parties and write them to the file of `--triples`.
+    Offline,
+    /// Evaluate the expression with the Beaver triples of the file of `--triples`, which are
+    /// consumed by the products instead of reducing their degree.
+    Online,
+}
+
+/// Security against the corrupted parties under which the products are computed.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum SecurityLevel {
+    /// The corrupted parties are assumed to follow the protocol.
+    Passive,
+    /// The products and the openings are checked, and the session aborts if some party deviates
+    /// from the protocol.
+    Active,
+}
+
+impl From<SecurityLevel> for Security {
+    fn from(security: SecurityLevel) -> Self {
+        match security {
+            SecurityLevel::Passive => Security::Passive,
+            SecurityLevel::Active => Security::Active,
+        }
+    }
+}
+
+/// Formats in which the logs are written.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per line, with the party, the session and the round of the network as
+    /// separate fields, for log aggregation systems.
+    Json,
+}
+
+/// Operations supported by the tool.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Deal the shares of a secret to files, one per party.
+    Share {
+        /// The secret to share.
+        #[arg(long)]
+        secret: u64,
+        /// Number of parties that receive a share.
+        #[arg(short, long)]
+        parties: usize,
+        /// Number of corrupted parties.
+        #[arg(short, long)]
+        corruptions: usize,
+        /// Directory in which the share files are written.
+        #[arg(short, long, default_value = ".")]
+        output_dir: PathBuf,
+        /// Format of the share files.
+        #[arg(long, value_enum, default_value_t = ShareFormat::Json)]
+        format: ShareFormat,
+        /// Number of 64-bit limbs of the elements in the files of MP-SPDZ, which is the
+        /// `GFP_MOD_SZ` with which it was built.
+        #[arg(long, default_value_t = mpspdz::DEFAULT_LIMBS)]
+        limbs: usize,
+        #[command(flatten)]
+        seed: SeedArgs,
+    },
+    /// Reconstruct a secret from the share files of enough parties. The files of MP-SPDZ may hold
+    /// several shares, and the secret of each of them is reconstructed.
+    Reconstruct {
+        /// Share files written by the `share` command or by MP-SPDZ, whose names end with the ID
+        /// of the party, e.g. `Transactions-P1.data`.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Format of the share files.
+        #[arg(long, value_enum, default_value_t = ShareFormat::Json)]
+        format: ShareFormat,
+        /// Number of corrupted parties, which is the degree of the shares in the files of MP-SPDZ.
+        #[arg(short, long, required_if_eq("format", "mp-spdz"))]
+        corruptions: Option<usize>,
+        /// Number of 64-bit limbs of the elements in the files of MP-SPDZ.
+        #[arg(long, default_value_t = mpspdz::DEFAULT_LIMBS)]
+        limbs: usize,
+    },
+    /// Reconstruct a secret offline from the share files of at least `t + 1` parties, after
+    /// checking that they belong to the field and to the same dealing and that the parties are
+    /// distinct, and print it or write it to a file.
+    ReconstructFiles {
+        /// Share files written by the `share` command.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// File to which the secret is written as a decimal integer, instead of printing it.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Deal the shares of random Beaver triples to the preprocessing files of MP-SPDZ, one per
+    /// party, in the directory `<n>-S-<bits>` of the output directory.
+    Triples {
+        /// Number of triples dealt.
+        #[arg(long)]
+        count: usize,
+        /// Number of parties that receive the shares.
+        #[arg(short, long)]
+        parties: usize,
+        /// Number of corrupted parties, which must allow the parties to multiply.
+        #[arg(short, long)]
+        corruptions: usize,
+        /// Directory in which the preprocessing directory is created, i.e. the `Player-Data`
+        /// directory of MP-SPDZ.
+        #[arg(short, long, default_value = "Player-Data")]
+        output_dir: PathBuf,
+        /// Number of 64-bit limbs of the elements in the files of MP-SPDZ.
+        #[arg(long, default_value_t = mpspdz::DEFAULT_LIMBS)]
+        limbs: usize,
+        #[command(flatten)]
+        seed: SeedArgs,
+    },
+    /// Generate the network configuration files of a session, and optionally the certificates of
+    /// the parties.
+    GenConfig {
+        /// Number of parties in the session.
+        #[arg(short, long)]
+        parties: usize,
+        /// Port of the party with ID 0. The party with ID `i` listens in the port `base_port + i`.
+        #[arg(long, default_value_t = 5000)]
+        base_port: u16,
+        /// Hosts of the parties as a comma-separated list, or a single host shared by all of them.
+        #[arg(long, value_delimiter = ',', default_value = "127.0.0.1")]
+        hosts: Vec<String>,
+        /// Timeout in milliseconds to connect with the other parties.
+        #[arg(long, default_value_t = 5000)]
+        timeout: u64,
+        /// Time in milliseconds between the attempts to connect with another party.
+        #[arg(long, default_value_t = 500)]
+        sleep_time: u64,
+        /// Directory in which the configuration files are written.
+        #[arg(short, long, default_value = ".")]
+        output_dir: PathBuf,
+        /// Directory of the certificates referenced by the configuration files. By default, it is
+        /// the `certs` directory inside the output directory.
+        #[arg(long)]
+        cert_dir: Option<PathBuf>,
+        /// Also generate a root CA and the certificates and keys of the parties signed by it.
+        #[arg(long)]
+        certs: bool,
+    },
+    /// Manage the keys and certificates of the parties.
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+    /// Verify the hash chain of an audit log and report the number of events that it records.
+    VerifyAudit {
+        /// Path to the audit log.
+        file: PathBuf,
+    },
+    /// Validate a network configuration and report all its problems without running a protocol.
+    Check {
+        /// Path to the network configuration file.
+        #[arg(short, long)]
+        net_config_file: PathBuf,
+        /// ID of the party that uses the configuration, whose address must be free to listen.
+        #[arg(short, long)]
+        id: Option<usize>,
+        /// Number of corrupted parties, which must allow the parties to multiply.
+        #[arg(short, long)]
+        corruptions: Option<usize>,
+        /// Check that the other parties accept connections, so they must be already running.
+        #[arg(long)]
+        probe: bool,
+        /// Timeout in milliseconds to connect with each party when probing.
+        #[arg(long, default_value_t = 1000, requires = "probe")]
+        probe_timeout: u64,
+    },
+    /// Run all the parties in this process over an in-memory network and print the result.
+    Simulate {
+        /// Number of parties in the session.
+        #[arg(short, long)]
+        parties: usize,
+        /// Number of corrupted parties. By default, it is the largest number for which the
+        /// products can be computed, i.e. lower than half of the parties, or one for two parties,
+        /// who multiply with Beaver triples dealt in this process as by a trusted dealer.
+        #[arg(short, long)]
+        corruptions: Option<usize>,
+        /// The input of each party as a comma-separated list.
+        #[arg(
+            long,
+            required_unless_present = "bench",
+            value_delimiter = ',',
+            allow_hyphen_values = true
+        )]
+        inputs: Vec<Decimal>,
+        /// Number of fractional bits of the fixed-point encoding of the inputs.
+        #[arg(long, default_value_t = 0)]
+        fractional_bits: u32,
+        /// Expression to evaluate, with the same syntax as in the `eval` command. By default, the
+        /// inputs are multiplied.
+        #[arg(short, long, visible_alias = "expr")]
+        expression: Option<Expression>,
+        /// File with the arithmetic circuit to evaluate instead of the expression, in the format
+        /// of the `--circuit` option of the sessions.
+        #[arg(long, value_name = "PATH", conflicts_with = "expression")]
+        circuit: Option<PathBuf>,
+        #[command(flatten)]
+        bench: BenchArgs,
+        #[command(flatten)]
+        seed: SeedArgs,
+        /// Evaluate the circuit in the clear next to the parties and check that their shares
+        /// open to the value in the clear at every gate, reporting the first gate where they
+        /// diverge. This is only intended to debug the protocols.
+        #[arg(long, conflicts_with = "bench")]
+        shadow: bool,
+    },
+    /// Add the inputs of all the parties.
+    Add(SessionArgs),
+    /// Multiply the inputs of all the parties.
+    Multiply(SessionArgs),
+    /// Evaluate an arithmetic expression over the inputs of the parties.
+    Eval {
+        #[command(flatten)]
+        session: SessionArgs,
+        /// Expression to evaluate, where `xi[k]` is the input `k` of party `i` and `xi` is its first
+        /// input, e.g. "x0 * x1[1] + 2 * x2". The inputs may also be written `pi[k]` and `pi`, e.g.
+        /// "(p0 + p1) * p2 - 5".
+        #[arg(
+            short,
+            long,
+            visible_alias = "expr",
+            required_unless_present = "circuit",
+            conflicts_with = "circuit"
+        )]
+        expression: Option<Expression>,
+    },
+    /// Issue commands interactively against a live session with the other parties, who must
+    /// issue the same commands in the same order.
+    Repl {
+        #[command(flatten)]
+        network: NetworkArgs,
+        /// Number of corrupted parties.
+        #[arg(short, long, env = "SHAMI_CORRUPTIONS")]
+        corruptions: usize,
+        #[command(flatten)]
+        seed: SeedArgs,
+    },
+    /// Keep the connections with the other parties alive and serve the jobs read from the
+    /// standard input, one JSON object per line, each one in its own session.
+    Daemon {
+        #[command(flatten)]
+        network: NetworkArgs,
+        /// Number of corrupted parties.
+        #[arg(short, long, env = "SHAMI_CORRUPTIONS")]
+        corruptions: usize,
+        #[command(flatten)]
+        seed: SeedArgs,
+        /// Address of a control service through which applications submit the jobs, instead of
+        /// reading them from the standard input. It should only be reachable by trusted
+        /// applications.
+        #[arg(long, value_name = "ADDRESS")]
+        control: Option<SocketAddr>,
+        /// Address of an HTTP API through which applications submit the jobs, instead of reading
+        /// them from the standard input. It may be combined with the control service, which is
+        /// needed to shut the daemon down. It should only be reachable by trusted applications.
+        #[arg(long, value_name = "ADDRESS")]
+        http: Option<SocketAddr>,
+    },
+    /// Act as a trusted dealer, which is the last party of the network configuration: deal the
+    /// shares of the given inputs and of preprocessing material to the other parties, record
+    /// what each party received in the audit log, and exit.
+    Dealer {
+        #[command(flatten)]
+        network: NetworkArgs,
+        /// Number of corrupted parties among the parties that receive the shares.
+        #[arg(short, long, env = "SHAMI_CORRUPTIONS")]
+        corruptions: usize,
+        /// Inputs whose shares are dealt, as a comma-separated list.
+        #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+        inputs: Vec<Decimal>,
+        /// Number of fractional bits of the fixed-point encoding of the inputs.
+        #[arg(long, default_value_t = 0)]
+        fractional_bits: u32,
+        /// Number of Beaver triples dealt.
+        #[arg(long, default_value_t = 0)]
+        triple_count: usize,
+        /// Number of random bits dealt.
+        #[arg(long, default_value_t = 0)]
+        bit_count: usize,
+        #[command(flatten)]
+        seed: SeedArgs,
+    },
+    /// Receive the material of the party from the trusted dealer and write it to the output
+    /// directory: all of it to `dealing_<i>.json`, and the Beaver triples also to
+    /// `Triples-S-P<i>`, which the other commands take with `--triples`.
+    ReceiveDealing {
+        #[command(flatten)]
+        network: NetworkArgs,
+        /// Number of corrupted parties, which is the degree of the shares.
+        #[arg(short, long, env = "SHAMI_CORRUPTIONS")]
+        corruptions: usize,
+        /// Directory in which the files are written.
+        #[arg(short, long, default_value = ".")]
+        output_dir: PathBuf,
+    },
+}
+
+impl Command {
+    /// Returns the number of parties given to the command, if it sets up the parties itself
+    /// instead of joining a network.
+    fn n_parties(&self) -> Option<usize> {
+        match self {
+            Command::Share { parties, .. }
+            | Command::Triples { parties, .. }
+            | Command::Simulate { parties, .. } => Some(*parties),
+            _ => None,
+        }
+    }
+
+    /// Returns the seed of the deterministic generator given to the command, if any.
+    fn seed(&self) -> Option<&Seed> {
+        match self {
+            Command::Share { seed, .. }
+            | Command::Triples { seed, .. }
+            | Command::Simulate { seed, .. }
+            | Command::Repl { seed, .. }
+            | Command::Daemon { seed, .. }
+            | Command::Dealer { seed, .. } => seed.seed.as_ref(),
+            Command::Add(session) | Command::Multiply(session) | Command::Eval { session, .. } => {
+                session.seed.seed.as_ref()
+            }
+            Command::Reconstruct { .. }
+            | Command::ReconstructFiles { .. }
+            | Command::GenConfig { .. }
+            | Command::VerifyAudit { .. }
+            | Command::Check { .. }
+            | Command::Keys { .. }
+            | Command::ReceiveDealing { .. } => None,
+        }
+    }
+}
+
+/// Operations on the keys and certificates of the parties.
+#[derive(Subcommand, Debug)]
+enum KeysCommand {
+    /// Generate a root CA and the private key and certificate of every party signed by it, and
+    /// print the fingerprints of the certificates of the parties.
+    Generate {
+        /// Number of parties in the session.
+        #[arg(short, long)]
+        parties: usize,
+        /// Hosts of the parties as a comma-separated list, or a single host shared by all of them,
+        /// which are added to the names of their certificates.
+        #[arg(long, value_delimiter = ',', default_value = "127.0.0.1")]
+        hosts: Vec<String>,
+        /// Directory in which the keys and certificates are written.
+        #[arg(long, default_value = "certs")]
+        cert_dir: PathBuf,
+    },
+    /// Print the SHA-256 fingerprints of certificates, in the format of `peer_fingerprints`.
+    Fingerprint {
+        /// Certificate files in PEM format.
+        #[arg(required = true)]
+        certs: Vec<PathBuf>,
+    },
+    /// Replace the private key and the certificate of a party with new ones signed by the root
+    /// CA, keeping the previous files with the `.old` extension.
+    Rotate {
+        /// ID of the party whose key is rotated.
+        #[arg(short, long)]
+        id: usize,
+        /// Host of the party, which is added to the names of its certificate.
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Directory of the keys and certificates.
+        #[arg(long, default_value = "certs")]
+        cert_dir: PathBuf,
+        /// Network configuration files, as a comma-separated list, in which the fingerprint of
+        /// the new certificate is pinned if they pin the certificates with `peer_fingerprints`.
+        #[arg(long, value_delimiter = ',')]
+        config: Vec<PathBuf>,
+    },
+}
+
+/// Arguments to connect with the other parties of a session.
+#[derive(clap::Args, Debug)]
+struct NetworkArgs {
     /// ID of the current player.
-    #[arg(short, long)]
+    #[arg(short, long, env = "SHAMI_ID")]
     id: usize,
     /// Path to the network configuration file.
-    #[arg(short, long)]
+    #[arg(short, long, env = "SHAMI_NET_CONFIG")]
     net_config_file: String,
+    /// Serve as the coordinator of the session.
+    #[arg(long, env = "SHAMI_COORDINATE")]
+    coordinate: bool,
+    /// Replace a setting of the network configuration file, e.g. `--set timeout=10000` or
+    /// `--set peer_ips.1=10.0.0.2`. The value is read as JSON, or as a string if it is not valid
+    /// JSON. The option may be repeated, and it takes precedence over the `SHAMI_CONFIG_*`
+    /// environment variables, which take precedence over the file.
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    overrides: Vec<Override>,
+    /// File with the shares of the Beaver triples of the party, written by the `triples` command
+    /// with the default number of limbs. With two parties and one corruption, the products are
+    /// computed with these triples instead of an honest majority. A triple must never be used
+    /// twice, so every session needs a fresh file.
+    #[arg(long, value_name = "PATH", env = "SHAMI_TRIPLES")]
+    triples: Option<PathBuf>,
+    /// Security against the corrupted parties. In the active mode, every batch of products is
+    /// verified and every opening checks the shares of all the parties, at the cost of three
+    /// times the products, and the session aborts if a check fails. Every party must use the
+    /// same security.
+    #[arg(long, value_enum, default_value_t = SecurityLevel::Passive, env = "SHAMI_SECURITY")]
+    security: SecurityLevel,
+}
+
+/// Arguments of the commands that run a protocol with the other parties.
+#[derive(clap::Args, Debug)]
+struct SessionArgs {
+    #[command(flatten)]
+    network: NetworkArgs,
     /// Number of corrupted parties.
-    #[arg(short, long)]
+    #[arg(short, long, env = "SHAMI_CORRUPTIONS")]
     corruptions: usize,
-    /// The number you want to multiply.
+    /// The inputs of the party. The option may be repeated or take a comma-separated list, and
+    /// the inputs may be negative or decimal numbers encoded in fixed point.
+    #[arg(
+        long,
+        env = "SHAMI_INPUT",
+        value_delimiter = ',',
+        allow_hyphen_values = true,
+        required_unless_present_any = ["input_file", "bench", "phase", "no_input"]
+    )]
+    input: Vec<Decimal>,
+    /// Take part without inputs, only holding the shares of the inputs of the other parties.
+    #[arg(long, conflicts_with_all = ["input", "input_file"])]
+    no_input: bool,
+    /// Open the result only to the parties with the given IDs, as a comma-separated list. By
+    /// default, every party learns the result.
+    #[arg(
+        long,
+        value_name = "IDS",
+        value_delimiter = ',',
+        env = "SHAMI_OUTPUT_TO"
+    )]
+    output_to: Vec<usize>,
+    /// CSV or JSON file with more inputs of the party, which follow the ones given by `--input`.
+    #[arg(long, value_name = "PATH", env = "SHAMI_INPUT_FILE")]
+    input_file: Option<PathBuf>,
+    /// Column of the input file to read, given by name or position. By default, every value of
+    /// the file is read.
+    #[arg(long, requires = "input_file")]
+    input_column: Option<String>,
+    /// Number of fractional bits of the fixed-point encoding of the inputs.
+    #[arg(long, default_value_t = 0)]
+    fractional_bits: u32,
+    /// Run only one phase of the computation. The offline phase generates `--triple-count`
+    /// Beaver triples with the other parties, without any input, and the online phase consumes
+    /// them, so that every product takes a single opening of its masked factors.
+    #[arg(long, value_enum, requires = "triples", conflicts_with_all = ["replay", "bench"])]
+    phase: Option<Phase>,
+    /// Number of Beaver triples generated in the offline phase, one per product of the online
+    /// phase.
+    #[arg(long, default_value_t = 0)]
+    triple_count: usize,
+    /// File with an arithmetic circuit to evaluate instead of the computation of the command,
+    /// in the arithmetic variant of the Bristol Fashion format: a line with the number of gates
+    /// and wires, a line with the number of parties and of inputs of each one, the line `1 1`
+    /// for the single output, which is the last wire, and a gate per line, such as
+    /// `2 1 0 1 2 MUL`. The gates are `ADD`, `SUB`, `MUL`, `CMUL` by a constant, `EQ` to a
+    /// constant and `EQW` to copy a wire.
+    #[arg(long, value_name = "PATH", env = "SHAMI_CIRCUIT")]
+    circuit: Option<PathBuf>,
+    /// Replay a recorded transcript instead of the network.
+    #[arg(long, value_name = "TRANSCRIPT")]
+    replay: Option<String>,
+    /// File where the party writes a checkpoint at every round boundary. If the connection with
+    /// another party fails, the party reconnects and resumes from its last checkpoint. Every
+    /// party must enable the checkpoints.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["resume", "replay", "bench"])]
+    checkpoint: Option<PathBuf>,
+    /// Rejoin the other parties after a crash, resuming from the checkpoint written with
+    /// `--checkpoint`, which keeps being updated.
+    #[arg(long, value_name = "CHECKPOINT", conflicts_with_all = ["replay", "bench"])]
+    resume: Option<PathBuf>,
+    /// Show a dashboard in the terminal with the phase of the protocol, the rounds completed,
+    /// the status of the connection with each peer and the throughput, instead of the logs below
+    /// the warnings.
+    #[arg(long, conflicts_with_all = ["replay", "bench"])]
+    dashboard: bool,
+    /// CSV file where the party writes a row per phase of the protocol with its duration, its
+    /// rounds and the bytes sent to and received from each peer. If the file has the `.json`
+    /// extension, the phases are written as JSON instead, with the packets of each peer too.
+    #[arg(long, value_name = "PATH")]
+    metrics_out: Option<PathBuf>,
+    #[command(flatten)]
+    seed: SeedArgs,
+    #[command(flatten)]
+    bench: BenchArgs,
+}
+
+/// Argument that makes the randomness of a command deterministic.
+#[derive(clap::Args, Debug)]
+struct SeedArgs {
+    /// Hexadecimal seed of a deterministic generator for the randomness, to reproduce the
+    /// execution bit for bit. This is INSECURE, since anybody who knows the seed can recompute
+    /// the shares, and it is only intended for tests. It requires `--allow-insecure-seed`.
+    #[arg(long, value_name = "HEX")]
+    seed: Option<Seed>,
+}
+
+impl SeedArgs {
+    /// Returns the generator of the party with the given ID: a deterministic one if there is a
+    /// seed, which [`Cli::check_seed`] only lets through with `--allow-insecure-seed`, and the
+    /// generator of the operating system otherwise.
+    fn rng(&self, party: usize) -> Box<dyn RandomnessSource> {
+        match &self.seed {
+            Some(seed) => {
+                log::warn!("the randomness of party {party} is derived from a fixed seed, which is INSECURE and only intended for tests");
+                Box::new(seed.rng(party))
+            }
+            None => Box::new(OsRng),
+        }
+    }
+}
+
+/// Arguments of the benchmark of the protocols.
+#[derive(clap::Args, Debug)]
+struct BenchArgs {
+    /// Run a benchmark of the sharing, multiplication and opening protocols instead of
+    /// computing over the inputs, and print a summary table.
     #[arg(long)]
-    input: u64,
+    bench: bool,
+    /// Number of multiplications of the benchmark.
+    #[arg(long, default_value_t = 1000, requires = "bench")]
+    multiplications: usize,
+    /// Number of values opened in the benchmark.
+    #[arg(long, default_value_t = 1000, requires = "bench")]
+    openings: usize,
+    /// Number of multiplications or openings computed together in the benchmark. By default,
+    /// all of them are computed together.
+    #[arg(long, requires = "bench")]
+    batch_size: Option<usize>,
+    /// Number of shared messages encrypted with MiMC under a shared key in the benchmark.
+    #[arg(long, default_value_t = 0, requires = "bench")]
+    mimc: usize,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut log_builder = env_logger::Builder::new();
-    log_builder.filter_level(log::LevelFilter::Debug).init();
+impl NetworkArgs {
+    /// Connects with the other parties of the session, aborting the protocol on Ctrl-C.
+    fn connect(&self) -> anyhow::Result<Network> {
+        Connection {
+            id: self.id,
+            net_config_file: Path::new(&self.net_config_file),
+            overrides: &self.overrides,
+            coordinate: self.coordinate,
+            interrupt: Some(&INTERRUPTED),
+        }
+        .connect()
+    }
 
-    let args = Args::parse();
+    /// Creates the engine in which the protocols run over the network, with the security of the
+    /// arguments.
+    fn engine(&self, network: Network) -> Engine<Network> {
+        Engine::new(network).with_security(self.security.into())
+    }
+}
 
-    let net_config = NetworkConfig::new(Path::new(&args.net_config_file))?;
-    let n_parties = net_config.peer_ips.len();
+impl BenchArgs {
+    /// Returns the configuration of the benchmark.
+    fn config(&self) -> BenchConfig {
+        BenchConfig {
+            multiplications: self.multiplications,
+            openings: self.openings,
+            batch_size: self.batch_size,
+            mimc: self.mimc,
+        }
+    }
+}
 
-    // Create the network for communication.
-    let mut network = Network::create(args.id, net_config)?;
+/// Flag set when the party is interrupted with Ctrl-C, which makes the network abort the
+/// protocol and notify the other parties.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
-    // Compute random shares to send to the other parties.
-    let mut rng = rand::thread_rng();
-    let own_shares = mpc::compute_shamir_share(
-        &Mersenne61::from(args.input),
-        n_parties,
-        args.corruptions,
-        &mut rng,
-    );
+/// Time that an interrupted party has to abort the protocol before the process exits anyway,
+/// e.g. because the party was not waiting for the other parties.
+const INTERRUPT_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Handles SIGINT by setting [`INTERRUPTED`]. The network notices the flag the next time it waits
+/// for a packet, so if the party is stuck elsewhere, a second Ctrl-C exits right away.
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        // SAFETY: `_exit` is async-signal-safe.
+        unsafe { libc::_exit(ShamiError::INTERRUPTED_EXIT_CODE.into()) };
+    }
+}
+
+/// Installs the handler of Ctrl-C, and a watchdog that exits once the grace period of an
+/// interrupt is over, since only the network reacts to the flag.
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    let handler = on_interrupt as extern "C" fn(libc::c_int);
+    // SAFETY: the handler only touches an atomic flag and calls `_exit`, which are
+    // async-signal-safe.
+    if unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) } == libc::SIG_ERR {
+        log::warn!("the handler of Ctrl-C could not be installed");
+        return;
+    }
+    thread::spawn(|| {
+        while !INTERRUPTED.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(100));
+        }
+        thread::sleep(INTERRUPT_GRACE_PERIOD);
+        let _ = writeln!(io::stderr(), "Error: {}", ShamiError::Interrupted);
+        std::process::exit(ShamiError::INTERRUPTED_EXIT_CODE.into());
+    });
+}
 
-    log::debug!("the shares of the inputs are {:?}", own_shares);
+/// Installs the handler of Ctrl-C, which is only supported on Unix.
+#[cfg(not(unix))]
+fn install_interrupt_handler() {}
 
-    // Send the share to all the parties.
-    log::info!("sending the shares of the input to the other parties");
-    for (i, share) in own_shares.iter().enumerate() {
-        log::debug!("sending share to party {i}: {:?}", share);
-        let share_bytes = bincode::serialize(&share)?;
-        let share_packet = Packet::new(share_bytes);
-        network.send_to(&share_packet, i)?;
+/// Writes the network configuration files of a session and, if requested, the certificates of
+/// the parties.
+fn generate_configs(
+    template: &ConfigTemplate,
+    output_dir: &Path,
+    certs: bool,
+) -> anyhow::Result<()> {
+    for path in template.write_configs(output_dir)? {
+        log::info!("wrote the network configuration to {path:?}");
     }
-    let mut shares = Vec::with_capacity(n_parties);
+    if certs {
+        generate::generate_certificates(&template.cert_dir, &template.hosts, template.n_parties)?;
+    }
+    Ok(())
+}
 
-    // Receive the shares from all the parties.
-    log::info!("receiving shares of the inputs from other parties");
-    for i in 0..n_parties {
-        let packet = network.recv_from(i)?;
-        let share: ShamirShare<Mersenne61> = bincode::deserialize(packet.as_slice())?;
-        log::debug!("received share from party {i}: {:?}", share);
-        shares.push(share);
+/// Runs an operation on the keys and certificates of the parties.
+fn manage_keys(command: KeysCommand) -> anyhow::Result<()> {
+    match command {
+        KeysCommand::Generate {
+            parties,
+            hosts,
+            cert_dir,
+        } => {
+            let fingerprints = generate::generate_certificates(&cert_dir, &hosts, parties)?;
+            for (id, fingerprint) in fingerprints.iter().enumerate() {
+                println!("party {id}: {fingerprint}");
+            }
+        }
+        KeysCommand::Fingerprint { certs } => {
+            for path in certs {
+                let fingerprint = Fingerprint::of(&keys::load_certificate(&path)?);
+                println!("{}: {fingerprint}", path.display());
+            }
+        }
+        KeysCommand::Rotate {
+            id,
+            host,
+            cert_dir,
+            config,
+        } => {
+            let rotation = keys::rotate_party(&cert_dir, id, &host)?;
+            if let Some(previous) = rotation.previous {
+                println!("previous: {previous}");
+            }
+            println!("current: {}", rotation.current);
+            for path in config {
+                if keys::pin_fingerprint(&path, id, &rotation.current)? {
+                    log::info!("pinned the new certificate of party {id} in {path:?}");
+                } else {
+                    log::warn!("the configuration {path:?} does not pin the certificates");
+                }
+            }
+        }
     }
+    Ok(())
+}
 
-    log::debug!("the received shares are {:?}", shares);
+/// Validates a network configuration, printing every problem found.
+fn check_network_config(path: &Path, options: &CheckOptions) -> anyhow::Result<()> {
+    let problems = check::check_config(path, options);
+    if problems.is_empty() {
+        println!("the configuration {path:?} is valid");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{problem}");
+    }
+    Err(io::Error::new(
+        ErrorKind::InvalidInput,
+        format!("the configuration has {} problems", problems.len()),
+    )
+    .into())
+}
 
-    log::info!("running multiplication protocol");
-    let mut mult_share = run_multiply_protocol(
-        &shares[0],
-        &shares[1],
-        n_parties,
+/// Reconstructs the secret from the share files without any network, and prints it as an
+/// integer or writes it to the output file.
+fn reconstruct_secret_offline<F: FiniteField>(
+    files: &[PathBuf],
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    let secret =
+        share_file::reconstruct_share_files(share_file::read_share_files::<F>(files)?)?.value();
+    match output {
+        Some(path) => {
+            fs::write(path, format!("{secret}\n"))?;
+            log::info!("wrote the reconstructed secret to {path:?}");
+        }
+        None => println!("{secret}"),
+    }
+    Ok(())
+}
+
+/// Reconstructs the secrets of the shares in the files of MP-SPDZ of several parties, which
+/// hold the shares in the same order.
+fn reconstruct_from_mp_spdz_files<F: PrimeField>(
+    files: &[PathBuf],
+    degree: usize,
+    limbs: usize,
+) -> anyhow::Result<()> {
+    let secrets = MpSpdzFormat::new(limbs).reconstruct_files::<F>(files, degree)?;
+    for (i, secret) in secrets.iter().enumerate() {
+        log::info!("the reconstructed secret {i} is: {:?}", secret);
+    }
+    Ok(())
+}
+
+/// Verifies the hash chain of an audit log.
+fn verify_audit_log(path: &Path) -> anyhow::Result<()> {
+    let entries = audit::verify(path)?;
+    println!(
+        "the audit log has {} events and its hash chain is intact",
+        entries.len()
+    );
+    Ok(())
+}
+
+/// Runs the protocol that evaluates an expression over the inputs of the parties and opens the
+/// result to every party. The expression is built from the number of inputs of each party once
+/// the shares of the inputs are received, unless the circuit of `--circuit` replaces it.
+fn run_session<F: PrimeField>(
+    args: &SessionArgs,
+    name: &str,
+    expression: impl Fn(&[usize]) -> Expression,
+) -> anyhow::Result<()> {
+    let file_circuit = args
+        .circuit
+        .as_deref()
+        .map(Circuit::read_file)
+        .transpose()?;
+    let name = if file_circuit.is_some() {
+        "circuit"
+    } else {
+        name
+    };
+    let circuit = |n_inputs: &[usize]| match &file_circuit {
+        Some(circuit) => circuit.clone(),
+        None => Circuit::from_expression(&expression(n_inputs)),
+    };
+    // Create the network for communication.
+    let mut network = match &args.replay {
+        Some(transcript) => Network::replay(Path::new(transcript))?,
+        None => args.network.connect()?,
+    };
+    session::check_receivers(&args.output_to, network.n_parties())?;
+    let mut rng = args.seed.rng(network.id());
+    let triples = args.network.triples.as_deref();
+    if args.phase == Some(Phase::Offline) {
+        return session::generate_triples::<F, _>(
+            triples,
+            args.triple_count,
+            args.corruptions,
+            &mut rng,
+            network,
+        );
+    }
+    let dashboard = if args.dashboard {
+        Dashboard::start(network.id(), network.n_parties())
+    } else {
+        None
+    };
+    if let Some(dashboard) = &dashboard {
+        network.set_progress(Arc::clone(dashboard.progress()));
+    }
+    let id = network.id();
+    let mut engine = args.network.engine(network);
+    session::load_triples::<F>(
+        triples,
         args.corruptions,
-        &mut rng,
-        &mut network,
+        args.phase == Some(Phase::Online),
+        &mut engine,
     )?;
-    for share in shares.iter().skip(2) {
-        mult_share = run_multiply_protocol(
-            &mult_share,
-            share,
-            n_parties,
+    let phases = if args.bench.bench {
+        let report = bench::run_benchmark::<F, _>(
+            &args.bench.config(),
             args.corruptions,
             &mut rng,
-            &mut network,
+            &mut engine,
         )?;
-    }
-
-    // Open the secret by sending the shares to other parties.
-    log::info!("sending the shares of the result to other parties");
-    log::debug!("the share of party {} is {:?}", args.id, mult_share);
-    let mult_share_bytes = bincode::serialize(&mult_share)?;
-    let mult_share_packet = Packet::new(mult_share_bytes);
-    network.send(&mult_share_packet)?;
+        println!("{report}");
+        session::close_session(engine.into_network(), Vec::new())?
+    } else {
+        let inputs: Vec<F> = input::encode_inputs(
+            &args.input,
+            args.fractional_bits,
+            args.input_file.as_deref(),
+            args.input_column.as_deref(),
+        )?;
+        let reconnect = || args.network.connect();
+        let recovery = args
+            .resume
+            .as_deref()
+            .or(args.checkpoint.as_deref())
+            .map(|checkpoint| Recovery {
+                checkpoint,
+                resume: args.resume.is_some(),
+                reconnect: &reconnect,
+            });
+        let (_, phases) = session::run_with_recovery(
+            engine,
+            &inputs,
+            args.corruptions,
+            &mut rng,
+            Computation {
+                name,
+                circuit,
+                receivers: &args.output_to,
+            },
+            recovery,
+        )?;
+        phases
+    };
 
-    // Receive the shares from the Network
-    log::info!("receiving the shares of the result from other parties");
-    let mut mult_shares_remote = Vec::with_capacity(n_parties);
-    for i in 0..n_parties {
-        let packet = network.recv_from(i)?;
-        let share: ShamirShare<Mersenne61> = bincode::deserialize(packet.as_slice())?;
-        log::debug!("received share from party {i}: {:?}", share);
-        mult_shares_remote.push(share);
+    if let Some(path) = &args.metrics_out {
+        stats::write_phases(path, id, &phases)?;
+        log::info!("metrics written to {}", path.display());
     }
 
-    log::debug!("multiplications shares: {:?}", mult_shares_remote);
+    Ok(())
+}
 
-    let mult_result = reconstruct_secret(mult_shares_remote);
+/// Runs an interactive session with the other parties, reading the commands from the standard
+/// input until it ends or the operator quits.
+fn run_repl<F: PrimeField>(
+    args: &NetworkArgs,
+    corruptions: usize,
+    seed: &SeedArgs,
+) -> anyhow::Result<()> {
+    let network = args.connect()?;
+    mpc::check_parameters::<F>(network.n_parties(), corruptions, true)?;
+    let mut rng = seed.rng(network.id());
+    println!(
+        "connected to {} parties, type `help` to list the commands",
+        network.n_parties()
+    );
+    let mut engine = args.engine(network);
+    session::load_triples::<F>(args.triples.as_deref(), corruptions, false, &mut engine)?;
+    Repl::<F, _>::new(&mut engine, corruptions, &mut rng).run(io::stdin().lock(), io::stdout())?;
+    engine.into_network().close()?;
+    Ok(())
+}
 
-    log::info!("the multiplication result is: {:?}", mult_result);
+/// Serves the jobs submitted through the control service or the HTTP API, if their addresses are
+/// given, or the jobs read from the standard input otherwise, whose outcomes are written to the
+/// standard output. The daemon runs until the control service is shut down or the standard input
+/// ends.
+fn run_daemon<F: PrimeField>(
+    args: &NetworkArgs,
+    corruptions: usize,
+    seed: &SeedArgs,
+    control: Option<SocketAddr>,
+    http: Option<SocketAddr>,
+) -> anyhow::Result<()> {
+    let network = args.connect()?;
+    mpc::check_parameters::<F>(network.n_parties(), corruptions, true)?;
+    let mut rng = seed.rng(network.id());
+    let mut engine = args.engine(network);
+    session::load_triples::<F>(args.triples.as_deref(), corruptions, false, &mut engine)?;
+    let n_jobs = if control.is_some() || http.is_some() {
+        let queue = Arc::new(JobQueue::new());
+        if let Some(address) = control {
+            control::spawn_control_service(address, Arc::clone(&queue))?;
+        }
+        if let Some(address) = http {
+            http::spawn_http_service(address, Arc::clone(&queue))?;
+        }
+        control::serve_queue::<F, _>(corruptions, &mut rng, &mut engine, &queue)
+    } else {
+        log::info!("serving the jobs read from the standard input");
+        daemon::serve_jobs::<F, _, _, _>(
+            corruptions,
+            &mut rng,
+            &mut engine,
+            io::stdin().lock(),
+            io::stdout(),
+        )?
+    };
+    log::info!("served {n_jobs} jobs");
+    engine.into_network().close()?;
+    Ok(())
+}
 
+/// Runs the trusted dealer, which deals the shares of the inputs and of the preprocessing
+/// material to the other parties of the network and exits.
+fn run_trusted_dealer<F: FiniteField>(
+    args: &NetworkArgs,
+    corruptions: usize,
+    inputs: &[F],
+    preprocessing: Preprocessing,
+    seed: &SeedArgs,
+) -> anyhow::Result<()> {
+    let mut network = args.connect()?;
+    let mut rng = seed.rng(network.id());
+    dealer::run_dealer(inputs, preprocessing, corruptions, &mut rng, &mut network)?;
     network.close()?;
-
     Ok(())
 }
+
+/// Receives the material of the party from the trusted dealer and writes it to the output
+/// directory.
+fn receive_dealing<F: PrimeField>(
+    args: &NetworkArgs,
+    corruptions: usize,
+    output_dir: &Path,
+) -> anyhow::Result<()> {
+    let mut network = args.connect()?;
+    let dealing = dealer::receive_dealing::<F>(corruptions, &mut network)?;
+    network.close()?;
+    dealer::write_dealing(&dealing, output_dir)
+}
+
+/// Initializes the logger with the given format.
+fn init_logger(format: LogFormat) {
+    let mut log_builder = env_logger::Builder::new();
+    log_builder.filter_level(log::LevelFilter::Debug);
+    if format == LogFormat::Json {
+        log_builder.format(|buf, record| {
+            let mut line = serde_json::json!({
+                "timestamp": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            if let (Some(line), Value::Object(context)) = (
+                line.as_object_mut(),
+                serde_json::to_value(log_context::current())?,
+            ) {
+                line.extend(context);
+            }
+            if let (Some(line), Some(Value::Object(spans))) = (
+                line.as_object_mut(),
+                trace::current().map(serde_json::to_value).transpose()?,
+            ) {
+                line.extend(spans);
+            }
+            writeln!(buf, "{line}")
+        });
+    }
+    log_builder.init();
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    init_logger(cli.log_format);
+    install_interrupt_handler();
+    memory::set_locking(cli.lock_memory);
+    parallel::set_num_threads(cli.threads);
+
+    let field = FieldKind::from(cli.field);
+    let field = match cli.command.n_parties() {
+        Some(n_parties) => field.fitting(n_parties),
+        None => field,
+    };
+    let result = cli.check_seed().and_then(|()| match field {
+        FieldKind::Mersenne61 => run_command::<Mersenne61>(cli.command),
+        FieldKind::Mersenne127 => run_command::<Mersenne127>(cli.command),
+        FieldKind::P128 => run_command::<P128>(cli.command),
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            // The kind of failure is told apart by the exit code.
+            let err = ShamiError::from(err);
+            let _ = writeln!(io::stderr(), "Error: {err}");
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+/// Runs a command with the secrets shared in the field `F`.
+fn run_command<F: PrimeField + Send + Sync>(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Share {
+            secret,
+            parties,
+            corruptions,
+            output_dir,
+            format,
+            limbs,
+            seed,
+        } => {
+            dealer::deal_share_files(
+                &F::from(secret),
+                parties,
+                corruptions,
+                &output_dir,
+                format.with_limbs(limbs),
+                &mut seed.rng(0),
+            )?;
+            Ok(())
+        }
+        Command::Reconstruct {
+            files,
+            format,
+            corruptions,
+            limbs,
+        } => match (format, corruptions) {
+            (ShareFormat::MpSpdz, Some(corruptions)) => {
+                reconstruct_from_mp_spdz_files::<F>(&files, corruptions, limbs)
+            }
+            _ => {
+                let files = share_file::read_share_files::<F>(&files)?;
+                let secret = share_file::reconstruct_share_files(files)?;
+                log::info!("the reconstructed secret is: {:?}", secret);
+                Ok(())
+            }
+        },
+        Command::ReconstructFiles { files, output } => {
+            reconstruct_secret_offline::<F>(&files, output.as_deref())
+        }
+        Command::Triples {
+            count,
+            parties,
+            corruptions,
+            output_dir,
+            limbs,
+            seed,
+        } => {
+            dealer::deal_triple_files::<F, _>(
+                count,
+                parties,
+                corruptions,
+                &output_dir,
+                limbs,
+                &mut seed.rng(0),
+            )?;
+            Ok(())
+        }
+        Command::GenConfig {
+            parties,
+            base_port,
+            hosts,
+            timeout,
+            sleep_time,
+            output_dir,
+            cert_dir,
+            certs,
+        } => {
+            let template = ConfigTemplate {
+                n_parties: parties,
+                base_port,
+                hosts,
+                timeout,
+                sleep_time,
+                cert_dir: cert_dir.unwrap_or_else(|| output_dir.join("certs")),
+            };
+            generate_configs(&template, &output_dir, certs)
+        }
+        Command::Check {
+            net_config_file,
+            id,
+            corruptions,
+            probe,
+            probe_timeout,
+        } => check_network_config(
+            &net_config_file,
+            &CheckOptions {
+                id,
+                corruptions,
+                probe_timeout: probe.then(|| Duration::from_millis(probe_timeout)),
+            },
+        ),
+        Command::Keys { command } => manage_keys(command),
+        Command::VerifyAudit { file } => verify_audit_log(&file),
+        Command::Simulate {
+            parties,
+            corruptions,
+            inputs,
+            fractional_bits,
+            expression,
+            circuit,
+            bench,
+            seed,
+            shadow,
+        } => {
+            let corruptions = corruptions.unwrap_or(if parties == 2 {
+                1
+            } else {
+                parties.saturating_sub(1) / 2
+            });
+            if bench.bench {
+                let report = simulation::simulate_benchmark::<F, _>(
+                    parties,
+                    corruptions,
+                    &bench.config(),
+                    |party| seed.rng(party),
+                )?;
+                println!("{report}");
+                return Ok(());
+            }
+            if inputs.len() != parties {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "the number of inputs is not correct",
+                )
+                .into());
+            }
+            let inputs = inputs
+                .iter()
+                .map(|input| input.encode(fractional_bits))
+                .collect::<Result<Vec<F>, _>>()?;
+            let circuit = match circuit {
+                Some(path) => Circuit::read_file(&path)?,
+                None => Circuit::from_expression(
+                    &expression.unwrap_or_else(|| Expression::product_of_inputs(&vec![1; parties])),
+                ),
+            };
+            let result = simulation::simulate(
+                corruptions,
+                &inputs,
+                circuit,
+                |party| seed.rng(party),
+                shadow,
+            )?;
+            println!("{result:?}");
+            Ok(())
+        }
+        Command::Add(args) => run_session::<F>(&args, "addition", Expression::sum_of_inputs),
+        Command::Multiply(args) => {
+            run_session::<F>(&args, "multiplication", Expression::product_of_inputs)
+        }
+        Command::Eval {
+            session,
+            expression,
+        } => run_session::<F>(&session, "evaluation", |_: &[usize]| {
+            // The expression is only missing if the circuit of the file replaces it.
+            expression.clone().unwrap_or(Expression::Constant(0))
+        }),
+        Command::Repl {
+            network,
+            corruptions,
+            seed,
+        } => run_repl::<F>(&network, corruptions, &seed),
+        Command::Daemon {
+            network,
+            corruptions,
+            seed,
+            control,
+            http,
+        } => run_daemon::<F>(&network, corruptions, &seed, control, http),
+        Command::Dealer {
+            network,
+            corruptions,
+            inputs,
+            fractional_bits,
+            triple_count,
+            bit_count,
+            seed,
+        } => {
+            let inputs = inputs
+                .iter()
+                .map(|input| input.encode(fractional_bits))
+                .collect::<Result<Vec<F>, _>>()?;
+            let preprocessing = Preprocessing {
+                triples: triple_count,
+                bits: bit_count,
+            };
+            run_trusted_dealer(&network, corruptions, &inputs, preprocessing, &seed)
+        }
+        Command::ReceiveDealing {
+            network,
+            corruptions,
+            output_dir,
+        } => receive_dealing::<F>(&network, corruptions, &output_dir),
+    }
+}
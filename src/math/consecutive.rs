@@ -0,0 +1,112 @@
+use zeroize::Zeroizing;
+
+use super::{FiniteField, Polynomial};
+
+/// Number of points from which the sharing and the reconstruction of a secret switch from the
+/// quadratic evaluation and interpolation to the functions of this module.
+pub const CONSECUTIVE_THRESHOLD: usize = 32;
+
+/// Tells whether the integers `1, 2, 3, ...` are consecutive in the field, i.e. whether adding
+/// one to the image of an integer gives the image of the next one. That holds in the prime
+/// fields, but not in GF(2^64), where the integers map to polynomials by their bits.
+pub fn has_consecutive_points<T: FiniteField>() -> bool {
+    T::ONE.add(&T::ONE).equal(&T::from(2))
+}
+
+/// Evaluates a polynomial of degree `d` at the points `1, 2, ..., count` by forward differences.
+/// The first `d + 1` evaluations use Horner's rule, and every further one costs `d` additions, so
+/// the sharing of a secret among `n` parties takes `O(d^2)` products instead of `O(n d)`. The
+/// points must be consecutive in the field, see [`has_consecutive_points`].
+pub fn evaluate_consecutive<T: FiniteField>(polynomial: &Polynomial<T>, count: usize) -> Vec<T> {
+    let degree = polynomial.0.len() - 1;
+    // The differences are derived from the polynomial, so they are wiped when dropped.
+    let mut differences: Zeroizing<Vec<T>> = Zeroizing::new(
+        (1..=degree.min(count.saturating_sub(1)) as u64 + 1)
+            .map(|point| polynomial.evaluate(&T::from(point)))
+            .collect(),
+    );
+    if differences.len() <= degree {
+        return differences.to_vec();
+    }
+    // Turns the evaluations at 1, ..., d + 1 into the differences of every order at 1.
+    for order in 1..=degree {
+        for i in (order..=degree).rev() {
+            differences[i] = differences[i].subtract(&differences[i - 1]);
+        }
+    }
+    let mut evaluations = Vec::with_capacity(count);
+    for _ in 0..count {
+        evaluations.push(differences[0].clone());
+        // The difference of order `d` is constant, and the others move to the next point.
+        for i in 0..degree {
+            differences[i] = differences[i].add(&differences[i + 1]);
+        }
+    }
+    evaluations
+}
+
+/// Computes the Lagrange basis at zero for the points `1, 2, ..., n` in `O(n)` products and a
+/// single inversion. The coefficient of the point `i` is `(-1)^(i + 1) C(n, i)`, which is
+/// computed from the factorials up to `n`, e.g. `(5, -10, 10, -5, 1)` for five points. The
+/// points must be consecutive and nonzero in the field, see [`has_consecutive_points`].
+pub fn lagrange_basis_at_zero<T: FiniteField>(n: usize) -> Vec<T> {
+    let mut factorials = Vec::with_capacity(n + 1);
+    factorials.push(T::ONE);
+    for i in 1..=n {
+        factorials.push(factorials[i - 1].multiply(&T::from(i as u64)));
+    }
+    // The unwrap is safe because the points are nonzero, so neither is their product.
+    let mut inverse_factorials = vec![T::ZERO; n + 1];
+    inverse_factorials[n] = factorials[n].inverse().unwrap();
+    for i in (1..=n).rev() {
+        inverse_factorials[i - 1] = inverse_factorials[i].multiply(&T::from(i as u64));
+    }
+    (1..=n)
+        .map(|i| {
+            let binomial = factorials[n]
+                .multiply(&inverse_factorials[i])
+                .multiply(&inverse_factorials[n - i]);
+            if i % 2 == 1 {
+                binomial
+            } else {
+                binomial.negate()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::{evaluate_consecutive, has_consecutive_points, lagrange_basis_at_zero};
+    use crate::math::{
+        gf2k::GF2k, lagrange::compute_lagrange_basis, mersenne127::Mersenne127,
+        mersenne61::Mersenne61, FiniteField, Polynomial,
+    };
+
+    #[test]
+    fn evaluations_match_horner() {
+        let mut rng = thread_rng();
+        for (degree, count) in [(0, 5), (1, 1), (3, 2), (3, 4), (13, 40), (20, 100)] {
+            let polynomial: Polynomial<Mersenne127> = Polynomial::random(degree, &mut rng);
+            let expected: Vec<_> = (1..=count as u64)
+                .map(|point| polynomial.evaluate(&Mersenne127::from(point)))
+                .collect();
+            assert_eq!(evaluate_consecutive(&polynomial, count), expected);
+        }
+    }
+
+    #[test]
+    fn basis_at_zero_matches_lagrange() {
+        for n in [1, 2, 5, 33, 100] {
+            let nodes = (1..=n as u64).map(Mersenne61::from).collect();
+            assert_eq!(
+                lagrange_basis_at_zero::<Mersenne61>(n),
+                compute_lagrange_basis(nodes, &Mersenne61::ZERO)
+            );
+        }
+        assert!(has_consecutive_points::<Mersenne61>());
+        assert!(!has_consecutive_points::<GF2k>());
+    }
+}
@@ -0,0 +1,368 @@
+use std::{fmt, str::FromStr};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    mersenne127::Mersenne127, mersenne61::Mersenne61, p128::P128, FieldError, FiniteField,
+    PrimeField,
+};
+
+/// Possible errors that may appear in the operations on elements whose field is chosen at
+/// runtime.
+#[derive(Debug, Error)]
+pub enum DynFieldError {
+    /// The operands belong to different fields.
+    #[error("the operands belong to different fields: {left} and {right}")]
+    Mismatch { left: FieldKind, right: FieldKind },
+
+    /// The name does not belong to any of the fields of the crate.
+    #[error("unknown field {0}, expected mersenne61, mersenne127 or p128")]
+    UnknownField(String),
+
+    /// The operation failed in the field, e.g. the inverse of zero.
+    #[error(transparent)]
+    Field(#[from] FieldError),
+}
+
+/// Field of the crate chosen at runtime, e.g. per session of a daemon, so that a single binary
+/// serves computations over any of them. The protocols are generic over [`FiniteField`], so
+/// [`FieldKind::dispatch`] runs a [`FieldVisitor`] with the type of the field, which compiles the
+/// protocols once per field and keeps their arithmetic free of runtime dispatch.
+///
+/// Only the prime fields are chosen at runtime, since the sessions take their inputs as
+/// fixed-point numbers and may exchange MP-SPDZ files, which need a [`PrimeField`]. The binary
+/// extension field [`super::gf2k::GF2k`] is used through the library instead, with the protocols
+/// that are generic over [`FiniteField`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    /// Integers modulo the Mersenne prime 2^61 - 1.
+    Mersenne61,
+    /// Integers modulo the Mersenne prime 2^127 - 1.
+    Mersenne127,
+    /// Integers modulo the prime 2^128 - 159.
+    P128,
+}
+
+/// Generic computation over a field chosen at runtime, run by [`FieldKind::dispatch`].
+pub trait FieldVisitor {
+    /// Result of the computation.
+    type Output;
+
+    /// Runs the computation in the field `T`.
+    fn visit<T: PrimeField + Into<DynField>>(self) -> Self::Output;
+}
+
+impl FieldKind {
+    /// Fields of the crate in increasing order of size.
+    pub const ALL: [FieldKind; 3] = [
+        FieldKind::Mersenne61,
+        FieldKind::Mersenne127,
+        FieldKind::P128,
+    ];
+
+    /// Returns the field of the type `T`, or `None` if it is not one of the fields of the crate.
+    pub fn of<T: PrimeField>() -> Option<FieldKind> {
+        Self::ALL.into_iter().find(|kind| kind.name() == T::NAME)
+    }
+
+    /// Returns the short name of the field, as in [`FiniteField::NAME`].
+    pub fn name(self) -> &'static str {
+        match self {
+            FieldKind::Mersenne61 => Mersenne61::NAME,
+            FieldKind::Mersenne127 => Mersenne127::NAME,
+            FieldKind::P128 => P128::NAME,
+        }
+    }
+
+    /// Returns the modulus of the field.
+    pub fn modulus(self) -> u128 {
+        match self {
+            FieldKind::Mersenne61 => Mersenne61::MODULUS.into(),
+            FieldKind::Mersenne127 => Mersenne127::MODULUS,
+            FieldKind::P128 => P128::MODULUS,
+        }
+    }
+
+    /// Returns the field if it has a distinct nonzero evaluation point for every party, or else
+    /// the smallest larger field that does. If no field is large enough, the field is kept, and
+    /// the protocols fail with [`crate::mpc::ThresholdError::FieldTooSmall`].
+    pub fn fitting(self, n_parties: usize) -> FieldKind {
+        let fits = |kind: &FieldKind| (n_parties as u128) < kind.modulus();
+        if fits(&self) {
+            return self;
+        }
+        match Self::ALL
+            .into_iter()
+            .filter(|kind| kind.modulus() > self.modulus())
+            .find(fits)
+        {
+            Some(kind) => {
+                log::warn!("the field {self} is too small for {n_parties} parties, using {kind}");
+                kind
+            }
+            None => self,
+        }
+    }
+
+    /// Runs the visitor with the type of the field.
+    pub fn dispatch<V: FieldVisitor>(self, visitor: V) -> V::Output {
+        match self {
+            FieldKind::Mersenne61 => visitor.visit::<Mersenne61>(),
+            FieldKind::Mersenne127 => visitor.visit::<Mersenne127>(),
+            FieldKind::P128 => visitor.visit::<P128>(),
+        }
+    }
+}
+
+impl fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldKind::Mersenne61 => "mersenne61",
+            FieldKind::Mersenne127 => "mersenne127",
+            FieldKind::P128 => "p128",
+        })
+    }
+}
+
+impl FromStr for FieldKind {
+    type Err = DynFieldError;
+
+    /// Parses the field from its name, e.g. `mersenne61`, or its short name, e.g. `M61`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s.to_lowercase() || kind.name() == s)
+            .ok_or_else(|| DynFieldError::UnknownField(s.to_string()))
+    }
+}
+
+/// Element of a field chosen at runtime. The arithmetic between elements of different fields
+/// fails with [`DynFieldError::Mismatch`]. It is serialized as the element of its field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum DynField {
+    /// Element of [`Mersenne61`].
+    Mersenne61(Mersenne61),
+    /// Element of [`Mersenne127`].
+    Mersenne127(Mersenne127),
+    /// Element of [`P128`].
+    P128(P128),
+}
+
+/// Builds the element of the field of the given kind computed by the expression, in which the
+/// type of the field is bound to the given name.
+macro_rules! in_field {
+    ($kind:expr, $field:ident => $body:expr) => {
+        match $kind {
+            FieldKind::Mersenne61 => {
+                type $field = Mersenne61;
+                DynField::Mersenne61($body)
+            }
+            FieldKind::Mersenne127 => {
+                type $field = Mersenne127;
+                DynField::Mersenne127($body)
+            }
+            FieldKind::P128 => {
+                type $field = P128;
+                DynField::P128($body)
+            }
+        }
+    };
+}
+
+/// Applies an operation to the element of every field.
+macro_rules! map_element {
+    ($value:expr, $element:ident => $body:expr) => {
+        match $value {
+            DynField::Mersenne61($element) => DynField::Mersenne61($body),
+            DynField::Mersenne127($element) => DynField::Mersenne127($body),
+            DynField::P128($element) => DynField::P128($body),
+        }
+    };
+}
+
+/// Applies a binary operation to two elements of the same field.
+macro_rules! zip_elements {
+    ($left:expr, $right:expr, $a:ident, $b:ident => $body:expr) => {
+        match ($left, $right) {
+            (DynField::Mersenne61($a), DynField::Mersenne61($b)) => Ok(DynField::Mersenne61($body)),
+            (DynField::Mersenne127($a), DynField::Mersenne127($b)) => {
+                Ok(DynField::Mersenne127($body))
+            }
+            (DynField::P128($a), DynField::P128($b)) => Ok(DynField::P128($body)),
+            (left, right) => Err(DynFieldError::Mismatch {
+                left: left.kind(),
+                right: right.kind(),
+            }),
+        }
+    };
+}
+
+impl DynField {
+    /// Returns the additive identity of the field.
+    pub fn zero(kind: FieldKind) -> Self {
+        in_field!(kind, T => T::ZERO)
+    }
+
+    /// Returns the multiplicative identity of the field.
+    pub fn one(kind: FieldKind) -> Self {
+        in_field!(kind, T => T::ONE)
+    }
+
+    /// Returns the element of the field that represents an integer, reduced modulo p.
+    pub fn from_u64(kind: FieldKind, value: u64) -> Self {
+        in_field!(kind, T => T::from(value))
+    }
+
+    /// Generates a random element of the field with a provided pseudo-random generator.
+    pub fn random<R: Rng>(kind: FieldKind, generator: &mut R) -> Self {
+        in_field!(kind, T => T::random(generator))
+    }
+
+    /// Returns the field of the element.
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            DynField::Mersenne61(_) => FieldKind::Mersenne61,
+            DynField::Mersenne127(_) => FieldKind::Mersenne127,
+            DynField::P128(_) => FieldKind::P128,
+        }
+    }
+
+    /// Adds two elements of the same field.
+    pub fn add(&self, other: &Self) -> Result<Self, DynFieldError> {
+        zip_elements!(self, other, a, b => a.add(b))
+    }
+
+    /// Subtracts two elements of the same field.
+    pub fn subtract(&self, other: &Self) -> Result<Self, DynFieldError> {
+        zip_elements!(self, other, a, b => a.subtract(b))
+    }
+
+    /// Multiplies two elements of the same field.
+    pub fn multiply(&self, other: &Self) -> Result<Self, DynFieldError> {
+        zip_elements!(self, other, a, b => a.multiply(b))
+    }
+
+    /// Computes the additive inverse of the element.
+    pub fn negate(&self) -> Self {
+        map_element!(self, a => a.negate())
+    }
+
+    /// Computes the multiplicative inverse of the element.
+    pub fn inverse(&self) -> Result<Self, DynFieldError> {
+        Ok(map_element!(self, a => a.inverse()?))
+    }
+
+    /// Compares two elements in constant time, where elements of different fields are never
+    /// equal.
+    pub fn equal(&self, other: &Self) -> bool {
+        match (self, other) {
+            (DynField::Mersenne61(a), DynField::Mersenne61(b)) => a.equal(b),
+            (DynField::Mersenne127(a), DynField::Mersenne127(b)) => a.equal(b),
+            (DynField::P128(a), DynField::P128(b)) => a.equal(b),
+            _ => false,
+        }
+    }
+
+    /// Returns the integer in `[0, p)` that represents the element.
+    pub fn value(&self) -> u128 {
+        match self {
+            DynField::Mersenne61(a) => a.value().into(),
+            DynField::Mersenne127(a) => a.value(),
+            DynField::P128(a) => a.value(),
+        }
+    }
+}
+
+impl From<Mersenne61> for DynField {
+    fn from(value: Mersenne61) -> Self {
+        DynField::Mersenne61(value)
+    }
+}
+
+impl From<Mersenne127> for DynField {
+    fn from(value: Mersenne127) -> Self {
+        DynField::Mersenne127(value)
+    }
+}
+
+impl From<P128> for DynField {
+    fn from(value: P128) -> Self {
+        DynField::P128(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::{DynField, DynFieldError, FieldKind, FieldVisitor};
+    use crate::math::{mersenne61::Mersenne61, PrimeField};
+
+    /// Visitor that computes `2 * 3 - 1` in the field and returns its short name.
+    struct Arithmetic;
+
+    impl FieldVisitor for Arithmetic {
+        type Output = (&'static str, DynField);
+
+        fn visit<T: PrimeField + Into<DynField>>(self) -> Self::Output {
+            let value = T::from(2).multiply(&T::from(3)).subtract(&T::ONE);
+            (T::NAME, value.into())
+        }
+    }
+
+    #[test]
+    fn small_fields_are_replaced_by_fitting_ones() {
+        assert_eq!(FieldKind::Mersenne61.fitting(5), FieldKind::Mersenne61);
+        assert_eq!(FieldKind::P128.fitting(5), FieldKind::P128);
+        let too_many = usize::try_from(FieldKind::Mersenne61.modulus()).unwrap();
+        assert_eq!(
+            FieldKind::Mersenne61.fitting(too_many),
+            FieldKind::Mersenne127
+        );
+    }
+
+    #[test]
+    fn fields_are_chosen_at_runtime() {
+        for kind in FieldKind::ALL {
+            let (name, value) = kind.dispatch(Arithmetic);
+            assert_eq!(name, kind.name());
+            assert_eq!(value.kind(), kind);
+            assert_eq!(value, DynField::from_u64(kind, 5));
+            assert_eq!(kind.to_string().parse::<FieldKind>().unwrap(), kind);
+            assert_eq!(kind.name().parse::<FieldKind>().unwrap(), kind);
+
+            let two = DynField::from_u64(kind, 2);
+            let sum = two.add(&DynField::one(kind)).unwrap();
+            assert!(sum.subtract(&two).unwrap().equal(&DynField::one(kind)));
+            assert_eq!(two.negate().add(&two).unwrap(), DynField::zero(kind));
+            let random = DynField::random(kind, &mut thread_rng());
+            assert!(random.value() < kind.modulus());
+            assert!(matches!(
+                DynField::zero(kind).inverse(),
+                Err(DynFieldError::Field(_))
+            ));
+            assert_eq!(
+                two.inverse().unwrap().multiply(&two).unwrap(),
+                DynField::one(kind)
+            );
+        }
+        assert_eq!(FieldKind::of::<Mersenne61>(), Some(FieldKind::Mersenne61));
+
+        let small = DynField::one(FieldKind::Mersenne61);
+        let large = DynField::one(FieldKind::P128);
+        assert!(matches!(
+            small.multiply(&large),
+            Err(DynFieldError::Mismatch {
+                left: FieldKind::Mersenne61,
+                right: FieldKind::P128,
+            })
+        ));
+        assert!(!small.equal(&large));
+        assert!("gf256".parse::<FieldKind>().is_err());
+        assert_eq!(serde_json::to_string(&small).unwrap(), "1");
+    }
+}
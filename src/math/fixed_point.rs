@@ -0,0 +1,171 @@
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+use super::{from_u128, FieldValue, PrimeField};
+
+/// Maximum number of digits of a decimal number, so that its digits fit in a `u128`.
+const MAX_DIGITS: usize = 38;
+
+/// Possible errors that may appear while encoding a decimal number as a field element.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FixedPointError {
+    /// The text is not a decimal number.
+    #[error("the number {0} is not correct")]
+    InvalidNumber(String),
+
+    /// The number has a fractional part, but it is encoded without fractional bits.
+    #[error("the number {0} has a fractional part, which needs fractional bits to be encoded")]
+    FractionalValue(String),
+
+    /// The encoded number does not fit in the half of the field reserved to its sign.
+    #[error("the number {0} is out of the range of the field")]
+    OutOfRange(String),
+}
+
+/// Decimal number parsed without loss of precision, e.g. `-12.375`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decimal {
+    /// Whether the number is negative.
+    negative: bool,
+    /// Digits of the number without the decimal point.
+    digits: u128,
+    /// Number of digits after the decimal point.
+    decimals: u32,
+}
+
+impl Decimal {
+    /// Encodes the number as a field element in fixed point with the given number of fractional
+    /// bits, i.e. as the integer closest to `number * 2^fractional_bits`, rounding the halves away
+    /// from zero. Negative numbers are encoded as their additive inverse in the field, so the
+    /// magnitude of the encoding must be lower than half of the modulus. Without fractional bits,
+    /// numbers with a fractional part are rejected instead of rounded.
+    pub fn encode<T: PrimeField>(&self, fractional_bits: u32) -> Result<T, FixedPointError> {
+        let scale = 10u128.pow(self.decimals);
+        if fractional_bits == 0 && !self.digits.is_multiple_of(scale) {
+            return Err(FixedPointError::FractionalValue(self.to_string()));
+        }
+        let out_of_range = || FixedPointError::OutOfRange(self.to_string());
+        let shifted = 1u128
+            .checked_shl(fractional_bits)
+            .and_then(|factor| self.digits.checked_mul(factor))
+            .ok_or_else(out_of_range)?;
+        let mut magnitude = shifted / scale;
+        if 2 * (shifted % scale) >= scale {
+            magnitude += 1;
+        }
+        if T::MODULUS
+            .to_u128()
+            .is_some_and(|modulus| magnitude > modulus / 2)
+        {
+            return Err(out_of_range());
+        }
+        let value: T = from_u128(magnitude);
+        Ok(if self.negative { value.negate() } else { value })
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = FixedPointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || FixedPointError::InvalidNumber(s.to_string());
+        let (negative, unsigned) = match s.trim().strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, s.trim().strip_prefix('+').unwrap_or(s.trim())),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let all_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if integer.len() + fraction.len() == 0
+            || integer.len() + fraction.len() > MAX_DIGITS
+            || !all_digits(integer)
+            || !all_digits(fraction)
+        {
+            return Err(invalid());
+        }
+        let digits = format!("{integer}{fraction}")
+            .parse()
+            .map_err(|_| invalid())?;
+        Ok(Self {
+            negative,
+            digits,
+            decimals: fraction.len() as u32,
+        })
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = format!(
+            "{:0>width$}",
+            self.digits,
+            width = self.decimals as usize + 1
+        );
+        let (integer, fraction) = digits.split_at(digits.len() - self.decimals as usize);
+        let sign = if self.negative { "-" } else { "" };
+        if fraction.is_empty() {
+            write!(f, "{sign}{integer}")
+        } else {
+            write!(f, "{sign}{integer}.{fraction}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decimal, FixedPointError};
+    use crate::math::{mersenne127::Mersenne127, mersenne61::Mersenne61, FiniteField};
+
+    fn encode(number: &str, fractional_bits: u32) -> Result<Mersenne61, FixedPointError> {
+        number.parse::<Decimal>()?.encode(fractional_bits)
+    }
+
+    #[test]
+    fn encode_decimals_in_fixed_point() {
+        assert_eq!(encode("42", 0), Ok(Mersenne61::from(42)));
+        assert_eq!(encode("-3", 0), Ok(Mersenne61::from(3).negate()));
+        assert_eq!(encode("2.5", 1), Ok(Mersenne61::from(5)));
+        assert_eq!(encode("-12.375", 3), Ok(Mersenne61::from(99).negate()));
+        // 0.1 * 2^4 = 1.6 is rounded to 2.
+        assert_eq!(encode("0.1", 4), Ok(Mersenne61::from(2)));
+        assert_eq!(encode("+7.", 0), Ok(Mersenne61::from(7)));
+        assert_eq!(encode("-0.03", 2).unwrap(), Mersenne61::ZERO);
+
+        // The numbers that do not fit in 64 bits are encoded in the larger fields.
+        let large: Result<Mersenne127, _> = "-36893488147419103232.5"
+            .parse::<Decimal>()
+            .unwrap()
+            .encode(1);
+        let two_to_33 = Mersenne127::from(1 << 33);
+        assert_eq!(
+            large,
+            Ok(two_to_33
+                .multiply(&two_to_33)
+                .add(&Mersenne127::ONE)
+                .negate())
+        );
+    }
+
+    #[test]
+    fn reject_invalid_decimals() {
+        for invalid in ["", "-", ".", "1e5", "1.2.3", "abc", "0x10"] {
+            assert!(matches!(
+                invalid.parse::<Decimal>(),
+                Err(FixedPointError::InvalidNumber(_))
+            ));
+        }
+        assert_eq!(
+            encode("2.5", 0),
+            Err(FixedPointError::FractionalValue("2.5".to_string()))
+        );
+        assert!(matches!(
+            encode("2305843009213693951", 0),
+            Err(FixedPointError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            encode("1", 200),
+            Err(FixedPointError::OutOfRange(_))
+        ));
+        assert_eq!("-0.050".parse::<Decimal>().unwrap().to_string(), "-0.050");
+    }
+}
@@ -0,0 +1,214 @@
+use serde::Deserialize;
+use serde::Serialize;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::FieldError;
+use super::FiniteField;
+
+/// Low terms `x^4 + x^3 + x + 1` of the irreducible polynomial `x^64 + x^4 + x^3 + x + 1` that
+/// defines the field, to which `x^64` is congruent.
+const REDUCTION: u64 = 0x1B;
+
+/// Representation of an element of the binary extension field GF(2^{64}), i.e. a polynomial of
+/// degree lower than 64 with coefficients in GF(2) modulo the irreducible polynomial
+/// `x^64 + x^4 + x^3 + x + 1`, whose coefficients are the bits of an integer, e.g. `x + 1` is 3.
+/// The additions are XORs and the products are carry-less, which suits the boolean workloads.
+///
+/// The field has characteristic two, so every element is its own additive inverse. The
+/// evaluation points of the parties are the polynomials of the integers `1..=n`, which are
+/// distinct and nonzero. The modulus is the integer of the irreducible polynomial, which is
+/// larger than every element. It does not implement [`super::PrimeField`], so the encodings of
+/// the crate that treat the elements as integers, such as the fixed-point numbers, the
+/// comparisons and the files of MP-SPDZ, do not accept it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GF2k(u64);
+
+impl GF2k {
+    /// Computes the carry-less product of two polynomials of degree lower than 64 by adding the
+    /// shifts of `a` selected by the bits of `b`, without branching on the values.
+    fn carryless_product(a: u64, b: u64) -> u128 {
+        (0..64).fold(0, |product, i| {
+            let mask = 0u128.wrapping_sub(u128::from((b >> i) & 1));
+            product ^ ((u128::from(a) << i) & mask)
+        })
+    }
+
+    /// Reduces a polynomial of degree lower than 128 modulo the irreducible polynomial.
+    fn reduce(value: u128) -> Self {
+        // As x^64 = x^4 + x^3 + x + 1, the high half is folded twice: the first fold leaves a
+        // polynomial of degree lower than 68, and the second one fits in the low half.
+        let fold = |high: u128| high ^ (high << 1) ^ (high << 3) ^ (high << 4);
+        let folded = (value as u64 as u128) ^ fold(value >> 64);
+        Self(((folded as u64 as u128) ^ fold(folded >> 64)) as u64)
+    }
+}
+
+impl ConstantTimeEq for GF2k {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for GF2k {
+    fn eq(&self, other: &Self) -> bool {
+        self.equal(other)
+    }
+}
+
+impl Eq for GF2k {}
+
+impl Zeroize for GF2k {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for GF2k {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for GF2k {}
+
+impl From<u64> for GF2k {
+    /// Returns the polynomial whose coefficients are the bits of the integer.
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl FiniteField for GF2k {
+    type ValueType = u128;
+
+    const MODULUS: u128 = (1 << 64) | REDUCTION as u128;
+    const BIT_SIZE: usize = 64;
+    const NAME: &'static str = "GF64";
+    const ONE: Self = Self(1);
+    const ZERO: Self = Self(0);
+
+    fn add(&self, other: &Self) -> Self {
+        Self(self.0 ^ other.0)
+    }
+
+    fn random<R: rand::Rng>(generator: &mut R) -> Self {
+        Self(generator.gen())
+    }
+
+    fn value(&self) -> Self::ValueType {
+        self.0.into()
+    }
+
+    fn multiply(&self, other: &Self) -> Self {
+        Self::reduce(Self::carryless_product(self.0, other.0))
+    }
+
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self(u64::conditional_select(&a.0, &b.0, choice))
+    }
+
+    fn inverse(&self) -> Result<Self, FieldError> {
+        if self.equal(&Self::ZERO) {
+            Err(FieldError::ZeroInverse)
+        } else {
+            // The multiplicative group has 2^{64} - 1 elements, so a^{-1} = a^{2^{64} - 2}.
+            Ok(super::pow(self, (1 << 64) - 2))
+        }
+    }
+
+    fn negate(&self) -> Self {
+        self.clone()
+    }
+
+    fn subtract(&self, other: &Self) -> Self {
+        self.add(other)
+    }
+
+    fn group_order_rem(divisor: u64) -> u64 {
+        u64::MAX % divisor
+    }
+
+    /// Returns the polynomial of an integer of at most 64 bits, since the larger integers do not
+    /// represent any element even if they are lower than the modulus.
+    fn from_value(value: u128) -> Option<Self> {
+        u64::try_from(value).ok().map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::GF2k;
+    use crate::math::{permutation_exponent, FiniteField};
+
+    #[test]
+    fn characteristic_two() {
+        let mut rng = thread_rng();
+        let elem = GF2k::random(&mut rng);
+        assert_eq!(elem.add(&elem), GF2k::ZERO);
+        assert_eq!(elem.negate(), elem);
+        assert_eq!(elem.subtract(&GF2k::ONE), elem.add(&GF2k::ONE));
+        assert_eq!(elem.multiply(&GF2k::ONE), elem);
+        assert_eq!(GF2k::from(2).add(&GF2k::from(3)), GF2k::ONE);
+        assert_eq!(GF2k::from_value(GF2k::MODULUS - 1), None);
+        assert!(GF2k::ZERO.inverse().is_err());
+        assert_eq!(permutation_exponent::<GF2k>(), 7);
+    }
+
+    #[test]
+    fn inverse() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let elem = GF2k::random(&mut rng);
+            let s = elem.multiply(&elem.inverse().unwrap());
+            assert_eq!(s, GF2k::ONE);
+        }
+    }
+
+    #[test]
+    fn distributivity() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let [a, b, c] = [(); 3].map(|_| GF2k::random(&mut rng));
+            assert_eq!(a.multiply(&b.add(&c)), a.multiply(&b).add(&a.multiply(&c)));
+        }
+    }
+
+    #[test]
+    fn golden_vectors() {
+        // Computed independently with polynomials over GF(2) modulo x^64 + x^4 + x^3 + x + 1, as
+        // (a, b, a + b, a * b, 1 / a).
+        const VECTORS: [[u64; 5]; 4] = [
+            [
+                0x8000000000000000,
+                0x2,
+                0x8000000000000002,
+                0x1b,
+                0xe38e38e38e38e386,
+            ],
+            [
+                0xffffffffffffffff,
+                0xffffffffffffffff,
+                0x0,
+                0x5555555555555513,
+                0x1d3a74e9d3a74e9c,
+            ],
+            [
+                0x0123456789abcdef,
+                0xfedcba9876543210,
+                0xffffffffffffffff,
+                0x48827ab55d976fa0,
+                0x482870f8db3decda,
+            ],
+            [0x3, 0x7, 0x4, 0x9, 0xfffffffffffffff6],
+        ];
+        for [a, b, sum, product, inverse] in VECTORS {
+            let (a, b) = (GF2k::from(a), GF2k::from(b));
+            assert_eq!(a.add(&b), GF2k::from(sum));
+            assert_eq!(a.multiply(&b), GF2k::from(product));
+            assert_eq!(a.inverse().unwrap(), GF2k::from(inverse));
+        }
+    }
+}
@@ -1,25 +1,39 @@
-use super::FiniteField;
+use std::{
+    any::{Any, TypeId},
+    collections::BTreeMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use super::{
+    consecutive::{has_consecutive_points, lagrange_basis_at_zero, CONSECUTIVE_THRESHOLD},
+    FiniteField,
+};
 
 /// Computes the lagrange basis evaluated at `x`
 pub fn compute_lagrange_basis<T: FiniteField>(nodes: Vec<T>, x: &T) -> Vec<T> {
-    let mut lagrange_basis = Vec::with_capacity(nodes.len());
+    let mut numerators = Vec::with_capacity(nodes.len());
+    let mut denominators = Vec::with_capacity(nodes.len());
     for j in 0..nodes.len() {
-        let mut basis = T::ONE;
+        let mut numerator = T::ONE;
+        let mut denominator = T::ONE;
         let x_j = &nodes[j];
         for (m, node) in nodes.iter().enumerate() {
             if m != j {
                 let x_m = node;
-                let numerator = x.subtract(x_m);
-                let denominator = x_j.subtract(x_m);
-
-                // The unwrap is safe because x_j - x_m is not zero.
-                let term = numerator.multiply(&denominator.inverse().unwrap());
-                basis = basis.multiply(&term);
+                numerator = numerator.multiply(&x.subtract(x_m));
+                denominator = denominator.multiply(&x_j.subtract(x_m));
             }
         }
-        lagrange_basis.push(basis);
+        numerators.push(numerator);
+        denominators.push(denominator);
     }
-    lagrange_basis
+    // The denominators are inverted at once, since an inversion costs as much as many products.
+    // The unwrap is safe because x_j - x_m is not zero for any m, so neither is any denominator.
+    numerators
+        .iter()
+        .zip(T::batch_inverse(&denominators).unwrap())
+        .map(|(numerator, inverse)| numerator.multiply(&inverse))
+        .collect()
 }
 
 /// Computes the evaluation of the interpolated polynomial at `x`.
@@ -33,15 +47,145 @@ pub fn interpolate_polynomial_at<T: FiniteField>(evaluations: Vec<T>, alphas: Ve
     interpolation
 }
 
+/// Lagrange bases at zero, i.e. recombination vectors, that were already computed, by field and
+/// by evaluation points. The protocols of a session recombine the shares of the same parties
+/// again and again, so each basis costs its inversions once instead of once per multiplication
+/// or reconstruction.
+#[derive(Default)]
+pub struct RecombinationCache {
+    bases: BTreeMap<(TypeId, Vec<u64>), Arc<dyn Any + Send + Sync>>,
+}
+
+impl RecombinationCache {
+    /// Most bases kept at once. The cache is emptied when it is full, since a session uses a few
+    /// sets of points, and many more only appear when the parties that answer keep changing.
+    pub const CAPACITY: usize = 64;
+
+    /// Creates an empty cache.
+    pub const fn new() -> Self {
+        Self {
+            bases: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the Lagrange basis at zero for the given evaluation points, which must be distinct
+    /// and nonzero, computing it if it is not in the cache. The basis of the points `1, ..., n`
+    /// from [`CONSECUTIVE_THRESHOLD`] points on is computed in linear time in the prime fields.
+    pub fn basis_at_zero<T: FiniteField>(&mut self, points: &[u64]) -> Arc<Vec<T>> {
+        let key = (TypeId::of::<T>(), points.to_vec());
+        if let Some(basis) = self
+            .bases
+            .get(&key)
+            .and_then(|basis| basis.clone().downcast().ok())
+        {
+            return basis;
+        }
+        let basis = Arc::new(compute_basis_at_zero(points));
+        if self.bases.len() >= Self::CAPACITY {
+            self.bases.clear();
+        }
+        self.bases.insert(key, basis.clone());
+        basis
+    }
+
+    /// Returns the number of bases in the cache.
+    pub fn len(&self) -> usize {
+        self.bases.len()
+    }
+
+    /// Returns whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.bases.is_empty()
+    }
+
+    /// Removes every basis from the cache.
+    pub fn clear(&mut self) {
+        self.bases.clear();
+    }
+}
+
+/// Cache shared by the protocols of the whole process.
+static RECOMBINATION_CACHE: Mutex<RecombinationCache> = Mutex::new(RecombinationCache::new());
+
+/// Returns the Lagrange basis at zero for the given evaluation points from the cache of the
+/// process, see [`RecombinationCache::basis_at_zero`].
+pub fn recombination_vector<T: FiniteField>(points: &[u64]) -> Arc<Vec<T>> {
+    RECOMBINATION_CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .basis_at_zero(points)
+}
+
+/// Removes every basis from the cache of the process.
+pub fn clear_recombination_cache() {
+    RECOMBINATION_CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+/// Computes the Lagrange basis at zero for the given evaluation points.
+fn compute_basis_at_zero<T: FiniteField>(points: &[u64]) -> Vec<T> {
+    let consecutive = points.iter().zip(1..).all(|(point, idx)| *point == idx);
+    if consecutive && points.len() >= CONSECUTIVE_THRESHOLD && has_consecutive_points::<T>() {
+        return lagrange_basis_at_zero(points.len());
+    }
+    compute_lagrange_basis(
+        points.iter().map(|point| T::from(*point)).collect(),
+        &T::ZERO,
+    )
+}
+
 #[cfg(test)]
 mod tests {
 
-    use crate::{math::FiniteField, Mersenne61};
+    use std::sync::Arc;
+
+    use crate::math::{mersenne61::Mersenne61, FiniteField};
     use rand::{seq::SliceRandom, thread_rng, Rng};
 
     use crate::math::Polynomial;
 
-    use super::interpolate_polynomial_at;
+    use super::{compute_lagrange_basis, interpolate_polynomial_at, RecombinationCache};
+    use crate::math::mersenne127::Mersenne127;
+
+    #[test]
+    fn golden_recombination_vector() {
+        // The recombination vector of the shares of five parties is (5, -10, 10, -5, 1).
+        let nodes = (1..=5).map(Mersenne61::from).collect();
+        let expected = [5, Mersenne61::MODULUS - 10, 10, Mersenne61::MODULUS - 5, 1];
+        assert_eq!(
+            compute_lagrange_basis(nodes, &Mersenne61::ZERO),
+            expected.map(Mersenne61::from)
+        );
+    }
+
+    #[test]
+    fn bases_are_computed_once_per_field_and_points() {
+        let mut cache = RecombinationCache::new();
+        let first = cache.basis_at_zero::<Mersenne61>(&[1, 2, 3, 4, 5]);
+        assert!(Arc::ptr_eq(
+            &first,
+            &cache.basis_at_zero::<Mersenne61>(&[1, 2, 3, 4, 5])
+        ));
+        assert_eq!(
+            *cache.basis_at_zero::<Mersenne127>(&[1, 2, 3, 4, 5]),
+            compute_lagrange_basis((1..=5).map(Mersenne127::from).collect(), &Mersenne127::ZERO)
+        );
+        let nodes = [2, 5, 7].map(Mersenne61::from).to_vec();
+        assert_eq!(
+            *cache.basis_at_zero::<Mersenne61>(&[2, 5, 7]),
+            compute_lagrange_basis(nodes, &Mersenne61::ZERO)
+        );
+        assert_eq!(cache.len(), 3);
+
+        for point in 0..RecombinationCache::CAPACITY as u64 {
+            cache.basis_at_zero::<Mersenne61>(&[point + 1]);
+        }
+        assert!(cache.len() <= RecombinationCache::CAPACITY);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
 
     #[test]
     fn interpolation() {
@@ -0,0 +1,175 @@
+use serde::Deserialize;
+use serde::Serialize;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeLess};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::FieldError;
+use super::FiniteField;
+use super::PrimeField;
+
+/// Representation of a field element modulo 2^{127} - 1.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Mersenne127(u128);
+
+impl Mersenne127 {
+    /// Reduces a value lower than 2^{128} modulo 2^{127} - 1 without branching on the value.
+    fn reduce(value: u128) -> Self {
+        let folded = (value & Self::MODULUS) + (value >> Self::BIT_SIZE);
+        let reduced = folded.wrapping_sub(Self::MODULUS);
+        Self(u128::conditional_select(
+            &reduced,
+            &folded,
+            folded.ct_lt(&Self::MODULUS),
+        ))
+    }
+}
+
+impl ConstantTimeEq for Mersenne127 {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for Mersenne127 {
+    fn eq(&self, other: &Self) -> bool {
+        self.equal(other)
+    }
+}
+
+impl Eq for Mersenne127 {}
+
+impl Zeroize for Mersenne127 {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for Mersenne127 {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Mersenne127 {}
+
+impl From<u64> for Mersenne127 {
+    fn from(value: u64) -> Self {
+        Self(value as u128)
+    }
+}
+
+impl FiniteField for Mersenne127 {
+    type ValueType = u128;
+
+    const MODULUS: u128 = (1 << 127) - 1;
+    const BIT_SIZE: usize = 127;
+    const NAME: &'static str = "M127";
+    const ONE: Self = Self(1);
+    const ZERO: Self = Self(0);
+
+    fn add(&self, other: &Self) -> Self {
+        // The sum of two reduced elements is lower than 2^{128}.
+        Self::reduce(self.0 + other.0)
+    }
+
+    fn random<R: rand::Rng>(generator: &mut R) -> Self {
+        let value: u128 = generator.gen();
+        Self::reduce(value >> 1)
+    }
+
+    fn value(&self) -> Self::ValueType {
+        self.0
+    }
+
+    fn multiply(&self, other: &Self) -> Self {
+        let (high, low) = super::widening_mul(self.0, other.0);
+
+        // As 2^{127} = 1, the product is congruent to 2 * high + low, where the high half is
+        // lower than 2^{126} because both factors are lower than 2^{127}.
+        let folded = (high << 1) + (low >> Self::BIT_SIZE) + (low & Self::MODULUS);
+        Self::reduce(folded)
+    }
+
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self(u128::conditional_select(&a.0, &b.0, choice))
+    }
+
+    fn inverse(&self) -> Result<Self, FieldError> {
+        if self.equal(&Self::ZERO) {
+            Err(FieldError::ZeroInverse)
+        } else {
+            Ok(super::pow(self, Self::MODULUS - 2))
+        }
+    }
+
+    fn negate(&self) -> Self {
+        Self::ZERO.subtract(self)
+    }
+
+    fn subtract(&self, other: &Self) -> Self {
+        let difference = self.0.wrapping_sub(other.0);
+        let borrow = self.0.ct_lt(&other.0);
+        Self(u128::conditional_select(
+            &difference,
+            &difference.wrapping_add(Self::MODULUS),
+            borrow,
+        ))
+    }
+}
+
+impl PrimeField for Mersenne127 {}
+
+#[cfg(test)]
+mod tests {
+    use super::Mersenne127;
+    use crate::math::FiniteField;
+    use rand::thread_rng;
+
+    #[test]
+    fn zero_and_one() {
+        let mut rng = thread_rng();
+        let elem = Mersenne127::random(&mut rng);
+        assert_eq!(elem.add(&Mersenne127::ZERO), elem);
+        assert_eq!(elem.subtract(&elem), Mersenne127::ZERO);
+        assert_eq!(elem.add(&elem.negate()), Mersenne127::ZERO);
+        assert_eq!(elem.multiply(&Mersenne127::ONE), elem);
+    }
+
+    #[test]
+    fn inverse() {
+        let mut rng = thread_rng();
+        const SAMPLES: usize = 100;
+        for _ in 0..SAMPLES {
+            let elem = Mersenne127::random(&mut rng);
+            let s = elem.multiply(&elem.inverse().unwrap());
+            assert_eq!(s, Mersenne127::ONE);
+        }
+        assert!(Mersenne127::ZERO.inverse().is_err());
+    }
+
+    #[test]
+    fn reduce_large_values() {
+        let minus_one = Mersenne127::ONE.negate();
+        assert_eq!(minus_one.multiply(&minus_one), Mersenne127::ONE);
+        assert_eq!(minus_one.add(&Mersenne127::from(3)), Mersenne127::from(2));
+        assert_eq!(
+            Mersenne127::from(1).subtract(&Mersenne127::from(3)),
+            Mersenne127::from(2).negate()
+        );
+
+        // 2^{64} * 2^{64} = 2^{128} = 2.
+        let two_to_64 = Mersenne127(1 << 64);
+        assert_eq!(two_to_64.multiply(&two_to_64), Mersenne127::from(2));
+    }
+
+    #[test]
+    fn mult_conmutativity() {
+        const SAMPLES: usize = 50;
+        let mut rng = thread_rng();
+        for _ in 0..SAMPLES {
+            let a = Mersenne127::random(&mut rng);
+            let b = Mersenne127::random(&mut rng);
+            assert_eq!(a.multiply(&b), b.multiply(&a));
+        }
+    }
+}
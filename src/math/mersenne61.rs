@@ -1,20 +1,62 @@
 use serde::Deserialize;
 use serde::Serialize;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeLess};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use super::FieldError;
 use super::FiniteField;
+use super::PrimeField;
 
 /// Representation of a field element modulo 2^{61} - 1.
-#[derive(PartialEq, Eq, Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Mersenne61(u64);
 
+impl Mersenne61 {
+    /// Reduces a value lower than 2^{64} modulo 2^{61} - 1 without branching on the value.
+    fn reduce(value: u64) -> Self {
+        // As 2^{61} = 1, the value is congruent to the sum of its low 61 bits and the rest, which
+        // is lower than twice the modulus.
+        let folded = (value & Self::MODULUS) + (value >> Self::BIT_SIZE);
+        let reduced = folded.wrapping_sub(Self::MODULUS);
+        Self(u64::conditional_select(
+            &reduced,
+            &folded,
+            folded.ct_lt(&Self::MODULUS),
+        ))
+    }
+}
+
+impl ConstantTimeEq for Mersenne61 {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for Mersenne61 {
+    fn eq(&self, other: &Self) -> bool {
+        self.equal(other)
+    }
+}
+
+impl Eq for Mersenne61 {}
+
+impl Zeroize for Mersenne61 {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for Mersenne61 {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Mersenne61 {}
+
 impl From<u64> for Mersenne61 {
     fn from(value: u64) -> Self {
-        let mut final_value = value;
-        while final_value >= Self::MODULUS {
-            final_value -= Self::MODULUS;
-        }
-        Self(final_value)
+        Self::reduce(value)
     }
 }
 
@@ -23,12 +65,12 @@ impl FiniteField for Mersenne61 {
 
     const MODULUS: u64 = 0x1FFFFFFFFFFFFFFF;
     const BIT_SIZE: usize = 61;
+    const NAME: &'static str = "M61";
     const ONE: Self = Self(1);
     const ZERO: Self = Self(0);
 
     fn add(&self, other: &Self) -> Self {
-        let add_result = self.0 + other.0;
-        Self::from(add_result)
+        Self::reduce(self.0 + other.0)
     }
 
     fn random<R: rand::Rng>(generator: &mut R) -> Self {
@@ -36,6 +78,10 @@ impl FiniteField for Mersenne61 {
         Self::from(value)
     }
 
+    fn value(&self) -> Self::ValueType {
+        self.0
+    }
+
     fn multiply(&self, other: &Self) -> Self {
         let non_reduced_mult: u128 = (self.0 as u128) * (other.0 as u128);
         let mut most_sig_bits = (non_reduced_mult >> Self::BIT_SIZE) as u64;
@@ -50,57 +96,36 @@ impl FiniteField for Mersenne61 {
         most_sig_bits_mod.add(&least_sig_bits_mod)
     }
 
-    fn equal(&self, other: &Self) -> bool {
-        self == other
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self(u64::conditional_select(&a.0, &b.0, choice))
     }
 
     fn inverse(&self) -> Result<Self, super::FieldError> {
         if self.equal(&Self::ZERO) {
             Err(FieldError::ZeroInverse)
         } else {
-            let mut k: i64 = 0;
-            let mut new_k: i64 = 1;
-            let mut r: i64 = Self::MODULUS as i64;
-            let mut new_r: i64 = self.0 as i64;
-
-            while new_r != 0 {
-                let q = r / new_r;
-                assign(&mut k, &mut new_k, q);
-                assign(&mut r, &mut new_r, q);
-            }
-
-            if k < 0 {
-                k += Self::MODULUS as i64;
-            }
-
-            Ok(Self::from(k as u64))
+            // By Fermat's little theorem, which takes the same steps for every element unlike the
+            // extended Euclidean algorithm.
+            Ok(super::pow(self, (Self::MODULUS - 2) as u128))
         }
     }
 
     fn negate(&self) -> Self {
-        if !self.equal(&Self::ZERO) {
-            Self::from(Self::MODULUS - self.0)
-        } else {
-            Self::ZERO
-        }
+        Self::ZERO.subtract(self)
     }
 
     fn subtract(&self, other: &Self) -> Self {
-        if other.0 > self.0 {
-            Self::from(self.0 + Self::MODULUS - other.0)
-        } else {
-            Self::from(self.0 - other.0)
-        }
+        let difference = self.0.wrapping_sub(other.0);
+        let borrow = self.0.ct_lt(&other.0);
+        Self(u64::conditional_select(
+            &difference,
+            &difference.wrapping_add(Self::MODULUS),
+            borrow,
+        ))
     }
 }
 
-/// Given v1, v2 and a constant q, computes the multiplicative exchange
-/// v1 <- v2 and v2 <- v1 - q * v2.
-fn assign(v1: &mut i64, v2: &mut i64, q: i64) {
-    let temp = *v2;
-    *v2 = *v1 - q * temp;
-    *v1 = temp;
-}
+impl PrimeField for Mersenne61 {}
 
 #[cfg(test)]
 mod tests {
@@ -165,6 +190,80 @@ mod tests {
         assert_eq!(s, r);
     }
 
+    #[test]
+    fn golden_vectors() {
+        // Computed independently with arbitrary-precision integers modulo 2^61 - 1, as
+        // (a, b, a + b, a - b, a * b, -a, 1 / a).
+        const VECTORS: [[u64; 7]; 6] = [
+            [
+                0x1,
+                0x1ffffffffffffffe,
+                0x0,
+                0x2,
+                0x1ffffffffffffffe,
+                0x1ffffffffffffffe,
+                0x1,
+            ],
+            [
+                0x1ffffffffffffffe,
+                0x1ffffffffffffffe,
+                0x1ffffffffffffffd,
+                0x0,
+                0x1,
+                0x1,
+                0x1ffffffffffffffe,
+            ],
+            [
+                0x1000000000000000,
+                0x1000000000000000,
+                0x1,
+                0x0,
+                0x800000000000000,
+                0xfffffffffffffff,
+                0x2,
+            ],
+            [
+                0x123456789abcdef,
+                0xfedcba987654321,
+                0x1111111111111110,
+                0x113579be02468acd,
+                0x2b46a8955120470,
+                0x1edcba9876543210,
+                0x1220f61c9107b0e3,
+            ],
+            [
+                0x1d5a5b2c3f4e6a71,
+                0x3,
+                0x1d5a5b2c3f4e6a74,
+                0x1d5a5b2c3f4e6a6e,
+                0x180f1184bdeb3f55,
+                0x2a5a4d3c0b1958e,
+                0xe2102c441616e62,
+            ],
+            [
+                0x5,
+                0x7,
+                0xc,
+                0x1ffffffffffffffd,
+                0x23,
+                0x1ffffffffffffffa,
+                0x1999999999999999,
+            ],
+        ];
+
+        for [a, b, sum, difference, product, negation, inverse] in
+            VECTORS.map(|vector| vector.map(Mersenne61::from))
+        {
+            assert_eq!(a.add(&b), sum);
+            assert_eq!(a.subtract(&b), difference);
+            assert_eq!(a.multiply(&b), product);
+            assert_eq!(a.negate(), negation);
+            assert_eq!(a.inverse().unwrap(), inverse);
+        }
+        assert_eq!(Mersenne61::from(u64::MAX), Mersenne61::from(7));
+        assert_eq!(Mersenne61::from(Mersenne61::MODULUS), Mersenne61::ZERO);
+    }
+
     #[test]
     fn mult_conmutativity() {
         const SAMPLES: usize = 50;
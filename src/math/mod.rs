@@ -1,34 +1,135 @@
 use std::{
-    fmt::Debug,
+    fmt::{Debug, Display},
     ops::{Index, IndexMut},
 };
 
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
 use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
+use crate::memory::SecretVec;
+
+/// Fast evaluation and interpolation of polynomials at the consecutive points of the parties.
+pub mod consecutive;
+/// Elements of a field chosen at runtime and dispatch of generic code to its type.
+pub mod dynamic;
+/// Fixed-point encoding of decimal numbers as field elements.
+pub mod fixed_point;
+/// Binary extension field GF(2^64).
+pub mod gf2k;
+/// Lagrange interpolation of polynomials.
 pub mod lagrange;
+/// Field of integers modulo the Mersenne prime 2^127 - 1.
+pub mod mersenne127;
+/// Field of integers modulo the Mersenne prime 2^61 - 1.
 pub mod mersenne61;
+/// Prime fields of any size in Montgomery form, defined by their modulus.
+pub mod montgomery;
+/// Field of integers modulo the prime 2^128 - 159.
+pub mod p128;
+/// Unsigned integers of a fixed number of 64-bit limbs.
+pub mod uint;
 
+/// Possible errors that may appear in the operations of a finite field.
 #[derive(Error, Debug)]
 pub enum FieldError {
+    /// The element zero does not have a multiplicative inverse.
     #[error("trying to compute the inverse of zero")]
     ZeroInverse,
 }
 
-/// Trait that represent a finite field of integers modulo a prime p.
+/// Integer that represents the elements of a field, which is a primitive integer for the fields
+/// of up to 128 bits and takes several 64-bit limbs for the larger ones.
+pub trait FieldValue: Copy + Debug + Display + Ord + Send + Sync + 'static {
+    /// Number of bytes of the integer.
+    const BYTES: usize;
+
+    /// Writes the integer in little-endian order, truncated or padded with zeros to the length of
+    /// the buffer.
+    fn write_le_bytes(&self, bytes: &mut [u8]);
+
+    /// Reads an integer in little-endian order, or returns `None` if it does not fit.
+    fn read_le_bytes(bytes: &[u8]) -> Option<Self>;
+
+    /// Returns the integer if it fits in 128 bits.
+    fn to_u128(&self) -> Option<u128>;
+
+    /// Returns the remainder of the division by a nonzero divisor.
+    fn rem_u64(&self, divisor: u64) -> u64;
+}
+
+/// Implements [`FieldValue`] for primitive unsigned integers.
+macro_rules! impl_field_value {
+    ($($int:ty),*) => {
+        $(
+            impl FieldValue for $int {
+                const BYTES: usize = std::mem::size_of::<$int>();
+
+                fn write_le_bytes(&self, bytes: &mut [u8]) {
+                    let len = bytes.len().min(Self::BYTES);
+                    bytes[..len].copy_from_slice(&self.to_le_bytes()[..len]);
+                    bytes[len..].fill(0);
+                }
+
+                fn read_le_bytes(bytes: &[u8]) -> Option<Self> {
+                    let (low, high) = bytes.split_at(bytes.len().min(Self::BYTES));
+                    if high.iter().any(|byte| *byte != 0) {
+                        return None;
+                    }
+                    let mut value = [0; std::mem::size_of::<$int>()];
+                    value[..low.len()].copy_from_slice(low);
+                    Some(<$int>::from_le_bytes(value))
+                }
+
+                fn to_u128(&self) -> Option<u128> {
+                    u128::try_from(*self).ok()
+                }
+
+                fn rem_u64(&self, divisor: u64) -> u64 {
+                    (*self % <$int>::from(divisor)) as u64
+                }
+            }
+        )*
+    };
+}
+
+impl_field_value!(u64, u128);
+
+/// Trait that represent a finite field, either the integers modulo a prime p, which implement
+/// [`PrimeField`] too, or a binary extension field such as [`gf2k::GF2k`]. Every element is
+/// represented by an integer lower than [`FiniteField::MODULUS`], which for the extension fields
+/// is the integer of the irreducible polynomial. The elements are wiped from memory when they are
+/// dropped, since they may be secrets or shares of secrets, and the arithmetic, the comparisons
+/// and the selections run in constant time, except the inverse of zero, which is an error.
 pub trait FiniteField:
-    Debug + Sized + Clone + From<u64> + Serialize + for<'a> Deserialize<'a>
+    Debug
+    + Send
+    + Sync
+    + Sized
+    + Clone
+    + From<u64>
+    + Serialize
+    + for<'a> Deserialize<'a>
+    + ConstantTimeEq
+    + Zeroize
+    + ZeroizeOnDrop
+    + 'static
 {
     /// Type of the underlying representation for a field element.
-    type ValueType;
+    type ValueType: FieldValue;
 
     /// Modulus used in for the field.
-    const MODULUS: u64;
+    const MODULUS: Self::ValueType;
 
     /// Bit size of the elements in the field.
     const BIT_SIZE: usize;
 
+    /// Short name of the field, e.g. `M61`, which identifies it in the headers of the values sent
+    /// through the network. It has at most four ASCII characters.
+    const NAME: &'static str;
+
     /// Additive identity of the field.
     const ZERO: Self;
 
@@ -44,8 +145,39 @@ pub trait FiniteField:
     /// Computes the inverse of field element.
     fn inverse(&self) -> Result<Self, FieldError>;
 
-    /// Compares equality between two field elements.
-    fn equal(&self, other: &Self) -> bool;
+    /// Computes the inverses of several elements with a single inversion by Montgomery's trick:
+    /// the inverse of the product of all the elements is multiplied by the products of the
+    /// others, which costs three products per element. It fails if some element is zero.
+    fn batch_inverse(elements: &[Self]) -> Result<Vec<Self>, FieldError> {
+        // The partial products are derived from the elements, so they are wiped when dropped.
+        let mut prefixes = Zeroizing::new(Vec::with_capacity(elements.len()));
+        let mut product = Self::ONE;
+        for element in elements {
+            prefixes.push(product.clone());
+            product = product.multiply(element);
+        }
+        let mut inverse = product.inverse()?;
+        let mut inverses = vec![Self::ZERO; elements.len()];
+        for (k, element) in elements.iter().enumerate().rev() {
+            inverses[k] = inverse.multiply(&prefixes[k]);
+            inverse = inverse.multiply(element);
+        }
+        Ok(inverses)
+    }
+
+    /// Raises the element to the given exponent by square and multiply. The time depends on the
+    /// exponent, which must be public, but not on the element.
+    fn pow(&self, exponent: u128) -> Self {
+        pow(self, exponent)
+    }
+
+    /// Compares equality between two field elements in constant time.
+    fn equal(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+
+    /// Returns `a` if the choice is zero and `b` if it is one, in constant time.
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self;
 
     /// Computes the additive inverse of a field element.
     fn negate(&self) -> Self;
@@ -55,11 +187,216 @@ pub trait FiniteField:
 
     /// Generates a random finite field element with a provided pseudo-random generator.
     fn random<R: Rng>(generator: &mut R) -> Self;
+
+    /// Returns the integer in `[0, p)` that represents the element.
+    fn value(&self) -> Self::ValueType;
+
+    /// Returns the remainder of the division of the number of nonzero elements of the field, i.e.
+    /// the order of its multiplicative group, by a nonzero divisor. It is `(p - 1) mod divisor`
+    /// for the prime fields.
+    fn group_order_rem(divisor: u64) -> u64 {
+        (Self::MODULUS.rem_u64(divisor) + divisor - 1) % divisor
+    }
+
+    /// Returns the element represented by an integer, or `None` if it is not lower than the
+    /// modulus.
+    fn from_value(value: Self::ValueType) -> Option<Self> {
+        if value >= Self::MODULUS {
+            return None;
+        }
+        let mut bytes = Zeroizing::new(vec![0; Self::ValueType::BYTES]);
+        value.write_le_bytes(&mut bytes);
+        Some(from_le_bytes(&bytes))
+    }
+}
+
+/// Marker of the fields of integers modulo a prime p, whose elements are the integers in
+/// `[0, p)` with their arithmetic. The encodings that treat the elements as integers, such as the
+/// fixed-point numbers, the comparisons of [`crate::mpc::compare`] and the files of MP-SPDZ, are
+/// only defined for them, so the binary extension fields are rejected at compile time:
+///
+/// ```compile_fail
+/// use shami_rs::math::{fixed_point::Decimal, gf2k::GF2k};
+///
+/// let value: GF2k = "1.5".parse::<Decimal>().unwrap().encode(8).unwrap();
+/// ```
+pub trait PrimeField: FiniteField {}
+
+/// Implements the arithmetic operators and their assigning forms for fields, by value and by
+/// reference, on top of the methods of [`FiniteField`]. The generics of the type go in brackets.
+macro_rules! impl_field_ops {
+    ($([$($generics:tt)*] $field:ty),* $(,)?) => {
+        $(
+            impl<$($generics)*> std::ops::Add for $field {
+                type Output = Self;
+
+                fn add(self, other: Self) -> Self {
+                    FiniteField::add(&self, &other)
+                }
+            }
+
+            impl<$($generics)*> std::ops::Add<&$field> for &$field {
+                type Output = $field;
+
+                fn add(self, other: &$field) -> $field {
+                    FiniteField::add(self, other)
+                }
+            }
+
+            impl<$($generics)*> std::ops::Sub for $field {
+                type Output = Self;
+
+                fn sub(self, other: Self) -> Self {
+                    FiniteField::subtract(&self, &other)
+                }
+            }
+
+            impl<$($generics)*> std::ops::Sub<&$field> for &$field {
+                type Output = $field;
+
+                fn sub(self, other: &$field) -> $field {
+                    FiniteField::subtract(self, other)
+                }
+            }
+
+            impl<$($generics)*> std::ops::Mul for $field {
+                type Output = Self;
+
+                fn mul(self, other: Self) -> Self {
+                    FiniteField::multiply(&self, &other)
+                }
+            }
+
+            impl<$($generics)*> std::ops::Mul<&$field> for &$field {
+                type Output = $field;
+
+                fn mul(self, other: &$field) -> $field {
+                    FiniteField::multiply(self, other)
+                }
+            }
+
+            impl<$($generics)*> std::ops::Neg for $field {
+                type Output = Self;
+
+                fn neg(self) -> Self {
+                    FiniteField::negate(&self)
+                }
+            }
+
+            impl<$($generics)*> std::ops::Neg for &$field {
+                type Output = $field;
+
+                fn neg(self) -> $field {
+                    FiniteField::negate(self)
+                }
+            }
+
+            impl<$($generics)*> std::ops::AddAssign<&$field> for $field {
+                fn add_assign(&mut self, other: &$field) {
+                    *self = FiniteField::add(self, other);
+                }
+            }
+
+            impl<$($generics)*> std::ops::SubAssign<&$field> for $field {
+                fn sub_assign(&mut self, other: &$field) {
+                    *self = FiniteField::subtract(self, other);
+                }
+            }
+
+            impl<$($generics)*> std::ops::MulAssign<&$field> for $field {
+                fn mul_assign(&mut self, other: &$field) {
+                    *self = FiniteField::multiply(self, other);
+                }
+            }
+        )*
+    };
+}
+
+impl_field_ops!(
+    [] gf2k::GF2k,
+    [] mersenne127::Mersenne127,
+    [] mersenne61::Mersenne61,
+    [P: montgomery::PrimeModulus<L>, const L: usize] montgomery::Montgomery<P, L>,
+    [] p128::P128,
+);
+
+/// Builds the field element of an integer of any size given in little-endian order, which is
+/// reduced modulo p. The integer is taken in 64-bit limbs from the most significant one.
+pub(crate) fn from_le_bytes<T: FiniteField>(bytes: &[u8]) -> T {
+    let two_to_32 = T::from(1 << 32);
+    let two_to_64 = two_to_32.multiply(&two_to_32);
+    bytes.chunks(8).rev().fold(T::ZERO, |value, chunk| {
+        let mut limb = Zeroizing::new([0; 8]);
+        limb[..chunk.len()].copy_from_slice(chunk);
+        value
+            .multiply(&two_to_64)
+            .add(&T::from(u64::from_le_bytes(*limb)))
+    })
+}
+
+/// Builds the field element of an integer of up to 128 bits, which is reduced modulo p. The
+/// field elements are built from 64-bit integers, so the integer is split in halves.
+pub(crate) fn from_u128<T: FiniteField>(value: u128) -> T {
+    let two_to_32 = T::from(1 << 32);
+    T::from((value >> 64) as u64)
+        .multiply(&two_to_32.multiply(&two_to_32))
+        .add(&T::from(value as u64))
+}
+
+/// Computes the full product of two 128-bit integers, which is returned as its high and low
+/// halves.
+pub(crate) fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    let (a_high, a_low) = (a >> 64, a as u64 as u128);
+    let (b_high, b_low) = (b >> 64, b as u64 as u128);
+    let low_low = a_low * b_low;
+    let low_high = a_low * b_high;
+    let high_low = a_high * b_low;
+    let high_high = a_high * b_high;
+
+    // The middle terms are added with the carry of the low term, which fits in 66 bits.
+    let middle = (low_low >> 64) + (low_high as u64 as u128) + (high_low as u64 as u128);
+    let low = (middle << 64) | (low_low as u64 as u128);
+    let high = high_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+    (high, low)
+}
+
+/// Raises a field element to the given exponent by square and multiply.
+pub(crate) fn pow<T: FiniteField>(base: &T, mut exponent: u128) -> T {
+    let mut result = T::ONE;
+    let mut square = base.clone();
+    while exponent != 0 {
+        if exponent & 1 == 1 {
+            result = result.multiply(&square);
+        }
+        square = square.multiply(&square);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Returns the smallest odd exponent above one that is coprime with the order of the
+/// multiplicative group, e.g. `p - 1`, for which raising to the power is a permutation of the
+/// field, as needed by the S-boxes of MiMC and Poseidon.
+pub(crate) fn permutation_exponent<T: FiniteField>() -> u128 {
+    (3..)
+        .step_by(2)
+        // The exponent is coprime with the order if it is coprime with its remainder.
+        .find(|exponent| gcd(*exponent, T::group_order_rem(*exponent)) == 1)
+        .map(u128::from)
+        .expect("there are odd exponents coprime with p - 1")
+}
+
+/// Returns the greatest common divisor of two integers.
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
 }
 
 /// Represents a polynomial whose coefficients are elements in a finite field.
 #[derive(PartialEq, Eq, Debug)]
-pub struct Polynomial<T: FiniteField>(Vec<T>);
+pub struct Polynomial<T: FiniteField>(SecretVec<T>);
 
 impl<T: FiniteField> Polynomial<T> {
     /// Evaluates the polynomial on a give value.
@@ -71,13 +408,33 @@ impl<T: FiniteField> Polynomial<T> {
         result
     }
 
+    /// Evaluates the polynomial on a value given its powers, where `powers[j]` is the value to
+    /// the power `j`, e.g. to evaluate many polynomials on the same value with the powers computed
+    /// once. There must be a power for every coefficient.
+    pub fn evaluate_with_powers(&self, powers: &[T]) -> T {
+        self.0
+            .iter()
+            .zip(powers)
+            .fold(T::ZERO, |result, (coeff, power)| {
+                result.add(&coeff.multiply(power))
+            })
+    }
+
     /// Generates a random polynomial of a given degree using a given pseudo-random generator.
     pub fn random<R: Rng>(degree: usize, rng: &mut R) -> Self {
         let mut coefficients = Vec::with_capacity(degree + 1);
         for _ in 0..degree + 1 {
             coefficients.push(T::random(rng));
         }
-        Self(coefficients)
+        Self(coefficients.into())
+    }
+}
+
+impl<T: FiniteField> Drop for Polynomial<T> {
+    /// Wipes the coefficients, including the spare capacity of the vector, since the constant
+    /// coefficient of a sharing polynomial is the secret.
+    fn drop(&mut self) {
+        self.0.zeroize();
     }
 }
 
@@ -97,6 +454,115 @@ impl<T: FiniteField> IndexMut<usize> for Polynomial<T> {
 
 impl<const N: usize, T: FiniteField> From<[T; N]> for Polynomial<T> {
     fn from(coefficients: [T; N]) -> Self {
-        Self(Vec::from_iter(coefficients))
+        Self(Vec::from_iter(coefficients).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use subtle::Choice;
+
+    use super::{
+        mersenne127::Mersenne127, mersenne61::Mersenne61, p128::P128, FieldError, FiniteField,
+        Polynomial,
+    };
+    use crate::canary::{freed_copies, CANARY};
+
+    #[test]
+    fn golden_polynomial_evaluations() {
+        // Computed independently with arbitrary-precision integers modulo 2^61 - 1.
+        let polynomial: Polynomial<Mersenne61> = Polynomial::from(
+            [3, 0xfedcba987654321, Mersenne61::MODULUS - 5, 42].map(Mersenne61::from),
+        );
+        let evaluations = [
+            (0x0, 0x3),
+            (0x1, 0xfedcba987654349),
+            (0x2, 0x1fdb97530eca8781),
+            (0x1ffffffffffffffe, 0x10123456789abcb2),
+            (0x123456789abcdef, 0x2ddef24f9da8c63),
+        ];
+        for (point, evaluation) in evaluations {
+            assert_eq!(
+                polynomial.evaluate(&Mersenne61::from(point)),
+                Mersenne61::from(evaluation)
+            );
+        }
+    }
+
+    #[test]
+    fn batch_inversion_matches_single_inversions() {
+        let mut rng = thread_rng();
+        let elements: Vec<Mersenne127> = (0..10).map(|_| Mersenne127::random(&mut rng)).collect();
+        let inverses = Mersenne127::batch_inverse(&elements).unwrap();
+        for (element, inverse) in elements.iter().zip(&inverses) {
+            assert_eq!(inverse, &element.inverse().unwrap());
+        }
+        assert!(Mersenne127::batch_inverse(&[]).unwrap().is_empty());
+        let with_zero = [Mersenne61::ONE, Mersenne61::ZERO];
+        assert!(matches!(
+            Mersenne61::batch_inverse(&with_zero),
+            Err(FieldError::ZeroInverse)
+        ));
+
+        let two = Mersenne61::from(2);
+        assert_eq!(two.pow(0), Mersenne61::ONE);
+        assert_eq!(two.pow(61), Mersenne61::ONE);
+        assert_eq!(two.pow(10), Mersenne61::from(1024));
+    }
+
+    #[test]
+    fn operators_match_the_field_methods() {
+        let mut rng = thread_rng();
+        let (a, b) = (P128::random(&mut rng), P128::random(&mut rng));
+        assert_eq!(&a + &b, a.add(&b));
+        assert_eq!(&a - &b, a.subtract(&b));
+        assert_eq!(&a * &b, a.multiply(&b));
+        assert_eq!(-&a, a.negate());
+        assert_eq!(
+            a.clone() * b.clone() - b.clone(),
+            a.multiply(&b).subtract(&b)
+        );
+
+        let mut c = a.clone();
+        c += &b;
+        c *= &b;
+        c -= &a;
+        assert_eq!(c, a.add(&b).multiply(&b).subtract(&a));
+    }
+
+    #[test]
+    fn coefficients_are_wiped_when_dropped() {
+        let leaked = freed_copies(CANARY, || {
+            let mut polynomial: Polynomial<Mersenne61> = Polynomial::random(3, &mut thread_rng());
+            polynomial[0] = Mersenne61::from(CANARY);
+            drop(polynomial);
+            drop(vec![Mersenne61::from(CANARY); 4]);
+        });
+        assert_eq!(leaked, 0);
+    }
+
+    fn check_constant_time_operations<T: FiniteField + PartialEq>() {
+        let (two, three) = (T::from(2), T::from(3));
+        assert!(bool::from(two.ct_eq(&T::from(2))));
+        assert!(!bool::from(two.ct_eq(&three)));
+        assert_eq!(T::conditional_select(&two, &three, Choice::from(0)), two);
+        assert_eq!(T::conditional_select(&two, &three, Choice::from(1)), three);
+        assert_eq!(T::ZERO.negate(), T::ZERO);
+        assert_eq!(two.subtract(&three), T::ONE.negate());
+        assert_eq!(three.subtract(&two), T::ONE);
+        assert_eq!(two.negate().add(&three), T::ONE);
+    }
+
+    #[test]
+    fn constant_time_operations() {
+        check_constant_time_operations::<Mersenne61>();
+        check_constant_time_operations::<Mersenne127>();
+        check_constant_time_operations::<P128>();
+        assert_eq!(
+            Mersenne61::from(u64::MAX),
+            Mersenne61::from(u64::MAX % Mersenne61::MODULUS)
+        );
     }
 }
@@ -0,0 +1,427 @@
+use std::{fmt, marker::PhantomData};
+
+use rand::Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::{uint::Uint, FieldError, FiniteField, PrimeField};
+
+/// Prime modulus of a [`Montgomery`] field of `L` limbs.
+pub trait PrimeModulus<const L: usize>: Send + Sync + 'static {
+    /// Prime modulus of the field, which must be odd.
+    const MODULUS: Uint<L>;
+
+    /// Short name of the field, as in [`FiniteField::NAME`].
+    const NAME: &'static str;
+}
+
+/// Representation of an element of the field of integers modulo the prime of `P`, which takes `L`
+/// 64-bit limbs, e.g. a safe prime of 256 bits for which the protocols need a larger field than
+/// the ones of the crate. The element `a` is kept in Montgomery form, as `a * R mod p` with
+/// `R = 2^{64 L}`, so that the products are reduced with multiplications and shifts instead of
+/// divisions. The arithmetic is slower than the one of the fields specialized for their prime,
+/// e.g. [`super::p128::P128`], but takes the same steps for every element.
+///
+/// The field is defined by a type that implements [`PrimeModulus`]:
+///
+/// ```
+/// use shami_rs::math::{montgomery::{Montgomery, PrimeModulus}, uint::Uint};
+///
+/// /// The prime 2^{255} - 19.
+/// struct P25519;
+///
+/// impl PrimeModulus<4> for P25519 {
+///     const MODULUS: Uint<4> = Uint([u64::MAX - 18, u64::MAX, u64::MAX, u64::MAX >> 1]);
+///     const NAME: &'static str = "P255";
+/// }
+///
+/// type F25519 = Montgomery<P25519, 4>;
+/// ```
+pub struct Montgomery<P, const L: usize> {
+    /// Limbs of the element in Montgomery form, lower than the modulus.
+    limbs: [u64; L],
+    /// Modulus of the field.
+    modulus: PhantomData<fn() -> P>,
+}
+
+/// Adds two integers of `L` limbs, and returns the sum and whether it overflows.
+const fn add<const L: usize>(a: &[u64; L], b: &[u64; L]) -> ([u64; L], bool) {
+    let mut sum = [0; L];
+    let mut carry = false;
+    let mut i = 0;
+    while i < L {
+        let (partial, first) = a[i].overflowing_add(b[i]);
+        let (partial, second) = partial.overflowing_add(carry as u64);
+        sum[i] = partial;
+        carry = first | second;
+        i += 1;
+    }
+    (sum, carry)
+}
+
+/// Subtracts two integers of `L` limbs, and returns the difference and whether it borrows.
+const fn sub<const L: usize>(a: &[u64; L], b: &[u64; L]) -> ([u64; L], bool) {
+    let mut difference = [0; L];
+    let mut borrow = false;
+    let mut i = 0;
+    while i < L {
+        let (partial, first) = a[i].overflowing_sub(b[i]);
+        let (partial, second) = partial.overflowing_sub(borrow as u64);
+        difference[i] = partial;
+        borrow = first | second;
+        i += 1;
+    }
+    (difference, borrow)
+}
+
+/// Computes `2^exponent mod p` by doubling, which is only run when the constants of a field are
+/// evaluated.
+const fn pow2_mod<const L: usize>(modulus: &[u64; L], exponent: usize) -> [u64; L] {
+    let mut value = [0; L];
+    value[0] = 1;
+    let mut i = 0;
+    while i < exponent {
+        let (doubled, carry) = add(&value, &value);
+        let (reduced, borrow) = sub(&doubled, modulus);
+        value = if carry || !borrow { reduced } else { doubled };
+        i += 1;
+    }
+    value
+}
+
+/// Selects `a` if the choice is zero and `b` if it is one, in constant time.
+fn select<const L: usize>(a: &[u64; L], b: &[u64; L], choice: Choice) -> [u64; L] {
+    std::array::from_fn(|i| u64::conditional_select(&a[i], &b[i], choice))
+}
+
+/// Computes `a + b * c + carry`, and returns its low and high limbs.
+fn mac(a: u64, b: u64, c: u64, carry: u64) -> (u64, u64) {
+    let value = u128::from(a) + u128::from(b) * u128::from(c) + u128::from(carry);
+    (value as u64, (value >> 64) as u64)
+}
+
+impl<P: PrimeModulus<L>, const L: usize> Montgomery<P, L> {
+    /// Inverse of `-p` modulo 2^{64}, computed by Newton's iteration, which doubles the correct
+    /// bits of the inverse of an odd number at every step.
+    const INVERSE: u64 = {
+        let low = P::MODULUS.0[0];
+        assert!(
+            low & 1 == 1,
+            "the modulus of a Montgomery field must be odd"
+        );
+        let mut inverse: u64 = 1;
+        let mut i = 0;
+        while i < 6 {
+            inverse = inverse.wrapping_mul(2u64.wrapping_sub(low.wrapping_mul(inverse)));
+            i += 1;
+        }
+        inverse.wrapping_neg()
+    };
+
+    /// `R mod p`, which is the element one in Montgomery form.
+    const R: [u64; L] = pow2_mod(&P::MODULUS.0, 64 * L);
+
+    /// `R^2 mod p`, by which an integer is multiplied to take it to Montgomery form.
+    const R2: [u64; L] = pow2_mod(&P::MODULUS.0, 128 * L);
+
+    /// Builds an element from its limbs in Montgomery form.
+    const fn from_limbs(limbs: [u64; L]) -> Self {
+        Self {
+            limbs,
+            modulus: PhantomData,
+        }
+    }
+
+    /// Computes the Montgomery product `a * b / R mod p` by coarsely integrated operand scanning,
+    /// which interleaves the multiplication by every limb of `b` with the reduction of the lowest
+    /// limb. The result is reduced for any `a` lower than `R` if `b` is lower than the modulus.
+    fn montgomery_product(a: &[u64; L], b: &[u64; L]) -> [u64; L] {
+        let modulus = &P::MODULUS.0;
+        let mut t = [0; L];
+        let mut high = 0u64;
+        for b_i in b {
+            let mut carry = 0;
+            for j in 0..L {
+                (t[j], carry) = mac(t[j], a[j], *b_i, carry);
+            }
+            let (sum, overflow) = high.overflowing_add(carry);
+            let m = t[0].wrapping_mul(Self::INVERSE);
+            let (_, mut carry) = mac(t[0], m, modulus[0], 0);
+            for j in 1..L {
+                (t[j - 1], carry) = mac(t[j], m, modulus[j], carry);
+            }
+            let (sum, last) = sum.overflowing_add(carry);
+            t[L - 1] = sum;
+            high = u64::from(overflow) + u64::from(last);
+        }
+        let (reduced, borrow) = sub(&t, modulus);
+        let keep = Choice::from(u8::from(high == 0)) & Choice::from(u8::from(borrow));
+        select(&reduced, &t, keep)
+    }
+}
+
+impl<P, const L: usize> Clone for Montgomery<P, L> {
+    fn clone(&self) -> Self {
+        Self {
+            limbs: self.limbs,
+            modulus: PhantomData,
+        }
+    }
+}
+
+impl<P: PrimeModulus<L>, const L: usize> fmt::Debug for Montgomery<P, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", P::NAME, self.value())
+    }
+}
+
+impl<P, const L: usize> ConstantTimeEq for Montgomery<P, L> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.limbs[..].ct_eq(&other.limbs[..])
+    }
+}
+
+impl<P: PrimeModulus<L>, const L: usize> PartialEq for Montgomery<P, L> {
+    fn eq(&self, other: &Self) -> bool {
+        self.equal(other)
+    }
+}
+
+impl<P: PrimeModulus<L>, const L: usize> Eq for Montgomery<P, L> {}
+
+impl<P, const L: usize> Zeroize for Montgomery<P, L> {
+    fn zeroize(&mut self) {
+        self.limbs.zeroize();
+    }
+}
+
+impl<P, const L: usize> Drop for Montgomery<P, L> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<P, const L: usize> ZeroizeOnDrop for Montgomery<P, L> {}
+
+impl<P: PrimeModulus<L>, const L: usize> From<u64> for Montgomery<P, L> {
+    fn from(value: u64) -> Self {
+        Self::from_limbs(Self::montgomery_product(&Uint::from(value).0, &Self::R2))
+    }
+}
+
+impl<P: PrimeModulus<L>, const L: usize> Serialize for Montgomery<P, L> {
+    /// Serializes the integer that represents the element rather than its Montgomery form.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value().serialize(serializer)
+    }
+}
+
+impl<'de, P: PrimeModulus<L>, const L: usize> Deserialize<'de> for Montgomery<P, L> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Uint::deserialize(deserializer)?;
+        Self::from_value(value)
+            .ok_or_else(|| serde::de::Error::custom("the element is not reduced"))
+    }
+}
+
+impl<P: PrimeModulus<L>, const L: usize> FiniteField for Montgomery<P, L> {
+    type ValueType = Uint<L>;
+
+    const MODULUS: Uint<L> = P::MODULUS;
+    const BIT_SIZE: usize = P::MODULUS.bits();
+    const NAME: &'static str = P::NAME;
+    const ONE: Self = Self::from_limbs(Self::R);
+    const ZERO: Self = Self::from_limbs([0; L]);
+
+    fn add(&self, other: &Self) -> Self {
+        let (sum, carry) = add(&self.limbs, &other.limbs);
+        let (reduced, borrow) = sub(&sum, &P::MODULUS.0);
+        let keep = Choice::from(u8::from(!carry)) & Choice::from(u8::from(borrow));
+        Self::from_limbs(select(&reduced, &sum, keep))
+    }
+
+    fn random<R: Rng>(generator: &mut R) -> Self {
+        // The integers of the bit size of the modulus are sampled until one is lower than it,
+        // which takes two samples at most on average.
+        let mut limbs = [0; L];
+        loop {
+            generator.fill(&mut limbs[..]);
+            for (i, limb) in limbs.iter_mut().enumerate() {
+                let bits = Self::BIT_SIZE.saturating_sub(64 * i).min(64) as u32;
+                *limb &= u64::MAX.checked_shr(64 - bits).unwrap_or(0);
+            }
+            if let Some(element) = Self::from_value(Uint(limbs)) {
+                limbs.zeroize();
+                return element;
+            }
+        }
+    }
+
+    fn value(&self) -> Uint<L> {
+        Uint(Self::montgomery_product(&self.limbs, &Uint::from(1).0))
+    }
+
+    fn from_value(value: Uint<L>) -> Option<Self> {
+        (value < P::MODULUS)
+            .then(|| Self::from_limbs(Self::montgomery_product(&value.0, &Self::R2)))
+    }
+
+    fn multiply(&self, other: &Self) -> Self {
+        Self::from_limbs(Self::montgomery_product(&self.limbs, &other.limbs))
+    }
+
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self::from_limbs(select(&a.limbs, &b.limbs, choice))
+    }
+
+    fn inverse(&self) -> Result<Self, FieldError> {
+        if self.equal(&Self::ZERO) {
+            return Err(FieldError::ZeroInverse);
+        }
+        // By Fermat's little theorem, with the public exponent p - 2 scanned from its highest bit.
+        let (exponent, _) = sub(&P::MODULUS.0, &Uint::from(2).0);
+        let exponent = Uint(exponent);
+        let mut result = Self::ONE;
+        for position in (0..exponent.bits()).rev() {
+            result = result.multiply(&result);
+            if exponent.bit(position) {
+                result = result.multiply(self);
+            }
+        }
+        Ok(result)
+    }
+
+    fn negate(&self) -> Self {
+        Self::ZERO.subtract(self)
+    }
+
+    fn subtract(&self, other: &Self) -> Self {
+        let (difference, borrow) = sub(&self.limbs, &other.limbs);
+        let (wrapped, _) = add(&difference, &P::MODULUS.0);
+        Self::from_limbs(select(
+            &difference,
+            &wrapped,
+            Choice::from(u8::from(borrow)),
+        ))
+    }
+}
+
+impl<P: PrimeModulus<L>, const L: usize> PrimeField for Montgomery<P, L> {}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::{Montgomery, PrimeModulus};
+    use crate::{
+        math::{mersenne61::Mersenne61, p128::P128, pow, uint::Uint, FieldValue, FiniteField},
+        mpc::{compute_shamir_share, reconstruct_secret, share::ShareBatch},
+    };
+
+    /// The prime 2^{255} - 19.
+    struct P25519;
+
+    impl PrimeModulus<4> for P25519 {
+        const MODULUS: Uint<4> = Uint([u64::MAX - 18, u64::MAX, u64::MAX, u64::MAX >> 1]);
+        const NAME: &'static str = "P255";
+    }
+
+    /// The prime 2^{128} - 159 of [`P128`].
+    struct Prime128;
+
+    impl PrimeModulus<2> for Prime128 {
+        const MODULUS: Uint<2> = Uint([u64::MAX - 158, u64::MAX]);
+        const NAME: &'static str = "M128";
+    }
+
+    /// The prime 2^{61} - 1 of [`Mersenne61`].
+    struct Prime61;
+
+    impl PrimeModulus<1> for Prime61 {
+        const MODULUS: Uint<1> = Uint([(1 << 61) - 1]);
+        const NAME: &'static str = "MM61";
+    }
+
+    type F25519 = Montgomery<P25519, 4>;
+
+    /// Checks that the arithmetic of the field `M` matches the one of the field `T`, which has the
+    /// same prime.
+    fn matches<T: FiniteField, M: FiniteField>() {
+        let mut rng = thread_rng();
+        let to_u128 = |value: M::ValueType| value.to_u128().unwrap();
+        let lift = |element: &T| -> M {
+            let mut bytes = [0; 16];
+            element.value().write_le_bytes(&mut bytes);
+            M::from_value(M::ValueType::read_le_bytes(&bytes).unwrap()).unwrap()
+        };
+        for _ in 0..100 {
+            let (a, b) = (T::random(&mut rng), T::random(&mut rng));
+            let (x, y) = (lift(&a), lift(&b));
+            let expected = [
+                a.add(&b),
+                a.subtract(&b),
+                a.multiply(&b),
+                a.negate(),
+                a.inverse().unwrap(),
+            ];
+            let found = [
+                x.add(&y),
+                x.subtract(&y),
+                x.multiply(&y),
+                x.negate(),
+                x.inverse().unwrap(),
+            ];
+            for (expected, found) in expected.iter().zip(&found) {
+                assert_eq!(to_u128(found.value()), expected.value().to_u128().unwrap());
+            }
+        }
+        assert_eq!(
+            to_u128(M::from(u64::MAX).value()),
+            T::from(u64::MAX).value().to_u128().unwrap()
+        );
+        assert_eq!(M::BIT_SIZE, T::BIT_SIZE);
+    }
+
+    #[test]
+    fn arithmetic_matches_the_specialized_fields() {
+        matches::<Mersenne61, Montgomery<Prime61, 1>>();
+        matches::<P128, Montgomery<Prime128, 2>>();
+    }
+
+    #[test]
+    fn large_fields() {
+        let mut rng = thread_rng();
+        assert_eq!(F25519::BIT_SIZE, 255);
+        // 2^{255} = 19 modulo the prime.
+        assert_eq!(pow(&F25519::from(2), 255), F25519::from(19));
+        assert_eq!(F25519::ZERO.value(), Uint::ZERO);
+        assert_eq!(F25519::ONE.value(), Uint::from(1));
+        assert!(F25519::from_value(P25519::MODULUS).is_none());
+        assert!(F25519::ZERO.inverse().is_err());
+        for _ in 0..20 {
+            let a = F25519::random(&mut rng);
+            assert!(a.value() < P25519::MODULUS);
+            assert_eq!(a.multiply(&a.inverse().unwrap()), F25519::ONE);
+            assert_eq!(a.add(&a.negate()), F25519::ZERO);
+            assert_eq!(F25519::from_value(a.value()), Some(a.clone()));
+        }
+
+        let secret = F25519::random(&mut rng);
+        let shares = compute_shamir_share(&secret, 5, 2, &mut rng);
+        assert_eq!(reconstruct_secret(shares[..3].to_vec()), secret);
+        let batch = ShareBatch::from_shares(&shares).unwrap();
+        let bytes = bincode::serialize(&batch).unwrap();
+        let decoded: ShareBatch<F25519> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded.into_shares(), shares);
+
+        let json = serde_json::to_string(&F25519::from(5)).unwrap();
+        assert_eq!(json, "[5,0,0,0]");
+        assert_eq!(
+            serde_json::from_str::<F25519>(&json).unwrap(),
+            F25519::from(5)
+        );
+        let unreduced = serde_json::to_string(&P25519::MODULUS).unwrap();
+        assert!(serde_json::from_str::<F25519>(&unreduced).is_err());
+    }
+}
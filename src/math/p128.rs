@@ -0,0 +1,196 @@
+use serde::Deserialize;
+use serde::Serialize;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeLess};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::FieldError;
+use super::FiniteField;
+use super::PrimeField;
+
+/// Difference between 2^{128} and the modulus, so that 2^{128} is congruent to it.
+const DELTA: u128 = 159;
+
+/// Number of times that the high half of a product is folded into the low half.
+const MULTIPLY_FOLDS: usize = 4;
+
+/// Representation of a field element modulo the prime 2^{128} - 159, which is the largest prime
+/// that fits in 128 bits.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct P128(u128);
+
+impl P128 {
+    /// Reduces a value lower than 2^{128} modulo 2^{128} - 159 without branching on the value.
+    fn reduce(value: u128) -> Self {
+        let reduced = value.wrapping_sub(Self::MODULUS);
+        Self(u128::conditional_select(
+            &reduced,
+            &value,
+            value.ct_lt(&Self::MODULUS),
+        ))
+    }
+}
+
+impl ConstantTimeEq for P128 {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for P128 {
+    fn eq(&self, other: &Self) -> bool {
+        self.equal(other)
+    }
+}
+
+impl Eq for P128 {}
+
+impl Zeroize for P128 {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for P128 {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for P128 {}
+
+impl From<u64> for P128 {
+    fn from(value: u64) -> Self {
+        Self(value as u128)
+    }
+}
+
+impl FiniteField for P128 {
+    type ValueType = u128;
+
+    const MODULUS: u128 = u128::MAX - DELTA + 1;
+    const BIT_SIZE: usize = 128;
+    const NAME: &'static str = "P128";
+    const ONE: Self = Self(1);
+    const ZERO: Self = Self(0);
+
+    fn add(&self, other: &Self) -> Self {
+        let (sum, carry) = self.0.overflowing_add(other.0);
+        // With a carry, the sum is 2^{128} + sum, which is congruent to sum + 159 and lower than
+        // the modulus, so the reduction leaves it as it is.
+        let folded = u128::conditional_select(
+            &sum,
+            &sum.wrapping_add(DELTA),
+            Choice::from(u8::from(carry)),
+        );
+        Self::reduce(folded)
+    }
+
+    fn random<R: rand::Rng>(generator: &mut R) -> Self {
+        let value: u128 = generator.gen();
+        Self::reduce(value)
+    }
+
+    fn value(&self) -> Self::ValueType {
+        self.0
+    }
+
+    fn multiply(&self, other: &Self) -> Self {
+        let (mut high, mut low) = super::widening_mul(self.0, other.0);
+
+        // As 2^{128} = 159, the high half is folded into the low half. The first fold leaves a
+        // high half of at most 2^{8}, the second one leaves at most a carry, and folding a carry
+        // twice makes it vanish, so four folds are always enough and the loop does not depend
+        // on the factors.
+        for _ in 0..MULTIPLY_FOLDS {
+            let (folded_high, folded_low) = super::widening_mul(high, DELTA);
+            let (sum, carry) = folded_low.overflowing_add(low);
+            high = folded_high + u128::from(carry);
+            low = sum;
+        }
+        Self::reduce(low)
+    }
+
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self(u128::conditional_select(&a.0, &b.0, choice))
+    }
+
+    fn inverse(&self) -> Result<Self, FieldError> {
+        if self.equal(&Self::ZERO) {
+            Err(FieldError::ZeroInverse)
+        } else {
+            Ok(super::pow(self, Self::MODULUS - 2))
+        }
+    }
+
+    fn negate(&self) -> Self {
+        Self::ZERO.subtract(self)
+    }
+
+    fn subtract(&self, other: &Self) -> Self {
+        let difference = self.0.wrapping_sub(other.0);
+        let borrow = self.0.ct_lt(&other.0);
+        Self(u128::conditional_select(
+            &difference,
+            &difference.wrapping_add(Self::MODULUS),
+            borrow,
+        ))
+    }
+}
+
+impl PrimeField for P128 {}
+
+#[cfg(test)]
+mod tests {
+    use super::P128;
+    use crate::math::FiniteField;
+    use rand::thread_rng;
+
+    #[test]
+    fn zero_and_one() {
+        let mut rng = thread_rng();
+        let elem = P128::random(&mut rng);
+        assert_eq!(elem.add(&P128::ZERO), elem);
+        assert_eq!(elem.subtract(&elem), P128::ZERO);
+        assert_eq!(elem.add(&elem.negate()), P128::ZERO);
+        assert_eq!(elem.multiply(&P128::ONE), elem);
+    }
+
+    #[test]
+    fn inverse() {
+        let mut rng = thread_rng();
+        const SAMPLES: usize = 100;
+        for _ in 0..SAMPLES {
+            let elem = P128::random(&mut rng);
+            let s = elem.multiply(&elem.inverse().unwrap());
+            assert_eq!(s, P128::ONE);
+        }
+        assert!(P128::ZERO.inverse().is_err());
+    }
+
+    #[test]
+    fn reduce_large_values() {
+        let minus_one = P128::ONE.negate();
+        assert_eq!(minus_one.multiply(&minus_one), P128::ONE);
+        assert_eq!(minus_one.add(&minus_one), P128::from(2).negate());
+        assert_eq!(minus_one.add(&P128::from(3)), P128::from(2));
+        assert_eq!(
+            P128::from(1).subtract(&P128::from(3)),
+            P128::from(2).negate()
+        );
+
+        // 2^{64} * 2^{64} = 2^{128} = 159.
+        let two_to_64 = P128(1 << 64);
+        assert_eq!(two_to_64.multiply(&two_to_64), P128::from(159));
+    }
+
+    #[test]
+    fn mult_conmutativity() {
+        const SAMPLES: usize = 50;
+        let mut rng = thread_rng();
+        for _ in 0..SAMPLES {
+            let a = P128::random(&mut rng);
+            let b = P128::random(&mut rng);
+            assert_eq!(a.multiply(&b), b.multiply(&a));
+        }
+    }
+}
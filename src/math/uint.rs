@@ -0,0 +1,209 @@
+use std::{cmp::Ordering, fmt, marker::PhantomData};
+
+use serde::{
+    de::{self, SeqAccess},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::FieldValue;
+
+/// Unsigned integer of `L` 64-bit limbs in little-endian order, which represents the elements of
+/// the fields larger than 128 bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Uint<const L: usize>(pub [u64; L]);
+
+impl<const L: usize> Uint<L> {
+    /// Integer zero.
+    pub const ZERO: Self = Self([0; L]);
+
+    /// Returns the number of bits of the integer, i.e. the position of its highest set bit plus
+    /// one.
+    pub const fn bits(&self) -> usize {
+        let mut limb = L;
+        while limb > 0 {
+            limb -= 1;
+            if self.0[limb] != 0 {
+                return 64 * limb + (u64::BITS - self.0[limb].leading_zeros()) as usize;
+            }
+        }
+        0
+    }
+
+    /// Returns whether the bit in the given position is set.
+    pub const fn bit(&self, position: usize) -> bool {
+        position < 64 * L && (self.0[position / 64] >> (position % 64)) & 1 == 1
+    }
+
+    /// Divides the integer by a nonzero divisor, and returns the quotient and the remainder.
+    fn div_rem_u64(&self, divisor: u64) -> (Self, u64) {
+        let mut quotient = [0; L];
+        let mut remainder = 0u128;
+        for limb in (0..L).rev() {
+            let dividend = (remainder << 64) | u128::from(self.0[limb]);
+            quotient[limb] = (dividend / u128::from(divisor)) as u64;
+            remainder = dividend % u128::from(divisor);
+        }
+        (Self(quotient), remainder as u64)
+    }
+}
+
+impl<const L: usize> From<u64> for Uint<L> {
+    fn from(value: u64) -> Self {
+        let mut limbs = [0; L];
+        limbs[0] = value;
+        Self(limbs)
+    }
+}
+
+impl<const L: usize> Ord for Uint<L> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl<const L: usize> PartialOrd for Uint<L> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const L: usize> fmt::Display for Uint<L> {
+    /// Writes the integer in decimal, in groups of 19 digits, which is the most that fit in a
+    /// limb.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const GROUP: u64 = 10_000_000_000_000_000_000;
+        let mut groups = Vec::new();
+        let mut value = *self;
+        loop {
+            let (quotient, remainder) = value.div_rem_u64(GROUP);
+            groups.push(remainder);
+            if quotient == Self::ZERO {
+                break;
+            }
+            value = quotient;
+        }
+        let mut digits = groups.pop().unwrap_or_default().to_string();
+        for group in groups.into_iter().rev() {
+            digits.push_str(&format!("{group:019}"));
+        }
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+impl<const L: usize> FieldValue for Uint<L> {
+    const BYTES: usize = 8 * L;
+
+    fn write_le_bytes(&self, bytes: &mut [u8]) {
+        bytes.fill(0);
+        for (chunk, limb) in bytes.chunks_mut(8).zip(self.0) {
+            chunk.copy_from_slice(&limb.to_le_bytes()[..chunk.len()]);
+        }
+    }
+
+    fn read_le_bytes(bytes: &[u8]) -> Option<Self> {
+        let (low, high) = bytes.split_at(bytes.len().min(Self::BYTES));
+        if high.iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        let mut limbs = [0; L];
+        for (limb, chunk) in limbs.iter_mut().zip(low.chunks(8)) {
+            let mut value = [0; 8];
+            value[..chunk.len()].copy_from_slice(chunk);
+            *limb = u64::from_le_bytes(value);
+        }
+        Some(Self(limbs))
+    }
+
+    fn to_u128(&self) -> Option<u128> {
+        if self.0.iter().skip(2).any(|limb| *limb != 0) {
+            return None;
+        }
+        let low = self.0.first().copied().unwrap_or_default();
+        let high = self.0.get(1).copied().unwrap_or_default();
+        Some((u128::from(high) << 64) | u128::from(low))
+    }
+
+    fn rem_u64(&self, divisor: u64) -> u64 {
+        self.div_rem_u64(divisor).1
+    }
+}
+
+impl<const L: usize> Serialize for Uint<L> {
+    /// Serializes the integer as the tuple of its limbs, since serde only implements the arrays of
+    /// up to 32 elements.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(L)?;
+        for limb in &self.0 {
+            tuple.serialize_element(limb)?;
+        }
+        tuple.end()
+    }
+}
+
+impl<'de, const L: usize> Deserialize<'de> for Uint<L> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(L, UintVisitor(PhantomData))
+    }
+}
+
+/// Visitor that decodes the limbs of a [`Uint`].
+struct UintVisitor<const L: usize>(PhantomData<[u64; L]>);
+
+impl<'de, const L: usize> de::Visitor<'de> for UintVisitor<L> {
+    type Value = Uint<L>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an integer of {L} limbs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut limbs = [0; L];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            *limb = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        Ok(Uint(limbs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Uint;
+    use crate::math::FieldValue;
+
+    #[test]
+    fn limbs_behave_as_integers() {
+        // 2^255 - 19 and 2^128 + 5.
+        let large = Uint([u64::MAX - 18, u64::MAX, u64::MAX, u64::MAX >> 1]);
+        let small = Uint([5, 0, 1, 0]);
+        assert_eq!(
+            large.to_string(),
+            "57896044618658097711785492504343953926634992332820282019728792003956564819949"
+        );
+        assert_eq!(small.to_string(), "340282366920938463463374607431768211461");
+        assert_eq!(format!("{:>3}", Uint::<2>::from(7)), "  7");
+        assert_eq!(
+            (large.bits(), small.bits(), Uint::<4>::ZERO.bits()),
+            (255, 129, 0)
+        );
+        assert!(small < large && Uint::<4>::from(u64::MAX) < small);
+        assert_eq!(large.rem_u64(1000), 949);
+        assert_eq!(small.to_u128(), None);
+        assert_eq!(Uint::<4>::from(9).to_u128(), Some(9));
+
+        let mut bytes = [0xFF; 40];
+        large.write_le_bytes(&mut bytes);
+        assert_eq!(Uint::read_le_bytes(&bytes), Some(large));
+        assert_eq!(&bytes[32..], &[0; 8]);
+        bytes[35] = 1;
+        assert_eq!(Uint::<4>::read_le_bytes(&bytes), None);
+        assert_eq!(Uint::<4>::read_le_bytes(&[1, 2]), Some(Uint::from(0x201)));
+
+        let json = serde_json::to_string(&small).unwrap();
+        assert_eq!(json, "[5,0,1,0]");
+        assert_eq!(serde_json::from_str::<Uint<4>>(&json).unwrap(), small);
+        assert!(serde_json::from_str::<Uint<4>>("[5,1]").is_err());
+    }
+}
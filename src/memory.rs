@@ -0,0 +1,327 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock, PoisonError,
+    },
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroize;
+
+/// Whether the buffers with secrets are locked in memory.
+static LOCKING: AtomicBool = AtomicBool::new(false);
+
+/// Number of locks held over each page, indexed by the address of the page. A page is locked
+/// while at least one lock holds it, since several buffers may share the same page.
+static LOCKED_PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+/// Whether a failure to lock a page was already reported.
+static FAILURE_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the locking of the buffers with secrets created from now on. Locking is
+/// disabled by default, since the memory that a process may lock is usually limited, e.g. by
+/// `ulimit -l`.
+pub fn set_locking(enabled: bool) {
+    LOCKING.store(enabled, Ordering::Relaxed);
+}
+
+/// Tells whether the buffers with secrets are locked in memory.
+pub fn is_locking() -> bool {
+    LOCKING.load(Ordering::Relaxed)
+}
+
+/// Returns the size in bytes of the pages of memory.
+fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+    *PAGE_SIZE.get_or_init(|| {
+        #[cfg(unix)]
+        // SAFETY: `sysconf` has no preconditions.
+        let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        #[cfg(not(unix))]
+        let size = 4096;
+        usize::try_from(size).unwrap_or(4096)
+    })
+}
+
+/// Locks a page so it is not swapped to disk, and excludes it from the core dumps. The locking
+/// is best effort: a failure is reported once in the logs, and the buffer is used anyway.
+fn lock_page(page: usize) {
+    #[cfg(unix)]
+    {
+        let address = page as *mut libc::c_void;
+        // SAFETY: the page belongs to an allocation that is alive while the lock is held, and
+        // locking it does not change its contents.
+        let mut failed = unsafe { libc::mlock(address, page_size()) } != 0;
+        #[cfg(target_os = "linux")]
+        {
+            // SAFETY: the advice only affects whether the page is written to core dumps.
+            failed |= unsafe { libc::madvise(address, page_size(), libc::MADV_DONTDUMP) } != 0;
+        }
+        if failed && !FAILURE_REPORTED.swap(true, Ordering::Relaxed) {
+            log::warn!(
+                "could not lock the memory of a secret: {}; raise the limit of locked memory \
+                 with `ulimit -l`",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = page;
+}
+
+/// Unlocks a page locked by [`lock_page`] and includes it again in the core dumps.
+fn unlock_page(page: usize) {
+    #[cfg(unix)]
+    {
+        let address = page as *mut libc::c_void;
+        // SAFETY: as in `lock_page`, the call does not change the contents of the page.
+        unsafe {
+            libc::munlock(address, page_size());
+            #[cfg(target_os = "linux")]
+            libc::madvise(address, page_size(), libc::MADV_DODUMP);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = page;
+}
+
+/// Lock over the pages of a buffer, which keeps them from being swapped to disk or written to
+/// a core dump until it is dropped. The buffer must not be moved while the lock is held.
+#[derive(Debug)]
+pub struct MemoryLock {
+    /// Address of the first page of the buffer.
+    first_page: usize,
+    /// Number of pages of the buffer.
+    n_pages: usize,
+}
+
+impl MemoryLock {
+    /// Locks the pages of a buffer.
+    pub fn new<T>(buffer: &[T]) -> Self {
+        Self::of_range(buffer.as_ptr() as usize, std::mem::size_of_val(buffer))
+    }
+
+    /// Locks the pages of the bytes of memory from the given address.
+    fn of_range(start: usize, size: usize) -> Self {
+        if size == 0 {
+            return Self {
+                first_page: 0,
+                n_pages: 0,
+            };
+        }
+        let first_page = start - start % page_size();
+        let n_pages = (start + size - first_page).div_ceil(page_size());
+        let mut pages = LOCKED_PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+        for page in (0..n_pages).map(|i| first_page + i * page_size()) {
+            let count = pages.entry(page).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                lock_page(page);
+            }
+        }
+        Self {
+            first_page,
+            n_pages,
+        }
+    }
+}
+
+impl Drop for MemoryLock {
+    fn drop(&mut self) {
+        let mut pages = LOCKED_PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+        for page in (0..self.n_pages).map(|i| self.first_page + i * page_size()) {
+            if let Some(count) = pages.get_mut(&page) {
+                *count -= 1;
+                if *count == 0 {
+                    pages.remove(&page);
+                    unlock_page(page);
+                }
+            }
+        }
+    }
+}
+
+/// Vector of fixed length that holds secrets, e.g. the coefficients of a sharing polynomial or
+/// the shares of the wires of a circuit. If locking is enabled with [`set_locking`], its
+/// buffer is locked in memory while it is alive. The elements can be modified, but the length
+/// can not change, so the buffer is never moved.
+///
+/// When the vector is dropped, its elements are dropped and the whole buffer is wiped before the
+/// lock is released, and the buffer is only freed after that, so a page is never unlocked once
+/// the allocator may have handed it out again.
+pub struct SecretVec<T> {
+    /// Lock over the buffer of the elements, if locking is enabled, which is only held until the
+    /// vector is dropped.
+    lock: Option<MemoryLock>,
+    /// Elements of the vector.
+    items: Vec<T>,
+}
+
+impl<T> SecretVec<T> {
+    /// Returns the elements of the vector, which are no longer locked.
+    pub fn into_vec(mut self) -> Vec<T> {
+        self.lock = None;
+        std::mem::take(&mut self.items)
+    }
+}
+
+impl<T> Drop for SecretVec<T> {
+    fn drop(&mut self) {
+        self.items.clear();
+        self.items.spare_capacity_mut().zeroize();
+        self.lock = None;
+    }
+}
+
+impl<T> From<Vec<T>> for SecretVec<T> {
+    fn from(items: Vec<T>) -> Self {
+        // The lock covers the spare capacity too, since it may hold secrets of earlier elements.
+        let lock = is_locking().then(|| {
+            MemoryLock::of_range(
+                items.as_ptr() as usize,
+                items.capacity() * std::mem::size_of::<T>(),
+            )
+        });
+        Self { lock, items }
+    }
+}
+
+impl<T> Deref for SecretVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+impl<T> DerefMut for SecretVec<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.items
+    }
+}
+
+impl<T: Clone> Clone for SecretVec<T> {
+    fn clone(&self) -> Self {
+        Self::from(self.items.clone())
+    }
+}
+
+impl<T: PartialEq> PartialEq for SecretVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+
+impl<T: Eq> Eq for SecretVec<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for SecretVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.items.fmt(f)
+    }
+}
+
+impl<T: Zeroize> Zeroize for SecretVec<T> {
+    fn zeroize(&mut self) {
+        self.items.zeroize();
+    }
+}
+
+impl<T: Serialize> Serialize for SecretVec<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.items.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for SecretVec<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{page_size, MemoryLock, SecretVec, LOCKED_PAGES};
+
+    /// Returns the number of locks held over a page.
+    fn locks_of(page: usize) -> usize {
+        LOCKED_PAGES
+            .lock()
+            .unwrap()
+            .get(&page)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn pages_shared_by_buffers_stay_locked() {
+        let buffer = vec![1u8; 3 * page_size()];
+        let first_page = buffer.as_ptr() as usize / page_size() * page_size();
+        let last_page = (buffer.as_ptr() as usize + buffer.len() - 1) / page_size() * page_size();
+
+        let whole = MemoryLock::new(&buffer);
+        let head = MemoryLock::new(&buffer[..1]);
+        assert_eq!(locks_of(first_page), 2);
+        assert_eq!(locks_of(last_page), 1);
+
+        drop(whole);
+        assert_eq!(locks_of(first_page), 1);
+        assert_eq!(locks_of(last_page), 0);
+        drop(head);
+        assert_eq!(locks_of(first_page), 0);
+        assert_eq!(buffer, vec![1u8; 3 * page_size()]);
+    }
+
+    #[test]
+    fn secret_vectors_behave_as_slices() {
+        let mut secret = SecretVec::from(vec![1, 2, 3]);
+        secret[0] = 4;
+        assert_eq!(&secret[..], &[4, 2, 3]);
+        assert_eq!(secret.clone(), secret);
+        let json = serde_json::to_string(&secret).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SecretVec<i32>>(&json).unwrap(),
+            secret
+        );
+        assert_eq!(secret.into_vec(), vec![4, 2, 3]);
+    }
+
+    /// Element that records the number of locks over its page when it is dropped.
+    struct Probe {
+        page: usize,
+        locks_at_drop: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl Drop for Probe {
+        fn drop(&mut self) {
+            self.locks_at_drop.lock().unwrap().push(locks_of(self.page));
+        }
+    }
+
+    #[test]
+    fn secret_vectors_are_unlocked_after_their_elements_are_dropped() {
+        let locks_at_drop = Arc::new(Mutex::new(Vec::new()));
+        let mut items = Vec::with_capacity(2 * page_size());
+        let page = items.as_ptr() as *const u8 as usize / page_size() * page_size();
+        items.push(Probe {
+            page,
+            locks_at_drop: locks_at_drop.clone(),
+        });
+        let secret = SecretVec {
+            lock: Some(MemoryLock::of_range(
+                items.as_ptr() as usize,
+                items.capacity() * std::mem::size_of::<Probe>(),
+            )),
+            items,
+        };
+        assert_eq!(locks_of(page), 1);
+
+        drop(secret);
+        assert_eq!(*locks_at_drop.lock().unwrap(), vec![1]);
+        assert_eq!(locks_of(page), 0);
+    }
+}
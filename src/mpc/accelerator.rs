@@ -0,0 +1,253 @@
+use super::{parallel, share::ShamirShare};
+use crate::math::{from_u128, FieldValue, FiniteField};
+
+/// Accelerator, such as a GPU, to which the large batches of field arithmetic of the preprocessing
+/// are offloaded: the products of the Beaver triples and the recombination of the shares of the
+/// products. The crate does not ship one, since the kernels depend on the hardware, e.g. CUDA or
+/// a `wgpu` compute shader, so applications pass their own to the protocols through
+/// [`super::engine::Engine::with_accelerator`], or to the functions of this module. The elements are passed as the integers in `[0, p)` that represent them, which is the form in
+/// which a kernel receives them.
+pub trait Accelerator: Send + Sync {
+    /// Name of the accelerator, which appears in the logs.
+    fn name(&self) -> &str;
+
+    /// Returns whether the accelerator computes in the field of the given name, e.g. `M61`, and
+    /// modulus. The batches of the other fields are computed by the CPU.
+    fn supports(&self, field: &str, modulus: u128) -> bool;
+
+    /// Smallest batch that is worth offloading, below which copying the elements to the device
+    /// costs more than computing them in the CPU.
+    fn min_batch(&self) -> usize {
+        1 << 14
+    }
+
+    /// Computes the products `a[k] * b[k]` modulo the modulus.
+    fn multiply(&self, modulus: u128, a: &[u128], b: &[u128]) -> anyhow::Result<Vec<u128>>;
+
+    /// Computes the linear combinations `sum_i coefficients[i] * rows[i][k]` modulo the modulus,
+    /// i.e. the product of the vector of coefficients by the matrix of the rows.
+    fn combine(
+        &self,
+        modulus: u128,
+        coefficients: &[u128],
+        rows: &[Vec<u128>],
+    ) -> anyhow::Result<Vec<u128>>;
+}
+
+/// Returns the given accelerator and the modulus of the field `T` if the accelerator supports the
+/// field and the batch of `n_items` items is large enough to offload. The fields larger than 128
+/// bits are always computed by the CPU.
+fn accelerator_for<T: FiniteField>(
+    accelerator: Option<&dyn Accelerator>,
+    n_items: usize,
+) -> Option<(&dyn Accelerator, u128)> {
+    let modulus = T::MODULUS.to_u128()?;
+    let accelerator = accelerator?;
+    (n_items >= accelerator.min_batch() && accelerator.supports(T::NAME, modulus))
+        .then_some((accelerator, modulus))
+}
+
+/// Converts the field elements into the integers that an accelerator receives, which fit in 128
+/// bits as checked by [`accelerator_for`].
+fn to_device<T: FiniteField>(values: impl Iterator<Item = T::ValueType>) -> Vec<u128> {
+    values
+        .map(|value| value.to_u128().expect("the field fits in 128 bits"))
+        .collect()
+}
+
+/// Converts the integers computed by an accelerator into field elements, checking that there is
+/// one for every item.
+fn from_device<T: FiniteField>(
+    accelerator: &dyn Accelerator,
+    result: anyhow::Result<Vec<u128>>,
+    n_items: usize,
+) -> Option<Vec<T>> {
+    match result {
+        Ok(values) if values.len() == n_items => Some(values.into_iter().map(from_u128).collect()),
+        Ok(values) => {
+            log::warn!(
+                "{} returned {} elements instead of {n_items}, computing them in the CPU",
+                accelerator.name(),
+                values.len()
+            );
+            None
+        }
+        Err(err) => {
+            log::warn!(
+                "{} failed, computing the batch in the CPU: {err:#}",
+                accelerator.name()
+            );
+            None
+        }
+    }
+}
+
+/// Computes the products `a[k] * b[k]` of two batches of the same length in the given
+/// accelerator, falling back to the CPU if there is none, the batch is small, or the accelerator
+/// fails.
+pub fn multiply<T: FiniteField>(a: &[T], b: &[T], accelerator: Option<&dyn Accelerator>) -> Vec<T> {
+    assert_eq!(a.len(), b.len(), "the batches must have the same length");
+    if let Some((accelerator, modulus)) = accelerator_for::<T>(accelerator, a.len()) {
+        log::debug!("multiplying {} elements in {}", a.len(), accelerator.name());
+        let result = accelerator.multiply(
+            modulus,
+            &to_device::<T>(a.iter().map(T::value)),
+            &to_device::<T>(b.iter().map(T::value)),
+        );
+        if let Some(products) = from_device(accelerator, result, a.len()) {
+            return products;
+        }
+    }
+    parallel::map_chunks(a.len(), parallel::CHUNK_SIZE, |_, chunk| {
+        chunk.map(|k| a[k].multiply(&b[k])).collect::<Vec<_>>()
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Computes the linear combinations `sum_i coefficients[i] * rows[i][k]` of the values of the
+/// first `n_items` shares of every row, e.g. the recombination of the shares of the products
+/// received from every party, in the given accelerator, falling back to the CPU as in
+/// [`multiply`].
+pub fn combine_shares<T: FiniteField>(
+    coefficients: &[T],
+    rows: &[Vec<ShamirShare<T>>],
+    n_items: usize,
+    accelerator: Option<&dyn Accelerator>,
+) -> Vec<T> {
+    assert_eq!(
+        coefficients.len(),
+        rows.len(),
+        "every row needs a coefficient"
+    );
+    if let Some((accelerator, modulus)) = accelerator_for::<T>(accelerator, n_items) {
+        log::debug!(
+            "combining {} rows of {n_items} elements in {}",
+            rows.len(),
+            accelerator.name()
+        );
+        let coefficients = to_device::<T>(coefficients.iter().map(T::value));
+        let rows: Vec<Vec<u128>> = rows
+            .iter()
+            .map(|row| to_device::<T>(row[..n_items].iter().map(|share| share.value.value())))
+            .collect();
+        let result = accelerator.combine(modulus, &coefficients, &rows);
+        if let Some(values) = from_device(accelerator, result, n_items) {
+            return values;
+        }
+    }
+    parallel::map_chunks(n_items, parallel::CHUNK_SIZE, |_, chunk| {
+        chunk
+            .map(|k| {
+                let mut value = T::ZERO;
+                for (coefficient, row) in coefficients.iter().zip(rows) {
+                    value = value.add(&coefficient.multiply(&row[k].value));
+                }
+                value
+            })
+            .collect::<Vec<_>>()
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use rand::thread_rng;
+
+    use super::{combine_shares, multiply, Accelerator};
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::share::ShamirShare,
+    };
+
+    /// Accelerator that computes in the CPU through the integers, and fails on command.
+    #[derive(Default)]
+    struct Emulator {
+        /// Number of batches that it computed.
+        batches: AtomicUsize,
+        /// Whether it fails every batch.
+        failing: bool,
+    }
+
+    impl Accelerator for Emulator {
+        fn name(&self) -> &str {
+            "emulator"
+        }
+
+        fn supports(&self, field: &str, _modulus: u128) -> bool {
+            field == Mersenne61::NAME
+        }
+
+        fn min_batch(&self) -> usize {
+            2
+        }
+
+        fn multiply(&self, modulus: u128, a: &[u128], b: &[u128]) -> anyhow::Result<Vec<u128>> {
+            anyhow::ensure!(!self.failing, "the device is lost");
+            self.batches.fetch_add(1, Ordering::Relaxed);
+            Ok(a.iter().zip(b).map(|(a, b)| a * b % modulus).collect())
+        }
+
+        fn combine(
+            &self,
+            modulus: u128,
+            coefficients: &[u128],
+            rows: &[Vec<u128>],
+        ) -> anyhow::Result<Vec<u128>> {
+            anyhow::ensure!(!self.failing, "the device is lost");
+            self.batches.fetch_add(1, Ordering::Relaxed);
+            Ok((0..rows[0].len())
+                .map(|k| {
+                    coefficients
+                        .iter()
+                        .zip(rows)
+                        .fold(0, |sum, (c, row)| (sum + c * row[k] % modulus) % modulus)
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn accelerators_compute_as_the_cpu() {
+        let mut rng = thread_rng();
+        let random = |rng: &mut _| -> Vec<Mersenne61> {
+            (0..100).map(|_| Mersenne61::random(rng)).collect()
+        };
+        let (a, b) = (random(&mut rng), random(&mut rng));
+        let coefficients = random(&mut rng)[..3].to_vec();
+        let rows: Vec<Vec<ShamirShare<Mersenne61>>> = (0..3)
+            .map(|_| {
+                random(&mut rng)
+                    .into_iter()
+                    .map(|value| ShamirShare::new(value, 1))
+                    .collect()
+            })
+            .collect();
+        let compute = |accelerator: Option<&dyn Accelerator>| {
+            (
+                multiply(&a, &b, accelerator),
+                combine_shares(&coefficients, &rows, 60, accelerator),
+            )
+        };
+        let cpu = compute(None);
+        assert!(cpu.0[7].equal(&a[7].multiply(&b[7])));
+        assert_eq!(cpu.1.len(), 60);
+
+        let emulator = Emulator::default();
+        assert_eq!(compute(Some(&emulator)), cpu);
+        assert_eq!(emulator.batches.load(Ordering::Relaxed), 2);
+
+        // A failing accelerator falls back to the CPU.
+        let failing = Emulator {
+            failing: true,
+            ..Default::default()
+        };
+        assert_eq!(compute(Some(&failing)), cpu);
+        assert_eq!(failing.batches.load(Ordering::Relaxed), 0);
+    }
+}
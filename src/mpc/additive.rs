@@ -0,0 +1,166 @@
+use rand::{CryptoRng, Rng};
+
+use super::{
+    check_threshold,
+    share::{ShamirShare, ShareBatch},
+    share_inputs, ReconstructionError,
+};
+use crate::{
+    math::{lagrange::recombination_vector, FiniteField},
+    transport::Transport,
+};
+
+/// Converts the Shamir shares of a party into an `n`-out-of-`n` additive sharing of the same
+/// secrets, i.e. into values whose sum over the parties is the secret. The conversion is local:
+/// every share is weighted with the Lagrange coefficient of the party at zero over the points of
+/// all the parties. The shares may have any degree below the number of parties, e.g. the local
+/// products of two shares of degree `t`, since every party takes part. All the parties must
+/// convert their shares to reconstruct the secrets from the additive shares.
+pub fn to_additive<T>(
+    shares: &[ShamirShare<T>],
+    party_id: usize,
+    n_parties: usize,
+) -> Result<Vec<T>, ReconstructionError>
+where
+    T: FiniteField,
+{
+    if let Some(share) = shares.iter().find(|share| share.degree >= n_parties) {
+        return Err(ReconstructionError::NotEnoughShares {
+            degree: share.degree,
+            received: n_parties,
+        });
+    }
+    // The share of the party with ID `i` is the evaluation of the polynomial at `i + 1`.
+    let points: Vec<u64> = (1..=n_parties as u64).collect();
+    let weight = recombination_vector::<T>(&points)[party_id].clone();
+    Ok(shares
+        .iter()
+        .map(|share| share.value.multiply(&weight))
+        .collect())
+}
+
+/// Converts the additive shares of a party back into Shamir shares of degree `threshold` of the
+/// same secrets. Every party shares each of its additive shares with the other parties in a
+/// single round, and the Shamir shares of a secret are the sums of the shares received for it,
+/// since the sharing is linear. Every party must hold the same number of additive shares.
+pub fn from_additive<T, R, N>(
+    values: &[T],
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    network: &mut N,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    check_threshold(n_parties, threshold, false)?;
+    let own_batches: Vec<ShareBatch<T>> = share_inputs(values, n_parties, threshold, rng)
+        .into_iter()
+        .map(|shares| ShareBatch {
+            degree: threshold,
+            values: shares.into_iter().map(|share| share.value).collect(),
+        })
+        .collect();
+    log::info!("resharing {} additive shares", values.len());
+    let batches: Vec<ShareBatch<T>> = network.exchange_values(&own_batches)?;
+    for (party, batch) in batches.iter().enumerate() {
+        anyhow::ensure!(
+            batch.values.len() == values.len() && batch.degree == threshold,
+            "party {party} reshared {} additive shares with degree {}, but {} with degree \
+             {threshold} were expected",
+            batch.values.len(),
+            batch.degree,
+            values.len()
+        );
+    }
+    Ok((0..values.len())
+        .map(|k| {
+            let sum = batches
+                .iter()
+                .fold(T::ZERO, |sum, batch| sum.add(&batch.values[k]));
+            ShamirShare::new(sum, threshold)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{from_additive, to_additive};
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{compute_shamir_share, reconstruct_secret, share::ShamirShare, ReconstructionError},
+        net::Network,
+    };
+
+    const N_PARTIES: usize = 5;
+    const THRESHOLD: usize = 2;
+
+    #[test]
+    fn additive_shares_sum_to_the_secret() {
+        let mut rng = thread_rng();
+        let x = Mersenne61::random(&mut rng);
+        let y = Mersenne61::random(&mut rng);
+        let x_shares = compute_shamir_share(&x, N_PARTIES, THRESHOLD, &mut rng);
+        let y_shares = compute_shamir_share(&y, N_PARTIES, THRESHOLD, &mut rng);
+
+        // The local products have degree 2t, which is below the number of parties.
+        let sums = (0..N_PARTIES)
+            .map(|id| {
+                let product = x_shares[id].multiply(&y_shares[id], N_PARTIES).unwrap();
+                let shares = [x_shares[id].clone(), product];
+                to_additive(&shares, id, N_PARTIES).unwrap()
+            })
+            .fold([Mersenne61::ZERO; 2], |[x_sum, xy_sum], additive| {
+                [x_sum.add(&additive[0]), xy_sum.add(&additive[1])]
+            });
+        assert_eq!(sums, [x.clone(), x.multiply(&y)]);
+
+        let too_high = ShamirShare::new(x.clone(), 3 * THRESHOLD);
+        assert_eq!(
+            to_additive(&[too_high], 0, N_PARTIES),
+            Err(ReconstructionError::NotEnoughShares {
+                degree: 3 * THRESHOLD,
+                received: N_PARTIES
+            })
+        );
+    }
+
+    #[test]
+    fn round_trip_through_additive_shares() {
+        let mut rng = thread_rng();
+        let secrets: Vec<Mersenne61> = (0..3).map(|_| Mersenne61::random(&mut rng)).collect();
+        let mut shares = vec![Vec::new(); N_PARTIES];
+        for secret in &secrets {
+            for (id, share) in compute_shamir_share(secret, N_PARTIES, THRESHOLD, &mut rng)
+                .into_iter()
+                .enumerate()
+            {
+                shares[id].push(share);
+            }
+        }
+
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(shares)
+            .enumerate()
+            .map(|(id, (mut network, shares))| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let additive = to_additive(&shares, id, N_PARTIES).unwrap();
+                    from_additive(&additive, N_PARTIES, THRESHOLD, &mut rng, &mut network).unwrap()
+                })
+            })
+            .collect();
+        let reshared: Vec<Vec<_>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        for (k, secret) in secrets.iter().enumerate() {
+            let shares = reshared.iter().map(|own| own[k].clone()).collect();
+            assert_eq!(&reconstruct_secret(shares), secret);
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    marker::PhantomData,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use crate::{
+    codec::{Codec, WireCodec},
+    math::FiniteField,
+    net::{
+        agreement::{AgreementMessage, AGREEMENT_PROTOCOL_ID},
+        broadcast::{BroadcastMessage, BROADCAST_PROTOCOL_ID},
+        channel::{Channel, Readiness},
+        value::{decode_value, encode_value},
+        Network, Packet,
+    },
+};
+
+use super::share::{ShamirShare, ShareBatch};
+
+/// Deviation from the protocols performed by a corrupted party on the packets that it sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deviation {
+    /// The party follows the protocols.
+    Honest,
+    /// The party adds one to every share that it sends, so its shares do not lie on the
+    /// polynomial of the shares of the honest parties.
+    WrongShares,
+    /// The party sends a different value to the given peer when it starts a reliable broadcast,
+    /// and the opposite bit in every message of a binary agreement.
+    Equivocate(usize),
+    /// The party sends again the previous packet of the same protocol before each tagged packet,
+    /// as if the message of an earlier round arrived late.
+    StaleRounds,
+}
+
+/// Handle to command the deviation of a corrupted party while it runs a protocol.
+#[derive(Debug, Clone)]
+pub struct Adversary {
+    deviation: Arc<Mutex<Deviation>>,
+}
+
+impl Adversary {
+    /// Corrupts the party of the network, which deviates from the protocols in the packets that
+    /// it sends to its peers as commanded through the returned handle. The shares tampered with
+    /// are the ones of the field `T`, decoded with the codec that the network has when it is
+    /// corrupted. The party starts being honest.
+    pub fn corrupt<T: FiniteField + 'static>(network: &mut Network) -> Self {
+        let adversary = Self {
+            deviation: Arc::new(Mutex::new(Deviation::Honest)),
+        };
+        let id = network.id();
+        let codec = network.codec();
+        network.wrap_channels(|party_id, channel| {
+            if party_id == id {
+                return channel;
+            }
+            Box::new(MaliciousChannel::<T> {
+                inner: channel,
+                party_id,
+                deviation: Arc::clone(&adversary.deviation),
+                last_tagged: None,
+                codec,
+                field: PhantomData,
+            })
+        });
+        adversary
+    }
+
+    /// Sets the deviation performed from now on.
+    pub fn set(&self, deviation: Deviation) {
+        *self
+            .deviation
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = deviation;
+    }
+
+    /// Returns the current deviation.
+    fn get(deviation: &Mutex<Deviation>) -> Deviation {
+        *deviation.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Channel of a corrupted party to one of its peers that rewrites the packets sent through
+/// another channel according to the commanded deviation.
+struct MaliciousChannel<T> {
+    /// Channel that actually transmits the packets.
+    inner: Box<dyn Channel>,
+    /// ID of the peer at the other end of the channel.
+    party_id: usize,
+    /// Deviation shared with the [`Adversary`] handle.
+    deviation: Arc<Mutex<Deviation>>,
+    /// Last tagged packet sent through the channel, which is replayed as a stale message.
+    last_tagged: Option<Packet>,
+    /// Codec with which the values of the packets are encoded.
+    codec: Codec,
+    /// Field of the shares, which the channel only decodes.
+    field: PhantomData<fn() -> T>,
+}
+
+impl<T: FiniteField> MaliciousChannel<T> {
+    /// Returns the packet with every share of the field `T` increased by one, or `None` if the
+    /// packet does not contain shares.
+    fn tamper_shares(&self, packet: &Packet) -> Option<Packet> {
+        let tamper = |share: &ShamirShare<T>| share.add_const(&T::ONE);
+        let payload = if let Ok(share) = decode_value::<ShamirShare<T>>(self.codec, packet) {
+            encode_value(self.codec, &tamper(&share))
+        } else if let Ok(shares) = decode_value::<Vec<ShamirShare<T>>>(self.codec, packet) {
+            encode_value(self.codec, &shares.iter().map(tamper).collect::<Vec<_>>())
+        } else if let Ok(mut batch) = decode_value::<ShareBatch<T>>(self.codec, packet) {
+            batch
+                .values
+                .iter_mut()
+                .for_each(|value| *value = value.add(&T::ONE));
+            encode_value(self.codec, &batch)
+        } else {
+            return None;
+        };
+        Some(Packet::with_header(
+            *packet.header(),
+            payload.ok()?.into_payload(),
+        ))
+    }
+
+    /// Returns the packet with the initial message of a reliable broadcast replaced by another
+    /// value, or the message of a binary agreement with the opposite bit, or `None` if the
+    /// packet belongs to neither.
+    fn equivocate(&self, packet: &Packet) -> Option<Packet> {
+        let message = match packet.header().protocol_id {
+            BROADCAST_PROTOCOL_ID => {
+                let BroadcastMessage::Initial(mut value) =
+                    self.codec.decode(packet.as_slice()).ok()?
+                else {
+                    return None;
+                };
+                value.push(u8::MAX);
+                self.codec.encode(&BroadcastMessage::Initial(value)).ok()?
+            }
+            AGREEMENT_PROTOCOL_ID => {
+                let message = match self.codec.decode(packet.as_slice()).ok()? {
+                    AgreementMessage::Vote(bit) => AgreementMessage::Vote(!bit),
+                    AgreementMessage::Propose(bit) => {
+                        AgreementMessage::Propose(bit.map(|bit| !bit))
+                    }
+                    AgreementMessage::King(bit) => AgreementMessage::King(!bit),
+                };
+                self.codec.encode(&message).ok()?
+            }
+            _ => return None,
+        };
+        Some(Packet::with_header(*packet.header(), message))
+    }
+}
+
+impl<T: FiniteField> Channel for MaliciousChannel<T> {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.inner.shutdown()
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.inner.flush()
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.inner.set_max_packet_size(max_packet_size)
+    }
+
+    fn readiness(&self) -> Readiness {
+        self.inner.readiness()
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        self.inner.rekey()
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        self.inner.export_keying_material(label, output)
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        let mut bytes_sent = 0;
+        let tampered = match Adversary::get(&self.deviation) {
+            Deviation::Honest => None,
+            Deviation::WrongShares => self.tamper_shares(packet),
+            Deviation::Equivocate(victim) if victim == self.party_id => self.equivocate(packet),
+            Deviation::Equivocate(_) => None,
+            Deviation::StaleRounds => {
+                if let Some(stale) = self.last_tagged.as_ref().filter(|stale| {
+                    stale.header().protocol_id == packet.header().protocol_id
+                        && stale.header().round < packet.header().round
+                }) {
+                    bytes_sent += self.inner.send(stale)?;
+                }
+                None
+            }
+        };
+        let packet = tampered.as_ref().unwrap_or(packet);
+        if packet.header().protocol_id != 0 {
+            self.last_tagged = Some(packet.clone());
+        }
+        Ok(bytes_sent + self.inner.send(packet)?)
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        self.inner.recv()
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        self.inner.recv_timeout(timeout)
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        self.inner.try_recv()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use rand::thread_rng;
+
+    use super::{Adversary, Deviation};
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{
+            compute_shamir_share, reconstruct_secret_from_parties, share::ShamirShare,
+            ReconstructionError,
+        },
+        net::{broadcast::reliable_broadcast, channel::ChannelError, Network, Packet},
+    };
+
+    const N_PARTIES: usize = 4;
+    const THRESHOLD: usize = 1;
+    const CORRUPTED: usize = 3;
+
+    /// Runs `protocol` with every party of a local network in its own thread, where the party
+    /// [`CORRUPTED`] deviates as given. Returns the outputs of the honest parties.
+    fn run_with_adversary<O, P>(deviation: Deviation, protocol: P) -> Vec<O>
+    where
+        O: Send + 'static,
+        P: Fn(usize, &mut Network, Option<&Adversary>) -> O + Send + Sync + 'static,
+    {
+        let protocol = Arc::new(protocol);
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .enumerate()
+            .map(|(id, mut network)| {
+                let protocol = Arc::clone(&protocol);
+                thread::spawn(move || {
+                    let adversary = (id == CORRUPTED).then(|| {
+                        let adversary = Adversary::corrupt::<Mersenne61>(&mut network);
+                        adversary.set(deviation);
+                        adversary
+                    });
+                    protocol(id, &mut network, adversary.as_ref())
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .enumerate()
+            .filter_map(|(id, handle)| {
+                let output = handle.join().unwrap();
+                (id != CORRUPTED).then_some(output)
+            })
+            .collect()
+    }
+
+    /// Opens a secret shared among the parties by gathering the shares of every party.
+    fn open(
+        shares: &[ShamirShare<Mersenne61>],
+        id: usize,
+        network: &mut Network,
+    ) -> Result<Mersenne61, ReconstructionError> {
+        let gathered = network.broadcast_and_gather_values(&shares[id]).unwrap();
+        reconstruct_secret_from_parties(gathered.into_iter().enumerate().collect())
+    }
+
+    #[test]
+    fn wrong_shares_are_detected() {
+        let secret = Mersenne61::random(&mut thread_rng());
+        let shares = compute_shamir_share(&secret, N_PARTIES, THRESHOLD, &mut thread_rng());
+
+        let honest_shares = shares.clone();
+        let outputs = run_with_adversary(Deviation::Honest, move |id, network, _| {
+            open(&honest_shares, id, network)
+        });
+        assert!(outputs.iter().all(|output| output == &Ok(secret.clone())));
+
+        let outputs = run_with_adversary(Deviation::WrongShares, move |id, network, _| {
+            open(&shares, id, network)
+        });
+        for output in outputs {
+            assert_eq!(
+                output,
+                Err(ReconstructionError::InconsistentShares(THRESHOLD))
+            );
+        }
+    }
+
+    #[test]
+    fn equivocation_is_caught_by_the_reliable_broadcast() {
+        const VICTIM: usize = 1;
+
+        let outputs = run_with_adversary(Deviation::Equivocate(VICTIM), |id, network, _| {
+            let message = (id == CORRUPTED).then(|| vec![4, 2]);
+            reliable_broadcast(network, CORRUPTED, 0, message, THRESHOLD).unwrap()
+        });
+        // The victim delivers the value sent to the rest of the parties, not the one it received.
+        assert_eq!(outputs, vec![vec![4, 2]; N_PARTIES - 1]);
+    }
+
+    #[test]
+    fn stale_rounds_are_caught() {
+        let outputs = run_with_adversary(Deviation::Honest, |id, network, adversary| {
+            // The adversary starts replaying stale messages on command after the first instance.
+            let message = (id == 0).then(|| vec![7]);
+            let first = reliable_broadcast(network, 0, 0, message.clone(), THRESHOLD).unwrap();
+            if let Some(adversary) = adversary {
+                adversary.set(Deviation::StaleRounds);
+            }
+            let second = reliable_broadcast(network, 0, 1, message, THRESHOLD).unwrap();
+            (first, second)
+        });
+        // The broadcast discards the messages of earlier instances.
+        assert_eq!(outputs, vec![(vec![7], vec![7]); N_PARTIES - 1]);
+
+        let outputs = run_with_adversary(Deviation::StaleRounds, |id, network, _| {
+            if id == CORRUPTED {
+                for round in 0..2 {
+                    network
+                        .send_to(&Packet::tagged(7, round, vec![round as u8]), 0)
+                        .unwrap();
+                }
+                return true;
+            }
+            if id != 0 {
+                return true;
+            }
+            let first = network.recv_tagged(CORRUPTED, 7, 0).unwrap();
+            assert_eq!(first.as_slice(), &[0]);
+            let err = network.recv_tagged(CORRUPTED, 7, 1).unwrap_err();
+            matches!(
+                err.downcast_ref::<ChannelError>(),
+                Some(ChannelError::StalePacket { round: 0, .. })
+            )
+        });
+        assert!(outputs.iter().all(|caught| *caught));
+    }
+}
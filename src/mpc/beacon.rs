@@ -0,0 +1,342 @@
+use std::sync::Mutex;
+
+use aws_lc_rs::digest::{self, SHA256};
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::{
+    hex::{decode_hex, encode_hex},
+    net::{
+        agreement::agree_on_values,
+        deadline::{missing_parties, DeadlineError},
+        value::{decode_value, encode_value},
+        Network, Packet,
+    },
+};
+
+/// Protocol ID of the packets of the reveals of [`run_beacon_round`].
+pub const BEACON_REVEAL_PROTOCOL_ID: u16 = 4;
+
+/// Size in bytes of the contribution of a party to a beacon value.
+const CONTRIBUTION_SIZE: usize = 32;
+
+/// Prefix of the commitments to the contributions.
+const COMMITMENT_DOMAIN: &[u8] = b"shami-rs beacon commitment";
+
+/// Prefix of the hash of the contributions that gives the beacon value.
+const VALUE_DOMAIN: &[u8] = b"shami-rs beacon value";
+
+/// Maximum number of beacon values kept by a [`BeaconLog`], beyond which the oldest ones are
+/// forgotten.
+const MAX_RECORDS: usize = 1 << 16;
+
+/// Possible errors that may appear while producing a beacon value.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BeaconError {
+    /// Some parties revealed a contribution that does not match their commitment.
+    #[error("the parties {parties:?} revealed contributions to round {round} that do not match their commitments")]
+    InvalidReveal { round: u32, parties: Vec<usize> },
+}
+
+/// Beacon value of a round together with its transcript, from which anybody can check the value:
+/// the commitment of every party, and the contribution that it revealed. Every byte string is
+/// written in hexadecimal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BeaconRecord {
+    /// Round of the beacon.
+    pub round: u32,
+    /// Beacon value, which is the hash of the contributions of every party.
+    pub value: String,
+    /// Commitments to the contributions, indexed by party ID.
+    pub commitments: Vec<String>,
+    /// Revealed contributions, indexed by party ID.
+    pub contributions: Vec<String>,
+}
+
+impl BeaconRecord {
+    /// Tells whether the contributions match the commitments and the value is their hash.
+    pub fn verify(&self) -> bool {
+        let Some(contributions) = self
+            .contributions
+            .iter()
+            .map(|contribution| decode_hex(contribution))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+        self.commitments.len() == contributions.len()
+            && contributions.iter().enumerate().zip(&self.commitments).all(
+                |((party_id, contribution), commitment)| {
+                    encode_hex(&commit(self.round, party_id, contribution)) == *commitment
+                },
+            )
+            && encode_hex(&beacon_value(self.round, &contributions)) == self.value
+    }
+}
+
+/// Returns the commitment of a party to its contribution to a round. The contributions are
+/// random strings of 32 bytes, so the commitment hides them without a separate salt.
+fn commit(round: u32, party_id: usize, contribution: &[u8]) -> Vec<u8> {
+    let mut context = digest::Context::new(&SHA256);
+    context.update(COMMITMENT_DOMAIN);
+    context.update(&round.to_le_bytes());
+    context.update(&(party_id as u64).to_le_bytes());
+    context.update(contribution);
+    context.finish().as_ref().to_vec()
+}
+
+/// Returns the beacon value of a round from the contributions of every party.
+fn beacon_value(round: u32, contributions: &[Vec<u8>]) -> Vec<u8> {
+    let mut context = digest::Context::new(&SHA256);
+    context.update(VALUE_DOMAIN);
+    context.update(&round.to_le_bytes());
+    context.update(&(contributions.len() as u64).to_le_bytes());
+    for contribution in contributions {
+        context.update(contribution);
+    }
+    context.finish().as_ref().to_vec()
+}
+
+/// Runs a round of the beacon by commit-reveal coin tossing in the session of the network given
+/// by the round, which must increase between rounds. Every party commits to a random
+/// contribution, and the parties agree on the set of commitments, so all the honest parties hold
+/// the same ones. Then every party reveals its contribution, and the beacon
+/// value is the hash of all of them, which is uniform as long as one party is honest, since the
+/// contributions are fixed before any of them is revealed.
+///
+/// The round fails, naming the cheaters, if a commitment is left out of the agreement, or a
+/// revealed contribution does not match its commitment or does not arrive before the round
+/// deadline of the network. A party can thus
+/// abort a round after learning the value, which is the known limitation of commit-reveal.
+pub fn run_beacon_round<R>(
+    round: u32,
+    rng: &mut R,
+    network: &mut Network,
+) -> anyhow::Result<BeaconRecord>
+where
+    R: Rng + CryptoRng,
+{
+    network.start_session(round)?;
+    let id = network.id();
+    let n_parties = network.n_parties();
+    let mut contribution = vec![0; CONTRIBUTION_SIZE];
+    rng.fill_bytes(&mut contribution);
+
+    // The agreement on the commitments tolerates as many corruptions as the number of parties
+    // allows, and leaves out the parties whose commitment misses the round deadline.
+    let deadline = network.round_deadline();
+    let commitments = agree_on_values(
+        network,
+        0,
+        commit(round, id, &contribution),
+        (n_parties - 1) / 3,
+        deadline.duration(),
+    )?;
+    let missing = missing_parties(&commitments);
+    if !missing.is_empty() {
+        anyhow::bail!(DeadlineError::MissingParties {
+            round,
+            deadline: deadline.duration(),
+            missing,
+        });
+    }
+    let commitments: Vec<Vec<u8>> = commitments.into_iter().flatten().collect();
+    let codec = network.codec();
+    let packet = Packet::tagged(
+        BEACON_REVEAL_PROTOCOL_ID,
+        round,
+        encode_value(codec, &contribution)?.into_payload(),
+    );
+    let gathered = network.broadcast_and_gather_within(&packet, deadline.duration())?;
+    let missing = missing_parties(&gathered);
+    if !missing.is_empty() {
+        anyhow::bail!(DeadlineError::MissingParties {
+            round,
+            deadline: deadline.duration(),
+            missing,
+        });
+    }
+    // The reveals that can not be decoded are taken as empty, so they do not match.
+    let contributions: Vec<Vec<u8>> = gathered
+        .into_iter()
+        .flatten()
+        .map(|packet| decode_value(codec, &packet).unwrap_or_default())
+        .collect();
+    let invalid: Vec<usize> = contributions
+        .iter()
+        .zip(&commitments)
+        .enumerate()
+        .filter(|(party_id, (contribution, commitment))| {
+            contribution.len() != CONTRIBUTION_SIZE
+                || commit(round, *party_id, contribution) != **commitment
+        })
+        .map(|(party_id, _)| party_id)
+        .collect();
+    if !invalid.is_empty() {
+        anyhow::bail!(BeaconError::InvalidReveal {
+            round,
+            parties: invalid,
+        });
+    }
+
+    let record = BeaconRecord {
+        round,
+        value: encode_hex(&beacon_value(round, &contributions)),
+        commitments: commitments
+            .iter()
+            .map(|commitment| encode_hex(commitment))
+            .collect(),
+        contributions: contributions
+            .iter()
+            .map(|contribution| encode_hex(contribution))
+            .collect(),
+    };
+    log::info!("beacon value of round {round}: {}", record.value);
+    Ok(record)
+}
+
+/// Beacon values published by a party, which are served by the HTTP API of the beacon.
+#[derive(Debug, Default)]
+pub struct BeaconLog {
+    /// Records of the beacon values, in increasing order of round.
+    records: Mutex<Vec<BeaconRecord>>,
+}
+
+impl BeaconLog {
+    /// Returns an empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publishes the beacon value of a round.
+    pub fn push(&self, record: BeaconRecord) {
+        let mut records = self.records.lock().unwrap_or_else(|err| err.into_inner());
+        if records.len() == MAX_RECORDS {
+            records.remove(0);
+        }
+        records.push(record);
+    }
+
+    /// Returns the last beacon value, if any was published.
+    pub fn latest(&self) -> Option<BeaconRecord> {
+        let records = self.records.lock().unwrap_or_else(|err| err.into_inner());
+        records.last().cloned()
+    }
+
+    /// Returns the beacon value of a round, if it was published and it was not forgotten.
+    pub fn get(&self, round: u32) -> Option<BeaconRecord> {
+        let records = self.records.lock().unwrap_or_else(|err| err.into_inner());
+        records
+            .binary_search_by_key(&round, |record| record.round)
+            .ok()
+            .map(|index| records[index].clone())
+    }
+}
+
+/// Answers a request to the HTTP API of a beacon, returning the status code, the reason phrase
+/// and the JSON body of the response, as [`route`](super::http::route) does for the jobs of a
+/// daemon. The API has two routes: `GET /beacon/latest` returns the last beacon value, and
+/// `GET /beacon/{round}` returns the beacon value of a round, both with their transcripts.
+pub fn route(log: &BeaconLog, method: &str, target: &str) -> (u16, &'static str, Value) {
+    let path = target.split_once('?').map_or(target, |(path, _)| path);
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let record = match (method, segments.as_slice()) {
+        ("GET", ["beacon", "latest"]) => log.latest(),
+        ("GET", ["beacon", round]) => match round.parse() {
+            Ok(round) => log.get(round),
+            Err(_) => {
+                return (
+                    400,
+                    "Bad Request",
+                    json!({ "error": format!("the round {round} is not correct") }),
+                )
+            }
+        },
+        (_, ["beacon", _]) => {
+            return (
+                405,
+                "Method Not Allowed",
+                json!({ "error": "the method is not allowed" }),
+            )
+        }
+        _ => {
+            return (
+                404,
+                "Not Found",
+                json!({ "error": "the path does not exist" }),
+            )
+        }
+    };
+    match record.map(serde_json::to_value) {
+        Some(Ok(record)) => (200, "OK", record),
+        Some(Err(err)) => (
+            500,
+            "Internal Server Error",
+            json!({ "error": err.to_string() }),
+        ),
+        None => (
+            404,
+            "Not Found",
+            json!({ "error": "the beacon value was not published" }),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{route, run_beacon_round, BeaconLog};
+    use crate::net::Network;
+
+    #[test]
+    fn beacon_rounds_over_local_network() {
+        let handles: Vec<_> = Network::create_local(4)
+            .into_iter()
+            .map(|mut network| {
+                thread::spawn(move || {
+                    (1..=3)
+                        .map(|round| run_beacon_round(round, &mut thread_rng(), &mut network))
+                        .collect::<anyhow::Result<Vec<_>>>()
+                        .unwrap()
+                })
+            })
+            .collect();
+        let records: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        for party_records in &records {
+            assert_eq!(*party_records, records[0]);
+        }
+        assert_ne!(records[0][0].value, records[0][1].value);
+
+        let log = BeaconLog::new();
+        for record in &records[0] {
+            assert!(record.verify());
+            log.push(record.clone());
+        }
+        let mut forged = records[0][1].clone();
+        forged.contributions[2] = forged.contributions[3].clone();
+        assert!(!forged.verify());
+        let mut forged = records[0][1].clone();
+        forged.round = 5;
+        assert!(!forged.verify());
+
+        let (code, _, body) = route(&log, "GET", "/beacon/latest");
+        assert_eq!(code, 200);
+        assert_eq!(body["round"], 3);
+        let (code, _, body) = route(&log, "GET", "/beacon/2");
+        assert_eq!(code, 200);
+        assert_eq!(body["value"], records[0][1].value.as_str());
+        assert_eq!(route(&log, "GET", "/beacon/7").0, 404);
+        assert_eq!(route(&log, "GET", "/beacon/x").0, 400);
+        assert_eq!(route(&log, "POST", "/beacon/latest").0, 405);
+        assert_eq!(route(&log, "GET", "/jobs/1").0, 404);
+        assert_eq!(route(&BeaconLog::new(), "GET", "/beacon/latest").0, 404);
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use rand::{CryptoRng, Rng};
+
+use super::{
+    circuit::{Circuit, Gate},
+    engine::Engine,
+    mimc::Mimc,
+    reconstruct_secret, run_batch_multiply_protocol,
+    share::ShamirShare,
+    share_inputs,
+};
+use crate::{
+    math::FiniteField,
+    net::{stats::NetworkStats, Network},
+    transport::Transport,
+};
+
+/// Parameters of a benchmark of the protocols.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchConfig {
+    /// Number of multiplications of shared values.
+    pub multiplications: usize,
+    /// Number of shared values opened to every party.
+    pub openings: usize,
+    /// Number of multiplications or openings that are computed together with a single exchange
+    /// of shares. If it is `None`, all of them are computed together.
+    pub batch_size: Option<usize>,
+    /// Number of shared messages encrypted with MiMC under a shared key, all of them in the same
+    /// circuit. If it is zero, the phase is skipped.
+    pub mimc: usize,
+}
+
+impl BenchConfig {
+    /// Returns the size of the batches in which `n_operations` operations are split.
+    fn batch_size(&self, n_operations: usize) -> usize {
+        self.batch_size.unwrap_or(n_operations).max(1)
+    }
+
+    /// Returns the number of products of the benchmark in the field `T`, which is the number of
+    /// Beaver triples that it consumes in the two-party mode.
+    pub fn n_products<T: FiniteField>(&self) -> usize {
+        let mimc = if self.mimc > 0 {
+            mimc_circuit::<T>(self.mimc).n_products()
+        } else {
+            0
+        };
+        self.multiplications + mimc
+    }
+}
+
+/// Returns the circuit that encrypts `n_messages` messages with MiMC under the same key, whose
+/// output is the sum of the ciphertexts, so the products of their rounds are multiplied
+/// together. The key is the input 0 of the party 0, and the messages are its next inputs.
+fn mimc_circuit<T: FiniteField>(n_messages: usize) -> Circuit {
+    let mimc = Mimc::<T>::new();
+    let mut circuit = Circuit::default();
+    let key = circuit.push(Gate::Input { party: 0, index: 0 });
+    let mut output = None;
+    for index in 1..=n_messages {
+        let message = circuit.push(Gate::Input { party: 0, index });
+        let ciphertext = mimc.push_encryption(&mut circuit, key, message);
+        output = Some(match output {
+            Some(output) => circuit.push(Gate::Add(output, ciphertext)),
+            None => ciphertext,
+        });
+    }
+    circuit
+}
+
+/// Measurements of a phase of a benchmark.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseReport {
+    /// Name of the phase.
+    pub name: &'static str,
+    /// Wall-clock time of the phase.
+    pub elapsed: Duration,
+    /// Number of communication rounds of the protocols of the phase, i.e. the number of
+    /// exchanges of shares.
+    pub rounds: usize,
+    /// Payload bytes sent to each peer during the phase, indexed by the ID of the peer.
+    pub bytes_sent: Vec<u64>,
+    /// Payload bytes received from each peer during the phase, indexed by the ID of the peer.
+    pub bytes_received: Vec<u64>,
+}
+
+impl PhaseReport {
+    /// Builds the report of a phase from the statistics of the network at its start and at its
+    /// end.
+    fn new(
+        name: &'static str,
+        elapsed: Duration,
+        rounds: usize,
+        start: &NetworkStats,
+        end: &NetworkStats,
+    ) -> Self {
+        let bytes_sent = start
+            .peers
+            .iter()
+            .zip(&end.peers)
+            .map(|(start, end)| end.bytes_sent - start.bytes_sent)
+            .collect();
+        let bytes_received = start
+            .peers
+            .iter()
+            .zip(&end.peers)
+            .map(|(start, end)| end.bytes_received - start.bytes_received)
+            .collect();
+        Self {
+            name,
+            elapsed,
+            rounds,
+            bytes_sent,
+            bytes_received,
+        }
+    }
+}
+
+/// Measurements of every phase of a benchmark executed by a party.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchReport {
+    /// ID of the party that executed the benchmark.
+    pub party: usize,
+    /// Measurements of the phases in the order in which they were executed.
+    pub phases: Vec<PhaseReport>,
+}
+
+impl fmt::Display for BenchReport {
+    /// Writes the report as a table with a row per phase, followed by a table with the bytes
+    /// sent and received from each peer in every phase.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "benchmark of party {}", self.party)?;
+        writeln!(
+            f,
+            "{:<16}{:>12}{:>10}{:>14}{:>16}",
+            "phase", "time (ms)", "rounds", "bytes sent", "bytes received"
+        )?;
+        for phase in &self.phases {
+            writeln!(
+                f,
+                "{:<16}{:>12.3}{:>10}{:>14}{:>16}",
+                phase.name,
+                phase.elapsed.as_secs_f64() * 1000.0,
+                phase.rounds,
+                phase.bytes_sent.iter().sum::<u64>(),
+                phase.bytes_received.iter().sum::<u64>(),
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "bytes sent/received per peer")?;
+        write!(f, "{:<16}", "peer")?;
+        for phase in &self.phases {
+            write!(f, "{:>24}", phase.name)?;
+        }
+        let n_peers = self
+            .phases
+            .first()
+            .map_or(0, |phase| phase.bytes_sent.len());
+        for peer in 0..n_peers {
+            writeln!(f)?;
+            write!(f, "{peer:<16}")?;
+            for phase in &self.phases {
+                let bytes = format!("{}/{}", phase.bytes_sent[peer], phase.bytes_received[peer]);
+                write!(f, "{bytes:>24}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs a benchmark of the protocols in three phases: the sharing of random values by every
+/// party, the multiplication of pairs of the shared values, and the opening of the products,
+/// followed by the encryption of shared values with MiMC if the configuration asks for it. The
+/// time and the number of rounds are measured for every phase, together with the traffic with
+/// each peer, which is taken from the statistics of the network and also recorded as the phases
+/// of the network. Every party must run the benchmark with the same parameters.
+pub fn run_benchmark<T, R>(
+    config: &BenchConfig,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<Network>,
+) -> anyhow::Result<BenchReport>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    let n_parties = engine.n_parties();
+    // The MiMC phase takes one more value as the key.
+    let n_values = config
+        .multiplications
+        .max(config.openings)
+        .max(config.mimc + usize::from(config.mimc > 0));
+    let mut phases = Vec::with_capacity(4);
+
+    // Every party shares random values, and the pairs of factors are taken from the values of
+    // two different parties.
+    engine.network_mut().begin_phase("sharing");
+    let start = (Instant::now(), engine.network().stats().clone());
+    let values: Vec<T> = (0..n_values).map(|_| T::random(rng)).collect();
+    let own_shares = share_inputs(&values, n_parties, threshold, rng);
+    let shares: Vec<Vec<ShamirShare<T>>> = engine.exchange_values(&own_shares)?;
+    phases.push(PhaseReport::new(
+        "sharing",
+        start.0.elapsed(),
+        1,
+        &start.1,
+        engine.network().stats(),
+    ));
+    let factors: Vec<_> = (0..n_values)
+        .map(|k| (shares[0][k].clone(), shares[1 % n_parties][k].clone()))
+        .collect();
+
+    engine.network_mut().begin_phase("multiplication");
+    let start = (Instant::now(), engine.network().stats().clone());
+    let mut products = Vec::with_capacity(config.multiplications);
+    let batches =
+        factors[..config.multiplications].chunks(config.batch_size(config.multiplications));
+    let rounds = batches.len();
+    for batch in batches {
+        products.extend(run_batch_multiply_protocol(
+            batch, n_parties, threshold, rng, engine,
+        )?);
+    }
+    phases.push(PhaseReport::new(
+        "multiplication",
+        start.0.elapsed(),
+        rounds,
+        &start.1,
+        engine.network().stats(),
+    ));
+
+    // The products are opened first, followed by the shared values if there are more openings.
+    let to_open: Vec<_> = products
+        .into_iter()
+        .chain(
+            factors[config.multiplications..]
+                .iter()
+                .map(|pair| pair.0.clone()),
+        )
+        .take(config.openings)
+        .collect();
+    engine.network_mut().begin_phase("opening");
+    let start = (Instant::now(), engine.network().stats().clone());
+    let batches = to_open.chunks(config.batch_size(config.openings));
+    let rounds = batches.len();
+    for batch in batches {
+        let opened: Vec<Vec<ShamirShare<T>>> =
+            engine.broadcast_and_gather_values(&batch.to_vec())?;
+        for k in 0..batch.len() {
+            let shares = opened
+                .iter()
+                .map(|shares| shares.get(k).cloned())
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| anyhow::anyhow!("a party sent fewer shares than expected"))?;
+            reconstruct_secret(shares);
+        }
+    }
+    phases.push(PhaseReport::new(
+        "opening",
+        start.0.elapsed(),
+        rounds,
+        &start.1,
+        engine.network().stats(),
+    ));
+
+    if config.mimc > 0 {
+        let circuit = mimc_circuit::<T>(config.mimc);
+        let mut inputs = vec![Vec::new(); n_parties];
+        inputs[0] = std::iter::once(factors[config.mimc].1.clone())
+            .chain(factors[..config.mimc].iter().map(|pair| pair.0.clone()))
+            .collect();
+
+        engine.network_mut().begin_phase("mimc");
+        let start = (Instant::now(), engine.network().stats().clone());
+        circuit.evaluate(&inputs, threshold, rng, engine)?;
+        phases.push(PhaseReport::new(
+            "mimc",
+            start.0.elapsed(),
+            circuit.n_rounds(),
+            &start.1,
+            engine.network().stats(),
+        ));
+    }
+    engine.network_mut().end_phase();
+
+    Ok(BenchReport {
+        party: engine.id(),
+        phases,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{run_benchmark, BenchConfig};
+    use crate::{math::mersenne61::Mersenne61, mpc::engine::Engine, net::Network};
+
+    fn run(config: BenchConfig) -> Vec<super::BenchReport> {
+        let handles: Vec<_> = Network::create_local(3)
+            .into_iter()
+            .map(|network| {
+                let config = config.clone();
+                thread::spawn(move || {
+                    let mut engine = Engine::new(network);
+                    let report =
+                        run_benchmark::<Mersenne61, _>(&config, 1, &mut thread_rng(), &mut engine)
+                            .unwrap();
+                    // The network measures the same traffic in its phases.
+                    let phases = engine.network().phases();
+                    assert_eq!(phases.len(), report.phases.len());
+                    for (phase, measured) in report.phases.iter().zip(phases) {
+                        assert_eq!(phase.name, measured.name);
+                        assert_eq!(phase.bytes_sent, measured.bytes_sent);
+                        assert_eq!(phase.bytes_received, measured.bytes_received);
+                    }
+                    report
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn benchmark_over_local_network() {
+        let batched = run(BenchConfig {
+            multiplications: 10,
+            openings: 12,
+            batch_size: None,
+            mimc: 0,
+        });
+        let names: Vec<_> = batched[0].phases.iter().map(|phase| phase.name).collect();
+        assert_eq!(names, ["sharing", "multiplication", "opening"]);
+        for report in &batched {
+            for phase in &report.phases {
+                assert_eq!(phase.rounds, 1);
+                assert_eq!(phase.bytes_sent.len(), 3);
+                assert!(phase.bytes_sent.iter().all(|bytes| *bytes > 0));
+            }
+        }
+
+        let sequential = run(BenchConfig {
+            multiplications: 10,
+            openings: 12,
+            batch_size: Some(4),
+            mimc: 0,
+        });
+        assert_eq!(sequential[1].phases[1].rounds, 3);
+        assert_eq!(sequential[1].phases[2].rounds, 3);
+        let table = sequential[1].to_string();
+        assert!(table.starts_with("benchmark of party 1\n"));
+        assert!(table.contains("multiplication"));
+
+        let mimc = run(BenchConfig {
+            multiplications: 0,
+            openings: 0,
+            batch_size: None,
+            mimc: 3,
+        });
+        let names: Vec<_> = mimc[0].phases.iter().map(|phase| phase.name).collect();
+        assert_eq!(names, ["sharing", "multiplication", "opening", "mimc"]);
+        assert_eq!(mimc[0].phases[3].rounds, 75);
+        let config = BenchConfig {
+            multiplications: 4,
+            openings: 0,
+            batch_size: None,
+            mimc: 3,
+        };
+        assert_eq!(config.n_products::<Mersenne61>(), 4 + 3 * 75);
+    }
+}
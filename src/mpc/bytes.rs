@@ -0,0 +1,208 @@
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use super::{
+    compute_shamir_share, reconstruct_secret_from_parties, share::ShamirShare, ReconstructionError,
+};
+use crate::math::{from_le_bytes, FieldValue, FiniteField};
+
+/// Possible errors that may appear while reconstructing a byte string from its shares.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ByteSharingError {
+    /// There are no shares from which to reconstruct the byte string.
+    #[error("there are no shares of the byte string")]
+    NoShares,
+
+    /// The shares belong to byte strings of different lengths.
+    #[error("the shares belong to byte strings of different lengths")]
+    LengthMismatch,
+
+    /// A share does not have one chunk for every chunk of the byte string.
+    #[error("the share of party {party_id} has {found} chunks, but a string of {len} bytes has {expected}")]
+    WrongChunks {
+        party_id: usize,
+        len: usize,
+        expected: usize,
+        found: usize,
+    },
+
+    /// A reconstructed chunk does not fit in the bytes of its chunk, so the shares were not
+    /// produced by [`share_bytes`].
+    #[error("the chunk {0} of the byte string is out of range")]
+    ChunkOutOfRange(usize),
+
+    /// The chunks of the byte string could not be reconstructed from the shares.
+    #[error(transparent)]
+    Reconstruction(#[from] ReconstructionError),
+}
+
+/// Share of a byte string held by a party. The string is split in chunks that fit in an element
+/// of the field `T`, which are 7 bytes for Mersenne61 and 15 bytes for the fields of 127 and 128
+/// bits, and each chunk is shared on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteShare<T> {
+    /// ID of the party that holds the share, whose shares are the evaluations at `party_id + 1`.
+    pub party_id: usize,
+    /// Length in bytes of the shared string.
+    pub len: usize,
+    /// Shares of the chunks of the string, in order.
+    pub chunks: Vec<ShamirShare<T>>,
+}
+
+/// Returns the number of bytes of a chunk of a byte string shared in the field `T`, which is the
+/// largest number of bytes whose values are all below the modulus.
+fn chunk_size<T: FiniteField>() -> usize {
+    (T::BIT_SIZE - 1) / 8
+}
+
+/// Shares a byte string among `n_parties` parties, so that any `threshold + 1` of them can
+/// reconstruct it with [`reconstruct_bytes`] and fewer learn nothing about it besides its
+/// length. The share in position `i` belongs to the party with ID `i`.
+pub fn share_bytes<T, R>(
+    secret: &[u8],
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+) -> Vec<ByteShare<T>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    let size = chunk_size::<T>();
+    let mut shares: Vec<ByteShare<T>> = (0..n_parties)
+        .map(|party_id| ByteShare {
+            party_id,
+            len: secret.len(),
+            chunks: Vec::with_capacity(secret.len().div_ceil(size)),
+        })
+        .collect();
+    for chunk in secret.chunks(size) {
+        let value: T = from_le_bytes(chunk);
+        for (share, chunk_share) in shares
+            .iter_mut()
+            .zip(compute_shamir_share(&value, n_parties, threshold, rng))
+        {
+            share.chunks.push(chunk_share);
+        }
+    }
+    shares
+}
+
+/// Reconstructs a byte string from the shares of a subset of the parties. At least
+/// `threshold + 1` shares are needed, and the shares beyond them must be consistent with the
+/// others, so a wrong share is caught if there is at least one more share than needed.
+pub fn reconstruct_bytes<T: FiniteField>(
+    shares: &[ByteShare<T>],
+) -> Result<Zeroizing<Vec<u8>>, ByteSharingError> {
+    let len = shares.first().ok_or(ByteSharingError::NoShares)?.len;
+    if shares.iter().any(|share| share.len != len) {
+        return Err(ByteSharingError::LengthMismatch);
+    }
+    let size = chunk_size::<T>();
+    let n_chunks = len.div_ceil(size);
+    if let Some(share) = shares.iter().find(|share| share.chunks.len() != n_chunks) {
+        return Err(ByteSharingError::WrongChunks {
+            party_id: share.party_id,
+            len,
+            expected: n_chunks,
+            found: share.chunks.len(),
+        });
+    }
+
+    let mut secret = Zeroizing::new(Vec::with_capacity(len));
+    for chunk in 0..n_chunks {
+        let value = reconstruct_secret_from_parties(
+            shares
+                .iter()
+                .map(|share| (share.party_id, share.chunks[chunk].clone()))
+                .collect(),
+        )?;
+        let mut bytes = Zeroizing::new(vec![0; T::ValueType::BYTES]);
+        value.value().write_le_bytes(&mut bytes);
+        let width = size.min(len - chunk * size);
+        if bytes[width..].iter().any(|byte| *byte != 0) {
+            return Err(ByteSharingError::ChunkOutOfRange(chunk));
+        }
+        secret.extend_from_slice(&bytes[..width]);
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{reconstruct_bytes, share_bytes, ByteShare, ByteSharingError};
+    use crate::{
+        math::{mersenne127::Mersenne127, mersenne61::Mersenne61, p128::P128, FiniteField},
+        mpc::ReconstructionError,
+    };
+
+    /// Shares random strings of every length up to a few chunks, and reconstructs them from
+    /// every window of `threshold + 1` consecutive parties.
+    fn roundtrip<T: FiniteField>() {
+        const N_PARTIES: usize = 5;
+        const THRESHOLD: usize = 2;
+
+        let mut rng = StdRng::seed_from_u64(1727);
+        for len in 0..48 {
+            let secret: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            let shares: Vec<ByteShare<T>> = share_bytes(&secret, N_PARTIES, THRESHOLD, &mut rng);
+            for first in 0..N_PARTIES - THRESHOLD {
+                let subset = &shares[first..=first + THRESHOLD];
+                assert_eq!(*reconstruct_bytes(subset).unwrap(), secret);
+            }
+        }
+    }
+
+    #[test]
+    fn bytes_roundtrip_in_every_field() {
+        roundtrip::<Mersenne61>();
+        roundtrip::<Mersenne127>();
+        roundtrip::<P128>();
+    }
+
+    #[test]
+    fn wrong_shares_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(1727);
+        let key = [0xa5; 32];
+        let shares: Vec<ByteShare<Mersenne61>> = share_bytes(&key, 4, 1, &mut rng);
+
+        assert_eq!(
+            reconstruct_bytes(&shares[..1]),
+            Err(ByteSharingError::Reconstruction(
+                ReconstructionError::NotEnoughShares {
+                    degree: 1,
+                    received: 1
+                }
+            ))
+        );
+        assert_eq!(
+            reconstruct_bytes::<Mersenne61>(&[]),
+            Err(ByteSharingError::NoShares)
+        );
+
+        let mut tampered = shares.clone();
+        tampered[2].chunks[3] = tampered[2].chunks[3].add_const(&Mersenne61::ONE);
+        assert_eq!(
+            reconstruct_bytes(&tampered[..3]),
+            Err(ByteSharingError::Reconstruction(
+                ReconstructionError::InconsistentShares(1)
+            ))
+        );
+
+        let mut truncated = shares;
+        truncated[1].chunks.pop();
+        assert!(matches!(
+            reconstruct_bytes(&truncated),
+            Err(ByteSharingError::WrongChunks {
+                party_id: 1,
+                expected: 5,
+                found: 4,
+                ..
+            })
+        ));
+    }
+}
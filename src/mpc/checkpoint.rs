@@ -0,0 +1,268 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    circuit::{Circuit, CircuitState},
+    share::ShamirShare,
+};
+use crate::{math::FiniteField, transport::Transport};
+
+/// Possible errors that may appear while resuming a protocol from a checkpoint.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CheckpointError {
+    /// The checkpoint was written by another party.
+    #[error("the checkpoint belongs to party {found}, but this is party {expected}")]
+    WrongParty { expected: usize, found: usize },
+
+    /// The checkpoint was written while evaluating another circuit.
+    #[error("the checkpoint belongs to a different computation")]
+    WrongCircuit,
+
+    /// The checkpoints of the parties were written while evaluating different circuits.
+    #[error("the checkpoints of the parties belong to different computations")]
+    Diverged,
+}
+
+/// State of a party at the boundary between two rounds of a protocol, written to disk so that
+/// the party can rejoin the other parties after a crash instead of starting over.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint<T> {
+    /// ID of the party that wrote the checkpoint.
+    pub party: usize,
+    /// Description of the circuit that the parties evaluate.
+    pub circuit: String,
+    /// Shares of the inputs of every party held by the party.
+    pub inputs: Vec<Vec<ShamirShare<T>>>,
+    /// Progress of the evaluation of the circuit.
+    pub state: CircuitState<T>,
+}
+
+impl<T: FiniteField> Checkpoint<T> {
+    /// Creates the checkpoint of a party that evaluates a circuit on the shares of the inputs.
+    pub fn new(
+        party: usize,
+        circuit: &Circuit,
+        inputs: Vec<Vec<ShamirShare<T>>>,
+        state: CircuitState<T>,
+    ) -> Self {
+        Self {
+            party,
+            circuit: circuit.description(),
+            inputs,
+            state,
+        }
+    }
+
+    /// Reads a checkpoint from a JSON file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("reading the checkpoint {}", path.display()))?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Writes the checkpoint to a JSON file. The checkpoint is first written to a temporary file
+    /// that then replaces the previous one, so a crash while writing never leaves a partial
+    /// checkpoint behind.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, serde_json::to_string(self)?)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// Checks that the checkpoint was written by the given party while evaluating the circuit.
+    pub fn check(&self, party: usize, circuit: &Circuit) -> Result<(), CheckpointError> {
+        if self.party != party {
+            return Err(CheckpointError::WrongParty {
+                expected: party,
+                found: self.party,
+            });
+        }
+        if self.circuit != circuit.description() {
+            return Err(CheckpointError::WrongCircuit);
+        }
+        Ok(())
+    }
+}
+
+/// Agrees with the other parties on the round from which the protocol resumes, given the
+/// checkpoint of the party, if it has one. The parties resume from the earliest round reached
+/// by any of them, since a party may crash right after the others finish a round. Returns `None`
+/// if some party has no checkpoint, in which case every party starts over from the sharing of
+/// the inputs.
+pub fn agree_on_round<T, N: Transport>(
+    network: &mut N,
+    checkpoint: Option<&Checkpoint<T>>,
+) -> anyhow::Result<Option<usize>> {
+    let progress =
+        checkpoint.map(|checkpoint| (checkpoint.circuit.clone(), checkpoint.state.round()));
+    let progresses: Vec<Option<(String, usize)>> =
+        network.broadcast_and_gather_values(&progress)?;
+    let Some(progresses) = progresses.into_iter().collect::<Option<Vec<_>>>() else {
+        return Ok(None);
+    };
+    if progresses
+        .iter()
+        .any(|(circuit, _)| *circuit != progresses[0].0)
+    {
+        anyhow::bail!(CheckpointError::Diverged);
+    }
+    Ok(progresses.into_iter().map(|(_, round)| round).min())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, thread};
+
+    use rand::thread_rng;
+
+    use super::{agree_on_round, Checkpoint, CheckpointError};
+    use crate::{
+        math::mersenne61::Mersenne61,
+        mpc::{
+            circuit::Circuit, engine::Engine, expression::Expression, reconstruct_secret,
+            share::ShamirShare, share_inputs,
+        },
+        net::Network,
+    };
+
+    #[test]
+    fn save_and_load_checkpoints() {
+        let circuit = Circuit::from_expression(&"x0 * x1".parse::<Expression>().unwrap());
+        let inputs = vec![
+            vec![ShamirShare::new(Mersenne61::from(3), 1)],
+            vec![ShamirShare::new(Mersenne61::from(4), 1)],
+        ];
+        let checkpoint = Checkpoint::new(1, &circuit, inputs, circuit.initial_state());
+        let path = env::temp_dir().join(format!("shami_rs_checkpoint_{}.json", std::process::id()));
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::<Mersenne61>::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, checkpoint);
+
+        assert_eq!(loaded.check(1, &circuit), Ok(()));
+        assert_eq!(
+            loaded.check(0, &circuit),
+            Err(CheckpointError::WrongParty {
+                expected: 0,
+                found: 1
+            })
+        );
+        let other = Circuit::from_expression(&"x0 + x1".parse::<Expression>().unwrap());
+        assert_eq!(loaded.check(1, &other), Err(CheckpointError::WrongCircuit));
+        assert!(Checkpoint::<Mersenne61>::load(&path).is_err());
+    }
+
+    #[test]
+    fn resume_from_checkpoints() {
+        const N_PARTIES: usize = 3;
+        const THRESHOLD: usize = 1;
+
+        let expression: Expression = "x0 * x1 * x2 * x0 + x1".parse().unwrap();
+        let circuit = Circuit::from_expression(&expression);
+        assert_eq!(circuit.n_rounds(), 3);
+
+        // Every party evaluates the circuit, keeping the checkpoint of each round. The party 2
+        // keeps the one written after two rounds as if it crashed during the third round, which
+        // the other parties finished.
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip([2u64, 3, 4])
+            .map(|(mut network, input)| {
+                let circuit = circuit.clone();
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let id = network.id();
+                    let own_shares =
+                        share_inputs(&[Mersenne61::from(input)], N_PARTIES, THRESHOLD, &mut rng);
+                    let shares: Vec<Vec<ShamirShare<Mersenne61>>> =
+                        network.exchange_values(&own_shares).unwrap();
+                    let mut checkpoints = Vec::new();
+                    circuit
+                        .evaluate_from(
+                            &shares,
+                            THRESHOLD,
+                            &mut rng,
+                            &mut Engine::new(&mut network),
+                            circuit.initial_state(),
+                            |state| {
+                                checkpoints.push(Checkpoint::new(
+                                    id,
+                                    &circuit,
+                                    shares.clone(),
+                                    state.clone(),
+                                ));
+                                Ok(())
+                            },
+                        )
+                        .unwrap();
+                    let crashed_round = if id == 2 { 1 } else { 2 };
+                    checkpoints.swap_remove(crashed_round)
+                })
+            })
+            .collect();
+        let checkpoints: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        // The parties resume over new connections from the earliest round.
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(checkpoints)
+            .map(|(mut network, mut checkpoint)| {
+                let circuit = circuit.clone();
+                thread::spawn(move || {
+                    checkpoint.check(network.id(), &circuit).unwrap();
+                    let round = agree_on_round(&mut network, Some(&checkpoint)).unwrap();
+                    assert_eq!(round, Some(2));
+                    checkpoint.state.rollback(&circuit, 2);
+                    circuit
+                        .evaluate_from(
+                            &checkpoint.inputs,
+                            THRESHOLD,
+                            &mut thread_rng(),
+                            &mut Engine::new(&mut network),
+                            checkpoint.state,
+                            |_| Ok(()),
+                        )
+                        .unwrap()
+                })
+            })
+            .collect();
+        let result_shares = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        assert_eq!(
+            reconstruct_secret(result_shares),
+            Mersenne61::from(2 * 3 * 4 * 2 + 3)
+        );
+
+        // A party without checkpoint makes every party start over.
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .map(|mut network| {
+                let circuit = circuit.clone();
+                thread::spawn(move || {
+                    let checkpoint = (network.id() != 0).then(|| {
+                        Checkpoint::<Mersenne61>::new(
+                            network.id(),
+                            &circuit,
+                            Vec::new(),
+                            circuit.initial_state(),
+                        )
+                    });
+                    agree_on_round(&mut network, checkpoint.as_ref()).unwrap()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), None);
+        }
+    }
+}
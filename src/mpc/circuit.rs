@@ -0,0 +1,971 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::Context;
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    engine::Engine,
+    expression::{Expression, ExpressionError},
+    run_batch_multiply_protocol,
+    share::ShamirShare,
+    start_batch_multiply_protocol, two_party,
+};
+use crate::{math::FiniteField, memory::SecretVec, trace, transport::Transport};
+
+/// Wire of a circuit, identified by the position of the gate that produces its value.
+pub type Wire = usize;
+
+/// Possible errors that may appear while parsing the description of a circuit.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CircuitError {
+    /// A line of the header is missing or does not have the expected numbers.
+    #[error("line {0} of the circuit is not a valid header")]
+    InvalidHeader(usize),
+
+    /// The circuit has more than one output value, or an output of more than one wire.
+    #[error("the circuit must have a single output of one wire")]
+    MultipleOutputs,
+
+    /// A line does not have the numbers of operands and outputs that it declares.
+    #[error("line {0} of the circuit is not a valid gate")]
+    InvalidGate(usize),
+
+    /// A gate has an operation that is not part of the format.
+    #[error("unknown operation {operation} in line {line} of the circuit")]
+    UnknownOperation { line: usize, operation: String },
+
+    /// A gate takes as operand a wire that no previous gate computes.
+    #[error("the wire {wire} in line {line} of the circuit is not computed before")]
+    UndefinedWire { line: usize, wire: u64 },
+
+    /// A gate computes a wire that was already computed.
+    #[error("the wire {wire} in line {line} of the circuit is computed twice")]
+    RedefinedWire { line: usize, wire: u64 },
+
+    /// The number of gates does not match the header.
+    #[error("the circuit declares {expected} gates, but it has {found}")]
+    GateCount { expected: u64, found: u64 },
+
+    /// No gate computes the output wire.
+    #[error("the output wire {0} of the circuit is not computed")]
+    MissingOutput(u64),
+}
+
+/// Number of batches of products that are in flight at the same time in a pipelined evaluation.
+/// With two, the shares of a batch are computed and sent while the previous one is in flight.
+const PIPELINE_DEPTH: usize = 2;
+
+/// Progress of the evaluation of a circuit at the boundary between two rounds, from which the
+/// evaluation can be resumed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitState<T> {
+    /// Number of rounds already evaluated, which is the next round to evaluate.
+    round: usize,
+    /// Shares of the values of the wires computed so far.
+    wires: SecretVec<Option<ShamirShare<T>>>,
+}
+
+impl<T> CircuitState<T> {
+    /// Returns the number of rounds already evaluated.
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    /// Returns the share of the value of a wire, or `None` if it is not computed yet.
+    pub fn wire(&self, wire: Wire) -> Option<&ShamirShare<T>> {
+        self.wires.get(wire)?.as_ref()
+    }
+
+    /// Goes back to the boundary before the given round of the circuit, forgetting the wires
+    /// computed from that round on. The products of those rounds are computed again with fresh
+    /// randomness when the evaluation resumes. Going forward has no effect.
+    pub fn rollback(&mut self, circuit: &Circuit, round: usize) {
+        if round >= self.round {
+            return;
+        }
+        for (wire, wire_round) in circuit.rounds().into_iter().enumerate() {
+            if wire_round >= round {
+                self.wires[wire] = None;
+            }
+        }
+        self.round = round;
+    }
+}
+
+/// Gate of an arithmetic circuit. The gates only take as operands the wires of previous gates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Gate {
+    /// Input at the given index of the party with the given ID.
+    Input { party: usize, index: usize },
+    /// Public constant.
+    Constant(u64),
+    /// Sum of two wires.
+    Add(Wire, Wire),
+    /// Difference of two wires.
+    Subtract(Wire, Wire),
+    /// Product of two wires.
+    Multiply(Wire, Wire),
+}
+
+/// Arithmetic circuit whose gates are sorted in topological order. The output of the circuit is
+/// the wire of the last gate. Besides building it from an [`Expression`], a circuit can be
+/// parsed from the text format of [`Circuit::from_str`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Circuit {
+    /// Gates of the circuit.
+    gates: Vec<Gate>,
+}
+
+impl Circuit {
+    /// Reads a circuit from a file in the arithmetic variant of the Bristol Fashion format, as
+    /// parsed by [`Circuit::from_str`].
+    pub fn read_file(path: &Path) -> anyhow::Result<Self> {
+        let description = fs::read_to_string(path)
+            .with_context(|| format!("reading the circuit {}", path.display()))?;
+        Ok(description.parse()?)
+    }
+
+    /// Builds the circuit that computes an expression.
+    pub fn from_expression(expression: &Expression) -> Self {
+        let mut circuit = Self::default();
+        circuit.push_expression(expression);
+        circuit
+    }
+
+    /// Appends the gates that compute an expression, returning the wire of its value.
+    fn push_expression(&mut self, expression: &Expression) -> Wire {
+        let gate = match expression {
+            Expression::Input { party, index } => Gate::Input {
+                party: *party,
+                index: *index,
+            },
+            Expression::Constant(constant) => Gate::Constant(*constant),
+            Expression::Add(left, right) => {
+                Gate::Add(self.push_expression(left), self.push_expression(right))
+            }
+            Expression::Subtract(left, right) => {
+                Gate::Subtract(self.push_expression(left), self.push_expression(right))
+            }
+            Expression::Multiply(left, right) => {
+                Gate::Multiply(self.push_expression(left), self.push_expression(right))
+            }
+        };
+        self.push(gate)
+    }
+
+    /// Appends a gate to the circuit, returning the wire of its value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an operand of the gate is not the wire of a previous gate.
+    pub fn push(&mut self, gate: Gate) -> Wire {
+        if let Gate::Add(left, right) | Gate::Subtract(left, right) | Gate::Multiply(left, right) =
+            gate
+        {
+            assert!(
+                left < self.gates.len() && right < self.gates.len(),
+                "the operands of a gate must be previous wires"
+            );
+        }
+        self.gates.push(gate);
+        self.gates.len() - 1
+    }
+
+    /// Appends the gates that raise the value of a wire to the given exponent by square and
+    /// multiply, returning the wire of the power.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the exponent is zero.
+    pub fn push_power(&mut self, base: Wire, mut exponent: u128) -> Wire {
+        assert!(exponent != 0, "the exponent of a power must not be zero");
+        let mut result = None;
+        let mut square = base;
+        loop {
+            if exponent & 1 == 1 {
+                result = Some(match result {
+                    Some(result) => self.push(Gate::Multiply(result, square)),
+                    None => square,
+                });
+            }
+            exponent >>= 1;
+            if exponent == 0 {
+                break;
+            }
+            square = self.push(Gate::Multiply(square, square));
+        }
+        // The unwrap is safe because at least one bit of the exponent is set.
+        result.unwrap()
+    }
+
+    /// Returns the gates of the circuit.
+    pub fn gates(&self) -> &[Gate] {
+        &self.gates
+    }
+
+    /// Returns the wire of the output of the circuit, or `None` if the circuit is empty.
+    pub fn output(&self) -> Option<Wire> {
+        self.gates.len().checked_sub(1)
+    }
+
+    /// Checks that the circuit only refers to existing inputs, given the number of inputs of each
+    /// party.
+    pub fn check_inputs(&self, n_inputs: &[usize]) -> Result<(), ExpressionError> {
+        for gate in &self.gates {
+            if let Gate::Input { party, index } = gate {
+                if n_inputs.get(*party).is_none_or(|n| index >= n) {
+                    return Err(ExpressionError::UnknownInput {
+                        party: *party,
+                        index: *index,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the round in which the value of each wire is known. The inputs and constants are
+    /// known in round zero, and a product of two wires that depend on the inputs needs one round
+    /// of interaction after its operands are known, while the rest of gates are computed locally.
+    fn rounds(&self) -> Vec<usize> {
+        self.analyze().0
+    }
+
+    /// Returns whether the value of each wire is computed with interaction, which is the case of
+    /// the products of two wires that depend on the inputs.
+    fn interactive(&self) -> Vec<bool> {
+        let public = self.analyze().1;
+        self.gates
+            .iter()
+            .map(|gate| {
+                matches!(gate, Gate::Multiply(left, right) if !public[*left] && !public[*right])
+            })
+            .collect()
+    }
+
+    /// Returns the round in which the value of each wire is known, as in [`Circuit::rounds`], and
+    /// whether each wire only depends on public constants.
+    fn analyze(&self) -> (Vec<usize>, Vec<bool>) {
+        let mut rounds: Vec<usize> = Vec::with_capacity(self.gates.len());
+        let mut public = Vec::with_capacity(self.gates.len());
+        for gate in &self.gates {
+            let (round, is_public) = match gate {
+                Gate::Input { .. } => (0, false),
+                Gate::Constant(_) => (0, true),
+                Gate::Add(left, right) | Gate::Subtract(left, right) => (
+                    rounds[*left].max(rounds[*right]),
+                    public[*left] && public[*right],
+                ),
+                Gate::Multiply(left, right) => {
+                    let round = rounds[*left].max(rounds[*right]);
+                    if public[*left] || public[*right] {
+                        (round, public[*left] && public[*right])
+                    } else {
+                        (round + 1, false)
+                    }
+                }
+            };
+            rounds.push(round);
+            public.push(is_public);
+        }
+        (rounds, public)
+    }
+
+    /// Returns the number of products that need interaction to evaluate the circuit, which is
+    /// the number of Beaver triples that it consumes in the two-party mode.
+    pub fn n_products(&self) -> usize {
+        self.interactive()
+            .into_iter()
+            .filter(|interactive| *interactive)
+            .count()
+    }
+
+    /// Returns the number of rounds of interaction needed to evaluate the circuit, which is its
+    /// multiplicative depth without counting the products by public values.
+    pub fn n_rounds(&self) -> usize {
+        self.rounds().into_iter().max().unwrap_or(0)
+    }
+
+    /// Returns a description of the gates of the circuit, which is the same for every party that
+    /// evaluates the same circuit.
+    pub fn description(&self) -> String {
+        format!("{:?}", self.gates)
+    }
+
+    /// Evaluates the circuit in the clear on the inputs of the parties, indexed by party ID and
+    /// then by the position of the input. This is the reference with which the result of the
+    /// evaluation on shares can be compared, and an empty circuit evaluates to zero as there.
+    pub fn evaluate_plain<T: FiniteField>(&self, inputs: &[Vec<T>]) -> Result<T, ExpressionError> {
+        Ok(self.evaluate_plain_wires(inputs)?.pop().unwrap_or(T::ZERO))
+    }
+
+    /// Evaluates the circuit in the clear as in [`Circuit::evaluate_plain`], returning the value
+    /// of every wire instead of only the output.
+    pub fn evaluate_plain_wires<T: FiniteField>(
+        &self,
+        inputs: &[Vec<T>],
+    ) -> Result<Vec<T>, ExpressionError> {
+        let n_inputs: Vec<usize> = inputs.iter().map(Vec::len).collect();
+        self.check_inputs(&n_inputs)?;
+        let mut wires: Vec<T> = Vec::with_capacity(self.gates.len());
+        for gate in &self.gates {
+            let value = match gate {
+                Gate::Input { party, index } => inputs[*party][*index].clone(),
+                Gate::Constant(constant) => T::from(*constant),
+                Gate::Add(left, right) => wires[*left].add(&wires[*right]),
+                Gate::Subtract(left, right) => wires[*left].subtract(&wires[*right]),
+                Gate::Multiply(left, right) => wires[*left].multiply(&wires[*right]),
+            };
+            wires.push(value);
+        }
+        Ok(wires)
+    }
+
+    /// Returns the state of an evaluation of the circuit that has not started.
+    pub fn initial_state<T: FiniteField>(&self) -> CircuitState<T> {
+        CircuitState {
+            round: 0,
+            wires: vec![None; self.gates.len()].into(),
+        }
+    }
+
+    /// Evaluates the circuit on the shares of the inputs of the parties, indexed by party ID and
+    /// then by the position of the input, returning the share of the output. The gates are
+    /// evaluated round by round, and all the products of a round are computed with a single
+    /// execution of the batched multiplication protocol, so every party must evaluate the same
+    /// circuit.
+    pub fn evaluate<T, R, N>(
+        &self,
+        inputs: &[Vec<ShamirShare<T>>],
+        threshold: usize,
+        rng: &mut R,
+        engine: &mut Engine<N>,
+    ) -> anyhow::Result<ShamirShare<T>>
+    where
+        T: FiniteField,
+        R: Rng + CryptoRng,
+        N: Transport,
+    {
+        self.evaluate_from(inputs, threshold, rng, engine, self.initial_state(), |_| {
+            Ok(())
+        })
+    }
+
+    /// Evaluates the circuit as in [`Circuit::evaluate`], starting from the given state instead
+    /// of the first round. The function `on_round` is called with the state reached at the end
+    /// of every round, e.g. to write a checkpoint from which the evaluation can be resumed.
+    pub fn evaluate_from<T, R, N, F>(
+        &self,
+        inputs: &[Vec<ShamirShare<T>>],
+        threshold: usize,
+        rng: &mut R,
+        engine: &mut Engine<N>,
+        mut state: CircuitState<T>,
+        mut on_round: F,
+    ) -> anyhow::Result<ShamirShare<T>>
+    where
+        T: FiniteField,
+        R: Rng + CryptoRng,
+        N: Transport,
+        F: FnMut(&CircuitState<T>) -> anyhow::Result<()>,
+    {
+        let n_inputs: Vec<usize> = inputs.iter().map(Vec::len).collect();
+        self.check_inputs(&n_inputs)?;
+        let Some(output) = self.output() else {
+            return Ok(ShamirShare::new(T::ZERO, 0));
+        };
+        if state.wires.len() != self.gates.len() {
+            anyhow::bail!("the state does not belong to the circuit");
+        }
+
+        let rounds = self.rounds();
+        let n_rounds = rounds.iter().copied().max().unwrap_or(0);
+        log::info!(
+            "evaluating a circuit of {} gates in {n_rounds} rounds from round {}",
+            self.gates.len(),
+            state.round
+        );
+        for round in state.round..=n_rounds {
+            let _span = trace::span(format!("round {round}"));
+            let wires = &mut state.wires;
+            // The products that need interaction in this round only depend on wires of previous
+            // rounds, so they are computed together first.
+            let (interactive, factors): (Vec<Wire>, Vec<_>) = self
+                .gates
+                .iter()
+                .enumerate()
+                .filter_map(|(wire, gate)| match gate {
+                    Gate::Multiply(left, right)
+                        if rounds[wire] == round && rounds[*left].max(rounds[*right]) < round =>
+                    {
+                        Some((wire, (wire_value(wires, *left), wire_value(wires, *right))))
+                    }
+                    _ => None,
+                })
+                .unzip();
+            if !interactive.is_empty() {
+                log::debug!(
+                    "multiplying {} pairs of wires in round {round}",
+                    factors.len()
+                );
+                let products =
+                    run_batch_multiply_protocol(&factors, inputs.len(), threshold, rng, engine)?;
+                for (wire, product) in interactive.into_iter().zip(products) {
+                    wires[wire] = Some(product);
+                }
+            }
+
+            // The rest of gates of the round are computed locally in topological order.
+            for (wire, gate) in self.gates.iter().enumerate() {
+                if rounds[wire] != round || wires[wire].is_some() {
+                    continue;
+                }
+                let value = match gate {
+                    Gate::Input { party, index } => inputs[*party][*index].clone(),
+                    // A public constant is shared with the constant polynomial, whose evaluation
+                    // at every point is the constant itself.
+                    Gate::Constant(constant) => ShamirShare::new(T::from(*constant), 0),
+                    Gate::Add(left, right) => {
+                        wire_value(wires, *left).add(&wire_value(wires, *right))
+                    }
+                    Gate::Subtract(left, right) => {
+                        wire_value(wires, *left).subtract(&wire_value(wires, *right))
+                    }
+                    Gate::Multiply(left, right) => wire_value(wires, *left)
+                        .multiply(&wire_value(wires, *right), inputs.len())?,
+                };
+                wires[wire] = Some(value);
+            }
+            state.round = round + 1;
+            on_round(&state)?;
+        }
+        Ok(wire_value(&state.wires, output))
+    }
+
+    /// Evaluates the circuit as in [`Circuit::evaluate`], but overlapping the computation and the
+    /// communication of consecutive batches of products instead of going round by round. The
+    /// products whose operands are known are multiplied in batches of at most `batch_size`
+    /// products, and up to two batches are in flight at the same time: the shares of a batch
+    /// are computed, encoded and sent while the previous batch is in flight, and the products
+    /// that only depend on a finished batch start before the rest of its round. This improves
+    /// the throughput of deep circuits on links with high latency. The schedule of the batches
+    /// only depends on the circuit, so every party must evaluate the same circuit with the same
+    /// batch size. The evaluation has no round boundaries, so it can not be checkpointed.
+    pub fn evaluate_pipelined<T, R, N>(
+        &self,
+        inputs: &[Vec<ShamirShare<T>>],
+        threshold: usize,
+        batch_size: usize,
+        rng: &mut R,
+        engine: &mut Engine<N>,
+    ) -> anyhow::Result<ShamirShare<T>>
+    where
+        T: FiniteField,
+        R: Rng + CryptoRng,
+        N: Transport,
+    {
+        let n_inputs: Vec<usize> = inputs.iter().map(Vec::len).collect();
+        self.check_inputs(&n_inputs)?;
+        let Some(output) = self.output() else {
+            return Ok(ShamirShare::new(T::ZERO, 0));
+        };
+        if batch_size == 0 {
+            anyhow::bail!("the batches of a pipelined evaluation can not be empty");
+        }
+
+        let interactive = self.interactive();
+        // The double sharings of every product are generated before the batches, which can not
+        // take a round of their own while other batches are in flight.
+        let n_products = if two_party::uses_triples(engine, threshold) {
+            0
+        } else {
+            interactive
+                .iter()
+                .filter(|interactive| **interactive)
+                .count()
+        };
+        let mut sharings =
+            engine.take_double_sharings(n_products, threshold, 2 * threshold, rng)?;
+        let mut wires: SecretVec<Option<ShamirShare<T>>> = vec![None; self.gates.len()].into();
+        let mut started = vec![false; self.gates.len()];
+        let mut in_flight = VecDeque::with_capacity(PIPELINE_DEPTH);
+        let mut n_batches = 0;
+        loop {
+            // The local gates whose operands are known are computed in topological order, so a
+            // single pass also computes the ones that depend on each other.
+            for (wire, gate) in self.gates.iter().enumerate() {
+                if wires[wire].is_some() || interactive[wire] {
+                    continue;
+                }
+                let known = |operand: &Wire| wires[*operand].is_some();
+                let value = match gate {
+                    Gate::Input { party, index } => inputs[*party][*index].clone(),
+                    Gate::Constant(constant) => ShamirShare::new(T::from(*constant), 0),
+                    Gate::Add(left, right) if known(left) && known(right) => {
+                        wire_value(&wires, *left).add(&wire_value(&wires, *right))
+                    }
+                    Gate::Subtract(left, right) if known(left) && known(right) => {
+                        wire_value(&wires, *left).subtract(&wire_value(&wires, *right))
+                    }
+                    Gate::Multiply(left, right) if known(left) && known(right) => {
+                        wire_value(&wires, *left)
+                            .multiply(&wire_value(&wires, *right), inputs.len())?
+                    }
+                    _ => continue,
+                };
+                wires[wire] = Some(value);
+            }
+
+            if in_flight.len() < PIPELINE_DEPTH {
+                let (batch, factors): (Vec<Wire>, Vec<_>) = self
+                    .gates
+                    .iter()
+                    .enumerate()
+                    .filter(|(wire, _)| interactive[*wire] && !started[*wire])
+                    .filter_map(|(wire, gate)| match gate {
+                        Gate::Multiply(left, right)
+                            if wires[*left].is_some() && wires[*right].is_some() =>
+                        {
+                            Some((
+                                wire,
+                                (wire_value(&wires, *left), wire_value(&wires, *right)),
+                            ))
+                        }
+                        _ => None,
+                    })
+                    .take(batch_size)
+                    .unzip();
+                if !batch.is_empty() {
+                    log::debug!("starting batch {n_batches} of {} products", batch.len());
+                    let pending =
+                        start_batch_multiply_protocol(&factors, threshold, &mut sharings, engine)?;
+                    for wire in &batch {
+                        started[*wire] = true;
+                    }
+                    in_flight.push_back((batch, pending));
+                    n_batches += 1;
+                    continue;
+                }
+            }
+
+            let Some((batch, pending)) = in_flight.pop_front() else {
+                break;
+            };
+            for (wire, product) in batch.into_iter().zip(pending.finish(engine)?) {
+                wires[wire] = Some(product);
+            }
+        }
+        log::info!(
+            "evaluated a circuit of {} gates in {n_batches} pipelined batches",
+            self.gates.len()
+        );
+        Ok(wire_value(&wires, output))
+    }
+}
+
+impl FromStr for Circuit {
+    type Err = CircuitError;
+
+    /// Parses a circuit in the arithmetic variant of the Bristol Fashion format. The first line
+    /// has the number of gates and the number of wires, the second one the number of parties
+    /// followed by the number of inputs of each party, and the third one `1 1`, as the circuit
+    /// has a single output of one wire. The inputs of the parties are the first wires, in order
+    /// of party and index, and the output is the last wire. Each of the next lines is a gate,
+    /// written as the number of operands, the number of outputs, the operands, the output wire
+    /// and the operation:
+    ///
+    /// - `2 1 a b c ADD`, `2 1 a b c SUB` and `2 1 a b c MUL` combine the wires `a` and `b`.
+    /// - `2 1 a k c CMUL` multiplies the wire `a` by the constant `k`.
+    /// - `1 1 k c EQ` sets the wire `c` to the constant `k`.
+    /// - `1 1 a c EQW` copies the wire `a`.
+    ///
+    /// The gates must be sorted in topological order, and the empty lines and the text after a
+    /// `#` are ignored. The gates that do not lead to the output are dropped.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(i, text)| (i + 1, text.split('#').next().unwrap_or_default().trim()))
+            .filter(|(_, text)| !text.is_empty());
+        let end = s.lines().count() + 1;
+        let mut header = |expected: usize| {
+            let (line, text) = lines.next().ok_or(CircuitError::InvalidHeader(end))?;
+            let numbers = parse_numbers(text).ok_or(CircuitError::InvalidHeader(line))?;
+            if expected > 0 && numbers.len() != expected {
+                return Err(CircuitError::InvalidHeader(line));
+            }
+            Ok((line, numbers))
+        };
+        let (_, sizes) = header(2)?;
+        let (n_gates, n_wires) = (sizes[0], sizes[1]);
+        let (line, inputs) = header(0)?;
+        match inputs.split_first() {
+            Some((n_parties, counts)) if *n_parties == counts.len() as u64 => {}
+            _ => return Err(CircuitError::InvalidHeader(line)),
+        }
+        if header(0)?.1 != [1, 1] {
+            return Err(CircuitError::MultipleOutputs);
+        }
+
+        let mut circuit = Circuit::default();
+        // Wires of the description mapped to the wires of the circuit that compute them.
+        let mut wires: HashMap<u64, Wire> = HashMap::new();
+        for (party, n_inputs) in inputs[1..].iter().enumerate() {
+            for index in 0..*n_inputs as usize {
+                let wire = circuit.push(Gate::Input { party, index });
+                wires.insert(wires.len() as u64, wire);
+            }
+        }
+        let mut found = 0;
+        for (line, text) in lines {
+            found += 1;
+            let (numbers, operation) = text.rsplit_once(char::is_whitespace).unwrap_or(("", text));
+            let numbers = parse_numbers(numbers).ok_or(CircuitError::InvalidGate(line))?;
+            let [n_operands, 1, ref operands @ .., output] = numbers[..] else {
+                return Err(CircuitError::InvalidGate(line));
+            };
+            if operands.len() as u64 != n_operands {
+                return Err(CircuitError::InvalidGate(line));
+            }
+            let wire = |wire: u64| {
+                wires
+                    .get(&wire)
+                    .copied()
+                    .ok_or(CircuitError::UndefinedWire { line, wire })
+            };
+            let value = match (operation, operands) {
+                ("ADD", [a, b]) => circuit.push(Gate::Add(wire(*a)?, wire(*b)?)),
+                ("SUB", [a, b]) => circuit.push(Gate::Subtract(wire(*a)?, wire(*b)?)),
+                ("MUL", [a, b]) => circuit.push(Gate::Multiply(wire(*a)?, wire(*b)?)),
+                ("CMUL", [a, constant]) => {
+                    let a = wire(*a)?;
+                    let constant = circuit.push(Gate::Constant(*constant));
+                    circuit.push(Gate::Multiply(a, constant))
+                }
+                ("EQ", [constant]) => circuit.push(Gate::Constant(*constant)),
+                ("EQW", [a]) => wire(*a)?,
+                ("ADD" | "SUB" | "MUL" | "CMUL" | "EQ" | "EQW", _) => {
+                    return Err(CircuitError::InvalidGate(line))
+                }
+                _ => {
+                    return Err(CircuitError::UnknownOperation {
+                        line,
+                        operation: operation.to_string(),
+                    })
+                }
+            };
+            if wires.insert(output, value).is_some() {
+                return Err(CircuitError::RedefinedWire { line, wire: output });
+            }
+        }
+        if found != n_gates {
+            return Err(CircuitError::GateCount {
+                expected: n_gates,
+                found,
+            });
+        }
+        let output = n_wires.saturating_sub(1);
+        let wire = *wires
+            .get(&output)
+            .ok_or(CircuitError::MissingOutput(output))?;
+        // The gates are sorted in topological order, so the ones after the output do not lead to
+        // it.
+        circuit.gates.truncate(wire + 1);
+        Ok(circuit)
+    }
+}
+
+/// Parses the numbers of a line of a circuit separated by whitespace, or returns `None` if some
+/// of them is not a number.
+fn parse_numbers(text: &str) -> Option<Vec<u64>> {
+    text.split_whitespace()
+        .map(|number| number.parse().ok())
+        .collect()
+}
+
+/// Returns the value of a wire that was already computed.
+fn wire_value<T: FiniteField>(wires: &[Option<ShamirShare<T>>], wire: Wire) -> ShamirShare<T> {
+    // The gates are evaluated after their operands, so the value is always present.
+    wires[wire]
+        .clone()
+        .expect("the operands are evaluated before the gate")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+
+    use super::{Circuit, CircuitError, Gate};
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{
+            engine::Engine,
+            expression::{Expression, ExpressionError},
+            reconstruct_secret,
+            share::ShamirShare,
+            share_inputs,
+        },
+        net::Network,
+    };
+
+    /// Evaluates a circuit on shares of the inputs of every party over a local network, and
+    /// returns the reconstructed output. The circuit is evaluated round by round, or pipelined in
+    /// batches of the given size.
+    fn evaluate_in_mpc(
+        circuit: &Circuit,
+        inputs: Vec<Vec<Mersenne61>>,
+        threshold: usize,
+        batch_size: Option<usize>,
+    ) -> Mersenne61 {
+        let n_parties = inputs.len();
+        let handles: Vec<_> = Network::create_local(n_parties)
+            .into_iter()
+            .zip(inputs)
+            .map(|(mut network, inputs)| {
+                let circuit = circuit.clone();
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let own_shares = share_inputs(&inputs, n_parties, threshold, &mut rng);
+                    let shares: Vec<Vec<ShamirShare<Mersenne61>>> =
+                        network.exchange_values(&own_shares).unwrap();
+                    match batch_size {
+                        Some(batch_size) => circuit.evaluate_pipelined(
+                            &shares,
+                            threshold,
+                            batch_size,
+                            &mut rng,
+                            &mut Engine::new(&mut network),
+                        ),
+                        None => circuit.evaluate(
+                            &shares,
+                            threshold,
+                            &mut rng,
+                            &mut Engine::new(&mut network),
+                        ),
+                    }
+                    .unwrap()
+                })
+            })
+            .collect();
+        let result_shares = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        reconstruct_secret(result_shares)
+    }
+
+    /// Returns a random circuit of the given number of gates over the inputs of the parties. The
+    /// first gate is an input, so every operand can refer to a previous wire.
+    fn random_circuit<R: Rng>(n_inputs: &[usize], n_gates: usize, rng: &mut R) -> Circuit {
+        let inputs: Vec<Gate> = n_inputs
+            .iter()
+            .enumerate()
+            .flat_map(|(party, n)| (0..*n).map(move |index| Gate::Input { party, index }))
+            .collect();
+        let mut circuit = Circuit::default();
+        circuit.push(inputs[rng.gen_range(0..inputs.len())].clone());
+        for _ in 1..n_gates {
+            let n_wires = circuit.gates().len();
+            let (left, right) = (rng.gen_range(0..n_wires), rng.gen_range(0..n_wires));
+            let gate = match rng.gen_range(0..6) {
+                0 => inputs[rng.gen_range(0..inputs.len())].clone(),
+                1 => Gate::Constant(rng.gen_range(0..100)),
+                2 => Gate::Add(left, right),
+                3 => Gate::Subtract(left, right),
+                _ => Gate::Multiply(left, right),
+            };
+            circuit.push(gate);
+        }
+        circuit
+    }
+
+    #[test]
+    fn circuit_from_expression() {
+        let expression: Expression = "x0 * (x1 + x2) + 5".parse().unwrap();
+        let circuit = Circuit::from_expression(&expression);
+        assert_eq!(
+            circuit.gates(),
+            &[
+                Gate::Input { party: 0, index: 0 },
+                Gate::Input { party: 1, index: 0 },
+                Gate::Input { party: 2, index: 0 },
+                Gate::Add(1, 2),
+                Gate::Multiply(0, 3),
+                Gate::Constant(5),
+                Gate::Add(4, 5),
+            ]
+        );
+        assert_eq!(circuit.output(), Some(6));
+        assert_eq!(circuit.n_rounds(), 1);
+
+        let by_constants: Expression = "3 * (x0 + x1) * (2 - 1)".parse().unwrap();
+        assert_eq!(Circuit::from_expression(&by_constants).n_rounds(), 0);
+        let product = Expression::product_of_inputs(&[2, 1, 1, 1, 3]);
+        assert_eq!(Circuit::from_expression(&product).n_rounds(), 3);
+    }
+
+    #[test]
+    fn evaluate_over_local_network() {
+        const THRESHOLD: usize = 1;
+
+        let expression: Expression = "x0 * (x1 + x2) + 5 + (x0[1] - x1) * (x2 * x2)"
+            .parse()
+            .unwrap();
+        let circuit = Circuit::from_expression(&expression);
+        let inputs: Vec<Vec<Mersenne61>> = [vec![3u64, 10], vec![4], vec![5]]
+            .into_iter()
+            .map(|inputs| inputs.into_iter().map(Mersenne61::from).collect())
+            .collect();
+
+        let expected = Mersenne61::from(3 * (4 + 5) + 5 + (10 - 4) * (5 * 5));
+        assert_eq!(circuit.evaluate_plain(&inputs), Ok(expected.clone()));
+        assert_eq!(
+            evaluate_in_mpc(&circuit, inputs.clone(), THRESHOLD, None),
+            expected
+        );
+        assert_eq!(
+            evaluate_in_mpc(&circuit, inputs, THRESHOLD, Some(1)),
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_bristol_fashion() {
+        // (x0 + 2 * x1[1]) * x2 - 7, with a gate that does not lead to the output.
+        const DESCRIPTION: &str = "7 11
+            3 1 2 1
+            1 1
+
+            2 1 2 2 4 CMUL # 2 * x1[1]
+            2 1 0 4 5 ADD
+            2 1 0 0 6 MUL
+            2 1 5 3 7 MUL
+            1 1 7 8 EQ
+            2 1 7 8 9 SUB
+            1 1 9 10 EQW
+        ";
+        let circuit: Circuit = DESCRIPTION.parse().unwrap();
+        assert_eq!(circuit.n_rounds(), 1);
+        assert_eq!(circuit.n_products(), 2);
+        let inputs: Vec<Vec<Mersenne61>> = [vec![3u64], vec![4, 5], vec![6]]
+            .into_iter()
+            .map(|inputs| inputs.into_iter().map(Mersenne61::from).collect())
+            .collect();
+        let expected = Mersenne61::from((3 + 2 * 5) * 6 - 7);
+        assert_eq!(circuit.evaluate_plain(&inputs), Ok(expected.clone()));
+        assert_eq!(evaluate_in_mpc(&circuit, inputs, 1, None), expected);
+
+        for (description, err) in [
+            (
+                "1 3\n1 2\n1 1\n2 1 0 1 2 DIV",
+                CircuitError::UnknownOperation {
+                    line: 4,
+                    operation: "DIV".to_string(),
+                },
+            ),
+            (
+                "1 3\n1 2\n1 1\n2 1 0 5 2 ADD",
+                CircuitError::UndefinedWire { line: 4, wire: 5 },
+            ),
+            (
+                "1 3\n1 2\n1 1\n2 1 0 1 1 ADD",
+                CircuitError::RedefinedWire { line: 4, wire: 1 },
+            ),
+            ("1 3\n1 2\n1 1\n2 1 0 2 ADD", CircuitError::InvalidGate(4)),
+            (
+                "2 3\n1 2\n1 1\n2 1 0 1 2 ADD",
+                CircuitError::GateCount {
+                    expected: 2,
+                    found: 1,
+                },
+            ),
+            (
+                "1 4\n1 2\n1 1\n2 1 0 1 2 ADD",
+                CircuitError::MissingOutput(3),
+            ),
+            ("1 3\n2 2\n1 1", CircuitError::InvalidHeader(2)),
+            ("1 3\n1 2\n2 1 1", CircuitError::MultipleOutputs),
+            ("1 3", CircuitError::InvalidHeader(2)),
+        ] {
+            assert_eq!(description.parse::<Circuit>(), Err(err), "{description}");
+        }
+    }
+
+    #[test]
+    fn evaluate_in_the_clear() {
+        let expression: Expression = "(x0 - x1) * (x0 - x1) - 2".parse().unwrap();
+        let circuit = Circuit::from_expression(&expression);
+        let inputs = [vec![Mersenne61::from(2)], vec![Mersenne61::from(5)]];
+        assert_eq!(circuit.evaluate_plain(&inputs), Ok(Mersenne61::from(7)));
+        assert_eq!(
+            Circuit::default().evaluate_plain::<Mersenne61>(&[]),
+            Ok(Mersenne61::ZERO)
+        );
+        assert_eq!(
+            circuit.evaluate_plain(&inputs[..1]),
+            Err(ExpressionError::UnknownInput { party: 1, index: 0 })
+        );
+    }
+
+    #[test]
+    fn dual_execution_of_random_circuits() {
+        const N_CIRCUITS: usize = 40;
+
+        let mut rng = StdRng::seed_from_u64(1708);
+        for case in 0..N_CIRCUITS {
+            let n_parties = rng.gen_range(3..=6);
+            let threshold = rng.gen_range(1..=(n_parties - 1) / 2);
+            let n_inputs: Vec<usize> = (0..n_parties).map(|_| rng.gen_range(1..=2)).collect();
+            let circuit = random_circuit(&n_inputs, rng.gen_range(1..=25), &mut rng);
+            let inputs: Vec<Vec<Mersenne61>> = n_inputs
+                .iter()
+                .map(|n| (0..*n).map(|_| Mersenne61::random(&mut rng)).collect())
+                .collect();
+
+            let expected = circuit.evaluate_plain(&inputs).unwrap();
+            assert_eq!(
+                evaluate_in_mpc(&circuit, inputs, threshold, None),
+                expected,
+                "case {case}: the MPC evaluation of {} with {n_parties} parties and threshold \
+                 {threshold} differs from the reference",
+                circuit.description()
+            );
+        }
+    }
+
+    #[test]
+    fn pipelined_evaluation_of_random_circuits() {
+        const N_CIRCUITS: usize = 20;
+
+        let mut rng = StdRng::seed_from_u64(1725);
+        for case in 0..N_CIRCUITS {
+            let n_parties = rng.gen_range(3..=5);
+            let threshold = rng.gen_range(1..=(n_parties - 1) / 2);
+            let n_inputs: Vec<usize> = (0..n_parties).map(|_| rng.gen_range(1..=2)).collect();
+            let circuit = random_circuit(&n_inputs, rng.gen_range(1..=40), &mut rng);
+            let inputs: Vec<Vec<Mersenne61>> = n_inputs
+                .iter()
+                .map(|n| (0..*n).map(|_| Mersenne61::random(&mut rng)).collect())
+                .collect();
+            let batch_size = rng.gen_range(1..=4);
+
+            let expected = circuit.evaluate_plain(&inputs).unwrap();
+            assert_eq!(
+                evaluate_in_mpc(&circuit, inputs, threshold, Some(batch_size)),
+                expected,
+                "case {case}: the pipelined evaluation of {} in batches of {batch_size} differs \
+                 from the reference",
+                circuit.description()
+            );
+        }
+    }
+}
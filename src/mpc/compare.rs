@@ -0,0 +1,692 @@
+use rand::{CryptoRng, Rng};
+use thiserror::Error;
+
+use super::{
+    check_degree, check_threshold, engine::Engine, run_multiply_many, share::ShamirShare,
+    share::ShareBatch, share_inputs, verification,
+};
+use crate::{
+    math::{FieldValue, PrimeField},
+    transport::Transport,
+};
+
+/// Statistical security parameter of the masks of the comparisons, i.e. the opened values leak
+/// the shared ones with a probability of at most `2^-40`.
+pub const STATISTICAL_SECURITY: usize = 40;
+
+/// Possible errors of the comparison protocols.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ComparisonError {
+    /// The shared values have more bits than the masks of the protocols can hide in the field.
+    #[error("the bit length must be between 1 and {max} in this field, but it is {bit_length}")]
+    BitLength { bit_length: usize, max: usize },
+}
+
+/// Returns the maximum bit length of the values that the protocols decompose in the field of `T`,
+/// which leaves room for the statistical mask of every contributor without wrapping around the
+/// modulus.
+pub fn max_bit_length<T: PrimeField>(threshold: usize) -> usize {
+    // The mask is the sum of `threshold + 1` integers of the bit length plus the statistical
+    // security, and the field holds every integer of `BIT_SIZE - 1` bits.
+    let contributors_bits = (usize::BITS - (threshold + 1).leading_zeros()) as usize;
+    (T::BIT_SIZE - 1).saturating_sub(STATISTICAL_SECURITY + contributors_bits)
+}
+
+/// Checks that the bit length of the shared values is supported by the protocols in the field.
+fn check_bit_length<T: PrimeField>(
+    bit_length: usize,
+    threshold: usize,
+) -> Result<(), ComparisonError> {
+    let max = max_bit_length::<T>(threshold);
+    if bit_length == 0 || bit_length > max {
+        return Err(ComparisonError::BitLength { bit_length, max });
+    }
+    Ok(())
+}
+
+/// Returns the element `2^exponent`.
+fn power_of_two<T: PrimeField>(exponent: usize) -> T {
+    T::from(2).pow(exponent as u128)
+}
+
+/// Draws a uniformly random integer of the given number of bits, which must be lower than the
+/// modulus.
+fn random_integer<T: PrimeField, R: Rng>(bits: usize, rng: &mut R) -> T {
+    let mut bytes = vec![0; T::ValueType::BYTES];
+    rng.fill_bytes(&mut bytes);
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        let kept = bits.saturating_sub(8 * i).min(8);
+        *byte &= (0xffu16 >> (8 - kept)) as u8;
+    }
+    let value = T::ValueType::read_le_bytes(&bytes).expect("the bytes fit in the integer");
+    T::from_value(value).expect("the integer is lower than the modulus")
+}
+
+/// Random values contributed by the first `threshold + 1` parties, so that at least one of them
+/// is honest.
+struct Contributions<T: PrimeField> {
+    /// Shares of the random bits of each contributor.
+    bits: Vec<Vec<ShamirShare<T>>>,
+    /// Shares of the sums of the random integers of the contributors.
+    integers: Vec<ShamirShare<T>>,
+}
+
+/// Shares `n_bits` random bits and `n_integers` random integers of `integer_bits` bits from every
+/// contributor in a single round. The other parties only receive shares.
+fn contribute<T, R, N>(
+    n_bits: usize,
+    (n_integers, integer_bits): (usize, usize),
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    network: &mut N,
+) -> anyhow::Result<Contributions<T>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    let expected = n_bits + n_integers;
+    let own_batches: Vec<ShareBatch<T>> = if network.id() <= threshold {
+        let mut randoms: Vec<T> = (0..n_bits)
+            .map(|_| T::from(u64::from(rng.gen::<bool>())))
+            .collect();
+        randoms.extend((0..n_integers).map(|_| random_integer(integer_bits, rng)));
+        share_inputs(&randoms, n_parties, threshold, rng)
+            .into_iter()
+            .map(|shares| ShareBatch {
+                degree: threshold,
+                values: shares.into_iter().map(|share| share.value).collect(),
+            })
+            .collect()
+    } else {
+        let empty = ShareBatch {
+            degree: threshold,
+            values: Vec::new(),
+        };
+        vec![empty; n_parties]
+    };
+    let batches: Vec<ShareBatch<T>> = network.exchange_values(&own_batches)?;
+    for (party, batch) in batches.iter().enumerate() {
+        let count = if party <= threshold { expected } else { 0 };
+        anyhow::ensure!(
+            batch.values.len() == count && batch.degree == threshold,
+            "party {party} sent {} random shares of degree {}, but {count} of degree {threshold} \
+             were expected",
+            batch.values.len(),
+            batch.degree
+        );
+    }
+
+    let contributors = &batches[..=threshold];
+    let bits = contributors
+        .iter()
+        .map(|batch| {
+            batch.values[..n_bits]
+                .iter()
+                .map(|value| ShamirShare::new(value.clone(), threshold))
+                .collect()
+        })
+        .collect();
+    let integers = (n_bits..expected)
+        .map(|k| {
+            let sum = contributors
+                .iter()
+                .fold(T::ZERO, |sum, batch| sum.add(&batch.values[k]));
+            ShamirShare::new(sum, threshold)
+        })
+        .collect();
+    Ok(Contributions { bits, integers })
+}
+
+/// Folds every list of shares into one by combining them in pairs with `combine`, which receives
+/// both shares and the shares of their product. The products of every level of the tree are
+/// computed in the same rounds, so the folding takes a logarithmic number of multiplications.
+fn fold_with_products<T, R, N, F>(
+    mut lists: Vec<Vec<ShamirShare<T>>>,
+    combine: F,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    N: Transport,
+    F: Fn(&ShamirShare<T>, &ShamirShare<T>, &ShamirShare<T>) -> ShamirShare<T>,
+{
+    while lists.iter().any(|list| list.len() > 1) {
+        let (left, right): (Vec<_>, Vec<_>) = lists
+            .iter()
+            .flat_map(|list| {
+                list.chunks_exact(2)
+                    .map(|pair| (pair[0].clone(), pair[1].clone()))
+            })
+            .unzip();
+        let mut products = run_multiply_many(&left, &right, n_parties, threshold, rng, engine)?
+            .into_iter()
+            .zip(left.iter().zip(&right))
+            .map(|(product, (a, b))| combine(a, b, &product));
+        lists = lists
+            .into_iter()
+            .map(|list| {
+                let mut folded: Vec<_> = products.by_ref().take(list.len() / 2).collect();
+                folded.extend(list.into_iter().skip(2 * folded.len()).take(1));
+                folded
+            })
+            .collect();
+    }
+    Ok(lists
+        .into_iter()
+        .map(|mut list| list.pop().expect("every list has some share"))
+        .collect())
+}
+
+/// Generates the shares of `count` random bits unknown to any coalition of up to `threshold`
+/// parties. Each of the first `threshold + 1` parties shares its own random bits, and the bits are
+/// the XORs `a + b - 2ab` of their contributions, which are uniform as long as one contributor is
+/// honest.
+pub fn random_bits<T, R, N>(
+    count: usize,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    check_threshold(n_parties, threshold, true)?;
+    let contributions = contribute(count, (0, 0), n_parties, threshold, rng, engine)?;
+    xor_contributions(contributions.bits, count, n_parties, threshold, rng, engine)
+}
+
+/// Combines the bits of the contributors with the XORs of [`random_bits`].
+fn xor_contributions<T, R, N>(
+    bits: Vec<Vec<ShamirShare<T>>>,
+    count: usize,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    let lists = (0..count)
+        .map(|k| bits.iter().map(|own| own[k].clone()).collect())
+        .collect();
+    let two = T::from(2);
+    let xor = |a: &ShamirShare<T>, b: &ShamirShare<T>, ab: &ShamirShare<T>| {
+        a.add(b).subtract(&ab.multiply_const(&two))
+    };
+    fold_with_products(lists, xor, n_parties, threshold, rng, engine)
+}
+
+/// Computes the shares of the `bits` least significant bits of every shared value of
+/// `bit_length` bits, least significant first. Every value `x` is masked with a random `r` whose
+/// low bits are shared random bits and whose high part hides the rest of `x` statistically, and
+/// the parties open `c = x + r` and subtract the shared bits of `r` from the public bits of `c`
+/// with a ripple of borrows, which takes one round of multiplications per bit.
+fn low_bits<T, R, N>(
+    values: &[ShamirShare<T>],
+    bit_length: usize,
+    bits: usize,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<Vec<ShamirShare<T>>>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    check_threshold(n_parties, threshold, true)?;
+    check_bit_length::<T>(bit_length, threshold)?;
+    check_degree(values, threshold)?;
+
+    log::info!("masking {} values of {bit_length} bits", values.len());
+    let n_bits = values.len() * bits;
+    let integers = (values.len(), bit_length + STATISTICAL_SECURITY - bits);
+    let contributions = contribute(n_bits, integers, n_parties, threshold, rng, engine)?;
+    let mask_bits: Vec<ShamirShare<T>> = xor_contributions(
+        contributions.bits,
+        n_bits,
+        n_parties,
+        threshold,
+        rng,
+        engine,
+    )?;
+    let mask_bits: Vec<&[ShamirShare<T>]> = mask_bits.chunks(bits.max(1)).collect();
+    let high = power_of_two::<T>(bits);
+    let masked: Vec<T> = values
+        .iter()
+        .zip(&contributions.integers)
+        .enumerate()
+        .map(|(k, (value, integer))| {
+            let low = mask_bits
+                .get(k)
+                .map_or(ShamirShare::new(T::ZERO, 0), |bits| combine_bits(bits));
+            value.add(&low).add(&integer.multiply_const(&high)).value
+        })
+        .collect();
+    let opened = verification::open_checked(&masked, threshold, engine)?;
+    let public_bits: Vec<Vec<bool>> = opened
+        .iter()
+        .map(|value| {
+            let mut bytes = vec![0; T::ValueType::BYTES];
+            value.value().write_le_bytes(&mut bytes);
+            (0..bits)
+                .map(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+                .collect()
+        })
+        .collect();
+
+    log::info!(
+        "subtracting the masks of {} values bit by bit",
+        values.len()
+    );
+    let mut borrows = vec![ShamirShare::new(T::ZERO, 0); values.len()];
+    let mut result = vec![Vec::with_capacity(bits); values.len()];
+    for i in 0..bits {
+        let masks: Vec<ShamirShare<T>> = mask_bits.iter().map(|bits| bits[i].clone()).collect();
+        let products = if i == 0 {
+            borrows.clone()
+        } else {
+            run_multiply_many(&masks, &borrows, n_parties, threshold, rng, engine)?
+        };
+        for (k, ((mask, borrow), product)) in
+            masks.iter().zip(&mut borrows).zip(products).enumerate()
+        {
+            let xor = mask
+                .add(borrow)
+                .subtract(&product.multiply_const(&T::from(2)));
+            if public_bits[k][i] {
+                result[k].push(xor.negate().add_const(&T::ONE));
+                *borrow = product;
+            } else {
+                result[k].push(xor);
+                *borrow = mask.add(borrow).subtract(&product);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Computes the shared value `sum_i 2^i bits[i]` of its shared bits, least significant first.
+fn combine_bits<T: PrimeField>(bits: &[ShamirShare<T>]) -> ShamirShare<T> {
+    bits.iter()
+        .rev()
+        .fold(ShamirShare::new(T::ZERO, 0), |sum, bit| {
+            sum.multiply_const(&T::from(2)).add(bit)
+        })
+}
+
+/// Decomposes every shared value of at most `bit_length` bits into the shares of its bits, least
+/// significant first. The bit length is at most [`max_bit_length`], since the values are opened
+/// masked with random integers that must not wrap around the modulus.
+pub fn bit_decomposition<T, R, N>(
+    values: &[ShamirShare<T>],
+    bit_length: usize,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<Vec<ShamirShare<T>>>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    low_bits(
+        values, bit_length, bit_length, n_parties, threshold, rng, engine,
+    )
+}
+
+/// Computes the shares of `x mod 2^exponent` of every shared value `x` of at most `bit_length`
+/// bits, from the shares of its low bits.
+pub fn modulo_power_of_two<T, R, N>(
+    values: &[ShamirShare<T>],
+    bit_length: usize,
+    exponent: usize,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    anyhow::ensure!(
+        exponent <= bit_length,
+        "reducing values of {bit_length} bits modulo 2^{exponent}"
+    );
+    let bits = low_bits(
+        values, bit_length, exponent, n_parties, threshold, rng, engine,
+    )?;
+    Ok(bits.iter().map(|bits| combine_bits(bits)).collect())
+}
+
+/// Computes the shares of `floor(x / 2^shift)` of every shared value `x` of at most `bit_length`
+/// bits, i.e. shifts the values to the right, which rescales the fixed-point numbers after a
+/// product.
+pub fn truncate<T, R, N>(
+    values: &[ShamirShare<T>],
+    bit_length: usize,
+    shift: usize,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    let remainders =
+        modulo_power_of_two(values, bit_length, shift, n_parties, threshold, rng, engine)?;
+    let inverse = power_of_two::<T>(shift).inverse()?;
+    Ok(values
+        .iter()
+        .zip(remainders)
+        .map(|(value, remainder)| value.subtract(&remainder).multiply_const(&inverse))
+        .collect())
+}
+
+/// Returns the shares of `a + 2^bit_length - b` for every pair of shared values, whose bit of
+/// weight `2^bit_length` is set if and only if `a >= b`, and whose low bits are zero if and only
+/// if `a = b`.
+fn shifted_differences<T: PrimeField>(
+    a: &[ShamirShare<T>],
+    b: &[ShamirShare<T>],
+    bit_length: usize,
+) -> anyhow::Result<Vec<ShamirShare<T>>> {
+    anyhow::ensure!(
+        a.len() == b.len(),
+        "comparing {} values with {} values, but there must be as many of each",
+        a.len(),
+        b.len()
+    );
+    let offset = power_of_two::<T>(bit_length);
+    Ok(a.iter()
+        .zip(b)
+        .map(|(a, b)| a.subtract(b).add_const(&offset))
+        .collect())
+}
+
+/// Computes the shares of the bit `a[k] < b[k]` for every pair of shared values of at most
+/// `bit_length` bits, which is one minus the bit of weight `2^bit_length` of
+/// `a[k] + 2^bit_length - b[k]`.
+pub fn less_than<T, R, N>(
+    a: &[ShamirShare<T>],
+    b: &[ShamirShare<T>],
+    bit_length: usize,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    let differences = shifted_differences(a, b, bit_length)?;
+    let top_bits = truncate(
+        &differences,
+        bit_length + 1,
+        bit_length,
+        n_parties,
+        threshold,
+        rng,
+        engine,
+    )?;
+    Ok(top_bits
+        .iter()
+        .map(|bit| bit.negate().add_const(&T::ONE))
+        .collect())
+}
+
+/// Computes the shares of the bit `a[k] = b[k]` for every pair of shared values of at most
+/// `bit_length` bits, which is the product of the negated low bits of
+/// `a[k] + 2^bit_length - b[k]`.
+pub fn equal<T, R, N>(
+    a: &[ShamirShare<T>],
+    b: &[ShamirShare<T>],
+    bit_length: usize,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    let differences = shifted_differences(a, b, bit_length)?;
+    let bits = low_bits(
+        &differences,
+        bit_length + 1,
+        bit_length,
+        n_parties,
+        threshold,
+        rng,
+        engine,
+    )?;
+    let negated = bits
+        .into_iter()
+        .map(|bits| {
+            bits.iter()
+                .map(|bit| bit.negate().add_const(&T::ONE))
+                .collect()
+        })
+        .collect();
+    let and = |_: &ShamirShare<T>, _: &ShamirShare<T>, ab: &ShamirShare<T>| ab.clone();
+    fold_with_products(negated, and, n_parties, threshold, rng, engine)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::{thread_rng, Rng};
+
+    use super::{
+        bit_decomposition, equal, less_than, max_bit_length, modulo_power_of_two, random_bits,
+        truncate, ComparisonError,
+    };
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{compute_shamir_share, engine::Engine, reconstruct_secret, share::ShamirShare},
+        net::Network,
+    };
+
+    const N_PARTIES: usize = 4;
+    const THRESHOLD: usize = 1;
+    const BIT_LENGTH: usize = 12;
+
+    /// Shares the given secrets among the parties, and returns the shares of each party.
+    fn share_values(values: &[u64]) -> Vec<Vec<ShamirShare<Mersenne61>>> {
+        let mut rng = thread_rng();
+        let mut shares = vec![Vec::new(); N_PARTIES];
+        for value in values {
+            let value_shares =
+                compute_shamir_share(&Mersenne61::from(*value), N_PARTIES, THRESHOLD, &mut rng);
+            for (party, share) in value_shares.into_iter().enumerate() {
+                shares[party].push(share);
+            }
+        }
+        shares
+    }
+
+    /// Reconstructs the secrets from the shares of every party, given in the same order.
+    fn reconstruct(shares: &[Vec<ShamirShare<Mersenne61>>]) -> Vec<Mersenne61> {
+        (0..shares[0].len())
+            .map(|k| reconstruct_secret(shares.iter().map(|own| own[k].clone()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn random_bits_are_bits() {
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .map(|mut network| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    random_bits::<Mersenne61, _, _>(
+                        32,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap()
+                })
+            })
+            .collect();
+        let shares: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        let bits = reconstruct(&shares);
+        assert!(bits
+            .iter()
+            .all(|bit| *bit == Mersenne61::ZERO || *bit == Mersenne61::ONE));
+        assert!(bits.contains(&Mersenne61::ONE) && bits.contains(&Mersenne61::ZERO));
+    }
+
+    #[test]
+    fn decomposition_and_truncation() {
+        let mut rng = thread_rng();
+        let mut values: Vec<u64> = (0..4).map(|_| rng.gen_range(0..1 << BIT_LENGTH)).collect();
+        values.extend([0, (1 << BIT_LENGTH) - 1]);
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(share_values(&values))
+            .map(|(mut network, shares)| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let bits = bit_decomposition(
+                        &shares,
+                        BIT_LENGTH,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap();
+                    let remainders = modulo_power_of_two(
+                        &shares,
+                        BIT_LENGTH,
+                        5,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap();
+                    let quotients = truncate(
+                        &shares,
+                        BIT_LENGTH,
+                        5,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap();
+                    (bits, remainders, quotients)
+                })
+            })
+            .collect();
+        let mut bits = Vec::new();
+        let mut remainders = Vec::new();
+        let mut quotients = Vec::new();
+        for handle in handles {
+            let (own_bits, own_remainders, own_quotients) = handle.join().unwrap();
+            bits.push(own_bits);
+            remainders.push(own_remainders);
+            quotients.push(own_quotients);
+        }
+        for (k, value) in values.iter().enumerate() {
+            let value_bits: Vec<_> = bits.iter().map(|own| own[k].clone()).collect();
+            let expected: Vec<Mersenne61> = (0..BIT_LENGTH)
+                .map(|i| Mersenne61::from((value >> i) & 1))
+                .collect();
+            assert_eq!(reconstruct(&value_bits), expected);
+        }
+        let expected: Vec<Mersenne61> = values.iter().map(|v| Mersenne61::from(v % 32)).collect();
+        assert_eq!(reconstruct(&remainders), expected);
+        let expected: Vec<Mersenne61> = values.iter().map(|v| Mersenne61::from(v / 32)).collect();
+        assert_eq!(reconstruct(&quotients), expected);
+    }
+
+    #[test]
+    fn comparisons() {
+        let a = [3, 7, 100, 0, 4095, 512];
+        let b = [5, 7, 99, 0, 4094, 4095];
+        let (a_shares, b_shares) = (share_values(&a), share_values(&b));
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(a_shares.into_iter().zip(b_shares))
+            .map(|(mut network, (a, b))| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let lt = less_than(
+                        &a,
+                        &b,
+                        BIT_LENGTH,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap();
+                    let eq = equal(
+                        &a,
+                        &b,
+                        BIT_LENGTH,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap();
+                    (lt, eq)
+                })
+            })
+            .collect();
+        let (lt, eq): (Vec<_>, Vec<_>) = handles.into_iter().map(|h| h.join().unwrap()).unzip();
+        let expected: Vec<Mersenne61> = a
+            .iter()
+            .zip(&b)
+            .map(|(a, b)| Mersenne61::from(u64::from(a < b)))
+            .collect();
+        assert_eq!(reconstruct(&lt), expected);
+        let expected: Vec<Mersenne61> = a
+            .iter()
+            .zip(&b)
+            .map(|(a, b)| Mersenne61::from(u64::from(a == b)))
+            .collect();
+        assert_eq!(reconstruct(&eq), expected);
+    }
+
+    #[test]
+    fn unsupported_parameters() {
+        assert_eq!(max_bit_length::<Mersenne61>(THRESHOLD), 18);
+        assert_eq!(
+            super::check_bit_length::<Mersenne61>(19, THRESHOLD),
+            Err(ComparisonError::BitLength {
+                bit_length: 19,
+                max: 18
+            })
+        );
+    }
+}
@@ -0,0 +1,435 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+};
+
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{daemon::Job, engine::Engine};
+use crate::{math::PrimeField, net::Network};
+
+/// Possible errors that may appear while handling a request to the control service.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ControlError {
+    /// The request is not a valid JSON object with the fields of a request.
+    #[error("the request is not correct: {0}")]
+    InvalidRequest(String),
+
+    /// The session of a submitted job does not follow the session of the previous job.
+    #[error("the session {session} does not follow the session {last} of the previous job")]
+    SessionNotIncreasing { session: u32, last: u32 },
+
+    /// No job was submitted for the session.
+    #[error("there is no job for the session {0}")]
+    UnknownJob(u32),
+
+    /// The daemon is shutting down and does not accept more jobs.
+    #[error("the daemon is shutting down")]
+    ShuttingDown,
+}
+
+/// Request sent to the control service of a daemon, written as a JSON object per line with the
+/// name of the method in the `method` field, e.g.
+/// `{"method": "submit", "job": {"session": 1, "inputs": [2], "expression": "x0 + x1"}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// Queues a job, which is identified by its session.
+    Submit { job: Job },
+    /// Returns the status of a job without waiting for it.
+    Status { session: u32 },
+    /// Waits until a job finishes and returns its final status.
+    Result { session: u32 },
+    /// Stops accepting jobs, so the daemon exits after running the queued ones.
+    Shutdown,
+}
+
+/// Status of a job submitted to the control service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    /// The job waits for the previous jobs.
+    Queued,
+    /// The job is being run with the other parties.
+    Running,
+    /// The job finished with the given result, which is the field element as it is serialized.
+    Done { result: String },
+    /// The job failed with the given error.
+    Failed { error: String },
+}
+
+impl JobStatus {
+    /// Returns whether the job finished, either successfully or not.
+    fn is_finished(&self) -> bool {
+        matches!(self, Self::Done { .. } | Self::Failed { .. })
+    }
+}
+
+/// Response of the control service to a request, written as a JSON object per line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ControlResponse {
+    /// Status of the job of a session.
+    Job {
+        session: u32,
+        #[serde(flatten)]
+        status: JobStatus,
+    },
+    /// The daemon is shutting down after running the given number of queued jobs.
+    ShuttingDown { queued: usize },
+    /// The request was rejected.
+    Error { error: String },
+}
+
+/// State of the queue of jobs.
+#[derive(Debug, Default)]
+struct QueueState {
+    /// Jobs waiting to be run, in the order in which they were submitted.
+    queued: VecDeque<Job>,
+    /// Status of every submitted job, indexed by its session.
+    statuses: BTreeMap<u32, JobStatus>,
+    /// Session of the last submitted job.
+    last_session: u32,
+    /// Whether the queue stopped accepting jobs.
+    closed: bool,
+}
+
+/// Queue of the jobs submitted through the control service, which is shared between the service
+/// and the daemon that runs the jobs.
+#[derive(Debug, Default)]
+pub struct JobQueue {
+    /// State of the queue.
+    state: Mutex<QueueState>,
+    /// Notified whenever a job is queued or finishes, or the queue is closed.
+    changed: Condvar,
+}
+
+impl JobQueue {
+    /// Creates an empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the state of the queue. A panic while holding the lock leaves the state consistent,
+    /// so a poisoned lock is recovered.
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Queues a job. The sessions of the jobs must increase in the order in which they are
+    /// submitted, as the sessions of the network do.
+    pub fn submit(&self, job: Job) -> Result<(), ControlError> {
+        let mut state = self.lock();
+        if state.closed {
+            return Err(ControlError::ShuttingDown);
+        }
+        if job.session <= state.last_session {
+            return Err(ControlError::SessionNotIncreasing {
+                session: job.session,
+                last: state.last_session,
+            });
+        }
+        state.last_session = job.session;
+        state.statuses.insert(job.session, JobStatus::Queued);
+        state.queued.push_back(job);
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    /// Returns the status of the job of a session.
+    pub fn status(&self, session: u32) -> Result<JobStatus, ControlError> {
+        self.lock()
+            .statuses
+            .get(&session)
+            .cloned()
+            .ok_or(ControlError::UnknownJob(session))
+    }
+
+    /// Waits until the job of a session finishes, returning its final status.
+    pub fn wait_result(&self, session: u32) -> Result<JobStatus, ControlError> {
+        let mut state = self.lock();
+        loop {
+            match state.statuses.get(&session) {
+                None => return Err(ControlError::UnknownJob(session)),
+                Some(status) if status.is_finished() => return Ok(status.clone()),
+                Some(_) => {
+                    state = self
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                }
+            }
+        }
+    }
+
+    /// Stops accepting jobs, returning the number of jobs that are still queued.
+    pub fn close(&self) -> usize {
+        let mut state = self.lock();
+        state.closed = true;
+        self.changed.notify_all();
+        state.queued.len()
+    }
+
+    /// Waits for the next job and marks it as running. Returns `None` once the queue is closed
+    /// and every job was taken.
+    pub fn next_job(&self) -> Option<Job> {
+        let mut state = self.lock();
+        loop {
+            if let Some(job) = state.queued.pop_front() {
+                state.statuses.insert(job.session, JobStatus::Running);
+                return Some(job);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self
+                .changed
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Records the final status of the job of a session.
+    pub fn finish(&self, session: u32, status: JobStatus) {
+        self.lock().statuses.insert(session, status);
+        self.changed.notify_all();
+    }
+
+    /// Handles a request to the control service.
+    pub fn handle(&self, request: ControlRequest) -> ControlResponse {
+        let outcome = match request {
+            ControlRequest::Submit { job } => {
+                let session = job.session;
+                self.submit(job).map(|()| (session, JobStatus::Queued))
+            }
+            ControlRequest::Status { session } => {
+                self.status(session).map(|status| (session, status))
+            }
+            ControlRequest::Result { session } => {
+                self.wait_result(session).map(|status| (session, status))
+            }
+            ControlRequest::Shutdown => {
+                return ControlResponse::ShuttingDown {
+                    queued: self.close(),
+                }
+            }
+        };
+        match outcome {
+            Ok((session, status)) => ControlResponse::Job { session, status },
+            Err(err) => ControlResponse::Error {
+                error: err.to_string(),
+            },
+        }
+    }
+}
+
+/// Starts the control service of a daemon in a background thread, which accepts connections at
+/// the given address and handles the requests of each connection in its own thread. Returns the
+/// address at which the service listens. Anybody who reaches the address can submit jobs, so it
+/// should only be reachable by trusted applications.
+pub fn spawn_control_service(
+    address: SocketAddr,
+    queue: Arc<JobQueue>,
+) -> anyhow::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+    log::info!("control service listening at {address}");
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let queue = Arc::clone(&queue);
+                    thread::spawn(move || {
+                        if let Err(err) = handle_connection(stream, &queue) {
+                            log::warn!("error in a connection of the control service: {err:#}");
+                        }
+                    });
+                }
+                Err(err) => {
+                    log::warn!("error accepting a connection of the control service: {err}")
+                }
+            }
+        }
+    });
+    Ok(address)
+}
+
+/// Answers the requests of a connection to the control service until it is closed.
+fn handle_connection(stream: TcpStream, queue: &JobQueue) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => queue.handle(request),
+            Err(err) => ControlResponse::Error {
+                error: ControlError::InvalidRequest(err.to_string()).to_string(),
+            },
+        };
+        let mut line = serde_json::to_vec(&response)?;
+        line.push(b'\n');
+        writer.write_all(&line)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Runs the jobs of the queue with the other parties, one after another, until the queue is
+/// closed and empty, keeping the connections alive between jobs. The status of every job is
+/// recorded in the queue. Returns the number of jobs run.
+pub fn serve_queue<T, R>(
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<Network>,
+    queue: &JobQueue,
+) -> usize
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+{
+    let mut n_jobs = 0;
+    while let Some(job) = queue.next_job() {
+        n_jobs += 1;
+        let status = job
+            .run::<T, R>(threshold, rng, engine)
+            .and_then(|result| Ok(serde_json::to_string(&result)?));
+        let status = match status {
+            Ok(result) => JobStatus::Done { result },
+            Err(err) => JobStatus::Failed {
+                error: format!("{err:#}"),
+            },
+        };
+        queue.finish(job.session, status);
+    }
+    n_jobs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpStream,
+        sync::Arc,
+        thread,
+    };
+
+    use rand::thread_rng;
+    use serde_json::{json, Value};
+
+    use super::{serve_queue, spawn_control_service, ControlError, JobQueue, JobStatus};
+    use crate::{math::mersenne61::Mersenne61, mpc::engine::Engine, net::Network};
+
+    /// Sends a request to the control service and returns the response.
+    fn request(stream: &mut TcpStream, request: Value) -> Value {
+        writeln!(stream, "{request}").unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn queue_jobs_in_order() {
+        let queue = JobQueue::new();
+        queue
+            .submit(r#"{"session": 2, "inputs": [1]}"#.parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            queue.submit(r#"{"session": 2, "inputs": [1]}"#.parse().unwrap()),
+            Err(ControlError::SessionNotIncreasing {
+                session: 2,
+                last: 2
+            })
+        );
+        assert_eq!(queue.status(2), Ok(JobStatus::Queued));
+        assert_eq!(queue.status(1), Err(ControlError::UnknownJob(1)));
+
+        assert_eq!(queue.next_job().unwrap().session, 2);
+        assert_eq!(queue.status(2), Ok(JobStatus::Running));
+        queue.finish(
+            2,
+            JobStatus::Done {
+                result: "7".to_string(),
+            },
+        );
+        assert_eq!(
+            queue.wait_result(2),
+            Ok(JobStatus::Done {
+                result: "7".to_string()
+            })
+        );
+
+        assert_eq!(queue.close(), 0);
+        assert_eq!(queue.next_job(), None);
+        assert_eq!(
+            queue.submit(r#"{"session": 3, "inputs": [1]}"#.parse().unwrap()),
+            Err(ControlError::ShuttingDown)
+        );
+    }
+
+    #[test]
+    fn control_jobs_over_local_network() {
+        let handles: Vec<_> = Network::create_local(3)
+            .into_iter()
+            .map(|network| {
+                let queue = Arc::new(JobQueue::new());
+                let address =
+                    spawn_control_service("127.0.0.1:0".parse().unwrap(), Arc::clone(&queue))
+                        .unwrap();
+                let daemon = thread::spawn(move || {
+                    let mut engine = Engine::new(network);
+                    let n_jobs =
+                        serve_queue::<Mersenne61, _>(1, &mut thread_rng(), &mut engine, &queue);
+                    engine.into_network().close().unwrap();
+                    n_jobs
+                });
+                (TcpStream::connect(address).unwrap(), daemon)
+            })
+            .collect();
+
+        let results: Vec<_> = handles
+            .into_iter()
+            .enumerate()
+            .map(|(id, (mut stream, daemon))| {
+                thread::spawn(move || {
+                    let input = id as u64 + 2;
+                    let submitted = request(
+                        &mut stream,
+                        json!({
+                            "method": "submit",
+                            "job": { "session": 1, "inputs": [input], "expression": "x0 * x1 + x2" },
+                        }),
+                    );
+                    assert_eq!(submitted, json!({ "session": 1, "status": "queued" }));
+                    let result = request(&mut stream, json!({ "method": "result", "session": 1 }));
+                    let unknown = request(&mut stream, json!({ "method": "status", "session": 5 }));
+                    assert_eq!(
+                        unknown,
+                        json!({ "error": ControlError::UnknownJob(5).to_string() })
+                    );
+                    let invalid = request(&mut stream, json!({ "method": "cancel" }));
+                    assert!(invalid["error"].is_string());
+                    let shutdown = request(&mut stream, json!({ "method": "shutdown" }));
+                    assert_eq!(shutdown, json!({ "queued": 0 }));
+                    assert_eq!(daemon.join().unwrap(), 1);
+                    result
+                })
+            })
+            .collect();
+
+        for result in results {
+            assert_eq!(
+                result.join().unwrap(),
+                json!({ "session": 1, "status": "done", "result": "10" })
+            );
+        }
+    }
+}
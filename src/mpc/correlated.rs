@@ -0,0 +1,185 @@
+use rand::{CryptoRng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use zeroize::Zeroizing;
+
+use crate::{math::FiniteField, transport::Transport};
+
+/// Label under which the seeds are exported from the sessions of the channels.
+pub const SEED_LABEL: &[u8] = b"EXPORTER-shami-rs pairwise seed";
+
+/// Correlated randomness of a party, drawn from a generator seeded with a secret that the party
+/// shares with every other party, so that protocols such as zero sharings or the masking of
+/// values sample their randomness without extra communication. Both parties of a pair draw the
+/// same values from their generator as long as they draw in the same order, so every party must
+/// make the same calls in the same order.
+#[derive(Debug)]
+pub struct CorrelatedRandomness {
+    /// ID of the party.
+    id: usize,
+    /// Generators shared with every party, indexed by party ID, with `None` for the party itself.
+    generators: Vec<Option<ChaCha20Rng>>,
+}
+
+impl CorrelatedRandomness {
+    /// Creates the correlated randomness of the party `id` from the seeds that it shares with
+    /// every party, indexed by party ID, with `None` for the party itself.
+    pub fn from_seeds(id: usize, seeds: Vec<Option<[u8; 32]>>) -> Self {
+        let generators = seeds
+            .into_iter()
+            .map(|seed| seed.map(ChaCha20Rng::from_seed))
+            .collect();
+        Self { id, generators }
+    }
+
+    /// Runs the setup phase in which the party establishes a seed with every other party. The
+    /// seed of a pair is derived from the session of their channel if the transport can, e.g.
+    /// with the keying material exporter of TLS, which takes no message. Otherwise, the party
+    /// with the lowest ID samples the seed and sends it to the other one, so the channel must be
+    /// encrypted to keep it secret from the other parties.
+    pub fn setup<N, R>(network: &mut N, rng: &mut R) -> anyhow::Result<Self>
+    where
+        N: Transport,
+        R: Rng + CryptoRng,
+    {
+        let id = network.id();
+        let n_parties = network.n_parties();
+        let mut seeds: Vec<Option<[u8; 32]>> = vec![None; n_parties];
+        let mut derived = 0;
+        for party in (0..n_parties).filter(|party| *party != id) {
+            seeds[party] = network.shared_secret(party, SEED_LABEL)?;
+            derived += usize::from(seeds[party].is_some());
+        }
+        // Every message is sent before any is received, as in the exchanges of the transport.
+        for (party, slot) in seeds.iter_mut().enumerate().skip(id + 1) {
+            if slot.is_none() {
+                let seed: [u8; 32] = rng.gen();
+                network.send_message(party, seed.to_vec())?;
+                *slot = Some(seed);
+            }
+        }
+        for (party, slot) in seeds.iter_mut().enumerate().take(id) {
+            if slot.is_none() {
+                let message = Zeroizing::new(network.recv_message(party)?);
+                let seed: [u8; 32] = message.as_slice().try_into().map_err(|_| {
+                    anyhow::anyhow!(
+                        "party {party} sent a seed of {} bytes instead of 32",
+                        message.len()
+                    )
+                })?;
+                *slot = Some(seed);
+            }
+        }
+        log::info!(
+            "established the pairwise seeds, {derived} of them derived from the channels and {} exchanged",
+            n_parties - 1 - derived
+        );
+        Ok(Self::from_seeds(id, seeds))
+    }
+
+    /// Returns the ID of the party.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the number of parties, including this one.
+    pub fn n_parties(&self) -> usize {
+        self.generators.len()
+    }
+
+    /// Returns the generator that the party shares with the party of the given ID, or `None` if
+    /// it is the party itself or there is no such party.
+    pub fn generator(&mut self, party_id: usize) -> Option<&mut ChaCha20Rng> {
+        self.generators.get_mut(party_id)?.as_mut()
+    }
+
+    /// Draws `count` random field elements that the party of the given ID draws as well, e.g. to
+    /// mask the values that they send to each other.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the party is the party itself or there is no such party.
+    pub fn common<T: FiniteField>(&mut self, party_id: usize, count: usize) -> Vec<T> {
+        let generator = self
+            .generator(party_id)
+            .unwrap_or_else(|| panic!("there is no generator shared with the party {party_id}"));
+        (0..count).map(|_| T::random(generator)).collect()
+    }
+
+    /// Draws additive shares of `count` zeros: the values of every party add up to zero. Every
+    /// pair of parties draws a common value, which the party with the lowest ID adds and the other
+    /// one subtracts. The shares are only random to a coalition that misses some party.
+    pub fn zero_sharing<T: FiniteField>(&mut self, count: usize) -> Vec<T> {
+        let id = self.id;
+        let mut shares = vec![T::ZERO; count];
+        for party in (0..self.n_parties()).filter(|party| *party != id) {
+            for (share, value) in shares.iter_mut().zip(self.common::<T>(party, count)) {
+                *share = if id < party {
+                    share.add(&value)
+                } else {
+                    share.subtract(&value)
+                };
+            }
+        }
+        shares
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::CorrelatedRandomness;
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        net::Network,
+    };
+
+    #[test]
+    fn pairs_draw_the_same_randomness() {
+        const N_PARTIES: usize = 4;
+
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .map(|mut network| {
+                thread::spawn(move || {
+                    let mut correlated =
+                        CorrelatedRandomness::setup(&mut network, &mut thread_rng()).unwrap();
+                    let zeros: Vec<Mersenne61> = correlated.zero_sharing(5);
+                    let common: Vec<Vec<Mersenne61>> = (0..N_PARTIES)
+                        .map(|party| {
+                            if party == network.id() {
+                                Vec::new()
+                            } else {
+                                correlated.common(party, 3)
+                            }
+                        })
+                        .collect();
+                    assert!(correlated.generator(network.id()).is_none());
+                    (zeros, common)
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        for k in 0..5 {
+            let sum = results
+                .iter()
+                .fold(Mersenne61::ZERO, |sum, (zeros, _)| sum.add(&zeros[k]));
+            assert!(sum.equal(&Mersenne61::ZERO));
+        }
+        assert!(!results[0].0[0].equal(&Mersenne61::ZERO));
+        for i in 0..N_PARTIES {
+            for j in 0..N_PARTIES {
+                if i != j {
+                    assert_eq!(results[i].1[j], results[j].1[i]);
+                }
+            }
+        }
+        assert_ne!(results[0].1[1], results[0].1[2]);
+    }
+}
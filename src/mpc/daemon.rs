@@ -0,0 +1,414 @@
+use std::{
+    io::{BufRead, Write},
+    str::FromStr,
+    time::Duration,
+};
+
+use rand::{CryptoRng, Rng};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use super::{
+    engine::Engine, expression::Expression, reconstruct_secret, share::ShamirShare, share_inputs,
+};
+use crate::{
+    math::{
+        dynamic::{DynField, FieldKind, FieldVisitor},
+        fixed_point::{Decimal, FixedPointError},
+        PrimeField,
+    },
+    net::Network,
+    transport::Transport,
+};
+
+/// Possible errors that may appear while reading or running a job.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum JobError {
+    /// The job is not a valid JSON object with the fields of a job.
+    #[error("the job is not correct: {0}")]
+    InvalidJob(String),
+
+    /// Some parties could not prepare their part of the job, e.g. because of an invalid input.
+    #[error("the parties {0:?} rejected the job")]
+    Rejected(Vec<usize>),
+
+    /// The parties received different jobs for the same session.
+    #[error("the parties received different jobs: {0:?}")]
+    Diverged(Vec<String>),
+}
+
+/// Computation requested to a daemon, which is run with the other parties in its own session of
+/// the network. A job is written as a JSON object, e.g.
+/// `{"session": 1, "inputs": [2, "3.5"], "expression": "x0 * x1", "fractional_bits": 1}`, and it
+/// may have a `deadline` in milliseconds and the `field` in which it is computed, e.g. `"p128"`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "JobSpec")]
+pub struct Job {
+    /// Session of the network in which the job is run, which must be greater than the session of
+    /// the previous job.
+    pub session: u32,
+    /// Private inputs of the party.
+    pub inputs: Vec<Decimal>,
+    /// Expression to evaluate over the inputs of the parties. If it is `None`, every input is
+    /// multiplied.
+    pub expression: Option<String>,
+    /// Number of fractional bits of the fixed-point encoding of the inputs.
+    pub fractional_bits: u32,
+    /// Time within which the job must finish, after which the parties abort it as in
+    /// [`Network::set_deadline`].
+    pub deadline: Option<Duration>,
+    /// Field in which the job is computed, or `None` for the default field of the daemon.
+    pub field: Option<FieldKind>,
+}
+
+/// Job as it is written in JSON.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobSpec {
+    session: u32,
+    #[serde(default)]
+    inputs: Vec<Value>,
+    expression: Option<String>,
+    #[serde(default)]
+    fractional_bits: u32,
+    deadline: Option<u64>,
+    field: Option<FieldKind>,
+}
+
+impl TryFrom<JobSpec> for Job {
+    type Error = FixedPointError;
+
+    fn try_from(spec: JobSpec) -> Result<Self, Self::Error> {
+        let inputs = spec
+            .inputs
+            .iter()
+            .map(|input| match input {
+                Value::Number(number) => number.to_string().parse(),
+                Value::String(number) => number.parse(),
+                _ => Err(FixedPointError::InvalidNumber(input.to_string())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            session: spec.session,
+            inputs,
+            expression: spec.expression,
+            fractional_bits: spec.fractional_bits,
+            deadline: spec.deadline.map(Duration::from_millis),
+            field: spec.field,
+        })
+    }
+}
+
+impl FromStr for Job {
+    type Err = JobError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(|err| JobError::InvalidJob(err.to_string()))
+    }
+}
+
+impl Job {
+    /// Returns the part of the job that every party must agree on, without the private inputs.
+    fn public_form(&self) -> String {
+        json!({
+            "session": self.session,
+            "expression": self.expression,
+            "fractional_bits": self.fractional_bits,
+            "deadline": self.deadline.map(|deadline| deadline.as_millis()),
+            "field": self.field,
+        })
+        .to_string()
+    }
+
+    /// Encodes the inputs and parses the expression of the job.
+    fn prepare<T: PrimeField>(&self) -> anyhow::Result<(Vec<T>, Option<Expression>)> {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| input.encode(self.fractional_bits))
+            .collect::<Result<_, _>>()?;
+        let expression = self
+            .expression
+            .as_deref()
+            .map(Expression::from_str)
+            .transpose()?;
+        Ok((inputs, expression))
+    }
+
+    /// Runs the job with the other parties in the session of the job, returning the opened
+    /// result. Before sharing the inputs, the parties check that they all received the same job
+    /// and that they could all prepare it, so a job rejected by some party fails for all of them
+    /// without running the protocol. A job that exceeds its deadline is aborted, which closes the
+    /// connections with the other parties, so the next jobs fail as well.
+    pub fn run<T, R>(
+        &self,
+        threshold: usize,
+        rng: &mut R,
+        engine: &mut Engine<Network>,
+    ) -> anyhow::Result<T>
+    where
+        T: PrimeField,
+        R: Rng + CryptoRng,
+    {
+        let Some(deadline) = self.deadline else {
+            return self.run_session(threshold, rng, engine);
+        };
+        let outer = engine.network_mut().start_deadline(deadline);
+        let result = self.run_session(threshold, rng, engine);
+        engine.network_mut().restore_deadline(outer);
+        result
+    }
+
+    /// Runs the job in its session of the network.
+    fn run_session<T, R>(
+        &self,
+        threshold: usize,
+        rng: &mut R,
+        engine: &mut Engine<Network>,
+    ) -> anyhow::Result<T>
+    where
+        T: PrimeField,
+        R: Rng + CryptoRng,
+    {
+        engine.network_mut().start_session(self.session)?;
+        let prepared = self.prepare::<T>();
+        if let Err(err) = &prepared {
+            log::warn!("rejecting the job of session {}: {err:#}", self.session);
+        }
+        let announcement = prepared.as_ref().ok().map(|_| self.public_form());
+        let announcements: Vec<Option<String>> =
+            engine.broadcast_and_gather_values(&announcement)?;
+        let rejected: Vec<usize> = announcements
+            .iter()
+            .enumerate()
+            .filter_map(|(party, announcement)| announcement.is_none().then_some(party))
+            .collect();
+        if !rejected.is_empty() {
+            anyhow::bail!(JobError::Rejected(rejected));
+        }
+        if announcements.iter().any(|other| *other != announcement) {
+            anyhow::bail!(JobError::Diverged(
+                announcements.into_iter().flatten().collect()
+            ));
+        }
+        let (inputs, expression) = prepared?;
+
+        log::info!("running the job of session {}", self.session);
+        let own_shares = share_inputs(&inputs, engine.n_parties(), threshold, rng);
+        let shares: Vec<Vec<ShamirShare<T>>> = engine.exchange_values(&own_shares)?;
+        let n_inputs: Vec<usize> = shares.iter().map(Vec::len).collect();
+        let expression = expression.unwrap_or_else(|| Expression::product_of_inputs(&n_inputs));
+        let result_share = expression.evaluate(&shares, threshold, rng, engine)?;
+        let result_shares: Vec<ShamirShare<T>> =
+            engine.broadcast_and_gather_values(&result_share)?;
+        Ok(reconstruct_secret(result_shares))
+    }
+}
+
+/// Runs a job in the field given by [`FieldKind::dispatch`].
+struct RunJob<'a, R> {
+    job: &'a Job,
+    threshold: usize,
+    rng: &'a mut R,
+    engine: &'a mut Engine<Network>,
+}
+
+impl<R: Rng + CryptoRng> FieldVisitor for RunJob<'_, R> {
+    type Output = anyhow::Result<DynField>;
+
+    fn visit<T: PrimeField + Into<DynField>>(self) -> Self::Output {
+        let result = self
+            .job
+            .run::<T, R>(self.threshold, self.rng, self.engine)?;
+        Ok(result.into())
+    }
+}
+
+/// Serves the jobs read from the input, one JSON object per line, until the input ends, keeping
+/// the connections with the other parties alive between jobs. The outcome of each job is written
+/// to the output as a JSON object per line, either `{"session": 1, "result": "24"}` or
+/// `{"session": 1, "error": "..."}`, where the result is the field element as it is serialized.
+/// The jobs are computed in the field `T`, unless they name another one. A failed job does not stop the daemon, since the next job runs in a new session. Every party
+/// must receive the same jobs in the same order. Returns the number of jobs served.
+pub fn serve_jobs<T, R, I, O>(
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<Network>,
+    jobs: I,
+    mut output: O,
+) -> anyhow::Result<usize>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+    I: BufRead,
+    O: Write,
+{
+    let mut n_jobs = 0;
+    for line in jobs.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        n_jobs += 1;
+        let outcome = match line.parse::<Job>() {
+            Ok(job) => match run_in_field::<T, R>(&job, threshold, rng, engine) {
+                Ok(result) => json!({
+                    "session": job.session,
+                    "result": result,
+                }),
+                Err(err) => json!({ "session": job.session, "error": format!("{err:#}") }),
+            },
+            Err(err) => json!({ "session": null, "error": err.to_string() }),
+        };
+        writeln!(output, "{outcome}")?;
+        output.flush()?;
+    }
+    Ok(n_jobs)
+}
+
+/// Runs a job in its field, or in the field `T` if it does not name one, and returns its result
+/// as it is serialized.
+fn run_in_field<T, R>(
+    job: &Job,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<Network>,
+) -> anyhow::Result<String>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+{
+    Ok(match job.field {
+        Some(field) => serde_json::to_string(&field.dispatch(RunJob {
+            job,
+            threshold,
+            rng,
+            engine,
+        })?)?,
+        None => serde_json::to_string(&job.run::<T, R>(threshold, rng, engine)?)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, thread, time::Duration};
+
+    use rand::thread_rng;
+    use serde_json::{json, Value};
+
+    use super::{serve_jobs, Job, JobError};
+    use crate::{
+        math::{dynamic::FieldKind, mersenne61::Mersenne61, p128::P128},
+        mpc::engine::Engine,
+        net::Network,
+    };
+
+    #[test]
+    fn parse_jobs() {
+        let job: Job = r#"{"session": 3, "inputs": [2, "-1.5"], "fractional_bits": 1}"#
+            .parse()
+            .unwrap();
+        assert_eq!(job.session, 3);
+        assert_eq!(job.inputs.len(), 2);
+        assert_eq!(job.expression, None);
+        assert_eq!(job.deadline, None);
+        let job: Job = r#"{"session": 4, "deadline": 1500, "field": "mersenne127"}"#
+            .parse()
+            .unwrap();
+        assert_eq!(job.deadline, Some(Duration::from_millis(1500)));
+        assert_eq!(job.field, Some(FieldKind::Mersenne127));
+
+        for invalid in [
+            r#"{"inputs": [1]}"#,
+            r#"{"session": 1, "inputs": [true]}"#,
+            r#"{"session": 1, "circuit": "x0"}"#,
+            "session 1",
+        ] {
+            assert!(matches!(
+                invalid.parse::<Job>(),
+                Err(JobError::InvalidJob(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn serve_jobs_over_local_network() {
+        let jobs = [
+            [
+                r#"{"session": 1, "inputs": [2]}"#,
+                r#"{"session": 2, "inputs": [2, 10], "expression": "x0[1] + x1 * x2"}"#,
+                r#"{"session": 4, "inputs": [1]}"#,
+                r#"{"session": 5, "inputs": [1], "expression": "x0 + x1"}"#,
+                r#"{"session": 6, "inputs": [5], "expression": "x0 - x1"}"#,
+                r#"{"session": 7, "inputs": [2], "field": "p128"}"#,
+            ],
+            [
+                r#"{"session": 1, "inputs": [3]}"#,
+                r#"{"session": 2, "inputs": [3], "expression": "x0[1] + x1 * x2"}"#,
+                r#"{"session": 4, "inputs": [1]}"#,
+                r#"{"session": 5, "inputs": [1], "expression": "x0 * x1"}"#,
+                r#"{"session": 6, "inputs": [2], "expression": "x0 - x1"}"#,
+                r#"{"session": 7, "inputs": [3], "field": "p128"}"#,
+            ],
+            [
+                r#"{"session": 1, "inputs": [4]}"#,
+                r#"{"session": 2, "inputs": [4], "expression": "x0[1] + x1 * x2"}"#,
+                r#"{"session": 4, "inputs": ["1.5"]}"#,
+                r#"{"session": 5, "inputs": [1], "expression": "x0 + x1"}"#,
+                r#"{"session": 6, "inputs": [0], "expression": "x0 - x1"}"#,
+                r#"{"session": 7, "inputs": [4], "field": "p128"}"#,
+            ],
+        ];
+        let handles: Vec<_> = Network::create_local(3)
+            .into_iter()
+            .zip(jobs)
+            .map(|(network, jobs)| {
+                thread::spawn(move || {
+                    let mut output = Vec::new();
+                    let mut engine = Engine::new(network);
+                    let n_jobs = serve_jobs::<Mersenne61, _, _, _>(
+                        1,
+                        &mut thread_rng(),
+                        &mut engine,
+                        Cursor::new(jobs.join("\n")),
+                        &mut output,
+                    )
+                    .unwrap();
+                    engine.into_network().close().unwrap();
+                    assert_eq!(n_jobs, 6);
+                    String::from_utf8(output)
+                        .unwrap()
+                        .lines()
+                        .map(|line| serde_json::from_str(line).unwrap())
+                        .collect::<Vec<Value>>()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let outcomes = handle.join().unwrap();
+            assert_eq!(outcomes[0], json!({ "session": 1, "result": "24" }));
+            assert_eq!(outcomes[1], json!({ "session": 2, "result": "22" }));
+            // The input 1.5 can not be encoded without fractional bits.
+            assert_eq!(
+                outcomes[2]["error"],
+                json!(JobError::Rejected(vec![2]).to_string())
+            );
+            assert!(outcomes[3]["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("the parties received different jobs"));
+            // The connections are still usable after the failed jobs.
+            assert_eq!(outcomes[4], json!({ "session": 6, "result": "3" }));
+            // The same connections serve a job in another field.
+            assert_eq!(
+                outcomes[5],
+                json!({
+                    "session": 7,
+                    "result": serde_json::to_string(&P128::from(24)).unwrap(),
+                })
+            );
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    check_parameters, compute_shamir_share, deal_beaver_triples,
+    mpspdz::{self, MpSpdzFormat},
+    parallel,
+    share::{BeaverTriple, ShamirShare},
+    share_file::ShareFile,
+    share_inputs,
+};
+use crate::{
+    math::{FiniteField, PrimeField},
+    net::{audit::AuditEvent, Network},
+};
+
+/// Possible errors in the material received from a trusted dealer.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DealingError {
+    /// The dealer sent the material of another party.
+    #[error("the dealer sent the material of party {found} to party {expected}")]
+    WrongParty { expected: usize, found: usize },
+
+    /// The dealer sent shares of a degree other than the number of corruptions.
+    #[error("the dealer sent shares of degree {found}, but {expected} was expected")]
+    WrongDegree { expected: usize, found: usize },
+}
+
+/// Material that a trusted dealer deals to a party: the shares of the inputs of the dealer, and
+/// preprocessing material, i.e. the shares of Beaver triples and of random bits. Every party
+/// receives the shares of the same inputs, triples and bits, in the same order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dealing<T> {
+    /// ID of the party that receives the material.
+    pub party: usize,
+    /// Number of corruptions tolerated, which is the degree of every share.
+    pub threshold: usize,
+    /// Shares of the inputs of the dealer.
+    pub inputs: Vec<ShamirShare<T>>,
+    /// Shares of random Beaver triples.
+    pub triples: Vec<BeaverTriple<T>>,
+    /// Shares of random bits, i.e. of random values that are zero or one.
+    pub bits: Vec<ShamirShare<T>>,
+}
+
+impl<T: FiniteField> Dealing<T> {
+    /// Checks that the material belongs to the given party and that every share has the degree
+    /// `threshold`.
+    pub fn check(&self, party: usize, threshold: usize) -> Result<(), DealingError> {
+        if self.party != party {
+            return Err(DealingError::WrongParty {
+                expected: party,
+                found: self.party,
+            });
+        }
+        let degrees = self
+            .inputs
+            .iter()
+            .chain(&self.bits)
+            .chain(
+                self.triples
+                    .iter()
+                    .flat_map(|triple| [&triple.a, &triple.b, &triple.c]),
+            )
+            .map(|share| share.degree);
+        for found in std::iter::once(self.threshold).chain(degrees) {
+            if found != threshold {
+                return Err(DealingError::WrongDegree {
+                    expected: threshold,
+                    found,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formats of the files to which the shares of a secret are dealt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareFileFormat {
+    /// A [`ShareFile`] `share_<i>.json` per party, with its ID and its share.
+    Json,
+    /// The file `Transactions-P<i>.data` in which MP-SPDZ persists the shares of each party, with
+    /// elements of the given number of 64-bit limbs.
+    MpSpdz { limbs: usize },
+}
+
+/// Amounts of preprocessing material that a trusted dealer deals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Preprocessing {
+    /// Number of Beaver triples.
+    pub triples: usize,
+    /// Number of random bits.
+    pub bits: usize,
+}
+
+/// Deals the shares of the inputs and of the preprocessing material to `n_parties`, returning
+/// the material of every party indexed by its ID.
+pub fn deal<T, R>(
+    inputs: &[T],
+    preprocessing: Preprocessing,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+) -> Vec<Dealing<T>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    let input_shares = share_inputs(inputs, n_parties, threshold, rng);
+    let triples = deal_beaver_triples(preprocessing.triples, n_parties, threshold, rng, None);
+    let mut bits: Vec<Vec<ShamirShare<T>>> = (0..n_parties)
+        .map(|_| Vec::with_capacity(preprocessing.bits))
+        .collect();
+    for _ in 0..preprocessing.bits {
+        let bit = T::from(u64::from(rng.gen::<bool>()));
+        for (party_bits, share) in bits
+            .iter_mut()
+            .zip(compute_shamir_share(&bit, n_parties, threshold, rng))
+        {
+            party_bits.push(share);
+        }
+    }
+    input_shares
+        .into_iter()
+        .zip(triples)
+        .zip(bits)
+        .enumerate()
+        .map(|(party, ((inputs, triples), bits))| Dealing {
+            party,
+            threshold,
+            inputs,
+            triples,
+            bits,
+        })
+        .collect()
+}
+
+/// Deals the shares of a secret to files in the output directory, one per party, in the given
+/// format. Returns the paths of the files, indexed by party ID.
+pub fn deal_share_files<T, R>(
+    secret: &T,
+    n_parties: usize,
+    threshold: usize,
+    output_dir: &Path,
+    format: ShareFileFormat,
+    rng: &mut R,
+) -> anyhow::Result<Vec<PathBuf>>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+{
+    check_parameters::<T>(n_parties, threshold, false)?;
+    let shares = compute_shamir_share(secret, n_parties, threshold, rng);
+    fs::create_dir_all(output_dir)?;
+    let mut paths = Vec::with_capacity(n_parties);
+    for file in ShareFile::for_dealing(shares, rng) {
+        let party = file.party;
+        let path = match format {
+            ShareFileFormat::Json => {
+                let path = output_dir.join(format!("share_{party}.json"));
+                fs::write(&path, serde_json::to_vec(&file)?)?;
+                path
+            }
+            ShareFileFormat::MpSpdz { limbs } => {
+                let path = output_dir.join(mpspdz::transactions_file(party));
+                MpSpdzFormat::new(limbs).write_shares(&path, &[file.share])?;
+                path
+            }
+        };
+        log::info!("wrote the share of party {party} to {path:?}");
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Deals the shares of `count` random Beaver triples to the preprocessing files of MP-SPDZ, in
+/// the directory `<n>-S-<bits>` of the output directory, which is returned.
+pub fn deal_triple_files<T, R>(
+    count: usize,
+    n_parties: usize,
+    threshold: usize,
+    output_dir: &Path,
+    limbs: usize,
+    rng: &mut R,
+) -> anyhow::Result<PathBuf>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+{
+    check_parameters::<T>(n_parties, threshold, true)?;
+    let triples = deal_beaver_triples::<T, _>(count, n_parties, threshold, rng, None);
+    let dir = output_dir.join(mpspdz::preprocessing_dir::<T>(n_parties));
+    fs::create_dir_all(&dir)?;
+    // The files of the parties are serialized and written in parallel.
+    parallel::map_chunks(n_parties, 1, |party, _| {
+        let path = dir.join(mpspdz::triples_file(party));
+        MpSpdzFormat::new(limbs).write_triples(&path, &triples[party])?;
+        log::info!("wrote {count} triples of party {party} to {path:?}");
+        Ok(())
+    })
+    .into_iter()
+    .collect::<anyhow::Result<()>>()?;
+    Ok(dir)
+}
+
+/// Returns the ID of the dealer in a network of `n_parties` that includes it, which is the last
+/// one, so the parties that receive the material keep the IDs from zero.
+pub fn dealer_id(n_parties: usize) -> usize {
+    n_parties.saturating_sub(1)
+}
+
+/// Runs the role of the trusted dealer, which must be the last party of the network: deals the
+/// shares of the inputs and of the preprocessing material to the other parties, sending each of
+/// them its material, and records what it dealt to each party in its audit log. The dealer
+/// learns every secret that it deals, so the material is only as trustworthy as the dealer.
+pub fn run_dealer<T, R>(
+    inputs: &[T],
+    preprocessing: Preprocessing,
+    threshold: usize,
+    rng: &mut R,
+    network: &mut Network,
+) -> anyhow::Result<()>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    let n_parties = network.n_parties();
+    if network.id() != dealer_id(n_parties) {
+        anyhow::bail!("the dealer must be the last party of the network");
+    }
+    let n_receivers = n_parties - 1;
+    check_parameters::<T>(n_receivers, threshold, preprocessing.triples > 0)?;
+    network.audit(AuditEvent::Parameters {
+        protocol: "dealing".to_string(),
+        modulus: T::MODULUS.to_string(),
+        n_parties: n_receivers,
+        threshold,
+    });
+    log::info!(
+        "dealing {} inputs, {} triples and {} bits to {n_receivers} parties",
+        inputs.len(),
+        preprocessing.triples,
+        preprocessing.bits
+    );
+    for dealing in deal(inputs, preprocessing, n_receivers, threshold, rng) {
+        network.send_value(&dealing, dealing.party)?;
+        network.audit(AuditEvent::Dealt {
+            party_id: dealing.party,
+            inputs: dealing.inputs.len(),
+            triples: dealing.triples.len(),
+            bits: dealing.bits.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Receives the material of the party from the trusted dealer of [`run_dealer`], checking that
+/// it belongs to the party and that its shares have the degree `threshold`.
+pub fn receive_dealing<T: FiniteField>(
+    threshold: usize,
+    network: &mut Network,
+) -> anyhow::Result<Dealing<T>> {
+    let dealer = dealer_id(network.n_parties());
+    if network.id() == dealer {
+        anyhow::bail!("the last party of the network is the dealer");
+    }
+    let dealing: Dealing<T> = network.recv_value(dealer)?;
+    dealing.check(network.id(), threshold)?;
+    log::info!(
+        "received {} inputs, {} triples and {} bits from the dealer",
+        dealing.inputs.len(),
+        dealing.triples.len(),
+        dealing.bits.len()
+    );
+    Ok(dealing)
+}
+
+/// Writes the material received from the dealer to the output directory: all of it to
+/// `dealing_<i>.json`, and the Beaver triples also to `Triples-S-P<i>` in the format of MP-SPDZ
+/// with the default number of limbs, which the sessions take as their triples.
+pub fn write_dealing<T: PrimeField>(dealing: &Dealing<T>, output_dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(output_dir)?;
+    let path = output_dir.join(format!("dealing_{}.json", dealing.party));
+    fs::write(&path, serde_json::to_vec(dealing)?)?;
+    log::info!("wrote the material of the dealer to {path:?}");
+    let path = output_dir.join(mpspdz::triples_file(dealing.party));
+    MpSpdzFormat::new(mpspdz::DEFAULT_LIMBS).write_triples(&path, &dealing.triples)?;
+    log::info!("wrote {} triples to {path:?}", dealing.triples.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{
+        deal, deal_share_files, deal_triple_files, receive_dealing, run_dealer, write_dealing,
+        Dealing, DealingError, Preprocessing, ShareFileFormat,
+    };
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{
+            mpspdz::{self, MpSpdzFormat},
+            reconstruct_secret,
+            share::{BeaverTriple, ShamirShare},
+            share_file::{read_share_files, reconstruct_share_files},
+        },
+        net::{
+            audit::{self, AuditEvent},
+            Network,
+        },
+    };
+
+    #[test]
+    fn dealer_deals_inputs_triples_and_bits() {
+        const N_PARTIES: usize = 3;
+        const THRESHOLD: usize = 1;
+
+        let inputs: Vec<Mersenne61> = vec![7.into(), 11.into()];
+        let preprocessing = Preprocessing {
+            triples: 4,
+            bits: 16,
+        };
+        let path =
+            std::env::temp_dir().join(format!("shami_rs_dealer_audit_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut networks = Network::create_local(N_PARTIES + 1);
+        let mut dealer = networks.pop().unwrap();
+        dealer.record_audit_log(&path).unwrap();
+        let dealer_inputs = inputs.clone();
+        let dealer = thread::spawn(move || {
+            run_dealer(
+                &dealer_inputs,
+                preprocessing,
+                THRESHOLD,
+                &mut thread_rng(),
+                &mut dealer,
+            )
+            .unwrap();
+        });
+        let handles: Vec<_> = networks
+            .into_iter()
+            .map(|mut network| {
+                thread::spawn(move || receive_dealing::<Mersenne61>(THRESHOLD, &mut network))
+            })
+            .collect();
+        let dealings: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap().unwrap())
+            .collect();
+        dealer.join().unwrap();
+
+        let open =
+            |shares: &dyn Fn(usize) -> _| reconstruct_secret((0..N_PARTIES).map(shares).collect());
+        for (k, input) in inputs.iter().enumerate() {
+            assert_eq!(&open(&|party| dealings[party].inputs[k].clone()), input);
+        }
+        for k in 0..preprocessing.triples {
+            let a = open(&|party| dealings[party].triples[k].a.clone());
+            let b = open(&|party| dealings[party].triples[k].b.clone());
+            let c = open(&|party| dealings[party].triples[k].c.clone());
+            assert_eq!(a.multiply(&b), c);
+        }
+        for k in 0..preprocessing.bits {
+            let bit = open(&|party| dealings[party].bits[k].clone());
+            assert!(bit == Mersenne61::ZERO || bit == Mersenne61::ONE);
+        }
+
+        let entries = audit::verify(&path).unwrap();
+        let dealt: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| match entry.event {
+                AuditEvent::Dealt { party_id, .. } => Some(party_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(dealt, [0, 1, 2]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(dealings[0].check(0, THRESHOLD), Ok(()));
+        assert_eq!(
+            dealings[0].check(1, THRESHOLD),
+            Err(DealingError::WrongParty {
+                expected: 1,
+                found: 0
+            })
+        );
+        assert_eq!(
+            dealings[0].check(0, 2),
+            Err(DealingError::WrongDegree {
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn dealings_are_written_to_files() {
+        let dir =
+            std::env::temp_dir().join(format!("shami_rs_dealer_files_{}", std::process::id()));
+        let secret = Mersenne61::from(42);
+
+        let paths = deal_share_files(
+            &secret,
+            3,
+            1,
+            &dir,
+            ShareFileFormat::Json,
+            &mut thread_rng(),
+        )
+        .unwrap();
+        let files = read_share_files::<Mersenne61>(&paths[..2]).unwrap();
+        assert_eq!(reconstruct_share_files(files).unwrap(), secret);
+        let format = ShareFileFormat::MpSpdz { limbs: 2 };
+        let paths = deal_share_files(&secret, 3, 1, &dir, format, &mut thread_rng()).unwrap();
+        let secrets: Vec<Mersenne61> = MpSpdzFormat::new(2).reconstruct_files(&paths, 1).unwrap();
+        assert_eq!(secrets, vec![secret.clone()]);
+
+        let triples_dir =
+            deal_triple_files::<Mersenne61, _>(2, 3, 1, &dir, 2, &mut thread_rng()).unwrap();
+        let triples: Vec<_> = (0..3)
+            .map(|party| {
+                let path = triples_dir.join(mpspdz::triples_file(party));
+                MpSpdzFormat::new(2)
+                    .read_triples::<Mersenne61>(&path, 1)
+                    .unwrap()
+            })
+            .collect();
+        for k in 0..2 {
+            let open = |share: &dyn Fn(&BeaverTriple<Mersenne61>) -> ShamirShare<Mersenne61>| {
+                reconstruct_secret(triples.iter().map(|party| share(&party[k])).collect())
+            };
+            let a = open(&|triple| triple.a.clone());
+            let b = open(&|triple| triple.b.clone());
+            assert_eq!(a.multiply(&b), open(&|triple| triple.c.clone()));
+        }
+
+        let preprocessing = Preprocessing {
+            triples: 1,
+            bits: 0,
+        };
+        let dealing = deal(
+            std::slice::from_ref(&secret),
+            preprocessing,
+            2,
+            1,
+            &mut thread_rng(),
+        )
+        .remove(1);
+        write_dealing(&dealing, &dir).unwrap();
+        let written: Dealing<Mersenne61> =
+            serde_json::from_slice(&std::fs::read(dir.join("dealing_1.json")).unwrap()).unwrap();
+        assert_eq!(written, dealing);
+        let path = dir.join(mpspdz::triples_file(1));
+        assert_eq!(
+            MpSpdzFormat::new(mpspdz::DEFAULT_LIMBS)
+                .read_triples(&path, 1)
+                .unwrap(),
+            dealing.triples
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
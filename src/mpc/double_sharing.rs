@@ -0,0 +1,417 @@
+use std::collections::VecDeque;
+
+use rand::{CryptoRng, Rng};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use super::{
+    engine::Engine,
+    recombine_products,
+    share::{ShamirShare, ShareBatch},
+    share_inputs,
+};
+use crate::{codec::WireCodec, math::FiniteField, transport::Transport};
+
+/// ID of the party that opens the masked products and sends them back to the other parties in
+/// the degree reduction of [`run_king_reduction`].
+pub const KING: usize = 0;
+
+/// Possible errors in the use of the double sharings.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DoubleSharingError {
+    /// The party does not hold enough double sharings for the values to reduce.
+    #[error("reducing {needed} values needs as many double sharings, but {available} are left")]
+    NotEnoughSharings { needed: usize, available: usize },
+
+    /// The double sharings can not mask values of the given degree.
+    #[error("the double sharings mask values of degree up to {found}, but the values have degree {expected}")]
+    DegreeTooLow { found: usize, expected: usize },
+}
+
+/// Shares of the same random secret with two degrees: the threshold, and a higher degree such as
+/// twice the threshold, which is the degree of the products of two shares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoubleSharing<T> {
+    /// Share of degree `threshold` of the secret.
+    pub low: ShamirShare<T>,
+    /// Share of the higher degree of the secret.
+    pub high: ShamirShare<T>,
+}
+
+/// Pool of random double sharings generated ahead of the products whose degree they reduce, as in
+/// the multiplication of Damgård and Nielsen, and consumed in order.
+#[derive(Debug)]
+pub struct DoubleSharings<T> {
+    /// Double sharings that are left.
+    sharings: VecDeque<DoubleSharing<T>>,
+}
+
+impl<T> Default for DoubleSharings<T> {
+    fn default() -> Self {
+        Self {
+            sharings: VecDeque::new(),
+        }
+    }
+}
+
+impl<T: FiniteField> DoubleSharings<T> {
+    /// Generates `count` random double sharings of degrees `threshold` and `high_degree` together
+    /// with the other parties, in a single round. Every party shares random secrets with both
+    /// degrees, and the shares received from the parties are combined with a Vandermonde matrix
+    /// of `n_parties - threshold` rows, every square submatrix of which is invertible, so the
+    /// secrets contributed by the honest parties give as many random double sharings that no
+    /// coalition of `threshold` parties knows. Each party shares one secret per
+    /// `n_parties - threshold` double sharings, so a double sharing costs `O(n)` elements of
+    /// communication instead of the `O(n^2)` of a resharing. No message is exchanged if `count`
+    /// is zero.
+    pub fn generate<R, N>(
+        count: usize,
+        n_parties: usize,
+        threshold: usize,
+        high_degree: usize,
+        rng: &mut R,
+        network: &mut N,
+    ) -> anyhow::Result<Self>
+    where
+        R: Rng + CryptoRng,
+        N: Transport,
+    {
+        if count == 0 {
+            return Ok(Self::default());
+        }
+        anyhow::ensure!(
+            threshold <= high_degree && high_degree < n_parties,
+            "{n_parties} parties can not generate double sharings of degrees {threshold} and \
+             {high_degree}"
+        );
+        let n_outputs = n_parties - threshold;
+        let n_secrets = count.div_ceil(n_outputs);
+        let secrets: Zeroizing<Vec<T>> =
+            Zeroizing::new((0..n_secrets).map(|_| T::random(rng)).collect());
+        let low = share_inputs(&secrets, n_parties, threshold, rng);
+        let high = share_inputs(&secrets, n_parties, high_degree, rng);
+        let batch = |degree: usize, shares: Vec<ShamirShare<T>>| ShareBatch {
+            degree,
+            values: shares.into_iter().map(|share| share.value).collect(),
+        };
+        let own: Vec<(ShareBatch<T>, ShareBatch<T>)> = low
+            .into_iter()
+            .zip(high)
+            .map(|(low, high)| (batch(threshold, low), batch(high_degree, high)))
+            .collect();
+        log::info!("generating {count} double sharings of degrees {threshold} and {high_degree}");
+        let received: Vec<(ShareBatch<T>, ShareBatch<T>)> = network.exchange_values(&own)?;
+        for (party, (low, high)) in received.iter().enumerate() {
+            for (batch, degree) in [(low, threshold), (high, high_degree)] {
+                if batch.values.len() != n_secrets {
+                    anyhow::bail!(
+                        "party {party} sent the shares of {} random secrets, but {n_secrets} were \
+                         expected",
+                        batch.values.len()
+                    );
+                }
+                if batch.degree != degree {
+                    anyhow::bail!(
+                        "party {party} sent shares of degree {}, but {degree} was expected",
+                        batch.degree
+                    );
+                }
+            }
+        }
+
+        // The row `k` of the matrix holds the powers `(i + 1)^k` of the points of the parties.
+        let matrix: Vec<Vec<T>> = (0..n_outputs)
+            .map(|k| {
+                (0..n_parties)
+                    .map(|i| T::from(i as u64 + 1).pow(k as u128))
+                    .collect()
+            })
+            .collect();
+        let extract = |row: &[T], values: &dyn Fn(usize) -> T| {
+            row.iter().enumerate().fold(T::ZERO, |sum, (i, entry)| {
+                sum.add(&entry.multiply(&values(i)))
+            })
+        };
+        let mut sharings = VecDeque::with_capacity(n_secrets * n_outputs);
+        for secret in 0..n_secrets {
+            for row in &matrix {
+                let low = extract(row, &|i| received[i].0.values[secret].clone());
+                let high = extract(row, &|i| received[i].1.values[secret].clone());
+                sharings.push_back(DoubleSharing {
+                    low: ShamirShare::new(low, threshold),
+                    high: ShamirShare::new(high, high_degree),
+                });
+            }
+        }
+        sharings.truncate(count);
+        Ok(Self { sharings })
+    }
+
+    /// Adds the double sharings of another pool after the ones that are left.
+    pub fn append(&mut self, mut other: Self) {
+        self.sharings.append(&mut other.sharings);
+    }
+
+    /// Moves the next `count` double sharings to a pool of their own, e.g. for the batches of a
+    /// pipelined evaluation, which are only moved if there are enough of them.
+    pub fn split(&mut self, count: usize) -> Result<Self, DoubleSharingError> {
+        Ok(Self {
+            sharings: self.take(count)?.into(),
+        })
+    }
+
+    /// Returns the number of double sharings that are left.
+    pub fn len(&self) -> usize {
+        self.sharings.len()
+    }
+
+    /// Tells whether no double sharing is left.
+    pub fn is_empty(&self) -> bool {
+        self.sharings.is_empty()
+    }
+
+    /// Consumes the next `count` double sharings, which are only consumed if there are enough of
+    /// them.
+    pub fn take(&mut self, count: usize) -> Result<Vec<DoubleSharing<T>>, DoubleSharingError> {
+        if self.sharings.len() < count {
+            return Err(DoubleSharingError::NotEnoughSharings {
+                needed: count,
+                available: self.sharings.len(),
+            });
+        }
+        Ok(self.sharings.drain(..count).collect())
+    }
+}
+
+/// Masks the values of shares of degree up to the high degree of the double sharings with their
+/// shares of that degree, and sends them to the [`KING`]. The king keeps its own masked values,
+/// which are returned.
+pub(crate) fn send_masked<T, N>(
+    values: &[T],
+    degree: usize,
+    sharings: &[DoubleSharing<T>],
+    network: &mut N,
+) -> anyhow::Result<Option<ShareBatch<T>>>
+where
+    T: FiniteField,
+    N: Transport,
+{
+    let high_degree = sharings
+        .first()
+        .map_or(degree, |sharing| sharing.high.degree);
+    if high_degree < degree {
+        anyhow::bail!(DoubleSharingError::DegreeTooLow {
+            found: high_degree,
+            expected: degree,
+        });
+    }
+    let masked = ShareBatch {
+        degree: high_degree,
+        values: values
+            .iter()
+            .zip(sharings)
+            .map(|(value, sharing)| value.add(&sharing.high.value))
+            .collect(),
+    };
+    if network.id() == KING {
+        return Ok(Some(masked));
+    }
+    let message = network.codec().encode(&masked)?;
+    network.send_message(KING, message)?;
+    Ok(None)
+}
+
+/// Receives the opened masked values from the [`KING`] and subtracts the shares of degree
+/// `threshold` of the double sharings, which gives shares of degree `threshold` of the values.
+/// The king opens the masked values that every party sent with [`send_masked`] and sends them
+/// to the other parties first.
+pub(crate) fn receive_opened<T, N>(
+    own: Option<ShareBatch<T>>,
+    sharings: &[DoubleSharing<T>],
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    N: Transport,
+{
+    let opened = match own {
+        Some(own) => {
+            let degree = own.degree;
+            let mut masked = Vec::with_capacity(engine.n_parties());
+            let mut own = Some(own);
+            for party_id in 0..engine.n_parties() {
+                let batch = if party_id == KING {
+                    own.take().expect("the own batch is taken once")
+                } else {
+                    let message = Zeroizing::new(engine.recv_message(party_id)?);
+                    engine.codec().decode::<ShareBatch<T>>(&message)?
+                };
+                if batch.values.len() != sharings.len() {
+                    anyhow::bail!(
+                        "party {party_id} sent {} masked values, but {} were expected",
+                        batch.values.len(),
+                        sharings.len()
+                    );
+                }
+                if batch.degree != degree {
+                    anyhow::bail!(
+                        "party {party_id} sent shares of degree {}, but {degree} was expected",
+                        batch.degree
+                    );
+                }
+                masked.push(batch.into_shares());
+            }
+            // The masked values are public, so they are sent as shares of degree zero.
+            let opened = ShareBatch {
+                degree: 0,
+                values: recombine_products(&masked, sharings.len(), engine.accelerator())
+                    .into_iter()
+                    .map(|share| share.value)
+                    .collect(),
+            };
+            let message = engine.codec().encode(&opened)?;
+            for party_id in (0..engine.n_parties()).filter(|party_id| *party_id != KING) {
+                engine.send_message(party_id, message.clone())?;
+            }
+            opened
+        }
+        None => {
+            let message = engine.recv_message(KING)?;
+            let opened = engine.codec().decode::<ShareBatch<T>>(&message)?;
+            if opened.values.len() != sharings.len() || opened.degree != 0 {
+                anyhow::bail!(
+                    "party {KING} opened {} values of degree {}, but {} of degree 0 were expected",
+                    opened.values.len(),
+                    opened.degree,
+                    sharings.len()
+                );
+            }
+            opened
+        }
+    };
+    Ok(opened
+        .values
+        .into_iter()
+        .zip(sharings)
+        .map(|(value, sharing)| {
+            ShamirShare::new(value.subtract(&sharing.low.value), sharing.low.degree)
+        })
+        .collect())
+}
+
+/// Reduces the values of shares of degree up to `degree` to shares of degree `threshold` of the
+/// same secrets with double sharings, in two rounds: every party sends its values masked with
+/// the shares of high degree to the [`KING`], which opens them and sends them back, and every
+/// party subtracts its shares of low degree from the opened values. The king only learns the
+/// masked values, which are uniformly random, and each value takes `O(n)` elements of
+/// communication.
+pub fn run_king_reduction<T, N>(
+    values: &[T],
+    degree: usize,
+    sharings: &[DoubleSharing<T>],
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    N: Transport,
+{
+    if values.len() != sharings.len() {
+        anyhow::bail!(DoubleSharingError::NotEnoughSharings {
+            needed: values.len(),
+            available: sharings.len(),
+        });
+    }
+    let own = send_masked(values, degree, sharings, engine)?;
+    receive_opened(own, sharings, engine)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{run_king_reduction, DoubleSharingError, DoubleSharings};
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{engine::Engine, reconstruct_secret, share_inputs},
+        net::Network,
+    };
+
+    #[test]
+    fn double_sharings_reduce_products() {
+        const N_PARTIES: usize = 5;
+        const THRESHOLD: usize = 2;
+        // More than the double sharings of a single secret per party.
+        const COUNT: usize = 7;
+
+        let mut rng = thread_rng();
+        let values: Vec<Mersenne61> = (0..2 * COUNT)
+            .map(|_| Mersenne61::random(&mut rng))
+            .collect();
+        let shares = share_inputs(&values, N_PARTIES, THRESHOLD, &mut rng);
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(shares)
+            .map(|(mut network, shares)| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let mut pool = DoubleSharings::<Mersenne61>::generate(
+                        COUNT + 1,
+                        N_PARTIES,
+                        THRESHOLD,
+                        2 * THRESHOLD,
+                        &mut rng,
+                        &mut network,
+                    )
+                    .unwrap();
+                    assert_eq!(pool.len(), COUNT + 1);
+                    let sharings = pool.take(COUNT).unwrap();
+                    assert_eq!(
+                        pool.take(2),
+                        Err(DoubleSharingError::NotEnoughSharings {
+                            needed: 2,
+                            available: 1
+                        })
+                    );
+                    let products: Vec<Mersenne61> = (0..COUNT)
+                        .map(|k| {
+                            shares[k]
+                                .multiply(&shares[COUNT + k], N_PARTIES)
+                                .unwrap()
+                                .value
+                        })
+                        .collect();
+                    let reduced = run_king_reduction(
+                        &products,
+                        2 * THRESHOLD,
+                        &sharings,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap();
+                    (sharings, reduced)
+                })
+            })
+            .collect();
+        let (sharings, reduced): (Vec<_>, Vec<_>) = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .unzip();
+
+        for k in 0..COUNT {
+            let low = reconstruct_secret(sharings.iter().map(|s| s[k].low.clone()).collect());
+            let high = reconstruct_secret(sharings.iter().map(|s| s[k].high.clone()).collect());
+            assert_eq!(low, high);
+            assert!(reduced.iter().all(|shares| shares[k].degree == THRESHOLD));
+            // The reduced shares lie on a polynomial of degree `THRESHOLD`.
+            let product = reconstruct_secret(
+                reduced
+                    .iter()
+                    .take(THRESHOLD + 1)
+                    .map(|shares| shares[k].clone())
+                    .collect(),
+            );
+            assert_eq!(product, values[k].multiply(&values[COUNT + k]));
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+};
+
+use rand::{CryptoRng, Rng};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    accelerator::Accelerator, double_sharing::DoubleSharings, two_party::TripleStore,
+    verification::Security,
+};
+use crate::{codec::Codec, math::FiniteField, transport::Transport};
+
+/// Double sharings of the pool of an engine, by field and by their degrees.
+type SharingPools = HashMap<(TypeId, usize, usize), Box<dyn Any + Send + Sync>>;
+
+/// Context in which a party runs the protocols of [`crate::mpc`], which owns the transport to the
+/// other parties together with the state that the protocols keep from one call to the next, such
+/// as the Beaver triples of the two-party mode, the double sharings generated ahead of the
+/// products, the security under which they run, and the accelerator to which they offload their
+/// batches. The transport only carries the messages, so it
+/// knows nothing of the protocols. The engine is a [`Transport`] itself, which forwards the
+/// messages to the one it owns, so the protocols that do not need its state take it as any other
+/// transport. A transport that is borrowed, e.g. `&mut Network`, is also a transport, so an
+/// engine can be created for the duration of a single protocol.
+pub struct Engine<N> {
+    /// Transport to the other parties.
+    network: N,
+    /// Beaver triples with which the products are computed in the two-party mode and in the
+    /// online phase of [`super::preprocessing`].
+    triples: TripleStore,
+    /// Double sharings with which the degree of the products is reduced, generated ahead of them.
+    double_sharings: SharingPools,
+    /// Security under which the products are computed.
+    security: Security,
+    /// Accelerator to which the large batches of field arithmetic are offloaded, if any.
+    accelerator: Option<Arc<dyn Accelerator>>,
+}
+
+impl<N: Transport> Engine<N> {
+    /// Creates the engine of the given transport, without Beaver triples and in the passive
+    /// mode, which computes every batch in the CPU.
+    pub fn new(network: N) -> Self {
+        Self {
+            network,
+            triples: TripleStore::default(),
+            double_sharings: SharingPools::new(),
+            security: Security::default(),
+            accelerator: None,
+        }
+    }
+
+    /// Sets the security under which the products are computed. Every party must use the same
+    /// security.
+    pub fn with_security(mut self, security: Security) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Returns the security under which the products are computed.
+    pub fn security(&self) -> Security {
+        self.security
+    }
+
+    /// Sets the accelerator to which the large batches of field arithmetic are offloaded, such as
+    /// the recombination of the products.
+    pub fn with_accelerator(mut self, accelerator: Arc<dyn Accelerator>) -> Self {
+        log::info!(
+            "offloading the batch field arithmetic to {}",
+            accelerator.name()
+        );
+        self.accelerator = Some(accelerator);
+        self
+    }
+
+    /// Returns the accelerator of the engine, if any.
+    pub fn accelerator(&self) -> Option<&dyn Accelerator> {
+        self.accelerator.as_deref()
+    }
+
+    /// Loads the Beaver triples with which the products are computed.
+    pub fn with_triples(mut self, triples: TripleStore) -> Self {
+        self.set_triples(triples);
+        self
+    }
+
+    /// Replaces the Beaver triples with which the products are computed.
+    pub fn set_triples(&mut self, triples: TripleStore) {
+        self.triples = triples;
+    }
+
+    /// Returns the Beaver triples of the engine, including the consumed ones.
+    pub fn triples(&self) -> &TripleStore {
+        &self.triples
+    }
+
+    /// Returns the Beaver triples of the engine, from which the products take theirs.
+    pub fn triples_mut(&mut self) -> &mut TripleStore {
+        &mut self.triples
+    }
+
+    /// Generates `count` random double sharings of degrees `threshold` and `high_degree` in the
+    /// field `T` together with the other parties, in a single round, and adds them to the pool
+    /// from which the degree reductions take theirs, so that the products do not spend a round
+    /// generating them. Every party must preprocess the same double sharings.
+    pub fn preprocess_double_sharings<T, R>(
+        &mut self,
+        count: usize,
+        threshold: usize,
+        high_degree: usize,
+        rng: &mut R,
+    ) -> anyhow::Result<()>
+    where
+        T: FiniteField,
+        R: Rng + CryptoRng,
+    {
+        let n_parties = self.n_parties();
+        let generated =
+            DoubleSharings::<T>::generate(count, n_parties, threshold, high_degree, rng, self)?;
+        self.sharing_pool::<T>(threshold, high_degree)
+            .append(generated);
+        Ok(())
+    }
+
+    /// Returns the number of double sharings of degrees `threshold` and `high_degree` in the
+    /// field `T` that are left in the pool.
+    pub fn double_sharings_left<T: FiniteField>(
+        &self,
+        threshold: usize,
+        high_degree: usize,
+    ) -> usize {
+        self.double_sharings
+            .get(&(TypeId::of::<T>(), threshold, high_degree))
+            .and_then(|pool| pool.downcast_ref::<DoubleSharings<T>>())
+            .map_or(0, DoubleSharings::len)
+    }
+
+    /// Takes `count` double sharings of degrees `threshold` and `high_degree` in the field `T`
+    /// from the pool, generating the ones that are missing with the other parties in a single
+    /// round. The pools of the parties hold the same double sharings, so they all generate the
+    /// same number of them.
+    pub fn take_double_sharings<T, R>(
+        &mut self,
+        count: usize,
+        threshold: usize,
+        high_degree: usize,
+        rng: &mut R,
+    ) -> anyhow::Result<DoubleSharings<T>>
+    where
+        T: FiniteField,
+        R: Rng + CryptoRng,
+    {
+        let missing = count.saturating_sub(self.double_sharings_left::<T>(threshold, high_degree));
+        if missing > 0 {
+            log::debug!("the pool lacks {missing} double sharings, generating them");
+            self.preprocess_double_sharings::<T, R>(missing, threshold, high_degree, rng)?;
+        }
+        Ok(self
+            .sharing_pool::<T>(threshold, high_degree)
+            .split(count)?)
+    }
+
+    /// Returns the pool of double sharings of degrees `threshold` and `high_degree` in the field
+    /// `T`, which is created empty the first time.
+    fn sharing_pool<T: FiniteField>(
+        &mut self,
+        threshold: usize,
+        high_degree: usize,
+    ) -> &mut DoubleSharings<T> {
+        self.double_sharings
+            .entry((TypeId::of::<T>(), threshold, high_degree))
+            .or_insert_with(|| Box::new(DoubleSharings::<T>::default()))
+            .downcast_mut()
+            .expect("the pools are keyed by the type of their field")
+    }
+
+    /// Returns the transport of the engine.
+    pub fn network(&self) -> &N {
+        &self.network
+    }
+
+    /// Returns the transport of the engine, e.g. to use the methods of a concrete transport.
+    pub fn network_mut(&mut self) -> &mut N {
+        &mut self.network
+    }
+
+    /// Returns the transport of the engine, dropping the rest of its state.
+    pub fn into_network(self) -> N {
+        self.network
+    }
+
+    /// Replaces the transport of the engine with the one that `connect` creates from it, e.g.
+    /// after a failure of the network, keeping the rest of the state of the engine, such as the
+    /// security, the accelerator and the Beaver triples that were left, so that no triple is used
+    /// twice. The double sharings are dropped instead, since the parties may have consumed
+    /// different numbers of them before the failure, and the next products generate new ones.
+    pub fn map_network<M, F>(self, connect: F) -> anyhow::Result<Engine<M>>
+    where
+        M: Transport,
+        F: FnOnce(N) -> anyhow::Result<M>,
+    {
+        Ok(Engine {
+            network: connect(self.network)?,
+            triples: self.triples,
+            double_sharings: SharingPools::new(),
+            security: self.security,
+            accelerator: self.accelerator,
+        })
+    }
+}
+
+impl<N: fmt::Debug> fmt::Debug for Engine<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Engine")
+            .field("network", &self.network)
+            .field("triples", &self.triples)
+            .field("double_sharing_pools", &self.double_sharings.len())
+            .field("security", &self.security)
+            .field(
+                "accelerator",
+                &self
+                    .accelerator
+                    .as_ref()
+                    .map(|accelerator| accelerator.name()),
+            )
+            .finish()
+    }
+}
+
+impl<N: Transport> Transport for Engine<N> {
+    fn id(&self) -> usize {
+        self.network.id()
+    }
+
+    fn n_parties(&self) -> usize {
+        self.network.n_parties()
+    }
+
+    fn codec(&self) -> Codec {
+        self.network.codec()
+    }
+
+    fn shared_secret(&mut self, party_id: usize, label: &[u8]) -> anyhow::Result<Option<[u8; 32]>> {
+        self.network.shared_secret(party_id, label)
+    }
+
+    fn send_message(&mut self, party_id: usize, message: Vec<u8>) -> anyhow::Result<()> {
+        self.network.send_message(party_id, message)
+    }
+
+    fn recv_message(&mut self, party_id: usize) -> anyhow::Result<Vec<u8>> {
+        self.network.recv_message(party_id)
+    }
+
+    fn exchange_values<T>(&mut self, values: &[T]) -> anyhow::Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.network.exchange_values(values)
+    }
+
+    fn broadcast_and_gather_values<T>(&mut self, value: &T) -> anyhow::Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.network.broadcast_and_gather_values(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::Engine;
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{
+            compute_shamir_share, deal_beaver_triples, reconstruct_secret, run_multiply_protocol,
+            two_party::TripleStore,
+        },
+        net::Network,
+        transport::Transport,
+    };
+
+    #[test]
+    fn triples_are_kept_when_the_network_is_replaced() {
+        const N_PARTIES: usize = 2;
+        const THRESHOLD: usize = 1;
+
+        let mut rng = thread_rng();
+        let x = Mersenne61::from(6);
+        let y = Mersenne61::from(7);
+        let x_shares = compute_shamir_share(&x, N_PARTIES, THRESHOLD, &mut rng);
+        let y_shares = compute_shamir_share(&y, N_PARTIES, THRESHOLD, &mut rng);
+        let triples = deal_beaver_triples::<Mersenne61, _>(3, N_PARTIES, THRESHOLD, &mut rng, None);
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(Network::create_local(N_PARTIES))
+            .map(|(network, replacement)| {
+                let id = network.id();
+                let (x, y) = (x_shares[id].clone(), y_shares[id].clone());
+                let store = TripleStore::new(&triples[id]).unwrap();
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let mut engine = Engine::new(network).with_triples(store);
+                    let mut products = Vec::new();
+                    products.push(
+                        run_multiply_protocol(&x, &y, N_PARTIES, THRESHOLD, &mut rng, &mut engine)
+                            .unwrap(),
+                    );
+                    let mut engine = engine
+                        .map_network(|mut network| {
+                            network.close()?;
+                            Ok(replacement)
+                        })
+                        .unwrap();
+                    assert_eq!(engine.id(), id);
+                    assert_eq!(engine.triples().remaining(), 2);
+                    products.push(
+                        run_multiply_protocol(&x, &y, N_PARTIES, THRESHOLD, &mut rng, &mut engine)
+                            .unwrap(),
+                    );
+                    assert_eq!(engine.triples().remaining(), 1);
+                    engine.into_network().close().unwrap();
+                    products
+                })
+            })
+            .collect();
+        let products: Vec<Vec<_>> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        for k in 0..2 {
+            let shares = products.iter().map(|shares| shares[k].clone()).collect();
+            assert_eq!(reconstruct_secret(shares), x.multiply(&y));
+        }
+    }
+
+    #[test]
+    fn double_sharings_are_taken_from_the_pool() {
+        const N_PARTIES: usize = 3;
+        const THRESHOLD: usize = 1;
+
+        let mut rng = thread_rng();
+        let x = Mersenne61::from(6);
+        let y = Mersenne61::from(7);
+        let x_shares = compute_shamir_share(&x, N_PARTIES, THRESHOLD, &mut rng);
+        let y_shares = compute_shamir_share(&y, N_PARTIES, THRESHOLD, &mut rng);
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(Network::create_local(N_PARTIES))
+            .map(|(network, replacement)| {
+                let id = network.id();
+                let (x, y) = (x_shares[id].clone(), y_shares[id].clone());
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let mut engine = Engine::new(network);
+                    engine
+                        .preprocess_double_sharings::<Mersenne61, _>(
+                            2,
+                            THRESHOLD,
+                            2 * THRESHOLD,
+                            &mut rng,
+                        )
+                        .unwrap();
+                    let left = |engine: &Engine<_>| {
+                        engine.double_sharings_left::<Mersenne61>(THRESHOLD, 2 * THRESHOLD)
+                    };
+                    assert_eq!(left(&engine), 2);
+                    let mut products = Vec::new();
+                    for expected in [1, 0, 0] {
+                        products.push(
+                            run_multiply_protocol(
+                                &x,
+                                &y,
+                                N_PARTIES,
+                                THRESHOLD,
+                                &mut rng,
+                                &mut engine,
+                            )
+                            .unwrap(),
+                        );
+                        assert_eq!(left(&engine), expected);
+                    }
+                    engine
+                        .preprocess_double_sharings::<Mersenne61, _>(
+                            1,
+                            THRESHOLD,
+                            2 * THRESHOLD,
+                            &mut rng,
+                        )
+                        .unwrap();
+                    let engine = engine
+                        .map_network(|mut network| {
+                            network.close()?;
+                            Ok(replacement)
+                        })
+                        .unwrap();
+                    assert_eq!(left(&engine), 0);
+                    engine.into_network().close().unwrap();
+                    products
+                })
+            })
+            .collect();
+        let products: Vec<Vec<_>> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        for k in 0..3 {
+            let shares = products.iter().map(|shares| shares[k].clone()).collect();
+            assert_eq!(reconstruct_secret(shares), x.multiply(&y));
+        }
+    }
+}
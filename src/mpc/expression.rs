@@ -0,0 +1,350 @@
+use std::{iter::Peekable, str::FromStr};
+
+use rand::{CryptoRng, Rng};
+use thiserror::Error;
+
+use super::{circuit::Circuit, engine::Engine, share::ShamirShare};
+use crate::{math::FiniteField, transport::Transport};
+
+/// Possible errors that may appear while parsing or evaluating an expression.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ExpressionError {
+    /// The expression contains a character that is not part of the grammar.
+    #[error("unexpected character '{0}' in the expression")]
+    UnexpectedCharacter(char),
+
+    /// The expression ended before it was complete.
+    #[error("the expression is incomplete")]
+    UnexpectedEnd,
+
+    /// The expression contains a number that does not fit in 64 bits.
+    #[error("the number {0} in the expression is not correct")]
+    InvalidNumber(String),
+
+    /// The expression refers to an input that the party did not provide.
+    #[error("the expression refers to the input x{party}[{index}], which does not exist")]
+    UnknownInput { party: usize, index: usize },
+}
+
+/// Arithmetic expression over the inputs of the parties. The input `k` of party `i` is written
+/// `xi[k]`, or just `xi` for its first input, and the expression may combine the inputs and
+/// constants with `+`, `-`, `*` and parentheses, e.g. `x0 * x1[1] + 3 * (x2 - 1)`. The inputs
+/// may also be written `pi[k]` and `pi`, e.g. `(p0 + p1) * p2 - 5`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    /// Input at the given index of the party with the given ID.
+    Input { party: usize, index: usize },
+    /// Public constant.
+    Constant(u64),
+    /// Sum of two expressions.
+    Add(Box<Expression>, Box<Expression>),
+    /// Difference of two expressions.
+    Subtract(Box<Expression>, Box<Expression>),
+    /// Product of two expressions.
+    Multiply(Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Returns the inputs of every party given the number of inputs of each one.
+    fn all_inputs(n_inputs: &[usize]) -> impl Iterator<Item = Self> + '_ {
+        n_inputs
+            .iter()
+            .enumerate()
+            .flat_map(|(party, n)| (0..*n).map(move |index| Self::Input { party, index }))
+    }
+
+    /// Returns the expression that adds every input of every party, given the number of inputs
+    /// of each party. The sum of no inputs is zero.
+    pub fn sum_of_inputs(n_inputs: &[usize]) -> Self {
+        Self::all_inputs(n_inputs)
+            .reduce(|sum, input| Self::Add(Box::new(sum), Box::new(input)))
+            .unwrap_or(Self::Constant(0))
+    }
+
+    /// Returns the expression that multiplies every input of every party, given the number of
+    /// inputs of each party. The product of no inputs is one. The factors are multiplied in a
+    /// balanced tree, so the products of each level can be computed in the same round.
+    pub fn product_of_inputs(n_inputs: &[usize]) -> Self {
+        let mut factors: Vec<Self> = Self::all_inputs(n_inputs).collect();
+        while factors.len() > 1 {
+            let mut pairs = factors.into_iter();
+            factors = Vec::new();
+            while let Some(left) = pairs.next() {
+                factors.push(match pairs.next() {
+                    Some(right) => Self::Multiply(Box::new(left), Box::new(right)),
+                    None => left,
+                });
+            }
+        }
+        factors.pop().unwrap_or(Self::Constant(1))
+    }
+
+    /// Checks that the expression only refers to existing inputs, given the number of inputs of
+    /// each party.
+    pub fn check_inputs(&self, n_inputs: &[usize]) -> Result<(), ExpressionError> {
+        Circuit::from_expression(self).check_inputs(n_inputs)
+    }
+
+    /// Evaluates the expression on the shares of the inputs of the parties, indexed by party ID
+    /// and then by the position of the input, returning the share of the result. The expression
+    /// is compiled into a [`Circuit`] that is evaluated with the batched multiplication protocol,
+    /// so every party must evaluate the same expression.
+    pub fn evaluate<T, R, N>(
+        &self,
+        inputs: &[Vec<ShamirShare<T>>],
+        threshold: usize,
+        rng: &mut R,
+        engine: &mut Engine<N>,
+    ) -> anyhow::Result<ShamirShare<T>>
+    where
+        T: FiniteField,
+        R: Rng + CryptoRng,
+        N: Transport,
+    {
+        Circuit::from_expression(self).evaluate(inputs, threshold, rng, engine)
+    }
+
+    /// Evaluates the expression in the clear on the inputs of the parties, as the reference for
+    /// [`Expression::evaluate`].
+    pub fn evaluate_plain<T: FiniteField>(&self, inputs: &[Vec<T>]) -> Result<T, ExpressionError> {
+        Circuit::from_expression(self).evaluate_plain(inputs)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars().filter(|c| !c.is_whitespace()).peekable();
+        let expression = parse_sum(&mut chars)?;
+        match chars.next() {
+            Some(c) => Err(ExpressionError::UnexpectedCharacter(c)),
+            None => Ok(expression),
+        }
+    }
+}
+
+/// Parses a sequence of terms joined by `+` and `-`.
+fn parse_sum<I: Iterator<Item = char>>(
+    chars: &mut Peekable<I>,
+) -> Result<Expression, ExpressionError> {
+    let mut sum = parse_product(chars)?;
+    while let Some(&op) = chars.peek() {
+        let constructor = match op {
+            '+' => Expression::Add,
+            '-' => Expression::Subtract,
+            _ => break,
+        };
+        chars.next();
+        sum = constructor(Box::new(sum), Box::new(parse_product(chars)?));
+    }
+    Ok(sum)
+}
+
+/// Parses a sequence of factors joined by `*`.
+fn parse_product<I: Iterator<Item = char>>(
+    chars: &mut Peekable<I>,
+) -> Result<Expression, ExpressionError> {
+    let mut product = parse_factor(chars)?;
+    while chars.next_if_eq(&'*').is_some() {
+        product = Expression::Multiply(Box::new(product), Box::new(parse_factor(chars)?));
+    }
+    Ok(product)
+}
+
+/// Parses an input, a constant, a negated factor or an expression between parentheses.
+fn parse_factor<I: Iterator<Item = char>>(
+    chars: &mut Peekable<I>,
+) -> Result<Expression, ExpressionError> {
+    match chars.next().ok_or(ExpressionError::UnexpectedEnd)? {
+        '(' => {
+            let expression = parse_sum(chars)?;
+            match chars.next() {
+                Some(')') => Ok(expression),
+                Some(c) => Err(ExpressionError::UnexpectedCharacter(c)),
+                None => Err(ExpressionError::UnexpectedEnd),
+            }
+        }
+        '-' => Ok(Expression::Subtract(
+            Box::new(Expression::Constant(0)),
+            Box::new(parse_factor(chars)?),
+        )),
+        // `pi` is another name for the inputs of party `i`, which reads better when each party
+        // has a single input.
+        'x' | 'p' => {
+            let party = parse_index(chars)?;
+            let index = if chars.next_if_eq(&'[').is_some() {
+                let index = parse_index(chars)?;
+                match chars.next() {
+                    Some(']') => index,
+                    Some(c) => return Err(ExpressionError::UnexpectedCharacter(c)),
+                    None => return Err(ExpressionError::UnexpectedEnd),
+                }
+            } else {
+                0
+            };
+            Ok(Expression::Input { party, index })
+        }
+        c if c.is_ascii_digit() => {
+            let digits = format!("{c}{}", parse_digits(chars));
+            let constant = digits
+                .parse()
+                .map_err(|_| ExpressionError::InvalidNumber(digits))?;
+            Ok(Expression::Constant(constant))
+        }
+        c => Err(ExpressionError::UnexpectedCharacter(c)),
+    }
+}
+
+/// Parses a non-empty sequence of decimal digits that identifies a party or an input.
+fn parse_index<I: Iterator<Item = char>>(
+    chars: &mut Peekable<I>,
+) -> Result<usize, ExpressionError> {
+    let digits = parse_digits(chars);
+    if digits.is_empty() {
+        return Err(match chars.next() {
+            Some(c) => ExpressionError::UnexpectedCharacter(c),
+            None => ExpressionError::UnexpectedEnd,
+        });
+    }
+    digits
+        .parse()
+        .map_err(|_| ExpressionError::InvalidNumber(digits))
+}
+
+/// Parses a possibly empty sequence of decimal digits.
+fn parse_digits<I: Iterator<Item = char>>(chars: &mut Peekable<I>) -> String {
+    let mut digits = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        digits.push(digit);
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{Expression, ExpressionError};
+    use crate::{
+        math::mersenne61::Mersenne61,
+        mpc::{engine::Engine, reconstruct_secret, share::ShamirShare, share_inputs},
+        net::Network,
+    };
+
+    #[test]
+    fn parse_expressions() {
+        let expression: Expression = "x0 * (x1[2] + 2) - -x2".parse().unwrap();
+        let input = |party, index| Box::new(Expression::Input { party, index });
+        assert_eq!(
+            expression,
+            Expression::Subtract(
+                Box::new(Expression::Multiply(
+                    input(0, 0),
+                    Box::new(Expression::Add(
+                        input(1, 2),
+                        Box::new(Expression::Constant(2))
+                    ))
+                )),
+                Box::new(Expression::Subtract(
+                    Box::new(Expression::Constant(0)),
+                    input(2, 0)
+                ))
+            )
+        );
+        assert_eq!(
+            "(p0 + p1[1]) * x2 - 5".parse::<Expression>(),
+            "(x0 + x1[1]) * x2 - 5".parse::<Expression>()
+        );
+        assert_eq!(
+            "x0 +".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            "x0 / x1".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedCharacter('/'))
+        );
+        assert_eq!(
+            "(x0".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            "xa".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedCharacter('a'))
+        );
+        assert_eq!(
+            "x0[1".parse::<Expression>(),
+            Err(ExpressionError::UnexpectedEnd)
+        );
+        for missing in ["x3", "x1[2]"] {
+            let expression: Expression = missing.parse().unwrap();
+            assert!(matches!(
+                expression.check_inputs(&[1, 2, 1]),
+                Err(ExpressionError::UnknownInput { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn evaluate_over_local_network() {
+        const N_PARTIES: usize = 3;
+        const THRESHOLD: usize = 1;
+
+        let expression: Expression = "x0 * x1 * x2 + 5*(x1 - x0) + 7 + x0[1] * x2[1]"
+            .parse()
+            .unwrap();
+        let inputs = [vec![3u64, 10], vec![4], vec![5, 6]];
+
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(inputs)
+            .map(|(mut network, inputs)| {
+                let expression = expression.clone();
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let inputs: Vec<_> = inputs.into_iter().map(Mersenne61::from).collect();
+                    let own_shares = share_inputs(&inputs, N_PARTIES, THRESHOLD, &mut rng);
+                    let shares: Vec<Vec<ShamirShare<Mersenne61>>> =
+                        network.exchange_values(&own_shares).unwrap();
+                    expression
+                        .evaluate(&shares, THRESHOLD, &mut rng, &mut Engine::new(&mut network))
+                        .unwrap()
+                })
+            })
+            .collect();
+        let result_shares = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert_eq!(
+            reconstruct_secret(result_shares),
+            Mersenne61::from(3 * 4 * 5 + 5 + 7 + 10 * 6)
+        );
+    }
+
+    #[test]
+    fn sum_and_product_of_every_input() {
+        let input = |party, index| Box::new(Expression::Input { party, index });
+        assert_eq!(
+            Expression::sum_of_inputs(&[2, 0, 1]),
+            Expression::Add(
+                Box::new(Expression::Add(input(0, 0), input(0, 1))),
+                input(2, 0)
+            )
+        );
+        assert_eq!(
+            Expression::product_of_inputs(&[1, 2]),
+            Expression::Multiply(
+                Box::new(Expression::Multiply(input(0, 0), input(1, 0))),
+                input(1, 1)
+            )
+        );
+        assert_eq!(
+            Expression::product_of_inputs(&[0, 0]),
+            Expression::Constant(1)
+        );
+    }
+}
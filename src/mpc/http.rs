@@ -0,0 +1,271 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+use super::{
+    control::{ControlError, JobQueue, JobStatus},
+    daemon::Job,
+};
+
+/// Maximum size in bytes of the body of a request.
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+/// Time that the service waits for a client to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns the HTTP status code and the reason phrase of an error of the control service.
+fn error_status(err: &ControlError) -> (u16, &'static str) {
+    match err {
+        ControlError::InvalidRequest(_) => (400, "Bad Request"),
+        ControlError::UnknownJob(_) => (404, "Not Found"),
+        ControlError::SessionNotIncreasing { .. } => (409, "Conflict"),
+        ControlError::ShuttingDown => (503, "Service Unavailable"),
+    }
+}
+
+/// Returns the body of the response with the status of the job of a session.
+fn job_body(session: u32, status: &JobStatus) -> Value {
+    let mut body = json!({ "session": session });
+    if let (Value::Object(body), Ok(Value::Object(status))) =
+        (&mut body, serde_json::to_value(status))
+    {
+        body.extend(status);
+    }
+    body
+}
+
+/// Answers a request to the HTTP API, returning the status code, the reason phrase and the JSON
+/// body of the response. The API has two routes: `POST /jobs` submits the job of the body, and
+/// `GET /jobs/{session}` returns the status of the job of a session, waiting until the job
+/// finishes if the query is `?wait=true`.
+pub fn route(
+    queue: &JobQueue,
+    method: &str,
+    target: &str,
+    body: &[u8],
+) -> (u16, &'static str, Value) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let outcome = match (method, segments.as_slice()) {
+        ("POST", ["jobs"]) => serde_json::from_slice::<Job>(body)
+            .map_err(|err| ControlError::InvalidRequest(err.to_string()))
+            .and_then(|job| {
+                let session = job.session;
+                queue
+                    .submit(job)
+                    .map(|()| (201, "Created", session, JobStatus::Queued))
+            }),
+        ("GET", ["jobs", session]) => match session.parse() {
+            Ok(session) if query == "wait=true" => queue
+                .wait_result(session)
+                .map(|status| (200, "OK", session, status)),
+            Ok(session) => queue
+                .status(session)
+                .map(|status| (200, "OK", session, status)),
+            Err(_) => Err(ControlError::InvalidRequest(format!(
+                "the session {session} is not correct"
+            ))),
+        },
+        (_, ["jobs"] | ["jobs", _]) => {
+            return (
+                405,
+                "Method Not Allowed",
+                json!({ "error": "the method is not allowed" }),
+            )
+        }
+        _ => {
+            return (
+                404,
+                "Not Found",
+                json!({ "error": "the path does not exist" }),
+            )
+        }
+    };
+    match outcome {
+        Ok((code, reason, session, status)) => (code, reason, job_body(session, &status)),
+        Err(err) => {
+            let (code, reason) = error_status(&err);
+            (code, reason, json!({ "error": err.to_string() }))
+        }
+    }
+}
+
+/// Starts the HTTP API of a daemon in a background thread, which accepts connections at the given
+/// address and answers a request per connection in its own thread. Returns the address at which
+/// the API listens. Anybody who reaches the address can submit jobs, so it should only be
+/// reachable by trusted applications.
+pub fn spawn_http_service(address: SocketAddr, queue: Arc<JobQueue>) -> anyhow::Result<SocketAddr> {
+    spawn_http_handler(address, move |method, target, body| {
+        route(&queue, method, target, body)
+    })
+}
+
+/// Starts an HTTP API in a background thread as [`spawn_http_service`] does, but answering the
+/// requests with the given handler, which takes the method, the target and the body of a request
+/// and returns the status code, the reason phrase and the JSON body of the response. This serves
+/// other APIs with the same service, e.g. the one of the randomness beacon.
+pub fn spawn_http_handler<H>(address: SocketAddr, handler: H) -> anyhow::Result<SocketAddr>
+where
+    H: Fn(&str, &str, &[u8]) -> (u16, &'static str, Value) + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+    let handler = Arc::new(handler);
+    log::info!("HTTP API listening at {address}");
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let handler = Arc::clone(&handler);
+                    thread::spawn(move || {
+                        if let Err(err) = handle_connection(stream, handler.as_ref()) {
+                            log::warn!("error in a connection of the HTTP API: {err:#}");
+                        }
+                    });
+                }
+                Err(err) => log::warn!("error accepting a connection of the HTTP API: {err}"),
+            }
+        }
+    });
+    Ok(address)
+}
+
+/// Reads a request from the connection, answers it and closes the connection.
+fn handle_connection<H>(stream: TcpStream, handler: &H) -> anyhow::Result<()>
+where
+    H: Fn(&str, &str, &[u8]) -> (u16, &'static str, Value),
+{
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let (code, reason, body) = match read_request(&mut reader) {
+        Ok((method, target, body)) => handler(&method, &target, &body),
+        Err(err) => (400, "Bad Request", json!({ "error": format!("{err:#}") })),
+    };
+    let body = body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {code} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads the method, the target and the body of an HTTP/1.1 request. The size of the body is
+/// given by the `Content-Length` header.
+fn read_request(reader: &mut impl BufRead) -> anyhow::Result<(String, String, Vec<u8>)> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        anyhow::bail!("the request line is not correct");
+    };
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            anyhow::bail!("the connection was closed before the end of the headers");
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        anyhow::bail!("the body is too large");
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok((method.to_string(), target.to_string(), body))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        sync::Arc,
+    };
+
+    use serde_json::json;
+
+    use super::{route, spawn_http_service};
+    use crate::mpc::control::{JobQueue, JobStatus};
+
+    #[test]
+    fn route_requests() {
+        let queue = JobQueue::new();
+        let job = br#"{"session": 1, "inputs": [2], "expression": "x0 + x1"}"#;
+        assert_eq!(
+            route(&queue, "POST", "/jobs", job),
+            (201, "Created", json!({ "session": 1, "status": "queued" }))
+        );
+        assert_eq!(route(&queue, "POST", "/jobs", job).0, 409);
+        assert_eq!(route(&queue, "POST", "/jobs", b"{}").0, 400);
+        assert_eq!(
+            route(&queue, "GET", "/jobs/1", b""),
+            (200, "OK", json!({ "session": 1, "status": "queued" }))
+        );
+        assert_eq!(route(&queue, "GET", "/jobs/2", b"").0, 404);
+        assert_eq!(route(&queue, "GET", "/jobs/x", b"").0, 400);
+        assert_eq!(route(&queue, "DELETE", "/jobs/1", b"").0, 405);
+        assert_eq!(route(&queue, "GET", "/results", b"").0, 404);
+
+        queue.next_job().unwrap();
+        queue.finish(
+            1,
+            JobStatus::Done {
+                result: "5".to_string(),
+            },
+        );
+        assert_eq!(
+            route(&queue, "GET", "/jobs/1?wait=true", b""),
+            (
+                200,
+                "OK",
+                json!({ "session": 1, "status": "done", "result": "5" })
+            )
+        );
+        assert_eq!(queue.close(), 0);
+        assert_eq!(route(&queue, "POST", "/jobs", br#"{"session": 2}"#).0, 503);
+    }
+
+    #[test]
+    fn answer_http_requests() {
+        let queue = Arc::new(JobQueue::new());
+        let address = spawn_http_service("127.0.0.1:0".parse().unwrap(), queue).unwrap();
+        let body = r#"{"session": 3, "inputs": ["1.5"], "fractional_bits": 1}"#;
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST /jobs HTTP/1.1\r\nHost: localhost\r\ncontent-length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 201 Created\r\n"));
+        assert!(response.ends_with("\r\n\r\n{\"session\":3,\"status\":\"queued\"}"));
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"garbage\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+}
@@ -0,0 +1,253 @@
+use std::{fs, path::Path};
+
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::math::{
+    fixed_point::{Decimal, FixedPointError},
+    PrimeField,
+};
+
+/// Possible errors that may appear while reading the inputs of a party from a file.
+#[derive(Debug, Error)]
+pub enum InputError {
+    /// The extension of the file is not one of the supported formats.
+    #[error("the input file {0} is not a CSV or JSON file")]
+    UnsupportedFormat(String),
+
+    /// A value of the file is not a number that can be encoded in the field.
+    #[error("the value at position {position} of the input file is not correct: {source}")]
+    InvalidValue {
+        position: usize,
+        #[source]
+        source: FixedPointError,
+    },
+
+    /// The requested column is not present in the file.
+    #[error("the column {0} is not present in the input file")]
+    UnknownColumn(String),
+
+    /// A row of the CSV file does not have a value in the requested column.
+    #[error("the row {0} of the input file does not have a value in the column")]
+    MissingValue(usize),
+
+    /// The JSON file does not have the expected structure.
+    #[error("the JSON input file must contain an array of numbers or an object of arrays")]
+    InvalidJson,
+}
+
+/// Encodes the inputs of a party given as decimal numbers, e.g. in the command line, followed by
+/// the ones of the input file, if any, read as in [`read_input_file`].
+pub fn encode_inputs<T: PrimeField>(
+    inputs: &[Decimal],
+    fractional_bits: u32,
+    file: Option<&Path>,
+    column: Option<&str>,
+) -> anyhow::Result<Vec<T>> {
+    let mut encoded = inputs
+        .iter()
+        .map(|input| input.encode(fractional_bits))
+        .collect::<Result<Vec<T>, _>>()?;
+    if let Some(path) = file {
+        encoded.extend(read_input_file::<T>(path, column, fractional_bits)?);
+    }
+    Ok(encoded)
+}
+
+/// Reads the private inputs of a party from a CSV or JSON file, chosen by the extension of the
+/// path, and encodes them in the field in fixed point with the given number of fractional bits,
+/// as explained in [`Decimal::encode`].
+///
+/// A CSV file contains numbers separated by commas and new lines, optionally preceded by a row of
+/// column names. A JSON file contains an array of numbers, or an object whose entries are arrays
+/// of numbers, where the numbers may also be written as strings to keep their precision. If a
+/// column is given, by name or by position, only the values of that column are read; otherwise,
+/// every value is read row by row.
+pub fn read_input_file<T: PrimeField>(
+    path: &Path,
+    column: Option<&str>,
+    fractional_bits: u32,
+) -> anyhow::Result<Vec<T>> {
+    let content = fs::read_to_string(path)?;
+    let values = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) if extension.eq_ignore_ascii_case("csv") => csv_values(&content, column)?,
+        Some(extension) if extension.eq_ignore_ascii_case("json") => {
+            json_values(&serde_json::from_str(&content)?, column)?
+        }
+        _ => anyhow::bail!(InputError::UnsupportedFormat(path.display().to_string())),
+    };
+    values
+        .iter()
+        .enumerate()
+        .map(|(position, value)| {
+            value
+                .parse::<Decimal>()
+                .and_then(|decimal| decimal.encode(fractional_bits))
+                .map_err(|source| InputError::InvalidValue { position, source }.into())
+        })
+        .collect()
+}
+
+/// Extracts the values of a CSV file, skipping its header and keeping only the given column if
+/// there is one.
+fn csv_values(content: &str, column: Option<&str>) -> Result<Vec<String>, InputError> {
+    let mut rows: Vec<Vec<&str>> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split(',').map(str::trim).collect())
+        .collect();
+
+    // The first row is a header if any of its cells is not a number.
+    let header = match rows.first() {
+        Some(first) if first.iter().any(|cell| cell.parse::<Decimal>().is_err()) => {
+            Some(rows.remove(0))
+        }
+        _ => None,
+    };
+    let Some(column) = column else {
+        return Ok(rows.concat().into_iter().map(String::from).collect());
+    };
+    let index = header
+        .as_ref()
+        .and_then(|header| header.iter().position(|name| *name == column))
+        .or_else(|| column.parse().ok())
+        .ok_or_else(|| InputError::UnknownColumn(column.to_string()))?;
+    rows.iter()
+        .enumerate()
+        .map(|(row, cells)| {
+            cells
+                .get(index)
+                .map(|cell| cell.to_string())
+                .ok_or(InputError::MissingValue(row))
+        })
+        .collect()
+}
+
+/// Extracts the values of a JSON file, keeping only the given entry if the file is an object.
+fn json_values(json: &Value, column: Option<&str>) -> Result<Vec<String>, InputError> {
+    let array = match (json, column) {
+        (Value::Array(array), None) => array,
+        (Value::Object(object), Some(column)) => object
+            .get(column)
+            .and_then(Value::as_array)
+            .ok_or_else(|| InputError::UnknownColumn(column.to_string()))?,
+        (Value::Object(object), None) => {
+            let mut values = Vec::new();
+            for entry in object.values() {
+                values.extend(json_values(entry, None)?);
+            }
+            return Ok(values);
+        }
+        (Value::Array(_), Some(column)) => {
+            return Err(InputError::UnknownColumn(column.to_string()))
+        }
+        _ => return Err(InputError::InvalidJson),
+    };
+    array
+        .iter()
+        .map(|value| match value {
+            Value::Number(number) => Ok(number.to_string()),
+            Value::String(number) => Ok(number.clone()),
+            _ => Err(InputError::InvalidJson),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{encode_inputs, read_input_file, InputError};
+    use crate::math::{fixed_point::FixedPointError, mersenne61::Mersenne61, FiniteField};
+
+    /// Writes a temporary input file with the given name and content.
+    fn input_file(name: &str, content: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("shami_rs_inputs_{}_{name}", std::process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn values(numbers: &[u64]) -> Vec<Mersenne61> {
+        numbers.iter().copied().map(Mersenne61::from).collect()
+    }
+
+    #[test]
+    fn inputs_are_followed_by_the_ones_of_the_file() {
+        let inputs = ["1.5".parse().unwrap(), "-2".parse().unwrap()];
+        let encoded: Vec<Mersenne61> = encode_inputs(&inputs, 1, None, None).unwrap();
+        assert_eq!(
+            encoded,
+            vec![Mersenne61::from(3), Mersenne61::from(4).negate()]
+        );
+
+        let path = input_file("more.csv", "5\n6.5\n");
+        let encoded: Vec<Mersenne61> = encode_inputs(&inputs[..1], 1, Some(&path), None).unwrap();
+        assert_eq!(encoded, values(&[3, 10, 13]));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn read_csv_files() {
+        let path = input_file("table.csv", "age, salary\n30, 1.5\n\n41, 2.25\n");
+        let ages: Vec<Mersenne61> = read_input_file(&path, Some("age"), 0).unwrap();
+        assert_eq!(ages, values(&[30, 41]));
+        let salaries: Vec<Mersenne61> = read_input_file(&path, Some("1"), 2).unwrap();
+        assert_eq!(salaries, values(&[6, 9]));
+        let err = read_input_file::<Mersenne61>(&path, None, 0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InputError>(),
+            Some(InputError::InvalidValue {
+                position: 1,
+                source: FixedPointError::FractionalValue(_)
+            })
+        ));
+        let err = read_input_file::<Mersenne61>(&path, Some("name"), 0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InputError>(),
+            Some(InputError::UnknownColumn(_))
+        ));
+        fs::remove_file(path).unwrap();
+
+        let path = input_file("vector.csv", "1,2\n3\n-4\n");
+        let all: Vec<Mersenne61> = read_input_file(&path, None, 0).unwrap();
+        let mut expected = values(&[1, 2, 3]);
+        expected.push(Mersenne61::from(4).negate());
+        assert_eq!(all, expected);
+        let err = read_input_file::<Mersenne61>(&path, Some("1"), 0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InputError>(),
+            Some(InputError::MissingValue(1))
+        ));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn read_json_files() {
+        let path = input_file("vector.json", r#"[1, 2.5, "0.125"]"#);
+        let all: Vec<Mersenne61> = read_input_file(&path, None, 3).unwrap();
+        assert_eq!(all, values(&[8, 20, 1]));
+        fs::remove_file(path).unwrap();
+
+        let path = input_file("columns.json", r#"{"a": [1, 2], "b": [3]}"#);
+        let column: Vec<Mersenne61> = read_input_file(&path, Some("b"), 0).unwrap();
+        assert_eq!(column, values(&[3]));
+        fs::remove_file(path).unwrap();
+
+        let path = input_file("invalid.json", r#"{"a": [[1]]}"#);
+        let err = read_input_file::<Mersenne61>(&path, None, 0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InputError>(),
+            Some(InputError::InvalidJson)
+        ));
+        fs::remove_file(path).unwrap();
+
+        let path = input_file("inputs.txt", "1");
+        let err = read_input_file::<Mersenne61>(&path, None, 0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InputError>(),
+            Some(InputError::UnsupportedFormat(_))
+        ));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,407 @@
+use aws_lc_rs::digest::{self, SHA256};
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use super::{compute_shamir_share, reconstruct_secret_from_parties, share::ShamirShare};
+use crate::{
+    codec::WireCodec,
+    math::{FieldValue, FiniteField},
+    net::{
+        broadcast::reliable_broadcast,
+        deadline::{missing_parties, DeadlineError},
+        value::{decode_value, encode_value},
+        Network, Packet,
+    },
+};
+
+/// Protocol ID of the packets of the openings of [`run_committed_opening`].
+pub const COMMITTED_OPEN_PROTOCOL_ID: u16 = 3;
+
+/// SHA-256 digest of a node of a Merkle tree.
+pub type Hash = [u8; 32];
+
+/// Prefix of the hashes of the leaves, which keeps them apart from the inner nodes.
+const LEAF_PREFIX: u8 = 0;
+/// Prefix of the hashes of the inner nodes.
+const NODE_PREFIX: u8 = 1;
+
+/// Possible errors that may appear while dealing or opening committed shares.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MerkleError {
+    /// The share that the dealer sent to the party does not match the root that it broadcast.
+    #[error("the share sent by the dealer {0} does not match the broadcast root")]
+    InvalidDealing(usize),
+
+    /// Too few parties contributed a share that matches the root.
+    #[error("only {valid} shares match the root, but {needed} are needed, and the parties {substituted:?} substituted theirs")]
+    NotEnoughValidShares {
+        valid: usize,
+        needed: usize,
+        substituted: Vec<usize>,
+    },
+}
+
+/// Merkle tree over the hashes of a list of leaves. An odd node at the end of a level is
+/// promoted to the next level unchanged, instead of being paired with a copy of itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// Hashes of each level of the tree, from the leaves to the root.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree over the given hashes of the leaves.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no leaves.
+    pub fn new(leaves: Vec<Hash>) -> Self {
+        assert!(!leaves.is_empty(), "a Merkle tree needs at least one leaf");
+        let mut levels = vec![leaves];
+        while levels[levels.len() - 1].len() > 1 {
+            let level = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!("the chunks have one or two nodes"),
+                })
+                .collect();
+            levels.push(level);
+        }
+        Self { levels }
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> Hash {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    /// Returns the proof that the leaf at the given index belongs to the tree.
+    pub fn proof(&self, index: usize) -> MerkleProof {
+        let mut siblings = Vec::with_capacity(self.levels.len());
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            siblings.push(level.get(position ^ 1).copied());
+            position /= 2;
+        }
+        MerkleProof { index, siblings }
+    }
+}
+
+/// Proof that a leaf is at a given index of a Merkle tree, which holds the sibling of the node
+/// on the path to the root at every level, or `None` if the node is promoted. Since a promoted
+/// node is always the last one of its level, the proof binds the leaf to a single index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Index of the leaf.
+    pub index: usize,
+    /// Siblings of the nodes on the path from the leaf to the root.
+    pub siblings: Vec<Option<Hash>>,
+}
+
+impl MerkleProof {
+    /// Tells whether the leaf with the given hash is at the index of the proof in the tree with
+    /// the given root.
+    pub fn verify(&self, root: &Hash, leaf: &Hash) -> bool {
+        let mut hash = *leaf;
+        let mut position = self.index;
+        for sibling in &self.siblings {
+            hash = match sibling {
+                Some(sibling) if position.is_multiple_of(2) => node_hash(&hash, sibling),
+                Some(sibling) => node_hash(sibling, &hash),
+                // A promoted node has no right sibling, so it is always on the left.
+                None if position.is_multiple_of(2) => hash,
+                None => return false,
+            };
+            position /= 2;
+        }
+        position == 0 && hash == *root
+    }
+}
+
+/// Returns the hash of an inner node from the hashes of its children.
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut context = digest::Context::new(&SHA256);
+    context.update(&[NODE_PREFIX]);
+    context.update(left);
+    context.update(right);
+    // The unwrap is safe because SHA-256 digests have 32 bytes.
+    context.finish().as_ref().try_into().unwrap()
+}
+
+/// Share of a party committed to by the root of the Merkle tree over the shares of every party.
+/// Each leaf hashes a share together with a random salt that only its holder learns, so the
+/// parties can not test guesses of the shares of the others, and hence of the secret, against
+/// the hashes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommittedShare<T> {
+    /// ID of the party that holds the share.
+    pub party_id: usize,
+    /// Share of the secret.
+    pub share: ShamirShare<T>,
+    /// Salt of the leaf of the share.
+    pub salt: Hash,
+    /// Proof that the leaf of the share is in the tree.
+    pub proof: MerkleProof,
+}
+
+impl<T: FiniteField> CommittedShare<T> {
+    /// Returns the hash of the leaf of the share.
+    fn leaf(&self) -> Hash {
+        let mut context = digest::Context::new(&SHA256);
+        context.update(&[LEAF_PREFIX]);
+        context.update(&self.salt);
+        context.update(&(self.party_id as u64).to_le_bytes());
+        context.update(&(self.share.degree as u64).to_le_bytes());
+        // The values are hashed in at least 16 bytes, the width of the fields of up to 128 bits.
+        let mut value = Zeroizing::new(vec![0; T::ValueType::BYTES.max(16)]);
+        self.share.value.value().write_le_bytes(&mut value);
+        context.update(&value);
+        // The unwrap is safe because SHA-256 digests have 32 bytes.
+        context.finish().as_ref().try_into().unwrap()
+    }
+
+    /// Tells whether the share is the one of its party committed to by the root.
+    pub fn verify(&self, root: &Hash) -> bool {
+        self.proof.index == self.party_id && self.proof.verify(root, &self.leaf())
+    }
+}
+
+/// Shares a secret among `n_parties` parties and commits to the shares with a Merkle tree,
+/// returning its root and the committed share of each party, indexed by party ID.
+pub fn commit_shares<T, R>(
+    secret: &T,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+) -> (Hash, Vec<CommittedShare<T>>)
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    let mut shares: Vec<CommittedShare<T>> =
+        compute_shamir_share(secret, n_parties, threshold, rng)
+            .into_iter()
+            .enumerate()
+            .map(|(party_id, share)| CommittedShare {
+                party_id,
+                share,
+                salt: rng.gen(),
+                proof: MerkleProof {
+                    index: party_id,
+                    siblings: Vec::new(),
+                },
+            })
+            .collect();
+    let tree = MerkleTree::new(shares.iter().map(CommittedShare::leaf).collect());
+    for share in &mut shares {
+        share.proof = tree.proof(share.party_id);
+    }
+    (tree.root(), shares)
+}
+
+/// Runs the dealing in which the party `dealer` shares a secret and commits to the shares. The
+/// dealer must provide `Some(secret)`, and the rest of the parties must provide `None`. The
+/// dealer sends each party its committed share and broadcasts the root with the reliable
+/// broadcast of the given instance, so every honest party obtains the same root. Each party
+/// checks its share against the root, and returns it together with the root.
+pub fn run_committed_dealing<T, R>(
+    secret: Option<&T>,
+    dealer: usize,
+    threshold: usize,
+    instance: u32,
+    rng: &mut R,
+    network: &mut Network,
+) -> anyhow::Result<(Hash, CommittedShare<T>)>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    let n_parties = network.n_parties();
+    let root = match secret {
+        Some(secret) => {
+            let (root, shares) = commit_shares(secret, n_parties, threshold, rng);
+            for share in &shares {
+                network.send_value(share, share.party_id)?;
+            }
+            Some(network.codec().encode(&root)?)
+        }
+        None => None,
+    };
+    let share: CommittedShare<T> = network.recv_value(dealer)?;
+    // The broadcast of the root tolerates as many corruptions as the number of parties allows,
+    // independently of the threshold of the sharing.
+    let root = reliable_broadcast(network, dealer, instance, root, (n_parties - 1) / 3)?;
+    let root: Hash = network.codec().decode(&root)?;
+    if share.party_id != network.id() || !share.verify(&root) {
+        anyhow::bail!(MerkleError::InvalidDealing(dealer));
+    }
+    Ok((root, share))
+}
+
+/// Opens a secret dealt with committed shares in the given round of the openings. Every party
+/// sends its committed share to every party, and the shares that do not match the root are
+/// discarded, so a party that substitutes its share is caught and named instead of corrupting
+/// the secret. The secret is reconstructed from the shares that match, which must be at least
+/// `degree + 1`. Parties that miss the round deadline of the network abort the opening as in
+/// [`run_open_protocol`](super::run_open_protocol), unless the deadline is robust or falls back
+/// to fewer shares, since then the shares that match are enough, and the absent parties are
+/// recorded.
+pub fn run_committed_opening<T>(
+    share: &CommittedShare<T>,
+    root: &Hash,
+    round: u32,
+    network: &mut Network,
+) -> anyhow::Result<T>
+where
+    T: FiniteField,
+{
+    let deadline = network.round_deadline();
+    let codec = network.codec();
+    let packet = Packet::tagged(
+        COMMITTED_OPEN_PROTOCOL_ID,
+        round,
+        encode_value(codec, share)?.into_payload(),
+    );
+    let gathered = network.broadcast_and_gather_within(&packet, deadline.duration())?;
+    let missing = missing_parties(&gathered);
+    if !missing.is_empty() {
+        if !deadline.tolerates_missing() {
+            anyhow::bail!(DeadlineError::MissingParties {
+                round,
+                deadline: deadline.duration(),
+                missing,
+            });
+        }
+        network.record_absence(round, missing);
+    }
+
+    let mut valid = Vec::new();
+    let mut substituted = Vec::new();
+    for (party_id, packet) in gathered.into_iter().enumerate() {
+        let Some(packet) = packet else { continue };
+        match decode_value::<CommittedShare<T>>(codec, &packet) {
+            Ok(share) if share.party_id == party_id && share.verify(root) => {
+                valid.push((party_id, share.share));
+            }
+            _ => substituted.push(party_id),
+        }
+    }
+    if !substituted.is_empty() {
+        log::warn!("the parties {substituted:?} sent shares that do not match the root");
+    }
+    let needed = share.share.degree + 1;
+    if valid.len() < needed {
+        anyhow::bail!(MerkleError::NotEnoughValidShares {
+            valid: valid.len(),
+            needed,
+            substituted,
+        });
+    }
+    Ok(reconstruct_secret_from_parties(valid)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{
+        commit_shares, run_committed_dealing, run_committed_opening, Hash, MerkleError, MerkleTree,
+    };
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        net::Network,
+    };
+
+    #[test]
+    fn proofs_verify_for_every_size() {
+        for n_leaves in 1..=9 {
+            let leaves: Vec<Hash> = (0..n_leaves).map(|i| [i as u8; 32]).collect();
+            let tree = MerkleTree::new(leaves.clone());
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(index);
+                assert!(proof.verify(&tree.root(), leaf), "{n_leaves} leaves");
+                assert!(!proof.verify(&tree.root(), &[0xff; 32]));
+                let moved = super::MerkleProof {
+                    index: index ^ 1,
+                    ..proof
+                };
+                assert!(n_leaves == 1 || !moved.verify(&tree.root(), leaf));
+            }
+        }
+    }
+
+    #[test]
+    fn committed_shares_catch_substitutions() {
+        let mut rng = thread_rng();
+        let (root, shares) = commit_shares(&Mersenne61::from(42), 4, 1, &mut rng);
+        assert!(shares.iter().all(|share| share.verify(&root)));
+
+        let mut substituted = shares[1].clone();
+        substituted.share = substituted.share.add_const(&Mersenne61::ONE);
+        assert!(!substituted.verify(&root));
+        // A valid share can not be replayed as the share of another party.
+        let mut replayed = shares[2].clone();
+        replayed.party_id = 1;
+        assert!(!replayed.verify(&root));
+    }
+
+    /// Deals a secret with committed shares among the parties of a local network and opens it,
+    /// with the parties in `cheaters` substituting their share in the opening.
+    fn deal_and_open(
+        n_parties: usize,
+        threshold: usize,
+        cheaters: &'static [usize],
+    ) -> Vec<anyhow::Result<Mersenne61>> {
+        let handles: Vec<_> = Network::create_local(n_parties)
+            .into_iter()
+            .map(|mut network| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let secret = (network.id() == 0).then(|| Mersenne61::from(42));
+                    let (root, mut share) = run_committed_dealing(
+                        secret.as_ref(),
+                        0,
+                        threshold,
+                        0,
+                        &mut rng,
+                        &mut network,
+                    )?;
+                    if cheaters.contains(&network.id()) {
+                        share.share = share.share.add_const(&Mersenne61::ONE);
+                    }
+                    run_committed_opening(&share, &root, 0, &mut network)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn openings_discard_substituted_shares() {
+        for result in deal_and_open(4, 1, &[]) {
+            assert_eq!(result.unwrap(), Mersenne61::from(42));
+        }
+        for result in deal_and_open(4, 1, &[2]) {
+            assert_eq!(result.unwrap(), Mersenne61::from(42));
+        }
+        for result in deal_and_open(4, 2, &[1, 3]) {
+            assert_eq!(
+                result.unwrap_err().downcast::<MerkleError>().unwrap(),
+                MerkleError::NotEnoughValidShares {
+                    valid: 2,
+                    needed: 3,
+                    substituted: vec![1, 3],
+                }
+            );
+        }
+    }
+}
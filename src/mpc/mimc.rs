@@ -0,0 +1,224 @@
+use std::marker::PhantomData;
+
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use super::{
+    circuit::{Circuit, Gate, Wire},
+    engine::Engine,
+    share::ShamirShare,
+};
+use crate::{
+    math::{permutation_exponent, FiniteField},
+    transport::Transport,
+};
+
+/// Seed of the generator of the round constants, so that every party derives the same constants
+/// without agreeing on them.
+const CONSTANTS_SEED: [u8; 32] = *b"shami-rs MiMC round constants\0\0\0";
+
+/// The MiMC block cipher over a field, whose rounds compute `x -> (x + k + c_i)^d` for a key `k`,
+/// round constants `c_i` and an exponent `d` coprime with `p - 1`, so that every round is a
+/// permutation of the field, and which adds the key again after the last round. With only one
+/// multiplicative operation per round, it is one of the cheapest ciphers to evaluate on shares,
+/// and with a shared key it is a distributed PRF, `F_k(m) = E_k(m)`, that no party can evaluate
+/// on its own.
+///
+/// The round constants are derived from a fixed seed and are below `2^64`, since the public
+/// constants of the circuits are 64-bit integers. The first round constant is zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mimc<T> {
+    /// Exponent of the rounds.
+    exponent: u128,
+    /// Constant added in each round.
+    round_constants: Vec<u64>,
+    /// Field of the cipher.
+    field: PhantomData<fn() -> T>,
+}
+
+impl<T: FiniteField> Default for Mimc<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: FiniteField> Mimc<T> {
+    /// Returns the cipher over the field `T`, whose exponent is the smallest odd exponent above
+    /// one that is coprime with `p - 1`, and whose number of rounds is `ceil(log_d(p))`, so that
+    /// the degree of the cipher as a polynomial in the message reaches the size of the field.
+    pub fn new() -> Self {
+        let exponent = permutation_exponent::<T>();
+        let n_rounds = (T::BIT_SIZE as f64 / (exponent as f64).log2()).ceil() as usize;
+        let mut rng = ChaCha20Rng::from_seed(CONSTANTS_SEED);
+        let round_constants = (0..n_rounds)
+            .map(|round| if round == 0 { 0 } else { rng.next_u64() })
+            .collect();
+        Self {
+            exponent,
+            round_constants,
+            field: PhantomData,
+        }
+    }
+
+    /// Returns the exponent of the rounds.
+    pub fn exponent(&self) -> u128 {
+        self.exponent
+    }
+
+    /// Returns the number of rounds of the cipher.
+    pub fn n_rounds(&self) -> usize {
+        self.round_constants.len()
+    }
+
+    /// Encrypts a message under a key in the clear.
+    pub fn encrypt(&self, key: &T, message: &T) -> T {
+        let mut state = message.clone();
+        for constant in &self.round_constants {
+            state = state.add(key).add(&T::from(*constant)).pow(self.exponent);
+        }
+        state.add(key)
+    }
+
+    /// Appends to a circuit the gates that encrypt the message of a wire under the key of
+    /// another wire, returning the wire of the ciphertext. The power of each round takes a
+    /// squaring for every bit of the exponent but the first and a product for every bit set
+    /// beyond the first, e.g. four squarings and one product for `x^17`.
+    pub fn push_encryption(&self, circuit: &mut Circuit, key: Wire, message: Wire) -> Wire {
+        let mut state = message;
+        for constant in &self.round_constants {
+            state = circuit.push(Gate::Add(state, key));
+            if *constant != 0 {
+                let constant = circuit.push(Gate::Constant(*constant));
+                state = circuit.push(Gate::Add(state, constant));
+            }
+            state = circuit.push_power(state, self.exponent);
+        }
+        circuit.push(Gate::Add(state, key))
+    }
+
+    /// Returns the circuit that encrypts the message computed by a gate under the key computed
+    /// by another gate, e.g. inputs of the parties or public constants.
+    pub fn circuit(&self, key: Gate, message: Gate) -> Circuit {
+        let mut circuit = Circuit::default();
+        let key = circuit.push(key);
+        let message = circuit.push(message);
+        self.push_encryption(&mut circuit, key, message);
+        circuit
+    }
+
+    /// Encrypts a shared message under a shared key, returning the share of the ciphertext. The
+    /// key and the message stay shared during the whole evaluation, so the parties can evaluate
+    /// the PRF on a key that none of them knows and open only the outputs they agree on. Every
+    /// party must call it with its shares of the same key and message.
+    pub fn encrypt_shared<R, N>(
+        &self,
+        key: &ShamirShare<T>,
+        message: &ShamirShare<T>,
+        threshold: usize,
+        rng: &mut R,
+        engine: &mut Engine<N>,
+    ) -> anyhow::Result<ShamirShare<T>>
+    where
+        R: Rng + CryptoRng,
+        N: Transport,
+    {
+        let circuit = self.circuit(
+            Gate::Input { party: 0, index: 0 },
+            Gate::Input { party: 0, index: 1 },
+        );
+        // The circuit takes the number of parties from the inputs, so the other parties are
+        // given no inputs.
+        let mut inputs = vec![Vec::new(); engine.n_parties()];
+        inputs[0] = vec![key.clone(), message.clone()];
+        circuit.evaluate(&inputs, threshold, rng, engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::{rngs::StdRng, thread_rng, SeedableRng};
+
+    use super::Mimc;
+    use crate::{
+        math::{mersenne127::Mersenne127, mersenne61::Mersenne61, p128::P128, FiniteField},
+        mpc::{circuit::Gate, compute_shamir_share, engine::Engine, reconstruct_secret},
+        net::Network,
+    };
+
+    /// Checks that the circuit of the cipher computes the same ciphertexts as the cipher in the
+    /// clear, and that the ciphertexts depend on the key and the message.
+    fn circuit_matches_cipher<T: FiniteField + PartialEq>() {
+        let mimc = Mimc::<T>::new();
+        let mut rng = StdRng::seed_from_u64(1730);
+        let circuit = mimc.circuit(
+            Gate::Input { party: 0, index: 0 },
+            Gate::Input { party: 1, index: 0 },
+        );
+        for _ in 0..4 {
+            let (key, message) = (T::random(&mut rng), T::random(&mut rng));
+            let ciphertext = mimc.encrypt(&key, &message);
+            let inputs = [vec![key.clone()], vec![message.clone()]];
+            assert_eq!(circuit.evaluate_plain(&inputs).unwrap(), ciphertext);
+            assert_ne!(mimc.encrypt(&key, &message.add(&T::ONE)), ciphertext);
+            assert_ne!(mimc.encrypt(&key.add(&T::ONE), &message), ciphertext);
+        }
+    }
+
+    #[test]
+    fn cipher_parameters_and_circuits() {
+        assert_eq!(Mimc::<Mersenne61>::new().exponent(), 17);
+        assert_eq!(Mimc::<Mersenne61>::new().n_rounds(), 15);
+        assert_eq!(Mimc::<Mersenne127>::new().exponent(), 5);
+        assert_eq!(Mimc::<Mersenne127>::new().n_rounds(), 55);
+        // The rounds of x^17 take four squarings and one product, and a circuit of public
+        // values takes no interaction.
+        let key = Gate::Input { party: 0, index: 0 };
+        let circuit = Mimc::<Mersenne61>::new().circuit(key, Gate::Constant(2));
+        assert_eq!(circuit.n_rounds(), 15 * 5);
+        let circuit = Mimc::<Mersenne61>::new().circuit(Gate::Constant(1), Gate::Constant(2));
+        assert_eq!(circuit.n_rounds(), 0);
+
+        circuit_matches_cipher::<Mersenne61>();
+        circuit_matches_cipher::<Mersenne127>();
+        circuit_matches_cipher::<P128>();
+    }
+
+    #[test]
+    fn encrypt_shared_key_and_message() {
+        const N_PARTIES: usize = 3;
+        const THRESHOLD: usize = 1;
+
+        let mut rng = thread_rng();
+        let (key, message) = (Mersenne61::random(&mut rng), Mersenne61::random(&mut rng));
+        let key_shares = compute_shamir_share(&key, N_PARTIES, THRESHOLD, &mut rng);
+        let message_shares = compute_shamir_share(&message, N_PARTIES, THRESHOLD, &mut rng);
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .map(|mut network| {
+                let key = key_shares[network.id()].clone();
+                let message = message_shares[network.id()].clone();
+                thread::spawn(move || {
+                    Mimc::new()
+                        .encrypt_shared(
+                            &key,
+                            &message,
+                            THRESHOLD,
+                            &mut thread_rng(),
+                            &mut Engine::new(&mut network),
+                        )
+                        .unwrap()
+                })
+            })
+            .collect();
+        let shares = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        assert_eq!(
+            reconstruct_secret(shares),
+            Mimc::new().encrypt(&key, &message)
+        );
+    }
+}
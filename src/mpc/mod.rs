@@ -1,17 +1,243 @@
-use rand::Rng;
-use share::ShamirShare;
+use std::{borrow::Cow, ops::Range};
 
+use accelerator::Accelerator;
+use double_sharing::{DoubleSharing, DoubleSharings};
+use engine::Engine;
+use rand::{CryptoRng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use share::{BeaverTriple, ShamirShare, ShareBatch};
+use subtle::{Choice, CtOption};
+use thiserror::Error;
+use verification::{Security, VerificationError};
+use zeroize::Zeroizing;
+
+#[cfg(feature = "native")]
+use crate::net::{
+    deadline::{missing_parties, DeadlineError},
+    value::{decode_value, encode_value},
+    Network, Packet,
+};
 use crate::{
+    codec::WireCodec,
     math::{
-        lagrange::{compute_lagrange_basis, interpolate_polynomial_at},
-        FiniteField, Polynomial,
+        consecutive::{evaluate_consecutive, has_consecutive_points, CONSECUTIVE_THRESHOLD},
+        lagrange::{interpolate_polynomial_at, recombination_vector},
+        FieldValue, FiniteField, Polynomial,
     },
-    net::{Network, Packet},
+    trace,
+    transport::Transport,
 };
 
+/// Offloading of the large batches of field arithmetic to an accelerator such as a GPU.
+pub mod accelerator;
+/// Conversion between Shamir sharings and the additive sharings of other protocols.
+pub mod additive;
+/// Corrupted party that deviates from the protocols on command, to test that they catch it.
+#[cfg(test)]
+pub(crate) mod adversary;
+/// Distributed randomness beacon built on commit-reveal coin tossing.
+#[cfg(feature = "native")]
+pub mod beacon;
+/// Benchmark of the sharing, multiplication and opening protocols.
+#[cfg(feature = "native")]
+pub mod bench;
+/// Sharing of byte strings, such as keys, as shares of their chunks.
+pub mod bytes;
+/// Checkpoints from which a party resumes a protocol after a crash.
+pub mod checkpoint;
+/// Arithmetic circuits evaluated on the shares of the inputs with batched multiplications.
+pub mod circuit;
+/// Bit decomposition, comparisons and truncation of shared integers.
+pub mod compare;
+/// Control service through which external applications submit jobs to a daemon.
+#[cfg(feature = "native")]
+pub mod control;
+/// Pairwise seeds from which the parties draw correlated randomness without communication.
+pub mod correlated;
+/// Jobs served by a long-running party, each one in its own session of the network.
+#[cfg(feature = "native")]
+pub mod daemon;
+/// Trusted dealer that deals the shares of inputs and preprocessing material to the parties.
+#[cfg(feature = "native")]
+pub mod dealer;
+/// Random double sharings that reduce the degree of the products by opening them to a single
+/// party.
+pub mod double_sharing;
+/// Context in which a party runs the protocols, with the transport and the preprocessing material.
+pub mod engine;
+/// Arithmetic expressions over the inputs of the parties evaluated on their shares.
+pub mod expression;
+/// HTTP API through which web backends and scripts submit jobs to a daemon.
+#[cfg(feature = "native")]
+pub mod http;
+/// Private inputs of a party read from CSV or JSON files.
+pub mod input;
+/// Merkle commitments to the shares of a dealer that catch substituted shares in openings.
+#[cfg(feature = "native")]
+pub mod merkle;
+/// The MiMC block cipher, in the clear and as a circuit evaluated on shares.
+pub mod mimc;
+/// Shares and Beaver triples in the file formats of MP-SPDZ.
+pub mod mpspdz;
+/// Splitting of the local computations among threads.
+pub mod parallel;
+/// The Poseidon hash, in the clear and as a circuit evaluated on shares.
+pub mod poseidon;
+/// Generation of Beaver triples with the other parties in an offline phase, ahead of the
+/// inputs.
+pub mod preprocessing;
+/// Interactive session to issue commands against the other parties.
+#[cfg(feature = "native")]
+pub mod repl;
+/// Deterministic randomness to reproduce executions in tests.
+pub mod seed;
+/// Sessions of a party with the other parties: the connection, the preprocessing and the
+/// evaluation of a computation with the recovery from failures.
+#[cfg(feature = "native")]
+pub mod session;
+/// Plaintext shadow of the evaluation of a circuit that pinpoints the gate where it diverges.
+pub mod shadow;
+/// Shares of a secret in the Shamir secret-sharing scheme.
 pub mod share;
+/// Share files with the metadata that tells which shares can be combined.
+pub mod share_file;
+/// Simulation of every party of a session in the same process.
+#[cfg(feature = "native")]
+pub mod simulation;
+/// Named shared values of a computation, scoped by session.
+pub mod store;
+/// Multiplications with the Beaver triples of a preprocessing phase, as in the mode of two
+/// parties.
+pub mod two_party;
+
+/// Verification of the products against parties that deviate from the protocols.
+pub mod verification;
+
+/// Possible errors that may appear while reconstructing a secret from a subset of its shares.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ReconstructionError {
+    /// There are not enough shares to determine the polynomial of the given degree.
+    #[error("reconstructing a secret of degree {degree} needs {} shares, but there are {received}", degree + 1)]
+    NotEnoughShares { degree: usize, received: usize },
+
+    /// There is more than one share of the same party.
+    #[error("there is more than one share of party {0}")]
+    DuplicatedParty(usize),
+
+    /// The shares have different degrees, so they do not belong to the same secret.
+    #[error("the shares have different degrees")]
+    DegreeMismatch,
+
+    /// The shares do not lie on the same polynomial of the given degree, so some party sent a
+    /// wrong share.
+    #[error("the shares are not consistent with a polynomial of degree {0}")]
+    InconsistentShares(usize),
+}
+
+/// Possible errors in the number of corruptions tolerated by a protocol.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ThresholdError {
+    /// The corrupted parties could reconstruct the secrets by themselves.
+    #[error("{threshold} corruptions are not lower than the {n_parties} parties")]
+    TooManyCorruptions { threshold: usize, n_parties: usize },
+
+    /// The products of the shares can not be reconstructed by the parties.
+    #[error("multiplying with {threshold} corruptions needs more than {} parties, but there are {n_parties}", 2 * threshold)]
+    NotEnoughPartiesToMultiply { threshold: usize, n_parties: usize },
+
+    /// The field has too few nonzero elements to evaluate the polynomials at a distinct point for
+    /// every party.
+    #[error("{n_parties} parties need distinct nonzero evaluation points, but the field has modulus {modulus}")]
+    FieldTooSmall { n_parties: usize, modulus: String },
+}
+
+/// Possible errors in the degrees of the shares used in a protocol.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DegreeError {
+    /// A share has a degree above the threshold, e.g. a local product of two shares, and the
+    /// protocol can not reduce it by itself.
+    #[error("a share of degree {degree} must be reduced to the threshold {threshold} first")]
+    AboveThreshold { degree: usize, threshold: usize },
+
+    /// A share has a degree that the parties can not reduce, since they hold too few shares to
+    /// interpolate its polynomial.
+    #[error("a share of degree {degree} can not be reduced by {n_parties} parties")]
+    Irreducible { degree: usize, n_parties: usize },
+}
+
+/// Checks that the secrets can be shared among `n_parties` with polynomials of degree
+/// `threshold`, which tolerate that number of corruptions. If the shares are multiplied, the
+/// products have twice the degree, so the number of parties must be greater than twice the
+/// threshold, except in the two-party mode of [`two_party::is_two_party`], which multiplies with
+/// Beaver triples instead.
+pub fn check_threshold(
+    n_parties: usize,
+    threshold: usize,
+    multiplication: bool,
+) -> Result<(), ThresholdError> {
+    if threshold >= n_parties {
+        return Err(ThresholdError::TooManyCorruptions {
+            threshold,
+            n_parties,
+        });
+    }
+    if multiplication
+        && 2 * threshold >= n_parties
+        && !two_party::is_two_party(n_parties, threshold)
+    {
+        return Err(ThresholdError::NotEnoughPartiesToMultiply {
+            threshold,
+            n_parties,
+        });
+    }
+    Ok(())
+}
+
+/// Checks that the parties can be given distinct nonzero evaluation points in the field of `T`.
+/// The share of the party `i` is the evaluation of the polynomial at `i + 1`, so the field must
+/// have more than `n_parties` elements, or the points of two parties would coincide, or one of
+/// them would be zero and give away the secret.
+pub fn check_field<T: FiniteField>(n_parties: usize) -> Result<(), ThresholdError> {
+    let too_small = T::MODULUS
+        .to_u128()
+        .is_some_and(|modulus| n_parties as u128 >= modulus);
+    if too_small {
+        return Err(ThresholdError::FieldTooSmall {
+            n_parties,
+            modulus: T::MODULUS.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks both the evaluation points of [`check_field`] and the threshold of
+/// [`check_threshold`].
+pub fn check_parameters<T: FiniteField>(
+    n_parties: usize,
+    threshold: usize,
+    multiplication: bool,
+) -> Result<(), ThresholdError> {
+    check_field::<T>(n_parties)?;
+    check_threshold(n_parties, threshold, multiplication)
+}
+
+/// Checks that none of the shares has a degree above the threshold, i.e. that they can be
+/// multiplied or opened without leaking more than the shared values.
+pub fn check_degree<'a, T: 'a>(
+    shares: impl IntoIterator<Item = &'a ShamirShare<T>>,
+    threshold: usize,
+) -> Result<(), DegreeError> {
+    match shares.into_iter().find(|share| share.degree > threshold) {
+        Some(share) => Err(DegreeError::AboveThreshold {
+            degree: share.degree,
+            threshold,
+        }),
+        None => Ok(()),
+    }
+}
 
-/// Computes the shamir shares of a secret.
+/// Computes the shamir shares of a secret. From [`CONSECUTIVE_THRESHOLD`] parties on, the
+/// polynomial is evaluated at the points of the parties by forward differences in prime fields.
 pub fn compute_shamir_share<T, R>(
     secret: &T,
     n_parties: usize,
@@ -20,13 +246,20 @@ pub fn compute_shamir_share<T, R>(
 ) -> Vec<ShamirShare<T>>
 where
     T: FiniteField,
-    R: Rng,
+    R: Rng + CryptoRng,
 {
-    log::info!("computing Shamir share of secret value: {:?}", secret);
+    log::debug!(
+        "computing the Shamir shares of a secret for {n_parties} parties with degree {threshold}"
+    );
     let mut rand_poly = Polynomial::random(threshold, rng);
     rand_poly[0] = secret.clone();
 
-    log::debug!("using polynomial to share the secret: {:?}", rand_poly);
+    if n_parties >= CONSECUTIVE_THRESHOLD && has_consecutive_points::<T>() {
+        return evaluate_consecutive(&rand_poly, n_parties)
+            .into_iter()
+            .map(|evaluation| ShamirShare::new(evaluation, threshold))
+            .collect();
+    }
 
     let mut shares = Vec::with_capacity(n_parties);
 
@@ -38,76 +271,769 @@ where
     shares
 }
 
-/// Reconstructs a secret given its shares.
+/// Computes the Shamir shares of several secrets at once. The polynomials of the secrets are
+/// sampled as in [`compute_shamir_share`], so the shares are the same as sharing the secrets one
+/// by one with the same generator, but they are evaluated with a table of the powers of the
+/// evaluation points computed once for the whole batch. The result is a matrix indexed by the ID
+/// of the party and then by the position of the secret, so the row of every party can be encoded
+/// in a packet of [`crate::net::Network::scatter`].
+pub fn compute_shamir_shares_batch<T, R>(
+    secrets: &[T],
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+) -> Vec<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    log::debug!("computing the Shamir shares of {} secrets", secrets.len());
+    // The powers of the evaluation point of the party `i`, which is `i + 1`.
+    let powers: Vec<Vec<T>> = (1..n_parties as u64 + 1)
+        .map(|point| {
+            let point = T::from(point);
+            let mut powers = Vec::with_capacity(threshold + 1);
+            powers.push(T::ONE);
+            for j in 1..threshold + 1 {
+                powers.push(powers[j - 1].multiply(&point));
+            }
+            powers
+        })
+        .collect();
+    let mut shares: Vec<Vec<ShamirShare<T>>> = (0..n_parties)
+        .map(|_| Vec::with_capacity(secrets.len()))
+        .collect();
+    let consecutive = n_parties >= CONSECUTIVE_THRESHOLD && has_consecutive_points::<T>();
+    for secret in secrets {
+        let mut rand_poly = Polynomial::random(threshold, rng);
+        rand_poly[0] = secret.clone();
+        if consecutive {
+            let evaluations = evaluate_consecutive(&rand_poly, n_parties);
+            for (party_shares, evaluation) in shares.iter_mut().zip(evaluations) {
+                party_shares.push(ShamirShare::new(evaluation, threshold));
+            }
+            continue;
+        }
+        for (party_shares, powers) in shares.iter_mut().zip(&powers) {
+            party_shares.push(ShamirShare::new(
+                rand_poly.evaluate_with_powers(powers),
+                threshold,
+            ));
+        }
+    }
+    shares
+}
+
+/// Computes the Shamir shares of a vector of secrets, as [`compute_shamir_share`] does for a
+/// single one. The polynomials are sampled and evaluated in a single batch with
+/// [`compute_shamir_shares_batch`], and the result is indexed by the ID of the party that
+/// receives the shares, so every party receives all of its shares in a single packet.
+pub fn compute_shamir_shares<T, R>(
+    secrets: &[T],
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+) -> Vec<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    compute_shamir_shares_batch(secrets, n_parties, threshold, rng)
+}
+
+/// Computes the Shamir shares of several secrets. The result is indexed by the ID of the party
+/// that receives the shares, and each entry contains the share of every secret in order. The
+/// secrets are shared with [`compute_shamir_shares_batch`] in chunks split among the threads of
+/// [`parallel::num_threads`].
+pub fn share_inputs<T, R>(
+    secrets: &[T],
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+) -> Vec<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    deal_in_chunks(secrets.len(), n_parties, rng, |chunk, rng| {
+        compute_shamir_shares_batch(&secrets[chunk], n_parties, threshold, rng)
+    })
+}
+
+/// Deals `n_items` items to every party in the chunks of [`parallel::map_chunks`], where `deal`
+/// returns the items of a chunk indexed by party ID. Every chunk draws its randomness from its own
+/// generator, seeded from `rng` in the order of the chunks, so the items do not depend on the
+/// number of threads.
+fn deal_in_chunks<X, R, F>(n_items: usize, n_parties: usize, rng: &mut R, deal: F) -> Vec<Vec<X>>
+where
+    X: Send,
+    R: Rng + CryptoRng,
+    F: Fn(Range<usize>, &mut ChaCha20Rng) -> Vec<Vec<X>> + Sync,
+{
+    let seeds: Vec<[u8; 32]> = (0..n_items.div_ceil(parallel::CHUNK_SIZE))
+        .map(|_| rng.gen())
+        .collect();
+    let chunks = parallel::map_chunks(n_items, parallel::CHUNK_SIZE, |index, chunk| {
+        deal(chunk, &mut ChaCha20Rng::from_seed(seeds[index]))
+    });
+    let mut items: Vec<Vec<X>> = (0..n_parties)
+        .map(|_| Vec::with_capacity(n_items))
+        .collect();
+    for chunk in chunks {
+        for (party_items, chunk_items) in items.iter_mut().zip(chunk) {
+            party_items.extend(chunk_items);
+        }
+    }
+    items
+}
+
+/// Deals the shares of random Beaver triples, as a trusted dealer does in the preprocessing
+/// phase. The result is indexed by the ID of the party that receives the shares, and each entry
+/// contains the share of every triple in order. The triples are dealt in chunks split among the
+/// threads of [`parallel::num_threads`], and their products are computed in a single batch, which
+/// is offloaded to the given [`accelerator`] if it is large enough.
+pub fn deal_beaver_triples<T, R>(
+    count: usize,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    accelerator: Option<&dyn Accelerator>,
+) -> Vec<Vec<BeaverTriple<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    // The factors are dealt as two rows, one for each factor.
+    let factors = deal_in_chunks(count, 2, rng, |chunk, rng| {
+        let (a, b): (Vec<T>, Vec<T>) = chunk.map(|_| (T::random(rng), T::random(rng))).unzip();
+        vec![a, b]
+    });
+    let (a, b) = (&factors[0], &factors[1]);
+    let c = accelerator::multiply(a, b, accelerator);
+    deal_in_chunks(count, n_parties, rng, |chunk, rng| {
+        let mut triples: Vec<Vec<BeaverTriple<T>>> = (0..n_parties)
+            .map(|_| Vec::with_capacity(chunk.len()))
+            .collect();
+        for k in chunk {
+            let shares = [&a[k], &b[k], &c[k]]
+                .map(|value| compute_shamir_share(value, n_parties, threshold, rng));
+            let [a_shares, b_shares, c_shares] = shares;
+            for (party_triples, ((a, b), c)) in triples
+                .iter_mut()
+                .zip(a_shares.into_iter().zip(b_shares).zip(c_shares))
+            {
+                party_triples.push(BeaverTriple { a, b, c });
+            }
+        }
+        triples
+    })
+}
+
+/// Reconstructs a secret given its shares. The Lagrange basis of the parties comes from the
+/// [`recombination_vector`] cache, where it is computed once per number of shares.
 pub fn reconstruct_secret<T>(shares: Vec<ShamirShare<T>>) -> T
 where
     T: FiniteField,
 {
-    let alphas: Vec<T> = (1..shares.len() + 1)
-        .map(|idx| T::from(idx as u64))
+    let points: Vec<u64> = (1..shares.len() as u64 + 1).collect();
+    recombine(
+        &recombination_vector(&points),
+        shares.iter().map(|share| &share.value),
+    )
+}
+
+/// Computes the linear combination of the values of the shares with the Lagrange basis.
+fn recombine<'a, T: FiniteField>(basis: &[T], values: impl IntoIterator<Item = &'a T>) -> T {
+    basis
+        .iter()
+        .zip(values)
+        .fold(T::ZERO, |secret, (basis, value)| {
+            secret.add(&value.multiply(basis))
+        })
+}
+
+/// Reconstructs a secret from the shares held by a subset of the parties, where each share is
+/// paired with the ID of the party that holds it. At least `degree + 1` shares are needed, and
+/// the shares beyond them must lie on the polynomial determined by the first `degree + 1` ones.
+pub fn reconstruct_secret_from_parties<T>(
+    shares: Vec<(usize, ShamirShare<T>)>,
+) -> Result<T, ReconstructionError>
+where
+    T: FiniteField,
+{
+    reconstruct_from(&shares)
+}
+
+/// Reconstructs a secret from borrowed shares of any subset of the parties, e.g. when some of
+/// them are offline, as [`reconstruct_secret_from_parties`] does. The Lagrange basis is built for
+/// exactly the points of the given parties, and the number of shares is checked against the
+/// degree that they carry.
+pub fn reconstruct_from<T>(shares: &[(usize, ShamirShare<T>)]) -> Result<T, ReconstructionError>
+where
+    T: FiniteField,
+{
+    let degree = shares.first().map_or(0, |(_, share)| share.degree);
+    if shares.iter().any(|(_, share)| share.degree != degree) {
+        return Err(ReconstructionError::DegreeMismatch);
+    }
+    if shares.len() <= degree || shares.is_empty() {
+        return Err(ReconstructionError::NotEnoughShares {
+            degree,
+            received: shares.len(),
+        });
+    }
+    let mut parties: Vec<usize> = shares.iter().map(|(id, _)| *id).collect();
+    parties.sort_unstable();
+    if let Some(window) = parties.windows(2).find(|window| window[0] == window[1]) {
+        return Err(ReconstructionError::DuplicatedParty(window[0]));
+    }
+
+    // The share of the party with ID `i` is the evaluation of the polynomial at `i + 1`.
+    let (mut alphas, mut share_values): (Vec<T>, Vec<T>) = shares
+        .iter()
+        .map(|(id, share)| (T::from(*id as u64 + 1), share.value.clone()))
+        .unzip();
+    let extra_alphas = alphas.split_off(degree + 1);
+    let extra_values = share_values.split_off(degree + 1);
+    // Every extra share is checked in constant time instead of stopping at the first one that
+    // does not match, so the time does not reveal which shares deviate.
+    let mut consistent = Choice::from(1);
+    for (alpha, value) in extra_alphas.iter().zip(&extra_values) {
+        let expected = interpolate_polynomial_at(share_values.clone(), alphas.clone(), alpha);
+        consistent &= expected.ct_eq(value);
+    }
+    let points: Vec<u64> = shares[..degree + 1]
+        .iter()
+        .map(|(id, _)| *id as u64 + 1)
         .collect();
-    let share_values: Vec<T> = shares.into_iter().map(|share| share.value).collect();
-    interpolate_polynomial_at(share_values, alphas, &T::ZERO)
+    let secret = recombine(&recombination_vector(&points), &share_values);
+    Option::from(CtOption::new(secret, consistent))
+        .ok_or(ReconstructionError::InconsistentShares(degree))
+}
+
+/// Protocol ID of the packets of the openings of [`run_open_protocol`].
+#[cfg(feature = "native")]
+pub const OPEN_PROTOCOL_ID: u16 = 2;
+
+/// Opens a shared secret in the given round of the openings by sending the share of the party to
+/// every party, and waiting for the shares of the others until the round deadline of the network.
+/// If some parties miss the deadline, the opening fails with [`DeadlineError::MissingParties`],
+/// unless the deadline is robust and at least `2 * degree + 1` shares arrived in time, or it
+/// falls back to at least `degree + 1` shares. In that case, the secret is reconstructed from
+/// them, the shares beyond the first `degree + 1` still reveal a party that sent a wrong share,
+/// and the absent parties are recorded in [`Network::absences`]. If `security` is the active mode
+/// of [`verification::Security`], at least `2 * degree + 1` shares are needed in any case, so
+/// that a wrong share is always caught.
+#[cfg(feature = "native")]
+pub fn run_open_protocol<T>(
+    share: &ShamirShare<T>,
+    round: u32,
+    security: Security,
+    network: &mut Network,
+) -> anyhow::Result<T>
+where
+    T: FiniteField,
+{
+    let _span = trace::span("opening");
+    let deadline = network.round_deadline();
+    let codec = network.codec();
+    let packet = Packet::tagged(
+        OPEN_PROTOCOL_ID,
+        round,
+        encode_value(codec, share)?.into_payload(),
+    );
+    let gathered = network.broadcast_and_gather_within(&packet, deadline.duration())?;
+
+    let missing = missing_parties(&gathered);
+    if !missing.is_empty() {
+        let needed = match security {
+            Security::Passive => deadline.needed_shares(share.degree),
+            Security::Active => 2 * share.degree + 1,
+        };
+        let received = gathered.len() - missing.len();
+        if !deadline.tolerates_missing() {
+            anyhow::bail!(DeadlineError::MissingParties {
+                round,
+                deadline: deadline.duration(),
+                missing,
+            });
+        }
+        if received < needed {
+            anyhow::bail!(DeadlineError::NotEnoughParties {
+                round,
+                received,
+                needed,
+                missing,
+            });
+        }
+        network.record_absence(round, missing);
+    }
+
+    let shares = gathered
+        .into_iter()
+        .enumerate()
+        .filter_map(|(party_id, packet)| Some((party_id, packet?)))
+        .map(|(party_id, packet)| Ok((party_id, decode_value(codec, &packet)?)))
+        .collect::<anyhow::Result<_>>()?;
+    Ok(reconstruct_secret_from_parties(shares)?)
+}
+
+/// Opens a shared secret only to the party with the given ID, which receives the share of every
+/// other party and reconstructs the secret from all of them, failing with
+/// [`ReconstructionError::InconsistentShares`] if some share is wrong. The other parties send
+/// their shares and learn nothing, so they get `None`.
+pub fn open_to<T, N>(
+    share: &ShamirShare<T>,
+    receiver: usize,
+    network: &mut N,
+) -> anyhow::Result<Option<T>>
+where
+    T: FiniteField,
+    N: Transport,
+{
+    anyhow::ensure!(
+        receiver < network.n_parties(),
+        "there is no party {receiver} to open the secret to"
+    );
+    let id = network.id();
+    if id != receiver {
+        let message = network.codec().encode(share)?;
+        network.send_message(receiver, message)?;
+        return Ok(None);
+    }
+    let shares = (0..network.n_parties())
+        .map(|party_id| {
+            if party_id == id {
+                return Ok((party_id, share.clone()));
+            }
+            let message = Zeroizing::new(network.recv_message(party_id)?);
+            Ok((party_id, network.codec().decode(&message)?))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(Some(reconstruct_secret_from_parties(shares)?))
 }
 
-/// Run the protocol to multiply `a` and `b`, where `a` and `b` are already secret shared.
-pub fn run_multiply_protocol<T, R>(
+/// Run the protocol to multiply `a` and `b`, where `a` and `b` are already secret shared. In the
+/// two-party mode and in the online phase of [`preprocessing`], the product is computed with a
+/// Beaver triple of the engine. A factor of degree above the threshold is reduced first, as
+/// in [`run_batch_multiply_protocol`], and the product is verified in the active mode.
+pub fn run_multiply_protocol<T, R, N>(
     a: &ShamirShare<T>,
     b: &ShamirShare<T>,
     n_parties: usize,
     threshold: usize,
     rng: &mut R,
-    network: &mut Network,
+    engine: &mut Engine<N>,
 ) -> anyhow::Result<ShamirShare<T>>
 where
     T: FiniteField,
-    R: Rng,
+    R: Rng + CryptoRng,
+    N: Transport,
 {
-    let h = a.multiply(b);
-    let h_own_shares = compute_shamir_share(&h.value, n_parties, threshold, rng);
+    let pair = [(a.clone(), b.clone())];
+    if engine.security() == Security::Active {
+        let mut products =
+            verification::run_verified_multiply(&pair, n_parties, threshold, rng, engine)?;
+        return Ok(products.remove(0));
+    }
+    let pairs = reduce_factors(&pair, n_parties, threshold, rng, engine)?;
+    if two_party::uses_triples(engine, threshold) {
+        let mut products = two_party::run_beaver_multiply_protocol(&pairs, threshold, engine)?;
+        return Ok(products.remove(0));
+    }
+    let (a, b) = &pairs[0];
+    let h = a.multiply(b, n_parties)?;
+    log::info!("reducing the product share of degree {}", h.degree);
+    let mut products = reduce_values(
+        std::slice::from_ref(&h.value),
+        h.degree,
+        threshold,
+        rng,
+        engine,
+    )?;
+    Ok(products.remove(0))
+}
+
+/// Runs the protocol to multiply several pairs of shared values at once, so that all the products
+/// take the rounds of a single one. The local products of degree `2 * d` are reduced as in the
+/// multiplication of Damgård and Nielsen: the parties generate random double sharings in a
+/// round, and open the products masked with them to a king party, which sends them back, in
+/// another two. Every product costs `O(n)` elements of communication instead of the `O(n^2)` of
+/// resharing it with every party. The shares of the products are returned in the order of the
+/// pairs. In the two-party mode and in the online phase of [`preprocessing`], the products are
+/// computed with the Beaver triples of the engine in a single round instead. If some factor
+/// has a degree above the threshold, e.g. a local product of two shares, the factors are reduced
+/// with [`run_degree_reduction_protocol`] first, which takes its own rounds. In the active mode
+/// of the engine, the products are verified with [`verification::run_verified_multiply`].
+pub fn run_batch_multiply_protocol<T, R, N>(
+    pairs: &[(ShamirShare<T>, ShamirShare<T>)],
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    let _span = trace::span("multiplication");
+    if engine.security() == Security::Active {
+        return verification::run_verified_multiply(pairs, n_parties, threshold, rng, engine);
+    }
+    multiply_pairs(pairs, n_parties, threshold, rng, engine)
+}
 
-    // Send product shares to other parties
-    log::info!("sending shares of the product share of degree 2 * d");
-    for (i, share) in h_own_shares.iter().enumerate() {
-        let share_bytes = bincode::serialize(&share)?;
-        network.send_to(&Packet::new(share_bytes), i)?;
+/// Multiplies the pairs of factors as in [`run_batch_multiply_protocol`] without verifying the
+/// products, which is the passive protocol that the verification of the active mode builds on.
+fn multiply_pairs<T, R, N>(
+    pairs: &Factors<T>,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    let pairs = reduce_factors(pairs, n_parties, threshold, rng, engine)?;
+    if two_party::uses_triples(engine, threshold) {
+        return two_party::run_beaver_multiply_protocol(&pairs, threshold, engine);
+    }
+    let mut h_values = Vec::with_capacity(pairs.len());
+    let mut degrees = Vec::with_capacity(pairs.len());
+    for (a, b) in pairs.iter() {
+        let h = a.multiply(b, n_parties)?;
+        h_values.push(h.value);
+        degrees.push(h.degree);
+    }
+    let degree = degrees.into_iter().max().unwrap_or(threshold);
+    log::info!("reducing {} products of degree up to {degree}", pairs.len());
+    reduce_values(&h_values, degree, threshold, rng, engine)
+}
+
+/// Multiplies the shares of `a[k]` by the shares of `b[k]` for every `k` in the same rounds, as
+/// [`run_batch_multiply_protocol`] with the factors given as two slices of the same length, e.g.
+/// the left and right inputs of a layer of multiplication gates.
+pub fn run_multiply_many<T, R, N>(
+    a: &[ShamirShare<T>],
+    b: &[ShamirShare<T>],
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    anyhow::ensure!(
+        a.len() == b.len(),
+        "multiplying {} factors by {} factors, but there must be as many of each",
+        a.len(),
+        b.len()
+    );
+    let pairs: Vec<_> = a.iter().cloned().zip(b.iter().cloned()).collect();
+    run_batch_multiply_protocol(&pairs, n_parties, threshold, rng, engine)
+}
+
+/// Runs the protocol that reduces the degree of the shares above the threshold to the threshold,
+/// masking them with random double sharings and opening them to a single party, as in the
+/// products of [`run_batch_multiply_protocol`]. The shares of degree up to the threshold are kept as
+/// they are, and the degrees are public, so every party reduces the same shares. A share can
+/// only be reduced if its degree is below the number of parties, which rules out the products
+/// of the two-party mode. The reduction can not be verified, so it fails in the active mode of
+/// the engine.
+pub fn run_degree_reduction_protocol<T, R, N>(
+    shares: &[ShamirShare<T>],
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    if let Some(share) = shares.iter().find(|share| share.degree >= n_parties) {
+        anyhow::bail!(DegreeError::Irreducible {
+            degree: share.degree,
+            n_parties,
+        });
+    }
+    let high: Vec<usize> = (0..shares.len())
+        .filter(|k| shares[*k].degree > threshold)
+        .collect();
+    let mut reduced = shares.to_vec();
+    if high.is_empty() {
+        return Ok(reduced);
     }
+    if engine.security() == Security::Active {
+        anyhow::bail!(VerificationError::UnverifiableReduction {
+            degree: shares[high[0]].degree,
+        });
+    }
+    log::info!("reducing {} shares of degree above {threshold}", high.len());
+    let values: Vec<T> = high.iter().map(|k| shares[*k].value.clone()).collect();
+    let degree = high
+        .iter()
+        .map(|k| shares[*k].degree)
+        .max()
+        .unwrap_or(threshold);
+    for (k, share) in high
+        .into_iter()
+        .zip(reduce_values(&values, degree, threshold, rng, engine)?)
+    {
+        reduced[k] = share;
+    }
+    Ok(reduced)
+}
 
-    log::debug!("sending own shares of h(i): {:?}", h_own_shares);
+/// Pairs of factors of products.
+type Factors<T> = [(ShamirShare<T>, ShamirShare<T>)];
 
-    // Get the shares from other parties.
-    log::info!("receiving shares of the product from other parties");
-    let mut h_shares = Vec::with_capacity(n_parties);
-    for i in 0..n_parties {
-        let share_packet = network.recv_from(i)?;
-        let share: ShamirShare<T> = bincode::deserialize(share_packet.as_slice())?;
-        h_shares.push(share);
+/// Reduces the factors of the products to the threshold if some of them have a higher degree.
+fn reduce_factors<'a, T, R, N>(
+    pairs: &'a Factors<T>,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Cow<'a, Factors<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    if check_degree(pairs.iter().flat_map(|(a, b)| [a, b]), threshold).is_ok() {
+        return Ok(Cow::Borrowed(pairs));
     }
+    log::warn!("reducing the factors of degree above {threshold} before multiplying them");
+    let factors: Vec<ShamirShare<T>> = pairs
+        .iter()
+        .flat_map(|(a, b)| [a.clone(), b.clone()])
+        .collect();
+    let reduced = run_degree_reduction_protocol(&factors, n_parties, threshold, rng, engine)?;
+    Ok(Cow::Owned(
+        reduced
+            .chunks_exact(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect(),
+    ))
+}
+
+/// Reduces the values of shares of degree up to `degree`, which is below the number of parties,
+/// to shares of degree `threshold` of the same secrets. The parties take as many double sharings
+/// of degrees `threshold` and `degree` from the pool of the engine, which only takes a round if
+/// the ones preprocessed with [`Engine::preprocess_double_sharings`] run out, and open the values
+/// masked with them to the king of [`double_sharing::run_king_reduction`] in another two, so that
+/// every value costs `O(n)` elements of communication instead of a resharing to every party.
+fn reduce_values<T, R, N>(
+    values: &[T],
+    degree: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    let sharings = engine
+        .take_double_sharings(values.len(), threshold, degree.max(threshold), rng)?
+        .take(values.len())?;
+    double_sharing::run_king_reduction(values, degree, &sharings, engine)
+}
 
-    log::debug!("received shares of h(i): {:?}", h_shares);
+/// Recombines the shares of `n_products` products of degree `2 * d` received from every party,
+/// indexed by party ID and then by product, into shares of degree `d` of the products. The
+/// products are recombined in the given [`accelerator`] if the batch is large enough, or else in
+/// chunks split among the threads of [`parallel::num_threads`]. The Lagrange basis of the
+/// parties comes from the [`recombination_vector`] cache, so it is computed once per session.
+fn recombine_products<T: FiniteField>(
+    h_shares: &[Vec<ShamirShare<T>>],
+    n_products: usize,
+    accelerator: Option<&dyn Accelerator>,
+) -> Vec<ShamirShare<T>> {
+    let points: Vec<u64> = (1..h_shares.len() as u64 + 1).collect();
+    let basis = recombination_vector(&points);
+    accelerator::combine_shares(&basis, h_shares, n_products, accelerator)
+        .into_iter()
+        .zip(&h_shares[0])
+        .map(|(value, share)| ShamirShare::new(value, share.degree))
+        .collect()
+}
 
-    // Compute recombination vector.
-    let basis = compute_lagrange_basis(
-        (1..n_parties + 1).map(|idx| T::from(idx as u64)).collect(),
-        &T::ZERO,
-    );
+/// Batch of products whose masked values were sent with [`start_batch_multiply_protocol`], while
+/// the opened values are still in flight.
+/// The party can do other work, such as starting the next batch, before finishing it with
+/// [`PendingProducts::finish`]. The batches are finished in the order in which they were
+/// started, which must be the same for every party.
+#[derive(Debug)]
+pub struct PendingProducts<T> {
+    /// Degree of the shares of the factors.
+    threshold: usize,
+    /// Masked values that the party kept for itself: its shares of the masked factors if the
+    /// products use Beaver triples, or else its masked products if the party is the king.
+    own: Option<ShareBatch<T>>,
+    /// Beaver triples with which the factors were masked, if the products use them, which is
+    /// the case if it is not empty.
+    triples: Vec<BeaverTriple<T>>,
+    /// Double sharings with which the products were masked otherwise.
+    sharings: Vec<DoubleSharing<T>>,
+}
 
-    let mut mult_share = h_shares[0].multiply_const(&basis[0]);
-    for (r, share) in basis.into_iter().zip(h_shares).skip(1) {
-        mult_share = mult_share.add(&share.multiply_const(&r));
+/// Starts the batched multiplication of [`run_batch_multiply_protocol`] by sending the masked
+/// products to the king of [`double_sharing::KING`], without waiting for the opened ones. The
+/// messages go through [`Transport::send_message`], so the messages of a batch must fit in the
+/// buffers of the connections while the party does not receive. The products are masked with
+/// double sharings of degrees `threshold` and `2 * threshold` taken from `sharings`, which the
+/// parties generate ahead of the batches with [`DoubleSharings::generate`], since generating
+/// them takes its own round. If the products use the Beaver triples of the engine, the party
+/// sends its shares of the factors masked with the triples to every party instead. The factors
+/// are not reduced here either, so a factor of degree above the threshold fails with
+/// [`DegreeError::AboveThreshold`]. The batches are not verified, so they can not be started in
+/// the active mode of the engine.
+pub fn start_batch_multiply_protocol<T, N>(
+    pairs: &[(ShamirShare<T>, ShamirShare<T>)],
+    threshold: usize,
+    sharings: &mut DoubleSharings<T>,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<PendingProducts<T>>
+where
+    T: FiniteField,
+    N: Transport,
+{
+    check_degree(pairs.iter().flat_map(|(a, b)| [a, b]), threshold)?;
+    if engine.security() == Security::Active {
+        anyhow::bail!(VerificationError::Pipelined);
+    }
+    if two_party::uses_triples(engine, threshold) && !pairs.is_empty() {
+        let triples = engine.triples_mut().take(pairs.len(), threshold)?;
+        let own = two_party::mask_factors(pairs, &triples, threshold);
+        let message = engine.codec().encode(&own)?;
+        let id = engine.id();
+        for party_id in (0..engine.n_parties()).filter(|party_id| *party_id != id) {
+            engine.send_message(party_id, message.clone())?;
+        }
+        return Ok(PendingProducts {
+            threshold,
+            own: Some(own),
+            triples,
+            sharings: Vec::new(),
+        });
     }
+    let n_parties = engine.n_parties();
+    let h_values = pairs
+        .iter()
+        .map(|(a, b)| Ok(a.multiply(b, n_parties)?.value))
+        .collect::<Result<Vec<T>, DegreeError>>()?;
+    let sharings = sharings.take(pairs.len())?;
+    let own = double_sharing::send_masked(&h_values, 2 * threshold, &sharings, engine)?;
+    Ok(PendingProducts {
+        threshold,
+        own,
+        triples: Vec::new(),
+        sharings,
+    })
+}
 
-    Ok(mult_share)
+impl<T: FiniteField> PendingProducts<T> {
+    /// Receives the masked values of the batch, which the king opens and sends back to the other
+    /// parties, and returns the shares of the products in the order of the pairs.
+    pub fn finish<N: Transport>(
+        self,
+        engine: &mut Engine<N>,
+    ) -> anyhow::Result<Vec<ShamirShare<T>>> {
+        if self.triples.is_empty() {
+            return double_sharing::receive_opened(self.own, &self.sharings, engine);
+        }
+        let mut own = self.own;
+        let masked = (0..engine.n_parties())
+            .map(|party_id| {
+                if party_id == engine.id() {
+                    Ok(own.take().expect("the own batch is taken once"))
+                } else {
+                    let message = Zeroizing::new(engine.recv_message(party_id)?);
+                    engine.codec().decode::<ShareBatch<T>>(&message)
+                }
+            })
+            .collect::<anyhow::Result<_>>()?;
+        two_party::unmask_products(&self.triples, masked, self.threshold)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use rand::{thread_rng, Rng};
+    use std::{thread, time::Duration};
+
+    use rand::{
+        rngs::{mock::StepRng, StdRng},
+        seq::SliceRandom,
+        thread_rng, CryptoRng, Rng, RngCore, SeedableRng,
+    };
 
+    use crate::canary::{freed_copies, CANARY};
+    use crate::math::gf2k::GF2k;
     use crate::math::mersenne61::Mersenne61;
     use crate::math::FiniteField;
+    use crate::net::Network;
+
+    use super::{
+        check_field, check_parameters, check_threshold, compute_shamir_share,
+        compute_shamir_shares, compute_shamir_shares_batch, deal_beaver_triples,
+        double_sharing::DoubleSharings, engine::Engine, open_to, parallel, reconstruct_from,
+        reconstruct_secret, reconstruct_secret_from_parties, run_batch_multiply_protocol,
+        run_degree_reduction_protocol, run_multiply_many, run_multiply_protocol, run_open_protocol,
+        share::ShamirShare, share_inputs, start_batch_multiply_protocol, verification::Security,
+        DegreeError, ReconstructionError, ThresholdError,
+    };
+    use crate::net::deadline::{Absence, DeadlineError, RoundDeadline};
+    use crate::net::stats::NetworkStats;
+
+    /// Generator that steps through the coefficients of the golden vectors. It is only marked as
+    /// cryptographic so that the sharing accepts it, since the golden vectors are public anyway.
+    struct SteppingRng(StepRng);
+
+    impl RngCore for SteppingRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0.next_u32()
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0.next_u64()
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.0.fill_bytes(dest)
+        }
 
-    use super::{compute_shamir_share, reconstruct_secret};
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.0.try_fill_bytes(dest)
+        }
+    }
+
+    impl CryptoRng for SteppingRng {}
+
+    /// Number of random cases checked by each property.
+    const N_CASES: usize = 50;
+
+    /// Returns a random number of parties and a threshold that is valid for them. If the shares
+    /// are multiplied, the number of parties is greater than twice the threshold.
+    fn random_parameters<R: Rng>(rng: &mut R, multiplication: bool) -> (usize, usize) {
+        let n_parties = rng.gen_range(3..12);
+        let max_threshold = if multiplication {
+            (n_parties - 1) / 2
+        } else {
+            n_parties - 1
+        };
+        (n_parties, rng.gen_range(0..=max_threshold))
+    }
 
     #[test]
     fn secret_sharing_reconstruction_correctness() {
@@ -126,4 +1052,818 @@ mod tests {
             assert_eq!(secret, reconst_secret);
         }
     }
+
+    #[test]
+    fn golden_sharing_vectors() {
+        // Computed independently with arbitrary-precision integers modulo 2^61 - 1. The
+        // generator steps through the coefficients, the first of which is replaced by the secret.
+        let vectors = [
+            (
+                123456789,
+                (0xdeadbeefcafebabe, 0x9e3779b97f4a7c15),
+                [
+                    0x1801eb0c1b38b6d4,
+                    0x63d3addc23d0666,
+                    0xab1ef74fc68bbc9,
+                    0x56008d1c9bbd6fe,
+                    0x164786f42a365804,
+                ],
+            ),
+            (
+                Mersenne61::MODULUS - 2,
+                (0x0123456789abcdef, 0x1111111111111111),
+                [
+                    0x1579be02468acf10,
+                    0x117e4b17e4b17e47,
+                    0x140da740da740da2,
+                    0x1d27d27d27d27d21,
+                    0xcccccccccccccc5,
+                ],
+            ),
+        ];
+        let products = [
+            0x1d99070ae5efe7d9,
+            0x163ebb2aef872354,
+            0x1e4ddf4ff8b87236,
+            0xa2ebacce5c069dd,
+            0xe551957946ddb10,
+        ];
+
+        let shares: Vec<_> = vectors
+            .iter()
+            .map(|(secret, (initial, increment), expected)| {
+                let secret = Mersenne61::from(*secret);
+                let shares = compute_shamir_share(
+                    &secret,
+                    5,
+                    2,
+                    &mut SteppingRng(StepRng::new(*initial, *increment)),
+                );
+                let values: Vec<_> = shares.iter().map(|share| share.value.clone()).collect();
+                assert_eq!(values, expected.map(Mersenne61::from));
+                assert_eq!(reconstruct_secret(shares.clone()), secret);
+                shares
+            })
+            .collect();
+
+        let product_shares: Vec<_> = shares[0]
+            .iter()
+            .zip(&shares[1])
+            .map(|(a, b)| a.multiply(b, 5).unwrap())
+            .collect();
+        let values: Vec<_> = product_shares
+            .iter()
+            .map(|share| share.value.clone())
+            .collect();
+        assert_eq!(values, products.map(Mersenne61::from));
+        assert_eq!(
+            reconstruct_secret(product_shares),
+            Mersenne61::from(0x1ffffffff14865d5)
+        );
+    }
+
+    #[test]
+    fn reconstruction_from_any_subset_of_enough_parties() {
+        let mut rng = thread_rng();
+        for _ in 0..N_CASES {
+            let (n_parties, threshold) = random_parameters(&mut rng, false);
+            let secret = Mersenne61::random(&mut rng);
+            let mut shares: Vec<_> = compute_shamir_share(&secret, n_parties, threshold, &mut rng)
+                .into_iter()
+                .enumerate()
+                .collect();
+            shares.shuffle(&mut rng);
+            let size = rng.gen_range(threshold + 1..=n_parties);
+            assert_eq!(
+                reconstruct_secret_from_parties(shares[..size].to_vec()),
+                Ok(secret),
+                "{size} of {n_parties} shares with threshold {threshold}"
+            );
+        }
+    }
+
+    #[test]
+    fn sharing_is_linear() {
+        let mut rng = thread_rng();
+        for _ in 0..N_CASES {
+            let (n_parties, threshold) = random_parameters(&mut rng, false);
+            let a = Mersenne61::random(&mut rng);
+            let b = Mersenne61::random(&mut rng);
+            let c = Mersenne61::random(&mut rng);
+            let a_shares = compute_shamir_share(&a, n_parties, threshold, &mut rng);
+            let b_shares = compute_shamir_share(&b, n_parties, threshold, &mut rng);
+
+            // The parties compute c * a + b - c locally on their shares.
+            let combined: Vec<_> = a_shares
+                .iter()
+                .zip(&b_shares)
+                .map(|(a_share, b_share)| {
+                    a_share.multiply_const(&c).add(b_share).subtract_const(&c)
+                })
+                .collect();
+            assert_eq!(
+                reconstruct_secret(combined),
+                c.multiply(&a).add(&b).subtract(&c),
+                "{n_parties} parties with threshold {threshold}"
+            );
+        }
+    }
+
+    #[test]
+    fn fewer_shares_than_the_threshold_reveal_nothing() {
+        const N_PARTIES: usize = 5;
+        const THRESHOLD: usize = 2;
+        const N_SAMPLES: usize = 4000;
+        const N_BUCKETS: usize = 16;
+        // Critical value of the chi-squared distribution with 15 degrees of freedom for a
+        // significance of 0.0001.
+        const CRITICAL_VALUE: f64 = 42.6;
+
+        // The generator is seeded so that the statistical test is deterministic.
+        let mut rng = StdRng::seed_from_u64(0x5ba1);
+        for secret in [Mersenne61::ZERO, Mersenne61::from(Mersenne61::MODULUS - 1)] {
+            // The shares held by any `THRESHOLD` parties must be uniform whatever the secret.
+            let mut counts = [[0usize; N_BUCKETS]; THRESHOLD];
+            for _ in 0..N_SAMPLES {
+                let shares = compute_shamir_share(&secret, N_PARTIES, THRESHOLD, &mut rng);
+                for (share, counts) in shares[N_PARTIES - THRESHOLD..].iter().zip(&mut counts) {
+                    let bytes = bincode::serialize(&share.value).unwrap();
+                    counts[usize::from(bytes[0]) % N_BUCKETS] += 1;
+                }
+            }
+            let expected = (N_SAMPLES / N_BUCKETS) as f64;
+            for counts in counts {
+                let chi_squared: f64 = counts
+                    .iter()
+                    .map(|&count| (count as f64 - expected).powi(2) / expected)
+                    .sum();
+                assert!(
+                    chi_squared < CRITICAL_VALUE,
+                    "the shares of {secret:?} are not uniform: {counts:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reconstruction_from_a_subset_of_parties() {
+        let mut rng = thread_rng();
+        let secret = Mersenne61::random(&mut rng);
+        let mut subset: Vec<_> = compute_shamir_share(&secret, 5, 2, &mut rng)
+            .into_iter()
+            .enumerate()
+            .filter(|(id, _)| [4, 1, 2].contains(id))
+            .collect();
+        subset.reverse();
+        assert_eq!(
+            reconstruct_secret_from_parties(subset[..2].to_vec()),
+            Err(ReconstructionError::NotEnoughShares {
+                degree: 2,
+                received: 2
+            })
+        );
+
+        let mut tampered = subset.clone();
+        tampered.push((0, ShamirShare::new(secret.clone(), 2)));
+        assert_eq!(
+            reconstruct_secret_from_parties(tampered),
+            Err(ReconstructionError::InconsistentShares(2))
+        );
+
+        let mut with_duplicate = subset.clone();
+        with_duplicate.push(subset[1].clone());
+        assert_eq!(
+            reconstruct_secret_from_parties(with_duplicate),
+            Err(ReconstructionError::DuplicatedParty(2))
+        );
+        // The shares can also be borrowed, e.g. from the shares of the parties that are online.
+        assert_eq!(
+            reconstruct_from(&subset[1..]),
+            Err(ReconstructionError::NotEnoughShares {
+                degree: 2,
+                received: 2
+            })
+        );
+        assert_eq!(reconstruct_from(&subset), Ok(secret.clone()));
+        assert_eq!(reconstruct_secret_from_parties(subset), Ok(secret));
+    }
+
+    #[test]
+    fn vectors_of_secrets_are_shared_and_reconstructed() {
+        const N_PARTIES: usize = 5;
+        const THRESHOLD: usize = 2;
+
+        let secrets: Vec<Mersenne61> = [3, 17, 42].into_iter().map(Mersenne61::from).collect();
+        let mut rng = StdRng::seed_from_u64(5);
+        let shares = compute_shamir_shares(&secrets, N_PARTIES, THRESHOLD, &mut rng);
+        assert_eq!(shares.len(), N_PARTIES);
+        assert!(shares.iter().all(|row| row.len() == secrets.len()));
+        for (k, secret) in secrets.iter().enumerate() {
+            let column: Vec<_> = shares.iter().map(|row| row[k].clone()).collect();
+            assert!(column.iter().all(|share| share.degree == THRESHOLD));
+            assert_eq!(&reconstruct_secret(column), secret);
+        }
+
+        assert_eq!(
+            compute_shamir_shares::<Mersenne61, _>(&[], N_PARTIES, THRESHOLD, &mut rng),
+            vec![Vec::new(); N_PARTIES]
+        );
+    }
+
+    #[test]
+    fn batches_are_shared_as_single_secrets() {
+        let secrets: Vec<Mersenne61> = (0..10).map(Mersenne61::from).collect();
+        // The second sizes are above the threshold of the evaluation by forward differences.
+        for (n_parties, threshold) in [(4, 2), (40, 13)] {
+            let mut rng = StdRng::seed_from_u64(3);
+            let batch = compute_shamir_shares_batch(&secrets, n_parties, threshold, &mut rng);
+            let mut rng = StdRng::seed_from_u64(3);
+            for (k, secret) in secrets.iter().enumerate() {
+                let shares = compute_shamir_share(secret, n_parties, threshold, &mut rng);
+                let column: Vec<_> = batch.iter().map(|shares| shares[k].clone()).collect();
+                assert_eq!(column, shares);
+                assert_eq!(&reconstruct_secret(shares), secret);
+            }
+        }
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(
+            compute_shamir_shares_batch::<Mersenne61, _>(&[], 4, 2, &mut rng),
+            vec![Vec::new(); 4]
+        );
+    }
+
+    #[test]
+    fn dealing_does_not_depend_on_the_threads() {
+        let secrets: Vec<Mersenne61> = (0..3 * parallel::CHUNK_SIZE as u64 / 2)
+            .map(Mersenne61::from)
+            .collect();
+        let deal = || {
+            let mut rng = StdRng::seed_from_u64(7);
+            (
+                share_inputs(&secrets, 3, 1, &mut rng),
+                deal_beaver_triples::<Mersenne61, _>(secrets.len(), 3, 1, &mut rng, None),
+            )
+        };
+        let (shares, triples) = deal();
+        parallel::set_num_threads(4);
+        assert_eq!(deal(), (shares.clone(), triples));
+        parallel::set_num_threads(1);
+
+        for (k, secret) in secrets.iter().enumerate() {
+            let parties = shares.iter().map(|shares| shares[k].clone()).collect();
+            assert_eq!(reconstruct_secret(parties), *secret);
+        }
+    }
+
+    #[test]
+    fn thresholds_depend_on_the_protocol() {
+        assert_eq!(check_threshold(3, 2, false), Ok(()));
+        assert_eq!(check_threshold(3, 1, true), Ok(()));
+        // Two parties multiply with Beaver triples instead.
+        assert_eq!(check_threshold(2, 1, true), Ok(()));
+        assert_eq!(
+            check_threshold(3, 3, false),
+            Err(ThresholdError::TooManyCorruptions {
+                threshold: 3,
+                n_parties: 3
+            })
+        );
+        assert_eq!(
+            check_threshold(4, 2, true),
+            Err(ThresholdError::NotEnoughPartiesToMultiply {
+                threshold: 2,
+                n_parties: 4
+            })
+        );
+    }
+
+    #[test]
+    fn parties_need_distinct_evaluation_points() {
+        let modulus = Mersenne61::MODULUS as usize;
+        assert_eq!(check_field::<Mersenne61>(modulus - 1), Ok(()));
+        assert_eq!(
+            check_field::<Mersenne61>(modulus),
+            Err(ThresholdError::FieldTooSmall {
+                n_parties: modulus,
+                modulus: Mersenne61::MODULUS.to_string()
+            })
+        );
+        assert!(check_parameters::<Mersenne61>(modulus, 1, true).is_err());
+        assert_eq!(check_parameters::<Mersenne61>(3, 1, true), Ok(()));
+        assert!(check_parameters::<Mersenne61>(3, 2, true).is_err());
+    }
+
+    #[test]
+    fn multiplication_over_local_network() {
+        multiply_over_local_network::<Mersenne61>();
+        multiply_over_local_network::<GF2k>();
+    }
+
+    /// Multiplies two random secrets of the field `T` among the parties of a local network.
+    fn multiply_over_local_network<T: FiniteField + 'static>() {
+        const N_PARTIES: usize = 5;
+        const THRESHOLD: usize = 2;
+
+        let mut rng = thread_rng();
+        let a = T::random(&mut rng);
+        let b = T::random(&mut rng);
+        let a_shares = compute_shamir_share(&a, N_PARTIES, THRESHOLD, &mut rng);
+        let b_shares = compute_shamir_share(&b, N_PARTIES, THRESHOLD, &mut rng);
+
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(a_shares.into_iter().zip(b_shares))
+            .map(|(mut network, (a_share, b_share))| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    run_multiply_protocol(
+                        &a_share,
+                        &b_share,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap()
+                })
+            })
+            .collect();
+        let mult_shares = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert!(reconstruct_secret(mult_shares).equal(&a.multiply(&b)));
+    }
+
+    #[test]
+    fn open_to_a_single_receiver() {
+        const N_PARTIES: usize = 4;
+        const RECEIVER: usize = 2;
+
+        let secret = Mersenne61::from(42);
+        let shares = compute_shamir_share(&secret, N_PARTIES, 1, &mut thread_rng());
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(shares)
+            .map(|(mut network, share)| {
+                thread::spawn(move || open_to(&share, RECEIVER, &mut network).unwrap())
+            })
+            .collect();
+        let opened: Vec<Option<Mersenne61>> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        assert_eq!(opened, [None, None, Some(secret), None]);
+    }
+
+    /// Opens a secret shared among the parties of a local network whose last `n_silent` parties
+    /// never send their share, and returns the outcome of each party that does.
+    fn open_with_silent_parties(
+        n_parties: usize,
+        threshold: usize,
+        n_silent: usize,
+        deadline: RoundDeadline,
+    ) -> Vec<anyhow::Result<Mersenne61>> {
+        let shares = compute_shamir_share(
+            &Mersenne61::from(42),
+            n_parties,
+            threshold,
+            &mut thread_rng(),
+        );
+        let handles: Vec<_> = Network::create_local(n_parties)
+            .into_iter()
+            .zip(shares)
+            .map(|(mut network, share)| {
+                thread::spawn(move || {
+                    network.set_round_deadline(deadline);
+                    if network.id() >= n_parties - n_silent {
+                        // The party keeps its connections open without sending, as if it hung.
+                        thread::sleep(Duration::from_millis(500));
+                        return None;
+                    }
+                    let result = run_open_protocol(&share, 0, Security::Passive, &mut network);
+                    if result.is_ok() && n_silent > 0 {
+                        let silent: Vec<usize> = (n_parties - n_silent..n_parties).collect();
+                        assert_eq!(
+                            network.absences(),
+                            [Absence {
+                                round: 0,
+                                parties: silent
+                            }]
+                        );
+                    }
+                    Some(result)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn openings_abort_when_parties_miss_the_deadline() {
+        let deadline = RoundDeadline {
+            millis: Some(100),
+            ..Default::default()
+        };
+        for result in open_with_silent_parties(4, 1, 0, deadline) {
+            assert_eq!(result.unwrap(), Mersenne61::from(42));
+        }
+        for result in open_with_silent_parties(4, 1, 1, deadline) {
+            assert_eq!(
+                result.unwrap_err().downcast::<DeadlineError>().unwrap(),
+                DeadlineError::MissingParties {
+                    round: 0,
+                    deadline: Duration::from_millis(100),
+                    missing: vec![3],
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn robust_openings_proceed_without_the_missing_parties() {
+        let deadline = RoundDeadline {
+            millis: Some(100),
+            robust: true,
+            ..Default::default()
+        };
+        for result in open_with_silent_parties(4, 1, 1, deadline) {
+            assert_eq!(result.unwrap(), Mersenne61::from(42));
+        }
+        // Two shares of degree one can not tell a wrong share, so the opening does not proceed.
+        for result in open_with_silent_parties(4, 1, 2, deadline) {
+            assert!(matches!(
+                result.unwrap_err().downcast::<DeadlineError>().unwrap(),
+                DeadlineError::NotEnoughParties {
+                    received: 2,
+                    needed: 3,
+                    ..
+                }
+            ));
+        }
+    }
+
+    #[test]
+    fn openings_fall_back_to_the_shares_that_arrived() {
+        let deadline = RoundDeadline {
+            millis: Some(100),
+            fallback: true,
+            ..Default::default()
+        };
+        for result in open_with_silent_parties(4, 1, 2, deadline) {
+            assert_eq!(result.unwrap(), Mersenne61::from(42));
+        }
+        for result in open_with_silent_parties(4, 1, 3, deadline) {
+            assert!(matches!(
+                result.unwrap_err().downcast::<DeadlineError>().unwrap(),
+                DeadlineError::NotEnoughParties {
+                    received: 1,
+                    needed: 2,
+                    ..
+                }
+            ));
+        }
+    }
+
+    /// Size in bytes of a value of Mersenne61 in a batch of shares, which only holds its degree
+    /// once.
+    const VALUE_BYTES: u64 = 8;
+    /// Size in bytes of the degree of a batch of shares of small degree.
+    const DEGREE_BYTES: u64 = 1;
+    /// Size in bytes of the header of a value in a packet: the version of the format, the ID of
+    /// its codec, the name of its field and its type tag.
+    const TYPE_TAG_BYTES: u64 = 14;
+    /// Size in bytes of the length of a vector in a packet.
+    const LENGTH_BYTES: u64 = 8;
+
+    /// Multiplies `n_pairs` pairs of random secrets shared among the parties of a local network,
+    /// and returns the traffic statistics of each party.
+    fn multiplication_traffic(
+        n_parties: usize,
+        threshold: usize,
+        n_pairs: usize,
+    ) -> Vec<NetworkStats> {
+        let mut rng = thread_rng();
+        let mut party_pairs = vec![Vec::new(); n_parties];
+        for _ in 0..n_pairs {
+            let a = compute_shamir_share(
+                &Mersenne61::random(&mut rng),
+                n_parties,
+                threshold,
+                &mut rng,
+            );
+            let b = compute_shamir_share(
+                &Mersenne61::random(&mut rng),
+                n_parties,
+                threshold,
+                &mut rng,
+            );
+            for (own, shares) in party_pairs.iter_mut().zip(a.into_iter().zip(b)) {
+                own.push(shares);
+            }
+        }
+        let handles: Vec<_> = Network::create_local(n_parties)
+            .into_iter()
+            .zip(party_pairs)
+            .map(|(mut network, own)| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    if let [(a, b)] = own.as_slice() {
+                        run_multiply_protocol(
+                            a,
+                            b,
+                            n_parties,
+                            threshold,
+                            &mut rng,
+                            &mut Engine::new(&mut network),
+                        )
+                        .unwrap();
+                    } else {
+                        run_batch_multiply_protocol(
+                            &own,
+                            n_parties,
+                            threshold,
+                            &mut rng,
+                            &mut Engine::new(&mut network),
+                        )
+                        .unwrap();
+                    }
+                    network.stats().clone()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    }
+
+    /// Bounds the bytes that a party sends to a peer to multiply `n_pairs` pairs: the shares of
+    /// the random secrets of the double sharings, one per `n - t` products with each degree, and
+    /// the masked products sent to the king, or the opened ones that the king sends back.
+    fn multiplication_bound(n_parties: usize, threshold: usize, n_pairs: usize) -> u64 {
+        let n_secrets = n_pairs.div_ceil(n_parties - threshold) as u64;
+        let batch = |n_values: u64| LENGTH_BYTES + DEGREE_BYTES + n_values * VALUE_BYTES;
+        2 * TYPE_TAG_BYTES + 2 * batch(n_secrets) + batch(n_pairs as u64)
+    }
+
+    #[test]
+    fn multiplication_communication_is_bounded() {
+        const N_PARTIES: usize = 5;
+        const THRESHOLD: usize = 2;
+
+        let bound = multiplication_bound(N_PARTIES, THRESHOLD, 1);
+        for stats in multiplication_traffic(N_PARTIES, THRESHOLD, 1) {
+            // An exchange of the double sharings, which the statistics count as a round each
+            // time the party sends after receiving from the previous peer, and the opening to
+            // the king.
+            assert!(
+                stats.rounds <= N_PARTIES as u64 + 1,
+                "{} rounds",
+                stats.rounds
+            );
+            for peer in &stats.peers {
+                assert!(peer.packets_sent <= 2);
+                assert!(peer.bytes_sent <= bound);
+                assert!(peer.bytes_received <= bound);
+            }
+        }
+    }
+
+    #[test]
+    fn batch_multiplication_communication_is_bounded() {
+        const N_PARTIES: usize = 5;
+        const THRESHOLD: usize = 2;
+
+        for n_pairs in [2, 8, 64] {
+            let bound = multiplication_bound(N_PARTIES, THRESHOLD, n_pairs);
+            for stats in multiplication_traffic(N_PARTIES, THRESHOLD, n_pairs) {
+                // The whole batch takes the rounds of a single multiplication.
+                assert!(
+                    stats.rounds <= N_PARTIES as u64 + 1,
+                    "{} rounds",
+                    stats.rounds
+                );
+                for peer in &stats.peers {
+                    assert!(peer.packets_sent <= 2);
+                    assert!(
+                        peer.bytes_sent <= bound,
+                        "{} bytes sent for {n_pairs} pairs",
+                        peer.bytes_sent
+                    );
+                    assert!(peer.bytes_received <= bound);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn multiplying_many_pairs_the_slices() {
+        const N_PARTIES: usize = 4;
+        const THRESHOLD: usize = 1;
+        const N_PRODUCTS: usize = 6;
+
+        let mut rng = thread_rng();
+        let a: Vec<Mersenne61> = (0..N_PRODUCTS)
+            .map(|_| Mersenne61::random(&mut rng))
+            .collect();
+        let b: Vec<Mersenne61> = (0..N_PRODUCTS)
+            .map(|_| Mersenne61::random(&mut rng))
+            .collect();
+        let a_shares = compute_shamir_shares_batch(&a, N_PARTIES, THRESHOLD, &mut rng);
+        let b_shares = compute_shamir_shares_batch(&b, N_PARTIES, THRESHOLD, &mut rng);
+
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(a_shares.into_iter().zip(b_shares))
+            .map(|(mut network, (a, b))| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    assert!(run_multiply_many(
+                        &a,
+                        &b[1..],
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network)
+                    )
+                    .is_err());
+                    run_multiply_many(
+                        &a,
+                        &b,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap()
+                })
+            })
+            .collect();
+        let shares: Vec<Vec<ShamirShare<Mersenne61>>> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        for k in 0..N_PRODUCTS {
+            let product = reconstruct_secret(shares.iter().map(|own| own[k].clone()).collect());
+            assert_eq!(product, a[k].multiply(&b[k]));
+        }
+    }
+
+    #[test]
+    fn batch_multiplication_for_random_parameters() {
+        const N_CASES: usize = 5;
+
+        let mut rng = thread_rng();
+        for _ in 0..N_CASES {
+            let (n_parties, threshold) = random_parameters(&mut rng, true);
+            let n_pairs = rng.gen_range(1..5);
+            let pairs: Vec<_> = (0..n_pairs)
+                .map(|_| (Mersenne61::random(&mut rng), Mersenne61::random(&mut rng)))
+                .collect();
+            let mut party_pairs: Vec<Vec<(ShamirShare<Mersenne61>, ShamirShare<Mersenne61>)>> =
+                vec![Vec::new(); n_parties];
+            for (a, b) in &pairs {
+                let a_shares = compute_shamir_share(a, n_parties, threshold, &mut rng);
+                let b_shares = compute_shamir_share(b, n_parties, threshold, &mut rng);
+                for (own, shares) in party_pairs
+                    .iter_mut()
+                    .zip(a_shares.into_iter().zip(b_shares))
+                {
+                    own.push(shares);
+                }
+            }
+
+            let handles: Vec<_> = Network::create_local(n_parties)
+                .into_iter()
+                .zip(party_pairs)
+                .map(|(mut network, own)| {
+                    thread::spawn(move || {
+                        run_batch_multiply_protocol(
+                            &own,
+                            n_parties,
+                            threshold,
+                            &mut thread_rng(),
+                            &mut Engine::new(&mut network),
+                        )
+                        .unwrap()
+                    })
+                })
+                .collect();
+            let mut product_shares: Vec<Vec<ShamirShare<Mersenne61>>> = vec![Vec::new(); n_pairs];
+            for handle in handles {
+                for (shares, share) in product_shares.iter_mut().zip(handle.join().unwrap()) {
+                    shares.push(share);
+                }
+            }
+
+            for ((a, b), shares) in pairs.iter().zip(product_shares) {
+                assert_eq!(
+                    reconstruct_secret(shares),
+                    a.multiply(b),
+                    "{n_parties} parties with threshold {threshold}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn secrets_are_wiped_after_sharing() {
+        let leaked = freed_copies(CANARY, || {
+            let secrets = vec![Mersenne61::from(CANARY); 3];
+            let shares = share_inputs(&secrets, 5, 2, &mut thread_rng());
+            drop(secrets);
+            drop(shares);
+        });
+        assert_eq!(leaked, 0);
+    }
+
+    #[test]
+    fn local_products_are_reduced_before_multiplying() {
+        const N_PARTIES: usize = 5;
+        const THRESHOLD: usize = 2;
+
+        let mut rng = thread_rng();
+        let values: Vec<Mersenne61> = (0..3).map(|_| Mersenne61::random(&mut rng)).collect();
+        let shares = share_inputs(&values, N_PARTIES, THRESHOLD, &mut rng);
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(shares)
+            .map(|(mut network, shares)| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    // The local product has degree 2 * t, so it is reduced before the product.
+                    let local = shares[0].multiply(&shares[1], N_PARTIES).unwrap();
+                    let pair = [(local.clone(), shares[2].clone())];
+                    let err = start_batch_multiply_protocol(
+                        &pair,
+                        THRESHOLD,
+                        &mut DoubleSharings::default(),
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap_err();
+                    assert_eq!(
+                        err.downcast_ref::<DegreeError>(),
+                        Some(&DegreeError::AboveThreshold {
+                            degree: 4,
+                            threshold: THRESHOLD
+                        })
+                    );
+                    let product = run_batch_multiply_protocol(
+                        &pair,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap()
+                    .remove(0);
+                    let reduced = run_degree_reduction_protocol(
+                        &[local, shares[2].clone()],
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap();
+                    (product, reduced)
+                })
+            })
+            .collect();
+        let (products, reduced): (Vec<_>, Vec<_>) = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .unzip();
+        assert!(products.iter().all(|share| share.degree == THRESHOLD));
+        assert_eq!(
+            reconstruct_secret(products),
+            values[0].multiply(&values[1]).multiply(&values[2])
+        );
+        let open = |k: usize| {
+            let shares: Vec<_> = reduced.iter().map(|shares| shares[k].clone()).collect();
+            assert!(shares.iter().all(|share| share.degree == THRESHOLD));
+            reconstruct_secret(shares)
+        };
+        assert_eq!(open(0), values[0].multiply(&values[1]));
+        assert_eq!(open(1), values[2]);
+
+        // The local products of two parties can not be reduced.
+        let mut network = Network::create_local(2).remove(0);
+        let share = ShamirShare::new(Mersenne61::ONE, 2);
+        let err =
+            run_degree_reduction_protocol(&[share], 2, 1, &mut rng, &mut Engine::new(&mut network))
+                .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DegreeError>(),
+            Some(&DegreeError::Irreducible {
+                degree: 2,
+                n_parties: 2
+            })
+        );
+    }
 }
@@ -0,0 +1,433 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use thiserror::Error;
+
+use super::{
+    reconstruct_secret_from_parties,
+    share::{BeaverTriple, ShamirShare},
+};
+use crate::math::{self, FieldValue, PrimeField};
+
+/// Type of the Shamir shares of a prime field in the headers of MP-SPDZ.
+pub const SHARE_TYPE: &str = "Shamir gfp";
+
+/// Number of 64-bit limbs of the elements of a prime field in MP-SPDZ when it is built with the
+/// default `GFP_MOD_SZ`.
+pub const DEFAULT_LIMBS: usize = 2;
+
+/// Possible errors that may appear while reading a file in the formats of MP-SPDZ.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MpSpdzError {
+    /// The file does not start with the header of MP-SPDZ.
+    #[error("the file does not start with a header of MP-SPDZ")]
+    MissingHeader,
+
+    /// The file holds values of another type than the Shamir shares of a prime field.
+    #[error("the file does not hold {SHARE_TYPE} shares")]
+    WrongType,
+
+    /// The file holds shares of a field with another prime.
+    #[error("the file holds shares modulo another prime than {0}")]
+    WrongPrime(String),
+
+    /// The length of the values does not fit a whole number of elements.
+    #[error("the file ends in the middle of an element of {0} bytes")]
+    Truncated(usize),
+
+    /// An element is not lower than the prime.
+    #[error("the element {0} is not reduced modulo the prime")]
+    NotReduced(usize),
+
+    /// The elements of the field do not fit in the limbs of the format.
+    #[error("the elements of {0} bits do not fit in {1} limbs")]
+    TooFewLimbs(usize, usize),
+
+    /// The number of elements is not a whole number of triples.
+    #[error("the file holds {0} shares, which do not form whole triples")]
+    IncompleteTriple(usize),
+}
+
+/// Format of the files of MP-SPDZ that hold shares of a prime field, such as the shares that
+/// persist between programs in `Persistence/Transactions-P<i>.data` and the Beaver triples of the
+/// preprocessing phase in `Player-Data/<n>-S-<bits>/Triples-S-P<i>`.
+///
+/// The file starts with a signature of the type of the shares, written as its length in 8 bytes
+/// in little-endian and its bytes, which are the type [`SHARE_TYPE`] followed by the prime as a
+/// big integer of MP-SPDZ: a sign byte, the number of bytes of its magnitude in 4 bytes in
+/// little-endian and the magnitude in big-endian. Then each element follows in Montgomery form,
+/// i.e. multiplied by `2^(64 * limbs)`, as its limbs of 64 bits in little-endian starting with
+/// the least significant one. A triple is written as the shares of `a`, `b` and `c` in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MpSpdzFormat {
+    /// Number of 64-bit limbs of each element.
+    limbs: usize,
+}
+
+impl Default for MpSpdzFormat {
+    fn default() -> Self {
+        Self::new(DEFAULT_LIMBS)
+    }
+}
+
+impl MpSpdzFormat {
+    /// Creates the format of an MP-SPDZ built with elements of the given number of 64-bit limbs,
+    /// which must be enough to hold the prime.
+    pub fn new(limbs: usize) -> Self {
+        Self { limbs }
+    }
+
+    /// Returns the number of bytes of each element.
+    fn element_size(&self) -> usize {
+        8 * self.limbs
+    }
+
+    /// Returns the Montgomery factor `2^(64 * limbs)` modulo the prime.
+    fn montgomery_factor<T: PrimeField>(&self) -> T {
+        let two_to_64 = math::from_u128::<T>(1 << 64);
+        math::pow(&two_to_64, self.limbs as u128)
+    }
+
+    /// Returns the signature of the type of the shares of the field `T`.
+    fn signature<T: PrimeField>() -> Vec<u8> {
+        let mut prime = vec![0; T::ValueType::BYTES];
+        T::MODULUS.write_le_bytes(&mut prime);
+        let magnitude: Vec<u8> = prime
+            .into_iter()
+            .rev()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        let mut signature = SHARE_TYPE.as_bytes().to_vec();
+        signature.push(0);
+        signature.extend((magnitude.len() as u32).to_le_bytes());
+        signature.extend(magnitude);
+        signature
+    }
+
+    /// Encodes the elements of the field `T` as the contents of a file, header included.
+    pub fn encode<T: PrimeField>(&self, elements: &[T]) -> Result<Vec<u8>, MpSpdzError> {
+        if 64 * self.limbs < T::BIT_SIZE {
+            return Err(MpSpdzError::TooFewLimbs(T::BIT_SIZE, self.limbs));
+        }
+        let signature = Self::signature::<T>();
+        let mut bytes =
+            Vec::with_capacity(8 + signature.len() + elements.len() * self.element_size());
+        bytes.extend((signature.len() as u64).to_le_bytes());
+        bytes.extend(signature);
+        let factor = self.montgomery_factor::<T>();
+        for element in elements {
+            let start = bytes.len();
+            bytes.resize(start + self.element_size(), 0);
+            element
+                .multiply(&factor)
+                .value()
+                .write_le_bytes(&mut bytes[start..]);
+        }
+        Ok(bytes)
+    }
+
+    /// Decodes the elements of the field `T` from the contents of a file, checking that its
+    /// header matches the field.
+    pub fn decode<T: PrimeField>(&self, bytes: &[u8]) -> Result<Vec<T>, MpSpdzError> {
+        let (length, rest) = bytes
+            .split_first_chunk::<8>()
+            .ok_or(MpSpdzError::MissingHeader)?;
+        let (signature, values) = usize::try_from(u64::from_le_bytes(*length))
+            .ok()
+            .filter(|length| *length <= rest.len())
+            .map(|length| rest.split_at(length))
+            .ok_or(MpSpdzError::MissingHeader)?;
+        if !signature.starts_with(SHARE_TYPE.as_bytes()) {
+            return Err(MpSpdzError::WrongType);
+        }
+        if signature != Self::signature::<T>() {
+            return Err(MpSpdzError::WrongPrime(T::MODULUS.to_string()));
+        }
+        if values.len() % self.element_size() != 0 {
+            return Err(MpSpdzError::Truncated(self.element_size()));
+        }
+
+        let inverse_factor = self
+            .montgomery_factor::<T>()
+            .inverse()
+            .expect("a power of two is not a multiple of an odd prime");
+        values
+            .chunks(self.element_size())
+            .enumerate()
+            .map(|(i, limbs)| {
+                let montgomery = T::ValueType::read_le_bytes(limbs)
+                    .and_then(T::from_value)
+                    .ok_or(MpSpdzError::NotReduced(i))?;
+                Ok(montgomery.multiply(&inverse_factor))
+            })
+            .collect()
+    }
+
+    /// Writes the shares to a file.
+    pub fn write_shares<T: PrimeField>(
+        &self,
+        path: &Path,
+        shares: &[ShamirShare<T>],
+    ) -> anyhow::Result<()> {
+        let values: Vec<T> = shares.iter().map(|share| share.value.clone()).collect();
+        fs::write(path, self.encode(&values)?)
+            .with_context(|| format!("writing the shares to {}", path.display()))
+    }
+
+    /// Reads the shares of a file, which MP-SPDZ writes without their degree, so it is given.
+    pub fn read_shares<T: PrimeField>(
+        &self,
+        path: &Path,
+        degree: usize,
+    ) -> anyhow::Result<Vec<ShamirShare<T>>> {
+        let bytes =
+            fs::read(path).with_context(|| format!("reading the shares of {}", path.display()))?;
+        let values: Vec<T> = self.decode(&bytes)?;
+        Ok(values
+            .into_iter()
+            .map(|value| ShamirShare::new(value, degree))
+            .collect())
+    }
+
+    /// Writes the shares of Beaver triples to a file.
+    pub fn write_triples<T: PrimeField>(
+        &self,
+        path: &Path,
+        triples: &[BeaverTriple<T>],
+    ) -> anyhow::Result<()> {
+        let shares: Vec<ShamirShare<T>> = triples
+            .iter()
+            .flat_map(|triple| [triple.a.clone(), triple.b.clone(), triple.c.clone()])
+            .collect();
+        self.write_shares(path, &shares)
+    }
+
+    /// Reads the shares of Beaver triples of a file, given the degree of the shares.
+    pub fn read_triples<T: PrimeField>(
+        &self,
+        path: &Path,
+        degree: usize,
+    ) -> anyhow::Result<Vec<BeaverTriple<T>>> {
+        let shares = self.read_shares(path, degree)?;
+        if shares.len() % 3 != 0 {
+            anyhow::bail!(MpSpdzError::IncompleteTriple(shares.len()));
+        }
+        let mut shares = shares.into_iter();
+        let mut triples = Vec::with_capacity(shares.len() / 3);
+        while let (Some(a), Some(b), Some(c)) = (shares.next(), shares.next(), shares.next()) {
+            triples.push(BeaverTriple { a, b, c });
+        }
+        Ok(triples)
+    }
+
+    /// Reconstructs the secrets of the shares in the files of several parties, which hold the
+    /// shares in the same order, given their degree. The ID of each party is read from the name
+    /// of its file, as in [`party_of_file`].
+    pub fn reconstruct_files<T: PrimeField>(
+        &self,
+        files: &[PathBuf],
+        degree: usize,
+    ) -> anyhow::Result<Vec<T>> {
+        let mut parties = Vec::with_capacity(files.len());
+        for path in files {
+            let party = party_of_file(path).ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the name of {path:?} does not end with the ID of a party"),
+                )
+            })?;
+            parties.push((party, self.read_shares::<T>(path, degree)?));
+        }
+        let count = parties.first().map_or(0, |(_, shares)| shares.len());
+        if parties.iter().any(|(_, shares)| shares.len() != count) {
+            anyhow::bail!("the files of the parties hold different numbers of shares");
+        }
+        (0..count)
+            .map(|i| {
+                let shares = parties
+                    .iter()
+                    .map(|(party, shares)| (*party, shares[i].clone()))
+                    .collect();
+                Ok(reconstruct_secret_from_parties(shares)?)
+            })
+            .collect()
+    }
+}
+
+/// Returns the ID of the party of a file of MP-SPDZ, which is the number after the last `P` of
+/// its name, e.g. in `Transactions-P1.data` or `Triples-S-P1`.
+pub fn party_of_file(path: &Path) -> Option<usize> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.rsplit_once('P'))
+        .and_then(|(_, id)| id.parse().ok())
+}
+
+/// Returns the name of the file in which MP-SPDZ persists the shares of a party.
+pub fn transactions_file(party: usize) -> String {
+    format!("Transactions-P{party}.data")
+}
+
+/// Returns the name of the file from which MP-SPDZ reads the Beaver triples of a party.
+pub fn triples_file(party: usize) -> String {
+    format!("Triples-S-P{party}")
+}
+
+/// Returns the directory, relative to `Player-Data`, from which MP-SPDZ reads the preprocessing
+/// of the Shamir shares of the field `T` among `n_parties`.
+pub fn preprocessing_dir<T: PrimeField>(n_parties: usize) -> String {
+    format!("{n_parties}-S-{}", T::BIT_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::thread_rng;
+
+    use std::path::Path;
+
+    use super::{party_of_file, preprocessing_dir, MpSpdzError, MpSpdzFormat};
+    use crate::{
+        math::{mersenne127::Mersenne127, mersenne61::Mersenne61, p128::P128, FiniteField},
+        mpc::{
+            self, deal_beaver_triples,
+            share::{BeaverTriple, ShamirShare},
+        },
+    };
+
+    #[test]
+    fn shares_of_several_files_are_reconstructed() {
+        let dir = std::env::temp_dir().join(format!("shami_rs_mpspdz_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let secrets = [Mersenne61::from(3), Mersenne61::from(14)];
+        let shares: Vec<_> = secrets
+            .iter()
+            .map(|secret| mpc::compute_shamir_share(secret, 3, 1, &mut thread_rng()))
+            .collect();
+        let format = MpSpdzFormat::new(2);
+        let files: Vec<_> = [2, 0]
+            .into_iter()
+            .map(|party| {
+                let path = dir.join(super::transactions_file(party));
+                let party_shares = [shares[0][party].clone(), shares[1][party].clone()];
+                format.write_shares(&path, &party_shares).unwrap();
+                path
+            })
+            .collect();
+        assert_eq!(
+            format.reconstruct_files::<Mersenne61>(&files, 1).unwrap(),
+            secrets
+        );
+
+        assert_eq!(party_of_file(Path::new("Triples-S-P12")), Some(12));
+        assert_eq!(party_of_file(Path::new("shares.data")), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn elements_are_written_in_montgomery_form() {
+        let bytes = MpSpdzFormat::new(1).encode(&[Mersenne61::ONE]).unwrap();
+        let mut expected = 23u64.to_le_bytes().to_vec();
+        expected.extend(b"Shamir gfp\x00\x08\x00\x00\x00\x1f\xff\xff\xff\xff\xff\xff\xff");
+        // 2^64 is 8 modulo 2^61 - 1.
+        expected.extend(8u64.to_le_bytes());
+        assert_eq!(bytes, expected);
+        assert_eq!(
+            MpSpdzFormat::new(1).decode::<Mersenne61>(&bytes),
+            Ok(vec![Mersenne61::ONE])
+        );
+
+        let elements: Vec<P128> = (0..10).map(|_| P128::random(&mut thread_rng())).collect();
+        for limbs in [2, 3] {
+            let format = MpSpdzFormat::new(limbs);
+            let bytes = format.encode(&elements).unwrap();
+            assert_eq!(format.decode(&bytes), Ok(elements.clone()));
+        }
+        assert_eq!(
+            MpSpdzFormat::new(1).encode(&elements),
+            Err(MpSpdzError::TooFewLimbs(128, 1))
+        );
+        assert_eq!(preprocessing_dir::<P128>(3), "3-S-128");
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        let format = MpSpdzFormat::default();
+        let bytes = format.encode(&[Mersenne61::from(5)]).unwrap();
+        assert_eq!(
+            format.decode::<Mersenne127>(&bytes),
+            Err(MpSpdzError::WrongPrime(Mersenne127::MODULUS.to_string()))
+        );
+        assert_eq!(
+            format.decode::<Mersenne61>(&bytes[..4]),
+            Err(MpSpdzError::MissingHeader)
+        );
+        assert_eq!(
+            format.decode::<Mersenne61>(&bytes[..bytes.len() - 1]),
+            Err(MpSpdzError::Truncated(16))
+        );
+        let mut not_reduced = bytes.clone();
+        let len = not_reduced.len();
+        not_reduced[len - 16..].fill(0xff);
+        assert_eq!(
+            format.decode::<Mersenne61>(&not_reduced),
+            Err(MpSpdzError::NotReduced(0))
+        );
+        let mut other_type = bytes;
+        other_type[8] = b's';
+        assert_eq!(
+            format.decode::<Mersenne61>(&other_type),
+            Err(MpSpdzError::WrongType)
+        );
+    }
+
+    #[test]
+    fn triples_survive_a_round_trip() {
+        let dir = std::env::temp_dir().join(format!("shami_rs_mpspdz_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let triples = deal_beaver_triples::<Mersenne61, _>(4, 3, 1, &mut thread_rng(), None);
+        let format = MpSpdzFormat::default();
+        for (party, party_triples) in triples.iter().enumerate() {
+            format
+                .write_triples(&dir.join(super::triples_file(party)), party_triples)
+                .unwrap();
+        }
+
+        let read: Vec<Vec<_>> = (0..3)
+            .map(|party| {
+                format
+                    .read_triples::<Mersenne61>(&dir.join(super::triples_file(party)), 1)
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(read, triples);
+        for i in 0..4 {
+            let open = |select: fn(&BeaverTriple<Mersenne61>) -> &ShamirShare<Mersenne61>| {
+                let shares = read
+                    .iter()
+                    .enumerate()
+                    .map(|(party, triples)| (party, select(&triples[i]).clone()));
+                mpc::reconstruct_secret_from_parties(shares.collect()).unwrap()
+            };
+            let a = open(|triple| &triple.a);
+            let b = open(|triple| &triple.b);
+            assert_eq!(open(|triple| &triple.c), a.multiply(&b));
+        }
+
+        fs::write(
+            dir.join("incomplete"),
+            format.encode(&[Mersenne61::ONE, Mersenne61::ONE]).unwrap(),
+        )
+        .unwrap();
+        let err = format
+            .read_triples::<Mersenne61>(&dir.join("incomplete"), 1)
+            .unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&MpSpdzError::IncompleteTriple(2)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
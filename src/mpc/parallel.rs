@@ -0,0 +1,111 @@
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Number of items of the chunks in which the local computations are split. It does not depend on
+/// the number of threads, so the computations that draw randomness per chunk give the same
+/// results with any number of threads.
+pub const CHUNK_SIZE: usize = 256;
+
+/// Number of threads of the local computations, where zero stands for one per core.
+static NUM_THREADS: AtomicUsize = AtomicUsize::new(1);
+
+/// Sets the number of threads among which the local computations are split, such as the sharing
+/// of many secrets or the recombination of a batch of products. Zero uses one thread per core.
+/// The computations run in the calling thread by default.
+pub fn set_num_threads(num_threads: usize) {
+    NUM_THREADS.store(num_threads, Ordering::Relaxed);
+}
+
+/// Returns the number of threads among which the local computations are split. Without the
+/// `native` feature, e.g. in WebAssembly, they always run in the calling thread.
+pub fn num_threads() -> usize {
+    #[cfg(feature = "native")]
+    match NUM_THREADS.load(Ordering::Relaxed) {
+        0 => std::thread::available_parallelism().map_or(1, usize::from),
+        num_threads => num_threads,
+    }
+    #[cfg(not(feature = "native"))]
+    1
+}
+
+/// Splits the range `0..n_items` into chunks of `chunk_size` items and applies the function to
+/// every chunk, returning the results in the order of the chunks. The chunks are distributed among
+/// [`num_threads`] scoped threads, unless there is a single chunk or a single thread.
+pub fn map_chunks<O, F>(n_items: usize, chunk_size: usize, f: F) -> Vec<O>
+where
+    O: Send,
+    F: Fn(usize, Range<usize>) -> O + Sync,
+{
+    let chunk_size = chunk_size.max(1);
+    let chunks: Vec<Range<usize>> = (0..n_items)
+        .step_by(chunk_size)
+        .map(|start| start..n_items.min(start + chunk_size))
+        .collect();
+    #[cfg(feature = "native")]
+    {
+        let num_threads = num_threads().min(chunks.len());
+        if num_threads > 1 {
+            return map_in_threads(chunks, num_threads, &f);
+        }
+    }
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| f(index, chunk))
+        .collect()
+}
+
+/// Applies the function to the chunks in `num_threads` scoped threads, each of which takes a
+/// contiguous group of chunks.
+#[cfg(feature = "native")]
+fn map_in_threads<O, F>(chunks: Vec<Range<usize>>, num_threads: usize, f: &F) -> Vec<O>
+where
+    O: Send,
+    F: Fn(usize, Range<usize>) -> O + Sync,
+{
+    let chunks: Vec<(usize, Range<usize>)> = chunks.into_iter().enumerate().collect();
+    let per_thread = chunks.len().div_ceil(num_threads);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .chunks(per_thread)
+            .map(|group| {
+                scope.spawn(move || {
+                    group
+                        .iter()
+                        .map(|(index, chunk)| f(*index, chunk.clone()))
+                        .collect::<Vec<O>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{map_chunks, set_num_threads};
+
+    #[test]
+    fn chunks_are_mapped_in_order() {
+        let sums = |n_items| {
+            map_chunks(n_items, 4, |index, chunk| {
+                (index, chunk.clone().count(), chunk.sum::<usize>())
+            })
+        };
+        let expected = vec![(0, 4, 6), (1, 4, 22), (2, 2, 17)];
+        assert_eq!(sums(10), expected);
+        set_num_threads(3);
+        assert_eq!(sums(10), expected);
+        assert_eq!(sums(0), []);
+        set_num_threads(1);
+    }
+}
@@ -0,0 +1,308 @@
+use std::marker::PhantomData;
+
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use super::{
+    circuit::{Circuit, Gate, Wire},
+    engine::Engine,
+    share::ShamirShare,
+};
+use crate::{
+    math::{permutation_exponent, FiniteField},
+    transport::Transport,
+};
+
+/// Number of elements of the state of the permutation.
+const WIDTH: usize = 3;
+
+/// Number of elements of the state absorbed at a time by the sponge, which leaves one element as
+/// the capacity.
+const RATE: usize = WIDTH - 1;
+
+/// Number of full rounds of the permutation, half of them before the partial rounds and half
+/// after them.
+const FULL_ROUNDS: usize = 8;
+
+/// Seed of the generator of the round constants, so that every party derives the same constants
+/// without agreeing on them.
+const CONSTANTS_SEED: [u8; 32] = *b"shami-rs Poseidon constants\0\0\0\0\0";
+
+/// State of the permutation.
+type State<T> = [T; WIDTH];
+
+/// The Poseidon hash over a field, a sponge over a permutation of a state of three elements with
+/// a rate of two. Each round adds a constant to every element, applies the S-box `x^d` to every
+/// element in the full rounds and only to the first one in the partial rounds, and multiplies the
+/// state by the MDS matrix `circ(2, 1, 1)`, which only takes additions. The exponent `d` is the
+/// smallest odd exponent coprime with `p - 1`, and the partial rounds follow the interpolation
+/// bound of the Poseidon paper, `ceil(log_d(p)) + ceil(log_d(3))`, with a margin of 7.5%.
+///
+/// Since the S-boxes are the only products, the hash is cheap to evaluate on shares, e.g. to
+/// commit to shared data. As with [`Mimc`](super::mimc::Mimc), the round constants are derived
+/// from a fixed seed and are below `2^64`, so the instances are not the reference instances
+/// generated with the Grain LFSR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Poseidon<T> {
+    /// Exponent of the S-box.
+    exponent: u128,
+    /// Number of partial rounds.
+    partial_rounds: usize,
+    /// Constants added to the state in each round.
+    round_constants: Vec<[u64; WIDTH]>,
+    /// Field of the hash.
+    field: PhantomData<fn() -> T>,
+}
+
+impl<T: FiniteField> Default for Poseidon<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: FiniteField> Poseidon<T> {
+    /// Returns the hash over the field `T`.
+    pub fn new() -> Self {
+        let exponent = permutation_exponent::<T>();
+        let log_exponent = (exponent as f64).log2();
+        let bound = (T::BIT_SIZE as f64 / log_exponent).ceil()
+            + ((WIDTH as f64).log2() / log_exponent).ceil();
+        let partial_rounds = (bound * 1.075).ceil() as usize;
+        let mut rng = ChaCha20Rng::from_seed(CONSTANTS_SEED);
+        let round_constants = (0..FULL_ROUNDS + partial_rounds)
+            .map(|_| [rng.next_u64(), rng.next_u64(), rng.next_u64()])
+            .collect();
+        Self {
+            exponent,
+            partial_rounds,
+            round_constants,
+            field: PhantomData,
+        }
+    }
+
+    /// Returns the exponent of the S-box.
+    pub fn exponent(&self) -> u128 {
+        self.exponent
+    }
+
+    /// Returns the number of partial rounds of the permutation.
+    pub fn partial_rounds(&self) -> usize {
+        self.partial_rounds
+    }
+
+    /// Tells whether a round of the permutation is a full round.
+    fn is_full_round(&self, round: usize) -> bool {
+        round < FULL_ROUNDS / 2 || round >= FULL_ROUNDS / 2 + self.partial_rounds
+    }
+
+    /// Applies the permutation to a state in the clear.
+    pub fn permute(&self, state: &mut State<T>) {
+        for (round, constants) in self.round_constants.iter().enumerate() {
+            for (element, constant) in state.iter_mut().zip(constants) {
+                *element = element.add(&T::from(*constant));
+            }
+            let n_sboxes = if self.is_full_round(round) { WIDTH } else { 1 };
+            for element in &mut state[..n_sboxes] {
+                *element = element.pow(self.exponent);
+            }
+            // The product by circ(2, 1, 1) adds the sum of the state to every element.
+            let sum = state[0].add(&state[1]).add(&state[2]);
+            for element in state.iter_mut() {
+                *element = element.add(&sum);
+            }
+        }
+    }
+
+    /// Hashes a list of field elements in the clear. The capacity starts at the number of
+    /// elements, so lists that only differ in trailing zeros have different hashes, and the last
+    /// block is padded with zeros.
+    pub fn hash(&self, inputs: &[T]) -> T {
+        let mut state = [T::from(inputs.len() as u64), T::ZERO, T::ZERO];
+        for block in inputs.chunks(RATE) {
+            for (element, input) in state[1..].iter_mut().zip(block) {
+                *element = element.add(input);
+            }
+            self.permute(&mut state);
+        }
+        if inputs.is_empty() {
+            self.permute(&mut state);
+        }
+        state[1].clone()
+    }
+
+    /// Appends to a circuit the gates of the permutation of the state held in the given wires,
+    /// returning the wires of the permuted state.
+    pub fn push_permutation(
+        &self,
+        circuit: &mut Circuit,
+        mut state: [Wire; WIDTH],
+    ) -> [Wire; WIDTH] {
+        for (round, constants) in self.round_constants.iter().enumerate() {
+            for (element, constant) in state.iter_mut().zip(constants) {
+                let constant = circuit.push(Gate::Constant(*constant));
+                *element = circuit.push(Gate::Add(*element, constant));
+            }
+            let n_sboxes = if self.is_full_round(round) { WIDTH } else { 1 };
+            for element in &mut state[..n_sboxes] {
+                *element = circuit.push_power(*element, self.exponent);
+            }
+            let sum = circuit.push(Gate::Add(state[0], state[1]));
+            let sum = circuit.push(Gate::Add(sum, state[2]));
+            for element in &mut state {
+                *element = circuit.push(Gate::Add(*element, sum));
+            }
+        }
+        state
+    }
+
+    /// Appends to a circuit the gates that hash the values of the given wires as in
+    /// [`Poseidon::hash`], returning the wire of the hash.
+    pub fn push_hash(&self, circuit: &mut Circuit, inputs: &[Wire]) -> Wire {
+        let length = circuit.push(Gate::Constant(inputs.len() as u64));
+        let zero = circuit.push(Gate::Constant(0));
+        let mut state = [length, zero, zero];
+        for block in inputs.chunks(RATE) {
+            for (element, input) in state[1..].iter_mut().zip(block) {
+                *element = circuit.push(Gate::Add(*element, *input));
+            }
+            state = self.push_permutation(circuit, state);
+        }
+        if inputs.is_empty() {
+            state = self.push_permutation(circuit, state);
+        }
+        state[1]
+    }
+
+    /// Returns the circuit that hashes the values computed by the given gates, e.g. inputs of the
+    /// parties or public constants.
+    pub fn circuit(&self, inputs: &[Gate]) -> Circuit {
+        let mut circuit = Circuit::default();
+        let wires: Vec<Wire> = inputs
+            .iter()
+            .map(|input| circuit.push(input.clone()))
+            .collect();
+        let hash = self.push_hash(&mut circuit, &wires);
+        // The output of a circuit is its last gate, and the hash is not the last element of the
+        // state that the permutation computes.
+        let zero = circuit.push(Gate::Constant(0));
+        circuit.push(Gate::Add(hash, zero));
+        circuit
+    }
+
+    /// Hashes a list of shared values, returning the share of the hash. The values stay shared
+    /// during the whole evaluation, so the parties can, e.g., open a commitment to shared data
+    /// without opening the data. Every party must call it with its shares of the same values.
+    pub fn hash_shared<R, N>(
+        &self,
+        inputs: &[ShamirShare<T>],
+        threshold: usize,
+        rng: &mut R,
+        engine: &mut Engine<N>,
+    ) -> anyhow::Result<ShamirShare<T>>
+    where
+        R: Rng + CryptoRng,
+        N: Transport,
+    {
+        let gates: Vec<Gate> = (0..inputs.len())
+            .map(|index| Gate::Input { party: 0, index })
+            .collect();
+        // The circuit takes the number of parties from the inputs, so the other parties are
+        // given no inputs.
+        let mut shares = vec![Vec::new(); engine.n_parties()];
+        shares[0] = inputs.to_vec();
+        self.circuit(&gates)
+            .evaluate(&shares, threshold, rng, engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::{rngs::StdRng, thread_rng, SeedableRng};
+
+    use super::Poseidon;
+    use crate::{
+        math::{mersenne127::Mersenne127, mersenne61::Mersenne61, p128::P128, FiniteField},
+        mpc::{circuit::Gate, compute_shamir_share, engine::Engine, reconstruct_secret},
+        net::Network,
+    };
+
+    /// Checks that the circuit of the hash computes the same hashes as the hash in the clear for
+    /// every length up to a few blocks, and that the hashes of different lists differ.
+    fn circuit_matches_hash<T: FiniteField + PartialEq>() {
+        let poseidon = Poseidon::<T>::new();
+        let mut rng = StdRng::seed_from_u64(1731);
+        let mut hashes = Vec::new();
+        for len in 0..6 {
+            let inputs: Vec<T> = (0..len).map(|_| T::random(&mut rng)).collect();
+            let gates: Vec<Gate> = (0..len)
+                .map(|index| Gate::Input { party: 0, index })
+                .collect();
+            let hash = poseidon.hash(&inputs);
+            assert_eq!(
+                poseidon.circuit(&gates).evaluate_plain(&[inputs]).unwrap(),
+                hash
+            );
+            hashes.push(hash);
+        }
+        hashes.push(poseidon.hash(&[T::ZERO]));
+        hashes.push(poseidon.hash(&[T::ZERO, T::ZERO]));
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(hashes[i + 1..].iter().all(|other| other != hash));
+        }
+    }
+
+    #[test]
+    fn hash_parameters_and_circuits() {
+        assert_eq!(Poseidon::<Mersenne61>::new().exponent(), 17);
+        assert_eq!(Poseidon::<Mersenne61>::new().partial_rounds(), 18);
+        assert_eq!(Poseidon::<Mersenne127>::new().exponent(), 5);
+        assert_eq!(Poseidon::<Mersenne127>::new().partial_rounds(), 61);
+        // Each round takes the depth of one S-box, which is five products for x^17.
+        let gates = [Gate::Input { party: 0, index: 0 }];
+        let circuit = Poseidon::<Mersenne61>::new().circuit(&gates);
+        assert_eq!(circuit.n_rounds(), (8 + 18) * 5);
+
+        circuit_matches_hash::<Mersenne61>();
+        circuit_matches_hash::<Mersenne127>();
+        circuit_matches_hash::<P128>();
+    }
+
+    #[test]
+    fn hash_shared_values() {
+        const N_PARTIES: usize = 3;
+        const THRESHOLD: usize = 1;
+
+        let mut rng = thread_rng();
+        let values: Vec<Mersenne61> = (0..3).map(|_| Mersenne61::random(&mut rng)).collect();
+        let shares: Vec<_> = values
+            .iter()
+            .map(|value| compute_shamir_share(value, N_PARTIES, THRESHOLD, &mut rng))
+            .collect();
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .map(|mut network| {
+                let inputs: Vec<_> = shares
+                    .iter()
+                    .map(|shares| shares[network.id()].clone())
+                    .collect();
+                thread::spawn(move || {
+                    Poseidon::new()
+                        .hash_shared(
+                            &inputs,
+                            THRESHOLD,
+                            &mut thread_rng(),
+                            &mut Engine::new(&mut network),
+                        )
+                        .unwrap()
+                })
+            })
+            .collect();
+        let hash = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        assert_eq!(reconstruct_secret(hash), Poseidon::new().hash(&values));
+    }
+}
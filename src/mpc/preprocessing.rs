@@ -0,0 +1,180 @@
+use rand::{CryptoRng, Rng};
+
+use super::{
+    check_threshold,
+    engine::Engine,
+    run_batch_multiply_protocol,
+    share::{BeaverTriple, ShamirShare, ShareBatch},
+    share_inputs, two_party,
+};
+use crate::{math::FiniteField, transport::Transport};
+
+/// Generates the shares of `count` random Beaver triples together with the other parties, which
+/// replaces the trusted dealer of [`super::deal_beaver_triples`] in the offline phase of a
+/// computation, before the inputs are known. Every party shares random values for the factors
+/// `a` and `b` of every triple, whose sums are unknown to any coalition of up to `threshold`
+/// parties, and the products `c = a * b` are computed with [`run_batch_multiply_protocol`]. The
+/// generation takes a constant number of rounds regardless of the number of triples, and needs
+/// an honest majority, so two parties keep taking their triples from a dealer.
+///
+/// The triples are consumed in the online phase by loading them into the
+/// [`two_party::TripleStore`] of the engine, after which every product takes a single round in
+/// which the parties open the factors masked with a triple.
+pub fn generate_triples<T, R, N>(
+    count: usize,
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<BeaverTriple<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    check_threshold(n_parties, threshold, true)?;
+    anyhow::ensure!(
+        !two_party::is_two_party(n_parties, threshold),
+        "two parties can not generate Beaver triples without a dealer"
+    );
+
+    // The random values of the factors of every triple are shared with every party at once.
+    let randoms: Vec<T> = (0..2 * count).map(|_| T::random(rng)).collect();
+    let own_batches: Vec<ShareBatch<T>> = share_inputs(&randoms, n_parties, threshold, rng)
+        .into_iter()
+        .map(|shares| ShareBatch {
+            degree: threshold,
+            values: shares.into_iter().map(|share| share.value).collect(),
+        })
+        .collect();
+    log::info!("sharing the random factors of {count} Beaver triples");
+    let batches: Vec<ShareBatch<T>> = engine.exchange_values(&own_batches)?;
+    for (party, batch) in batches.iter().enumerate() {
+        if batch.values.len() != randoms.len() {
+            anyhow::bail!(
+                "party {party} sent the shares of {} random values, but {} were expected",
+                batch.values.len(),
+                randoms.len()
+            );
+        }
+        if batch.degree != threshold {
+            anyhow::bail!(
+                "party {party} sent shares of degree {}, but {threshold} was expected",
+                batch.degree
+            );
+        }
+    }
+    let factors: Vec<ShamirShare<T>> = (0..randoms.len())
+        .map(|k| {
+            let sum = batches
+                .iter()
+                .fold(T::ZERO, |sum, batch| sum.add(&batch.values[k]));
+            ShamirShare::new(sum, threshold)
+        })
+        .collect();
+    let pairs: Vec<(ShamirShare<T>, ShamirShare<T>)> = factors[..count]
+        .iter()
+        .cloned()
+        .zip(factors[count..].iter().cloned())
+        .collect();
+
+    log::info!("multiplying the factors of {count} Beaver triples");
+    let products = run_batch_multiply_protocol(&pairs, n_parties, threshold, rng, engine)?;
+    Ok(pairs
+        .into_iter()
+        .zip(products)
+        .map(|((a, b), c)| BeaverTriple { a, b, c })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::generate_triples;
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{
+            compute_shamir_share,
+            engine::Engine,
+            reconstruct_secret, run_batch_multiply_protocol,
+            share::BeaverTriple,
+            two_party::{TripleStore, TwoPartyError},
+        },
+        net::Network,
+    };
+
+    #[test]
+    fn generated_triples_multiply_online() {
+        const N_PARTIES: usize = 5;
+        const THRESHOLD: usize = 2;
+        const COUNT: usize = 4;
+
+        let mut rng = thread_rng();
+        let x = Mersenne61::random(&mut rng);
+        let y = Mersenne61::random(&mut rng);
+        let x_shares = compute_shamir_share(&x, N_PARTIES, THRESHOLD, &mut rng);
+        let y_shares = compute_shamir_share(&y, N_PARTIES, THRESHOLD, &mut rng);
+
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(x_shares.into_iter().zip(y_shares))
+            .map(|(network, (x_share, y_share))| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let mut engine = Engine::new(network);
+                    // Offline phase.
+                    let triples: Vec<BeaverTriple<Mersenne61>> =
+                        generate_triples(COUNT, N_PARTIES, THRESHOLD, &mut rng, &mut engine)
+                            .unwrap();
+
+                    // Online phase, which consumes the triples in order.
+                    engine.set_triples(TripleStore::new(&triples[1..]).unwrap());
+                    let pairs = vec![(x_share.clone(), y_share.clone()); COUNT - 1];
+                    let products = run_batch_multiply_protocol(
+                        &pairs,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut engine,
+                    )
+                    .unwrap();
+                    assert_eq!(engine.triples().remaining(), 0);
+                    let exhausted = run_batch_multiply_protocol(
+                        &pairs[..1],
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut engine,
+                    )
+                    .unwrap_err();
+                    assert!(matches!(
+                        exhausted.downcast_ref(),
+                        Some(TwoPartyError::NotEnoughTriples { .. })
+                    ));
+                    (triples[0].clone(), products)
+                })
+            })
+            .collect();
+        let (triples, products): (Vec<_>, Vec<_>) = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .unzip();
+
+        let a = reconstruct_secret(triples.iter().map(|triple| triple.a.clone()).collect());
+        let b = reconstruct_secret(triples.iter().map(|triple| triple.b.clone()).collect());
+        let c = reconstruct_secret(triples.iter().map(|triple| triple.c.clone()).collect());
+        assert_eq!(c, a.multiply(&b));
+        for k in 0..COUNT - 1 {
+            let product = reconstruct_secret(
+                products
+                    .iter()
+                    .map(|shares: &Vec<_>| shares[k].clone())
+                    .collect(),
+            );
+            assert_eq!(product, x.multiply(&y));
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use std::{
+    fmt,
+    io::{BufRead, Write},
+    str::FromStr,
+};
+
+use rand::{CryptoRng, Rng};
+use thiserror::Error;
+
+use super::{
+    engine::Engine, reconstruct_secret, run_batch_multiply_protocol, share::ShamirShare,
+    share_inputs, store::ShareStore,
+};
+use crate::{
+    math::{
+        fixed_point::{Decimal, FixedPointError},
+        FiniteField, PrimeField,
+    },
+    net::Network,
+    transport::Transport,
+};
+
+/// Help message of the REPL.
+const HELP: &str = "\
+commands:
+  input <value>          share a private input, which every party contributes at once
+  [<name> =] add <a> <b> add two values
+  [<name> =] sub <a> <b> subtract two values
+  [<name> =] mul <a> <b> multiply two values
+  open <a>               reveal a value to every party
+  vars                   list the variables
+  help                   print this message
+  quit                   leave the session
+the operands are variables, constants, or the input `k` of party `i` written as `xi[k]`";
+
+/// Possible errors that may appear while parsing or executing a command of the REPL.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ReplError {
+    /// The command is not known or does not have the expected arguments.
+    #[error("the command is not correct, type `help` to list the commands")]
+    InvalidCommand,
+
+    /// The value of an input is not a number.
+    #[error(transparent)]
+    InvalidInput(#[from] FixedPointError),
+
+    /// A variable can not be named as an input or a constant.
+    #[error("the name {0} is not a valid variable name")]
+    InvalidName(String),
+
+    /// The operand does not refer to an existing value.
+    #[error("the value {0} does not exist")]
+    UnknownOperand(String),
+
+    /// The parties issued different commands.
+    #[error("the parties issued different commands: {0:?}")]
+    Diverged(Vec<String>),
+}
+
+/// Operand of a command of the REPL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    /// Variable holding a shared value.
+    Variable(String),
+    /// Input at the given index of the party with the given ID.
+    Input { party: usize, index: usize },
+    /// Public constant.
+    Constant(u64),
+}
+
+impl FromStr for Operand {
+    type Err = ReplError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(constant) = s.parse() {
+            return Ok(Self::Constant(constant));
+        }
+        if let Some(input) = s.strip_prefix('x') {
+            let (party, index) = match input.strip_suffix(']').and_then(|s| s.split_once('[')) {
+                Some((party, index)) => (party, index),
+                None => (input, "0"),
+            };
+            if let (Ok(party), Ok(index)) = (party.parse(), index.parse()) {
+                return Ok(Self::Input { party, index });
+            }
+        }
+        let is_name = s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if is_name {
+            Ok(Self::Variable(s.to_string()))
+        } else {
+            Err(ReplError::InvalidName(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Variable(name) => write!(f, "{name}"),
+            Self::Input { party, index } => write!(f, "x{party}[{index}]"),
+            Self::Constant(constant) => write!(f, "{constant}"),
+        }
+    }
+}
+
+/// Operation between two values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Sum of the values.
+    Add,
+    /// Difference of the values.
+    Subtract,
+    /// Product of the values.
+    Multiply,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Add => write!(f, "add"),
+            Self::Subtract => write!(f, "sub"),
+            Self::Multiply => write!(f, "mul"),
+        }
+    }
+}
+
+/// Command issued to the REPL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplCommand {
+    /// Shares a private input.
+    Input(Decimal),
+    /// Computes an operation and stores the result in a variable, which gets a fresh name if it
+    /// is not given.
+    Compute {
+        name: Option<String>,
+        operation: Operation,
+        left: Operand,
+        right: Operand,
+    },
+    /// Reveals a value to every party.
+    Open(Operand),
+    /// Lists the variables.
+    Vars,
+    /// Prints the help message.
+    Help,
+    /// Leaves the session.
+    Quit,
+}
+
+impl ReplCommand {
+    /// Returns the command as it is announced to the other parties, without the private
+    /// values, or `None` if the command does not involve the other parties.
+    fn public_form(&self) -> Option<String> {
+        match self {
+            Self::Input(_) => Some("input".to_string()),
+            Self::Compute {
+                name,
+                operation,
+                left,
+                right,
+            } => Some(match name {
+                Some(name) => format!("{name} = {operation} {left} {right}"),
+                None => format!("{operation} {left} {right}"),
+            }),
+            Self::Open(operand) => Some(format!("open {operand}")),
+            Self::Vars | Self::Help | Self::Quit => None,
+        }
+    }
+}
+
+impl FromStr for ReplCommand {
+    type Err = ReplError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens: Vec<&str> = s.split_whitespace().collect();
+        let name = match tokens.as_slice() {
+            [name, "=", ..] => {
+                let name = match name.parse()? {
+                    Operand::Variable(name) => name,
+                    _ => return Err(ReplError::InvalidName(name.to_string())),
+                };
+                tokens.drain(..2);
+                Some(name)
+            }
+            _ => None,
+        };
+        let operation = |tokens: &[&str]| match tokens {
+            ["add", ..] => Some(Operation::Add),
+            ["sub", ..] => Some(Operation::Subtract),
+            ["mul", ..] => Some(Operation::Multiply),
+            _ => None,
+        };
+        match (name, tokens.as_slice()) {
+            (name, [_, left, right]) if operation(&tokens).is_some() => Ok(Self::Compute {
+                name,
+                operation: operation(&tokens).ok_or(ReplError::InvalidCommand)?,
+                left: left.parse()?,
+                right: right.parse()?,
+            }),
+            (None, ["input", value]) => Ok(Self::Input(value.parse()?)),
+            (None, ["open", operand]) => Ok(Self::Open(operand.parse()?)),
+            (None, ["vars"]) => Ok(Self::Vars),
+            (None, ["help"]) => Ok(Self::Help),
+            (None, ["quit" | "exit"]) => Ok(Self::Quit),
+            _ => Err(ReplError::InvalidCommand),
+        }
+    }
+}
+
+/// Interactive session in which the operators of the parties issue the same commands, in the
+/// same order, to share inputs, compute on the shared values and open them. Before executing a
+/// command that involves the other parties, every party announces the command without its
+/// private values, and the command is rejected by all of them if they do not agree.
+pub struct Repl<'a, T: FiniteField, R: Rng + CryptoRng> {
+    /// Network with the other parties.
+    engine: &'a mut Engine<Network>,
+    /// Degree of the sharing polynomials.
+    threshold: usize,
+    /// Random generator to share the values.
+    rng: &'a mut R,
+    /// Shares of the inputs of each party.
+    inputs: Vec<Vec<ShamirShare<T>>>,
+    /// Shares of the values of the variables, in the scope of the session of the network.
+    variables: ShareStore<T>,
+}
+
+impl<'a, T: PrimeField, R: Rng + CryptoRng> Repl<'a, T, R> {
+    /// Creates a REPL over the given network.
+    pub fn new(engine: &'a mut Engine<Network>, threshold: usize, rng: &'a mut R) -> Self {
+        let n_parties = engine.n_parties();
+        Self {
+            engine,
+            threshold,
+            rng,
+            inputs: vec![Vec::new(); n_parties],
+            variables: ShareStore::new(),
+        }
+    }
+
+    /// Reads commands from the input until it ends or a `quit` command is issued, writing a
+    /// prompt before each command and the outcome of the command after it. The errors of the
+    /// commands are written to the output, while the errors of the network end the session.
+    pub fn run<I: BufRead, O: Write>(&mut self, input: I, mut output: O) -> anyhow::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            write!(output, "> ")?;
+            output.flush()?;
+            let Some(line) = lines.next().transpose()? else {
+                return Ok(());
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let command = match line.parse::<ReplCommand>() {
+                Ok(ReplCommand::Quit) => return Ok(()),
+                Ok(command) => command,
+                Err(err) => {
+                    writeln!(output, "error: {err}")?;
+                    continue;
+                }
+            };
+            match self.execute(&command) {
+                Ok(message) => writeln!(output, "{message}")?,
+                Err(err) => match err.downcast::<ReplError>() {
+                    Ok(err) => writeln!(output, "error: {err}")?,
+                    Err(err) => return Err(err),
+                },
+            }
+        }
+    }
+
+    /// Executes a command, returning the message for the operator.
+    pub fn execute(&mut self, command: &ReplCommand) -> anyhow::Result<String> {
+        if let Some(public_form) = command.public_form() {
+            let commands: Vec<String> = self.engine.broadcast_and_gather_values(&public_form)?;
+            if commands.iter().any(|other| *other != public_form) {
+                anyhow::bail!(ReplError::Diverged(commands));
+            }
+        }
+        match command {
+            ReplCommand::Input(value) => {
+                let value: T = value.encode(0).map_err(ReplError::from)?;
+                let n_parties = self.engine.n_parties();
+                let own_shares = share_inputs(&[value], n_parties, self.threshold, self.rng);
+                let shares: Vec<Vec<ShamirShare<T>>> = self.engine.exchange_values(&own_shares)?;
+                let mut names = Vec::with_capacity(n_parties);
+                for (party, shares) in shares.into_iter().enumerate() {
+                    names.push(format!("x{party}[{}]", self.inputs[party].len()));
+                    self.inputs[party].extend(shares);
+                }
+                Ok(format!("shared the inputs {}", names.join(", ")))
+            }
+            ReplCommand::Compute {
+                name,
+                operation,
+                left,
+                right,
+            } => {
+                let (left, right) = (self.value(left)?, self.value(right)?);
+                let result = match operation {
+                    Operation::Add => left.add(&right),
+                    Operation::Subtract => left.subtract(&right),
+                    // The products by public constants do not need interaction.
+                    Operation::Multiply if left.degree == 0 || right.degree == 0 => {
+                        left.multiply(&right, self.engine.n_parties())?
+                    }
+                    Operation::Multiply => run_batch_multiply_protocol(
+                        &[(left, right)],
+                        self.engine.n_parties(),
+                        self.threshold,
+                        self.rng,
+                        self.engine,
+                    )?
+                    .remove(0),
+                };
+                let session = self.engine.network().session();
+                let name = name
+                    .clone()
+                    .unwrap_or_else(|| self.variables.fresh_name(session));
+                self.variables.insert(session, name.clone(), result);
+                Ok(format!("{name} is shared"))
+            }
+            ReplCommand::Open(operand) => {
+                let share = self.value(operand)?;
+                let shares: Vec<ShamirShare<T>> =
+                    self.engine.broadcast_and_gather_values(&share)?;
+                Ok(format!("{operand} = {:?}", reconstruct_secret(shares)))
+            }
+            ReplCommand::Vars => {
+                let names: Vec<&str> = self
+                    .variables
+                    .names(self.engine.network().session())
+                    .collect();
+                Ok(names.join(" "))
+            }
+            ReplCommand::Help => Ok(HELP.to_string()),
+            ReplCommand::Quit => Ok(String::new()),
+        }
+    }
+
+    /// Returns the share of the value of an operand.
+    fn value(&self, operand: &Operand) -> Result<ShamirShare<T>, ReplError> {
+        let unknown = || ReplError::UnknownOperand(operand.to_string());
+        match operand {
+            Operand::Variable(name) => self
+                .variables
+                .get(self.engine.network().session(), name)
+                .cloned()
+                .ok_or_else(unknown),
+            Operand::Input { party, index } => self
+                .inputs
+                .get(*party)
+                .and_then(|inputs| inputs.get(*index))
+                .cloned()
+                .ok_or_else(unknown),
+            // A public constant is shared with the constant polynomial.
+            Operand::Constant(constant) => Ok(ShamirShare::new(T::from(*constant), 0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, thread};
+
+    use rand::thread_rng;
+
+    use super::{Operand, Operation, Repl, ReplCommand, ReplError};
+    use crate::{math::mersenne61::Mersenne61, mpc::engine::Engine, net::Network};
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(
+            "c = mul x0 x1[2]".parse(),
+            Ok(ReplCommand::Compute {
+                name: Some("c".to_string()),
+                operation: Operation::Multiply,
+                left: Operand::Input { party: 0, index: 0 },
+                right: Operand::Input { party: 1, index: 2 },
+            })
+        );
+        assert_eq!(
+            " add  v1 3 ".parse(),
+            Ok(ReplCommand::Compute {
+                name: None,
+                operation: Operation::Add,
+                left: Operand::Variable("v1".to_string()),
+                right: Operand::Constant(3),
+            })
+        );
+        assert_eq!(
+            "open c".parse(),
+            Ok(ReplCommand::Open(Operand::Variable("c".to_string())))
+        );
+        assert!(matches!(
+            "input -2".parse::<ReplCommand>(),
+            Ok(ReplCommand::Input(_))
+        ));
+        assert_eq!(
+            "x0 = add x1 x2".parse::<ReplCommand>(),
+            Err(ReplError::InvalidName("x0".to_string()))
+        );
+        assert_eq!(
+            "mul a".parse::<ReplCommand>(),
+            Err(ReplError::InvalidCommand)
+        );
+        assert_eq!(
+            "open a-b".parse::<ReplCommand>(),
+            Err(ReplError::InvalidName("a-b".to_string()))
+        );
+    }
+
+    #[test]
+    fn repl_over_local_network() {
+        let scripts = [
+            "input 5\nc = mul x0 x1\nopen c\nopen missing\nadd c 1\nopen v1\nvars\nquit\n",
+            "input 6\nc = mul x0 x1\nopen c\nopen missing\nadd c 1\nopen v1\nvars\n",
+            "input abc\ninput 7\nc = mul x0 x1\nopen c\nopen missing\nadd c 2\nopen v1\n",
+        ];
+        let handles: Vec<_> = Network::create_local(3)
+            .into_iter()
+            .zip(scripts)
+            .map(|(network, script)| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let mut output = Vec::new();
+                    Repl::<Mersenne61, _>::new(&mut Engine::new(network), 1, &mut rng)
+                        .run(Cursor::new(script), &mut output)
+                        .unwrap();
+                    String::from_utf8(output).unwrap()
+                })
+            })
+            .collect();
+        let outputs: Vec<String> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        for output in &outputs {
+            assert!(output.contains("> shared the inputs x0[0], x1[0], x2[0]\n"));
+            assert!(output.contains("> c = Mersenne61(30)\n"));
+            assert!(output.contains("> error: the value missing does not exist\n"));
+            assert!(output.contains("> error: the parties issued different commands"));
+        }
+        // The diverged command is rejected by every party, so the variable is not created.
+        assert!(outputs[0].contains("> error: the value v1 does not exist\n"));
+        assert!(outputs[0].contains("> c\n"));
+        assert!(outputs[2].starts_with("> error: the number abc is not correct\n"));
+    }
+}
@@ -0,0 +1,121 @@
+use std::{fmt, str::FromStr};
+
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use thiserror::Error;
+
+use crate::hex::decode_hex;
+
+/// Size in bytes of the seed of a ChaCha20 generator.
+const SEED_SIZE: usize = 32;
+
+/// Possible errors that may appear when parsing a seed.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SeedError {
+    /// The seed is not a string of hexadecimal digits.
+    #[error("the seed is not a hexadecimal string")]
+    InvalidHex,
+
+    /// The seed is empty or longer than 32 bytes.
+    #[error("the seed has {0} bytes, but it must have between 1 and 32 bytes")]
+    InvalidLength(usize),
+
+    /// A seed was given without explicitly allowing the insecure deterministic randomness.
+    #[error("a seed makes the randomness deterministic, which is INSECURE, so it must be allowed with --allow-insecure-seed")]
+    NotAllowed,
+}
+
+/// Seed of the deterministic generators used to reproduce an execution bit for bit. This is
+/// INSECURE, since anybody who knows the seed can recompute the shares of a party, and it is only
+/// intended for tests and bug reports.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Seed([u8; SEED_SIZE]);
+
+impl Seed {
+    /// Returns a ChaCha20 generator for the party with the given ID. The parties draw from
+    /// different streams of the same seed, so they can share the seed and still sample
+    /// independent polynomials.
+    pub fn rng(&self, party: usize) -> ChaCha20Rng {
+        let mut rng = ChaCha20Rng::from_seed(self.0);
+        rng.set_stream(party as u64);
+        rng
+    }
+}
+
+/// Source of the randomness of a party. The protocols only accept cryptographically secure
+/// generators, so a test that needs reproducible executions injects a seeded CSPRNG such as the
+/// ones of [`Seed::rng`] instead of a weak generator.
+pub trait RandomnessSource: RngCore + CryptoRng + Send {}
+
+impl<R: RngCore + CryptoRng + Send> RandomnessSource for R {}
+
+impl FromStr for Seed {
+    type Err = SeedError;
+
+    /// Parses a seed written in hexadecimal, optionally prefixed by `0x`. Seeds shorter than 32
+    /// bytes are padded with zeros at the end.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        let bytes = decode_hex(hex).ok_or(SeedError::InvalidHex)?;
+        if bytes.is_empty() || bytes.len() > SEED_SIZE {
+            return Err(SeedError::InvalidLength(bytes.len()));
+        }
+        let mut seed = [0; SEED_SIZE];
+        seed[..bytes.len()].copy_from_slice(&bytes);
+        Ok(Self(seed))
+    }
+}
+
+impl fmt::Debug for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The seed determines the shares of the parties, so it is not printed in the logs.
+        write!(f, "Seed(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::{Seed, SeedError};
+    use crate::{math::mersenne61::Mersenne61, mpc::compute_shamir_share};
+
+    #[test]
+    fn seeded_shares_are_reproducible() {
+        let seed: Seed = "0x00112233".parse().unwrap();
+        let secret = Mersenne61::from(42);
+        let shares = compute_shamir_share(&secret, 5, 2, &mut seed.rng(0));
+        assert_eq!(
+            shares,
+            compute_shamir_share(&secret, 5, 2, &mut seed.rng(0))
+        );
+        assert_ne!(
+            shares,
+            compute_shamir_share(&secret, 5, 2, &mut seed.rng(1))
+        );
+        let other: Seed = "00112234".parse().unwrap();
+        assert_ne!(
+            shares,
+            compute_shamir_share(&secret, 5, 2, &mut other.rng(0))
+        );
+        assert_ne!(
+            shares,
+            compute_shamir_share(&secret, 5, 2, &mut thread_rng())
+        );
+    }
+
+    #[test]
+    fn parse_seeds() {
+        let mut bytes = [0; 32];
+        bytes[0] = 0xab;
+        assert_eq!("ab".parse::<Seed>(), Ok(Seed(bytes)));
+        assert_eq!("".parse::<Seed>(), Err(SeedError::InvalidLength(0)));
+        assert_eq!("0xabc".parse::<Seed>(), Err(SeedError::InvalidHex));
+        assert_eq!("zz".parse::<Seed>(), Err(SeedError::InvalidHex));
+        assert_eq!(
+            "00".repeat(33).parse::<Seed>(),
+            Err(SeedError::InvalidLength(33))
+        );
+        assert_eq!(format!("{:?}", "ab".parse::<Seed>().unwrap()), "Seed(..)");
+    }
+}
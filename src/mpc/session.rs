@@ -0,0 +1,610 @@
+use std::{path::Path, sync::atomic::AtomicBool};
+
+use rand::{CryptoRng, Rng};
+use thiserror::Error;
+
+use super::{
+    check_parameters,
+    checkpoint::{self, Checkpoint},
+    circuit::{Circuit, CircuitState},
+    engine::Engine,
+    mpspdz::{self, MpSpdzFormat},
+    open_to, preprocessing, reconstruct_secret, reconstruct_secret_from_parties,
+    shadow::Shadow,
+    share::ShamirShare,
+    share_inputs,
+    two_party::{self, TripleStore},
+    verification::{Security, VerificationError},
+    ReconstructionError,
+};
+use crate::{
+    math::{FiniteField, PrimeField},
+    net::{
+        audit::AuditEvent,
+        log_context,
+        overrides::{self, Override},
+        stats::PhaseStats,
+        Network, NetworkConfig,
+    },
+    transport::Transport,
+};
+
+/// Number of times that a party with checkpoints reconnects with the other parties to resume the
+/// protocol after a failure.
+pub const RECOVERY_ATTEMPTS: u32 = 3;
+
+/// Possible errors that may appear while setting up a session.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SessionError {
+    /// The result is opened to a party that is not in the session.
+    #[error(
+        "the result can not be opened to party {receiver}, since there are {n_parties} parties"
+    )]
+    UnknownReceiver { receiver: usize, n_parties: usize },
+
+    /// The offline phase has no file to which the Beaver triples are written.
+    #[error("the offline phase writes the Beaver triples to a file, but none was given")]
+    MissingTriplesFile,
+}
+
+/// Settings with which a party connects with the other parties of a session.
+#[derive(Debug, Clone, Copy)]
+pub struct Connection<'a> {
+    /// ID of the party.
+    pub id: usize,
+    /// Path to the network configuration file.
+    pub net_config_file: &'a Path,
+    /// Settings that replace the ones of the file, after the ones of the `SHAMI_CONFIG_*`
+    /// environment variables.
+    pub overrides: &'a [Override],
+    /// Whether the party serves as the coordinator of the session.
+    pub coordinate: bool,
+    /// Flag that interrupts the party, e.g. from a signal handler.
+    pub interrupt: Option<&'static AtomicBool>,
+}
+
+impl Connection<'_> {
+    /// Creates the network described by the configuration file, registering with the
+    /// coordinator of the session if there is one.
+    pub fn connect(&self) -> anyhow::Result<Network> {
+        log_context::set_party(self.id);
+        // The settings of the file are replaced by the environment and then by the overrides.
+        let mut overrides = overrides::from_env(std::env::vars())?;
+        overrides.extend(self.overrides.iter().cloned());
+        let mut net_config = NetworkConfig::with_overrides(self.net_config_file, &overrides)?;
+        let coordinator = if self.coordinate {
+            Some(net_config.serve_coordinator()?)
+        } else {
+            None
+        };
+
+        // Get the roster of the session from the coordinator, if there is one.
+        net_config.register(self.id)?;
+
+        let mut network = Network::create(self.id, net_config)?;
+        if let Some(interrupt) = self.interrupt {
+            network.set_interrupt(interrupt);
+        }
+        if let Some(coordinator) = coordinator {
+            match coordinator.join() {
+                Ok(result) => result?,
+                Err(_) => log::error!("the coordinator thread panicked"),
+            }
+        }
+        Ok(network)
+    }
+}
+
+/// Loads the Beaver triples of the file into the engine in the two-party mode or in the online
+/// phase, in which the products are computed with them. Otherwise, the products do not need
+/// triples and the file is ignored.
+pub fn load_triples<T: PrimeField>(
+    path: Option<&Path>,
+    threshold: usize,
+    online: bool,
+    engine: &mut Engine<Network>,
+) -> anyhow::Result<()> {
+    if !online && !two_party::is_two_party(engine.n_parties(), threshold) {
+        if path.is_some() {
+            log::warn!(
+                "ignoring the Beaver triples, which are only used by two parties or in the online \
+                 phase"
+            );
+        }
+        return Ok(());
+    }
+    let Some(path) = path else {
+        log::warn!("two parties multiply with Beaver triples, but none were given");
+        return Ok(());
+    };
+    let triples = MpSpdzFormat::new(mpspdz::DEFAULT_LIMBS).read_triples::<T>(path, threshold)?;
+    log::info!("loaded {} Beaver triples from {path:?}", triples.len());
+    engine.set_triples(TripleStore::new(&triples)?);
+    Ok(())
+}
+
+/// Runs the offline phase of a session, in which the party generates `count` Beaver triples with
+/// the other parties and writes its shares to the file for the online phase. The network is
+/// closed afterwards.
+pub fn generate_triples<T, R>(
+    path: Option<&Path>,
+    count: usize,
+    threshold: usize,
+    rng: &mut R,
+    mut network: Network,
+) -> anyhow::Result<()>
+where
+    T: PrimeField,
+    R: Rng + CryptoRng,
+{
+    let path = path.ok_or(SessionError::MissingTriplesFile)?;
+    check_parameters::<T>(network.n_parties(), threshold, true)?;
+    network.begin_phase("preprocessing");
+    let triples = preprocessing::generate_triples::<T, _, _>(
+        count,
+        network.n_parties(),
+        threshold,
+        rng,
+        &mut Engine::new(&mut network),
+    )?;
+    network.end_phase();
+    MpSpdzFormat::new(mpspdz::DEFAULT_LIMBS).write_triples(path, &triples)?;
+    log::info!("wrote {} Beaver triples to {path:?}", triples.len());
+    network.close()?;
+    Ok(())
+}
+
+/// Checks that the result can be opened to every receiver, which must be a party of the
+/// session.
+pub fn check_receivers(receivers: &[usize], n_parties: usize) -> Result<(), SessionError> {
+    match receivers.iter().find(|receiver| **receiver >= n_parties) {
+        Some(receiver) => Err(SessionError::UnknownReceiver {
+            receiver: *receiver,
+            n_parties,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Checkpoints written by a party at the round boundaries of the protocol.
+pub struct Checkpoints<'a, T> {
+    /// File where the checkpoints are written.
+    pub path: &'a Path,
+    /// Checkpoint from which the party resumes, if any.
+    pub resume: Option<Checkpoint<T>>,
+}
+
+/// Computation evaluated by the parties of a session.
+pub struct Computation<'a, C> {
+    /// Name of the computation in the logs and the audit log.
+    pub name: &'a str,
+    /// Function that builds the circuit from the number of inputs of each party.
+    pub circuit: C,
+    /// Parties to which the result is opened, or every party if there are none.
+    pub receivers: &'a [usize],
+}
+
+/// Work done by a party at the round boundaries of the protocol besides the evaluation.
+pub struct RoundHooks<'a, T> {
+    /// Checkpoints written by the party, if any.
+    pub checkpoints: Option<Checkpoints<'a, T>>,
+    /// Plaintext shadow in which the party records its shares of the wires, if any.
+    pub shadow: Option<&'a Shadow<T>>,
+}
+
+/// Recovery of a party with checkpoints from the failures of the network.
+pub struct Recovery<'a> {
+    /// File where the checkpoints are written.
+    pub checkpoint: &'a Path,
+    /// Whether the party resumes from the checkpoint of the file right away, after a crash.
+    pub resume: bool,
+    /// Function that connects again with the other parties.
+    pub reconnect: &'a dyn Fn() -> anyhow::Result<Network>,
+}
+
+/// Shares the inputs of the party, evaluates the circuit over the shares of the inputs of every
+/// party and opens the result, which is returned. The circuit is built from the number of inputs
+/// of each party. If the computation has designated receivers, the result is only opened to
+/// them, and the rest of parties get `None`. With checkpoints, the parties first
+/// agree on the round from which they resume, and the party writes a checkpoint after sharing
+/// the inputs and after every round of the evaluation. With a shadow, the party records its
+/// shares of the wires in it after every round.
+pub fn run_protocol<T, R, C>(
+    engine: &mut Engine<Network>,
+    inputs: &[T],
+    threshold: usize,
+    rng: &mut R,
+    computation: Computation<C>,
+    hooks: RoundHooks<T>,
+) -> anyhow::Result<Option<T>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    C: FnOnce(&[usize]) -> Circuit,
+{
+    let Computation {
+        name,
+        circuit,
+        receivers,
+    } = computation;
+    let n_parties = engine.n_parties();
+    let id = engine.id();
+    let progress = engine.network().progress().cloned();
+    engine.network_mut().audit(AuditEvent::Parameters {
+        protocol: name.to_string(),
+        modulus: T::MODULUS.to_string(),
+        n_parties,
+        threshold,
+    });
+    let RoundHooks {
+        checkpoints,
+        shadow,
+    } = hooks;
+    let (path, resume) = match checkpoints {
+        Some(Checkpoints { path, resume }) => {
+            engine
+                .network_mut()
+                .begin_phase("agreeing on the checkpoint");
+            let round = checkpoint::agree_on_round(engine, resume.as_ref())?;
+            (Some(path), round.zip(resume))
+        }
+        None => (None, None),
+    };
+
+    let (shares, circuit, state) = match resume {
+        Some((round, mut checkpoint)) => {
+            let n_inputs: Vec<usize> = checkpoint.inputs.iter().map(Vec::len).collect();
+            let circuit = circuit(&n_inputs);
+            checkpoint.check(id, &circuit)?;
+            checkpoint.state.rollback(&circuit, round);
+            log::info!("resuming the {name} protocol from round {round}");
+            (checkpoint.inputs, circuit, checkpoint.state)
+        }
+        None => {
+            // Compute random shares to send to the other parties.
+            engine.network_mut().begin_phase("sharing the inputs");
+            log::info!("sharing {} inputs with the other parties", inputs.len());
+            let own_shares = share_inputs(inputs, n_parties, threshold, rng);
+
+            // Send the share to all the parties and receive the shares from all the parties.
+            log::info!("exchanging the shares of the inputs with the other parties");
+            let shares: Vec<Vec<ShamirShare<T>>> = engine.exchange_values(&own_shares)?;
+            let n_inputs: Vec<usize> = shares.iter().map(Vec::len).collect();
+            let circuit = circuit(&n_inputs);
+            let state = circuit.initial_state();
+            (shares, circuit, state)
+        }
+    };
+
+    let on_round = |state: &CircuitState<T>| -> anyhow::Result<()> {
+        if let Some(progress) = &progress {
+            // The first round of the evaluation only has local gates.
+            progress.set_rounds(state.round().saturating_sub(1), circuit.n_rounds());
+        }
+        if let Some(path) = path {
+            log::debug!("writing the checkpoint of round {}", state.round());
+            Checkpoint::new(id, &circuit, shares.clone(), state.clone()).save(path)?;
+        }
+        if let Some(shadow) = shadow {
+            shadow.record(id, state);
+        }
+        Ok(())
+    };
+    on_round(&state)?;
+
+    if !two_party::uses_triples(engine, threshold) {
+        // The products of the circuit take their double sharings from the pool of the engine,
+        // which is filled in a single round, and the verification of the active mode multiplies
+        // every pair twice more.
+        let per_product = match engine.security() {
+            Security::Passive => 1,
+            Security::Active => 3,
+        };
+        engine
+            .network_mut()
+            .begin_phase("generating the double sharings");
+        engine.preprocess_double_sharings::<T, _>(
+            per_product * circuit.n_products(),
+            threshold,
+            2 * threshold,
+            rng,
+        )?;
+    }
+
+    engine.network_mut().begin_phase("evaluating the circuit");
+    log::info!("running the {name} protocol");
+    let result_share = circuit.evaluate_from(&shares, threshold, rng, engine, state, on_round)?;
+    engine.network_mut().begin_phase("opening the result");
+
+    if !receivers.is_empty() {
+        // The result is only revealed to the designated receivers, one after the other.
+        let mut result = None;
+        for receiver in receivers {
+            log::info!("opening the result to party {receiver}");
+            if let Some(opened) = open_to(&result_share, *receiver, engine)? {
+                log::info!("the {name} result is: {:?}", opened);
+                result = Some(opened);
+            }
+        }
+        engine.network_mut().end_phase();
+        return Ok(result);
+    }
+
+    // Open the secret by sending the shares to other parties.
+    log::info!("sending the shares of the result to other parties");
+    log::debug!("the share of party {} is {:?}", engine.id(), result_share);
+
+    // Receive the shares from the Network
+    log::info!("receiving the shares of the result from other parties");
+    let result_shares_remote: Vec<ShamirShare<T>> =
+        engine.broadcast_and_gather_values(&result_share)?;
+    for (i, share) in result_shares_remote.iter().enumerate() {
+        log::debug!("received share from party {i}: {:?}", share);
+    }
+
+    log::debug!("{name} shares: {:?}", result_shares_remote);
+
+    // In the active mode, the result is only accepted if the shares of every party agree.
+    let result = match engine.security() {
+        Security::Passive => reconstruct_secret(result_shares_remote),
+        Security::Active => {
+            reconstruct_secret_from_parties(result_shares_remote.into_iter().enumerate().collect())?
+        }
+    };
+
+    log::info!("the {name} result is: {:?}", result);
+    engine.network_mut().end_phase();
+
+    Ok(Some(result))
+}
+
+/// Runs the protocol of [`run_protocol`], recovering from the failures of the network if the
+/// party has checkpoints: the party connects again with the other parties and resumes from its
+/// last checkpoint, up to [`RECOVERY_ATTEMPTS`] times. A failed check of the active mode aborts
+/// the session instead, as does an interrupt. The network of the engine is replaced by the new
+/// one on every recovery, which keeps the progress, while the engine keeps the Beaver triples
+/// that were left. The last network is closed as in [`close_session`].
+///
+/// Returns the result of the protocol and the phases of every network of the session.
+pub fn run_with_recovery<T, R, C>(
+    mut engine: Engine<Network>,
+    inputs: &[T],
+    threshold: usize,
+    rng: &mut R,
+    computation: Computation<C>,
+    recovery: Option<Recovery>,
+) -> anyhow::Result<(Option<T>, Vec<PhaseStats>)>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    C: Fn(&[usize]) -> Circuit,
+{
+    let mut phases = Vec::new();
+    let mut resume = recovery.as_ref().is_some_and(|recovery| recovery.resume);
+    let mut attempts = 0;
+    loop {
+        let checkpoints = match &recovery {
+            Some(recovery) => Some(Checkpoints {
+                path: recovery.checkpoint,
+                resume: if resume {
+                    Some(Checkpoint::load(recovery.checkpoint)?)
+                } else {
+                    None
+                },
+            }),
+            None => None,
+        };
+        let result = run_protocol(
+            &mut engine,
+            inputs,
+            threshold,
+            rng,
+            Computation {
+                name: computation.name,
+                circuit: &computation.circuit,
+                receivers: computation.receivers,
+            },
+            RoundHooks {
+                checkpoints,
+                shadow: None,
+            },
+        );
+        // In the active mode, a failed check aborts the session instead of resuming it with the
+        // same parties.
+        let mut aborted = false;
+        if let Err(err) = &result {
+            if err
+                .chain()
+                .any(|cause| cause.is::<ReconstructionError>() || cause.is::<VerificationError>())
+            {
+                engine.network_mut().audit(AuditEvent::CheckFailed {
+                    party_id: None,
+                    reason: format!("{err:#}"),
+                });
+                aborted = engine.security() == Security::Active;
+            }
+        }
+        match (result, &recovery) {
+            (Ok(result), _) => return Ok((result, close_session(engine.into_network(), phases)?)),
+            (Err(err), Some(recovery))
+                if !aborted
+                    && attempts < RECOVERY_ATTEMPTS
+                    && !engine.network().is_interrupted() =>
+            {
+                attempts += 1;
+                log::warn!(
+                    "the protocol failed: {err:#}; reconnecting to resume from the last \
+                     checkpoint (attempt {attempts} of {RECOVERY_ATTEMPTS})"
+                );
+                engine.network_mut().end_phase();
+                phases.extend_from_slice(engine.network().phases());
+                // The engine keeps the triples that were left, since the consumed ones must not
+                // be used again. The connections are closed before listening again on the same
+                // address.
+                engine = engine.map_network(|network| {
+                    let progress = network.progress().cloned();
+                    drop(network);
+                    let mut network = (recovery.reconnect)()?;
+                    if let Some(progress) = progress {
+                        network.set_progress(progress);
+                    }
+                    Ok(network)
+                })?;
+                resume = recovery.checkpoint.exists();
+            }
+            (Err(err), _) => return Err(err),
+        }
+    }
+}
+
+/// Closes the network at the end of a session and marks the progress as done. Returns the
+/// phases of the session, which follow the ones of the networks that failed before.
+pub fn close_session(
+    mut network: Network,
+    mut phases: Vec<PhaseStats>,
+) -> anyhow::Result<Vec<PhaseStats>> {
+    log::debug!("network statistics: {:?}", network.stats());
+    network.begin_phase("closing");
+    network.close()?;
+    network.end_phase();
+    if let Some(progress) = network.progress() {
+        progress.set_phase("done");
+    }
+    phases.extend_from_slice(network.phases());
+    Ok(phases)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{
+        check_receivers, load_triples, run_protocol, run_with_recovery, Computation, RoundHooks,
+        SessionError,
+    };
+    use crate::{
+        math::mersenne61::Mersenne61,
+        mpc::{circuit::Circuit, engine::Engine, expression::Expression},
+        net::Network,
+    };
+
+    /// Runs the protocol with a party per input, each one with a single input, and returns the
+    /// result of every party.
+    fn run_parties(
+        inputs: &[u64],
+        expression: fn(&[usize]) -> Expression,
+        receivers: &'static [usize],
+    ) -> Vec<Option<Mersenne61>> {
+        let inputs: Vec<Mersenne61> = inputs
+            .iter()
+            .map(|input| Mersenne61::from(*input))
+            .collect();
+        let handles: Vec<_> = Network::create_local(inputs.len())
+            .into_iter()
+            .map(|network| {
+                let input = inputs[network.id()].clone();
+                thread::spawn(move || {
+                    let mut engine = Engine::new(network);
+                    let result = run_protocol(
+                        &mut engine,
+                        &[input],
+                        1,
+                        &mut thread_rng(),
+                        Computation {
+                            name: "test",
+                            circuit: |n_inputs: &[usize]| {
+                                Circuit::from_expression(&expression(n_inputs))
+                            },
+                            receivers,
+                        },
+                        RoundHooks {
+                            checkpoints: None,
+                            shadow: None,
+                        },
+                    )
+                    .unwrap();
+                    engine.into_network().close().unwrap();
+                    result
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn every_party_gets_the_result() {
+        let results = run_parties(&[2, 3, 4], Expression::product_of_inputs, &[]);
+        assert_eq!(results, vec![Some(Mersenne61::from(24)); 3]);
+        let results = run_parties(&[2, 3, 4], Expression::sum_of_inputs, &[]);
+        assert_eq!(results, vec![Some(Mersenne61::from(9)); 3]);
+    }
+
+    #[test]
+    fn only_the_receivers_get_the_result() {
+        let results = run_parties(&[2, 3, 4], Expression::product_of_inputs, &[0, 2]);
+        let result = Some(Mersenne61::from(24));
+        assert_eq!(results, vec![result.clone(), None, result]);
+    }
+
+    #[test]
+    fn sessions_without_checkpoints_are_not_recovered() {
+        let handles: Vec<_> = Network::create_local(3)
+            .into_iter()
+            .map(|network| {
+                thread::spawn(move || {
+                    let input = Mersenne61::from(network.id() as u64 + 1);
+                    let (result, phases) = run_with_recovery(
+                        Engine::new(network),
+                        &[input],
+                        1,
+                        &mut thread_rng(),
+                        Computation {
+                            name: "test",
+                            circuit: |n_inputs: &[usize]| {
+                                Circuit::from_expression(&Expression::sum_of_inputs(n_inputs))
+                            },
+                            receivers: &[],
+                        },
+                        None,
+                    )
+                    .unwrap();
+                    assert!(phases.iter().any(|phase| phase.name == "closing"));
+                    result
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Some(Mersenne61::from(6)));
+        }
+    }
+
+    #[test]
+    fn receivers_must_be_parties() {
+        assert_eq!(check_receivers(&[0, 2], 3), Ok(()));
+        assert_eq!(
+            check_receivers(&[1, 3], 3),
+            Err(SessionError::UnknownReceiver {
+                receiver: 3,
+                n_parties: 3
+            })
+        );
+    }
+
+    #[test]
+    fn triples_are_only_loaded_when_needed() {
+        let mut engine = Engine::new(Network::create_local(3).remove(0));
+        // Three parties with one corruption do not use triples, so the missing file is ignored.
+        load_triples::<Mersenne61>(Some("missing_triples".as_ref()), 1, false, &mut engine)
+            .unwrap();
+        assert!(engine.triples().is_empty());
+        assert!(
+            load_triples::<Mersenne61>(Some("missing_triples".as_ref()), 1, true, &mut engine)
+                .is_err()
+        );
+    }
+}
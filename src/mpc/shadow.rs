@@ -0,0 +1,222 @@
+use std::sync::{Mutex, PoisonError};
+
+use thiserror::Error;
+
+use super::{
+    circuit::{Circuit, CircuitState, Gate, Wire},
+    expression::ExpressionError,
+    reconstruct_secret_from_parties,
+    share::ShamirShare,
+    ReconstructionError,
+};
+use crate::math::FiniteField;
+
+/// Possible divergences between the evaluation of a circuit on shares and its plaintext shadow.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ShadowError {
+    /// Some party did not record its share of a wire.
+    #[error("party {party} did not record its share of the wire {wire}")]
+    Missing { wire: Wire, party: usize },
+
+    /// The shares of a wire do not determine a value.
+    #[error("the shares of the wire {wire}, computed by {gate:?}, do not open: {source}")]
+    Inconsistent {
+        wire: Wire,
+        gate: Gate,
+        source: ReconstructionError,
+    },
+
+    /// The shares of a wire open to another value than the evaluation in the clear.
+    #[error("the wire {wire}, computed by {gate:?}, opens to {found} instead of {expected}")]
+    Diverged {
+        wire: Wire,
+        gate: Gate,
+        expected: String,
+        found: String,
+    },
+}
+
+/// Plaintext shadow of an evaluation of a circuit, which carries the value in the clear of every
+/// wire next to the shares that the parties compute, to pinpoint the first gate at which the
+/// shared computation diverges from it, e.g. while debugging a protocol. The shadow needs the
+/// inputs of every party and the shares of every party, so it only makes sense in simulations in
+/// which all the parties run in the same process, where it is shared among them.
+#[derive(Debug)]
+pub struct Shadow<T> {
+    /// Gates of the circuit.
+    gates: Vec<Gate>,
+    /// Values of the wires in the clear.
+    values: Vec<T>,
+    /// Shares of the wires recorded by every party, indexed by party ID and then by wire.
+    shares: Mutex<Vec<Vec<Option<ShamirShare<T>>>>>,
+}
+
+impl<T: FiniteField> Shadow<T> {
+    /// Creates the shadow of the evaluation of a circuit by `n_parties` on the given inputs in
+    /// the clear, indexed by party ID and then by the position of the input.
+    pub fn new(
+        circuit: &Circuit,
+        inputs: &[Vec<T>],
+        n_parties: usize,
+    ) -> Result<Self, ExpressionError> {
+        let values = circuit.evaluate_plain_wires(inputs)?;
+        let shares = vec![vec![None; values.len()]; n_parties];
+        Ok(Self {
+            gates: circuit.gates().to_vec(),
+            values,
+            shares: Mutex::new(shares),
+        })
+    }
+
+    /// Records the shares of the wires that a party has computed so far, e.g. from the state of
+    /// every round passed to the callback of [`Circuit::evaluate_from`].
+    pub fn record(&self, party: usize, state: &CircuitState<T>) {
+        for wire in 0..self.gates.len() {
+            if let Some(share) = state.wire(wire) {
+                self.record_share(party, wire, share.clone());
+            }
+        }
+    }
+
+    /// Records the share of a party of the value of a wire.
+    pub fn record_share(&self, party: usize, wire: Wire, share: ShamirShare<T>) {
+        let mut shares = self.shares.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(slot) = shares
+            .get_mut(party)
+            .and_then(|shares| shares.get_mut(wire))
+        {
+            *slot = Some(share);
+        }
+    }
+
+    /// Opens the shares of every wire in topological order and compares them with the value of
+    /// the wire in the clear, returning the first wire at which they diverge. Since the operands
+    /// of a gate come before it, that is the gate that introduced the divergence.
+    pub fn check(&self) -> Result<(), ShadowError> {
+        let shares = self.shares.lock().unwrap_or_else(PoisonError::into_inner);
+        for (wire, (gate, expected)) in self.gates.iter().zip(&self.values).enumerate() {
+            let wire_shares = shares
+                .iter()
+                .enumerate()
+                .map(|(party, shares)| {
+                    shares[wire]
+                        .clone()
+                        .map(|share| (party, share))
+                        .ok_or(ShadowError::Missing { wire, party })
+                })
+                .collect::<Result<_, _>>()?;
+            let found = reconstruct_secret_from_parties(wire_shares).map_err(|source| {
+                ShadowError::Inconsistent {
+                    wire,
+                    gate: gate.clone(),
+                    source,
+                }
+            })?;
+            if !found.equal(expected) {
+                return Err(ShadowError::Diverged {
+                    wire,
+                    gate: gate.clone(),
+                    expected: format!("{expected:?}"),
+                    found: format!("{found:?}"),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{Shadow, ShadowError};
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{
+            circuit::{Circuit, Gate},
+            engine::Engine,
+            expression::Expression,
+            share::ShamirShare,
+            share_inputs,
+        },
+        net::Network,
+    };
+
+    #[test]
+    fn shadow_pinpoints_the_diverging_gate() {
+        const N_PARTIES: usize = 3;
+        const THRESHOLD: usize = 1;
+
+        let mut rng = thread_rng();
+        let inputs: Vec<Vec<Mersenne61>> = (0..N_PARTIES)
+            .map(|_| vec![Mersenne61::random(&mut rng)])
+            .collect();
+        let expression: Expression = "x0 * x1 + 2 * x2".parse().unwrap();
+        let circuit = Circuit::from_expression(&expression);
+        let shadow = Shadow::new(&circuit, &inputs, N_PARTIES).unwrap();
+        let own_shares: Vec<_> = inputs
+            .iter()
+            .map(|inputs| share_inputs(inputs, N_PARTIES, THRESHOLD, &mut rng))
+            .collect();
+        thread::scope(|scope| {
+            for mut network in Network::create_local(N_PARTIES) {
+                let id = network.id();
+                let shares: Vec<Vec<ShamirShare<Mersenne61>>> =
+                    own_shares.iter().map(|shares| shares[id].clone()).collect();
+                let (circuit, shadow) = (&circuit, &shadow);
+                scope.spawn(move || {
+                    circuit
+                        .evaluate_from(
+                            &shares,
+                            THRESHOLD,
+                            &mut thread_rng(),
+                            &mut Engine::new(&mut network),
+                            circuit.initial_state(),
+                            |state| {
+                                shadow.record(id, state);
+                                Ok(())
+                            },
+                        )
+                        .unwrap();
+                });
+            }
+        });
+        assert_eq!(shadow.check(), Ok(()));
+
+        // A wrong share of one party is caught at the product, where the shares do not lie on
+        // a polynomial of their degree.
+        let product = 2;
+        assert_eq!(circuit.gates()[product], Gate::Multiply(0, 1));
+        let honest = |party: usize| {
+            shadow.shares.lock().unwrap()[party][product]
+                .clone()
+                .unwrap()
+        };
+        let share = honest(1);
+        shadow.record_share(1, product, share.add_const(&Mersenne61::ONE));
+        assert!(matches!(
+            shadow.check(),
+            Err(ShadowError::Inconsistent { wire: 2, .. })
+        ));
+
+        // The same error in every share opens to another value.
+        for party in 0..N_PARTIES {
+            let share = if party == 1 {
+                share.clone()
+            } else {
+                honest(party)
+            };
+            shadow.record_share(party, product, share.add_const(&Mersenne61::ONE));
+        }
+        assert!(matches!(
+            shadow.check(),
+            Err(ShadowError::Diverged {
+                wire: 2,
+                gate: Gate::Multiply(0, 1),
+                ..
+            })
+        ));
+    }
+}
@@ -1,11 +1,21 @@
-use std::cmp;
+use std::{
+    cmp, fmt,
+    marker::PhantomData,
+    ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
 
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::Zeroizing;
 
-use crate::math::FiniteField;
+use super::DegreeError;
+use crate::{
+    codec::{read_varint, write_varint},
+    math::{FieldValue, FiniteField},
+};
 
 /// Represents a Shamir Share of a value.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ShamirShare<T> {
     /// The degree of the Shamir share.
     pub degree: usize,
@@ -18,17 +28,29 @@ impl<T> ShamirShare<T>
 where
     T: FiniteField,
 {
+    /// Creates a share with the given value and degree.
     pub fn new(value: T, degree: usize) -> Self {
         Self { value, degree }
     }
 
-    pub fn multiply(&self, other: &Self) -> Self {
-        Self {
-            value: self.value.multiply(&other.value),
-            degree: self.degree + other.degree,
+    /// Multiplies two shares of a secret shared among `n_parties`. The degree of the result is
+    /// the sum of the degrees of the shares, so the product of two shares of degree `t` must be
+    /// reduced with [`run_degree_reduction_protocol`](super::run_degree_reduction_protocol)
+    /// before it is opened. The multiplication protocols reduce such factors by themselves. The
+    /// product fails with [`DegreeError::Irreducible`] if its degree is not below the number of
+    /// parties, since their shares could not interpolate it any more.
+    pub fn multiply(&self, other: &Self, n_parties: usize) -> Result<Self, DegreeError> {
+        let degree = self.degree + other.degree;
+        if degree >= n_parties {
+            return Err(DegreeError::Irreducible { degree, n_parties });
         }
+        Ok(Self {
+            value: self.value.multiply(&other.value),
+            degree,
+        })
     }
 
+    /// Multiplies the share by a public constant.
     pub fn multiply_const(&self, other: &T) -> Self {
         Self {
             value: self.value.multiply(other),
@@ -36,6 +58,7 @@ where
         }
     }
 
+    /// Adds a public constant to the share.
     pub fn add_const(&self, other: &T) -> Self {
         Self {
             value: self.value.add(other),
@@ -43,13 +66,15 @@ where
         }
     }
 
+    /// Subtracts a public constant from the share.
     pub fn subtract_const(&self, other: &T) -> Self {
         Self {
-            value: self.value.subtract(&other.negate()),
+            value: self.value.subtract(other),
             degree: self.degree,
         }
     }
 
+    /// Adds two shares. The degree of the result is the maximum of the degrees of the shares.
     pub fn add(&self, other: &Self) -> Self {
         Self {
             value: self.value.add(&other.value),
@@ -57,6 +82,7 @@ where
         }
     }
 
+    /// Negates the share.
     pub fn negate(&self) -> Self {
         Self {
             value: self.value.negate(),
@@ -64,7 +90,323 @@ where
         }
     }
 
+    /// Subtracts two shares. The degree of the result is the maximum of the degrees of the shares.
     pub fn subtract(&self, other: &Self) -> Self {
-        self.add(&other.negate())
+        Self {
+            value: self.value.subtract(&other.value),
+            degree: cmp::max(self.degree, other.degree),
+        }
+    }
+
+    /// Returns the share `a` if the choice is zero and `b` if it is one, in constant time.
+    pub fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self {
+            value: T::conditional_select(&a.value, &b.value, choice),
+            degree: u64::conditional_select(&(a.degree as u64), &(b.degree as u64), choice)
+                as usize,
+        }
+    }
+}
+
+impl<T: FiniteField> Add<&ShamirShare<T>> for &ShamirShare<T> {
+    type Output = ShamirShare<T>;
+
+    fn add(self, other: &ShamirShare<T>) -> ShamirShare<T> {
+        ShamirShare::add(self, other)
+    }
+}
+
+impl<T: FiniteField> Add for ShamirShare<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        ShamirShare::add(&self, &other)
+    }
+}
+
+impl<T: FiniteField> Sub<&ShamirShare<T>> for &ShamirShare<T> {
+    type Output = ShamirShare<T>;
+
+    fn sub(self, other: &ShamirShare<T>) -> ShamirShare<T> {
+        self.subtract(other)
+    }
+}
+
+impl<T: FiniteField> Sub for ShamirShare<T> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.subtract(&other)
+    }
+}
+
+impl<T: FiniteField> Mul<&T> for &ShamirShare<T> {
+    type Output = ShamirShare<T>;
+
+    /// Multiplies the share by a public constant. The product of two shares is
+    /// [`ShamirShare::multiply`], which checks the degree of the result.
+    fn mul(self, constant: &T) -> ShamirShare<T> {
+        self.multiply_const(constant)
+    }
+}
+
+impl<T: FiniteField> Mul<T> for ShamirShare<T> {
+    type Output = Self;
+
+    fn mul(self, constant: T) -> Self {
+        self.multiply_const(&constant)
+    }
+}
+
+impl<T: FiniteField> Neg for &ShamirShare<T> {
+    type Output = ShamirShare<T>;
+
+    fn neg(self) -> ShamirShare<T> {
+        self.negate()
+    }
+}
+
+impl<T: FiniteField> Neg for ShamirShare<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.negate()
+    }
+}
+
+impl<T: FiniteField> AddAssign<&ShamirShare<T>> for ShamirShare<T> {
+    fn add_assign(&mut self, other: &ShamirShare<T>) {
+        *self = ShamirShare::add(self, other);
+    }
+}
+
+impl<T: FiniteField> SubAssign<&ShamirShare<T>> for ShamirShare<T> {
+    fn sub_assign(&mut self, other: &ShamirShare<T>) {
+        *self = self.subtract(other);
+    }
+}
+
+impl<T: FiniteField> MulAssign<&T> for ShamirShare<T> {
+    fn mul_assign(&mut self, constant: &T) {
+        *self = self.multiply_const(constant);
+    }
+}
+
+impl<T: FiniteField> ConstantTimeEq for ShamirShare<T> {
+    /// Compares the values of the shares in constant time. The degrees are public, so they are
+    /// compared as usual.
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.value.ct_eq(&other.value) & Choice::from(u8::from(self.degree == other.degree))
+    }
+}
+
+/// Shares of a Beaver triple, i.e. of two random values `a` and `b` and of their product `c`,
+/// which are generated in a preprocessing phase to multiply shared values later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BeaverTriple<T> {
+    /// Share of the first random value.
+    pub a: ShamirShare<T>,
+    /// Share of the second random value.
+    pub b: ShamirShare<T>,
+    /// Share of the product of the random values.
+    pub c: ShamirShare<T>,
+}
+
+/// Shares of several values that have the same degree, as the ones that a party sends to a peer
+/// in a round of a batched protocol. They are encoded compactly as a single byte string: the
+/// degree as a variable-length integer, followed by every value in little-endian order in the
+/// fixed width of the field, which is 8 bytes for Mersenne61 and 16 bytes for the fields of 128
+/// bits. The number of values is implied by the length of the string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareBatch<T> {
+    /// Degree of every share of the batch.
+    pub degree: usize,
+
+    /// Values of the shares.
+    pub values: Vec<T>,
+}
+
+impl<T: FiniteField> ShareBatch<T> {
+    /// Returns the batch of the given shares, or `None` if their degrees differ.
+    pub fn from_shares(shares: &[ShamirShare<T>]) -> Option<Self> {
+        let degree = shares.first().map_or(0, |share| share.degree);
+        shares
+            .iter()
+            .all(|share| share.degree == degree)
+            .then(|| Self {
+                degree,
+                values: shares.iter().map(|share| share.value.clone()).collect(),
+            })
+    }
+
+    /// Returns the shares of the batch.
+    pub fn into_shares(self) -> Vec<ShamirShare<T>> {
+        let degree = self.degree;
+        self.values
+            .into_iter()
+            .map(|value| ShamirShare::new(value, degree))
+            .collect()
+    }
+
+    /// Returns the number of bytes in which a value of the field is encoded.
+    fn width() -> usize {
+        T::BIT_SIZE.div_ceil(8)
+    }
+}
+
+impl<T: FiniteField> Serialize for ShareBatch<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let width = Self::width();
+        let mut bytes = Zeroizing::new(Vec::with_capacity(10 + self.values.len() * width));
+        write_varint(&mut bytes, self.degree as u128);
+        for value in &self.values {
+            let start = bytes.len();
+            bytes.resize(start + width, 0);
+            value.value().write_le_bytes(&mut bytes[start..]);
+        }
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+impl<'de, T: FiniteField> Deserialize<'de> for ShareBatch<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(ShareBatchVisitor(PhantomData))
+    }
+}
+
+/// Visitor that decodes the byte string of a [`ShareBatch`].
+struct ShareBatchVisitor<T>(PhantomData<T>);
+
+impl<T: FiniteField> de::Visitor<'_> for ShareBatchVisitor<T> {
+    type Value = ShareBatch<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a batch of shares")
+    }
+
+    fn visit_bytes<E: de::Error>(self, mut bytes: &[u8]) -> Result<Self::Value, E> {
+        let degree = read_varint(&mut bytes).map_err(E::custom)?;
+        let degree = usize::try_from(degree).map_err(|_| E::custom("the degree is too large"))?;
+        let width = ShareBatch::<T>::width();
+        if !bytes.len().is_multiple_of(width) {
+            return Err(E::custom(format!(
+                "the values take {} bytes, which is not a multiple of {width}",
+                bytes.len()
+            )));
+        }
+        let values = bytes
+            .chunks(width)
+            .map(|chunk| {
+                T::ValueType::read_le_bytes(chunk)
+                    .and_then(T::from_value)
+                    .ok_or_else(|| E::custom("a value of the batch is not reduced"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(ShareBatch { degree, values })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShamirShare, ShareBatch};
+    use crate::{
+        codec::{Codec, WireCodec},
+        math::{mersenne61::Mersenne61, p128::P128, FiniteField},
+        mpc::DegreeError,
+    };
+
+    #[test]
+    fn operators_match_the_share_methods() {
+        let a = ShamirShare::new(Mersenne61::from(7), 2);
+        let b = ShamirShare::new(Mersenne61::from(5), 1);
+        let c = Mersenne61::from(3);
+        assert_eq!(&a + &b, ShamirShare::new(Mersenne61::from(12), 2));
+        assert_eq!(&b - &a, ShamirShare::new(Mersenne61::from(2).negate(), 2));
+        assert_eq!(&a * &c, ShamirShare::new(Mersenne61::from(21), 2));
+        assert_eq!(-a.clone(), a.negate());
+        assert_eq!(
+            a.clone() + b.clone() - b.clone() * c.clone(),
+            a.add(&b).subtract(&b.multiply_const(&c))
+        );
+
+        let mut d = a.clone();
+        d += &b;
+        d *= &c;
+        d -= &a;
+        assert_eq!(d, ShamirShare::new(Mersenne61::from(29), 2));
+    }
+
+    #[test]
+    fn products_must_be_reconstructible() {
+        let a = ShamirShare::new(Mersenne61::from(7), 2);
+        assert_eq!(
+            a.multiply(&a, 5),
+            Ok(ShamirShare::new(Mersenne61::from(49), 4))
+        );
+        assert_eq!(
+            a.multiply(&a, 4),
+            Err(DegreeError::Irreducible {
+                degree: 4,
+                n_parties: 4
+            })
+        );
+    }
+
+    #[test]
+    fn batches_roundtrip_in_every_codec() {
+        let shares: Vec<_> = (0..5)
+            .map(|i| ShamirShare::new(P128::from(u64::MAX - i).negate(), 3))
+            .collect();
+        let batch = ShareBatch::from_shares(&shares).unwrap();
+        for codec in [Codec::Bincode, Codec::Postcard, Codec::Protobuf] {
+            let bytes = codec.encode(&batch).unwrap();
+            let decoded: ShareBatch<P128> = codec.decode(&bytes).unwrap();
+            assert_eq!(decoded.into_shares(), shares, "{codec}");
+        }
+        assert!(ShareBatch::from_shares(&[
+            ShamirShare::new(P128::ONE, 1),
+            ShamirShare::new(P128::ONE, 2)
+        ])
+        .is_none());
+    }
+
+    #[test]
+    fn batches_halve_the_bytes_of_the_shares() {
+        let shares = vec![ShamirShare::new(Mersenne61::ONE.negate(), 2); 64];
+        let batch = ShareBatch::from_shares(&shares).unwrap();
+        let codec = Codec::Bincode;
+        // The length of the string, the degree and 8 bytes per value, instead of the length of
+        // the vector and 16 bytes per share.
+        assert_eq!(codec.encode(&batch).unwrap().len(), 8 + 1 + 64 * 8);
+        assert_eq!(codec.encode(&shares).unwrap().len(), 8 + 64 * 16);
+        for codec in [Codec::Postcard, Codec::Protobuf] {
+            assert!(codec.encode(&batch).unwrap().len() < codec.encode(&shares).unwrap().len());
+        }
+    }
+
+    #[test]
+    fn malformed_batches_are_rejected() {
+        let codec = Codec::Bincode;
+        // Seven bytes of values are not a whole value of Mersenne61.
+        let bytes = codec
+            .encode(&serde_bytes_of(&[1, 0, 0, 0, 0, 0, 0, 0]))
+            .unwrap();
+        assert!(codec.decode::<ShareBatch<Mersenne61>>(&bytes).is_err());
+        // The modulus itself is not reduced.
+        let mut value = vec![1];
+        value.extend_from_slice(&((1u64 << 61) - 1).to_le_bytes());
+        let bytes = codec.encode(&serde_bytes_of(&value)).unwrap();
+        assert!(codec.decode::<ShareBatch<Mersenne61>>(&bytes).is_err());
+    }
+
+    /// Wraps some bytes so they are encoded as a byte string, as a batch is.
+    fn serde_bytes_of(bytes: &[u8]) -> impl serde::Serialize + '_ {
+        struct Bytes<'a>(&'a [u8]);
+        impl serde::Serialize for Bytes<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+        Bytes(bytes)
     }
 }
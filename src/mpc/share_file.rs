@@ -0,0 +1,248 @@
+use std::{fs, path::PathBuf};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{reconstruct_secret_from_parties, share::ShamirShare};
+use crate::{hex::encode_hex, math::FiniteField};
+
+/// Possible errors in the metadata of the share files of a secret.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ShareFileError {
+    /// A file holds a share of another field.
+    #[error("the share of party {party} belongs to the field of modulus {found}, but the modulus is {expected}")]
+    WrongField {
+        party: usize,
+        expected: String,
+        found: String,
+    },
+
+    /// The files were written by different dealings, so they hold shares of different secrets.
+    #[error("the share of party {party} belongs to the session {found:?}, but {expected:?} was expected")]
+    SessionMismatch {
+        party: usize,
+        expected: Option<String>,
+        found: Option<String>,
+    },
+
+    /// The files disagree on the number of parties of the dealing.
+    #[error(
+        "the share of party {party} was dealt to {found:?} parties, but {expected:?} was expected"
+    )]
+    PartiesMismatch {
+        party: usize,
+        expected: Option<usize>,
+        found: Option<usize>,
+    },
+
+    /// The ID of the party of a file is not one of the parties of the dealing.
+    #[error("party {party} is not one of the {n_parties} parties of the dealing")]
+    PartyOutOfRange { party: usize, n_parties: usize },
+}
+
+/// Contents of a share file, which holds the share of a secret of one party together with the
+/// metadata that tells which shares can be combined: the modulus of the field, a random session
+/// ID of the dealing, shared by all the shares of the same secret, and the number of parties.
+/// The metadata is missing in the files of older versions, which only have the party and the
+/// share.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareFile<T> {
+    /// ID of the party that holds the share.
+    pub party: usize,
+    /// Share of the secret.
+    pub share: ShamirShare<T>,
+    /// Modulus of the field in decimal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modulus: Option<String>,
+    /// ID of the dealing that produced the share, in hexadecimal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// Number of parties among which the secret was shared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n_parties: Option<usize>,
+}
+
+impl<T: FiniteField> ShareFile<T> {
+    /// Returns the files of the shares of a secret dealt to every party, indexed by party ID,
+    /// with the metadata of the field and a fresh session ID.
+    pub fn for_dealing<R: Rng>(shares: Vec<ShamirShare<T>>, rng: &mut R) -> Vec<Self> {
+        let session = encode_hex(&rng.gen::<[u8; 16]>());
+        let n_parties = shares.len();
+        shares
+            .into_iter()
+            .enumerate()
+            .map(|(party, share)| Self {
+                party,
+                share,
+                modulus: Some(T::MODULUS.to_string()),
+                session: Some(session.clone()),
+                n_parties: Some(n_parties),
+            })
+            .collect()
+    }
+}
+
+/// Reads the share files written in JSON, e.g. by the `share` command.
+pub fn read_share_files<T: FiniteField>(paths: &[PathBuf]) -> anyhow::Result<Vec<ShareFile<T>>> {
+    paths
+        .iter()
+        .map(|path| {
+            serde_json::from_slice(&fs::read(path)?)
+                .map_err(|err| anyhow::anyhow!("{path:?} is not a share file: {err}"))
+        })
+        .collect()
+}
+
+/// Reconstructs a secret from the share files of some of the parties, which must be at least
+/// `t + 1` for shares of degree `t`. Before the reconstruction, the metadata of the files must
+/// belong to the field `T`, agree on the session and the number of parties, and the IDs of the
+/// parties must be in range. The shares beyond the first `t + 1` must be consistent with them.
+pub fn reconstruct_share_files<T: FiniteField>(files: Vec<ShareFile<T>>) -> anyhow::Result<T> {
+    let modulus = T::MODULUS.to_string();
+    let (session, n_parties) = files
+        .first()
+        .map_or((None, None), |file| (file.session.clone(), file.n_parties));
+    for file in &files {
+        if let Some(found) = file.modulus.as_ref().filter(|found| **found != modulus) {
+            anyhow::bail!(ShareFileError::WrongField {
+                party: file.party,
+                expected: modulus,
+                found: found.clone(),
+            });
+        }
+        if file.session != session {
+            anyhow::bail!(ShareFileError::SessionMismatch {
+                party: file.party,
+                expected: session,
+                found: file.session.clone(),
+            });
+        }
+        if file.n_parties != n_parties {
+            anyhow::bail!(ShareFileError::PartiesMismatch {
+                party: file.party,
+                expected: n_parties,
+                found: file.n_parties,
+            });
+        }
+        if let Some(n_parties) = n_parties.filter(|n_parties| file.party >= *n_parties) {
+            anyhow::bail!(ShareFileError::PartyOutOfRange {
+                party: file.party,
+                n_parties,
+            });
+        }
+    }
+    let shares = files
+        .into_iter()
+        .map(|file| (file.party, file.share))
+        .collect();
+    Ok(reconstruct_secret_from_parties(shares)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use std::fs;
+
+    use super::{read_share_files, reconstruct_share_files, ShareFile, ShareFileError};
+    use crate::{
+        math::{mersenne127::Mersenne127, mersenne61::Mersenne61},
+        mpc::{compute_shamir_share, ReconstructionError},
+    };
+
+    #[test]
+    fn share_files_are_read_back() {
+        let mut rng = StdRng::seed_from_u64(1776);
+        let secret = Mersenne61::from(1776);
+        let files = ShareFile::for_dealing(compute_shamir_share(&secret, 3, 1, &mut rng), &mut rng);
+        let paths: Vec<_> = files
+            .iter()
+            .map(|file| {
+                let path = std::env::temp_dir().join(format!(
+                    "shami_rs_share_file_{}_{}.json",
+                    std::process::id(),
+                    file.party
+                ));
+                fs::write(&path, serde_json::to_vec(file).unwrap()).unwrap();
+                path
+            })
+            .collect();
+        let read: Vec<ShareFile<Mersenne61>> = read_share_files(&paths[1..]).unwrap();
+        assert_eq!(read, files[1..]);
+        assert_eq!(reconstruct_share_files(read).unwrap(), secret);
+
+        fs::write(&paths[0], b"{}").unwrap();
+        assert!(read_share_files::<Mersenne61>(&paths[..1]).is_err());
+        for path in paths {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn share_files_are_validated_before_reconstructing() {
+        let mut rng = StdRng::seed_from_u64(1735);
+        let secret = Mersenne61::from(1735);
+        let files = ShareFile::for_dealing(compute_shamir_share(&secret, 5, 2, &mut rng), &mut rng);
+        let other = ShareFile::for_dealing(compute_shamir_share(&secret, 5, 2, &mut rng), &mut rng);
+        assert_eq!(
+            reconstruct_share_files(vec![files[4].clone(), files[0].clone(), files[2].clone()])
+                .unwrap(),
+            secret
+        );
+
+        // Two shares of degree two are not enough, and a party can only give one share.
+        let err = reconstruct_share_files(files[..2].to_vec()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ReconstructionError>(),
+            Some(&ReconstructionError::NotEnoughShares {
+                degree: 2,
+                received: 2
+            })
+        );
+        let duplicated = vec![files[1].clone(), files[1].clone(), files[3].clone()];
+        assert!(reconstruct_share_files(duplicated).is_err());
+
+        // The shares of another dealing of the same secret do not combine with these.
+        let mixed = vec![files[0].clone(), files[1].clone(), other[2].clone()];
+        let err = reconstruct_share_files(mixed).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ShareFileError>(),
+            Some(ShareFileError::SessionMismatch { party: 2, .. })
+        ));
+
+        let mut out_of_range = files[..3].to_vec();
+        out_of_range[2].party = 7;
+        let err = reconstruct_share_files(out_of_range).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ShareFileError>(),
+            Some(&ShareFileError::PartyOutOfRange {
+                party: 7,
+                n_parties: 5
+            })
+        );
+
+        // The files of Mersenne61 are rejected when reading them as shares of Mersenne127.
+        let json = serde_json::to_string(&files[..3]).unwrap();
+        let wrong_field: Vec<ShareFile<Mersenne127>> = serde_json::from_str(&json).unwrap();
+        let err = reconstruct_share_files(wrong_field).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ShareFileError>(),
+            Some(ShareFileError::WrongField { party: 0, .. })
+        ));
+
+        // The files of older versions have no metadata.
+        let legacy: Vec<ShareFile<Mersenne61>> = files[1..4]
+            .iter()
+            .map(|file| {
+                let json = format!(
+                    r#"{{"party":{},"share":{}}}"#,
+                    file.party,
+                    serde_json::to_string(&file.share).unwrap()
+                );
+                serde_json::from_str(&json).unwrap()
+            })
+            .collect();
+        assert_eq!(reconstruct_share_files(legacy).unwrap(), secret);
+    }
+}
@@ -0,0 +1,217 @@
+use std::thread;
+
+use rand::{CryptoRng, Rng};
+
+use super::{
+    bench::{self, BenchConfig, BenchReport},
+    check_parameters,
+    circuit::Circuit,
+    deal_beaver_triples,
+    engine::Engine,
+    seed::RandomnessSource,
+    session::{self, Computation, RoundHooks},
+    shadow::Shadow,
+    share::BeaverTriple,
+    two_party::{self, TripleStore},
+};
+use crate::{
+    math::FiniteField,
+    net::{log_context, Network},
+    transport::Transport,
+};
+
+/// Runs a function for every party of an in-memory network, each one in its own thread with an
+/// engine over its network, and closes the networks afterwards. The outputs are indexed by party
+/// ID.
+pub fn run_local<O, F>(n_parties: usize, run: F) -> anyhow::Result<Vec<O>>
+where
+    O: Send,
+    F: Fn(&mut Engine<Network>) -> anyhow::Result<O> + Sync,
+{
+    thread::scope(|scope| {
+        let handles: Vec<_> = Network::create_local(n_parties)
+            .into_iter()
+            .map(|network| {
+                let run = &run;
+                scope.spawn(move || {
+                    log_context::set_party(network.id());
+                    let mut engine = Engine::new(network);
+                    let output = run(&mut engine)?;
+                    engine.into_network().close()?;
+                    Ok(output)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("the thread of a party panicked")))
+            })
+            .collect()
+    })
+}
+
+/// Deals the Beaver triples of `n_products` products to the parties of a simulation in the
+/// two-party mode, as a trusted dealer with the given generator. With more parties, no triple is
+/// dealt.
+pub fn deal_simulated_triples<T, R>(
+    n_parties: usize,
+    threshold: usize,
+    n_products: usize,
+    rng: &mut R,
+) -> Vec<Vec<BeaverTriple<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+{
+    if !two_party::is_two_party(n_parties, threshold) {
+        return vec![Vec::new(); n_parties];
+    }
+    log::info!("dealing {n_products} Beaver triples to the two parties");
+    deal_beaver_triples(n_products, n_parties, threshold, rng, None)
+}
+
+/// Runs all the parties of a session in the same process, each one in its own thread and
+/// connected to the others through an in-memory network, and checks that every party obtains
+/// the same result, which is returned. Each party contributes one input and takes its
+/// randomness from `rng(id)`, and the dealer of the two-party mode from `rng(n_parties)`. With a
+/// shadow, the shares of every wire are checked against the evaluation of the circuit in the
+/// clear.
+pub fn simulate<T, G>(
+    threshold: usize,
+    inputs: &[T],
+    circuit: Circuit,
+    rng: G,
+    shadow: bool,
+) -> anyhow::Result<T>
+where
+    T: FiniteField + Send + Sync,
+    G: Fn(usize) -> Box<dyn RandomnessSource> + Sync,
+{
+    let n_parties = inputs.len();
+    check_parameters::<T>(n_parties, threshold, circuit.n_rounds() > 0)?;
+    let shadow = if shadow {
+        let plain: Vec<Vec<T>> = inputs.iter().map(|input| vec![input.clone()]).collect();
+        Some(Shadow::new(&circuit, &plain, n_parties)?)
+    } else {
+        None
+    };
+    let triples = deal_simulated_triples::<T, _>(
+        n_parties,
+        threshold,
+        circuit.n_products(),
+        &mut rng(n_parties),
+    );
+    let results = run_local(n_parties, |engine| {
+        let id = engine.id();
+        engine.set_triples(TripleStore::new(&triples[id])?);
+        session::run_protocol(
+            engine,
+            std::slice::from_ref(&inputs[id]),
+            threshold,
+            &mut rng(id),
+            Computation {
+                name: "simulation",
+                circuit: |_: &[usize]| circuit.clone(),
+                receivers: &[],
+            },
+            RoundHooks {
+                checkpoints: None,
+                shadow: shadow.as_ref(),
+            },
+        )
+        .map(|result| result.expect("every party receives the result of a simulation"))
+    })?;
+    if let Some(shadow) = &shadow {
+        shadow.check()?;
+        log::info!("the shares match the evaluation in the clear at every gate");
+    }
+    if results.windows(2).any(|pair| !pair[0].equal(&pair[1])) {
+        anyhow::bail!("the parties obtained different results: {results:?}");
+    }
+    Ok(results[0].clone())
+}
+
+/// Runs the benchmark of the protocols with all the parties in the same process, as in
+/// [`simulate`], and returns the report of the party 0.
+pub fn simulate_benchmark<T, G>(
+    n_parties: usize,
+    threshold: usize,
+    config: &BenchConfig,
+    rng: G,
+) -> anyhow::Result<BenchReport>
+where
+    T: FiniteField + Sync,
+    G: Fn(usize) -> Box<dyn RandomnessSource> + Sync,
+{
+    check_parameters::<T>(n_parties, threshold, true)?;
+    let triples = deal_simulated_triples::<T, _>(
+        n_parties,
+        threshold,
+        config.n_products::<T>(),
+        &mut rng(n_parties),
+    );
+    let mut reports = run_local(n_parties, |engine| {
+        let id = engine.id();
+        engine.set_triples(TripleStore::new(&triples[id])?);
+        bench::run_benchmark::<T, _>(config, threshold, &mut rng(id), engine)
+    })?;
+    Ok(reports.swap_remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_local, simulate};
+    use crate::{
+        math::mersenne61::Mersenne61,
+        mpc::{
+            circuit::Circuit,
+            expression::Expression,
+            seed::{RandomnessSource, Seed},
+        },
+        transport::Transport,
+    };
+
+    fn seeded(party: usize) -> Box<dyn RandomnessSource> {
+        let seed: Seed = "07".parse().unwrap();
+        Box::new(seed.rng(party))
+    }
+
+    fn product(n_parties: usize) -> Circuit {
+        Circuit::from_expression(&Expression::product_of_inputs(&vec![1; n_parties]))
+    }
+
+    #[test]
+    fn simulation_computes_the_product() {
+        let inputs: Vec<Mersenne61> = [2, 3, 4].map(Mersenne61::from).to_vec();
+        let result = simulate(1, &inputs, product(3), seeded, false).unwrap();
+        assert_eq!(result, Mersenne61::from(24));
+    }
+
+    #[test]
+    fn simulation_of_two_parties_uses_triples() {
+        let inputs: Vec<Mersenne61> = [5, 7].map(Mersenne61::from).to_vec();
+        let result = simulate(1, &inputs, product(2), seeded, false).unwrap();
+        assert_eq!(result, Mersenne61::from(35));
+    }
+
+    #[test]
+    fn simulation_checks_the_shadow() {
+        let inputs: Vec<Mersenne61> = [2, 3, 4, 5, 6].map(Mersenne61::from).to_vec();
+        let result = simulate(2, &inputs, product(5), seeded, true).unwrap();
+        assert_eq!(result, Mersenne61::from(720));
+    }
+
+    #[test]
+    fn local_outputs_are_indexed_by_party() {
+        let ids = run_local(4, |engine| Ok(engine.id())).unwrap();
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+        assert!(run_local(2, |engine| {
+            anyhow::ensure!(engine.id() == 0, "party {} failed", engine.id());
+            Ok(())
+        })
+        .is_err());
+    }
+}
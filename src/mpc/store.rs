@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+
+use super::share::ShamirShare;
+
+/// Shared values of one session, by name.
+#[derive(Debug, Clone)]
+struct Scope<T> {
+    /// Shares of the values by name.
+    values: BTreeMap<String, ShamirShare<T>>,
+    /// Number of fresh names handed out so far.
+    fresh_names: usize,
+}
+
+impl<T> Default for Scope<T> {
+    fn default() -> Self {
+        Self {
+            values: BTreeMap::new(),
+            fresh_names: 0,
+        }
+    }
+}
+
+/// Store of the shares of named values, so that the steps of a computation refer to their
+/// intermediate values by name. Every session of the network has its own scope, so the same name
+/// refers to different values in different sessions, and the values of a session are dropped
+/// together once the session ends. Every party must store the shares of the same values under the
+/// same names.
+#[derive(Debug, Clone)]
+pub struct ShareStore<T> {
+    /// Scopes by session.
+    scopes: BTreeMap<u32, Scope<T>>,
+}
+
+impl<T> Default for ShareStore<T> {
+    fn default() -> Self {
+        Self {
+            scopes: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Clone> ShareStore<T> {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores the share of a value under a name in the scope of a session, returning the share
+    /// that the name held before, if any.
+    pub fn insert(
+        &mut self,
+        session: u32,
+        name: impl Into<String>,
+        share: ShamirShare<T>,
+    ) -> Option<ShamirShare<T>> {
+        self.scopes
+            .entry(session)
+            .or_default()
+            .values
+            .insert(name.into(), share)
+    }
+
+    /// Returns the share of the value with the given name in the scope of a session.
+    pub fn get(&self, session: u32, name: &str) -> Option<&ShamirShare<T>> {
+        self.scopes.get(&session)?.values.get(name)
+    }
+
+    /// Removes the value with the given name from the scope of a session, returning its share.
+    pub fn remove(&mut self, session: u32, name: &str) -> Option<ShamirShare<T>> {
+        self.scopes.get_mut(&session)?.values.remove(name)
+    }
+
+    /// Returns the names of the values of a session in lexicographic order.
+    pub fn names(&self, session: u32) -> impl Iterator<Item = &str> {
+        self.scopes
+            .get(&session)
+            .into_iter()
+            .flat_map(|scope| scope.values.keys().map(String::as_str))
+    }
+
+    /// Returns a name of the form `vk` that no value of the session holds. The parties that
+    /// create the same values in the same order get the same names.
+    pub fn fresh_name(&mut self, session: u32) -> String {
+        let scope = self.scopes.entry(session).or_default();
+        loop {
+            scope.fresh_names += 1;
+            let name = format!("v{}", scope.fresh_names);
+            if !scope.values.contains_key(&name) {
+                return name;
+            }
+        }
+    }
+
+    /// Drops every value of a session, returning how many there were.
+    pub fn clear_session(&mut self, session: u32) -> usize {
+        self.scopes
+            .remove(&session)
+            .map_or(0, |scope| scope.values.len())
+    }
+
+    /// Returns the number of values stored across every session.
+    pub fn len(&self) -> usize {
+        self.scopes.values().map(|scope| scope.values.len()).sum()
+    }
+
+    /// Tells whether the store holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShareStore;
+    use crate::{math::mersenne61::Mersenne61, mpc::share::ShamirShare};
+
+    #[test]
+    fn values_are_scoped_by_session() {
+        let share = |value: u64| ShamirShare::new(Mersenne61::from(value), 1);
+        let mut store = ShareStore::new();
+        assert!(store.is_empty());
+        assert_eq!(store.insert(1, "a", share(1)), None);
+        assert_eq!(store.insert(2, "a", share(2)), None);
+        assert_eq!(store.get(1, "a"), Some(&share(1)));
+        assert_eq!(store.get(2, "a"), Some(&share(2)));
+        assert_eq!(store.get(3, "a"), None);
+        assert_eq!(store.insert(1, "a", share(3)), Some(share(1)));
+
+        // The fresh names skip the names already in use and are counted per session.
+        store.insert(1, "v1", share(4));
+        assert_eq!(store.fresh_name(1), "v2");
+        assert_eq!(store.fresh_name(2), "v1");
+        store.insert(1, "b", share(5));
+        assert_eq!(store.names(1).collect::<Vec<_>>(), ["a", "b", "v1"]);
+        assert_eq!(store.len(), 4);
+
+        assert_eq!(store.remove(1, "b"), Some(share(5)));
+        assert_eq!(store.remove(1, "b"), None);
+        assert_eq!(store.clear_session(1), 2);
+        assert_eq!(store.names(1).count(), 0);
+        assert_eq!(store.get(2, "a"), Some(&share(2)));
+        assert_eq!(store.clear_session(2), 1);
+        assert!(store.is_empty());
+    }
+}
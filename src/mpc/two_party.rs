@@ -0,0 +1,368 @@
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use super::{
+    engine::Engine,
+    reconstruct_secret,
+    share::{BeaverTriple, ShamirShare, ShareBatch},
+};
+use crate::{
+    math::{FieldValue, FiniteField},
+    transport::Transport,
+};
+
+/// Possible errors of the multiplications of the two-party mode.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TwoPartyError {
+    /// The party does not hold enough Beaver triples for the products.
+    #[error(
+        "multiplying {needed} products needs as many Beaver triples, but {available} are left"
+    )]
+    NotEnoughTriples { needed: usize, available: usize },
+
+    /// The Beaver triples were dealt in another field.
+    #[error("the Beaver triples belong to another field")]
+    WrongField,
+
+    /// The Beaver triples were dealt with shares of another degree.
+    #[error("the Beaver triples have shares of degree {found}, but {expected} was expected")]
+    WrongDegree { found: usize, expected: usize },
+}
+
+/// Tells whether a session runs in the two-party mode, which is the case of two parties that
+/// tolerate one corruption. The shares of degree one of two parties are a 2-out-of-2 sharing,
+/// i.e. an additive sharing up to the Lagrange coefficients, so the products of the shares have
+/// degree two and can not be reduced without a third party. Instead, the products are computed
+/// with the Beaver triples of a preprocessing phase, and the security holds against one
+/// semi-honest party without an honest majority.
+pub fn is_two_party(n_parties: usize, threshold: usize) -> bool {
+    n_parties == 2 && threshold == 1
+}
+
+/// Tells whether the products are computed with the Beaver triples of the engine, which is the
+/// case in the two-party mode and in the online phase of [`super::preprocessing`], in which the
+/// parties load the triples they generated beforehand. Once triples are loaded, the products
+/// keep consuming them, and fail if they run out instead of falling back to the degree
+/// reduction.
+pub fn uses_triples<N: Transport>(engine: &Engine<N>, threshold: usize) -> bool {
+    is_two_party(engine.n_parties(), threshold) || !engine.triples().is_empty()
+}
+
+/// Beaver triples of a party kept for the multiplications of the two-party mode, which are
+/// consumed in order. The transports are not generic over the field, so the values of the
+/// shares are kept as integers in little-endian order together with the modulus of their field,
+/// and are wiped from memory when the store is dropped. Both parties must load the shares of the
+/// same triples.
+#[derive(Debug, Default)]
+pub struct TripleStore {
+    /// Modulus of the field of the triples in little-endian order.
+    modulus: Vec<u8>,
+    /// Degree of the shares of the triples.
+    degree: usize,
+    /// Values of the shares of `a`, `b` and `c` of every triple, one after the other.
+    values: Zeroizing<Vec<u8>>,
+    /// Number of bytes of every value.
+    width: usize,
+    /// Number of triples.
+    len: usize,
+    /// Position of the next triple to consume.
+    next: usize,
+}
+
+impl TripleStore {
+    /// Creates the store of the shares of the given triples, which must have the same degree.
+    pub fn new<T: FiniteField>(triples: &[BeaverTriple<T>]) -> Result<Self, TwoPartyError> {
+        let degree = triples.first().map_or(0, |triple| triple.a.degree);
+        if let Some(share) = triples
+            .iter()
+            .flat_map(|triple| [&triple.a, &triple.b, &triple.c])
+            .find(|share| share.degree != degree)
+        {
+            return Err(TwoPartyError::WrongDegree {
+                found: share.degree,
+                expected: degree,
+            });
+        }
+        let width = T::ValueType::BYTES;
+        let mut values = Zeroizing::new(vec![0; 3 * width * triples.len()]);
+        for (triple, bytes) in triples.iter().zip(values.chunks_mut(3 * width)) {
+            for (share, bytes) in [&triple.a, &triple.b, &triple.c]
+                .into_iter()
+                .zip(bytes.chunks_mut(width))
+            {
+                share.value.value().write_le_bytes(bytes);
+            }
+        }
+        let mut modulus = vec![0; width];
+        T::MODULUS.write_le_bytes(&mut modulus);
+        Ok(Self {
+            modulus,
+            degree,
+            values,
+            width,
+            len: triples.len(),
+            next: 0,
+        })
+    }
+
+    /// Returns the number of triples loaded in the store, including the consumed ones.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Tells whether no triple was loaded in the store.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of triples that are left.
+    pub fn remaining(&self) -> usize {
+        self.len - self.next
+    }
+
+    /// Consumes the next `count` triples, whose shares must have the degree `threshold`. The
+    /// triples are only consumed if there are enough of them.
+    pub fn take<T: FiniteField>(
+        &mut self,
+        count: usize,
+        threshold: usize,
+    ) -> Result<Vec<BeaverTriple<T>>, TwoPartyError> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        if self.remaining() < count {
+            return Err(TwoPartyError::NotEnoughTriples {
+                needed: count,
+                available: self.remaining(),
+            });
+        }
+        let mut modulus = vec![0; T::ValueType::BYTES];
+        T::MODULUS.write_le_bytes(&mut modulus);
+        if self.modulus != modulus {
+            return Err(TwoPartyError::WrongField);
+        }
+        if self.degree != threshold {
+            return Err(TwoPartyError::WrongDegree {
+                found: self.degree,
+                expected: threshold,
+            });
+        }
+        let width = self.width;
+        let share = |bytes: &[u8]| {
+            let value = T::ValueType::read_le_bytes(bytes)
+                .and_then(T::from_value)
+                .expect("the values were taken from elements of the same field");
+            ShamirShare::new(value, self.degree)
+        };
+        let triples = self.values[3 * width * self.next..3 * width * (self.next + count)]
+            .chunks(3 * width)
+            .map(|values| BeaverTriple {
+                a: share(&values[..width]),
+                b: share(&values[width..2 * width]),
+                c: share(&values[2 * width..]),
+            })
+            .collect();
+        self.next += count;
+        Ok(triples)
+    }
+}
+
+/// Masks the factors of the products with the random values of the triples, returning the
+/// shares of `x - a` of every product followed by the shares of `y - b`, which are opened.
+pub(crate) fn mask_factors<T: FiniteField>(
+    pairs: &[(ShamirShare<T>, ShamirShare<T>)],
+    triples: &[BeaverTriple<T>],
+    threshold: usize,
+) -> ShareBatch<T> {
+    let (d, e): (Vec<T>, Vec<T>) = pairs
+        .iter()
+        .zip(triples)
+        .map(|((x, y), triple)| {
+            (
+                x.value.subtract(&triple.a.value),
+                y.value.subtract(&triple.b.value),
+            )
+        })
+        .unzip();
+    ShareBatch {
+        degree: threshold,
+        values: d.into_iter().chain(e).collect(),
+    }
+}
+
+/// Opens the masked factors with the shares received from every party, indexed by party ID,
+/// and returns the shares of the products as `c + d * b + e * a + d * e`, where `d = x - a` and
+/// `e = y - b` are the opened values.
+pub(crate) fn unmask_products<T: FiniteField>(
+    triples: &[BeaverTriple<T>],
+    masked: Vec<ShareBatch<T>>,
+    threshold: usize,
+) -> anyhow::Result<Vec<ShamirShare<T>>> {
+    for (party, batch) in masked.iter().enumerate() {
+        if batch.values.len() != 2 * triples.len() {
+            anyhow::bail!(
+                "party {party} sent {} masked factors, but {} were expected",
+                batch.values.len(),
+                2 * triples.len()
+            );
+        }
+        if batch.degree != threshold {
+            anyhow::bail!(
+                "party {party} sent shares of degree {}, but {threshold} was expected",
+                batch.degree
+            );
+        }
+    }
+    let masked: Vec<Vec<ShamirShare<T>>> =
+        masked.into_iter().map(ShareBatch::into_shares).collect();
+    let open =
+        |k: usize| reconstruct_secret(masked.iter().map(|shares| shares[k].clone()).collect());
+    Ok(triples
+        .iter()
+        .enumerate()
+        .map(|(k, triple)| {
+            let d = open(k);
+            let e = open(triples.len() + k);
+            triple
+                .c
+                .add(&triple.b.multiply_const(&d))
+                .add(&triple.a.multiply_const(&e))
+                .add_const(&d.multiply(&e))
+        })
+        .collect())
+}
+
+/// Multiplies several pairs of shared values with the Beaver triples of the engine, in a single
+/// round of interaction in which the parties open the factors masked with the triples. The
+/// shares of the products are returned in the order of the pairs.
+pub fn run_beaver_multiply_protocol<T, N>(
+    pairs: &[(ShamirShare<T>, ShamirShare<T>)],
+    threshold: usize,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    N: Transport,
+{
+    let triples = engine.triples_mut().take(pairs.len(), threshold)?;
+    let masked = mask_factors(pairs, &triples, threshold);
+    log::info!("opening the masked factors of {} products", pairs.len());
+    let masked = engine.broadcast_and_gather_values(&masked)?;
+    unmask_products(&triples, masked, threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{is_two_party, TripleStore, TwoPartyError};
+    use crate::{
+        math::{mersenne127::Mersenne127, mersenne61::Mersenne61, FiniteField},
+        mpc::{
+            circuit::Circuit, compute_shamir_share, deal_beaver_triples, engine::Engine,
+            expression::Expression, reconstruct_secret, run_multiply_protocol, share_inputs,
+        },
+        net::Network,
+    };
+
+    #[test]
+    fn triple_store_checks_the_triples() {
+        let mut rng = thread_rng();
+        assert!(is_two_party(2, 1));
+        assert!(!is_two_party(3, 1));
+        assert!(!is_two_party(2, 0));
+
+        let triples = deal_beaver_triples::<Mersenne61, _>(3, 2, 1, &mut rng, None);
+        let mut store = TripleStore::new(&triples[0]).unwrap();
+        assert_eq!(
+            store.take::<Mersenne127>(1, 1),
+            Err(TwoPartyError::WrongField)
+        );
+        assert_eq!(
+            store.take::<Mersenne61>(1, 2),
+            Err(TwoPartyError::WrongDegree {
+                found: 1,
+                expected: 2
+            })
+        );
+        assert_eq!(store.take::<Mersenne61>(2, 1).unwrap(), triples[0][..2]);
+        assert_eq!(
+            store.take::<Mersenne61>(2, 1),
+            Err(TwoPartyError::NotEnoughTriples {
+                needed: 2,
+                available: 1
+            })
+        );
+        assert_eq!(store.remaining(), 1);
+
+        let mut mixed = triples[0].clone();
+        mixed[1].c = compute_shamir_share(&Mersenne61::ONE, 3, 2, &mut rng).remove(0);
+        assert_eq!(
+            TripleStore::new(&mixed).unwrap_err(),
+            TwoPartyError::WrongDegree {
+                found: 2,
+                expected: 1
+            }
+        );
+    }
+
+    #[test]
+    fn two_parties_multiply_with_triples() {
+        const N_PARTIES: usize = 2;
+        const THRESHOLD: usize = 1;
+
+        let mut rng = thread_rng();
+        let inputs: Vec<Mersenne61> = (0..3).map(|_| Mersenne61::random(&mut rng)).collect();
+        let shares = share_inputs(&inputs, N_PARTIES, THRESHOLD, &mut rng);
+        let triples = deal_beaver_triples::<Mersenne61, _>(3, N_PARTIES, THRESHOLD, &mut rng, None);
+        // The product of the three inputs and the square of their sum take three products.
+        let expression = "x0 * x0[1] * x0[2] + (x0 + x0[1] + x0[2]) * (x0 + x0[1] + x0[2])"
+            .parse::<Expression>()
+            .unwrap();
+        let circuit = Circuit::from_expression(&expression);
+        assert_eq!(circuit.n_products(), 3);
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .map(|network| {
+                let shares = shares[network.id()].clone();
+                let store = TripleStore::new(&triples[network.id()]).unwrap();
+                let circuit = circuit.clone();
+                thread::spawn(move || {
+                    let mut engine = Engine::new(network).with_triples(store);
+                    let mut inputs = vec![Vec::new(); N_PARTIES];
+                    inputs[0] = shares;
+                    let mut rng = thread_rng();
+                    let output = circuit
+                        .evaluate(&inputs, THRESHOLD, &mut rng, &mut engine)
+                        .unwrap();
+                    // Every triple was consumed, so another product fails.
+                    let product = run_multiply_protocol(
+                        &inputs[0][0],
+                        &inputs[0][1],
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut engine,
+                    );
+                    assert!(product.is_err());
+                    assert_eq!(engine.triples().remaining(), 0);
+                    output
+                })
+            })
+            .collect();
+        let output = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        let sum = inputs[0].add(&inputs[1]).add(&inputs[2]);
+        assert_eq!(
+            reconstruct_secret(output),
+            inputs[0]
+                .multiply(&inputs[1])
+                .multiply(&inputs[2])
+                .add(&sum.multiply(&sum))
+        );
+    }
+}
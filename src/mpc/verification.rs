@@ -0,0 +1,393 @@
+use rand::{CryptoRng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use thiserror::Error;
+
+use super::{
+    double_sharing::DoubleSharings,
+    engine::Engine,
+    multiply_pairs, reconstruct_from,
+    share::{ShamirShare, ShareBatch},
+    two_party, Factors,
+};
+use crate::{
+    math::{FieldValue, FiniteField},
+    transport::Transport,
+};
+
+/// Security against the corrupted parties under which the protocols run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Security {
+    /// The corrupted parties follow the protocols and only try to learn from the messages that
+    /// they receive, so the products are not checked.
+    #[default]
+    Passive,
+    /// The corrupted parties may deviate from the protocols, so every batch of products is
+    /// verified with [`run_verified_multiply`] and the openings check the consistency of every
+    /// share, aborting if a check fails.
+    Active,
+}
+
+/// Possible errors of the verification of the active mode.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum VerificationError {
+    /// The check of a batch of products failed, so some party deviated from the protocol.
+    #[error("the verification of {0} products failed, so some party deviated from the protocol")]
+    ProductsRejected(usize),
+
+    /// The products of a pipelined evaluation are not verified.
+    #[error("the products can not be pipelined in the active mode")]
+    Pipelined,
+
+    /// A share of degree above the threshold, e.g. a local product of two shares, is reduced
+    /// without a pair of factors that the verification could check.
+    #[error("a share of degree {degree} can not be reduced in the active mode")]
+    UnverifiableReduction { degree: usize },
+
+    /// The parties do not have an honest majority, so they can not check the openings.
+    #[error("the active mode needs an honest majority, which two parties do not have")]
+    NoHonestMajority,
+}
+
+/// Opens the values of shares of the given degree by sending them to every party, and
+/// reconstructs each secret from the shares of all the parties, which fails with
+/// [`super::ReconstructionError::InconsistentShares`] if some party sent a share that does not
+/// lie on the polynomial of the others.
+pub(crate) fn open_checked<T, N>(
+    values: &[T],
+    degree: usize,
+    network: &mut N,
+) -> anyhow::Result<Vec<T>>
+where
+    T: FiniteField,
+    N: Transport,
+{
+    let own = ShareBatch {
+        degree,
+        values: values.to_vec(),
+    };
+    let batches: Vec<ShareBatch<T>> = network.broadcast_and_gather_values(&own)?;
+    for (party, batch) in batches.iter().enumerate() {
+        anyhow::ensure!(
+            batch.degree == degree && batch.values.len() == values.len(),
+            "party {party} opened {} values of degree {}, but {} of degree {degree} were expected",
+            batch.values.len(),
+            batch.degree,
+            values.len()
+        );
+    }
+    (0..values.len())
+        .map(|k| {
+            let shares: Vec<(usize, ShamirShare<T>)> = batches
+                .iter()
+                .enumerate()
+                .map(|(party, batch)| (party, ShamirShare::new(batch.values[k].clone(), degree)))
+                .collect();
+            Ok(reconstruct_from(&shares)?)
+        })
+        .collect()
+}
+
+/// Number of random secrets opened as the seed of the coefficients of the check, so that the
+/// seed has 32 bytes even if the field is smaller.
+fn seed_secrets<T: FiniteField>() -> usize {
+    32usize.div_ceil(T::ValueType::BYTES)
+}
+
+/// Multiplies the pairs of factors with the passive protocol and verifies the products, as in
+/// the protocol of Chida et al. for an honest majority. Besides every product `z = x * y`, the
+/// parties compute `r * x` and `r * z` with a random secret `r`, in another two batches of the
+/// passive protocol. Once the products are done, they open random coefficients `α` and `β` and
+/// the secret `r`, and open the value `Σ α ([r * z] - r [z]) + β ([r * x] - r [x])`, which is
+/// zero if every product is correct. An additive error that a corrupted party introduced in a product
+/// makes it nonzero except with probability `2 / |F|`, in which case every party aborts with
+/// [`VerificationError::ProductsRejected`] before the products are used. The openings check
+/// the shares of every party, so a party that sends a wrong share makes them abort too.
+///
+/// The verification triples the products and takes another three rounds per batch, and the
+/// factors must have a degree up to the threshold, since a reduced share does not come with
+/// factors that could be checked.
+pub fn run_verified_multiply<T, R, N>(
+    pairs: &[(ShamirShare<T>, ShamirShare<T>)],
+    n_parties: usize,
+    threshold: usize,
+    rng: &mut R,
+    engine: &mut Engine<N>,
+) -> anyhow::Result<Vec<ShamirShare<T>>>
+where
+    T: FiniteField,
+    R: Rng + CryptoRng,
+    N: Transport,
+{
+    if two_party::is_two_party(n_parties, threshold) {
+        anyhow::bail!(VerificationError::NoHonestMajority);
+    }
+    let degree = pairs
+        .iter()
+        .flat_map(|(a, b)| [a.degree, b.degree])
+        .max()
+        .unwrap_or(threshold);
+    if degree > threshold {
+        anyhow::bail!(VerificationError::UnverifiableReduction { degree });
+    }
+    if pairs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // The random secret `r` and the secrets of the seed, which are opened after the products.
+    let randoms: Vec<ShamirShare<T>> = DoubleSharings::generate(
+        1 + seed_secrets::<T>(),
+        n_parties,
+        threshold,
+        threshold,
+        rng,
+        engine,
+    )?
+    .take(1 + seed_secrets::<T>())?
+    .into_iter()
+    .map(|sharing| sharing.low)
+    .collect();
+    let r = &randoms[0];
+
+    log::info!("multiplying {} pairs with their verification", pairs.len());
+    let with_r: Vec<(ShamirShare<T>, ShamirShare<T>)> = pairs
+        .iter()
+        .cloned()
+        .chain(pairs.iter().map(|(x, _)| (r.clone(), x.clone())))
+        .collect();
+    let mut products = multiply_pairs(&with_r, n_parties, threshold, rng, engine)?;
+    let r_x = products.split_off(pairs.len());
+    let r_pairs: Vec<(ShamirShare<T>, ShamirShare<T>)> = r_x
+        .iter()
+        .cloned()
+        .zip(pairs.iter().map(|(_, y)| y.clone()))
+        .collect();
+    let r_z = multiply_pairs(&r_pairs, n_parties, threshold, rng, engine)?;
+    verify_products(pairs, &products, &r_x, &r_z, &randoms, engine)?;
+    Ok(products)
+}
+
+/// Opens the random secrets, the first of which is `r` and the rest the seed of the coefficients,
+/// and checks that the products `z` of the pairs and their products `r * x` and `r * z` are
+/// consistent, as described in [`run_verified_multiply`].
+fn verify_products<T, N>(
+    pairs: &Factors<T>,
+    z: &[ShamirShare<T>],
+    r_x: &[ShamirShare<T>],
+    r_z: &[ShamirShare<T>],
+    randoms: &[ShamirShare<T>],
+    network: &mut N,
+) -> anyhow::Result<()>
+where
+    T: FiniteField,
+    N: Transport,
+{
+    let degree = randoms[0].degree;
+    let values: Vec<T> = randoms.iter().map(|share| share.value.clone()).collect();
+    let opened = open_checked(&values, degree, network)?;
+    let (r, seed_values) = opened.split_first().expect("the secret r is opened");
+    let mut seed = [0; 32];
+    let mut bytes = vec![0; T::ValueType::BYTES];
+    for (chunk, value) in seed.chunks_mut(T::ValueType::BYTES).zip(seed_values) {
+        value.value().write_le_bytes(&mut bytes);
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+    let mut coefficients = ChaCha20Rng::from_seed(seed);
+
+    let mut u = ShamirShare::new(T::ZERO, degree);
+    let mut w = ShamirShare::new(T::ZERO, degree);
+    for k in 0..pairs.len() {
+        let alpha = T::random(&mut coefficients);
+        let beta = T::random(&mut coefficients);
+        u = u
+            .add(&r_z[k].multiply_const(&alpha))
+            .add(&r_x[k].multiply_const(&beta));
+        w = w
+            .add(&z[k].multiply_const(&alpha))
+            .add(&pairs[k].0.multiply_const(&beta));
+    }
+    let check = u.subtract(&w.multiply_const(r));
+    let opened = open_checked(std::slice::from_ref(&check.value), check.degree, network)?;
+    if !opened[0].equal(&T::ZERO) {
+        anyhow::bail!(VerificationError::ProductsRejected(pairs.len()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use rand::thread_rng;
+
+    use super::{run_verified_multiply, verify_products, Security, VerificationError};
+    use crate::{
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{
+            adversary::{Adversary, Deviation},
+            compute_shamir_share,
+            double_sharing::DoubleSharings,
+            engine::Engine,
+            multiply_pairs, reconstruct_secret, run_batch_multiply_protocol,
+            share::ShamirShare,
+            ReconstructionError,
+        },
+        net::Network,
+    };
+
+    const N_PARTIES: usize = 4;
+    const THRESHOLD: usize = 1;
+
+    /// Shares the given secrets among the parties, and returns the pairs of shares of each party.
+    fn share_pairs(
+        pairs: &[(Mersenne61, Mersenne61)],
+    ) -> Vec<Vec<(ShamirShare<Mersenne61>, ShamirShare<Mersenne61>)>> {
+        let mut rng = thread_rng();
+        let mut shares = vec![Vec::new(); N_PARTIES];
+        for (x, y) in pairs {
+            let x_shares = compute_shamir_share(x, N_PARTIES, THRESHOLD, &mut rng);
+            let y_shares = compute_shamir_share(y, N_PARTIES, THRESHOLD, &mut rng);
+            for (party, share) in x_shares.into_iter().zip(y_shares).enumerate() {
+                shares[party].push(share);
+            }
+        }
+        shares
+    }
+
+    #[test]
+    fn honest_products_are_accepted() {
+        let mut rng = thread_rng();
+        let pairs: Vec<(Mersenne61, Mersenne61)> = (0..3)
+            .map(|_| (Mersenne61::random(&mut rng), Mersenne61::random(&mut rng)))
+            .collect();
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(share_pairs(&pairs))
+            .map(|(network, pairs)| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    run_batch_multiply_protocol(
+                        &pairs,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(network).with_security(Security::Active),
+                    )
+                    .unwrap()
+                })
+            })
+            .collect();
+        let products: Vec<Vec<_>> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        for (k, (x, y)) in pairs.iter().enumerate() {
+            let product =
+                reconstruct_secret(products.iter().map(|shares| shares[k].clone()).collect());
+            assert_eq!(product, x.multiply(y));
+        }
+    }
+
+    #[test]
+    fn additive_errors_are_rejected() {
+        let mut rng = thread_rng();
+        let pairs: Vec<(Mersenne61, Mersenne61)> = (0..3)
+            .map(|_| (Mersenne61::random(&mut rng), Mersenne61::random(&mut rng)))
+            .collect();
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(share_pairs(&pairs))
+            .map(|(mut network, pairs)| {
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let randoms: Vec<_> = DoubleSharings::generate(
+                        5,
+                        N_PARTIES,
+                        THRESHOLD,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut network,
+                    )
+                    .unwrap()
+                    .take(5)
+                    .unwrap()
+                    .into_iter()
+                    .map(|sharing| sharing.low)
+                    .collect();
+                    let with_r: Vec<_> = pairs
+                        .iter()
+                        .cloned()
+                        .chain(pairs.iter().map(|(x, _)| (randoms[0].clone(), x.clone())))
+                        .collect();
+                    let mut z = multiply_pairs(
+                        &with_r,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap();
+                    let r_x = z.split_off(pairs.len());
+                    let r_pairs: Vec<_> = r_x
+                        .iter()
+                        .cloned()
+                        .zip(pairs.iter().map(|(_, y)| y.clone()))
+                        .collect();
+                    let r_z = multiply_pairs(
+                        &r_pairs,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                    .unwrap();
+                    // A corrupted king adds the same error to the opened value of a product,
+                    // which shifts the shares of every party without breaking their consistency.
+                    z[1] = z[1].add_const(&Mersenne61::from(7));
+                    verify_products(&pairs, &z, &r_x, &r_z, &randoms, &mut network).unwrap_err()
+                })
+            })
+            .collect();
+        for handle in handles {
+            let err = handle.join().unwrap();
+            assert_eq!(
+                err.downcast_ref(),
+                Some(&VerificationError::ProductsRejected(3))
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_shares_abort_the_multiplication() {
+        const CORRUPTED: usize = N_PARTIES - 1;
+
+        let mut rng = thread_rng();
+        let pairs = vec![(Mersenne61::random(&mut rng), Mersenne61::random(&mut rng))];
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(share_pairs(&pairs))
+            .enumerate()
+            .map(|(id, (mut network, pairs))| {
+                thread::spawn(move || {
+                    if id == CORRUPTED {
+                        Adversary::corrupt::<Mersenne61>(&mut network).set(Deviation::WrongShares);
+                    }
+                    let mut rng = thread_rng();
+                    run_verified_multiply(
+                        &pairs,
+                        N_PARTIES,
+                        THRESHOLD,
+                        &mut rng,
+                        &mut Engine::new(&mut network),
+                    )
+                })
+            })
+            .collect();
+        for (id, handle) in handles.into_iter().enumerate() {
+            let result = handle.join().unwrap();
+            if id != CORRUPTED {
+                let err = result.unwrap_err();
+                assert!(err.is::<ReconstructionError>() || err.is::<VerificationError>());
+            }
+        }
+    }
+}
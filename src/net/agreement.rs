@@ -0,0 +1,315 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{broadcast::ConcurrentBroadcasts, Network, Packet};
+use crate::codec::WireCodec;
+
+/// Protocol ID used to tag the packets of the binary agreement.
+pub const AGREEMENT_PROTOCOL_ID: u16 = 6;
+
+/// Number of rounds of each phase of the binary agreement.
+const ROUNDS_PER_PHASE: u32 = 3;
+
+/// Number of round timeouts that the reliable broadcasts of [`agree_on_values`] are given to
+/// deliver, which is the number of rounds that they take when the sender is honest.
+const BROADCAST_ROUNDS: u32 = 3;
+
+/// Possible errors that may appear in an agreement.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AgreementError {
+    /// The binary agreement only tolerates less than a third of corrupted parties.
+    #[error("agreement needs at least {} parties to tolerate {threshold} corruptions, but there are {n_parties}", 3 * threshold + 1)]
+    NotEnoughParties { n_parties: usize, threshold: usize },
+
+    /// The rounds of the instance do not fit in the round of a packet.
+    #[error("the rounds of the agreement instance {instance} do not fit in the round of a packet")]
+    InstanceTooLarge { instance: u32 },
+}
+
+/// Messages exchanged during a phase of the binary agreement.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum AgreementMessage {
+    /// Value of a party at the start of the phase.
+    Vote(bool),
+    /// Value voted by at least `n - t` parties, if there is one.
+    Propose(Option<bool>),
+    /// Value of a party after the proposals, of which only the one of the king is used.
+    King(bool),
+}
+
+/// Runs a binary agreement with the phase-king protocol, in which every party proposes a bit and
+/// all the honest parties output the same bit. If every honest party proposes the same bit, the
+/// output is that bit, so a decision like "continue vs abort" is only taken when the honest
+/// parties propose it unanimously.
+///
+/// The protocol tolerates `threshold` corrupted parties as long as the number of parties is at
+/// least `3 * threshold + 1`. It runs `threshold + 1` phases of three rounds, where the king of
+/// each phase is the party with the ID of the phase, so at least one of them is honest. Each
+/// round waits at most `round_timeout` for the message of every party, and a message that does
+/// not arrive in time, or is malformed, is taken as the default one, so a silent party can not
+/// block the agreement. The protocol assumes that the messages of the honest parties arrive
+/// within the timeout.
+///
+/// Each execution is identified by `instance`, which must increase between executions.
+pub fn agree_on_bit(
+    network: &mut Network,
+    instance: u32,
+    bit: bool,
+    threshold: usize,
+    round_timeout: Duration,
+) -> anyhow::Result<bool> {
+    let n_parties = network.n_parties();
+    if n_parties < 3 * threshold + 1 {
+        anyhow::bail!(AgreementError::NotEnoughParties {
+            n_parties,
+            threshold,
+        });
+    }
+    let n_rounds = (threshold as u32 + 1) * ROUNDS_PER_PHASE;
+    let first_round = instance
+        .checked_mul(n_rounds)
+        .ok_or(AgreementError::InstanceTooLarge { instance })?;
+
+    let mut value = bit;
+    for (phase, round) in (first_round..first_round + n_rounds)
+        .step_by(ROUNDS_PER_PHASE as usize)
+        .enumerate()
+    {
+        let votes = exchange(
+            network,
+            round,
+            &AgreementMessage::Vote(value),
+            round_timeout,
+        )?;
+        let votes_for_true = votes
+            .iter()
+            .filter(|vote| matches!(vote, Some(AgreementMessage::Vote(true))))
+            .count();
+        let proposal = if votes_for_true >= n_parties - threshold {
+            Some(true)
+        } else if n_parties - votes_for_true >= n_parties - threshold {
+            Some(false)
+        } else {
+            None
+        };
+
+        let proposals = exchange(
+            network,
+            round + 1,
+            &AgreementMessage::Propose(proposal),
+            round_timeout,
+        )?;
+        // Only one bit can be proposed by more than `t` parties, since the honest parties that
+        // propose a bit saw `n - t` votes for it.
+        let mut strong = false;
+        for candidate in [false, true] {
+            let count = proposals
+                .iter()
+                .filter(|proposal| {
+                    matches!(proposal, Some(AgreementMessage::Propose(Some(bit))) if *bit == candidate)
+                })
+                .count();
+            if count > threshold {
+                value = candidate;
+                strong = count >= n_parties - threshold;
+            }
+        }
+
+        let kings = exchange(
+            network,
+            round + 2,
+            &AgreementMessage::King(value),
+            round_timeout,
+        )?;
+        let king_value = matches!(kings[phase], Some(AgreementMessage::King(true)));
+        if !strong {
+            value = king_value;
+        }
+    }
+    log::info!("agreement instance {instance} decided {value}");
+    Ok(value)
+}
+
+/// Sends a message of the binary agreement to every party and gathers the message of every
+/// party in the same round, waiting at most `round_timeout`. The messages that do not arrive in
+/// time or are malformed are `None`.
+fn exchange(
+    network: &mut Network,
+    round: u32,
+    message: &AgreementMessage,
+    round_timeout: Duration,
+) -> anyhow::Result<Vec<Option<AgreementMessage>>> {
+    let codec = network.codec();
+    let packet = Packet::tagged(AGREEMENT_PROTOCOL_ID, round, codec.encode(message)?);
+    let gathered = network.broadcast_and_gather_within(&packet, round_timeout)?;
+    Ok(gathered
+        .into_iter()
+        .enumerate()
+        .map(|(party_id, packet)| {
+            let packet = packet?;
+            codec
+                .decode(packet.as_slice())
+                .map_err(|err| {
+                    log::warn!("ignoring malformed agreement message from party {party_id}: {err}");
+                })
+                .ok()
+        })
+        .collect())
+}
+
+/// Runs an agreement on a common subset, in which every party contributes a value and all the
+/// honest parties agree on the same vector of values, where the `i`-th entry is the value of
+/// party `i`, or `None` if the value of the party was left out. The values of the honest parties
+/// are included as long as their broadcasts deliver within the timeout.
+///
+/// Every party broadcasts its value with the reliable broadcast, and the parties wait for the
+/// broadcasts at most three round timeouts. Then, for each party, they run a binary agreement
+/// on whether they received its value, and the values agreed on are included. A value included
+/// was received by some honest party, so the broadcast eventually delivers it to every honest
+/// party, which waits for it without timeout.
+///
+/// The broadcasts use the instances `first_instance..first_instance + n_parties` and so do the
+/// binary agreements, hence the next broadcast or agreement executed by the parties must use an
+/// instance greater than these.
+pub fn agree_on_values(
+    network: &mut Network,
+    first_instance: u32,
+    value: Vec<u8>,
+    threshold: usize,
+    round_timeout: Duration,
+) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+    let n_parties = network.n_parties();
+    let mut broadcasts = ConcurrentBroadcasts::start(network, first_instance, value, threshold)?;
+    let until = Instant::now().checked_add(round_timeout.saturating_mul(BROADCAST_ROUNDS));
+    broadcasts.run(network, until, |delivered| {
+        delivered.iter().all(Option::is_some)
+    })?;
+
+    let mut included = Vec::with_capacity(n_parties);
+    for sender in 0..n_parties {
+        let received = broadcasts.delivered()[sender].is_some();
+        let instance = first_instance + sender as u32;
+        included.push(agree_on_bit(
+            network,
+            instance,
+            received,
+            threshold,
+            round_timeout,
+        )?);
+    }
+
+    broadcasts.run(network, None, |delivered| {
+        included
+            .iter()
+            .zip(delivered)
+            .all(|(included, delivered)| !included || delivered.is_some())
+    })?;
+    Ok(broadcasts
+        .into_delivered()
+        .into_iter()
+        .zip(included)
+        .map(|(delivered, included)| delivered.filter(|_| included))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::{agree_on_bit, agree_on_values, AgreementError};
+    use crate::{
+        math::mersenne61::Mersenne61,
+        mpc::adversary::{Adversary, Deviation},
+        net::{channel::LoopBackChannel, Network},
+    };
+
+    const ROUND_TIMEOUT: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn agreement_single_party() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        let values = agree_on_values(&mut network, 0, vec![7], 0, ROUND_TIMEOUT).unwrap();
+        assert_eq!(values, vec![Some(vec![7])]);
+        assert!(agree_on_bit(&mut network, 1, true, 0, ROUND_TIMEOUT).unwrap());
+        assert!(!agree_on_bit(&mut network, 2, false, 0, ROUND_TIMEOUT).unwrap());
+    }
+
+    #[test]
+    fn agreement_rejects_large_threshold() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        let err = agree_on_bit(&mut network, 0, true, 1, ROUND_TIMEOUT).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AgreementError>(),
+            Some(&AgreementError::NotEnoughParties {
+                n_parties: 1,
+                threshold: 1
+            })
+        );
+    }
+
+    #[test]
+    fn agreement_local_network() {
+        let votes = [true, true, false, true];
+        let handles: Vec<_> = Network::create_local(votes.len())
+            .into_iter()
+            .enumerate()
+            .map(|(id, mut network)| {
+                thread::spawn(move || {
+                    agree_on_bit(&mut network, 0, votes[id], 1, ROUND_TIMEOUT).unwrap()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn agreement_tolerates_silent_and_equivocating_parties() {
+        const N_PARTIES: usize = 7;
+        const THRESHOLD: usize = 2;
+        // The corrupted parties are the kings of the first phases.
+        const SILENT: usize = 0;
+        const EQUIVOCATING: usize = 1;
+        const VICTIM: usize = 4;
+
+        let mut networks = Network::create_local(N_PARTIES);
+        // The silent party keeps its connections open without sending anything.
+        let silent = networks.remove(SILENT);
+        let handles: Vec<_> = networks
+            .into_iter()
+            .map(|mut network| {
+                thread::spawn(move || {
+                    let id = network.id();
+                    if id == EQUIVOCATING {
+                        let adversary = Adversary::corrupt::<Mersenne61>(&mut network);
+                        adversary.set(Deviation::Equivocate(VICTIM));
+                    }
+                    let values =
+                        agree_on_values(&mut network, 0, vec![id as u8], THRESHOLD, ROUND_TIMEOUT)
+                            .unwrap();
+                    (id, values)
+                })
+            })
+            .collect();
+
+        let mut outputs = Vec::new();
+        for handle in handles {
+            let (id, values) = handle.join().unwrap();
+            if id != EQUIVOCATING {
+                outputs.push(values);
+            }
+        }
+        drop(silent);
+
+        assert_eq!(outputs.len(), N_PARTIES - 2);
+        assert!(outputs.iter().all(|values| *values == outputs[0]));
+        let values = &outputs[0];
+        assert_eq!(values[SILENT], None);
+        for id in (0..N_PARTIES).filter(|id| ![SILENT, EQUIVOCATING].contains(id)) {
+            assert_eq!(values[id], Some(vec![id as u8]));
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use aws_lc_rs::digest::{self, SHA256};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Hash that the first entry of an audit log chains to.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Possible errors that may appear while verifying an audit log.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AuditError {
+    /// A line of the log is not an entry.
+    #[error("line {0} of the audit log is not a valid entry")]
+    Malformed(usize),
+
+    /// An entry does not have the index that follows the one of the previous entry, so entries
+    /// were removed or reordered.
+    #[error("the entry at line {line} has index {index}, but index {expected} was expected")]
+    BrokenSequence {
+        line: usize,
+        index: u64,
+        expected: u64,
+    },
+
+    /// An entry does not chain to the hash of the previous entry, so entries were removed or
+    /// replaced.
+    #[error("the entry {0} does not chain to the previous entry")]
+    BrokenChain(u64),
+
+    /// The hash of an entry does not match its contents, so the entry was modified.
+    #[error("the hash of the entry {0} does not match its contents")]
+    Tampered(u64),
+}
+
+/// Security-relevant event recorded in an audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// The connection with a peer was established, and the peer proved its identity in the
+    /// handshake if the transport is authenticated.
+    PeerConnected {
+        party_id: usize,
+        transport: String,
+        authenticated: bool,
+    },
+    /// The parameters of a protocol run by the party.
+    Parameters {
+        protocol: String,
+        modulus: String,
+        n_parties: usize,
+        threshold: usize,
+    },
+    /// A check of the protocol failed, either on a packet of a given peer or on the values
+    /// received from several of them.
+    CheckFailed {
+        party_id: Option<usize>,
+        reason: String,
+    },
+    /// A peer was identified as misbehaving and disconnected.
+    CheaterIdentified { party_id: usize, reason: String },
+    /// The keys that protect the channel of a peer were rotated.
+    KeysRotated { party_id: usize },
+    /// The trusted dealer sent to a party the shares of the given numbers of inputs, Beaver
+    /// triples and random bits.
+    Dealt {
+        party_id: usize,
+        inputs: usize,
+        triples: usize,
+        bits: usize,
+    },
+    /// A peer announced that it aborted the protocol.
+    PeerAborted { party_id: usize },
+    /// A round of the openings proceeded without the messages of some parties, which did not
+    /// arrive before the deadline.
+    PartiesAbsent { round: u32, parties: Vec<usize> },
+    /// The execution did not finish within its deadline of the given milliseconds, so the party
+    /// aborts the protocol.
+    DeadlineExceeded { deadline_ms: u64 },
+    /// The party aborted the protocol.
+    Aborted,
+}
+
+/// Contents of an entry of an audit log that are covered by its hash.
+#[derive(Serialize)]
+struct EntryBody<'a> {
+    index: u64,
+    timestamp_micros: u64,
+    party: usize,
+    event: &'a AuditEvent,
+    previous: &'a str,
+}
+
+/// Entry of an audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position of the entry in the log, starting at zero.
+    pub index: u64,
+    /// Time elapsed since the UNIX epoch in microseconds when the event was recorded.
+    pub timestamp_micros: u64,
+    /// ID of the party that recorded the event.
+    pub party: usize,
+    /// Event recorded.
+    pub event: AuditEvent,
+    /// Hash of the previous entry, in hexadecimal.
+    pub previous: String,
+    /// SHA-256 hash of the previous hash and the contents of the entry, in hexadecimal.
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Computes the hash that chains the entry to the previous one.
+    fn compute_hash(&self) -> String {
+        let body = EntryBody {
+            index: self.index,
+            timestamp_micros: self.timestamp_micros,
+            party: self.party,
+            event: &self.event,
+            previous: &self.previous,
+        };
+        // Serializing a struct of strings and integers can not fail.
+        let bytes = serde_json::to_vec(&body).expect("the entry can be serialized");
+        digest::digest(&SHA256, &bytes)
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
+/// Append-only log of the security-relevant events of a party, so incidents can be investigated
+/// after the execution. Each line of the file is an [`AuditEntry`] in JSON, whose hash covers
+/// the hash of the previous entry, so removing, reordering or modifying entries breaks the chain
+/// and is detected by [`verify`].
+pub struct AuditLog {
+    /// File to which the entries are appended.
+    file: File,
+    /// ID of the party that records the events.
+    party: usize,
+    /// Index of the next entry.
+    next_index: u64,
+    /// Hash of the last entry.
+    last_hash: String,
+}
+
+impl AuditLog {
+    /// Opens the audit log at the given path for the party with the given ID. If the file
+    /// already exists, its chain is verified and the new entries continue it.
+    pub fn open(path: &Path, party: usize) -> anyhow::Result<Self> {
+        let entries = if path.exists() {
+            verify(path)?
+        } else {
+            Vec::new()
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (next_index, last_hash) = match entries.last() {
+            Some(entry) => (entry.index + 1, entry.hash.clone()),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        Ok(Self {
+            file,
+            party,
+            next_index,
+            last_hash,
+        })
+    }
+
+    /// Appends an event to the log, and writes it to the disk before returning.
+    pub fn record(&mut self, event: AuditEvent) -> anyhow::Result<()> {
+        let timestamp_micros =
+            u64::try_from(SystemTime::now().duration_since(UNIX_EPOCH)?.as_micros())?;
+        let mut entry = AuditEntry {
+            index: self.next_index,
+            timestamp_micros,
+            party: self.party,
+            event,
+            previous: self.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+        self.next_index += 1;
+        self.last_hash = entry.hash;
+        Ok(())
+    }
+}
+
+/// Audit log shared by the parts of a network that record events.
+pub type SharedAuditLog = Arc<Mutex<AuditLog>>;
+
+/// Reads the audit log at the given path and checks that its entries form an unbroken chain.
+/// Returns the entries of the log.
+pub fn verify(path: &Path) -> anyhow::Result<Vec<AuditEntry>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries: Vec<AuditEntry> = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line_number = i + 1;
+        let entry: AuditEntry =
+            serde_json::from_str(&line?).map_err(|_| AuditError::Malformed(line_number))?;
+        let (expected_index, expected_previous) = match entries.last() {
+            Some(last) => (last.index + 1, last.hash.as_str()),
+            None => (0, GENESIS_HASH),
+        };
+        if entry.index != expected_index {
+            anyhow::bail!(AuditError::BrokenSequence {
+                line: line_number,
+                index: entry.index,
+                expected: expected_index,
+            });
+        }
+        if entry.previous != expected_previous {
+            anyhow::bail!(AuditError::BrokenChain(entry.index));
+        }
+        if entry.hash != entry.compute_hash() {
+            anyhow::bail!(AuditError::Tampered(entry.index));
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{verify, AuditError, AuditEvent, AuditLog};
+
+    #[test]
+    fn the_chain_detects_tampering() {
+        let path = std::env::temp_dir().join(format!("shami_rs_audit_{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut log = AuditLog::open(&path, 1).unwrap();
+        log.record(AuditEvent::PeerConnected {
+            party_id: 0,
+            transport: "tls".to_string(),
+            authenticated: true,
+        })
+        .unwrap();
+        log.record(AuditEvent::CheaterIdentified {
+            party_id: 0,
+            reason: "flooding".to_string(),
+        })
+        .unwrap();
+        drop(log);
+        // Reopening the log continues the chain.
+        let mut log = AuditLog::open(&path, 1).unwrap();
+        log.record(AuditEvent::Aborted).unwrap();
+        drop(log);
+
+        let entries = verify(&path).unwrap();
+        let indices: Vec<u64> = entries.iter().map(|entry| entry.index).collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert_eq!(entries[2].event, AuditEvent::Aborted);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        fs::write(&path, contents.replace("flooding", "nothing")).unwrap();
+        let err = verify(&path).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&AuditError::Tampered(1)));
+
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        let err = verify(&path).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&AuditError::BrokenSequence {
+                line: 2,
+                index: 2,
+                expected: 1
+            })
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+}
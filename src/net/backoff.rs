@@ -0,0 +1,256 @@
+use std::{
+    io::{Error, ErrorKind},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+use serde_json::Value;
+
+use super::channel::ChannelError;
+
+/// Factor by which the delay grows after each attempt if it is not configured.
+const DEFAULT_MULTIPLIER: f64 = 2.0;
+
+/// Policy that governs how the connection with a peer is retried while the peer is not
+/// listening yet. The delay between attempts grows exponentially up to a maximum, and it is
+/// randomized with some jitter so the parties do not retry at the same time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Time during which the connection is retried.
+    pub timeout: Duration,
+    /// Delay before the second attempt.
+    pub initial_delay: Duration,
+    /// Maximum delay between two attempts.
+    pub max_delay: Duration,
+    /// Factor by which the delay grows after each attempt.
+    pub multiplier: f64,
+    /// Fraction of the delay that is randomized, between 0 and 1. A jitter `j` makes the delay
+    /// `d` be chosen uniformly in `[d * (1 - j), d * (1 + j)]`.
+    pub jitter: f64,
+    /// Maximum number of attempts. If it is not present, the connection is retried until the
+    /// timeout is reached.
+    pub max_attempts: Option<u32>,
+}
+
+impl Backoff {
+    /// Creates a policy that retries during the timeout waiting always the same sleep time
+    /// between attempts.
+    pub fn fixed(timeout: Duration, sleep_time: Duration) -> Self {
+        Self {
+            timeout,
+            initial_delay: sleep_time,
+            max_delay: sleep_time,
+            multiplier: 1.0,
+            jitter: 0.0,
+            max_attempts: None,
+        }
+    }
+
+    /// Reads the policy from the `backoff` object of the network configuration, where the
+    /// `sleep_time` of the configuration is the initial delay. If the object is not present, the
+    /// sleep time is fixed.
+    pub(crate) fn from_json(
+        json: &Value,
+        timeout: Duration,
+        sleep_time: Duration,
+    ) -> anyhow::Result<Self> {
+        let mut backoff = Self::fixed(timeout, sleep_time);
+        if json.is_null() {
+            return Ok(backoff);
+        }
+        if !json.is_object() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the backoff configuration is not correct",
+            )
+            .into());
+        }
+
+        backoff.multiplier = match &json["multiplier"] {
+            Value::Null => DEFAULT_MULTIPLIER,
+            value => value
+                .as_f64()
+                .filter(|multiplier| *multiplier >= 1.0)
+                .ok_or(Error::new(
+                    ErrorKind::InvalidInput,
+                    "the backoff multiplier is not correct",
+                ))?,
+        };
+        backoff.max_delay = match &json["max_delay"] {
+            Value::Null => timeout,
+            value => Duration::from_millis(value.as_u64().ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                "the maximum backoff delay is not correct",
+            ))?),
+        };
+        backoff.jitter = match &json["jitter"] {
+            Value::Null => 0.0,
+            value => value
+                .as_f64()
+                .filter(|jitter| (0.0..=1.0).contains(jitter))
+                .ok_or(Error::new(
+                    ErrorKind::InvalidInput,
+                    "the backoff jitter is not correct",
+                ))?,
+        };
+        backoff.max_attempts = match &json["max_attempts"] {
+            Value::Null => None,
+            value => Some(
+                value
+                    .as_u64()
+                    .and_then(|attempts| u32::try_from(attempts).ok())
+                    .filter(|attempts| *attempts > 0)
+                    .ok_or(Error::new(
+                        ErrorKind::InvalidInput,
+                        "the maximum number of connection attempts is not correct",
+                    ))?,
+            ),
+        };
+        Ok(backoff)
+    }
+
+    /// Starts counting the attempts of a connection.
+    pub fn start(&self) -> Attempts<'_> {
+        Attempts {
+            backoff: self,
+            start_time: Instant::now(),
+            attempts: 1,
+            delay: self.initial_delay.min(self.max_delay),
+        }
+    }
+
+    /// Returns the delay before the attempt that follows the given number of attempts, without
+    /// jitter.
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let factor = self.multiplier.powi(attempts.saturating_sub(1) as i32);
+        self.initial_delay
+            .mul_f64(factor.min(u32::MAX as f64))
+            .min(self.max_delay)
+    }
+}
+
+/// Attempts of a connection made with a [`Backoff`] policy.
+pub struct Attempts<'a> {
+    /// Policy that governs the attempts.
+    backoff: &'a Backoff,
+    /// Instant of the first attempt.
+    start_time: Instant,
+    /// Number of attempts made so far.
+    attempts: u32,
+    /// Delay before the next attempt, without jitter.
+    delay: Duration,
+}
+
+impl Attempts<'_> {
+    /// Returns the number of attempts made so far.
+    pub fn count(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Returns the error that explains why no more attempts can be made.
+    pub fn error(&self) -> ChannelError {
+        match self.backoff.max_attempts {
+            Some(max_attempts) if self.attempts >= max_attempts => {
+                ChannelError::AttemptsExhausted(self.attempts)
+            }
+            _ => ChannelError::Timeout,
+        }
+    }
+
+    /// Waits before the next attempt after a failed one. Returns `false` without waiting if the
+    /// timeout or the maximum number of attempts was reached, in which case no more attempts
+    /// must be made.
+    pub fn wait(&mut self) -> bool {
+        if self.start_time.elapsed() > self.backoff.timeout {
+            return false;
+        }
+        if let Some(max_attempts) = self.backoff.max_attempts {
+            if self.attempts >= max_attempts {
+                return false;
+            }
+        }
+
+        let jitter = self.backoff.jitter;
+        let delay = if jitter > 0.0 {
+            self.delay
+                .mul_f64(rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter))
+        } else {
+            self.delay
+        };
+        thread::sleep(delay);
+
+        self.attempts += 1;
+        self.delay = self.backoff.delay(self.attempts);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::Backoff;
+    use crate::net::channel::ChannelError;
+
+    #[test]
+    fn delay_grows_up_to_the_maximum() {
+        let backoff = Backoff::from_json(
+            &json!({"multiplier": 2.0, "max_delay": 500}),
+            Duration::from_secs(5),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+        let delays: Vec<_> = (1..=5).map(|attempts| backoff.delay(attempts)).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 500, 500]
+                .map(Duration::from_millis)
+                .to_vec()
+        );
+
+        let fixed = Backoff::from_json(
+            &serde_json::Value::Null,
+            Duration::from_secs(5),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+        assert_eq!(fixed.delay(10), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn stop_after_the_maximum_attempts() {
+        let backoff = Backoff::from_json(
+            &json!({"max_attempts": 3, "jitter": 0.5}),
+            Duration::from_secs(5),
+            Duration::from_millis(1),
+        )
+        .unwrap();
+        let mut attempts = backoff.start();
+        assert!(attempts.wait());
+        assert!(attempts.wait());
+        assert!(!attempts.wait());
+        assert_eq!(attempts.count(), 3);
+        assert!(matches!(
+            attempts.error(),
+            ChannelError::AttemptsExhausted(3)
+        ));
+    }
+
+    #[test]
+    fn reject_invalid_configuration() {
+        let timeout = Duration::from_secs(5);
+        let sleep_time = Duration::from_millis(100);
+        for json in [
+            json!({"multiplier": 0.5}),
+            json!({"jitter": 2.0}),
+            json!({"max_attempts": 0}),
+            json!({"max_delay": "1s"}),
+            json!(3),
+        ] {
+            assert!(Backoff::from_json(&json, timeout, sleep_time).is_err());
+        }
+    }
+}
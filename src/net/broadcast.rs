@@ -0,0 +1,526 @@
+use std::{collections::HashMap, time::Instant};
+
+use aws_lc_rs::digest::{self, SHA256};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{audit::AuditEvent, channel::is_connection_closed, Disposition, Network, Packet};
+use crate::codec::WireCodec;
+
+/// Protocol ID used to tag the packets of the reliable broadcast.
+pub const BROADCAST_PROTOCOL_ID: u16 = 1;
+
+/// Protocol ID used to tag the packets of the echo broadcast.
+pub const ECHO_BROADCAST_PROTOCOL_ID: u16 = 5;
+
+/// Possible errors that may appear in a broadcast.
+#[derive(Debug, Error)]
+pub enum BroadcastError {
+    /// The reliable broadcast only tolerates less than a third of corrupted parties.
+    #[error("reliable broadcast needs at least {} parties to tolerate {threshold} corruptions, but there are {n_parties}", 3 * threshold + 1)]
+    NotEnoughParties { n_parties: usize, threshold: usize },
+
+    /// A party echoed a digest of the message of the sender that differs from the one that this
+    /// party received, so either the sender sent different messages or the party lied.
+    #[error(
+        "party {party_id} echoed a different message of the sender {sender} of the echo broadcast"
+    )]
+    Equivocation { sender: usize, party_id: usize },
+}
+
+/// Messages exchanged during the reliable broadcast.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum BroadcastMessage {
+    /// Message sent by the sender to start the broadcast.
+    Initial(Vec<u8>),
+    /// Message sent by every party after receiving the initial message.
+    Echo(Vec<u8>),
+    /// Message sent by every party once it knows that enough parties echoed the same value.
+    Ready(Vec<u8>),
+}
+
+/// State of a party during an execution of the reliable broadcast.
+struct BroadcastState {
+    /// Whether the party already sent its echo message.
+    echo_sent: bool,
+    /// Whether the party already sent its ready message.
+    ready_sent: bool,
+    /// Parties from which an echo message was already received.
+    echo_from: Vec<bool>,
+    /// Parties from which a ready message was already received.
+    ready_from: Vec<bool>,
+    /// Number of echo messages received for each value.
+    echo_count: HashMap<Vec<u8>, usize>,
+    /// Number of ready messages received for each value.
+    ready_count: HashMap<Vec<u8>, usize>,
+}
+
+impl BroadcastState {
+    fn new(n_parties: usize) -> Self {
+        Self {
+            echo_sent: false,
+            ready_sent: false,
+            echo_from: vec![false; n_parties],
+            ready_from: vec![false; n_parties],
+            echo_count: HashMap::new(),
+            ready_count: HashMap::new(),
+        }
+    }
+
+    /// Processes a message of the instance received from a party, sending the echo and ready
+    /// messages that it triggers. Returns the message of the sender once enough parties are
+    /// ready to deliver it.
+    fn handle(
+        &mut self,
+        network: &mut Network,
+        instance: u32,
+        sender: usize,
+        threshold: usize,
+        party_id: usize,
+        message: BroadcastMessage,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let n_parties = self.echo_from.len();
+        let echo_threshold = (n_parties + threshold + 2) / 2;
+        match message {
+            BroadcastMessage::Initial(value) => {
+                if party_id == sender && !self.echo_sent {
+                    self.echo_sent = true;
+                    send_message(network, instance, &BroadcastMessage::Echo(value))?;
+                }
+            }
+            BroadcastMessage::Echo(value) => {
+                if !self.echo_from[party_id] {
+                    self.echo_from[party_id] = true;
+                    let count = self.echo_count.entry(value.clone()).or_insert(0);
+                    *count += 1;
+                    if *count >= echo_threshold && !self.ready_sent {
+                        self.ready_sent = true;
+                        send_message(network, instance, &BroadcastMessage::Ready(value))?;
+                    }
+                }
+            }
+            BroadcastMessage::Ready(value) => {
+                if !self.ready_from[party_id] {
+                    self.ready_from[party_id] = true;
+                    let count = self.ready_count.entry(value.clone()).or_insert(0);
+                    *count += 1;
+                    let count = *count;
+                    if count > threshold && !self.ready_sent {
+                        self.ready_sent = true;
+                        send_message(network, instance, &BroadcastMessage::Ready(value.clone()))?;
+                    }
+                    if count > 2 * threshold {
+                        return Ok(Some(value));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Messages exchanged during the echo broadcast.
+#[derive(Debug, Serialize, Deserialize)]
+enum EchoMessage {
+    /// Message sent by the sender to every party.
+    Value(Vec<u8>),
+    /// SHA-256 digest of the message received from the sender, sent by every party.
+    Echo([u8; 32]),
+}
+
+/// Sends a broadcast message to all the parties, including the current one. Parties that already
+/// closed their connection are skipped, given that they already finished the protocol.
+fn send_message(
+    network: &mut Network,
+    instance: u32,
+    message: &BroadcastMessage,
+) -> anyhow::Result<()> {
+    let message_bytes = network.codec().encode(message)?;
+    send_to_all(
+        network,
+        &Packet::tagged(BROADCAST_PROTOCOL_ID, instance, message_bytes),
+    )
+}
+
+/// Sends a packet to all the parties, including the current one, skipping the parties that
+/// already closed their connection.
+fn send_to_all(network: &mut Network, packet: &Packet) -> anyhow::Result<()> {
+    for party_id in 0..network.peer_channels.len() {
+        match network.send_to(packet, party_id) {
+            Ok(_) => {}
+            Err(err) if is_connection_closed(&err) => {
+                log::debug!("skipping broadcast message to party {party_id}: {err}");
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Runs an execution of Bracha's reliable broadcast in which the party `sender` broadcasts a
+/// message to all the parties. The sender must provide `Some(message)`, and the rest of the parties
+/// must provide `None`. When the function returns, all the honest parties obtain the same message,
+/// even if the sender is corrupted.
+///
+/// The protocol tolerates `threshold` corrupted parties as long as the number of parties is at
+/// least `3 * threshold + 1`. Each execution is identified by `instance`, which must increase
+/// between executions: messages that arrive late from earlier instances are discarded.
+pub fn reliable_broadcast(
+    network: &mut Network,
+    sender: usize,
+    instance: u32,
+    message: Option<Vec<u8>>,
+    threshold: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let n_parties = network.peer_channels.len();
+    check_parties(n_parties, threshold)?;
+
+    if let Some(message) = message {
+        log::info!("starting reliable broadcast instance {instance} as the sender");
+        send_message(network, instance, &BroadcastMessage::Initial(message))?;
+    }
+
+    let mut state = BroadcastState::new(n_parties);
+    loop {
+        let (party_id, packet) = network.recv_any_matching(|header| {
+            if header.protocol_id != BROADCAST_PROTOCOL_ID || header.round > instance {
+                Ok(Disposition::Buffer)
+            } else if header.round < instance {
+                Ok(Disposition::Discard)
+            } else {
+                Ok(Disposition::Accept)
+            }
+        })?;
+        let Some(message) = decode_message(network, party_id, &packet) else {
+            continue;
+        };
+        if let Some(value) =
+            state.handle(network, instance, sender, threshold, party_id, message)?
+        {
+            log::info!("delivered reliable broadcast instance {instance}");
+            return Ok(value);
+        }
+    }
+}
+
+/// Executions of the reliable broadcast run at the same time, one per party, where the party `i`
+/// is the sender of the instance `first_instance + i`. A party waits for the messages of every
+/// instance at once, so a sender that stays silent does not hold back the instances of the
+/// others.
+pub(crate) struct ConcurrentBroadcasts {
+    /// Instance of the broadcast of the party 0.
+    first_instance: u32,
+    /// Number of corrupted parties tolerated.
+    threshold: usize,
+    /// State of the execution of each instance.
+    states: Vec<BroadcastState>,
+    /// Message delivered by each instance, if it was delivered already.
+    delivered: Vec<Option<Vec<u8>>>,
+}
+
+impl ConcurrentBroadcasts {
+    /// Starts the executions, in which this party broadcasts `message`. The broadcasts use the
+    /// instances `first_instance..first_instance + n_parties`.
+    pub(crate) fn start(
+        network: &mut Network,
+        first_instance: u32,
+        message: Vec<u8>,
+        threshold: usize,
+    ) -> anyhow::Result<Self> {
+        let n_parties = network.peer_channels.len();
+        check_parties(n_parties, threshold)?;
+        let instance = first_instance + network.id() as u32;
+        log::info!("starting reliable broadcast instance {instance} as the sender");
+        send_message(network, instance, &BroadcastMessage::Initial(message))?;
+        Ok(Self {
+            first_instance,
+            threshold,
+            states: (0..n_parties)
+                .map(|_| BroadcastState::new(n_parties))
+                .collect(),
+            delivered: vec![None; n_parties],
+        })
+    }
+
+    /// Processes the messages of the executions until `done` holds for the messages delivered
+    /// so far, or until the instant `until`, if any, is reached.
+    pub(crate) fn run<F>(
+        &mut self,
+        network: &mut Network,
+        until: Option<Instant>,
+        done: F,
+    ) -> anyhow::Result<()>
+    where
+        F: Fn(&[Option<Vec<u8>>]) -> bool,
+    {
+        let first_instance = self.first_instance;
+        let last_instance = first_instance + self.states.len() as u32 - 1;
+        while !done(&self.delivered) {
+            let Some((party_id, packet)) = network.recv_any_matching_until(until, |header| {
+                if header.protocol_id != BROADCAST_PROTOCOL_ID || header.round > last_instance {
+                    Ok(Disposition::Buffer)
+                } else if header.round < first_instance {
+                    Ok(Disposition::Discard)
+                } else {
+                    Ok(Disposition::Accept)
+                }
+            })?
+            else {
+                return Ok(());
+            };
+            let instance = packet.header().round;
+            let Some(message) = decode_message(network, party_id, &packet) else {
+                continue;
+            };
+            let sender = (instance - first_instance) as usize;
+            let delivered = self.states[sender].handle(
+                network,
+                instance,
+                sender,
+                self.threshold,
+                party_id,
+                message,
+            )?;
+            if let (Some(value), None) = (delivered, &self.delivered[sender]) {
+                log::info!("delivered reliable broadcast instance {instance}");
+                self.delivered[sender] = Some(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the message delivered by the instance of each sender, if it was delivered.
+    pub(crate) fn delivered(&self) -> &[Option<Vec<u8>>] {
+        &self.delivered
+    }
+
+    /// Returns the messages delivered so far, indexed by sender.
+    pub(crate) fn into_delivered(self) -> Vec<Option<Vec<u8>>> {
+        self.delivered
+    }
+}
+
+/// Checks that the reliable broadcast tolerates `threshold` corruptions among the parties.
+fn check_parties(n_parties: usize, threshold: usize) -> Result<(), BroadcastError> {
+    if n_parties < 3 * threshold + 1 {
+        return Err(BroadcastError::NotEnoughParties {
+            n_parties,
+            threshold,
+        });
+    }
+    Ok(())
+}
+
+/// Decodes a message of the reliable broadcast, or returns `None` if it is malformed.
+fn decode_message(network: &Network, party_id: usize, packet: &Packet) -> Option<BroadcastMessage> {
+    match network.codec().decode(packet.as_slice()) {
+        Ok(message) => Some(message),
+        Err(err) => {
+            log::warn!("ignoring malformed broadcast message from party {party_id}: {err}");
+            None
+        }
+    }
+}
+
+/// Runs an execution of the echo broadcast in which the party `sender` broadcasts a message to
+/// all the parties. The sender must provide `Some(message)`, and the rest of the parties must
+/// provide `None`. Every party echoes the SHA-256 digest of the message that it received from the
+/// sender to every party, and compares the digests of the others with its own. If some digest
+/// differs, the sender sent different messages to different parties, or the party that echoed it
+/// lied, and the party aborts the protocol with [`BroadcastError::Equivocation`]. Otherwise,
+/// every honest party obtains the same message.
+///
+/// Unlike [`reliable_broadcast`], it works with any number of parties, at the cost of aborting
+/// instead of agreeing on a message when some party misbehaves, and it takes two rounds. Each
+/// execution is identified by `instance`, which must be different between executions.
+pub fn echo_broadcast(
+    network: &mut Network,
+    sender: usize,
+    instance: u32,
+    message: Option<Vec<u8>>,
+) -> anyhow::Result<Vec<u8>> {
+    let n_parties = network.peer_channels.len();
+    if let Some(message) = message {
+        log::debug!("starting echo broadcast instance {instance} as the sender");
+        let bytes = network.codec().encode(&EchoMessage::Value(message))?;
+        send_to_all(
+            network,
+            &Packet::tagged(ECHO_BROADCAST_PROTOCOL_ID, instance, bytes),
+        )?;
+    }
+
+    let mut value: Option<(Vec<u8>, [u8; 32])> = None;
+    let mut echoes: Vec<Option<[u8; 32]>> = vec![None; n_parties];
+    loop {
+        if let Some((message, digest)) = &value {
+            if let Some(party_id) = echoes
+                .iter()
+                .position(|echo| echo.is_some_and(|echo| echo != *digest))
+            {
+                return Err(equivocation(network, sender, party_id));
+            }
+            if echoes.iter().all(Option::is_some) {
+                log::debug!("delivered echo broadcast instance {instance}");
+                return Ok(message.clone());
+            }
+        }
+        let (party_id, packet) = network.recv_any_matching(|header| {
+            if header.protocol_id == ECHO_BROADCAST_PROTOCOL_ID && header.round == instance {
+                Ok(Disposition::Accept)
+            } else {
+                Ok(Disposition::Buffer)
+            }
+        })?;
+        match network.codec().decode(packet.as_slice())? {
+            EchoMessage::Value(message) if party_id == sender && value.is_none() => {
+                let digest = digest_message(sender, instance, &message);
+                let bytes = network.codec().encode(&EchoMessage::Echo(digest))?;
+                send_to_all(
+                    network,
+                    &Packet::tagged(ECHO_BROADCAST_PROTOCOL_ID, instance, bytes),
+                )?;
+                value = Some((message, digest));
+            }
+            EchoMessage::Value(_) => {
+                log::warn!("ignoring an unexpected echo broadcast message from party {party_id}");
+            }
+            EchoMessage::Echo(digest) => match echoes[party_id] {
+                // A party that echoes twice with different digests equivocates itself.
+                Some(echo) if echo != digest => {
+                    return Err(equivocation(network, sender, party_id));
+                }
+                _ => echoes[party_id] = Some(digest),
+            },
+        }
+    }
+}
+
+/// Records an equivocation detected in the echo broadcast of `sender` in the audit log and aborts
+/// the protocol, returning the error.
+fn equivocation(network: &mut Network, sender: usize, party_id: usize) -> anyhow::Error {
+    let err = BroadcastError::Equivocation { sender, party_id };
+    network.audit(AuditEvent::CheckFailed {
+        party_id: Some(sender),
+        reason: err.to_string(),
+    });
+    network.abort();
+    err.into()
+}
+
+/// Computes the digest of a message of the echo broadcast, bound to the sender and the instance.
+fn digest_message(sender: usize, instance: u32, message: &[u8]) -> [u8; 32] {
+    let mut context = digest::Context::new(&SHA256);
+    context.update(&(sender as u64).to_be_bytes());
+    context.update(&instance.to_be_bytes());
+    context.update(message);
+    let mut digest = [0; 32];
+    digest.copy_from_slice(context.finish().as_ref());
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::{
+        digest_message, echo_broadcast, reliable_broadcast, BroadcastError, EchoMessage,
+        ECHO_BROADCAST_PROTOCOL_ID,
+    };
+    use crate::{
+        codec::WireCodec,
+        net::{channel::LoopBackChannel, Network, Packet},
+    };
+
+    #[test]
+    fn broadcast_single_party() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        let message = reliable_broadcast(&mut network, 0, 0, Some(vec![1, 2, 3]), 0).unwrap();
+        assert_eq!(message, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn broadcast_local_network() {
+        const SENDER: usize = 2;
+        let handles: Vec<_> = Network::create_local(4)
+            .into_iter()
+            .enumerate()
+            .map(|(id, mut network)| {
+                thread::spawn(move || {
+                    let message = (id == SENDER).then(|| vec![4, 2]);
+                    let first = reliable_broadcast(&mut network, SENDER, 0, message.clone(), 1);
+                    let second = reliable_broadcast(&mut network, SENDER, 1, message, 1);
+                    (first.unwrap(), second.unwrap())
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), (vec![4, 2], vec![4, 2]));
+        }
+    }
+
+    #[test]
+    fn broadcast_rejects_large_threshold() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        assert!(reliable_broadcast(&mut network, 0, 0, Some(vec![1]), 1).is_err());
+    }
+
+    #[test]
+    fn echo_broadcast_delivers_the_same_message() {
+        const SENDER: usize = 1;
+        let handles: Vec<_> = Network::create_local(3)
+            .into_iter()
+            .enumerate()
+            .map(|(id, mut network)| {
+                thread::spawn(move || {
+                    let first = if id == SENDER {
+                        network.broadcast(0, vec![7, 1]).map(|()| vec![7, 1])
+                    } else {
+                        network.recv_broadcast(SENDER, 0)
+                    };
+                    let message = (id == SENDER).then(|| vec![3]);
+                    let second = echo_broadcast(&mut network, SENDER, 1, message);
+                    (first.unwrap(), second.unwrap())
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), (vec![7, 1], vec![3]));
+        }
+    }
+
+    #[test]
+    fn echo_broadcast_aborts_on_equivocation() {
+        const SENDER: usize = 0;
+        let mut networks = Network::create_local(3);
+        let mut sender = networks.remove(SENDER);
+        let handles: Vec<_> = networks
+            .into_iter()
+            .map(|mut network| thread::spawn(move || network.recv_broadcast(SENDER, 0)))
+            .collect();
+
+        // The sender gives each party a different message, and echoes the first one.
+        let codec = sender.codec();
+        for (party_id, message) in [(1, vec![1]), (2, vec![2])] {
+            let bytes = codec.encode(&EchoMessage::Value(message)).unwrap();
+            let packet = Packet::tagged(ECHO_BROADCAST_PROTOCOL_ID, 0, bytes);
+            sender.send_to(&packet, party_id).unwrap();
+        }
+        let echo = EchoMessage::Echo(digest_message(SENDER, 0, &[1]));
+        let bytes = codec.encode(&echo).unwrap();
+        for party_id in [1, 2] {
+            let packet = Packet::tagged(ECHO_BROADCAST_PROTOCOL_ID, 0, bytes.clone());
+            sender.send_to(&packet, party_id).unwrap();
+        }
+        sender.flush().unwrap();
+
+        let errors: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap().unwrap_err())
+            .collect();
+        // The party that detects the equivocation first aborts, which may stop the other one.
+        assert!(errors.iter().any(|err| matches!(
+            err.downcast_ref::<BroadcastError>(),
+            Some(BroadcastError::Equivocation { sender: SENDER, .. })
+        )));
+    }
+}
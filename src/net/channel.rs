@@ -1,18 +1,25 @@
-use crate::net::Packet;
+use crate::net::{
+    backoff::Backoff,
+    identity::{IdentityError, PeerIdentities},
+    stream::{StreamReceiver, StreamSender},
+    Packet, PacketHeader, PeerEndpoint,
+};
 use rustls::pki_types::ServerName;
 use rustls::{
-    ClientConfig, ClientConnection, ConnectionCommon, ServerConfig, ServerConnection, SideData,
-    StreamOwned,
+    ClientConfig, ClientConnection, ConnectionCommon, ProtocolVersion, ServerConfig,
+    ServerConnection, SideData, StreamOwned,
 };
 use std::collections::VecDeque;
 use std::io::{self, Read, Write};
-use std::ops::{Deref, DerefMut};
-use std::sync::Arc;
+use std::ops::{Deref, DerefMut, Range};
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
 use std::{
-    net::{SocketAddr, TcpListener, TcpStream},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
     time::{Duration, Instant},
 };
 use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
 
 /// Possible errors that may appear in a channel.
 #[derive(Debug, Error)]
@@ -21,88 +28,803 @@ pub enum ChannelError {
     #[error("connection timeout")]
     Timeout,
 
+    /// The party tried to connect to the other party the maximum number of times.
+    #[error("could not connect after {0} attempts")]
+    AttemptsExhausted(u32),
+
     /// Trying to read from a channel with no information.
     #[error("channel buffer is empty")]
     EmptyBuffer,
+
+    /// No packet arrived through the channel before the timeout was reached.
+    #[error("receive timeout reached after {0:?}")]
+    RecvTimeout(Duration),
+
+    /// The remote party closed the connection.
+    #[error("connection closed by the remote party")]
+    ConnectionClosed,
+
+    /// The remote party aborted the protocol, e.g. because its operator interrupted it.
+    #[error("peer {0} aborted")]
+    PeerAborted(usize),
+
+    /// The party was interrupted while waiting for a packet, so it aborted the protocol.
+    #[error("the party was interrupted")]
+    Interrupted,
+
+    /// The execution did not finish within its deadline, so the party aborted the protocol.
+    #[error("the execution exceeded its deadline of {0:?}")]
+    DeadlineExceeded(Duration),
+
+    /// The packet does not fit in the length prefix of the wire format.
+    #[error("the packet has {0} bytes, which exceeds the maximum size of the wire format")]
+    PacketTooLarge(usize),
+
+    /// The packet received announces a payload larger than the configured maximum.
+    #[error("the packet received has {size} bytes, but the maximum packet size is {limit} bytes")]
+    PacketExceedsLimit { size: usize, limit: usize },
+
+    /// The remote party encodes the packets with a different version of the wire format.
+    #[error("unsupported framing version {0}, expected version {FRAMING_VERSION}")]
+    UnsupportedFraming(u8),
+
+    /// The packet received does not have the expected sequence number.
+    #[error("packet out of order: expected sequence number {expected}, received {received}")]
+    OutOfOrder { expected: u64, received: u64 },
+
+    /// The header of the packet received names a sender other than the party at the other end
+    /// of the channel.
+    #[error("the packet received from party {party_id} claims to be sent by party {sender}")]
+    UnexpectedSender { party_id: usize, sender: usize },
+
+    /// The packet received belongs to a round that already finished.
+    #[error(
+        "stale packet for protocol {protocol_id} at round {round}, expected round {expected_round}"
+    )]
+    StalePacket {
+        protocol_id: u16,
+        round: u32,
+        expected_round: u32,
+    },
+}
+
+/// Event that tells when a packet may be received from a channel, which the background threads
+/// of the split and threaded channels wait for instead of polling the channel.
+#[derive(Clone)]
+pub enum Readiness {
+    /// A packet may be received once the file descriptor becomes readable.
+    Fd(RawFd),
+    /// A packet may be received once the signal is raised by the remote party.
+    Signal(Arc<Signal>),
+    /// Packets only arrive through the sends of the channel itself, as in a loopback channel.
+    Sends,
+    /// The readiness of the channel is unknown, e.g. because it retransmits or delays packets
+    /// on timers, so it must be polled periodically.
+    Polled,
+}
+
+/// Flag with which a thread waits until another one raises it, e.g. because it sent a packet
+/// through an in-memory channel.
+#[derive(Default)]
+pub struct Signal {
+    /// Whether the signal was raised since the last wait.
+    raised: Mutex<bool>,
+    /// Condition variable that wakes the waiting thread.
+    condvar: Condvar,
+}
+
+impl Signal {
+    /// Raises the signal, waking the thread that waits for it.
+    pub fn raise(&self) {
+        *self.raised.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.condvar.notify_all();
+    }
+
+    /// Waits until the signal is raised, or at most `timeout` if there is one, and lowers it.
+    pub fn wait(&self, timeout: Option<Duration>) {
+        let raised = self.raised.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut raised = match timeout {
+            Some(timeout) => {
+                self.condvar
+                    .wait_timeout_while(raised, timeout, |raised| !*raised)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            }
+            None => self
+                .condvar
+                .wait_while(raised, |raised| !*raised)
+                .unwrap_or_else(PoisonError::into_inner),
+        };
+        *raised = false;
+    }
 }
 
 /// Defines a channel of the network.
-pub trait Channel {
+pub trait Channel: Send {
     /// Closes a channel.
     fn shutdown(&mut self) -> anyhow::Result<()>;
     /// Send a packet using the current channel.
     fn send(&mut self, packet: &Packet) -> anyhow::Result<usize>;
     /// Receives a packet from the current channel.
     fn recv(&mut self) -> anyhow::Result<Packet>;
+    /// Receives a packet from the current channel into the given buffer, whose contents are
+    /// replaced by the payload, and returns the header of the packet. Reusing the same buffer
+    /// for every packet avoids allocating a new one each time. Channels that can not decode the
+    /// payload directly into the buffer copy it from the received packet.
+    fn recv_into(&mut self, buffer: &mut Vec<u8>) -> anyhow::Result<PacketHeader> {
+        let packet = self.recv()?;
+        buffer.clear();
+        buffer.extend_from_slice(packet.as_slice());
+        Ok(*packet.header())
+    }
+    /// Receives a packet from the current channel waiting at most the given timeout. If the
+    /// timeout is reached, the function returns [`ChannelError::RecvTimeout`].
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet>;
+    /// Receives a packet from the current channel if there is one ready, without blocking.
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>>;
+    /// Writes the packets buffered by previous sends to the connection. The channels that
+    /// buffer their sends also flush them before receiving and when the buffer fills up.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+    /// Returns the number of bytes buffered by previous sends that were not written to the
+    /// connection yet, which [`Channel::flush`] writes. Channels that do not buffer their sends
+    /// return zero.
+    fn buffered(&self) -> usize {
+        0
+    }
+    /// Sets the maximum size in bytes of the payload of a received packet. A packet that
+    /// announces a larger payload is rejected with [`ChannelError::PacketExceedsLimit`] before
+    /// its payload is buffered. Channels that do not receive through a stream ignore the limit.
+    fn set_max_packet_size(&mut self, _max_packet_size: usize) {}
+    /// Returns the event that tells when a packet may be received with [`Channel::try_recv`],
+    /// once it returned `None`.
+    fn readiness(&self) -> Readiness {
+        Readiness::Polled
+    }
+    /// Replaces the keys that protect the traffic of the channel by fresh ones, announcing it to
+    /// the remote party in band, so it switches to the new keys right after the packets already
+    /// sent. Returns whether the keys were rotated, which channels without encryption and TLS
+    /// connections before version 1.3 do not support.
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+    /// Fills `output` with keying material exported from the session of the channel under the
+    /// given label, as in RFC 5705, which the remote party derives as well without any message.
+    /// Returns whether the material was exported, which only TLS channels support.
+    fn export_keying_material(&self, _label: &[u8], _output: &mut [u8]) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+    /// Sends the bytes read from `reader` until its end as a stream of chunks of at most
+    /// `chunk_size` bytes, so a large payload never has to be in memory at once. The chunk size
+    /// must not exceed the maximum packet size of the remote party. Returns the number of bytes
+    /// sent.
+    fn send_stream(&mut self, reader: &mut dyn Read, chunk_size: usize) -> anyhow::Result<u64> {
+        let mut stream = StreamSender::new(chunk_size)?;
+        while let Some(chunk) = stream.next_chunk(reader)? {
+            self.send(&chunk)?;
+        }
+        Ok(stream.bytes())
+    }
+    /// Receives a stream sent with [`Channel::send_stream`] and writes its bytes to `writer` as
+    /// the chunks arrive. Returns the number of bytes received.
+    fn recv_stream(&mut self, writer: &mut dyn Write) -> anyhow::Result<u64> {
+        let mut stream = StreamReceiver::default();
+        while stream.write_chunk(&self.recv()?, writer)? {}
+        Ok(stream.bytes())
+    }
+}
+
+impl<C: Channel + ?Sized> Channel for Box<C> {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        (**self).shutdown()
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        (**self).send(packet)
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        (**self).recv()
+    }
+
+    fn recv_into(&mut self, buffer: &mut Vec<u8>) -> anyhow::Result<PacketHeader> {
+        (**self).recv_into(buffer)
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        (**self).recv_timeout(timeout)
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        (**self).try_recv()
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        (**self).flush()
+    }
+
+    fn buffered(&self) -> usize {
+        (**self).buffered()
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        (**self).set_max_packet_size(max_packet_size)
+    }
+
+    fn readiness(&self) -> Readiness {
+        (**self).readiness()
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        (**self).rekey()
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        (**self).export_keying_material(label, output)
+    }
+}
+
+/// Checks whether an error was produced because the remote party closed the connection.
+pub(crate) fn is_connection_closed(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<ChannelError>(),
+        Some(ChannelError::ConnectionClosed)
+    ) || err
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(is_closed_by_peer)
+}
+
+/// Checks whether an error was produced because no packet arrived before a timeout.
+pub(crate) fn is_recv_timeout(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<ChannelError>(),
+        Some(ChannelError::RecvTimeout(_))
+    )
+}
+
+/// Checks whether an IO error was produced by a stream whose remote party closed it.
+fn is_closed_by_peer(err: &io::Error) -> bool {
+    matches!(
+        err.get_ref()
+            .and_then(|inner| inner.downcast_ref::<ChannelError>()),
+        Some(ChannelError::ConnectionClosed)
+    )
+}
+
+/// Checks whether an error was produced because the connection dropped unexpectedly, as opposed
+/// to being closed by the remote party.
+pub(crate) fn is_connection_dropped(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|err| {
+            !is_closed_by_peer(err)
+                && matches!(
+                    err.kind(),
+                    io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::ConnectionAborted
+                        | io::ErrorKind::BrokenPipe
+                        | io::ErrorKind::UnexpectedEof
+                        | io::ErrorKind::NotConnected
+                )
+        })
+}
+
+/// Checks whether an IO error was produced by a read that timed out or that would block.
+pub(crate) fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Assigns the next sequence number of a channel to the header of a packet that will be sent.
+pub(crate) fn next_header(packet: &Packet, send_seq: &mut u64) -> PacketHeader {
+    let header = PacketHeader {
+        seq: *send_seq,
+        ..*packet.header()
+    };
+    *send_seq += 1;
+    header
+}
+
+/// Checks that a received header has the sequence number expected by the channel.
+pub(crate) fn check_sequence(
+    header: &PacketHeader,
+    recv_seq: &mut u64,
+) -> Result<(), ChannelError> {
+    if header.seq != *recv_seq {
+        return Err(ChannelError::OutOfOrder {
+            expected: *recv_seq,
+            received: header.seq,
+        });
+    }
+    *recv_seq += 1;
+    Ok(())
+}
+
+/// Version of the format in which the packets travel through a stream. It is the first byte of
+/// every encoded packet, so parties with incompatible formats detect it on the first packet.
+pub(crate) const FRAMING_VERSION: u8 = 3;
+
+/// Size in bytes of the prefix of a packet in the wire: the framing version followed by the
+/// length of the payload as a big-endian `u32`.
+const FRAME_PREFIX_SIZE: usize = 1 + 4;
+
+/// Maximum size in bytes of the payload of a packet that fits in the wire format.
+pub(crate) const MAX_WIRE_PACKET_SIZE: usize = u32::MAX as usize;
+
+/// Size in bytes of the chunks read from the stream.
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Size in bytes from which the buffer of sent packets is written to the stream without waiting
+/// for an explicit flush.
+pub(crate) const SEND_BUFFER_LIMIT: usize = 64 * 1024;
+
+/// Encodes a packet in the format in which it travels through a stream, assigning it the next
+/// sequence number of the channel. First comes the framing version and the size of the payload,
+/// so that the receiver knows the amount of bytes that are being sent, then the header, and
+/// finally the payload. The encoded packet is wiped from memory when it is dropped.
+pub(crate) fn encode_packet(
+    packet: &Packet,
+    send_seq: &mut u64,
+) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let packet_size =
+        u32::try_from(packet.size()).map_err(|_| ChannelError::PacketTooLarge(packet.size()))?;
+    let header = next_header(packet, send_seq);
+    let mut bytes = Vec::with_capacity(FRAME_PREFIX_SIZE + PacketHeader::SIZE + packet.size());
+    bytes.push(FRAMING_VERSION);
+    bytes.extend_from_slice(&packet_size.to_be_bytes());
+    bytes.extend(bincode::serialize(&header)?);
+    bytes.extend_from_slice(packet.as_slice());
+    Ok(Zeroizing::new(bytes))
+}
+
+/// Buffer of the bytes received from a stream that were not decoded into packets yet. Decoding
+/// a packet advances a cursor instead of shifting the remaining bytes, and the allocation is
+/// reused for the following packets. The decoded bytes are wiped before they are discarded.
+pub(crate) struct RecvBuffer {
+    /// Bytes received from the stream.
+    data: Vec<u8>,
+    /// Position of the first byte that was not decoded yet.
+    start: usize,
+    /// Maximum size in bytes of the payload of a packet.
+    max_packet_size: usize,
+}
+
+impl Default for RecvBuffer {
+    fn default() -> Self {
+        Self {
+            data: Vec::new(),
+            start: 0,
+            max_packet_size: MAX_WIRE_PACKET_SIZE,
+        }
+    }
+}
+
+impl RecvBuffer {
+    /// Sets the maximum size in bytes of the payload of a packet.
+    pub(crate) fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.max_packet_size = max_packet_size;
+    }
+
+    /// Returns the maximum size in bytes of an encoded packet, including its prefix and header.
+    pub(crate) fn max_encoded_size(&self) -> usize {
+        self.max_packet_size
+            .saturating_add(FRAME_PREFIX_SIZE + PacketHeader::SIZE)
+    }
+
+    /// Appends the bytes received from the stream. The decoded bytes are discarded first if they
+    /// take at least half of the buffer.
+    pub(crate) fn extend_from_slice(&mut self, bytes: &[u8]) {
+        if self.start > 0 && self.start >= self.data.len() / 2 {
+            self.data[..self.start].zeroize();
+            self.data.drain(..self.start);
+            self.start = 0;
+        }
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Returns the bytes that were not decoded yet.
+    fn pending(&self) -> &[u8] {
+        &self.data[self.start..]
+    }
+
+    /// Takes the payload at the given range of the pending bytes and marks the bytes up to the
+    /// end of the range as decoded. If the payload ends the buffer and is larger than a read
+    /// chunk, the whole buffer is handed to the packet instead of copying the payload.
+    fn take_payload(&mut self, header: PacketHeader, payload: Range<usize>) -> Packet {
+        let start = self.start + payload.start;
+        let end = self.start + payload.end;
+        if end == self.data.len() && payload.len() >= READ_CHUNK_SIZE {
+            self.start = 0;
+            let data = std::mem::take(&mut self.data);
+            return Packet::with_header(header, data).slice(start..end);
+        }
+        let packet = Packet::with_header(header, self.data[start..end].to_vec());
+        self.start = end;
+        if self.start == self.data.len() {
+            self.data.zeroize();
+            self.start = 0;
+        }
+        packet
+    }
+
+    /// Copies the payload at the given range of the pending bytes into the given buffer,
+    /// replacing its contents, and marks the bytes up to the end of the range as decoded.
+    fn copy_payload(&mut self, payload: Range<usize>, buffer: &mut Vec<u8>) {
+        let start = self.start + payload.start;
+        let end = self.start + payload.end;
+        buffer.clear();
+        buffer.extend_from_slice(&self.data[start..end]);
+        self.start = end;
+        if self.start == self.data.len() {
+            self.data.zeroize();
+            self.start = 0;
+        }
+    }
+}
+
+impl Drop for RecvBuffer {
+    fn drop(&mut self) {
+        self.data.zeroize();
+    }
+}
+
+/// Extracts a packet encoded with [`encode_packet`] from the beginning of a buffer of received
+/// bytes if the buffer contains a complete one. The sequence number of the packet is checked.
+pub(crate) fn decode_packet(
+    recv_buffer: &mut RecvBuffer,
+    recv_seq: &mut u64,
+) -> anyhow::Result<Option<Packet>> {
+    Ok(decode_frame(recv_buffer, recv_seq)?
+        .map(|(header, payload)| recv_buffer.take_payload(header, payload)))
+}
+
+/// Works as [`decode_packet`], but the payload is copied into the given buffer, whose
+/// allocation is reused, and only the header is returned.
+pub(crate) fn decode_packet_into(
+    recv_buffer: &mut RecvBuffer,
+    recv_seq: &mut u64,
+    buffer: &mut Vec<u8>,
+) -> anyhow::Result<Option<PacketHeader>> {
+    Ok(
+        decode_frame(recv_buffer, recv_seq)?.map(|(header, payload)| {
+            recv_buffer.copy_payload(payload, buffer);
+            header
+        }),
+    )
+}
+
+/// Decodes the header of the packet at the beginning of the pending bytes if they contain a
+/// complete packet. Returns the header and the range of the payload in the pending bytes.
+fn decode_frame(
+    recv_buffer: &RecvBuffer,
+    recv_seq: &mut u64,
+) -> anyhow::Result<Option<(PacketHeader, Range<usize>)>> {
+    let header_end = FRAME_PREFIX_SIZE + PacketHeader::SIZE;
+    let pending = recv_buffer.pending();
+    if let Some(&version) = pending.first() {
+        if version != FRAMING_VERSION {
+            anyhow::bail!(ChannelError::UnsupportedFraming(version));
+        }
+    }
+    if pending.len() < header_end {
+        return Ok(None);
+    }
+    let mut size_bytes = [0; 4];
+    size_bytes.copy_from_slice(&pending[1..FRAME_PREFIX_SIZE]);
+    let packet_size = u32::from_be_bytes(size_bytes) as usize;
+    if packet_size > recv_buffer.max_packet_size {
+        anyhow::bail!(ChannelError::PacketExceedsLimit {
+            size: packet_size,
+            limit: recv_buffer.max_packet_size,
+        });
+    }
+    if pending.len() < header_end + packet_size {
+        return Ok(None);
+    }
+
+    let header: PacketHeader = bincode::deserialize(&pending[FRAME_PREFIX_SIZE..header_end])?;
+    check_sequence(&header, recv_seq)?;
+    Ok(Some((header, header_end..header_end + packet_size)))
+}
+
+/// Rotates the traffic keys of a TLS stream with a `KeyUpdate` message that also requests the
+/// remote party to rotate its own keys. Returns whether the keys were rotated, which is only
+/// supported by TLS 1.3.
+pub(crate) fn refresh_tls_keys<C, S>(stream: &mut StreamOwned<C, TcpStream>) -> anyhow::Result<bool>
+where
+    C: DerefMut + Deref<Target = ConnectionCommon<S>>,
+    S: SideData,
+{
+    if stream.conn.protocol_version() != Some(ProtocolVersion::TLSv1_3) {
+        return Ok(false);
+    }
+    stream.conn.refresh_traffic_keys()?;
+    stream.flush()?;
+    Ok(true)
+}
+
+/// Exports keying material from a TLS connection under the given label, without context.
+pub(crate) fn export_tls_keying_material<S: SideData>(
+    conn: &ConnectionCommon<S>,
+    label: &[u8],
+    output: &mut [u8],
+) -> anyhow::Result<bool> {
+    conn.export_keying_material(output, label, None)?;
+    Ok(true)
+}
+
+/// Byte stream through which a [`StreamChannel`] sends and receives the encoded packets. Besides
+/// reading and writing, the channel needs to bound the time that a read waits and to poll the
+/// stream without blocking, which the streams delegate to their underlying socket.
+pub trait PacketStream: Read + Write + Send {
+    /// Sets the timeout of the reads from the underlying socket, or removes it with `None`.
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Moves the underlying socket into or out of the nonblocking mode.
+    fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()>;
+
+    /// Closes the stream for writing, once the buffered packets were written to it.
+    fn shutdown(&mut self) -> anyhow::Result<()>;
+
+    /// Returns the file descriptor of the underlying socket.
+    fn raw_fd(&self) -> RawFd;
+
+    /// Refreshes the keys that protect the stream, as explained in [`Channel::rekey`].
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    /// Exports keying material bound to the stream, as explained in
+    /// [`Channel::export_keying_material`].
+    fn export_keying_material(&self, _label: &[u8], _output: &mut [u8]) -> anyhow::Result<bool> {
+        Ok(false)
+    }
 }
 
-impl<C, T, S> Channel for StreamOwned<C, T>
+impl<C, S> PacketStream for StreamOwned<C, TcpStream>
 where
-    C: Sized + DerefMut + Deref<Target = ConnectionCommon<S>>,
-    T: Sized + Read + Write,
+    C: Sized + Send + DerefMut + Deref<Target = ConnectionCommon<S>>,
     S: SideData,
 {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        self.sock.set_nonblocking(nonblocking)
+    }
+
     fn shutdown(&mut self) -> anyhow::Result<()> {
         self.conn.send_close_notify();
+        Ok(())
+    }
+
+    fn raw_fd(&self) -> RawFd {
+        self.sock.as_raw_fd()
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        refresh_tls_keys(self)
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        export_tls_keying_material(&self.conn, label, output)
+    }
+}
+
+/// Channel that frames the packets over a byte stream: each packet is encoded with its sequence
+/// number, the packets sent are buffered until they are flushed, and the bytes received are
+/// buffered until they form a complete packet. The channels over TLS, Unix domain sockets,
+/// plain TCP and Noise only differ in their stream.
+pub struct StreamChannel<T> {
+    /// Stream connected to the remote party.
+    stream: T,
+    /// Bytes received from the stream that do not form a complete packet yet.
+    recv_buffer: RecvBuffer,
+    /// Encoded packets that were sent but not written to the stream yet.
+    send_buffer: Vec<u8>,
+    /// Sequence number of the next packet to be sent.
+    send_seq: u64,
+    /// Sequence number of the next packet to be received.
+    recv_seq: u64,
+}
+
+/// Channel that sends packets to a remote party through a TLS stream over TCP.
+pub type TcpChannel<C> = StreamChannel<StreamOwned<C, TcpStream>>;
+
+impl<T: PacketStream> StreamChannel<T> {
+    /// Creates a new channel from a connected stream.
+    pub fn new(stream: T) -> Self {
+        Self {
+            stream,
+            recv_buffer: RecvBuffer::default(),
+            send_buffer: Vec::new(),
+            send_seq: 0,
+            recv_seq: 0,
+        }
+    }
+
+    /// Extracts a packet from the receive buffer if the buffer contains a complete one.
+    fn take_buffered_packet(&mut self) -> anyhow::Result<Option<Packet>> {
+        decode_packet(&mut self.recv_buffer, &mut self.recv_seq)
+    }
+
+    /// Reads the available bytes from the stream into the receive buffer.
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        let read_bytes = self.stream.read(&mut chunk)?;
+        if read_bytes == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                ChannelError::ConnectionClosed,
+            ));
+        }
+        self.recv_buffer.extend_from_slice(&chunk[..read_bytes]);
+        Ok(())
+    }
+}
+
+impl<T: PacketStream> Channel for StreamChannel<T> {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.flush()?;
+        self.stream.shutdown()?;
         log::info!("channel successfully closed");
         Ok(())
     }
 
     fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
-        // First, we need to send the size of the packet to be able to know the amout
-        // of bits that are being sent.
-        let packet_size = packet.size();
-        let bytes_size_packet = bincode::serialize(&packet_size)?;
-        self.write_all(&bytes_size_packet)?;
-
-        // Then, we send the actual packet.
-        self.write_all(packet.as_slice())?;
+        self.send_buffer
+            .extend_from_slice(&encode_packet(packet, &mut self.send_seq)?);
+        if self.send_buffer.len() >= SEND_BUFFER_LIMIT {
+            self.flush()?;
+        }
         Ok(packet.size())
     }
 
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if !self.send_buffer.is_empty() {
+            self.stream.write_all(&self.send_buffer)?;
+            self.stream.flush()?;
+            self.send_buffer.zeroize();
+        }
+        Ok(())
+    }
+
+    fn buffered(&self) -> usize {
+        self.send_buffer.len()
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.recv_buffer.set_max_packet_size(max_packet_size);
+    }
+
+    fn readiness(&self) -> Readiness {
+        Readiness::Fd(self.stream.raw_fd())
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        self.flush()?;
+        self.stream.rekey()
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        self.stream.export_keying_material(label, output)
+    }
+
     fn recv(&mut self) -> anyhow::Result<Packet> {
-        let mut buffer_packet_size = [0; (usize::BITS / 8) as usize];
-        self.read_exact(&mut buffer_packet_size)?;
-        let packet_size: usize = bincode::deserialize(&buffer_packet_size)?;
+        self.flush()?;
+        // We receive bytes until the buffer contains the whole packet.
+        loop {
+            if let Some(packet) = self.take_buffered_packet()? {
+                return Ok(packet);
+            }
+            self.fill_buffer()?;
+        }
+    }
 
-        // Then, we receive the buffer the amount bytes until the end is reached.
-        let mut payload_buffer = vec![0; packet_size];
-        self.read_exact(&mut payload_buffer)?;
+    fn recv_into(&mut self, buffer: &mut Vec<u8>) -> anyhow::Result<PacketHeader> {
+        self.flush()?;
+        loop {
+            if let Some(header) =
+                decode_packet_into(&mut self.recv_buffer, &mut self.recv_seq, buffer)?
+            {
+                return Ok(header);
+            }
+            self.fill_buffer()?;
+        }
+    }
 
-        Ok(Packet::new(payload_buffer))
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        self.flush()?;
+        let deadline = Instant::now() + timeout;
+        let result = loop {
+            if let Some(packet) = self.take_buffered_packet()? {
+                break Ok(packet);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break Err(ChannelError::RecvTimeout(timeout).into());
+            }
+            self.stream.set_read_timeout(Some(remaining))?;
+            match self.fill_buffer() {
+                Ok(()) => continue,
+                Err(err) if is_timeout(&err) => {
+                    break Err(ChannelError::RecvTimeout(timeout).into())
+                }
+                Err(err) => break Err(err.into()),
+            }
+        };
+        self.stream.set_read_timeout(None)?;
+        result
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        self.flush()?;
+        if let Some(packet) = self.take_buffered_packet()? {
+            return Ok(Some(packet));
+        }
+        self.stream.set_nonblocking(true)?;
+        let result = loop {
+            match self.fill_buffer() {
+                Ok(()) => continue,
+                Err(err) if is_timeout(&err) => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+        result?;
+        self.take_buffered_packet()
     }
 }
 
-/// Accepts a connection in the corresponding listener.
+/// Completes a connection accepted from a remote party. The ID of the remote party is derived from
+/// the certificate it presents, and it must coincide with the ID that the party claims.
 pub(crate) fn accept_connection(
-    listener: &TcpListener,
+    stream: TcpStream,
     server_conf: &ServerConfig,
+    identities: &PeerIdentities,
 ) -> anyhow::Result<(ServerConnection, TcpStream, usize)> {
-    let (mut stream, socket) = listener.accept()?;
-    stream.set_nonblocking(false)?;
-
-    let mut tls_conn = ServerConnection::new(Arc::new(server_conf.clone()))?;
-    let (read_bytes, write_bytes) = tls_conn.complete_io(&mut stream)?;
-    log::debug!("Created TLS connection: read {read_bytes} bytes, write {write_bytes} bytes");
+    let (mut tls_conn, mut stream, remote_id) = accept_tls(stream, server_conf, identities)?;
 
     // Once the client is connected, we receive his ID from the current established channel.
     let mut id_buffer = [0; (usize::BITS / 8) as usize];
+    // The ID may have arrived together with the last messages of the handshake, so we try to
+    // read it before reading more data from the stream.
     loop {
-        if tls_conn.wants_read() {
-            tls_conn.read_tls(&mut stream)?;
-            tls_conn.process_new_packets()?;
-
-            match tls_conn.reader().read_exact(&mut id_buffer) {
-                Ok(()) => break Ok(()),
-                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
-                    continue;
-                }
-                Err(err) => break Err(err),
+        match tls_conn.reader().read_exact(&mut id_buffer) {
+            Ok(()) => break Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                tls_conn.read_tls(&mut stream)?;
+                tls_conn.process_new_packets()?;
             }
+            Err(err) => break Err(err),
         }
     }?;
+    check_claimed_id(usize::from_le_bytes(id_buffer), remote_id)?;
+
+    Ok((tls_conn, stream, remote_id))
+}
 
-    let remote_id = usize::from_le_bytes(id_buffer);
+/// Completes the TLS handshake of a connection accepted from a remote party. Returns the
+/// TLS connection, its underlying TCP stream, and the ID of the remote party derived from the
+/// certificate it presents.
+pub(crate) fn accept_tls(
+    mut stream: TcpStream,
+    server_conf: &ServerConfig,
+    identities: &PeerIdentities,
+) -> anyhow::Result<(ServerConnection, TcpStream, usize)> {
+    let socket = stream.peer_addr()?;
+    stream.set_nonblocking(false)?;
+
+    let mut tls_conn = ServerConnection::new(Arc::new(server_conf.clone()))?;
+    let (read_bytes, write_bytes) = tls_conn.complete_io(&mut stream)?;
+    log::debug!("Created TLS connection: read {read_bytes} bytes, write {write_bytes} bytes");
+
+    let remote_id = identities.identify_chain(tls_conn.peer_certificates())?;
     log::info!(
         "accepted connection request acting like a server from {:?} with ID {}",
         socket,
@@ -112,73 +834,151 @@ pub(crate) fn accept_connection(
     Ok((tls_conn, stream, remote_id))
 }
 
-/// Connect to the remote address as a client using the corresponding timeout. The party
-/// tries to connect to the "server" (the other node) multiple times using a sleep time between calls.
-/// If the "server" party does not answer within the timeout, then the function returns
-/// an error.
+/// Checks that the ID claimed by a remote party coincides with the ID in its certificate.
+pub(crate) fn check_claimed_id(claimed_id: usize, certified_id: usize) -> anyhow::Result<()> {
+    if claimed_id != certified_id {
+        anyhow::bail!(IdentityError::IdMismatch {
+            claimed: claimed_id,
+            certified: certified_id,
+        });
+    }
+    Ok(())
+}
+
+/// Resolves the address of a host, which can be an IPv4 address, an IPv6 address, or a hostname.
+/// If the hostname resolves to several addresses, the first one is used.
+pub(crate) fn resolve_host(host: &str, port: u16) -> io::Result<SocketAddr> {
+    // IPv6 literals may come between brackets, as they appear in URLs.
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    (host, port).to_socket_addrs()?.next().ok_or(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no address found for host {host}"),
+    ))
+}
+
+/// Connect to the remote host as a client using the corresponding timeout, as explained in
+/// [`connect_tcp`], and establishes a TLS connection on top of it, as explained in
+/// [`connect_tls`]. Then, the party sends its ID to the remote party.
 pub(crate) fn connect_as_client(
     local_id: usize,
-    remote_addr: SocketAddr,
-    timeout: Duration,
-    sleep_time: Duration,
+    remote_id: usize,
+    peer: &PeerEndpoint,
+    backoff: &Backoff,
     client_conf: &ClientConfig,
+    identities: &PeerIdentities,
 ) -> anyhow::Result<(ClientConnection, TcpStream)> {
-    let start_time = Instant::now();
+    let (mut client_conn, mut stream) =
+        connect_tls(local_id, remote_id, peer, backoff, client_conf, identities)?;
 
-    // Repeatedly tries to connect to the server during the timeout.
-    log::info!("trying to connect as a client to {:?}", remote_addr);
-    loop {
-        match TcpStream::connect(remote_addr) {
-            Ok(mut stream) => {
-                // We want the stream to actually block.
-                stream.set_nonblocking(false)?;
+    // Send the id of the party that is connecting to the
+    // server once the connection is successfull.
+    client_conn.writer().write_all(&local_id.to_le_bytes())?;
+    let bytes = loop {
+        if client_conn.wants_write() {
+            match client_conn.write_tls(&mut stream) {
+                Ok(bytes) => break Ok(bytes),
+                Err(err) => break Err(err),
+            }
+        }
+    }?;
+    log::debug!("sending ID to {:?}: {bytes} bytes", stream.peer_addr()?);
 
-                // Create the client connection.
-                let mut client_conn = ClientConnection::new(
-                    Arc::new(client_conf.clone()),
-                    ServerName::from(remote_addr.ip()),
-                )?;
-                let (read_bytes, write_bytes) = client_conn.complete_io(&mut stream)?;
-                log::debug!(
-                    "TLS connection with {:?}: write {write_bytes} bytes, read {read_bytes} bytes",
-                    remote_addr
-                );
+    Ok((client_conn, stream))
+}
 
-                // Send the id of the party that is connecting to the
-                // server once the connection is successfull.
-                client_conn.writer().write_all(&local_id.to_le_bytes())?;
-                let bytes = loop {
-                    if client_conn.wants_write() {
-                        match client_conn.write_tls(&mut stream) {
-                            Ok(bytes) => break Ok(bytes),
-                            Err(err) => break Err(err),
-                        }
-                    }
-                }?;
-                log::debug!("sending ID to {:?}: {bytes} bytes", remote_addr);
+/// Connect to the remote host as a client using the corresponding timeout, as explained in
+/// [`connect_tcp`], and completes the TLS handshake. Once connected, the certificate presented by
+/// the server must belong to the party with ID `remote_id`.
+pub(crate) fn connect_tls(
+    local_id: usize,
+    remote_id: usize,
+    peer: &PeerEndpoint,
+    backoff: &Backoff,
+    client_conf: &ClientConfig,
+    identities: &PeerIdentities,
+) -> anyhow::Result<(ClientConnection, TcpStream)> {
+    let server_name = ServerName::try_from(
+        peer.host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string(),
+    )?;
 
+    let mut stream = connect_tcp(local_id, peer, backoff)?;
+    let remote_addr = stream.peer_addr()?;
+
+    // Create the client connection.
+    let mut client_conn = ClientConnection::new(Arc::new(client_conf.clone()), server_name)?;
+    let (read_bytes, write_bytes) = client_conn.complete_io(&mut stream)?;
+    log::debug!(
+        "TLS connection with {:?}: write {write_bytes} bytes, read {read_bytes} bytes",
+        remote_addr
+    );
+
+    // Check that the server is the party we wanted to connect with.
+    let certified_id = identities.identify_chain(client_conn.peer_certificates())?;
+    if certified_id != remote_id {
+        anyhow::bail!(IdentityError::IdMismatch {
+            claimed: remote_id,
+            certified: certified_id,
+        });
+    }
+
+    Ok((client_conn, stream))
+}
+
+/// Opens a TCP connection with the remote host following the backoff policy. The party tries
+/// to connect to the "server" (the other node) multiple times, waiting a growing delay between
+/// attempts. The address of the host is resolved again in each attempt, so changes in the DNS
+/// records are taken into account. If the peer has a SOCKS5 proxy, the connection is opened
+/// through it. If the "server" party does not answer within the timeout or the maximum number of
+/// attempts, then the function returns an error.
+pub(crate) fn connect_tcp(
+    local_id: usize,
+    peer: &PeerEndpoint,
+    backoff: &Backoff,
+) -> anyhow::Result<TcpStream> {
+    let mut attempts = backoff.start();
+
+    // Repeatedly tries to connect to the server following the backoff policy.
+    log::info!(
+        "trying to connect as a client to {}:{}",
+        peer.host,
+        peer.port
+    );
+    loop {
+        let result = match &peer.proxy {
+            Some(proxy) => proxy.connect(&peer.host, peer.port),
+            None => resolve_host(&peer.host, peer.port)
+                .and_then(TcpStream::connect)
+                .map_err(Into::into),
+        };
+        match result {
+            Ok(stream) => {
+                // We want the stream to actually block.
+                stream.set_nonblocking(false)?;
                 log::info!(
                     "connected successfully with {:?} using the local port {:?}",
-                    remote_addr,
+                    stream.peer_addr()?,
                     stream.local_addr()?
                 );
-
-                break Ok((client_conn, stream));
+                break Ok(stream);
             }
-            Err(_) => {
-                let elapsed = start_time.elapsed();
-                if elapsed > timeout {
-                    // At this moment the enlapsed time passed the timeout. Hence we return an
-                    // error. Tired of waiting for the "server" to be ready.
+            Err(err) => {
+                // The connection was not successfull. Hence, we try to connect again with the
+                // "server" party after waiting.
+                if !attempts.wait() {
+                    // At this moment the timeout or the maximum number of attempts was
+                    // reached. Hence we return an error. Tired of waiting for the "server" to be
+                    // ready.
                     log::error!(
-                        "timeout reached, server not listening from ID {local_id} to server {:?}",
-                        remote_addr
+                        "server not listening from ID {local_id} to server {}:{} after {} attempts: {err}",
+                        peer.host,
+                        peer.port,
+                        attempts.count()
                     );
-                    anyhow::bail!(ChannelError::Timeout)
+                    anyhow::bail!(attempts.error())
                 }
-                // The connection was not successfull. Hence, we try to connect again with the
-                // "server" party.
-                std::thread::sleep(sleep_time)
             }
         }
     }
@@ -189,6 +989,10 @@ pub(crate) fn connect_as_client(
 pub struct LoopBackChannel {
     /// Queue of incomming channels.
     buffer: VecDeque<Packet>,
+    /// Sequence number of the next packet to be sent.
+    send_seq: u64,
+    /// Sequence number of the next packet to be received.
+    recv_seq: u64,
 }
 
 impl Channel for LoopBackChannel {
@@ -199,16 +1003,38 @@ impl Channel for LoopBackChannel {
     }
 
     fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
-        log::info!("sent {} bytes to myself", packet.0.len());
-        self.buffer.push_back(Packet::from(packet.as_slice()));
-        Ok(packet.0.len())
+        log::info!("sent {} bytes to myself", packet.size());
+        let header = next_header(packet, &mut self.send_seq);
+        self.buffer
+            .push_back(Packet::with_header(header, packet.as_slice().to_vec()));
+        Ok(packet.size())
     }
 
     fn recv(&mut self) -> anyhow::Result<Packet> {
         log::info!("received packet from myself");
-        self.buffer
+        let packet = self
+            .buffer
             .pop_front()
-            .ok_or(anyhow::Error::new(ChannelError::EmptyBuffer))
+            .ok_or(anyhow::Error::new(ChannelError::EmptyBuffer))?;
+        check_sequence(packet.header(), &mut self.recv_seq)?;
+        Ok(packet)
+    }
+
+    fn readiness(&self) -> Readiness {
+        Readiness::Sends
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        // Nobody else can write in this channel, so waiting does not make sense.
+        self.try_recv()?
+            .ok_or(anyhow::Error::new(ChannelError::RecvTimeout(timeout)))
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+        self.recv().map(Some)
     }
 }
 
@@ -227,4 +1053,98 @@ impl Channel for DummyChannel {
     fn recv(&mut self) -> anyhow::Result<Packet> {
         Ok(Packet::empty())
     }
+
+    fn recv_timeout(&mut self, _: Duration) -> anyhow::Result<Packet> {
+        Ok(Packet::empty())
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        Ok(Some(Packet::empty()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_claimed_id, decode_packet, encode_packet, ChannelError, RecvBuffer};
+    use crate::{
+        canary::{freed_copies, CANARY},
+        net::{identity::IdentityError, Packet},
+    };
+
+    #[test]
+    fn reject_ids_that_do_not_match_the_certificate() {
+        assert!(check_claimed_id(2, 2).is_ok());
+        let err = check_claimed_id(0, 2).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<IdentityError>(),
+            Some(IdentityError::IdMismatch {
+                claimed: 0,
+                certified: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn framing_roundtrip() {
+        let mut send_seq = 0;
+        let mut recv_seq = 0;
+        let mut buffer = RecvBuffer::default();
+        let encoded = encode_packet(&Packet::tagged(2, 3, vec![7; 300]), &mut send_seq).unwrap();
+        assert_eq!(&encoded[1..5], &300u32.to_be_bytes());
+        buffer.extend_from_slice(&encoded[..100]);
+        assert!(decode_packet(&mut buffer, &mut recv_seq).unwrap().is_none());
+        buffer.extend_from_slice(&encoded[100..]);
+        let packet = decode_packet(&mut buffer, &mut recv_seq).unwrap().unwrap();
+        assert_eq!(packet.as_slice(), &[7; 300]);
+        assert!(packet.header().matches(2, 3));
+    }
+
+    #[test]
+    fn framing_buffers_are_wiped() {
+        let leaked = freed_copies(CANARY, || {
+            let mut send_seq = 0;
+            let mut recv_seq = 0;
+            let mut buffer = RecvBuffer::default();
+            let packet = Packet::new(CANARY.to_le_bytes().to_vec());
+            for _ in 0..3 {
+                let encoded = encode_packet(&packet, &mut send_seq).unwrap();
+                buffer.extend_from_slice(&encoded);
+            }
+            for _ in 0..2 {
+                drop(decode_packet(&mut buffer, &mut recv_seq).unwrap().unwrap());
+            }
+            // The last packet is still pending when the buffer is dropped.
+            drop(buffer);
+        });
+        assert_eq!(leaked, 0);
+    }
+
+    #[test]
+    fn reject_packets_over_the_limit() {
+        let encoded = encode_packet(&Packet::new(vec![0; 101]), &mut 0).unwrap();
+        let mut buffer = RecvBuffer::default();
+        buffer.set_max_packet_size(100);
+        buffer.extend_from_slice(&encoded[..30]);
+        let err = decode_packet(&mut buffer, &mut 0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::PacketExceedsLimit {
+                size: 101,
+                limit: 100
+            })
+        ));
+    }
+
+    #[test]
+    fn reject_unknown_framing_version() {
+        let mut encoded = encode_packet(&Packet::new(vec![1]), &mut 0).unwrap();
+        encoded[0] = 0;
+        let mut buffer = RecvBuffer::default();
+        buffer.extend_from_slice(&encoded);
+        let err = decode_packet(&mut buffer, &mut 0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::UnsupportedFraming(0))
+        ));
+    }
 }
@@ -0,0 +1,396 @@
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use thiserror::Error;
+
+use super::{
+    channel::Channel,
+    decorator::{Bandwidth, Delay, DelayedChannel, Faults, FaultyChannel, ThrottledChannel},
+    Network,
+};
+use crate::error::ShamiError;
+
+/// Time after which the parties of a chaos test are interrupted if they did not finish.
+pub const DEFAULT_DEADLINE: Duration = Duration::from_secs(2);
+
+/// Adverse conditions of the link from a party to one of its peers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinkConditions {
+    /// Latency injected before sending a packet to the peer.
+    pub send_delay: Delay,
+    /// Latency injected after receiving a packet from the peer.
+    pub recv_delay: Delay,
+    /// Faults injected in the packets received from the peer.
+    pub faults: Faults,
+    /// Bandwidth of the packets sent to the peer, or `None` if it is not limited.
+    pub bandwidth: Option<Bandwidth>,
+}
+
+impl LinkConditions {
+    /// Draws random conditions that keep a test short: latencies of a few milliseconds, faults
+    /// in a small fraction of the packets and bandwidths of a few megabytes per second. Each
+    /// kind of fault is only injected in some of the links. The packets are never corrupted,
+    /// since a bit flip in a payload without authentication is not detected by design.
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        let mut fault = |max: f64| {
+            if rng.gen_bool(0.25) {
+                rng.gen_range(0.0..max)
+            } else {
+                0.0
+            }
+        };
+        let faults = Faults {
+            drop: fault(0.05),
+            duplicate: fault(0.05),
+            reorder: fault(0.2),
+            corrupt: 0.0,
+        };
+        let max_delay = Duration::from_millis(3);
+        Self {
+            send_delay: Delay::uniform(Duration::ZERO, rng.gen_range(Duration::ZERO..max_delay)),
+            recv_delay: Delay::uniform(Duration::ZERO, rng.gen_range(Duration::ZERO..max_delay)),
+            faults,
+            bandwidth: rng
+                .gen_bool(0.5)
+                .then(|| Bandwidth::megabits_per_second(rng.gen_range(8.0..80.0))),
+        }
+    }
+
+    /// Decorates a channel with the conditions. The faults are drawn from the given seed.
+    fn wrap(&self, channel: Box<dyn Channel>, seed: u64) -> Box<dyn Channel> {
+        let channel = DelayedChannel::new(
+            FaultyChannel::new(channel, self.faults, seed),
+            self.send_delay,
+            self.recv_delay,
+        );
+        match self.bandwidth {
+            Some(bandwidth) => Box::new(ThrottledChannel::new(channel, bandwidth)),
+            None => Box::new(channel),
+        }
+    }
+}
+
+/// Script of a chaos test, which gives the conditions of the link from each party to each of its
+/// peers. The scripts drawn at random are determined by a seed, so a failing test can be
+/// reproduced from the seed that it reports.
+#[derive(Debug, Clone)]
+pub struct ChaosScript {
+    /// Seed from which the conditions and the faults are drawn.
+    seed: u64,
+    /// Time after which the parties that did not finish are interrupted.
+    deadline: Duration,
+    /// Conditions of the link from the party `i` to the party `j` in position `[i][j]`.
+    links: Vec<Vec<LinkConditions>>,
+}
+
+impl ChaosScript {
+    /// Creates a script for `n_parties` in which every link is ideal.
+    pub fn new(n_parties: usize, seed: u64) -> Self {
+        Self {
+            seed,
+            deadline: DEFAULT_DEADLINE,
+            links: vec![vec![LinkConditions::default(); n_parties]; n_parties],
+        }
+    }
+
+    /// Creates a script for `n_parties` in which the conditions of every link are drawn at
+    /// random from the seed with [`LinkConditions::random`].
+    pub fn random(n_parties: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let links = (0..n_parties)
+            .map(|_| {
+                (0..n_parties)
+                    .map(|_| LinkConditions::random(&mut rng))
+                    .collect()
+            })
+            .collect();
+        Self {
+            seed,
+            deadline: DEFAULT_DEADLINE,
+            links,
+        }
+    }
+
+    /// Sets the conditions of the link from `party` to `peer`.
+    pub fn with_link(mut self, party: usize, peer: usize, conditions: LinkConditions) -> Self {
+        self.links[party][peer] = conditions;
+        self
+    }
+
+    /// Sets the time after which the parties that did not finish are interrupted.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Returns the seed of the script.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the number of parties of the script.
+    pub fn n_parties(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Decorates the channels of the network of a party with the conditions of its links. The
+    /// loop-back channel is kept as it is.
+    fn apply(&self, party: usize, network: &mut Network) {
+        let n_parties = self.n_parties() as u64;
+        network.wrap_channels(|peer, channel| {
+            if peer == party {
+                return channel;
+            }
+            let seed = self.seed ^ (party as u64 * n_parties + peer as u64);
+            self.links[party][peer].wrap(channel, seed)
+        });
+    }
+
+    /// Runs `protocol` with every party of a local network in its own thread under the
+    /// conditions of the script, passing the network of the party. The parties that did not
+    /// finish by the deadline are interrupted, so they abort and fail instead of waiting forever
+    /// for packets that were dropped.
+    pub fn run<T, P>(&self, protocol: P) -> ChaosOutcome<T>
+    where
+        T: Send + 'static,
+        P: Fn(&mut Network) -> anyhow::Result<T> + Send + Sync + 'static,
+    {
+        let protocol = Arc::new(protocol);
+        let (done_sender, done_receiver) = mpsc::channel();
+        let mut interrupts = Vec::with_capacity(self.n_parties());
+        let handles: Vec<_> = Network::create_local(self.n_parties())
+            .into_iter()
+            .enumerate()
+            .map(|(id, mut network)| {
+                self.apply(id, &mut network);
+                // The network needs a static flag, so it is leaked, which only takes a byte per
+                // party of each test.
+                let interrupt: &'static AtomicBool = Box::leak(Box::new(AtomicBool::new(false)));
+                network.set_interrupt(interrupt);
+                interrupts.push(interrupt);
+                let protocol = Arc::clone(&protocol);
+                let done = done_sender.clone();
+                thread::spawn(move || {
+                    let result = protocol(&mut network);
+                    // The channels are closed before reporting, so the peers stop waiting.
+                    drop(network);
+                    let _ = done.send(());
+                    result
+                })
+            })
+            .collect();
+        drop(done_sender);
+
+        let deadline = Instant::now() + self.deadline;
+        for _ in 0..self.n_parties() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if done_receiver.recv_timeout(remaining).is_err() {
+                log::warn!(
+                    "interrupting the parties of the chaos script with seed {} after {:?}",
+                    self.seed,
+                    self.deadline
+                );
+                for interrupt in &interrupts {
+                    interrupt.store(true, Ordering::SeqCst);
+                }
+                break;
+            }
+        }
+
+        let parties = handles
+            .into_iter()
+            .enumerate()
+            .map(|(party, handle)| match handle.join() {
+                Ok(Ok(output)) => PartyOutcome::Finished(output),
+                Ok(Err(err)) => {
+                    log::info!(
+                        "party {party} failed under the chaos script with seed {}: {err:#}",
+                        self.seed
+                    );
+                    PartyOutcome::Failed(ShamiError::from(err))
+                }
+                Err(payload) => PartyOutcome::Panicked(panic_message(payload)),
+            })
+            .collect();
+        ChaosOutcome {
+            seed: self.seed,
+            parties,
+        }
+    }
+}
+
+/// Returns the message of a panic.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "the panic has no message".to_string())
+}
+
+/// Outcome of a party in a chaos test.
+#[derive(Debug)]
+pub enum PartyOutcome<T> {
+    /// The party finished the protocol with an output.
+    Finished(T),
+    /// The party failed with an error, classified by the kind of failure.
+    Failed(ShamiError),
+    /// The party panicked, which is never a clean failure.
+    Panicked(String),
+}
+
+/// Possible errors that may appear when a chaos test does not end in a success or a clean
+/// failure.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ChaosError {
+    /// A party finished the protocol with an output other than the expected one.
+    #[error("party {party} obtained a wrong output under the chaos script with seed {seed}")]
+    WrongOutput { party: usize, seed: u64 },
+
+    /// A party panicked instead of failing with an error.
+    #[error("party {party} panicked under the chaos script with seed {seed}: {message}")]
+    Panicked {
+        party: usize,
+        seed: u64,
+        message: String,
+    },
+}
+
+/// Outcome of a chaos test, with the outcome of each party indexed by its ID.
+#[derive(Debug)]
+pub struct ChaosOutcome<T> {
+    /// Seed of the script under which the parties ran.
+    pub seed: u64,
+    /// Outcome of each party.
+    pub parties: Vec<PartyOutcome<T>>,
+}
+
+impl<T: PartialEq> ChaosOutcome<T> {
+    /// Checks that every party either obtained the expected output or failed with an error,
+    /// which is classified by its kind of failure and attributed to the party and the seed in
+    /// the logs.
+    pub fn check(&self, expected: &T) -> Result<(), ChaosError> {
+        for (party, outcome) in self.parties.iter().enumerate() {
+            match outcome {
+                PartyOutcome::Finished(output) if output != expected => {
+                    return Err(ChaosError::WrongOutput {
+                        party,
+                        seed: self.seed,
+                    })
+                }
+                PartyOutcome::Panicked(message) => {
+                    return Err(ChaosError::Panicked {
+                        party,
+                        seed: self.seed,
+                        message: message.clone(),
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the IDs of the parties that failed with an error.
+    pub fn failed(&self) -> Vec<usize> {
+        self.parties
+            .iter()
+            .enumerate()
+            .filter(|(_, outcome)| matches!(outcome, PartyOutcome::Failed(_)))
+            .map(|(party, _)| party)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rand::thread_rng;
+
+    use super::{ChaosError, ChaosScript, LinkConditions, PartyOutcome};
+    use crate::{
+        error::ShamiError,
+        math::{mersenne61::Mersenne61, FiniteField},
+        mpc::{
+            engine::Engine, reconstruct_secret_from_parties, run_multiply_protocol,
+            share::ShamirShare, share_inputs,
+        },
+        net::{decorator::Faults, Network},
+    };
+
+    const N_PARTIES: usize = 3;
+    const THRESHOLD: usize = 1;
+
+    /// Multiplies the inputs of the first two parties and opens the product.
+    fn multiply(network: &mut Network) -> anyhow::Result<Mersenne61> {
+        let id = network.id();
+        let input = Mersenne61::from(id as u64 + 6);
+        let own_shares = share_inputs(&[input], N_PARTIES, THRESHOLD, &mut thread_rng());
+        let shares: Vec<Vec<ShamirShare<Mersenne61>>> = network.exchange_values(&own_shares)?;
+        let product = run_multiply_protocol(
+            &shares[0][0],
+            &shares[1][0],
+            N_PARTIES,
+            THRESHOLD,
+            &mut thread_rng(),
+            &mut Engine::new(&mut *network),
+        )?;
+        let gathered = network.broadcast_and_gather_values(&product)?;
+        Ok(reconstruct_secret_from_parties(
+            gathered.into_iter().enumerate().collect(),
+        )?)
+    }
+
+    #[test]
+    fn random_conditions_succeed_or_fail_cleanly() {
+        const N_SCRIPTS: u64 = 6;
+
+        let expected = Mersenne61::from(6 * 7);
+        let ideal = ChaosScript::new(N_PARTIES, 0).run(multiply);
+        ideal.check(&expected).unwrap();
+        assert!(ideal.failed().is_empty());
+
+        for seed in 0..N_SCRIPTS {
+            let script =
+                ChaosScript::random(N_PARTIES, seed).with_deadline(Duration::from_millis(500));
+            script.run(multiply).check(&expected).unwrap();
+        }
+    }
+
+    #[test]
+    fn dropped_link_fails_cleanly() {
+        let dropping = LinkConditions {
+            faults: Faults {
+                drop: 1.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let script = ChaosScript::new(N_PARTIES, 0)
+            .with_link(0, 1, dropping)
+            .with_deadline(Duration::from_millis(500));
+        let outcome = script.run(multiply);
+        outcome.check(&Mersenne61::from(6 * 7)).unwrap();
+        // The party 0 never hears from the party 1, so it is interrupted at the deadline.
+        assert!(matches!(
+            outcome.parties[0],
+            PartyOutcome::Failed(ShamiError::Interrupted)
+        ));
+        assert!(outcome.failed().contains(&1));
+
+        let outcome = ChaosScript::new(N_PARTIES, 0).run(|_| Ok(Mersenne61::ZERO));
+        assert_eq!(
+            outcome.check(&Mersenne61::ONE),
+            Err(ChaosError::WrongOutput { party: 0, seed: 0 })
+        );
+    }
+}
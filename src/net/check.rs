@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    fs,
+    net::{TcpListener, TcpStream},
+    path::Path,
+    time::Duration,
+};
+
+use serde_json::Value;
+use thiserror::Error;
+
+use super::{channel, NetworkConfig, PeerEndpoint};
+use crate::mpc::{self, ThresholdError};
+
+/// Problems found while validating a network configuration without running a protocol.
+#[derive(Debug, Error)]
+pub enum ConfigProblem {
+    /// The configuration can not be read or is rejected when the network is configured.
+    #[error("the configuration is not valid: {0}")]
+    Invalid(String),
+
+    /// Two parties are reached at the same address.
+    #[error("the parties {first} and {second} have the same address {address}")]
+    DuplicatedAddress {
+        first: usize,
+        second: usize,
+        address: String,
+    },
+
+    /// A list of the configuration does not have one entry per party.
+    #[error("the field {field} has {found} entries, but there are {n_parties} parties")]
+    PartyCountMismatch {
+        field: &'static str,
+        found: usize,
+        n_parties: usize,
+    },
+
+    /// The ID of the party is not one of the parties of the configuration.
+    #[error("the ID {id} is not correct for {n_parties} parties")]
+    InvalidId { id: usize, n_parties: usize },
+
+    /// The number of corruptions is not supported by the number of parties.
+    #[error("the number of corruptions is not correct: {0}")]
+    InvalidThreshold(#[from] ThresholdError),
+
+    /// The party can not listen in its address.
+    #[error("the party can not listen in {address}: {reason}")]
+    AddressUnavailable { address: String, reason: String },
+
+    /// A peer does not accept connections in its address.
+    #[error("the party {party} is not reachable at {address}: {reason}")]
+    Unreachable {
+        party: usize,
+        address: String,
+        reason: String,
+    },
+}
+
+/// Options of the validation of a network configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckOptions {
+    /// ID of the party that uses the configuration. If it is present, the ID must belong to a
+    /// party, and the party must be able to listen in its address.
+    pub id: Option<usize>,
+    /// Number of corruptions tolerated by the protocol. If it is present, it must allow the
+    /// parties to multiply their shares.
+    pub corruptions: Option<usize>,
+    /// Timeout to connect with each peer. If it is present, every peer except the party itself
+    /// must accept connections, so the peers must be already running.
+    pub probe_timeout: Option<Duration>,
+}
+
+/// Validates the network configuration of the given file without running a protocol, returning
+/// every problem found. The configuration is parsed as it is when the network is created, and
+/// it is also checked that the addresses of the parties are distinct, that the lists indexed by
+/// party ID have one entry per party, and that the number of corruptions is valid. The ports are
+/// checked according to the options.
+pub fn check_config(path: &Path, options: &CheckOptions) -> Vec<ConfigProblem> {
+    let json: Value = match fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| Ok(serde_json::from_str(&content)?))
+    {
+        Ok(json) => json,
+        Err(err) => return vec![ConfigProblem::Invalid(err.to_string())],
+    };
+    let mut problems = Vec::new();
+    if let Err(err) = NetworkConfig::new(path) {
+        problems.push(ConfigProblem::Invalid(format!("{err:#}")));
+    }
+
+    // The endpoints are only given in the configuration if there is no coordinator.
+    let peers = if json["coordinator"].is_null() && json["unix_sockets"].is_null() {
+        PeerEndpoint::parse_all(&json).ok()
+    } else {
+        None
+    };
+    let n_parties = match (
+        &peers,
+        &json["coordinator"]["n_parties"],
+        &json["unix_sockets"],
+    ) {
+        (Some(peers), _, _) => Some(peers.len()),
+        (None, Value::Number(n_parties), _) => n_parties.as_u64().map(|n| n as usize),
+        (None, _, Value::Array(paths)) => Some(paths.len()),
+        _ => None,
+    };
+    let Some(n_parties) = n_parties else {
+        return problems;
+    };
+
+    if let Some(peers) = &peers {
+        let mut addresses = HashMap::new();
+        for (party, peer) in peers.iter().enumerate() {
+            let address = peer.address().to_lowercase();
+            if let Some(first) = addresses.insert(address.clone(), party) {
+                problems.push(ConfigProblem::DuplicatedAddress {
+                    first,
+                    second: party,
+                    address,
+                });
+            }
+        }
+    }
+    for field in ["peer_certs", "peer_fingerprints", "unix_sockets"] {
+        if let Some(entries) = json[field].as_array() {
+            if entries.len() != n_parties {
+                problems.push(ConfigProblem::PartyCountMismatch {
+                    field,
+                    found: entries.len(),
+                    n_parties,
+                });
+            }
+        }
+    }
+    if let Some(id) = options.id.filter(|id| *id >= n_parties) {
+        problems.push(ConfigProblem::InvalidId { id, n_parties });
+    }
+    if let Some(corruptions) = options.corruptions {
+        if let Err(err) = mpc::check_threshold(n_parties, corruptions, true) {
+            problems.push(err.into());
+        }
+    }
+
+    if let Some(peers) = &peers {
+        problems.extend(check_ports(peers, options));
+    }
+    problems
+}
+
+/// Checks that the party can listen in its address and, if requested, that the other parties
+/// accept connections.
+fn check_ports(peers: &[PeerEndpoint], options: &CheckOptions) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    if let Some(peer) = options.id.and_then(|id| peers.get(id)) {
+        let address = peer.bind.clone().unwrap_or_else(|| peer.address());
+        if let Err(err) = peer
+            .listen_address()
+            .and_then(|address| Ok(TcpListener::bind(address)?))
+        {
+            problems.push(ConfigProblem::AddressUnavailable {
+                address,
+                reason: format!("{err:#}"),
+            });
+        }
+    }
+    let Some(timeout) = options.probe_timeout else {
+        return problems;
+    };
+    for (party, peer) in peers.iter().enumerate() {
+        if Some(party) == options.id {
+            continue;
+        }
+        let address = format!("{}:{}", peer.host, peer.port);
+        let reachable = channel::resolve_host(&peer.host, peer.port)
+            .and_then(|address| TcpStream::connect_timeout(&address, timeout));
+        if let Err(err) = reachable {
+            problems.push(ConfigProblem::Unreachable {
+                party,
+                address,
+                reason: err.to_string(),
+            });
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, net::TcpListener, path::PathBuf, time::Duration};
+
+    use serde_json::json;
+
+    use super::{check_config, CheckOptions, ConfigProblem};
+    use crate::mpc::ThresholdError;
+
+    /// Writes a temporary configuration file with the given name and content.
+    fn config_file(name: &str, json: serde_json::Value) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("shami_rs_check_{}_{name}", std::process::id()));
+        fs::write(&path, json.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn report_every_problem() {
+        let path = config_file(
+            "problems.json",
+            json!({
+                "timeout": 5000,
+                "sleep_time": 500,
+                "insecure_plaintext": true,
+                "peers": [
+                    { "host": "127.0.0.1", "port": 1 },
+                    { "host": "127.0.0.1", "port": 1 },
+                    { "host": "127.0.0.1", "port": 2 },
+                ],
+                "peer_fingerprints": ["00"],
+            }),
+        );
+        let problems = check_config(
+            &path,
+            &CheckOptions {
+                id: Some(3),
+                corruptions: Some(2),
+                probe_timeout: None,
+            },
+        );
+        fs::remove_file(path).unwrap();
+        assert!(matches!(
+            problems.as_slice(),
+            [
+                ConfigProblem::DuplicatedAddress {
+                    first: 0,
+                    second: 1,
+                    ..
+                },
+                ConfigProblem::PartyCountMismatch {
+                    field: "peer_fingerprints",
+                    found: 1,
+                    n_parties: 3
+                },
+                ConfigProblem::InvalidId {
+                    id: 3,
+                    n_parties: 3
+                },
+                ConfigProblem::InvalidThreshold(ThresholdError::NotEnoughPartiesToMultiply { .. }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn check_the_ports_of_the_parties() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let busy_port = listener.local_addr().unwrap().port();
+        let free_port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let path = config_file(
+            "ports.json",
+            json!({
+                "timeout": 5000,
+                "sleep_time": 500,
+                "insecure_plaintext": true,
+                "peers": [
+                    { "host": "127.0.0.1", "port": busy_port },
+                    { "host": "127.0.0.1", "port": free_port },
+                ],
+            }),
+        );
+        let mut options = CheckOptions {
+            id: Some(1),
+            corruptions: None,
+            probe_timeout: Some(Duration::from_secs(1)),
+        };
+        assert!(check_config(&path, &options).is_empty());
+
+        options.id = Some(0);
+        let problems = check_config(&path, &options);
+        fs::remove_file(path).unwrap();
+        assert!(matches!(
+            problems.as_slice(),
+            [
+                ConfigProblem::AddressUnavailable { .. },
+                ConfigProblem::Unreachable { party: 1, .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn report_unreadable_configurations() {
+        let problems = check_config(
+            &PathBuf::from("./does_not_exist.json"),
+            &CheckOptions::default(),
+        );
+        assert!(matches!(problems.as_slice(), [ConfigProblem::Invalid(_)]));
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    io::{BufRead, BufReader, Error, ErrorKind, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::{backoff::Backoff, channel, PeerEndpoint};
+
+/// Time that the coordinator waits for the registration of a party after accepting its
+/// connection.
+const REGISTRATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Possible errors that may appear while registering with the coordinator.
+#[derive(Debug, Error)]
+pub enum CoordinatorError {
+    /// The coordinator rejected the registration of the party.
+    #[error("the coordinator rejected the registration: {0}")]
+    Rejected(String),
+
+    /// The roster sent by the coordinator does not match the registration.
+    #[error("the roster sent by the coordinator is not correct: {0}")]
+    InvalidRoster(&'static str),
+}
+
+/// Configuration to register with the coordinator of a session.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CoordinatorConfig {
+    /// Host of the coordinator.
+    pub host: String,
+    /// Port in which the coordinator listens for registrations.
+    pub port: u16,
+    /// Number of parties in the session.
+    pub n_parties: usize,
+    /// Endpoint at which the party can be reached, which is announced to the other parties.
+    pub endpoint: PeerEndpoint,
+}
+
+impl CoordinatorConfig {
+    /// Reads the configuration of the coordinator from the `coordinator` object of the network
+    /// configuration. Returns `None` if the object is not present.
+    pub fn from_json(json: &Value) -> anyhow::Result<Option<Self>> {
+        if json.is_null() {
+            return Ok(None);
+        }
+        let config: Self = serde_json::from_value(json.clone()).map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("the coordinator configuration is not correct: {err}"),
+            )
+        })?;
+        if config.host.is_empty() || config.endpoint.host.is_empty() || config.n_parties == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the coordinator configuration is not correct",
+            )
+            .into());
+        }
+        Ok(Some(config))
+    }
+}
+
+/// Registration sent by a party to the coordinator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Registration {
+    /// ID of the party.
+    pub id: usize,
+    /// Number of parties in the session, which must be the same for every party.
+    pub n_parties: usize,
+    /// Host at which the party can be reached.
+    pub host: String,
+    /// Port in which the party listens for connections.
+    pub port: u16,
+    /// Certificate of the party in DER format.
+    pub cert: Vec<u8>,
+}
+
+/// Entry of a party in the roster of the session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RosterEntry {
+    /// Host at which the party can be reached.
+    pub host: String,
+    /// Port in which the party listens for connections.
+    pub port: u16,
+    /// Certificate of the party in DER format.
+    pub cert: Vec<u8>,
+}
+
+impl RosterEntry {
+    /// Returns the endpoint at which the party can be reached.
+    pub fn endpoint(&self) -> PeerEndpoint {
+        PeerEndpoint {
+            host: self.host.clone(),
+            port: self.port,
+            bind: None,
+            proxy: None,
+        }
+    }
+}
+
+/// Response of the coordinator to a registration.
+#[derive(Debug, Serialize, Deserialize)]
+enum Response {
+    /// Every party registered. The roster is indexed by party ID.
+    Roster(Vec<RosterEntry>),
+    /// The registration was not accepted.
+    Rejected(String),
+}
+
+/// Service that collects the registrations of the parties of a session and, once every party
+/// registered, sends the final roster to all of them.
+pub struct Coordinator {
+    /// Listener in which the parties register.
+    listener: TcpListener,
+    /// Number of parties in the session.
+    n_parties: usize,
+}
+
+impl Coordinator {
+    /// Creates a coordinator for a session of `n_parties` that listens at the given address.
+    pub fn bind(address: SocketAddr, n_parties: usize) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        log::info!("coordinator listening at {address} for {n_parties} parties");
+        Ok(Self {
+            listener,
+            n_parties,
+        })
+    }
+
+    /// Returns the address at which the coordinator listens.
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accepts registrations until every party registered, and then sends the roster to every
+    /// party. Invalid registrations are rejected without stopping the coordinator.
+    pub fn run(self) -> anyhow::Result<Vec<RosterEntry>> {
+        let mut registered: Vec<Option<(RosterEntry, TcpStream)>> =
+            (0..self.n_parties).map(|_| None).collect();
+        let mut n_registered = 0;
+        while n_registered < self.n_parties {
+            let (mut stream, address) = self.listener.accept()?;
+            stream.set_read_timeout(Some(REGISTRATION_TIMEOUT))?;
+            let registration = match read_message::<Registration>(&mut stream) {
+                Ok(registration) => registration,
+                Err(err) => {
+                    log::warn!("invalid registration from {address}: {err}");
+                    continue;
+                }
+            };
+            if let Err(reason) = self.check(&registration, &registered) {
+                log::warn!("rejected the registration from {address}: {reason}");
+                let _ = write_message(&mut stream, &Response::Rejected(reason));
+                continue;
+            }
+
+            log::info!(
+                "party {} registered with the endpoint {}:{}",
+                registration.id,
+                registration.host,
+                registration.port
+            );
+            let entry = RosterEntry {
+                host: registration.host,
+                port: registration.port,
+                cert: registration.cert,
+            };
+            registered[registration.id] = Some((entry, stream));
+            n_registered += 1;
+        }
+
+        let (roster, streams): (Vec<_>, Vec<_>) = registered.into_iter().flatten().unzip();
+        let response = Response::Roster(roster.clone());
+        for (id, mut stream) in streams.into_iter().enumerate() {
+            if let Err(err) = write_message(&mut stream, &response) {
+                log::error!("error sending the roster to party {id}: {err}");
+            }
+        }
+        log::info!("sent the roster to the {} parties", self.n_parties);
+        Ok(roster)
+    }
+
+    /// Checks that a registration is valid and that its ID was not registered before.
+    fn check(
+        &self,
+        registration: &Registration,
+        registered: &[Option<(RosterEntry, TcpStream)>],
+    ) -> Result<(), String> {
+        if registration.n_parties != self.n_parties {
+            return Err(format!(
+                "the session has {} parties, but the party expects {}",
+                self.n_parties, registration.n_parties
+            ));
+        }
+        if registration.id >= self.n_parties {
+            return Err(format!("the ID {} is out of range", registration.id));
+        }
+        if registered[registration.id].is_some() {
+            return Err(format!("the ID {} is already registered", registration.id));
+        }
+        Ok(())
+    }
+}
+
+/// Registers a party with the coordinator and waits until the coordinator sends the roster,
+/// which is indexed by party ID. The connection with the coordinator is retried following the
+/// backoff policy.
+pub fn register(
+    config: &CoordinatorConfig,
+    registration: &Registration,
+    backoff: &Backoff,
+) -> anyhow::Result<Vec<RosterEntry>> {
+    let coordinator = PeerEndpoint {
+        host: config.host.clone(),
+        port: config.port,
+        bind: None,
+        proxy: None,
+    };
+    let mut stream = channel::connect_tcp(registration.id, &coordinator, backoff)?;
+    write_message(&mut stream, registration)?;
+    log::info!("registered with the coordinator, waiting for the other parties");
+
+    let roster = match read_message::<Response>(&mut stream)? {
+        Response::Roster(roster) => roster,
+        Response::Rejected(reason) => return Err(CoordinatorError::Rejected(reason).into()),
+    };
+    if roster.len() != registration.n_parties {
+        return Err(CoordinatorError::InvalidRoster("the number of parties is not correct").into());
+    }
+    let own_entry = &roster[registration.id];
+    if own_entry.host != registration.host
+        || own_entry.port != registration.port
+        || own_entry.cert != registration.cert
+    {
+        return Err(CoordinatorError::InvalidRoster("the entry of the party was modified").into());
+    }
+    Ok(roster)
+}
+
+/// Writes a message as a line of JSON.
+fn write_message<T: Serialize>(stream: &mut TcpStream, message: &T) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Reads a message written as a line of JSON.
+fn read_message<T: for<'de> Deserialize<'de>>(stream: &mut TcpStream) -> anyhow::Result<T> {
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    if line.is_empty() {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "the connection was closed before receiving the message",
+        )
+        .into());
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::{register, Coordinator, CoordinatorConfig, CoordinatorError, Registration};
+    use crate::net::{backoff::Backoff, PeerEndpoint};
+
+    fn registration(id: usize, n_parties: usize) -> Registration {
+        Registration {
+            id,
+            n_parties,
+            host: "127.0.0.1".to_string(),
+            port: 6000 + id as u16,
+            cert: vec![id as u8; 4],
+        }
+    }
+
+    #[test]
+    fn every_party_receives_the_roster() {
+        let coordinator = Coordinator::bind("127.0.0.1:0".parse().unwrap(), 3).unwrap();
+        let config = CoordinatorConfig {
+            host: "127.0.0.1".to_string(),
+            port: coordinator.local_addr().unwrap().port(),
+            n_parties: 3,
+            endpoint: PeerEndpoint {
+                host: "127.0.0.1".to_string(),
+                port: 6000,
+                bind: None,
+                proxy: None,
+            },
+        };
+        let service = thread::spawn(move || coordinator.run().unwrap());
+
+        let parties: Vec<_> = [2, 0, 1]
+            .into_iter()
+            .map(|id| {
+                let config = config.clone();
+                thread::spawn(move || {
+                    let backoff = Backoff::fixed(Duration::from_secs(5), Duration::from_millis(10));
+                    register(&config, &registration(id, 3), &backoff).unwrap()
+                })
+            })
+            .collect();
+        let roster = service.join().unwrap();
+        for party in parties {
+            assert_eq!(party.join().unwrap(), roster);
+        }
+        for (id, entry) in roster.iter().enumerate() {
+            assert_eq!(entry.port, 6000 + id as u16);
+            assert_eq!(entry.cert, vec![id as u8; 4]);
+        }
+    }
+
+    #[test]
+    fn reject_duplicated_and_mismatched_registrations() {
+        let coordinator = Coordinator::bind("127.0.0.1:0".parse().unwrap(), 2).unwrap();
+        let config = CoordinatorConfig {
+            host: "127.0.0.1".to_string(),
+            port: coordinator.local_addr().unwrap().port(),
+            n_parties: 2,
+            endpoint: PeerEndpoint {
+                host: "127.0.0.1".to_string(),
+                port: 6000,
+                bind: None,
+                proxy: None,
+            },
+        };
+        let service = thread::spawn(move || coordinator.run().unwrap());
+        let backoff = Backoff::fixed(Duration::from_secs(5), Duration::from_millis(10));
+
+        let first = {
+            let config = config.clone();
+            thread::spawn(move || register(&config, &registration(0, 2), &backoff))
+        };
+        // Wait until the first registration is stored before sending the duplicated one.
+        thread::sleep(Duration::from_millis(100));
+        for invalid in [registration(0, 2), registration(1, 3), registration(2, 2)] {
+            let err = register(&config, &invalid, &backoff).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<CoordinatorError>(),
+                Some(CoordinatorError::Rejected(_))
+            ));
+        }
+        register(&config, &registration(1, 2), &backoff).unwrap();
+        assert!(first.join().unwrap().is_ok());
+        service.join().unwrap();
+    }
+}
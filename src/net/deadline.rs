@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Deadline of the rounds in which a party gathers a message from every party, and what the
+/// party does with the parties whose message did not arrive in time. If no deadline is given,
+/// the rounds wait for every message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoundDeadline {
+    /// Number of milliseconds that a round waits for the messages of the parties.
+    #[serde(default)]
+    pub millis: Option<u64>,
+    /// Whether the round proceeds with the messages that arrived in time, as long as there are
+    /// enough of them, instead of aborting.
+    #[serde(default)]
+    pub robust: bool,
+    /// Whether an opening proceeds with as few as `t + 1` shares of degree `t` that arrived in
+    /// time, which determine the secret but can not reveal a wrong share among them. A robust
+    /// deadline takes precedence and needs `2t + 1` shares.
+    #[serde(default)]
+    pub fallback: bool,
+}
+
+impl RoundDeadline {
+    /// Returns the time that a round waits for the messages of the parties, which is
+    /// [`Duration::MAX`] if there is no deadline.
+    pub fn duration(&self) -> Duration {
+        self.millis.map_or(Duration::MAX, Duration::from_millis)
+    }
+
+    /// Tells whether a round proceeds without the parties that miss the deadline.
+    pub fn tolerates_missing(&self) -> bool {
+        self.robust || self.fallback
+    }
+
+    /// Returns the number of shares of the given degree that an opening needs to proceed without
+    /// the parties that miss the deadline.
+    pub fn needed_shares(&self, degree: usize) -> usize {
+        if self.robust {
+            2 * degree + 1
+        } else {
+            degree + 1
+        }
+    }
+}
+
+/// Parties whose message of a round did not arrive in time, while the round proceeded without
+/// them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Absence {
+    /// Round of the openings in which the parties were absent.
+    pub round: u32,
+    /// IDs of the absent parties.
+    pub parties: Vec<usize>,
+}
+
+/// Possible errors that may appear when the messages of a round do not arrive before its
+/// deadline.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DeadlineError {
+    /// Some parties did not send their message of the round in time.
+    #[error(
+        "the parties {missing:?} did not send their message of round {round} within {deadline:?}"
+    )]
+    MissingParties {
+        round: u32,
+        deadline: Duration,
+        missing: Vec<usize>,
+    },
+
+    /// Too few parties sent their message of the round in time to proceed without the others.
+    #[error("only {received} parties sent their message of round {round} in time, but {needed} are needed to proceed without the parties {missing:?}")]
+    NotEnoughParties {
+        round: u32,
+        received: usize,
+        needed: usize,
+        missing: Vec<usize>,
+    },
+}
+
+/// Returns the IDs of the parties whose message is missing from the messages gathered in a
+/// round.
+pub fn missing_parties<T>(gathered: &[Option<T>]) -> Vec<usize> {
+    gathered
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| message.is_none())
+        .map(|(party_id, _)| party_id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{missing_parties, RoundDeadline};
+
+    #[test]
+    fn deadlines_are_parsed() {
+        let deadline: RoundDeadline =
+            serde_json::from_str(r#"{ "millis": 250, "robust": true }"#).unwrap();
+        assert_eq!(deadline.duration(), Duration::from_millis(250));
+        assert!(deadline.robust);
+        assert!(deadline.tolerates_missing());
+        assert_eq!(deadline.needed_shares(2), 5);
+        let fallback: RoundDeadline =
+            serde_json::from_str(r#"{ "millis": 250, "fallback": true }"#).unwrap();
+        assert_eq!(fallback.needed_shares(2), 3);
+        assert!(!RoundDeadline::default().tolerates_missing());
+        assert_eq!(RoundDeadline::default().duration(), Duration::MAX);
+        assert!(serde_json::from_str::<RoundDeadline>(r#"{ "seconds": 1 }"#).is_err());
+        assert_eq!(missing_parties(&[Some(1), None, Some(3), None]), [1, 3]);
+    }
+}
@@ -0,0 +1,471 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    channel::{Channel, ChannelError, Readiness},
+    Packet,
+};
+
+/// Latency injected in a channel operation. The latency is sampled uniformly at random between
+/// `fixed` and `fixed + jitter`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Delay {
+    /// Minimum latency of the operation.
+    pub fixed: Duration,
+    /// Maximum latency added on top of the fixed latency.
+    pub jitter: Duration,
+}
+
+impl Delay {
+    /// Creates a constant latency.
+    pub fn fixed(latency: Duration) -> Self {
+        Self {
+            fixed: latency,
+            jitter: Duration::ZERO,
+        }
+    }
+
+    /// Creates a latency sampled uniformly between `min` and `max`.
+    pub fn uniform(min: Duration, max: Duration) -> Self {
+        Self {
+            fixed: min,
+            jitter: max.saturating_sub(min),
+        }
+    }
+
+    /// Samples the latency of an operation.
+    fn sample(&self) -> Duration {
+        if self.jitter.is_zero() {
+            self.fixed
+        } else {
+            self.fixed + rand::thread_rng().gen_range(Duration::ZERO..=self.jitter)
+        }
+    }
+
+    /// Blocks the current thread during a sampled latency.
+    fn wait(&self) {
+        let latency = self.sample();
+        if !latency.is_zero() {
+            std::thread::sleep(latency);
+        }
+    }
+}
+
+/// Channel that injects latency in the operations of another channel. It is intended to
+/// exercise the tolerance of the protocols to network delays without using real networks.
+pub struct DelayedChannel<C> {
+    /// Channel that actually transmits the packets.
+    inner: C,
+    /// Latency injected before sending a packet.
+    send_delay: Delay,
+    /// Latency injected after receiving a packet.
+    recv_delay: Delay,
+}
+
+impl<C: Channel> DelayedChannel<C> {
+    /// Creates a channel that delays the send and receive operations of `inner`.
+    pub fn new(inner: C, send_delay: Delay, recv_delay: Delay) -> Self {
+        Self {
+            inner,
+            send_delay,
+            recv_delay,
+        }
+    }
+}
+
+impl<C: Channel> Channel for DelayedChannel<C> {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.inner.shutdown()
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.inner.flush()
+    }
+
+    fn buffered(&self) -> usize {
+        self.inner.buffered()
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.inner.set_max_packet_size(max_packet_size)
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        self.inner.rekey()
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        self.inner.export_keying_material(label, output)
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        self.send_delay.wait();
+        self.inner.send(packet)
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        let packet = self.inner.recv()?;
+        self.recv_delay.wait();
+        Ok(packet)
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        let packet = self.inner.recv_timeout(timeout)?;
+        self.recv_delay.wait();
+        Ok(packet)
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        let packet = self.inner.try_recv()?;
+        if packet.is_some() {
+            self.recv_delay.wait();
+        }
+        Ok(packet)
+    }
+}
+
+/// Probabilities of the faults injected by a [`FaultyChannel`] on each received packet.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Faults {
+    /// Probability of dropping a packet.
+    pub drop: f64,
+    /// Probability of delivering a packet twice.
+    pub duplicate: f64,
+    /// Probability of delivering a packet after the next one.
+    pub reorder: f64,
+    /// Probability of flipping a random bit in the payload of a packet.
+    pub corrupt: f64,
+}
+
+/// Channel that injects faults in the packets received through another channel. The faulty
+/// packets keep their original header, so the layers on top of the channel can detect the
+/// anomaly. It is intended to verify in tests that the protocols either survive faults or fail
+/// with a clear error.
+pub struct FaultyChannel<C> {
+    /// Channel that actually transmits the packets.
+    inner: C,
+    /// Probabilities of each kind of fault.
+    faults: Faults,
+    /// Pseudo-random generator used to decide the faults. It is seeded to reproduce failures.
+    rng: StdRng,
+    /// Packet held back to be delivered after the next one.
+    held: Option<Packet>,
+    /// Packets ready to be delivered before receiving new ones from the inner channel.
+    ready: VecDeque<Packet>,
+}
+
+impl<C: Channel> FaultyChannel<C> {
+    /// Creates a channel that injects faults in the packets received through `inner`.
+    pub fn new(inner: C, faults: Faults, seed: u64) -> Self {
+        Self {
+            inner,
+            faults,
+            rng: StdRng::seed_from_u64(seed),
+            held: None,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Applies the faults to a packet received from the inner channel. Returns the packet that
+    /// must be delivered now, if any.
+    fn inject(&mut self, packet: Packet) -> Option<Packet> {
+        if self.rng.gen_bool(self.faults.drop) {
+            log::warn!("fault injection: dropping packet {:?}", packet.header());
+            return None;
+        }
+
+        let packet = if self.rng.gen_bool(self.faults.corrupt) && packet.size() > 0 {
+            let mut payload = packet.as_slice().to_vec();
+            let bit = self.rng.gen_range(0..payload.len() * 8);
+            payload[bit / 8] ^= 1 << (bit % 8);
+            log::warn!(
+                "fault injection: flipping bit {bit} of packet {:?}",
+                packet.header()
+            );
+            Packet::with_header(*packet.header(), payload)
+        } else {
+            packet
+        };
+
+        if self.held.is_none() && self.rng.gen_bool(self.faults.reorder) {
+            log::warn!("fault injection: reordering packet {:?}", packet.header());
+            self.held = Some(packet);
+            return None;
+        }
+        if let Some(held) = self.held.take() {
+            self.ready.push_back(held);
+        }
+        if self.rng.gen_bool(self.faults.duplicate) {
+            log::warn!("fault injection: duplicating packet {:?}", packet.header());
+            self.ready.push_front(packet.clone());
+        }
+        Some(packet)
+    }
+}
+
+impl<C: Channel> Channel for FaultyChannel<C> {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.inner.shutdown()
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.inner.flush()
+    }
+
+    fn buffered(&self) -> usize {
+        self.inner.buffered()
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.inner.set_max_packet_size(max_packet_size)
+    }
+
+    fn readiness(&self) -> Readiness {
+        self.inner.readiness()
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        self.inner.rekey()
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        self.inner.export_keying_material(label, output)
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        self.inner.send(packet)
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        loop {
+            if let Some(packet) = self.ready.pop_front() {
+                return Ok(packet);
+            }
+            let packet = self.inner.recv()?;
+            if let Some(packet) = self.inject(packet) {
+                return Ok(packet);
+            }
+        }
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(packet) = self.ready.pop_front() {
+                return Ok(packet);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                anyhow::bail!(ChannelError::RecvTimeout(timeout));
+            }
+            let packet = self.inner.recv_timeout(remaining)?;
+            if let Some(packet) = self.inject(packet) {
+                return Ok(packet);
+            }
+        }
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        loop {
+            if let Some(packet) = self.ready.pop_front() {
+                return Ok(Some(packet));
+            }
+            match self.inner.try_recv()? {
+                Some(packet) => {
+                    if let Some(packet) = self.inject(packet) {
+                        return Ok(Some(packet));
+                    }
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Throughput of a link in bytes per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bandwidth {
+    /// Number of bytes transmitted per second.
+    pub bytes_per_second: f64,
+}
+
+impl Bandwidth {
+    /// Creates a bandwidth of the given number of bytes per second, which must be positive.
+    pub fn bytes_per_second(bytes_per_second: f64) -> Self {
+        Self { bytes_per_second }
+    }
+
+    /// Creates a bandwidth of the given number of megabits per second, e.g. `10.0` for a
+    /// 10 Mbit link.
+    pub fn megabits_per_second(megabits: f64) -> Self {
+        Self::bytes_per_second(megabits * 1_000_000.0 / 8.0)
+    }
+
+    /// Returns the time that the link takes to transmit the given number of bytes.
+    fn transmission_time(&self, bytes: usize) -> Duration {
+        Duration::from_secs_f64(bytes as f64 / self.bytes_per_second)
+    }
+}
+
+/// Channel that limits the throughput of the packets sent through another channel to a given
+/// bandwidth. Sending a packet blocks until the link would have transmitted it, so the packets
+/// sent in a burst queue up behind each other. It is intended to measure the performance of the
+/// protocols under constrained links without using real networks.
+pub struct ThrottledChannel<C> {
+    /// Channel that actually transmits the packets.
+    inner: C,
+    /// Bandwidth of the simulated link.
+    bandwidth: Bandwidth,
+    /// Instant at which the simulated link finishes transmitting the packets sent so far.
+    link_free_at: Instant,
+}
+
+impl<C: Channel> ThrottledChannel<C> {
+    /// Creates a channel that sends the packets of `inner` at the given bandwidth.
+    pub fn new(inner: C, bandwidth: Bandwidth) -> Self {
+        Self {
+            inner,
+            bandwidth,
+            link_free_at: Instant::now(),
+        }
+    }
+}
+
+impl<C: Channel> Channel for ThrottledChannel<C> {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.inner.shutdown()
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.inner.flush()
+    }
+
+    fn buffered(&self) -> usize {
+        self.inner.buffered()
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.inner.set_max_packet_size(max_packet_size)
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        self.inner.rekey()
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        self.inner.export_keying_material(label, output)
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        let now = Instant::now();
+        let start = self.link_free_at.max(now);
+        self.link_free_at = start + self.bandwidth.transmission_time(packet.size());
+        std::thread::sleep(self.link_free_at - now);
+        self.inner.send(packet)
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        self.inner.recv()
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        self.inner.recv_timeout(timeout)
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        self.inner.try_recv()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Bandwidth, Delay, DelayedChannel, Faults, FaultyChannel, ThrottledChannel};
+    use crate::net::{
+        channel::{Channel, LoopBackChannel},
+        Packet,
+    };
+
+    #[test]
+    fn delayed_channel_waits() {
+        let delay = Duration::from_millis(20);
+        let mut channel = DelayedChannel::new(
+            LoopBackChannel::default(),
+            Delay::fixed(delay),
+            Delay::uniform(delay, 2 * delay),
+        );
+        let start = Instant::now();
+        channel.send(&Packet::new(vec![1])).unwrap();
+        let packet = channel.recv().unwrap();
+        assert_eq!(packet.as_slice(), &[1]);
+        assert!(start.elapsed() >= 2 * delay);
+    }
+
+    #[test]
+    fn faulty_channel_duplicates_and_corrupts() {
+        let faults = Faults {
+            duplicate: 1.0,
+            ..Default::default()
+        };
+        let mut channel = FaultyChannel::new(LoopBackChannel::default(), faults, 0);
+        channel.send(&Packet::new(vec![1])).unwrap();
+        assert_eq!(channel.recv().unwrap().as_slice(), &[1]);
+        assert_eq!(channel.recv().unwrap().as_slice(), &[1]);
+
+        let faults = Faults {
+            corrupt: 1.0,
+            ..Default::default()
+        };
+        let mut channel = FaultyChannel::new(LoopBackChannel::default(), faults, 0);
+        channel.send(&Packet::new(vec![0; 4])).unwrap();
+        let packet = channel.recv().unwrap();
+        let flipped_bits: u32 = packet.as_slice().iter().map(|byte| byte.count_ones()).sum();
+        assert_eq!(flipped_bits, 1);
+    }
+
+    #[test]
+    fn faulty_channel_drops_and_reorders() {
+        let faults = Faults {
+            drop: 1.0,
+            ..Default::default()
+        };
+        let mut channel = FaultyChannel::new(LoopBackChannel::default(), faults, 0);
+        channel.send(&Packet::new(vec![1])).unwrap();
+        assert!(channel.try_recv().unwrap().is_none());
+
+        let faults = Faults {
+            reorder: 1.0,
+            ..Default::default()
+        };
+        let mut channel = FaultyChannel::new(LoopBackChannel::default(), faults, 0);
+        channel.send(&Packet::new(vec![1])).unwrap();
+        channel.send(&Packet::new(vec![2])).unwrap();
+        channel.send(&Packet::new(vec![3])).unwrap();
+        assert_eq!(channel.recv().unwrap().as_slice(), &[2]);
+        assert_eq!(channel.recv().unwrap().as_slice(), &[1]);
+    }
+
+    #[test]
+    fn throttled_channel_limits_the_throughput() {
+        let bandwidth = Bandwidth::bytes_per_second(100_000.0);
+        let mut channel = ThrottledChannel::new(LoopBackChannel::default(), bandwidth);
+        let start = Instant::now();
+        for _ in 0..10 {
+            channel.send(&Packet::new(vec![0; 1000])).unwrap();
+        }
+        // Sending 10 KB at 100 KB/s takes 100 ms.
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        for _ in 0..10 {
+            assert_eq!(channel.recv().unwrap().size(), 1000);
+        }
+        assert_eq!(
+            Bandwidth::megabits_per_second(10.0),
+            Bandwidth::bytes_per_second(1_250_000.0)
+        );
+    }
+}
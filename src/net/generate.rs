@@ -0,0 +1,189 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+use super::{keys, pinning::Fingerprint};
+
+/// Template from which the network configuration of every party of a session is generated. The
+/// parties are reached at `base_port + i` and use mutual TLS with certificates signed by a common
+/// root CA, as produced by [`generate_certificates`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigTemplate {
+    /// Number of parties in the session.
+    pub n_parties: usize,
+    /// Port of the party with ID 0. The party with ID `i` listens in the port `base_port + i`.
+    pub base_port: u16,
+    /// Host of each party, or a single host shared by all the parties.
+    pub hosts: Vec<String>,
+    /// Timeout in milliseconds to connect with the other parties.
+    pub timeout: u64,
+    /// Time in milliseconds between the attempts to connect with another party.
+    pub sleep_time: u64,
+    /// Directory of the certificates and private keys referenced by the configurations.
+    pub cert_dir: PathBuf,
+}
+
+impl ConfigTemplate {
+    /// Checks that the template describes a valid session.
+    fn check(&self) -> anyhow::Result<()> {
+        if self.n_parties == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the number of parties is not correct",
+            )
+            .into());
+        }
+        if self.hosts.len() != 1 && self.hosts.len() != self.n_parties
+            || self.hosts.iter().any(String::is_empty)
+        {
+            return Err(
+                Error::new(ErrorKind::InvalidInput, "the list of hosts is not correct").into(),
+            );
+        }
+        if usize::from(self.base_port) + self.n_parties - 1 > usize::from(u16::MAX) {
+            return Err(Error::new(ErrorKind::InvalidInput, "the base port is not correct").into());
+        }
+        Ok(())
+    }
+
+    /// Returns the host of the party with the given ID.
+    fn host(&self, id: usize) -> &str {
+        if self.hosts.len() == 1 {
+            &self.hosts[0]
+        } else {
+            &self.hosts[id]
+        }
+    }
+
+    /// Returns the path of a file of the certificate directory as it is written in the
+    /// configurations.
+    fn cert_path(&self, file_name: &str) -> String {
+        self.cert_dir.join(file_name).display().to_string()
+    }
+
+    /// Returns the network configuration of the party with the given ID.
+    pub fn party_config(&self, id: usize) -> anyhow::Result<Value> {
+        self.check()?;
+        if id >= self.n_parties {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the ID of the party is not correct",
+            )
+            .into());
+        }
+        let peer_ips: Vec<&str> = (0..self.n_parties).map(|i| self.host(i)).collect();
+        Ok(json!({
+            "base_port": self.base_port,
+            "timeout": self.timeout,
+            "sleep_time": self.sleep_time,
+            "peer_ips": peer_ips,
+            "server_cert": self.cert_path(&keys::party_cert_file(id)),
+            "priv_key": self.cert_path(&keys::party_key_file(id)),
+            "trusted_certs": [self.cert_path(keys::CA_CERT_FILE)],
+        }))
+    }
+
+    /// Writes the network configuration of every party to the file `net_config_p<i>.json` of the
+    /// output directory, returning the paths of the files.
+    pub fn write_configs(&self, output_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        self.check()?;
+        fs::create_dir_all(output_dir)?;
+        let mut paths = Vec::with_capacity(self.n_parties);
+        for id in 0..self.n_parties {
+            let path = output_dir.join(format!("net_config_p{id}.json"));
+            fs::write(
+                &path,
+                serde_json::to_string_pretty(&self.party_config(id)?)?,
+            )?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+/// Generates in the given directory a self-signed root CA and, for each party, a private key and
+/// a certificate signed by the root CA with the DNS name `party<i>` that identifies the party.
+/// The hosts of the parties are added to the subject alternative names of their certificates,
+/// either as IP addresses or as DNS names, as described in [`keys::party_alt_names`]. Returns the
+/// fingerprints of the certificates of the parties.
+pub fn generate_certificates(
+    cert_dir: &Path,
+    hosts: &[String],
+    n_parties: usize,
+) -> anyhow::Result<Vec<Fingerprint>> {
+    if hosts.len() != 1 && hosts.len() != n_parties || hosts.iter().any(String::is_empty) {
+        return Err(Error::new(ErrorKind::InvalidInput, "the list of hosts is not correct").into());
+    }
+    keys::generate_ca(cert_dir)?;
+    let mut fingerprints = Vec::with_capacity(n_parties);
+    for id in 0..n_parties {
+        let host = if hosts.len() == 1 {
+            &hosts[0]
+        } else {
+            &hosts[id]
+        };
+        fingerprints.push(keys::generate_party(cert_dir, id, host)?);
+        log::info!(
+            "generated the certificate of party {id} in {:?}",
+            cert_dir.join(keys::party_cert_file(id))
+        );
+    }
+    Ok(fingerprints)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+
+    use super::ConfigTemplate;
+
+    fn template(n_parties: usize, hosts: &[&str]) -> ConfigTemplate {
+        ConfigTemplate {
+            n_parties,
+            base_port: 5000,
+            hosts: hosts.iter().map(|host| host.to_string()).collect(),
+            timeout: 5000,
+            sleep_time: 500,
+            cert_dir: PathBuf::from("./certs"),
+        }
+    }
+
+    #[test]
+    fn generate_party_configs() {
+        let config = template(3, &["127.0.0.1"]).party_config(1).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "base_port": 5000,
+                "timeout": 5000,
+                "sleep_time": 500,
+                "peer_ips": ["127.0.0.1", "127.0.0.1", "127.0.0.1"],
+                "server_cert": "./certs/server_cert_p1.crt",
+                "priv_key": "./certs/priv_key_p1.pem",
+                "trusted_certs": ["./certs/rootCA.crt"],
+            })
+        );
+        let config = template(2, &["10.0.0.1", "party1.example.com"])
+            .party_config(0)
+            .unwrap();
+        assert_eq!(
+            config["peer_ips"],
+            json!(["10.0.0.1", "party1.example.com"])
+        );
+
+        assert!(template(3, &["127.0.0.1"]).party_config(3).is_err());
+        assert!(template(0, &["127.0.0.1"]).party_config(0).is_err());
+        assert!(template(3, &["10.0.0.1", "10.0.0.2"])
+            .party_config(0)
+            .is_err());
+        let mut out_of_ports = template(3, &["127.0.0.1"]);
+        out_of_ports.base_port = u16::MAX - 1;
+        assert!(out_of_ports.party_config(0).is_err());
+    }
+}
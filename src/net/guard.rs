@@ -0,0 +1,190 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Length of the windows in which the rate of packets of a peer is measured.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Possible violations of the limits on the traffic received from a peer.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum GuardError {
+    /// The peer sent more packets in a second than allowed.
+    #[error("party {party_id} sent more than {limit} packets in a second")]
+    RateExceeded { party_id: usize, limit: u32 },
+
+    /// The peer sent a packet while the maximum number of its packets were still pending.
+    #[error("party {party_id} sent a packet while {limit} of its packets were still pending")]
+    TooManyPending { party_id: usize, limit: usize },
+
+    /// The peer sent a packet while its pending packets took the maximum number of bytes.
+    #[error(
+        "party {party_id} sent a packet while its pending packets took more than {limit} bytes"
+    )]
+    PendingBytesExceeded { party_id: usize, limit: usize },
+
+    /// The channel of the peer was closed because it exceeded the limits before.
+    #[error("the channel of party {0} was closed because it exceeded the receive limits")]
+    Disconnected(usize),
+}
+
+/// Limits on the traffic received from each peer, which keep a misbehaving peer from exhausting
+/// the memory or the CPU of the party. A peer that exceeds any of them is disconnected. The
+/// packets of the party to itself are not limited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReceiveLimits {
+    /// Maximum number of packets that a peer may send in each window of one second.
+    #[serde(default)]
+    pub max_packets_per_second: Option<u32>,
+    /// Maximum number of packets of a peer that may be pending, i.e. received but not processed
+    /// yet because they belong to a later round, protocol or session.
+    #[serde(default)]
+    pub max_pending_packets: Option<usize>,
+    /// Maximum number of bytes that the payloads of the pending packets of a peer may take.
+    #[serde(default)]
+    pub max_pending_bytes: Option<usize>,
+}
+
+/// Enforces the receive limits on the packets of each peer, and remembers the peers that were
+/// disconnected for exceeding them.
+#[derive(Debug, Clone)]
+pub struct ReceiveGuard {
+    /// Limits on the traffic of every peer.
+    limits: ReceiveLimits,
+    /// Start of the current rate window of each peer and number of packets received in it.
+    windows: Vec<Option<(Instant, u32)>>,
+    /// Whether each peer was disconnected for exceeding the limits.
+    disconnected: Vec<bool>,
+}
+
+impl ReceiveGuard {
+    /// Creates a guard without limits for the peers of a network of `n_parties`.
+    pub fn new(n_parties: usize) -> Self {
+        Self {
+            limits: ReceiveLimits::default(),
+            windows: vec![None; n_parties],
+            disconnected: vec![false; n_parties],
+        }
+    }
+
+    /// Sets the limits on the traffic of every peer.
+    pub fn set_limits(&mut self, limits: ReceiveLimits) {
+        self.limits = limits;
+    }
+
+    /// Returns the limits on the traffic of every peer.
+    pub fn limits(&self) -> &ReceiveLimits {
+        &self.limits
+    }
+
+    /// Checks a packet received from a given party at the given instant, given the number of
+    /// packets of the party that are pending and the bytes that they take.
+    pub fn check(
+        &mut self,
+        party_id: usize,
+        now: Instant,
+        pending_packets: usize,
+        pending_bytes: usize,
+    ) -> Result<(), GuardError> {
+        if self.disconnected[party_id] {
+            return Err(GuardError::Disconnected(party_id));
+        }
+        if let Some(limit) = self.limits.max_packets_per_second {
+            let window = match self.windows[party_id] {
+                Some((start, count)) if now.duration_since(start) < RATE_WINDOW => {
+                    (start, count + 1)
+                }
+                _ => (now, 1),
+            };
+            self.windows[party_id] = Some(window);
+            if window.1 > limit {
+                return Err(GuardError::RateExceeded { party_id, limit });
+            }
+        }
+        if let Some(limit) = self.limits.max_pending_packets {
+            if pending_packets >= limit {
+                return Err(GuardError::TooManyPending { party_id, limit });
+            }
+        }
+        if let Some(limit) = self.limits.max_pending_bytes {
+            if pending_bytes > limit {
+                return Err(GuardError::PendingBytesExceeded { party_id, limit });
+            }
+        }
+        Ok(())
+    }
+
+    /// Remembers that a party was disconnected, so its following packets are rejected.
+    pub fn disconnect(&mut self, party_id: usize) {
+        self.disconnected[party_id] = true;
+    }
+
+    /// Tells whether a party was disconnected for exceeding the limits.
+    pub fn is_disconnected(&self, party_id: usize) -> bool {
+        self.disconnected[party_id]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{GuardError, ReceiveGuard, ReceiveLimits};
+
+    #[test]
+    fn limit_the_rate_of_packets() {
+        let mut guard = ReceiveGuard::new(2);
+        guard.set_limits(ReceiveLimits {
+            max_packets_per_second: Some(3),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        for _ in 0..3 {
+            guard.check(0, start, 0, 0).unwrap();
+        }
+        // The windows of each peer are independent.
+        guard.check(1, start, 0, 0).unwrap();
+        assert_eq!(
+            guard.check(0, start + Duration::from_millis(500), 0, 0),
+            Err(GuardError::RateExceeded {
+                party_id: 0,
+                limit: 3
+            })
+        );
+        // A new window starts a second later.
+        guard
+            .check(0, start + Duration::from_secs(1), 0, 0)
+            .unwrap();
+    }
+
+    #[test]
+    fn limit_the_pending_packets() {
+        let mut guard = ReceiveGuard::new(1);
+        guard.set_limits(ReceiveLimits {
+            max_pending_packets: Some(2),
+            max_pending_bytes: Some(100),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        guard.check(0, now, 1, 100).unwrap();
+        assert_eq!(
+            guard.check(0, now, 2, 0),
+            Err(GuardError::TooManyPending {
+                party_id: 0,
+                limit: 2
+            })
+        );
+        assert_eq!(
+            guard.check(0, now, 1, 101),
+            Err(GuardError::PendingBytesExceeded {
+                party_id: 0,
+                limit: 100
+            })
+        );
+
+        assert!(!guard.is_disconnected(0));
+        guard.disconnect(0);
+        assert_eq!(guard.check(0, now, 0, 0), Err(GuardError::Disconnected(0)));
+    }
+}
@@ -0,0 +1,161 @@
+use rustls::pki_types::CertificateDer;
+use thiserror::Error;
+use webpki::EndEntityCert;
+
+use super::pinning::Fingerprint;
+
+/// Prefix of the DNS name in the subject alternative names of a certificate that identifies the
+/// party that owns the certificate. The party with ID `i` has the name `party<i>`.
+pub const PARTY_NAME_PREFIX: &str = "party";
+
+/// Possible errors that may appear when identifying a peer from its certificate.
+#[derive(Debug, Error)]
+pub enum IdentityError {
+    /// The peer did not present any certificate.
+    #[error("the peer did not present a certificate")]
+    MissingCertificate,
+
+    /// The certificate presented by the peer does not identify any party.
+    #[error("the certificate presented by the peer does not identify any party")]
+    UnknownCertificate,
+
+    /// The peer claimed an ID different from the one in its certificate.
+    #[error(
+        "the peer claimed to be party {claimed}, but its certificate belongs to party {certified}"
+    )]
+    IdMismatch { claimed: usize, certified: usize },
+}
+
+/// Source of the identity of the parties.
+#[derive(Debug, Clone, Default)]
+enum IdentitySource {
+    /// The ID of a party is taken from the `party<i>` DNS name in the subject alternative names of
+    /// its certificate.
+    #[default]
+    SubjectNames,
+    /// Certificate of each party, indexed by party ID.
+    Certs(Vec<CertificateDer<'static>>),
+    /// SHA-256 fingerprint of the certificate of each party, indexed by party ID.
+    Fingerprints(Vec<Fingerprint>),
+}
+
+/// Maps the certificates presented by the peers to their party IDs.
+#[derive(Debug, Clone, Default)]
+pub struct PeerIdentities {
+    /// Source of the identity of the parties.
+    source: IdentitySource,
+}
+
+impl PeerIdentities {
+    /// Creates the identities from the list of certificates of each party.
+    pub fn from_certs(peer_certs: Vec<CertificateDer<'static>>) -> Self {
+        Self {
+            source: IdentitySource::Certs(peer_certs),
+        }
+    }
+
+    /// Creates the identities from the list of fingerprints of the certificate of each party.
+    pub fn from_fingerprints(fingerprints: Vec<Fingerprint>) -> Self {
+        Self {
+            source: IdentitySource::Fingerprints(fingerprints),
+        }
+    }
+
+    /// Creates the identities that are derived from the subject alternative names of the
+    /// certificates.
+    pub fn from_subject_names() -> Self {
+        Self {
+            source: IdentitySource::SubjectNames,
+        }
+    }
+
+    /// Returns the pinned fingerprints of the certificates of the parties, if the identities are
+    /// given by fingerprints.
+    pub fn pinned_fingerprints(&self) -> Option<&[Fingerprint]> {
+        match &self.source {
+            IdentitySource::Fingerprints(fingerprints) => Some(fingerprints),
+            _ => None,
+        }
+    }
+
+    /// Returns the ID of the party that owns the given certificate.
+    pub fn identify(&self, cert: &CertificateDer) -> Result<usize, IdentityError> {
+        let party_id = match &self.source {
+            IdentitySource::SubjectNames => party_id_from_subject_names(cert),
+            IdentitySource::Certs(peer_certs) => peer_certs
+                .iter()
+                .position(|peer_cert| peer_cert.as_ref() == cert.as_ref()),
+            IdentitySource::Fingerprints(fingerprints) => {
+                let fingerprint = Fingerprint::of(cert);
+                fingerprints
+                    .iter()
+                    .position(|pinned| *pinned == fingerprint)
+            }
+        };
+        party_id.ok_or(IdentityError::UnknownCertificate)
+    }
+
+    /// Returns the ID of the party that owns the first certificate of a chain presented by a peer.
+    pub fn identify_chain(&self, chain: Option<&[CertificateDer]>) -> Result<usize, IdentityError> {
+        let cert = chain
+            .and_then(|chain| chain.first())
+            .ok_or(IdentityError::MissingCertificate)?;
+        self.identify(cert)
+    }
+}
+
+/// Extracts the party ID from the `party<i>` DNS name in the subject alternative names of a
+/// certificate.
+fn party_id_from_subject_names(cert: &CertificateDer) -> Option<usize> {
+    let cert = EndEntityCert::try_from(cert).ok()?;
+    let party_id = cert.valid_dns_names().find_map(|name| {
+        name.strip_prefix(PARTY_NAME_PREFIX)
+            .and_then(|id| id.parse().ok())
+    });
+    party_id
+}
+
+#[cfg(test)]
+mod tests {
+    use rustls::pki_types::CertificateDer;
+
+    use super::{IdentityError, PeerIdentities};
+    use crate::net::pinning::Fingerprint;
+
+    #[test]
+    fn identify_from_allow_list() {
+        let certs: Vec<CertificateDer> =
+            (0..3u8).map(|i| CertificateDer::from(vec![i; 4])).collect();
+        let identities = PeerIdentities::from_certs(certs.clone());
+        assert_eq!(identities.identify(&certs[2]).unwrap(), 2);
+        assert!(matches!(
+            identities.identify(&CertificateDer::from(vec![7; 4])),
+            Err(IdentityError::UnknownCertificate)
+        ));
+        assert!(matches!(
+            identities.identify_chain(None),
+            Err(IdentityError::MissingCertificate)
+        ));
+    }
+
+    #[test]
+    fn identify_from_fingerprints() {
+        let certs: Vec<CertificateDer> =
+            (0..3u8).map(|i| CertificateDer::from(vec![i; 4])).collect();
+        let fingerprints = certs.iter().map(Fingerprint::of).collect();
+        let identities = PeerIdentities::from_fingerprints(fingerprints);
+        assert_eq!(identities.identify(&certs[1]).unwrap(), 1);
+        assert!(identities
+            .identify(&CertificateDer::from(vec![7; 4]))
+            .is_err());
+    }
+
+    #[test]
+    fn reject_unparsable_certificate() {
+        let identities = PeerIdentities::from_subject_names();
+        assert!(matches!(
+            identities.identify(&CertificateDer::from(vec![0; 4])),
+            Err(IdentityError::UnknownCertificate)
+        ));
+    }
+}
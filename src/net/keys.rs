@@ -0,0 +1,685 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use aws_lc_rs::{
+    rand::SystemRandom,
+    signature::{
+        EcdsaKeyPair, KeyPair as _, RsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING, RSA_PKCS1_SHA256,
+    },
+};
+use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use serde_json::Value;
+
+use super::{pinning::Fingerprint, websocket::encode_base64};
+
+/// Name of the certificate of the root CA in the certificate directory.
+pub const CA_CERT_FILE: &str = "rootCA.crt";
+
+/// Name of the private key of the root CA in the certificate directory.
+pub const CA_KEY_FILE: &str = "rootCA.key";
+
+/// Days during which the certificate of the root CA is valid.
+const CA_VALIDITY_DAYS: u64 = 1825;
+
+/// Days during which the certificate of a party is valid.
+const PARTY_VALIDITY_DAYS: u64 = 365;
+
+/// Object identifier of the public keys on elliptic curves.
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+/// Object identifier of the P-256 curve.
+const OID_PRIME256V1: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+/// Object identifier of the ECDSA signatures with SHA-256.
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x02];
+/// Object identifier of the RSA signatures with SHA-256.
+const OID_SHA256_WITH_RSA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x0B];
+/// Object identifier of the common name of a subject.
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+/// Object identifier of the basic constraints extension.
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1D, 0x13];
+/// Object identifier of the key usage extension.
+const OID_KEY_USAGE: &[u8] = &[0x55, 0x1D, 0x0F];
+/// Object identifier of the subject alternative names extension.
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1D, 0x11];
+/// Object identifier of the extended key usage extension.
+const OID_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1D, 0x25];
+/// Object identifier of the usage of a key to authenticate a TLS server.
+const OID_SERVER_AUTH: &[u8] = &[0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
+/// Object identifier of the usage of a key to authenticate a TLS client.
+const OID_CLIENT_AUTH: &[u8] = &[0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x02];
+
+/// Returns the name of the certificate of a party in the certificate directory.
+pub fn party_cert_file(id: usize) -> String {
+    format!("server_cert_p{id}.crt")
+}
+
+/// Returns the name of the private key of a party in the certificate directory.
+pub fn party_key_file(id: usize) -> String {
+    format!("priv_key_p{id}.pem")
+}
+
+/// Name through which a party is reached, written in the subject alternative names of its
+/// certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubjectAltName {
+    /// DNS name.
+    Dns(String),
+    /// IP address.
+    Ip(IpAddr),
+}
+
+/// Returns the names of the certificate of a party, which contain the DNS name `party<id>` and
+/// the names through which the party is reached locally and at its host.
+pub fn party_alt_names(id: usize, host: &str) -> Vec<SubjectAltName> {
+    let mut alt_names = vec![
+        SubjectAltName::Dns("server".to_string()),
+        SubjectAltName::Dns("localhost".to_string()),
+        SubjectAltName::Dns(format!("party{id}")),
+        SubjectAltName::Ip(IpAddr::from([127, 0, 0, 1])),
+        SubjectAltName::Ip(IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1])),
+    ];
+    match host.parse::<IpAddr>() {
+        Ok(ip) if !ip.is_loopback() => alt_names.push(SubjectAltName::Ip(ip)),
+        Ok(_) => {}
+        Err(_) if host != "localhost" => alt_names.push(SubjectAltName::Dns(host.to_string())),
+        Err(_) => {}
+    }
+    alt_names
+}
+
+/// Private key in PKCS#8 format with which a certificate is issued or signed. The generated keys
+/// are ECDSA keys on the P-256 curve, while existing RSA keys, such as the ones created by the
+/// `openssl` command, can still sign certificates.
+pub struct KeyPair {
+    /// DER encoding of the key in PKCS#8 format.
+    pkcs8: Vec<u8>,
+}
+
+/// Key pair able to sign certificates.
+enum Signer {
+    /// ECDSA key on the P-256 curve.
+    Ecdsa(EcdsaKeyPair),
+    /// RSA key.
+    Rsa(RsaKeyPair),
+}
+
+impl KeyPair {
+    /// Generates a new ECDSA key pair on the P-256 curve.
+    pub fn generate() -> anyhow::Result<Self> {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &SystemRandom::new())
+                .map_err(|_| anyhow::anyhow!("the key pair could not be generated"))?;
+        Ok(Self {
+            pkcs8: pkcs8.as_ref().to_vec(),
+        })
+    }
+
+    /// Reads a private key in PKCS#8 format from a PEM file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let key = PrivateKeyDer::from_pem_file(path).map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("the private key {path:?} is not correct: {err}"),
+            )
+        })?;
+        let PrivateKeyDer::Pkcs8(key) = key else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("the private key {path:?} is not in PKCS#8 format"),
+            )
+            .into());
+        };
+        Ok(Self {
+            pkcs8: key.secret_pkcs8_der().to_vec(),
+        })
+    }
+
+    /// Writes the private key to a PEM file, which only its owner can read.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        write_private(path, &encode_pem("PRIVATE KEY", &self.pkcs8))
+    }
+
+    /// Returns the key as a key pair able to sign.
+    fn signer(&self) -> anyhow::Result<Signer> {
+        if let Ok(key) = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &self.pkcs8) {
+            return Ok(Signer::Ecdsa(key));
+        }
+        match RsaKeyPair::from_pkcs8(&self.pkcs8) {
+            Ok(key) => Ok(Signer::Rsa(key)),
+            Err(_) => anyhow::bail!("the private key is neither an ECDSA P-256 nor an RSA key"),
+        }
+    }
+
+    /// Returns the DER encoding of the public key as it is written in a certificate.
+    fn public_key_info(&self) -> anyhow::Result<Vec<u8>> {
+        let Signer::Ecdsa(key) = self.signer()? else {
+            anyhow::bail!("only ECDSA P-256 keys can be certified");
+        };
+        Ok(sequence(&[
+            sequence(&[der(0x06, OID_EC_PUBLIC_KEY), der(0x06, OID_PRIME256V1)]),
+            bit_string(key.public_key().as_ref()),
+        ]))
+    }
+}
+
+impl Signer {
+    /// Returns the DER encoding of the algorithm of the signatures.
+    fn algorithm(&self) -> Vec<u8> {
+        match self {
+            Signer::Ecdsa(_) => sequence(&[der(0x06, OID_ECDSA_WITH_SHA256)]),
+            Signer::Rsa(_) => sequence(&[der(0x06, OID_SHA256_WITH_RSA), der(0x05, &[])]),
+        }
+    }
+
+    /// Signs a message.
+    fn sign(&self, message: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let rng = SystemRandom::new();
+        let signature = match self {
+            Signer::Ecdsa(key) => key.sign(&rng, message).map(|sig| sig.as_ref().to_vec()),
+            Signer::Rsa(key) => {
+                let mut signature = vec![0; key.public_modulus_len()];
+                key.sign(&RSA_PKCS1_SHA256, &rng, message, &mut signature)
+                    .map(|()| signature)
+            }
+        };
+        signature.map_err(|_| anyhow::anyhow!("the certificate could not be signed"))
+    }
+}
+
+/// Parameters of a certificate to issue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateParams {
+    /// Common name of the subject of the certificate.
+    pub common_name: String,
+    /// Names through which the subject is reached.
+    pub alt_names: Vec<SubjectAltName>,
+    /// Whether the subject is a certificate authority that signs other certificates.
+    pub is_ca: bool,
+    /// Days during which the certificate is valid from now.
+    pub validity_days: u64,
+}
+
+impl CertificateParams {
+    /// Issues a certificate for the key signed by the key itself.
+    pub fn self_signed(&self, key: &KeyPair) -> anyhow::Result<CertificateDer<'static>> {
+        self.issue(key, name(&self.common_name), key)
+    }
+
+    /// Issues a certificate for the key signed by the key of the certificate of an issuer.
+    pub fn signed_by(
+        &self,
+        key: &KeyPair,
+        issuer_cert: &CertificateDer,
+        issuer_key: &KeyPair,
+    ) -> anyhow::Result<CertificateDer<'static>> {
+        let issuer = subject_of(issuer_cert).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "the certificate of the issuer is not correct",
+            )
+        })?;
+        self.issue(key, issuer.to_vec(), issuer_key)
+    }
+
+    /// Issues a certificate for the key with the given issuer name and signing key.
+    fn issue(
+        &self,
+        key: &KeyPair,
+        issuer: Vec<u8>,
+        issuer_key: &KeyPair,
+    ) -> anyhow::Result<CertificateDer<'static>> {
+        let signer = issuer_key.signer()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        // The certificate is valid since a while ago, in case the clocks of the parties differ.
+        let not_before = now.saturating_sub(3600);
+        let not_after = now + self.validity_days * 86400;
+
+        // A random serial number, which is positive and has no leading zeros in DER.
+        let mut serial: [u8; 16] = rand::random();
+        serial[0] = serial[0] & 0x7F | 0x40;
+
+        let mut extensions = vec![extension(
+            OID_BASIC_CONSTRAINTS,
+            true,
+            &if self.is_ca {
+                sequence(&[der(0x01, &[0xFF])])
+            } else {
+                sequence(&[])
+            },
+        )];
+        if self.is_ca {
+            // Signing of certificates and revocation lists.
+            extensions.push(extension(OID_KEY_USAGE, true, &key_usage(0x06)));
+        } else {
+            // Digital signatures and key encipherment.
+            extensions.push(extension(OID_KEY_USAGE, true, &key_usage(0xA0)));
+            extensions.push(extension(
+                OID_EXTENDED_KEY_USAGE,
+                false,
+                &sequence(&[der(0x06, OID_SERVER_AUTH), der(0x06, OID_CLIENT_AUTH)]),
+            ));
+        }
+        if !self.alt_names.is_empty() {
+            let names: Vec<Vec<u8>> = self
+                .alt_names
+                .iter()
+                .map(|alt_name| match alt_name {
+                    SubjectAltName::Dns(dns) => der(0x82, dns.as_bytes()),
+                    SubjectAltName::Ip(IpAddr::V4(ip)) => der(0x87, &ip.octets()),
+                    SubjectAltName::Ip(IpAddr::V6(ip)) => der(0x87, &ip.octets()),
+                })
+                .collect();
+            extensions.push(extension(OID_SUBJECT_ALT_NAME, false, &sequence(&names)));
+        }
+
+        let tbs_certificate = sequence(&[
+            der(0xA0, &der(0x02, &[2])),
+            der(0x02, &serial),
+            signer.algorithm(),
+            issuer,
+            sequence(&[time(not_before), time(not_after)]),
+            name(&self.common_name),
+            key.public_key_info()?,
+            der(0xA3, &sequence(&extensions)),
+        ]);
+        let signature = signer.sign(&tbs_certificate)?;
+        let certificate = sequence(&[tbs_certificate, signer.algorithm(), bit_string(&signature)]);
+        Ok(CertificateDer::from(certificate))
+    }
+}
+
+/// Reads the first certificate of a PEM file.
+pub fn load_certificate(path: &Path) -> anyhow::Result<CertificateDer<'static>> {
+    CertificateDer::from_pem_file(path).map_err(|err| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("the certificate {path:?} is not correct: {err}"),
+        )
+        .into()
+    })
+}
+
+/// Writes a certificate to a PEM file.
+pub fn save_certificate(path: &Path, cert: &CertificateDer) -> anyhow::Result<()> {
+    fs::write(path, encode_pem("CERTIFICATE", cert.as_ref()))?;
+    Ok(())
+}
+
+/// Generates in the given directory a root CA with a self-signed certificate, returning the
+/// fingerprint of its certificate.
+pub fn generate_ca(cert_dir: &Path) -> anyhow::Result<Fingerprint> {
+    fs::create_dir_all(cert_dir)?;
+    let key = KeyPair::generate()?;
+    let cert = CertificateParams {
+        common_name: "shami-rs root CA".to_string(),
+        alt_names: Vec::new(),
+        is_ca: true,
+        validity_days: CA_VALIDITY_DAYS,
+    }
+    .self_signed(&key)?;
+    key.save(&cert_dir.join(CA_KEY_FILE))?;
+    save_certificate(&cert_dir.join(CA_CERT_FILE), &cert)?;
+    Ok(Fingerprint::of(&cert))
+}
+
+/// Generates in the given directory a private key and a certificate for a party, signed by the
+/// root CA of the directory, returning the fingerprint of the certificate. The host of the party
+/// is added to the names of the certificate.
+pub fn generate_party(cert_dir: &Path, id: usize, host: &str) -> anyhow::Result<Fingerprint> {
+    let ca_cert = load_certificate(&cert_dir.join(CA_CERT_FILE))?;
+    let ca_key = KeyPair::load(&cert_dir.join(CA_KEY_FILE))?;
+    let key = KeyPair::generate()?;
+    let cert = CertificateParams {
+        common_name: format!("party{id}"),
+        alt_names: party_alt_names(id, host),
+        is_ca: false,
+        validity_days: PARTY_VALIDITY_DAYS,
+    }
+    .signed_by(&key, &ca_cert, &ca_key)?;
+    key.save(&cert_dir.join(party_key_file(id)))?;
+    save_certificate(&cert_dir.join(party_cert_file(id)), &cert)?;
+    Ok(Fingerprint::of(&cert))
+}
+
+/// Fingerprints of the certificate of a party before and after rotating its key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation {
+    /// Fingerprint of the previous certificate, if the party had one.
+    pub previous: Option<Fingerprint>,
+    /// Fingerprint of the new certificate.
+    pub current: Fingerprint,
+}
+
+/// Replaces the private key and the certificate of a party with new ones signed by the root CA
+/// of the directory. The previous files are kept with the `.old` extension, so they can be
+/// restored if the rotation has to be undone.
+pub fn rotate_party(cert_dir: &Path, id: usize, host: &str) -> anyhow::Result<Rotation> {
+    let cert_path = cert_dir.join(party_cert_file(id));
+    let previous = if cert_path.exists() {
+        let previous = Fingerprint::of(&load_certificate(&cert_path)?);
+        for path in [cert_path.clone(), cert_dir.join(party_key_file(id))] {
+            if path.exists() {
+                fs::rename(&path, old_path(&path))?;
+            }
+        }
+        Some(previous)
+    } else {
+        None
+    };
+    let current = generate_party(cert_dir, id, host)?;
+    Ok(Rotation { previous, current })
+}
+
+/// Pins the fingerprint of the certificate of a party in a network configuration, replacing the
+/// entry of the party in `peer_fingerprints`. Returns `false` without changing the configuration
+/// if it does not pin the certificates.
+pub fn pin_fingerprint(
+    config_path: &Path,
+    id: usize,
+    fingerprint: &Fingerprint,
+) -> anyhow::Result<bool> {
+    let mut config: Value = serde_json::from_slice(&fs::read(config_path)?)?;
+    let Some(fingerprints) = config["peer_fingerprints"].as_array_mut() else {
+        return Ok(false);
+    };
+    let Some(entry) = fingerprints.get_mut(id) else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("the peer fingerprints of {config_path:?} are not correct"),
+        )
+        .into());
+    };
+    *entry = Value::String(fingerprint.to_string());
+    fs::write(config_path, serde_json::to_string_pretty(&config)?)?;
+    Ok(true)
+}
+
+/// Returns the path with the `.old` extension appended.
+fn old_path(path: &Path) -> PathBuf {
+    let mut old = path.as_os_str().to_owned();
+    old.push(".old");
+    PathBuf::from(old)
+}
+
+/// Writes a file that only its owner can read.
+fn write_private(path: &Path, contents: &str) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::{io::Write, os::unix::fs::OpenOptionsExt};
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(contents.as_bytes())?;
+    }
+    #[cfg(not(unix))]
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Encodes DER bytes in a PEM block with the given label.
+fn encode_pem(label: &str, der: &[u8]) -> String {
+    let encoded = encode_base64(der);
+    let mut pem = format!("-----BEGIN {label}-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(&String::from_utf8_lossy(line));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {label}-----\n"));
+    pem
+}
+
+/// Encodes a DER element with the given tag and contents.
+fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    if contents.len() < 0x80 {
+        encoded.push(contents.len() as u8);
+    } else {
+        let length: Vec<u8> = contents
+            .len()
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        encoded.push(0x80 | length.len() as u8);
+        encoded.extend(length);
+    }
+    encoded.extend_from_slice(contents);
+    encoded
+}
+
+/// Encodes a DER sequence of elements.
+fn sequence(elements: &[Vec<u8>]) -> Vec<u8> {
+    der(0x30, &elements.concat())
+}
+
+/// Encodes a DER bit string of whole bytes.
+fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    let mut contents = vec![0];
+    contents.extend_from_slice(bytes);
+    der(0x03, &contents)
+}
+
+/// Encodes the key usage extension with the given flags, where the first usage is the most
+/// significant bit. The trailing zero bits are not encoded.
+fn key_usage(flags: u8) -> Vec<u8> {
+    der(0x03, &[flags.trailing_zeros() as u8, flags])
+}
+
+/// Encodes an extension of a certificate.
+fn extension(oid: &[u8], critical: bool, value: &[u8]) -> Vec<u8> {
+    let mut elements = vec![der(0x06, oid)];
+    if critical {
+        elements.push(der(0x01, &[0xFF]));
+    }
+    elements.push(der(0x04, value));
+    sequence(&elements)
+}
+
+/// Encodes a name with a single common name.
+fn name(common_name: &str) -> Vec<u8> {
+    sequence(&[der(
+        0x31,
+        &sequence(&[
+            der(0x06, OID_COMMON_NAME),
+            der(0x0C, common_name.as_bytes()),
+        ]),
+    )])
+}
+
+/// Encodes a time given in seconds since the Unix epoch, as a UTC time until 2049 and as a
+/// generalized time afterwards.
+fn time(seconds: u64) -> Vec<u8> {
+    let (year, month, day) = civil_date(seconds / 86400);
+    let time_of_day = seconds % 86400;
+    let rest = format!(
+        "{month:02}{day:02}{:02}{:02}{:02}Z",
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    );
+    if year < 2050 {
+        der(0x17, format!("{:02}{rest}", year % 100).as_bytes())
+    } else {
+        der(0x18, format!("{year:04}{rest}").as_bytes())
+    }
+}
+
+/// Returns the year, month and day of a number of days since the Unix epoch.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // Days since March 1st of the year 0, split into eras of 400 years.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Splits the first DER element of the input, returning its contents, its whole encoding and
+/// the rest of the input.
+fn split_element(input: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let first = *input.get(1)?;
+    let (length, header) = if first < 0x80 {
+        (usize::from(first), 2)
+    } else {
+        let n_bytes = usize::from(first & 0x7F);
+        let bytes = input.get(2..2 + n_bytes)?;
+        if n_bytes > size_of::<usize>() {
+            return None;
+        }
+        let length = bytes
+            .iter()
+            .fold(0, |length, byte| length << 8 | usize::from(*byte));
+        (length, 2 + n_bytes)
+    };
+    let end = header.checked_add(length)?;
+    let element = input.get(..end)?;
+    Some((&element[header..], element, &input[end..]))
+}
+
+/// Returns the DER encoding of the subject of a certificate.
+fn subject_of<'a>(cert: &'a CertificateDer) -> Option<&'a [u8]> {
+    let (certificate, _, _) = split_element(cert.as_ref())?;
+    let (mut fields, _, _) = split_element(certificate)?;
+    // The version is optional and tagged with [0].
+    if fields.first() == Some(&0xA0) {
+        fields = split_element(fields)?.2;
+    }
+    // Skip the serial number, the signature algorithm, the issuer and the validity.
+    for _ in 0..4 {
+        fields = split_element(fields)?.2;
+    }
+    Some(split_element(fields)?.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, net::IpAddr, path::PathBuf};
+
+    use rustls::{
+        crypto::aws_lc_rs,
+        pki_types::{ServerName, UnixTime},
+    };
+    use serde_json::json;
+    use webpki::{EndEntityCert, KeyUsage};
+
+    use super::{
+        civil_date, generate_ca, load_certificate, party_alt_names, party_cert_file,
+        party_key_file, pin_fingerprint, rotate_party, subject_of, time, KeyPair, SubjectAltName,
+        CA_CERT_FILE,
+    };
+    use crate::net::pinning::Fingerprint;
+
+    fn cert_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shami_rs_keys_{}_{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn party_alt_names_contain_the_hosts() {
+        assert!(party_alt_names(2, "127.0.0.1").contains(&SubjectAltName::Dns("party2".into())));
+        assert_eq!(party_alt_names(2, "127.0.0.1").len(), 5);
+        assert!(party_alt_names(0, "10.0.0.1")
+            .contains(&SubjectAltName::Ip("10.0.0.1".parse::<IpAddr>().unwrap())));
+        assert!(party_alt_names(0, "party0.example.com")
+            .contains(&SubjectAltName::Dns("party0.example.com".into())));
+    }
+
+    #[test]
+    fn encode_times() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(19_782), (2024, 2, 29));
+        assert_eq!(time(0), b"\x17\x0d700101000000Z");
+        assert_eq!(time(2_524_608_000 + 3661), b"\x18\x0f20500101010101Z");
+    }
+
+    #[test]
+    fn generate_and_verify_certificates() {
+        let dir = cert_dir("verify");
+        generate_ca(&dir).unwrap();
+        let rotation = rotate_party(&dir, 1, "10.0.0.1").unwrap();
+        assert_eq!(rotation.previous, None);
+
+        let ca_cert = load_certificate(&dir.join(CA_CERT_FILE)).unwrap();
+        let party_cert = load_certificate(&dir.join(party_cert_file(1))).unwrap();
+        assert_eq!(Fingerprint::of(&party_cert), rotation.current);
+        assert!(subject_of(&party_cert).is_some());
+        KeyPair::load(&dir.join(party_key_file(1))).unwrap();
+
+        let anchor = webpki::anchor_from_trusted_cert(&ca_cert).unwrap();
+        let end_entity = EndEntityCert::try_from(&party_cert).unwrap();
+        let algorithms = aws_lc_rs::default_provider()
+            .signature_verification_algorithms
+            .all;
+        for usage in [KeyUsage::server_auth(), KeyUsage::client_auth()] {
+            end_entity
+                .verify_for_usage(
+                    algorithms,
+                    std::slice::from_ref(&anchor),
+                    &[],
+                    UnixTime::now(),
+                    usage,
+                    None,
+                    None,
+                )
+                .unwrap();
+        }
+        for name in ["party1", "localhost", "10.0.0.1", "::1"] {
+            end_entity
+                .verify_is_valid_for_subject_name(&ServerName::try_from(name).unwrap())
+                .unwrap();
+        }
+        assert!(end_entity
+            .verify_is_valid_for_subject_name(&ServerName::try_from("party2").unwrap())
+            .is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotate_and_pin_keys() {
+        let dir = cert_dir("rotate");
+        generate_ca(&dir).unwrap();
+        let first = rotate_party(&dir, 0, "127.0.0.1").unwrap().current;
+        let config_path = dir.join("net_config_p1.json");
+        fs::write(
+            &config_path,
+            json!({ "peer_fingerprints": [first.to_string(), "00"] }).to_string(),
+        )
+        .unwrap();
+
+        let rotation = rotate_party(&dir, 0, "127.0.0.1").unwrap();
+        assert_eq!(rotation.previous, Some(first));
+        assert_ne!(rotation.current, first);
+        assert!(dir.join(format!("{}.old", party_cert_file(0))).exists());
+        assert!(dir.join(format!("{}.old", party_key_file(0))).exists());
+
+        assert!(pin_fingerprint(&config_path, 0, &rotation.current).unwrap());
+        let config: serde_json::Value =
+            serde_json::from_slice(&fs::read(&config_path).unwrap()).unwrap();
+        assert_eq!(
+            Fingerprint::parse(config["peer_fingerprints"][0].as_str().unwrap()).unwrap(),
+            rotation.current
+        );
+        assert!(pin_fingerprint(&config_path, 2, &rotation.current).is_err());
+        fs::write(&config_path, "{}").unwrap();
+        assert!(!pin_fingerprint(&config_path, 0, &rotation.current).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::{
+    channel::{check_sequence, next_header, Channel, ChannelError, Readiness, Signal},
+    Packet,
+};
+
+/// Channel that connects two parties running in the same process using in-memory queues.
+pub struct LocalChannel {
+    /// Queue to send packets to the remote party, closed when the channel is dropped.
+    sender: Option<Sender<Packet>>,
+    /// Queue to receive packets from the remote party.
+    receiver: Receiver<Packet>,
+    /// Signal raised by the remote party when it sends a packet or drops its channel.
+    recv_signal: Arc<Signal>,
+    /// Signal of the remote party, raised when this channel sends a packet or is dropped.
+    send_signal: Arc<Signal>,
+    /// Sequence number of the next packet to be sent.
+    send_seq: u64,
+    /// Sequence number of the next packet to be received.
+    recv_seq: u64,
+}
+
+impl LocalChannel {
+    /// Creates a pair of channels connected with each other.
+    pub fn pair() -> (Self, Self) {
+        let (sender_a, receiver_b) = mpsc::channel();
+        let (sender_b, receiver_a) = mpsc::channel();
+        let signal_a = Arc::new(Signal::default());
+        let signal_b = Arc::new(Signal::default());
+        (
+            Self::new(sender_a, receiver_a, signal_a.clone(), signal_b.clone()),
+            Self::new(sender_b, receiver_b, signal_b, signal_a),
+        )
+    }
+
+    fn new(
+        sender: Sender<Packet>,
+        receiver: Receiver<Packet>,
+        recv_signal: Arc<Signal>,
+        send_signal: Arc<Signal>,
+    ) -> Self {
+        Self {
+            sender: Some(sender),
+            receiver,
+            recv_signal,
+            send_signal,
+            send_seq: 0,
+            recv_seq: 0,
+        }
+    }
+
+    /// Checks the sequence number of a packet received from the queue.
+    fn accept(&mut self, packet: Packet) -> anyhow::Result<Packet> {
+        check_sequence(packet.header(), &mut self.recv_seq)?;
+        Ok(packet)
+    }
+}
+
+impl Channel for LocalChannel {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        log::info!("channel successfully closed");
+        Ok(())
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        let header = next_header(packet, &mut self.send_seq);
+        self.sender
+            .as_ref()
+            .ok_or(ChannelError::ConnectionClosed)?
+            .send(Packet::with_header(header, packet.as_slice().to_vec()))
+            .map_err(|_| ChannelError::ConnectionClosed)?;
+        self.send_signal.raise();
+        Ok(packet.size())
+    }
+
+    fn readiness(&self) -> Readiness {
+        Readiness::Signal(self.recv_signal.clone())
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        let packet = self
+            .receiver
+            .recv()
+            .map_err(|_| ChannelError::ConnectionClosed)?;
+        self.accept(packet)
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        let packet = self
+            .receiver
+            .recv_timeout(timeout)
+            .map_err(|err| match err {
+                RecvTimeoutError::Timeout => ChannelError::RecvTimeout(timeout),
+                RecvTimeoutError::Disconnected => ChannelError::ConnectionClosed,
+            })?;
+        self.accept(packet)
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        match self.receiver.try_recv() {
+            Ok(packet) => self.accept(packet).map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(ChannelError::ConnectionClosed.into()),
+        }
+    }
+}
+
+impl Drop for LocalChannel {
+    fn drop(&mut self) {
+        // The queue is closed before raising the signal, so the remote party that waits for it
+        // notices the closure once it wakes.
+        drop(self.sender.take());
+        self.send_signal.raise();
+    }
+}
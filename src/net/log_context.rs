@@ -0,0 +1,86 @@
+use std::cell::Cell;
+
+use serde::Serialize;
+
+thread_local! {
+    /// Context of the network used last by the current thread.
+    static CONTEXT: Cell<LogContext> = const { Cell::new(LogContext::EMPTY) };
+}
+
+/// Context of the network in which a log record is emitted, which allows to correlate the logs
+/// of the parties of a distributed execution. The network updates the context of the thread
+/// that uses it every time it sends a packet or starts a session, so the fields are not known
+/// before that.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LogContext {
+    /// ID of the party.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub party: Option<usize>,
+    /// Current session of the network.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<u32>,
+    /// Number of communication rounds of the network so far.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub round: Option<u64>,
+}
+
+impl LogContext {
+    /// Context without any field.
+    const EMPTY: Self = Self {
+        party: None,
+        session: None,
+        round: None,
+    };
+}
+
+/// Returns the context of the network used last by the current thread.
+pub fn current() -> LogContext {
+    CONTEXT.get()
+}
+
+/// Sets the ID of the party in the context of the current thread, so the records logged before
+/// the network is created are also attributed to the party.
+pub fn set_party(party: usize) {
+    CONTEXT.set(LogContext {
+        party: Some(party),
+        ..CONTEXT.get()
+    });
+}
+
+/// Sets the context of the current thread.
+pub(crate) fn set(context: LogContext) {
+    CONTEXT.set(context);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::{current, set_party, LogContext};
+    use crate::net::{Network, Packet};
+
+    #[test]
+    fn networks_update_the_context_of_their_thread() {
+        assert_eq!(current(), LogContext::default());
+        set_party(7);
+        assert_eq!(current().party, Some(7));
+
+        let handles: Vec<_> = Network::create_local(2)
+            .into_iter()
+            .map(|mut network| {
+                thread::spawn(move || {
+                    network.start_session(3).unwrap();
+                    network.broadcast_and_gather(&Packet::new(vec![1])).unwrap();
+                    network.broadcast_and_gather(&Packet::new(vec![2])).unwrap();
+                    (network.id(), current())
+                })
+            })
+            .collect();
+        for handle in handles {
+            let (id, context) = handle.join().unwrap();
+            assert_eq!(context.party, Some(id));
+            assert_eq!(context.session, Some(3));
+            assert!(context.round.is_some_and(|round| round >= 2));
+        }
+    }
+}
@@ -1,183 +1,1318 @@
+/// Byzantine agreement on a bit and on a common subset of the values contributed by the parties.
+pub mod agreement;
+/// Append-only, hash-chained log of the security-relevant events of a party.
+pub mod audit;
+/// Policy that governs the attempts to connect with a peer.
+pub mod backoff;
+/// Reliable broadcast and echo broadcast of a value to every party.
+pub mod broadcast;
+/// Channels that carry the packets between two parties, and the TLS channel over TCP.
 pub mod channel;
+/// Chaos tests that run protocols over local networks under randomized adverse conditions.
+pub mod chaos;
+/// Validation of network configurations without running a protocol.
+pub mod check;
+/// Coordinator that provides the roster of a session to its parties.
+pub mod coordinator;
+/// Deadlines of the rounds and the handling of the parties that miss them.
+pub mod deadline;
+/// Decorators that inject latency, faults and bandwidth limits in the channels for testing.
+pub mod decorator;
+/// Generation of the network configurations and certificates of a session.
+pub mod generate;
+/// Limits on the traffic received from each peer that protect a party from a flooding peer.
+pub mod guard;
+/// Identification of the peers from the names in their certificates.
+pub mod identity;
+/// Key pairs and certificates of the parties, generated and rotated without external tools.
+pub mod keys;
+/// In-memory channels between parties that run in the same process.
+pub mod local;
+/// Context of the network attached to the log records.
+pub mod log_context;
+/// Transport secured with the Noise protocol.
+#[cfg(feature = "noise")]
+pub mod noise;
+/// Overrides of the network configuration given by the environment and the command line.
+pub mod overrides;
+/// Pinning of the certificates of the peers by their fingerprint.
+pub mod pinning;
+/// INSECURE transport over plain TCP for benchmarking.
+pub mod plaintext;
+/// Live progress of a party, shown in a dashboard.
+pub mod progress;
+/// Channels that re-establish their connection when it drops.
+pub mod reconnect;
+/// Rotation of the keys of the channels in long-running sessions.
+pub mod rekey;
+/// Detection of packets delivered twice or out of sequence.
+pub mod sequence;
+/// Connections opened through a SOCKS5 proxy.
+pub mod socks;
+/// Halves of a network that send and receive from different threads.
+pub mod split;
+/// Statistics of the traffic of a network.
+pub mod stats;
+/// Streams of bytes split into chunks of bounded size.
+pub mod stream;
+/// Interface to the network whose receives are futures completed by a thread per channel, so
+/// that the receives from every peer are awaited at once.
+pub mod threaded;
+/// Configuration of mutual TLS between the parties.
+pub mod tls;
+/// Patterns in which the packets of the broadcasts travel among the parties.
+pub mod topology;
+/// Recording and replay of the packets received by a party.
+pub mod transcript;
+/// EXPERIMENTAL transport over UDP with a lightweight reliability layer.
+#[cfg(feature = "udp")]
+pub mod udp;
+/// Transport over Unix domain sockets for parties in the same host.
+#[cfg(unix)]
+pub mod unix;
+/// Typed values encoded in packets.
+pub mod value;
+/// Negotiation of the version of the wire format and the optional features with the peers.
+pub mod version;
+/// Transport that wraps the TLS channels in WebSocket frames.
+pub mod websocket;
 
+use crate::codec::Codec;
 use crate::net::channel::Channel;
-use channel::{DummyChannel, LoopBackChannel};
-use rustls::{
-    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
-    ClientConfig, RootCertStore, ServerConfig, StreamOwned,
-};
+use crate::trace::{self, Span};
+use crate::transport::Transport;
+use audit::{AuditEvent, AuditLog, SharedAuditLog};
+use backoff::Backoff;
+use channel::{ChannelError, DummyChannel, LoopBackChannel, TcpChannel};
+use coordinator::{Coordinator, CoordinatorConfig, Registration};
+use deadline::{missing_parties, Absence, RoundDeadline};
+use guard::{GuardError, ReceiveGuard, ReceiveLimits};
+use identity::PeerIdentities;
+use local::LocalChannel;
+use log_context::LogContext;
+#[cfg(feature = "noise")]
+use noise::{NoiseChannel, NoiseConfig};
+use overrides::Override;
+use plaintext::PlaintextChannel;
+use progress::{PeerStatus, Progress};
+use reconnect::{Acceptor, Reconnect, ReconnectingChannel, RetryPolicy};
+use rekey::{RekeyPolicy, RekeySchedule};
+use rustls::{pki_types::CertificateDer, ClientConfig, ServerConfig, StreamOwned};
+use sequence::{SequenceError, SequenceTracker};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
+use socks::Socks5Proxy;
+use split::{NetworkReceiver, NetworkSender};
+use stats::{NetworkStats, PhaseStats};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
 use std::{
     cmp::Ordering,
-    net::{Ipv4Addr, SocketAddr, TcpListener},
-    path::Path,
+    collections::VecDeque,
+    fmt,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    ops::Range,
+    panic,
+    path::{Path, PathBuf},
     str::FromStr,
-    time::Duration,
+    sync::{
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 use std::{
     fs,
-    io::{Error, ErrorKind},
+    io::{Error, ErrorKind, Read, Write},
 };
+use stream::{StreamReceiver, StreamSender};
+use threaded::ThreadedNetwork;
+use tls::TlsConfig;
+use topology::Topology;
+use transcript::{RecordingChannel, ReplayChannel, SharedTranscript, TranscriptWriter};
+#[cfg(feature = "udp")]
+use udp::{UdpChannel, UdpListener};
+#[cfg(unix)]
+use unix::UnixChannel;
+use value::{decode_value, encode_value};
+use version::{Capabilities, Features, Hello, VersionError, HELLO_PROTOCOL_ID};
+use websocket::WebSocketChannel;
+use zeroize::Zeroize;
+
+/// Header attached to every packet sent through a channel. It allows the receiver to detect
+/// messages that belong to a different protocol or round, and messages that arrive out of order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PacketHeader {
+    /// Session of the network in which the packet was sent. This value is assigned by the
+    /// network when the packet is sent.
+    pub session: u32,
+    /// ID of the party that sent the packet. This value is assigned by the network when the
+    /// packet is sent, and the receiver checks it against the channel through which the packet
+    /// arrived.
+    pub sender: u16,
+    /// Identifier of the protocol that produced the packet.
+    pub protocol_id: u16,
+    /// Round of the protocol in which the packet was produced.
+    pub round: u32,
+    /// Logical sub-channel of the connection in which the packet travels.
+    pub sub_channel: u16,
+    /// Position of the packet in the channel. This value is assigned by the channel when the
+    /// packet is sent.
+    pub seq: u64,
+}
+
+impl PacketHeader {
+    /// Size in bytes of the serialized header.
+    pub const SIZE: usize = 4 + 2 + 2 + 4 + 2 + 8;
 
-/// Packet of information sent through a given channel.
-pub struct Packet(Vec<u8>);
+    /// Checks whether the header has the given protocol ID and round.
+    pub fn matches(&self, protocol_id: u16, round: u32) -> bool {
+        self.protocol_id == protocol_id && self.round == round
+    }
+}
+
+/// Packet of information sent through a given channel. The payload is shared between the clones
+/// and slices of a packet, so cloning or slicing a packet does not copy its bytes. The payload is
+/// wiped from memory when the last packet that shares it is dropped.
+#[derive(Clone)]
+pub struct Packet {
+    /// Header of the packet.
+    header: PacketHeader,
+    /// Buffer that contains the payload of the packet.
+    buffer: Arc<Vec<u8>>,
+    /// Range of the buffer that contains the payload.
+    range: Range<usize>,
+}
 
 impl Packet {
+    /// Creates a packet without payload.
     pub fn empty() -> Self {
-        Self(Vec::new())
+        Self::new(Vec::new())
     }
-    /// Creates a new packet.
+
+    /// Creates a new packet with the default header. The buffer is moved into the packet
+    /// without copying it.
     pub fn new(buffer: Vec<u8>) -> Self {
-        Self(buffer)
+        Self::with_header(PacketHeader::default(), buffer)
+    }
+
+    /// Creates a new packet tagged with a protocol ID and a round number.
+    pub fn tagged(protocol_id: u16, round: u32, buffer: Vec<u8>) -> Self {
+        let header = PacketHeader {
+            protocol_id,
+            round,
+            ..Default::default()
+        };
+        Self::with_header(header, buffer)
+    }
+
+    /// Creates a packet from a header and a payload.
+    pub fn with_header(header: PacketHeader, buffer: Vec<u8>) -> Self {
+        Self {
+            header,
+            range: 0..buffer.len(),
+            buffer: Arc::new(buffer),
+        }
+    }
+
+    /// Moves the packet to the given session of the network.
+    pub(crate) fn in_session(mut self, session: u32) -> Self {
+        self.header.session = session;
+        self
+    }
+
+    /// Marks the packet as sent by the party of the given ID.
+    pub(crate) fn sent_by(mut self, sender: usize) -> Self {
+        self.header.sender = sender as u16;
+        self
+    }
+
+    /// Moves the packet to the given logical sub-channel.
+    pub fn in_sub_channel(mut self, sub_channel: u16) -> Self {
+        self.header.sub_channel = sub_channel;
+        self
+    }
+
+    /// Returns a packet with the same header whose payload is the given range of the payload of
+    /// this packet. The bytes are shared with this packet instead of being copied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of the bounds of the payload.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        assert!(
+            range.start <= range.end && range.end <= self.size(),
+            "range {range:?} out of the bounds of a payload of {} bytes",
+            self.size()
+        );
+        Self {
+            header: self.header,
+            buffer: Arc::clone(&self.buffer),
+            range: self.range.start + range.start..self.range.start + range.end,
+        }
+    }
+
+    /// Returns the header of the packet.
+    pub fn header(&self) -> &PacketHeader {
+        &self.header
     }
 
     /// Returns an slice to the packet.
     pub fn as_slice(&self) -> &[u8] {
-        &self.0
+        &self.buffer[self.range.clone()]
     }
 
     /// Returns the size of the packet.
     pub fn size(&self) -> usize {
-        self.0.len()
+        self.range.len()
+    }
+
+    /// Returns the payload of the packet. The buffer is returned without copying it if the
+    /// packet is its only owner and the payload spans the whole buffer.
+    pub fn into_payload(mut self) -> Vec<u8> {
+        if self.range.start == 0 && self.range.end == self.buffer.len() {
+            Arc::try_unwrap(std::mem::take(&mut self.buffer))
+                .unwrap_or_else(|buffer| buffer.as_ref().clone())
+        } else {
+            self.buffer[self.range.clone()].to_vec()
+        }
+    }
+}
+
+impl Drop for Packet {
+    fn drop(&mut self) {
+        if let Some(buffer) = Arc::get_mut(&mut self.buffer) {
+            buffer.zeroize();
+        }
+    }
+}
+
+impl fmt::Debug for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Packet")
+            .field("header", &self.header)
+            .field("payload", &self.as_slice())
+            .finish()
     }
 }
 
 impl From<&[u8]> for Packet {
     fn from(value: &[u8]) -> Self {
-        Self(Vec::from(value))
+        Self::new(Vec::from(value))
     }
 }
 
-/// Configuration of the network
-pub struct NetworkConfig<'a> {
-    /// Port that will be use as a base to define the port of each party. Party `i` will listen at
-    /// port `base_port + i`.
-    base_port: u16,
-    /// Timeout for receiving a message after calling the `recv()` function.
-    timeout: Duration,
-    /// Sleep time before trying to connect again with other party.
-    sleep_time: Duration,
-    /// IPs of each peer.
-    pub peer_ips: Vec<Ipv4Addr>,
-    /// Root of trust certificates when acting as a client.
-    root_cert_store: RootCertStore,
-    /// Certificates to act like a server.
-    server_cert: Vec<CertificateDer<'a>>,
-    /// Private key to act like a server.
-    priv_key: PrivateKeyDer<'a>,
+impl From<Vec<u8>> for Packet {
+    fn from(value: Vec<u8>) -> Self {
+        Self::new(value)
+    }
 }
 
-impl<'a> NetworkConfig<'a> {
-    /// Creates a configuration for the network from a configuration file.
-    pub fn new(path_file: &Path) -> anyhow::Result<Self> {
-        let json_content = fs::read_to_string(path_file)?;
-        let json: Value = serde_json::from_str(&json_content)?;
+/// Address at which a peer can be reached.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PeerEndpoint {
+    /// Host of the peer. It can be either an IP address or a hostname, which is resolved when
+    /// connecting to the peer.
+    pub host: String,
+    /// Port in which the peer listens for connections.
+    pub port: u16,
+    /// Local address in which the peer binds its listener, in case it differs from the public
+    /// address, as it happens behind a NAT.
+    #[serde(default)]
+    pub bind: Option<String>,
+    /// SOCKS5 proxy through which the connections with the peer are opened. If it is not
+    /// present, the global proxy of the configuration is used, if any.
+    #[serde(default)]
+    pub proxy: Option<Socks5Proxy>,
+}
+
+impl PeerEndpoint {
+    /// Parses the endpoints of the peers from the configuration JSON. The endpoints are taken from
+    /// the `peers` array if it is present, whose entries are either objects or `host:port`
+    /// strings. Otherwise, they are derived from the `peer_ips` array and the `base_port`, where
+    /// the party `i` listens at port `base_port + i`.
+    fn parse_all(json: &Value) -> anyhow::Result<Vec<Self>> {
+        if !json["peers"].is_null() {
+            let peers_json = json["peers"].as_array().ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                "the array of peers is not correct",
+            ))?;
+            let peers = peers_json
+                .iter()
+                .map(|peer| match peer.as_str() {
+                    Some(endpoint) => endpoint.parse(),
+                    None => Ok(serde_json::from_value(peer.clone())?),
+                })
+                .collect::<anyhow::Result<Vec<Self>>>()?;
+            if peers.iter().any(|peer| peer.host.is_empty()) {
+                return Err(
+                    Error::new(ErrorKind::InvalidInput, "the host of a peer is empty").into(),
+                );
+            }
+            return Ok(peers);
+        }
 
-        // Deserialize the peer ips.
+        let base_port = json["base_port"].as_u64().ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "the base port is not correct",
+        ))? as u16;
         let peers_ips_json = json["peer_ips"].as_array().ok_or(Error::new(
             ErrorKind::InvalidInput,
             "the array of peers is not correct",
         ))?;
-        let mut peer_ips = Vec::new();
-        for ip_value in peers_ips_json {
-            let ip_str = ip_value.as_str().ok_or(Error::new(
+        let mut peers = Vec::new();
+        for (i, ip_value) in peers_ips_json.iter().enumerate() {
+            let host = ip_value.as_str().ok_or(Error::new(
                 ErrorKind::InvalidInput,
                 "the ip of peer is not correct",
             ))?;
-            peer_ips.push(Ipv4Addr::from_str(ip_str)?);
+            if host.is_empty() {
+                return Err(
+                    Error::new(ErrorKind::InvalidInput, "the host of a peer is empty").into(),
+                );
+            }
+            peers.push(Self {
+                host: host.to_string(),
+                port: base_port + i as u16,
+                bind: None,
+                proxy: None,
+            });
         }
+        Ok(peers)
+    }
 
-        // Get private key.
-        let priv_key_pem = json["priv_key"].as_str().ok_or(Error::new(
-            ErrorKind::InvalidData,
-            "the private key has not the correct format",
-        ))?;
-        let priv_key = PrivateKeyDer::from_pem_file(priv_key_pem)?;
+    /// Returns the endpoint written as `host:port`, with the IPv6 addresses in brackets, as it is
+    /// parsed by [`PeerEndpoint::from_str`].
+    pub fn address(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
 
-        // Get the server certificate.
-        let server_cert_file = json["server_cert"].as_str().ok_or(Error::new(
-            ErrorKind::InvalidData,
-            "the private key has not the correct format",
-        ))?;
-        let server_cert = CertificateDer::pem_file_iter(server_cert_file)?
-            .map(|cert| cert.unwrap())
-            .collect();
+    /// Returns the address in which the peer must listen for connections.
+    fn listen_address(&self) -> anyhow::Result<SocketAddr> {
+        match &self.bind {
+            Some(bind) => bind.to_socket_addrs()?.next().ok_or(
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the bind address {bind} is not correct"),
+                )
+                .into(),
+            ),
+            None => Ok(channel::resolve_host(&self.host, self.port)?),
+        }
+    }
+}
+
+impl FromStr for PeerEndpoint {
+    type Err = anyhow::Error;
+
+    /// Parses an endpoint written as `host:port`, where the host is a hostname, an IPv4 address
+    /// or an IPv6 address in brackets, e.g. `[::1]:6000`.
+    fn from_str(endpoint: &str) -> anyhow::Result<Self> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("the endpoint {endpoint:?} is not of the form host:port"),
+            )
+        };
+        let (host, port) = endpoint.rsplit_once(':').ok_or_else(invalid)?;
+        let host = match host.strip_prefix('[') {
+            Some(host) => host.strip_suffix(']').ok_or_else(invalid)?,
+            // An IPv6 address without brackets can not be told apart from its port.
+            None if host.contains(':') => return Err(invalid().into()),
+            None => host,
+        };
+        if host.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "the host of a peer is empty").into());
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port: port.parse().map_err(|_| invalid())?,
+            bind: None,
+            proxy: None,
+        })
+    }
+}
+
+/// Configuration of the network
+pub struct NetworkConfig<'a> {
+    /// Timeout for receiving a message after calling the `recv()` function.
+    timeout: Duration,
+    /// Policy to retry the connection with other party while it is not listening yet.
+    backoff: Backoff,
+    /// Endpoint of each peer.
+    pub peers: Vec<PeerEndpoint>,
+    /// Configuration of the transport used to secure the connections.
+    transport: TransportConfig<'a>,
+    /// Whether the packets travel as WebSocket messages on top of the TLS connections.
+    websocket: bool,
+    /// Policy to re-establish the connections that drop. If it is not present, a dropped
+    /// connection is an error.
+    reconnect: Option<RetryPolicy>,
+    /// Maximum size in bytes of the payload of a received packet. If it is not present, the
+    /// size is only limited by the wire format.
+    max_packet_size: Option<usize>,
+    /// Limits on the rate and the pending packets of each peer.
+    receive_limits: ReceiveLimits,
+    /// Policy that decides when the keys of the channels are rotated.
+    rekey: RekeyPolicy,
+    /// Codec with which the values are encoded.
+    codec: Codec,
+    /// Deadline of the rounds that gather a message from every party.
+    round_deadline: RoundDeadline,
+    /// Pattern in which the packets of the broadcasts travel among the parties.
+    topology: Topology,
+    /// Time within which every execution must finish, after which the party aborts it.
+    deadline: Option<Duration>,
+    /// SOCKS5 proxy through which the connections with the peers that do not have their own
+    /// proxy are opened.
+    proxy: Option<Socks5Proxy>,
+    /// Path of the file in which the traffic of the party is recorded, if it is present.
+    transcript: Option<PathBuf>,
+    /// Path of the file in which the security-relevant events are recorded, if it is present.
+    audit_log: Option<PathBuf>,
+    /// Coordinator that provides the roster of the session. If it is present, the endpoints and
+    /// certificates of the peers are received from the coordinator.
+    coordinator: Option<CoordinatorConfig>,
+}
+
+/// Configuration of the transport used to secure the connections between parties.
+enum TransportConfig<'a> {
+    /// The connections are secured with mutual TLS.
+    Tls(TlsConfig<'a>),
+    /// The connections are secured with the Noise protocol.
+    #[cfg(feature = "noise")]
+    Noise(Arc<NoiseConfig>),
+    /// The connections are not secured. This is INSECURE and only intended for benchmarking on
+    /// trusted local networks.
+    Plaintext,
+    /// The packets travel through UDP with a lightweight reliability layer. This is EXPERIMENTAL
+    /// and, as the packets are not secured, only intended for trusted networks.
+    #[cfg(feature = "udp")]
+    Udp,
+    /// The parties run in the same host and communicate through Unix domain sockets, whose paths
+    /// are indexed by party ID.
+    #[cfg(unix)]
+    Unix(Vec<PathBuf>),
+}
+
+impl TransportConfig<'static> {
+    /// Reads the configuration of the transport from the JSON of the network configuration. Unix
+    /// domain sockets are used if the `unix_sockets` array is present, UDP is used if `udp` is
+    /// enabled, the Noise transport is used if the `noise` object is present, and TLS is used
+    /// otherwise.
+    fn from_json(json: &Value, n_parties: usize) -> anyhow::Result<Self> {
+        if !json["unix_sockets"].is_null() {
+            #[cfg(unix)]
+            return Ok(Self::Unix(Self::parse_unix_sockets(json)?));
+
+            #[cfg(not(unix))]
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Unix domain sockets are not supported in this platform",
+            )
+            .into());
+        }
+
+        if json["udp"].as_bool().unwrap_or(false) {
+            #[cfg(feature = "udp")]
+            {
+                log::warn!("the UDP transport is experimental and the packets are not secured");
+                return Ok(Self::Udp);
+            }
+
+            #[cfg(not(feature = "udp"))]
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "the UDP transport requires compiling with the `udp` feature",
+            )
+            .into());
+        }
+
+        if json["insecure_plaintext"].as_bool().unwrap_or(false) {
+            log::warn!("the connections are not secured: the packets travel in plain text");
+            return Ok(Self::Plaintext);
+        }
+
+        if json["noise"].is_null() {
+            return Ok(Self::Tls(TlsConfig::from_json(json, n_parties)?));
+        }
+
+        #[cfg(feature = "noise")]
+        return Ok(Self::Noise(Arc::new(NoiseConfig::from_json(
+            &json["noise"],
+            n_parties,
+        )?)));
+
+        #[cfg(not(feature = "noise"))]
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "the Noise transport requires compiling with the `noise` feature",
+        )
+        .into())
+    }
+}
+
+impl TransportConfig<'_> {
+    /// Returns the name of the transport.
+    fn name(&self) -> &'static str {
+        match self {
+            TransportConfig::Tls(_) => "tls",
+            #[cfg(feature = "noise")]
+            TransportConfig::Noise(_) => "noise",
+            TransportConfig::Plaintext => "plaintext",
+            #[cfg(feature = "udp")]
+            TransportConfig::Udp => "udp",
+            #[cfg(unix)]
+            TransportConfig::Unix(_) => "unix",
+        }
+    }
+
+    /// Tells whether the peers prove their identity in the handshake of the transport.
+    fn is_authenticated(&self) -> bool {
+        match self {
+            TransportConfig::Tls(_) => true,
+            #[cfg(feature = "noise")]
+            TransportConfig::Noise(_) => true,
+            _ => false,
+        }
+    }
+}
 
-        // Get trusted certificates.
-        let trusted_certs_json = json["trusted_certs"].as_array().ok_or(Error::new(
+#[cfg(unix)]
+impl TransportConfig<'_> {
+    /// Parses the paths of the Unix domain sockets of the parties.
+    fn parse_unix_sockets(json: &Value) -> anyhow::Result<Vec<PathBuf>> {
+        let paths_json = json["unix_sockets"].as_array().ok_or(Error::new(
             ErrorKind::InvalidInput,
-            "the array of peers is not correct",
+            "the array of Unix sockets is not correct",
         ))?;
-        let mut trusted_certs = Vec::new();
-        for trusted_cert in trusted_certs_json {
-            let trusted_cert_path = trusted_cert.as_str().ok_or(Error::new(
+        let mut paths = Vec::new();
+        for path in paths_json {
+            let path = path.as_str().ok_or(Error::new(
                 ErrorKind::InvalidInput,
-                "the ip of peer is not correct",
+                "the path of a Unix socket is not correct",
             ))?;
-            trusted_certs
-                .extend(CertificateDer::pem_file_iter(trusted_cert_path)?.map(|cert| cert.unwrap()))
+            paths.push(PathBuf::from(path));
         }
-        let mut root_cert_store = RootCertStore::empty();
-        let (certs_added, certs_ignored) = root_cert_store.add_parsable_certificates(trusted_certs);
-        log::info!("added {certs_added} certificates, ignored {certs_ignored} certificates to the root certificate store");
+        Ok(paths)
+    }
+}
 
-        Ok(Self {
-            base_port: json["base_port"].as_u64().ok_or(Error::new(
+/// Establishes the secure connections with the peers using the configured transport.
+#[derive(Clone)]
+enum Connector {
+    /// Connects using mutual TLS.
+    Tls {
+        client_conf: Arc<ClientConfig>,
+        server_conf: Arc<ServerConfig>,
+        identities: PeerIdentities,
+        websocket: bool,
+    },
+    /// Connects using the Noise protocol.
+    #[cfg(feature = "noise")]
+    Noise(Arc<NoiseConfig>),
+    /// Connects without securing the connections.
+    Plaintext,
+}
+
+impl Connector {
+    /// Creates the connector for the configured transport.
+    fn new(config: &NetworkConfig<'static>) -> anyhow::Result<Self> {
+        match &config.transport {
+            TransportConfig::Tls(tls) => {
+                let (client_conf, server_conf) = tls.configure()?;
+                Ok(Self::Tls {
+                    client_conf: Arc::new(client_conf),
+                    server_conf: Arc::new(server_conf),
+                    identities: tls.identities.clone(),
+                    websocket: config.websocket,
+                })
+            }
+            #[cfg(feature = "noise")]
+            TransportConfig::Noise(noise) => Ok(Self::Noise(noise.clone())),
+            TransportConfig::Plaintext => Ok(Self::Plaintext),
+            #[cfg(unix)]
+            TransportConfig::Unix(_) => unreachable!("Unix sockets do not use a connector"),
+            #[cfg(feature = "udp")]
+            TransportConfig::Udp => unreachable!("UDP sockets do not use a connector"),
+        }
+    }
+
+    /// Connects as a client with the peer `remote_id`, which listens at the given endpoint.
+    fn connect(
+        &self,
+        local_id: usize,
+        remote_id: usize,
+        peer: &PeerEndpoint,
+        backoff: &Backoff,
+    ) -> anyhow::Result<Box<dyn Channel>> {
+        match self {
+            Self::Tls {
+                client_conf,
+                identities,
+                websocket: true,
+                ..
+            } => {
+                let (client_conn, tcp_stream) = channel::connect_tls(
+                    local_id,
+                    remote_id,
+                    peer,
+                    backoff,
+                    client_conf,
+                    identities,
+                )?;
+                let stream = StreamOwned::new(client_conn, tcp_stream);
+                Ok(Box::new(WebSocketChannel::connect(
+                    stream, &peer.host, local_id,
+                )?))
+            }
+            Self::Tls {
+                client_conf,
+                identities,
+                websocket: false,
+                ..
+            } => {
+                let (client_conn, tcp_stream) = channel::connect_as_client(
+                    local_id,
+                    remote_id,
+                    peer,
+                    backoff,
+                    client_conf,
+                    identities,
+                )?;
+                Ok(Box::new(TcpChannel::new(StreamOwned::new(
+                    client_conn,
+                    tcp_stream,
+                ))))
+            }
+            #[cfg(feature = "noise")]
+            Self::Noise(noise) => {
+                let tcp_stream = channel::connect_tcp(local_id, peer, backoff)?;
+                Ok(Box::new(NoiseChannel::connect(
+                    tcp_stream, remote_id, noise,
+                )?))
+            }
+            Self::Plaintext => Ok(Box::new(PlaintextChannel::connect(
+                local_id, peer, backoff,
+            )?)),
+        }
+    }
+
+    /// Completes the connection accepted from a peer as a server. Returns the channel and the ID
+    /// of the peer.
+    fn accept(&self, tcp_stream: TcpStream) -> anyhow::Result<(Box<dyn Channel>, usize)> {
+        match self {
+            Self::Tls {
+                server_conf,
+                identities,
+                websocket: true,
+                ..
+            } => {
+                let (server_conn, tcp_stream, remote_id) =
+                    channel::accept_tls(tcp_stream, server_conf, identities)?;
+                let stream = StreamOwned::new(server_conn, tcp_stream);
+                let (channel, claimed_id) = WebSocketChannel::accept(stream)?;
+                channel::check_claimed_id(claimed_id, remote_id)?;
+                Ok((Box::new(channel), remote_id))
+            }
+            Self::Tls {
+                server_conf,
+                identities,
+                websocket: false,
+                ..
+            } => {
+                let (server_conn, tcp_stream, remote_id) =
+                    channel::accept_connection(tcp_stream, server_conf, identities)?;
+                Ok((
+                    Box::new(TcpChannel::new(StreamOwned::new(server_conn, tcp_stream))),
+                    remote_id,
+                ))
+            }
+            #[cfg(feature = "noise")]
+            Self::Noise(noise) => {
+                let (channel, remote_id) = NoiseChannel::accept(tcp_stream, noise)?;
+                Ok((Box::new(channel), remote_id))
+            }
+            Self::Plaintext => {
+                let (channel, remote_id) = PlaintextChannel::accept(tcp_stream)?;
+                Ok((Box::new(channel), remote_id))
+            }
+        }
+    }
+}
+
+impl<'a> NetworkConfig<'a> {
+    /// Creates a configuration for the network from a configuration file.
+    pub fn new(path_file: &Path) -> anyhow::Result<Self> {
+        Self::with_overrides(path_file, &[])
+    }
+
+    /// Creates a configuration for the network from a configuration file whose settings are
+    /// replaced by the given overrides, applied in order.
+    pub fn with_overrides(path_file: &Path, overrides: &[Override]) -> anyhow::Result<Self> {
+        let json_content = fs::read_to_string(path_file)?;
+        let mut json: Value = serde_json::from_str(&json_content)?;
+        overrides::apply_all(&mut json, overrides)?;
+
+        // Deserialize the endpoints of the peers. They are not needed if the parties communicate
+        // through Unix domain sockets, and they are received later if there is a coordinator.
+        let coordinator = CoordinatorConfig::from_json(&json["coordinator"])?;
+        let peers = if json["unix_sockets"].is_null() && coordinator.is_none() {
+            PeerEndpoint::parse_all(&json)?
+        } else {
+            Vec::new()
+        };
+
+        // Get the cryptographic material of the transport.
+        let transport = TransportConfig::from_json(&json, peers.len())?;
+        let websocket = json["websocket"].as_bool().unwrap_or(false);
+        if websocket && !matches!(transport, TransportConfig::Tls(_)) {
+            return Err(Error::new(
                 ErrorKind::InvalidInput,
-                "the base port is not correct",
-            ))? as u16,
-            timeout: Duration::from_millis(json["timeout"].as_u64().ok_or(Error::new(
+                "the WebSocket transport requires TLS",
+            )
+            .into());
+        }
+        if coordinator.is_some()
+            && (!matches!(transport, TransportConfig::Tls(_))
+                || !json["peer_certs"].is_null()
+                || !json["peer_fingerprints"].is_null())
+        {
+            return Err(Error::new(
                 ErrorKind::InvalidInput,
-                "timeout is not correct",
-            ))?),
-            sleep_time: Duration::from_millis(json["sleep_time"].as_u64().ok_or(Error::new(
+                "the coordinator requires TLS with the certificates of the peers taken from the roster",
+            )
+            .into());
+        }
+        let reconnect = RetryPolicy::from_json(&json["reconnect"])?;
+        if reconnect.is_some() && json["unix_sockets"].is_array() {
+            return Err(Error::new(
                 ErrorKind::InvalidInput,
-                "the timeout is not correct",
-            ))?),
-            peer_ips,
-            root_cert_store,
-            priv_key,
-            server_cert,
-        })
+                "the connections through Unix domain sockets cannot be re-established",
+            )
+            .into());
+        }
+        #[cfg(feature = "udp")]
+        if matches!(transport, TransportConfig::Udp)
+            && (reconnect.is_some() || !json["proxy"].is_null())
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the UDP transport does not support reconnections nor proxies",
+            )
+            .into());
+        }
+
+        let max_packet_size = match &json["max_packet_size"] {
+            Value::Null => None,
+            value => Some(
+                value
+                    .as_u64()
+                    .and_then(|size| usize::try_from(size).ok())
+                    .filter(|size| *size <= channel::MAX_WIRE_PACKET_SIZE)
+                    .ok_or(Error::new(
+                        ErrorKind::InvalidInput,
+                        "the maximum packet size is not correct",
+                    ))?,
+            ),
+        };
+
+        let receive_limits = match &json["receive_limits"] {
+            Value::Null => ReceiveLimits::default(),
+            value => serde_json::from_value(value.clone()).map_err(|err| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the receive limits are not correct: {err}"),
+                )
+            })?,
+        };
+
+        let rekey = match &json["rekey"] {
+            Value::Null => RekeyPolicy::default(),
+            value => serde_json::from_value(value.clone()).map_err(|err| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the rekey policy is not correct: {err}"),
+                )
+            })?,
+        };
+
+        let codec = match &json["codec"] {
+            Value::Null => Codec::default(),
+            value => serde_json::from_value(value.clone()).map_err(|err| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the codec is not correct: {err}"),
+                )
+            })?,
+        };
+
+        let round_deadline = match &json["round_deadline"] {
+            Value::Null => RoundDeadline::default(),
+            value => serde_json::from_value(value.clone()).map_err(|err| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the round deadline is not correct: {err}"),
+                )
+            })?,
+        };
+
+        let topology = match &json["topology"] {
+            Value::Null => Topology::default(),
+            value => serde_json::from_value(value.clone()).map_err(|err| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the topology is not correct: {err}"),
+                )
+            })?,
+        };
+
+        let deadline = match &json["deadline"] {
+            Value::Null => None,
+            value => Some(Duration::from_millis(value.as_u64().ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                "the deadline is not correct",
+            ))?)),
+        };
+
+        let proxy: Option<Socks5Proxy> = match &json["proxy"] {
+            Value::Null => None,
+            value => Some(serde_json::from_value(value.clone()).map_err(|err| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the proxy configuration is not correct: {err}"),
+                )
+            })?),
+        };
+
+        let transcript = match &json["transcript"] {
+            Value::Null => None,
+            value => Some(PathBuf::from(value.as_str().ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                "the path of the transcript is not correct",
+            ))?)),
+        };
+
+        let audit_log = match &json["audit_log"] {
+            Value::Null => None,
+            value => Some(PathBuf::from(value.as_str().ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                "the path of the audit log is not correct",
+            ))?)),
+        };
+
+        let timeout = Duration::from_millis(json["timeout"].as_u64().ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "timeout is not correct",
+        ))?);
+        let sleep_time = Duration::from_millis(json["sleep_time"].as_u64().ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "the timeout is not correct",
+        ))?);
+        let backoff = Backoff::from_json(&json["backoff"], timeout, sleep_time)?;
+
+        let mut config = Self {
+            timeout,
+            backoff,
+            peers,
+            transport,
+            websocket,
+            reconnect,
+            max_packet_size,
+            receive_limits,
+            rekey,
+            codec,
+            round_deadline,
+            topology,
+            deadline,
+            proxy,
+            transcript,
+            audit_log,
+            coordinator,
+        };
+        config.use_global_proxy();
+        Ok(config)
+    }
+
+    /// Makes the peers that do not have their own proxy use the global proxy.
+    fn use_global_proxy(&mut self) {
+        if let Some(proxy) = &self.proxy {
+            for peer in self.peers.iter_mut().filter(|peer| peer.proxy.is_none()) {
+                peer.proxy = Some(proxy.clone());
+            }
+        }
+    }
+
+    /// Registers the party with the coordinator of the session and waits for the roster, which
+    /// gives the endpoints and certificates of the peers. It does nothing if the configuration
+    /// does not have a coordinator.
+    pub fn register(&mut self, id: usize) -> anyhow::Result<()> {
+        let (Some(coordinator), TransportConfig::Tls(tls)) =
+            (&self.coordinator, &mut self.transport)
+        else {
+            return Ok(());
+        };
+        let registration = Registration {
+            id,
+            n_parties: coordinator.n_parties,
+            host: coordinator.endpoint.host.clone(),
+            port: coordinator.endpoint.port,
+            cert: tls.own_cert().to_vec(),
+        };
+        let roster = coordinator::register(coordinator, &registration, &self.backoff)?;
+
+        // The party listens at its own bind address, which is not shared with the others.
+        self.peers = roster.iter().map(|entry| entry.endpoint()).collect();
+        self.peers[id] = coordinator.endpoint.clone();
+        tls.trust_peer_certs(
+            roster
+                .into_iter()
+                .map(|entry| CertificateDer::from(entry.cert))
+                .collect(),
+        );
+        self.use_global_proxy();
+        Ok(())
+    }
+
+    /// Serves as the coordinator of the session in a background thread, which finishes once it
+    /// sends the roster to every party.
+    pub fn serve_coordinator(&self) -> anyhow::Result<JoinHandle<anyhow::Result<()>>> {
+        let coordinator = self.coordinator.as_ref().ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "the configuration does not have a coordinator",
+        ))?;
+        let address = channel::resolve_host(&coordinator.host, coordinator.port)?;
+        let coordinator = Coordinator::bind(address, coordinator.n_parties)?;
+        Ok(thread::spawn(move || coordinator.run().map(|_| ())))
+    }
+
+    /// Returns the number of parties in the network.
+    pub fn n_parties(&self) -> usize {
+        match &self.transport {
+            #[cfg(unix)]
+            TransportConfig::Unix(paths) => paths.len(),
+            _ => self.peers.len(),
+        }
+    }
+}
+
+/// Errors produced while communicating with several peers in a single call, together with the
+/// ID of the peer that produced each one.
+#[derive(Debug)]
+pub struct PeerErrors(pub Vec<(usize, anyhow::Error)>);
+
+impl fmt::Display for PeerErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "communication failed with {} peers", self.0.len())?;
+        for (party_id, err) in &self.0 {
+            write!(f, "; party {party_id}: {err}")?;
+        }
+        Ok(())
     }
 }
 
+impl std::error::Error for PeerErrors {}
+
+/// Decision taken over a packet received while waiting for a specific kind of packet.
+enum Disposition {
+    /// The packet is the one expected.
+    Accept,
+    /// The packet is not expected yet, so it is kept for later.
+    Buffer,
+    /// The packet is not useful anymore, so it is dropped.
+    Discard,
+}
+
+impl Disposition {
+    /// Accepts the packet if the condition holds. Otherwise, the packet is buffered.
+    fn accept_if(condition: bool) -> Self {
+        if condition {
+            Self::Accept
+        } else {
+            Self::Buffer
+        }
+    }
+}
+
+/// Time to wait between polls of the channels when no packet is ready in [`Network::recv_any`].
+const RECV_ANY_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Protocol ID reserved for the packets of the close handshake.
+const CLOSE_PROTOCOL_ID: u16 = u16::MAX;
+/// Payload of the packet that announces that a party will not send more packets.
+const FIN: u8 = 0;
+/// Payload of the packet that confirms that all the packets sent before a FIN were received.
+const FIN_ACK: u8 = 1;
+/// Payload of the packet that announces that a party aborted the protocol.
+const ABORT: u8 = 2;
+
+/// Time between the checks of the interrupt flag while a party waits for a packet.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of bytes from which the network writes to the peers in parallel, either a packet sent
+/// to every party or the packets buffered in the channels. Smaller writes fit in the buffers of
+/// the connections without blocking, so a thread per peer would cost more than it saves.
+const PARALLEL_WRITE_THRESHOLD: usize = 64 * 1024;
+
+/// Runs a function on the channel of every party in a thread of its own, and returns the results
+/// in the order of the parties. Each channel is used by a single thread, so the packets of each
+/// peer keep their order.
+fn on_every_channel<R, F>(channels: &mut [Box<dyn Channel>], function: F) -> Vec<R>
+where
+    R: Send,
+    F: Fn(usize, &mut dyn Channel) -> R + Sync,
+{
+    thread::scope(|scope| {
+        let handles: Vec<_> = channels
+            .iter_mut()
+            .enumerate()
+            .map(|(party_id, channel)| {
+                let function = &function;
+                scope.spawn(move || function(party_id, channel.as_mut()))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
 /// Network that contains all the channels connected to the party. Each channel is
 /// a connection to other parties.
 pub struct Network {
+    /// ID of the party that owns the network.
+    id: usize,
     /// Channnels for each peer.
     peer_channels: Vec<Box<dyn Channel>>,
+    /// Packets received from each peer that were not expected yet.
+    pending: Vec<VecDeque<Packet>>,
+    /// Party whose channel is polled first in the next call to [`Network::recv_any`].
+    next_poll: usize,
+    /// Traffic statistics of the network.
+    stats: NetworkStats,
+    /// Whether each peer already announced that it will not send more packets.
+    fin_received: Vec<bool>,
+    /// Sequence numbers of the packets delivered by the channel of each peer.
+    sequences: SequenceTracker,
+    /// Current session of the network.
+    session: u32,
+    /// Packets received from each peer that belong to a later session.
+    deferred: Vec<VecDeque<Packet>>,
+    /// Flag that interrupts the party, which aborts the protocol once it is set.
+    interrupt: Option<&'static AtomicBool>,
+    /// Progress of the party updated with the status and the traffic of each peer.
+    progress: Option<Arc<Progress>>,
+    /// Status of the connection with each peer, with the instant at which it changed.
+    statuses: Vec<(PeerStatus, Instant)>,
+    /// Phase of the protocol in progress, with the time and the statistics at its start.
+    phase: Option<(String, Instant, NetworkStats, Span)>,
+    /// Traffic of the phases of the protocol that already finished.
+    phases: Vec<PhaseStats>,
+    /// Limits on the traffic received from each peer.
+    guard: ReceiveGuard,
+    /// Log in which the security-relevant events are recorded, if there is one.
+    audit: Option<SharedAuditLog>,
+    /// Transcript in which the packets are recorded, if there is one.
+    transcript: Option<SharedTranscript>,
+    /// Schedule of the rotations of the keys of the channels.
+    rekey: RekeySchedule,
+    /// Codec with which the values are encoded.
+    codec: Codec,
+    /// Deadline of the rounds that gather a message from every party.
+    round_deadline: RoundDeadline,
+    /// Pattern in which the packets of the broadcasts travel among the parties.
+    topology: Topology,
+    /// Deadline of the execution, as the instant at which it expires and its duration.
+    deadline: Option<(Instant, Duration)>,
+    /// Rounds that proceeded without the parties that missed the deadline.
+    absences: Vec<Absence>,
+    /// Version and features agreed with each peer, if they were negotiated.
+    capabilities: Vec<Option<Capabilities>>,
 }
 
 impl Network {
-    fn configure_tls(
-        config: &NetworkConfig<'static>,
-    ) -> anyhow::Result<(ClientConfig, ServerConfig)> {
-        // Configure the client TLS
-        let client_conf = ClientConfig::builder()
-            .with_root_certificates(config.root_cert_store.clone())
-            .with_no_client_auth();
-
-        let server_conf = ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(config.server_cert.clone(), config.priv_key.clone_key())?;
-
-        Ok((client_conf, server_conf))
-    }
-
     /// Creates a new network using the ID of the current party and the number of parties connected
     /// to the network.
     pub fn create(id: usize, config: NetworkConfig<'static>) -> anyhow::Result<Self> {
+        let _span = trace::span("network setup");
         log::info!("creating network");
-        let n_parties = config.peer_ips.len();
-        let server_port = config.base_port + id as u16;
-        let server_address =
-            SocketAddr::new(std::net::IpAddr::V4(config.peer_ips[id]), server_port);
+        let n_parties = config.n_parties();
+
+        #[cfg(unix)]
+        if let TransportConfig::Unix(paths) = &config.transport {
+            // A stale socket file of a previous execution would prevent binding the listener.
+            if paths[id].exists() {
+                fs::remove_file(&paths[id])?;
+            }
+            let listener = UnixListener::bind(&paths[id])?;
+            log::info!("listening on {:?}", paths[id]);
+            let peers = Self::connect_all(
+                id,
+                n_parties,
+                |i| {
+                    let channel = UnixChannel::connect(id, &paths[i], &config.backoff)?;
+                    Ok(Box::new(channel))
+                },
+                || {
+                    let (channel, remote_id) = UnixChannel::accept(&listener)?;
+                    Ok((Box::new(channel), remote_id))
+                },
+            )?;
+            fs::remove_file(&paths[id])?;
+            return Self::establish(id, peers, &config);
+        }
+
+        #[cfg(feature = "udp")]
+        if let TransportConfig::Udp = &config.transport {
+            let server_address = config.peers[id].listen_address()?;
+            let mut listener = UdpListener::bind(server_address, id, config.timeout)?;
+            log::info!("listening on {:?} through UDP", server_address);
+            let peers = Self::connect_all(
+                id,
+                n_parties,
+                |i| {
+                    let channel = UdpChannel::connect(
+                        id,
+                        i,
+                        &config.peers[i],
+                        &config.backoff,
+                        config.timeout,
+                    )?;
+                    Ok(Box::new(channel))
+                },
+                || {
+                    let (channel, remote_id) = listener.accept()?;
+                    Ok((Box::new(channel), remote_id))
+                },
+            )?;
+            return Self::establish(id, peers, &config);
+        }
+
+        let server_address = config.peers[id].listen_address()?;
         let server_listener = TcpListener::bind(server_address)?;
         log::info!("listening on {:?}", server_address);
 
-        let (client_conf, server_conf) = Self::configure_tls(&config)?;
+        let connector = Connector::new(&config)?;
+        let mut peers = Self::connect_all(
+            id,
+            n_parties,
+            |i| connector.connect(id, i, &config.peers[i], &config.backoff),
+            || connector.accept(server_listener.accept()?.0),
+        )?;
+        if let Some(policy) = config.reconnect {
+            peers =
+                Self::enable_reconnection(id, peers, policy, connector, server_listener, &config);
+        }
+        Self::establish(id, peers, &config)
+    }
+
+    /// Creates the network over the channels connected with the peers, negotiates the wire
+    /// format with them and applies the options of the configuration.
+    fn establish(
+        id: usize,
+        peers: Vec<Box<dyn Channel>>,
+        config: &NetworkConfig,
+    ) -> anyhow::Result<Self> {
+        let mut network = Self::from_channels(id, peers);
+        network.negotiate(Features::required(config.codec, &config.rekey))?;
+        network.apply_options(config)?;
+        Ok(network)
+    }
+
+    /// Applies the options of the configuration to the channels once they are established.
+    fn apply_options(&mut self, config: &NetworkConfig) -> anyhow::Result<()> {
+        if let Some(max_packet_size) = config.max_packet_size {
+            self.set_max_packet_size(max_packet_size);
+        }
+        self.set_receive_limits(config.receive_limits);
+        self.set_rekey_policy(config.rekey);
+        self.set_codec(config.codec);
+        self.set_round_deadline(config.round_deadline);
+        self.set_topology(config.topology);
+        self.set_deadline(config.deadline);
+        if let Some(path) = &config.transcript {
+            self.record_transcript(path)?;
+        }
+        if let Some(path) = &config.audit_log {
+            self.record_audit_log(path)?;
+            for party_id in (0..self.n_parties()).filter(|&party_id| party_id != self.id) {
+                self.audit(AuditEvent::PeerConnected {
+                    party_id,
+                    transport: config.transport.name().to_string(),
+                    authenticated: config.transport.is_authenticated(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Wraps the channels with the peers so that they are re-established when their connection
+    /// drops. The party dials again the peers with a lower ID, and waits in the listener for the
+    /// peers with a higher ID to dial again.
+    fn enable_reconnection(
+        id: usize,
+        peers: Vec<Box<dyn Channel>>,
+        policy: RetryPolicy,
+        connector: Connector,
+        listener: TcpListener,
+        config: &NetworkConfig,
+    ) -> Vec<Box<dyn Channel>> {
+        let handshake_connector = connector.clone();
+        let acceptor = Arc::new(Mutex::new(Acceptor::new(
+            listener,
+            Box::new(move |stream| handshake_connector.accept(stream)),
+            config.timeout,
+        )));
+        peers
+            .into_iter()
+            .enumerate()
+            .map(|(i, channel)| {
+                let reconnect: Reconnect = match i.cmp(&id) {
+                    Ordering::Equal => return channel,
+                    Ordering::Less => {
+                        let connector = connector.clone();
+                        let peer = config.peers[i].clone();
+                        let backoff = config.backoff;
+                        Box::new(move || connector.connect(id, i, &peer, &backoff))
+                    }
+                    Ordering::Greater => {
+                        let acceptor = acceptor.clone();
+                        Box::new(move || {
+                            acceptor
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner)
+                                .accept_from(i)
+                        })
+                    }
+                };
+                Box::new(ReconnectingChannel::new(channel, reconnect, policy)) as Box<dyn Channel>
+            })
+            .collect()
+    }
 
+    /// Establishes the channels with all the peers. The party connects as a client with the peers
+    /// with a lower ID using `connect`, and accepts the connections of the peers with a higher ID
+    /// using `accept`, which returns the channel and the ID of the peer.
+    fn connect_all<C, A>(
+        id: usize,
+        n_parties: usize,
+        mut connect: C,
+        mut accept: A,
+    ) -> anyhow::Result<Vec<Box<dyn Channel>>>
+    where
+        C: FnMut(usize) -> anyhow::Result<Box<dyn Channel>>,
+        A: FnMut() -> anyhow::Result<(Box<dyn Channel>, usize)>,
+    {
         let mut peers: Vec<Box<dyn Channel>> = Vec::new();
         for i in 0..n_parties {
             if i != id {
@@ -187,29 +1322,34 @@ impl Network {
             }
         }
 
+        // Only the peers with a higher ID connect to this party, and each of them only once, so a
+        // peer can not take the place of another one in the mesh.
+        let mut accepted = vec![false; n_parties];
         for i in 0..n_parties {
             match i.cmp(&id) {
                 Ordering::Less => {
                     log::info!("connecting as a client with peer ID {i}");
-                    let remote_port = config.base_port + i as u16;
-                    let remote_address =
-                        SocketAddr::new(std::net::IpAddr::V4(config.peer_ips[i]), remote_port);
-                    let (client_conn, tcp_stream) = channel::connect_as_client(
-                        id,
-                        remote_address,
-                        config.timeout,
-                        config.sleep_time,
-                        &client_conf,
-                    )?;
-                    let stream = StreamOwned::new(client_conn, tcp_stream);
-                    peers[i] = Box::new(stream);
+                    peers[i] = connect(i)?;
                 }
                 Ordering::Greater => {
                     log::info!("acting as a server for peer ID {i}");
-                    let (server_conn, tcp_stream, remote_id) =
-                        channel::accept_connection(&server_listener, &server_conf)?;
-                    let stream = StreamOwned::new(server_conn, tcp_stream);
-                    peers[remote_id] = Box::new(stream);
+                    let (channel, remote_id) = accept()?;
+                    if remote_id >= n_parties {
+                        anyhow::bail!(Error::new(
+                            ErrorKind::InvalidData,
+                            format!("the peer claimed the invalid ID {remote_id}"),
+                        ));
+                    }
+                    if remote_id <= id || accepted[remote_id] {
+                        anyhow::bail!(Error::new(
+                            ErrorKind::InvalidData,
+                            format!(
+                                "the peer {remote_id} is not expected to connect to party {id}"
+                            ),
+                        ));
+                    }
+                    accepted[remote_id] = true;
+                    peers[remote_id] = channel;
                 }
                 Ordering::Equal => {
                     log::info!("adding the loop-back channel");
@@ -217,59 +1357,2665 @@ impl Network {
                 }
             }
         }
-        Ok(Self {
-            peer_channels: peers,
-        })
+        Ok(peers)
     }
 
-    /// Send a packet to every party in the network.
-    pub fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
-        let mut bytes_sent = 0;
-        for i in 0..self.peer_channels.len() {
-            bytes_sent = self
-                .peer_channels
-                .get_mut(i)
-                .expect("channel index not found")
-                .send(packet)?;
+    /// Creates `n_parties` networks connected with each other through in-memory channels, where
+    /// the `i`-th network belongs to the party with ID `i`. This allows to run a protocol with
+    /// all the parties in the same process, each one in its own thread, without sockets or TLS.
+    pub fn create_local(n_parties: usize) -> Vec<Self> {
+        let mut channels: Vec<Vec<Box<dyn Channel>>> = (0..n_parties)
+            .map(|_| Vec::with_capacity(n_parties))
+            .collect();
+        for i in 0..n_parties {
+            let (previous, next) = channels.split_at_mut(i + 1);
+            let own_channels = &mut previous[i];
+            own_channels.push(Box::new(LoopBackChannel::default()));
+            for peer_channels in next.iter_mut() {
+                let (channel_own, channel_peer) = LocalChannel::pair();
+                own_channels.push(Box::new(channel_own));
+                peer_channels.push(Box::new(channel_peer));
+            }
         }
-        Ok(bytes_sent)
+        channels
+            .into_iter()
+            .enumerate()
+            .map(|(id, channels)| Self::from_channels(id, channels))
+            .collect()
     }
 
-    /// Receive a packet from each party in the network.
-    pub fn recv(&mut self) -> anyhow::Result<Vec<Packet>> {
-        let mut packets = Vec::new();
-        for i in 0..self.peer_channels.len() {
-            let packet = self
-                .peer_channels
-                .get_mut(i)
-                .expect("channel index not found")
-                .recv()?;
-            packets.push(packet);
+    /// Creates the network of the party `id` from the channels to each peer. The channel in
+    /// position `id` must be a loopback channel.
+    fn from_channels(id: usize, peer_channels: Vec<Box<dyn Channel>>) -> Self {
+        let pending = peer_channels.iter().map(|_| VecDeque::new()).collect();
+        let stats = NetworkStats::new(peer_channels.len());
+        let fin_received = vec![false; peer_channels.len()];
+        let sequences = SequenceTracker::new(peer_channels.len());
+        let deferred = peer_channels.iter().map(|_| VecDeque::new()).collect();
+        let guard = ReceiveGuard::new(peer_channels.len());
+        let rekey = RekeySchedule::new(peer_channels.len());
+        let capabilities = vec![None; peer_channels.len()];
+        let statuses = (0..peer_channels.len())
+            .map(|party_id| {
+                let status = if party_id == id {
+                    PeerStatus::Local
+                } else {
+                    PeerStatus::Connected
+                };
+                (status, Instant::now())
+            })
+            .collect();
+        Self {
+            id,
+            peer_channels,
+            pending,
+            next_poll: 0,
+            stats,
+            fin_received,
+            sequences,
+            session: 0,
+            deferred,
+            interrupt: None,
+            progress: None,
+            statuses,
+            phase: None,
+            phases: Vec::new(),
+            guard,
+            audit: None,
+            transcript: None,
+            rekey,
+            codec: Codec::default(),
+            round_deadline: RoundDeadline::default(),
+            topology: Topology::default(),
+            deadline: None,
+            absences: Vec::new(),
+            capabilities,
         }
+    }
 
-        Ok(packets)
+    /// Splits the network into a sending and a receiving half, which can be used at the same time
+    /// from different threads. Each channel is serviced by a background thread, so the
+    /// connections with the peers are used in full duplex. The packets received before the split
+    /// that were not delivered yet are kept by the receiving half. The halves do not collect
+    /// traffic statistics, and they do not isolate the sessions: the sending half sends in the
+    /// current session, and the receiving half delivers the packets of every session.
+    pub fn split(mut self) -> (NetworkSender, NetworkReceiver) {
+        for (pending, deferred) in self.pending.iter_mut().zip(self.deferred) {
+            pending.extend(deferred);
+        }
+        split::split_network(self.peer_channels, self.pending, self.session)
     }
 
-    /// Closes the network by closing each channel.
-    pub fn close(&mut self) -> anyhow::Result<()> {
-        for i in 0..self.peer_channels.len() {
-            self.peer_channels
-                .get_mut(i)
-                .expect("channel index not found")
-                .shutdown()?;
+    /// Converts the network into a [`ThreadedNetwork`], whose sends and receives are futures that
+    /// can be awaited at the same time for every peer. Each channel is serviced by a background
+    /// thread, as in [`Network::split`], and the packets received before the conversion that were
+    /// not delivered yet are kept.
+    pub fn into_threaded(mut self) -> ThreadedNetwork {
+        for (pending, deferred) in self.pending.iter_mut().zip(self.deferred) {
+            pending.extend(deferred);
         }
-        Ok(())
+        threaded::threaded_network(self.id, self.peer_channels, self.pending, self.session)
     }
 
-    /// Sends a packet of information to a given party.
-    pub fn send_to(&mut self, packet: &Packet, party_id: usize) -> anyhow::Result<usize> {
-        let bytes_sent = self.peer_channels[party_id].send(packet)?;
-        Ok(bytes_sent)
+    /// Sets the maximum size in bytes of the payload of the packets received from every peer.
+    /// A peer that sends a larger packet makes the receive fail with
+    /// [`ChannelError::PacketExceedsLimit`].
+    pub fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        for channel in &mut self.peer_channels {
+            channel.set_max_packet_size(max_packet_size);
+        }
     }
 
-    /// Receives a packet from a given party.
-    pub fn recv_from(&mut self, party_id: usize) -> anyhow::Result<Packet> {
-        let packet = self.peer_channels[party_id].recv()?;
-        Ok(packet)
+    /// Sets the limits on the rate and the pending packets of every peer. A peer that exceeds them
+    /// is disconnected and the receive fails with a [`GuardError`].
+    pub fn set_receive_limits(&mut self, limits: ReceiveLimits) {
+        self.guard.set_limits(limits);
+    }
+
+    /// Sets the policy that decides when the keys of the channels are rotated. The keys of the
+    /// channel of a peer are rotated when the party starts a new round after the limits of the
+    /// policy are reached.
+    pub fn set_rekey_policy(&mut self, policy: RekeyPolicy) {
+        self.rekey.set_policy(policy);
+    }
+
+    /// Sets the codec with which the values are encoded. Every party must use the same codec, and
+    /// a value encoded with another one is rejected with [`value::ValueError::CodecMismatch`].
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
+    /// Returns the codec with which the values are encoded.
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// Sets the deadline of the rounds that gather a message from every party, such as the
+    /// openings of [`crate::mpc::run_open_protocol`].
+    pub fn set_round_deadline(&mut self, deadline: RoundDeadline) {
+        self.round_deadline = deadline;
+    }
+
+    /// Returns the deadline of the rounds that gather a message from every party.
+    pub fn round_deadline(&self) -> RoundDeadline {
+        self.round_deadline
+    }
+
+    /// Sets the time within which the execution must finish, counted from now, or removes the
+    /// deadline if it is `None`. While the party waits for a packet, it checks the deadline as
+    /// often as the interrupt flag of [`Network::set_interrupt`], and once it expires, it records
+    /// the event in the audit log, aborts as in [`Network::abort`] and the receive fails with
+    /// [`ChannelError::DeadlineExceeded`], so every party stops instead of some of them waiting
+    /// for packets that will never arrive.
+    pub fn set_deadline(&mut self, deadline: Option<Duration>) {
+        self.deadline = deadline.map(|deadline| (Instant::now() + deadline, deadline));
+    }
+
+    /// Returns the time within which the execution must finish, if there is a deadline.
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline.map(|(_, deadline)| deadline)
+    }
+
+    /// Starts a deadline for a part of the execution, such as a job of a daemon, which expires
+    /// no later than the current deadline. Returns the current deadline, which is restored with
+    /// [`Network::restore_deadline`] once the part finishes.
+    pub(crate) fn start_deadline(&mut self, deadline: Duration) -> Option<(Instant, Duration)> {
+        let outer = self.deadline;
+        let expires_at = Instant::now() + deadline;
+        self.deadline = match outer {
+            Some((outer_expires_at, outer_deadline)) if outer_expires_at < expires_at => {
+                Some((outer_expires_at, outer_deadline))
+            }
+            _ => Some((expires_at, deadline)),
+        };
+        outer
+    }
+
+    /// Restores the deadline returned by [`Network::start_deadline`].
+    pub(crate) fn restore_deadline(&mut self, deadline: Option<(Instant, Duration)>) {
+        self.deadline = deadline;
+    }
+
+    /// Sets the pattern in which the packets of [`Network::broadcast_and_gather`] and
+    /// [`Network::broadcast_from`] travel among the parties. Every party must use the same
+    /// topology.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    /// Returns the pattern in which the packets of the broadcasts travel among the parties.
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Records that a round proceeded without the parties that missed its deadline, also in the
+    /// audit log, if there is one.
+    pub(crate) fn record_absence(&mut self, round: u32, parties: Vec<usize>) {
+        log::warn!("round {round} proceeds without the parties {parties:?}");
+        self.audit(AuditEvent::PartiesAbsent {
+            round,
+            parties: parties.clone(),
+        });
+        self.absences.push(Absence { round, parties });
+    }
+
+    /// Returns the rounds that proceeded without some parties, which missed their deadline, in
+    /// the order in which they happened.
+    pub fn absences(&self) -> &[Absence] {
+        &self.absences
+    }
+
+    /// Negotiates the version of the wire format and the optional features with every peer. The
+    /// party sends its [`Hello`] to every peer before it waits for theirs, so the negotiation
+    /// must be the first exchange over the channels. A peer that is not compatible is rejected
+    /// with a [`VersionError`] at both ends of the connection before any value is exchanged.
+    pub fn negotiate(&mut self, required: Features) -> anyhow::Result<()> {
+        let hello = Hello::new(required);
+        let peers: Vec<usize> = (0..self.n_parties())
+            .filter(|&party_id| party_id != self.id)
+            .collect();
+        for &party_id in &peers {
+            let packet = Packet::tagged(HELLO_PROTOCOL_ID, 0, hello.encode()).sent_by(self.id);
+            self.peer_channels[party_id].send(&packet)?;
+        }
+        for &party_id in &peers {
+            self.peer_channels[party_id].flush()?;
+        }
+        for party_id in peers {
+            let packet = self.peer_channels[party_id].recv()?;
+            self.check_sequence(party_id, packet.header())?;
+            if packet.header().protocol_id != HELLO_PROTOCOL_ID {
+                anyhow::bail!(VersionError::MissingHello(party_id));
+            }
+            let peer = Hello::decode(party_id, packet.as_slice())?;
+            let capabilities = hello.negotiate(party_id, &peer).inspect_err(|err| {
+                log::error!("rejecting the connection: {err}");
+            })?;
+            log::info!(
+                "agreed on the version {} of the wire format with party {party_id} and the features {}",
+                capabilities.version,
+                capabilities.features,
+            );
+            self.capabilities[party_id] = Some(capabilities);
+        }
+        Ok(())
+    }
+
+    /// Returns the version and the features agreed with a given peer, or `None` if they were not
+    /// negotiated, as in the local networks.
+    pub fn peer_capabilities(&self, party_id: usize) -> Option<Capabilities> {
+        self.capabilities[party_id]
+    }
+
+    /// Creates a network that replays the traffic recorded in a transcript in place of the real
+    /// network, so that the execution of the party that recorded it can be reproduced in a single
+    /// process. The network has the ID of the party that recorded the transcript.
+    pub fn replay(path: &Path) -> anyhow::Result<Self> {
+        let (id, channels) = ReplayChannel::load_all(path)?;
+        log::info!("replaying the transcript {path:?} of party {id}");
+        let peer_channels = channels
+            .into_iter()
+            .map(|channel| Box::new(channel) as Box<dyn Channel>)
+            .collect();
+        Ok(Self::from_channels(id, peer_channels))
+    }
+
+    /// Records every packet sent and received from now on in a transcript file at the given
+    /// path, which can be read with a [`transcript::TranscriptReader`].
+    pub fn record_transcript(&mut self, path: &Path) -> anyhow::Result<()> {
+        let mut writer = TranscriptWriter::create(path, self.id)?;
+        writer.set_phase(self.phase.as_ref().map(|(name, ..)| name.as_str()));
+        let transcript = Arc::new(Mutex::new(writer));
+        log::info!("recording the traffic in the transcript {path:?}");
+        self.wrap_channels(|peer_id, channel| {
+            Box::new(RecordingChannel::new(channel, peer_id, transcript.clone()))
+        });
+        self.transcript = Some(transcript);
+        Ok(())
+    }
+
+    /// Records the security-relevant events of the party from now on in the audit log at the
+    /// given path, which can be checked with [`audit::verify`]. If the log exists, the new events
+    /// are appended to it.
+    pub fn record_audit_log(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.audit = Some(Arc::new(Mutex::new(AuditLog::open(path, self.id)?)));
+        log::info!("recording the security events in the audit log {path:?}");
+        Ok(())
+    }
+
+    /// Records a security-relevant event in the audit log, if there is one. A failure to write
+    /// the log is reported in the logs, but does not stop the protocol.
+    pub fn audit(&self, event: AuditEvent) {
+        if let Some(audit) = &self.audit {
+            let result = audit
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record(event);
+            if let Err(err) = result {
+                log::error!("could not write to the audit log: {err:#}");
+            }
+        }
+    }
+
+    /// Replaces each channel of the network by the result of applying `wrap` to the ID of the
+    /// peer and its current channel. This allows to decorate the channels, for example, to inject
+    /// faults in tests.
+    pub fn wrap_channels<F>(&mut self, mut wrap: F)
+    where
+        F: FnMut(usize, Box<dyn Channel>) -> Box<dyn Channel>,
+    {
+        let channels = std::mem::take(&mut self.peer_channels);
+        self.peer_channels = channels
+            .into_iter()
+            .enumerate()
+            .map(|(party_id, channel)| wrap(party_id, channel))
+            .collect();
+    }
+
+    /// Returns the traffic statistics collected since the network was created or since the last
+    /// call to [`Network::reset_stats`].
+    pub fn stats(&self) -> &NetworkStats {
+        &self.stats
+    }
+
+    /// Records the party, the session and the round of the network in the context of the log
+    /// records of the current thread.
+    fn update_log_context(&self) {
+        log_context::set(LogContext {
+            party: Some(self.id),
+            session: Some(self.session),
+            round: Some(self.stats.rounds),
+        });
+    }
+
+    /// Starts a phase of the protocol, finishing the previous one. The traffic of each phase is
+    /// measured separately and returned by [`Network::phases`], the phase is shown in the
+    /// progress, if there is one, the packets recorded in the transcript are tagged with it, and
+    /// it is traced as a span of the current thread.
+    pub fn begin_phase(&mut self, name: &str) {
+        self.end_phase();
+        if let Some(progress) = &self.progress {
+            progress.set_phase(name);
+        }
+        self.tag_transcript(Some(name));
+        self.phase = Some((
+            name.to_string(),
+            Instant::now(),
+            self.stats.clone(),
+            trace::span(name),
+        ));
+    }
+
+    /// Tags the packets recorded in the transcript from now on with the given phase.
+    fn tag_transcript(&self, phase: Option<&str>) {
+        if let Some(transcript) = &self.transcript {
+            transcript
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .set_phase(phase);
+        }
+    }
+
+    /// Finishes the phase of the protocol in progress, if any.
+    pub fn end_phase(&mut self) {
+        self.tag_transcript(None);
+        if let Some((name, start, stats, _span)) = self.phase.take() {
+            self.phases.push(PhaseStats::between(
+                &name,
+                start.elapsed(),
+                &stats,
+                &self.stats,
+            ));
+        }
+    }
+
+    /// Returns the traffic of the phases of the protocol that already finished, in the order in
+    /// which they were executed.
+    pub fn phases(&self) -> &[PhaseStats] {
+        &self.phases
+    }
+
+    /// Resets the traffic statistics of the network.
+    pub fn reset_stats(&mut self) {
+        self.stats = NetworkStats::new(self.peer_channels.len());
+    }
+
+    /// Sends a packet through the channel of a given party in the current session, recording
+    /// the traffic.
+    fn channel_send(&mut self, packet: &Packet, party_id: usize) -> anyhow::Result<usize> {
+        if self.stats.starts_round() {
+            self.rotate_due_keys()?;
+        }
+        let packet = packet.clone().in_session(self.session).sent_by(self.id);
+        let start = Instant::now();
+        let bytes_sent = self.peer_channels[party_id]
+            .send(&packet)
+            .inspect_err(|_| self.report_status(party_id, PeerStatus::Failed))?;
+        self.record_send(party_id, bytes_sent, start.elapsed());
+        self.update_log_context();
+        Ok(bytes_sent)
+    }
+
+    /// Records the traffic of a packet sent to a given party.
+    fn record_send(&mut self, party_id: usize, bytes: usize, elapsed: Duration) {
+        self.stats.record_send(party_id, bytes, elapsed);
+        self.rekey.record_send(party_id, bytes);
+        if let Some(progress) = &self.progress {
+            progress.record_send(party_id, bytes);
+        }
+    }
+
+    /// Rotates the keys of the channels of the peers whose rotation is due. It is called at the
+    /// start of a round, when the packets of the previous round were already received, so the
+    /// channels are idle.
+    fn rotate_due_keys(&mut self) -> anyhow::Result<()> {
+        let now = Instant::now();
+        for party_id in 0..self.peer_channels.len() {
+            if party_id == self.id || !self.rekey.is_due(party_id, now) {
+                continue;
+            }
+            if self.peer_channels[party_id].rekey()? {
+                log::info!("rotated the keys of the channel of party {party_id}");
+                self.audit(AuditEvent::KeysRotated { party_id });
+            }
+            self.rekey.rotated(party_id, now);
+        }
+        Ok(())
+    }
+
+    /// Receives a packet of the current session from the channel of a given party, recording the
+    /// traffic.
+    fn channel_recv(&mut self, party_id: usize) -> anyhow::Result<Packet> {
+        self.flush()?;
+        loop {
+            self.report_status(party_id, PeerStatus::Waiting);
+            let start = Instant::now();
+            let packet = self
+                .recv_interruptible(party_id)
+                .inspect_err(|_| self.report_status(party_id, PeerStatus::Failed))?;
+            self.record_recv(party_id, Some(packet.size()), start.elapsed());
+            let packet = self.check_received(party_id, packet)?;
+            if let Some(packet) = self.filter_session(party_id, packet) {
+                return Ok(packet);
+            }
+        }
+    }
+
+    /// Receives a packet of the current session from the channel of a given party into the
+    /// given buffer, recording the traffic.
+    fn channel_recv_into(
+        &mut self,
+        party_id: usize,
+        buffer: &mut Vec<u8>,
+    ) -> anyhow::Result<PacketHeader> {
+        self.flush()?;
+        loop {
+            self.report_status(party_id, PeerStatus::Waiting);
+            let start = Instant::now();
+            let header = if self.polls_interrupt(party_id) {
+                // Only the packets can be received with a timeout, so they are copied.
+                self.recv_interruptible(party_id).map(|packet| {
+                    buffer.clear();
+                    buffer.extend_from_slice(packet.as_slice());
+                    *packet.header()
+                })
+            } else {
+                self.peer_channels[party_id].recv_into(buffer)
+            }
+            .inspect_err(|_| self.report_status(party_id, PeerStatus::Failed))?;
+            self.record_recv(party_id, Some(buffer.len()), start.elapsed());
+            self.check_received_header(party_id, &header, buffer)?;
+            if header.session == self.session {
+                return Ok(header);
+            }
+            // Packets of other sessions are rare, so only they pay for copying the buffer.
+            let packet = Packet::with_header(header, buffer.clone());
+            if self.filter_session(party_id, packet).is_some() {
+                return Ok(header);
+            }
+        }
+    }
+
+    /// Receives a packet of the current session from the channel of a given party waiting at
+    /// most the given timeout, recording the traffic.
+    fn channel_recv_timeout(
+        &mut self,
+        party_id: usize,
+        timeout: Duration,
+    ) -> anyhow::Result<Packet> {
+        self.flush()?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                anyhow::bail!(ChannelError::RecvTimeout(timeout));
+            }
+            self.check_interrupt()?;
+            let start = Instant::now();
+            let result = if self.polls_interrupt(party_id) {
+                match self.peer_channels[party_id]
+                    .recv_timeout(remaining.min(INTERRUPT_POLL_INTERVAL))
+                {
+                    Err(err) if channel::is_recv_timeout(&err) => continue,
+                    result => result,
+                }
+            } else {
+                self.peer_channels[party_id].recv_timeout(remaining)
+            };
+            let bytes = result.as_ref().ok().map(Packet::size);
+            self.record_recv(party_id, bytes, start.elapsed());
+            let packet = self.check_received(party_id, result?)?;
+            if let Some(packet) = self.filter_session(party_id, packet) {
+                return Ok(packet);
+            }
+        }
+    }
+
+    /// Receives a packet of the current session from the channel of a given party if there is
+    /// one ready, recording the traffic.
+    fn channel_try_recv(&mut self, party_id: usize) -> anyhow::Result<Option<Packet>> {
+        self.flush()?;
+        loop {
+            let start = Instant::now();
+            let packet = self.peer_channels[party_id].try_recv()?;
+            let bytes = packet.as_ref().map(Packet::size);
+            self.record_recv(party_id, bytes, start.elapsed());
+            let Some(packet) = packet else {
+                return Ok(None);
+            };
+            let packet = self.check_received(party_id, packet)?;
+            if let Some(packet) = self.filter_session(party_id, packet) {
+                return Ok(Some(packet));
+            }
+        }
+    }
+
+    /// Records the time spent waiting for a packet from a given party and the size of the
+    /// packet, if one was received, in the statistics and the progress.
+    fn record_recv(&mut self, party_id: usize, bytes: Option<usize>, elapsed: Duration) {
+        self.stats.record_recv(party_id, bytes, elapsed);
+        if bytes.is_some() {
+            self.report_status(party_id, PeerStatus::Connected);
+        }
+        if let (Some(progress), Some(bytes)) = (&self.progress, bytes) {
+            progress.record_recv(party_id, bytes);
+        }
+    }
+
+    /// Updates the status of the connection with a given party, also in the progress if there is
+    /// one. The status of the party itself is always [`PeerStatus::Local`].
+    fn report_status(&mut self, party_id: usize, status: PeerStatus) {
+        let (current, since) = &mut self.statuses[party_id];
+        if *current != PeerStatus::Local && *current != status {
+            *current = status;
+            *since = Instant::now();
+        }
+        if let Some(progress) = &self.progress {
+            progress.set_status(party_id, status);
+        }
+    }
+
+    /// Returns the status of the connection with a given party together with the time elapsed
+    /// since it changed, which tells a slow peer from one that is gone: a peer that the party has
+    /// been [`PeerStatus::Waiting`] for during a long time is slow, while a peer whose
+    /// communication [`PeerStatus::Failed`], even after the reconnection attempts of the
+    /// `reconnect` policy, or whose connection is [`PeerStatus::Closed`] is gone.
+    pub fn peer_status(&self, party_id: usize) -> (PeerStatus, Duration) {
+        let (status, since) = self.statuses[party_id];
+        (status, since.elapsed())
+    }
+
+    /// Keeps apart a packet received from a given party if it belongs to another session. The
+    /// packets of a later session are deferred until that session starts, and the packets of an
+    /// earlier session are discarded. Returns the packet if it belongs to the current session.
+    /// The packets of the close handshake belong to every session.
+    fn filter_session(&mut self, party_id: usize, packet: Packet) -> Option<Packet> {
+        let header = *packet.header();
+        if header.protocol_id == CLOSE_PROTOCOL_ID {
+            return Some(packet);
+        }
+        match header.session.cmp(&self.session) {
+            Ordering::Equal => Some(packet),
+            Ordering::Greater => {
+                log::debug!(
+                    "deferring packet from party {party_id} for the session {}",
+                    header.session
+                );
+                self.deferred[party_id].push_back(packet);
+                None
+            }
+            Ordering::Less => {
+                log::debug!(
+                    "discarding packet from party {party_id} of the finished session {}",
+                    header.session
+                );
+                None
+            }
+        }
+    }
+
+    /// Returns the current session of the network.
+    pub fn session(&self) -> u32 {
+        self.session
+    }
+
+    /// Starts a new session of the network, so the connections can be reused for another
+    /// execution of a protocol without establishing them again. Every packet is tagged with the
+    /// session in which it is sent, and only the packets of the current session are delivered:
+    /// the packets of a later session are kept until it starts, and the packets of an earlier
+    /// session are discarded, including the ones that were received but not delivered yet. The
+    /// sessions must be started in increasing order, and every party must go through the same
+    /// sessions.
+    pub fn start_session(&mut self, session: u32) -> anyhow::Result<()> {
+        if session <= self.session {
+            anyhow::bail!(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "the session {session} does not follow the current session {}",
+                    self.session
+                ),
+            ));
+        }
+        self.session = session;
+        self.update_log_context();
+        log::info!("starting the session {session}");
+        for party_id in 0..self.peer_channels.len() {
+            let discarded = self.pending[party_id].len();
+            if discarded > 0 {
+                log::warn!(
+                    "discarding {discarded} packets from party {party_id} that were not received in the previous session"
+                );
+            }
+            self.pending[party_id].clear();
+            for packet in std::mem::take(&mut self.deferred[party_id]) {
+                if let Some(packet) = self.filter_session(party_id, packet) {
+                    self.pending[party_id].push_back(packet);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a packet to every party in the network. A packet of at least
+    /// [`PARALLEL_WRITE_THRESHOLD`] bytes is written to the peers in parallel, so a peer that
+    /// reads slowly does not delay the others.
+    pub fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        if packet.size() < PARALLEL_WRITE_THRESHOLD || self.peer_channels.len() <= 2 {
+            let mut bytes_sent = 0;
+            for i in 0..self.peer_channels.len() {
+                bytes_sent = self.channel_send(packet, i)?;
+            }
+            return Ok(bytes_sent);
+        }
+        if self.stats.starts_round() {
+            self.rotate_due_keys()?;
+        }
+        let packet = packet.clone().in_session(self.session).sent_by(self.id);
+        let results = on_every_channel(&mut self.peer_channels, |_, channel| {
+            let start = Instant::now();
+            let sent = channel
+                .send(&packet)
+                .and_then(|bytes| channel.flush().map(|()| bytes));
+            (sent, start.elapsed())
+        });
+        let mut bytes_sent = Ok(0);
+        for (party_id, (sent, elapsed)) in results.into_iter().enumerate() {
+            match sent {
+                Ok(bytes) => {
+                    self.record_send(party_id, bytes, elapsed);
+                    bytes_sent = bytes_sent.map(|_| bytes);
+                }
+                Err(err) => {
+                    self.report_status(party_id, PeerStatus::Failed);
+                    if bytes_sent.is_ok() {
+                        bytes_sent = Err(err);
+                    }
+                }
+            }
+        }
+        self.update_log_context();
+        bytes_sent
+    }
+
+    /// Receive a packet from each party in the network. The channels of the peers are read in
+    /// parallel, so a large packet of a peer does not wait in its connection until the packets of
+    /// the peers before it were received. If the reception from some peer fails, the packets of
+    /// the others are kept to be received later.
+    pub fn recv(&mut self) -> anyhow::Result<Vec<Packet>> {
+        if self.peer_channels.len() <= 2 {
+            let mut packets = Vec::new();
+            for i in 0..self.peer_channels.len() {
+                let packet = self.channel_recv(i)?;
+                packets.push(packet);
+            }
+            return Ok(packets);
+        }
+        self.flush()?;
+        for party_id in 0..self.peer_channels.len() {
+            self.report_status(party_id, PeerStatus::Waiting);
+        }
+        let (id, interrupt, deadline) = (self.id, self.interrupt, self.deadline);
+        let polls = interrupt.is_some() || deadline.is_some();
+        let results = on_every_channel(&mut self.peer_channels, |party_id, channel| {
+            let start = Instant::now();
+            let received = if polls && party_id != id {
+                // An interrupted reception returns no packet, and the network reports why.
+                loop {
+                    let interrupted = interrupt
+                        .is_some_and(|flag| flag.load(AtomicOrdering::SeqCst))
+                        || deadline.is_some_and(|(expires_at, _)| Instant::now() >= expires_at);
+                    if interrupted {
+                        break Ok(None);
+                    }
+                    match channel.recv_timeout(INTERRUPT_POLL_INTERVAL) {
+                        Err(err) if channel::is_recv_timeout(&err) => continue,
+                        result => break result.map(Some),
+                    }
+                }
+            } else {
+                channel.recv().map(Some)
+            };
+            (received, start.elapsed())
+        });
+        let mut packets = Vec::with_capacity(results.len());
+        let mut failure = None;
+        for (party_id, (received, elapsed)) in results.into_iter().enumerate() {
+            let packet = match received {
+                Ok(Some(packet)) => {
+                    self.record_recv(party_id, Some(packet.size()), elapsed);
+                    self.check_received(party_id, packet)
+                        .map(|packet| self.filter_session(party_id, packet))
+                }
+                Ok(None) => self.check_interrupt().map(|()| None),
+                Err(err) => {
+                    self.report_status(party_id, PeerStatus::Failed);
+                    Err(err)
+                }
+            };
+            match packet {
+                Ok(Some(packet)) => packets.push(packet),
+                // The packet belonged to another session, so the next one is awaited.
+                Ok(None) if failure.is_none() => match self.channel_recv(party_id) {
+                    Ok(packet) => packets.push(packet),
+                    Err(err) => failure = Some(err),
+                },
+                Ok(None) => {}
+                Err(err) => {
+                    failure.get_or_insert(err);
+                }
+            }
+        }
+        if let Some(err) = failure {
+            for packet in packets {
+                let party_id = usize::from(packet.header().sender);
+                self.pending[party_id].push_back(packet);
+            }
+            return Err(err);
+        }
+        Ok(packets)
+    }
+
+    /// Receives a packet from each party in the network, waiting at most the given deadline for
+    /// all of them, so a party that crashed does not hang the others. The peers are polled at the
+    /// same time, so the deadline bounds the whole reception instead of each peer. If some packet
+    /// does not arrive in time, the function returns [`ChannelError::RecvTimeout`], and the
+    /// packets that arrived are kept to be received later.
+    pub fn recv_all_deadline(&mut self, deadline: Duration) -> anyhow::Result<Vec<Packet>> {
+        let ends_at = Instant::now().checked_add(deadline);
+        let mut gathered: Vec<Option<Packet>> = vec![None; self.peer_channels.len()];
+        loop {
+            self.gather_ready(&mut gathered)?;
+            if gathered.iter().all(Option::is_some) {
+                return Ok(gathered.into_iter().flatten().collect());
+            }
+            if ends_at.is_some_and(|ends_at| Instant::now() >= ends_at) {
+                let missing: Vec<usize> = (0..gathered.len())
+                    .filter(|&party_id| gathered[party_id].is_none())
+                    .collect();
+                log::warn!("the parties {missing:?} did not send a packet within {deadline:?}");
+                for (party_id, packet) in gathered.into_iter().enumerate() {
+                    if let Some(packet) = packet {
+                        self.pending[party_id].push_front(packet);
+                    }
+                }
+                anyhow::bail!(ChannelError::RecvTimeout(deadline));
+            }
+            self.check_interrupt()?;
+            std::thread::sleep(RECV_ANY_POLL_INTERVAL);
+        }
+    }
+
+    /// Closes the network. Before closing the channels, the party sends a FIN packet to each peer
+    /// and acknowledges the FIN of each peer, so it does not exit until the peers received all
+    /// its packets. The packets that arrive before the FIN of a peer are kept, so they can still
+    /// be received. Peers that already closed their connection are skipped.
+    pub fn close(&mut self) -> anyhow::Result<()> {
+        let n_parties = self.peer_channels.len();
+        let mut open = vec![true; n_parties];
+        for (party_id, is_open) in open.iter_mut().enumerate() {
+            *is_open = self.send_close_packet(party_id, FIN)?;
+        }
+        for (party_id, is_open) in open.iter_mut().enumerate() {
+            if *is_open && !self.fin_received[party_id] {
+                *is_open = self.wait_close_packet(party_id, FIN)?;
+            }
+            if *is_open {
+                *is_open = self.send_close_packet(party_id, FIN_ACK)?;
+            }
+        }
+        for (party_id, is_open) in open.iter().enumerate() {
+            if *is_open {
+                self.wait_close_packet(party_id, FIN_ACK)?;
+            }
+        }
+
+        for i in 0..self.peer_channels.len() {
+            self.peer_channels
+                .get_mut(i)
+                .expect("channel index not found")
+                .shutdown()?;
+            self.report_status(i, PeerStatus::Closed);
+        }
+        Ok(())
+    }
+
+    /// Sets the progress that the network updates with the status of the connection and the
+    /// traffic of each peer, e.g. to show it in a dashboard.
+    pub fn set_progress(&mut self, progress: Arc<Progress>) {
+        self.progress = Some(progress);
+    }
+
+    /// Returns the progress that the network updates, if any.
+    pub fn progress(&self) -> Option<&Arc<Progress>> {
+        self.progress.as_ref()
+    }
+
+    /// Sets the flag that interrupts the party, e.g. from a signal handler. While the party waits
+    /// for a packet, it checks the flag every [`INTERRUPT_POLL_INTERVAL`], and once the flag is
+    /// set, it aborts as in [`Network::abort`] and the receive fails with
+    /// [`ChannelError::Interrupted`].
+    pub fn set_interrupt(&mut self, interrupt: &'static AtomicBool) {
+        self.interrupt = Some(interrupt);
+    }
+
+    /// Returns whether the flag of [`Network::set_interrupt`] is set, in which case the party
+    /// must not retry the protocol.
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupt
+            .is_some_and(|interrupt| interrupt.load(AtomicOrdering::SeqCst))
+    }
+
+    /// Aborts the protocol, announcing it to every peer before closing the channels, so the
+    /// peers fail right away with [`ChannelError::PeerAborted`] instead of waiting for packets
+    /// that will never arrive. The errors of the peers that already closed their connection are
+    /// ignored.
+    pub fn abort(&mut self) {
+        log::warn!("aborting the protocol");
+        self.audit(AuditEvent::Aborted);
+        for party_id in 0..self.peer_channels.len() {
+            if party_id != self.id {
+                let _ = self.send_close_packet(party_id, ABORT);
+            }
+        }
+        let _ = self.flush_open();
+        for channel in &mut self.peer_channels {
+            let _ = channel.shutdown();
+        }
+    }
+
+    /// Aborts the protocol if the party was interrupted or the execution exceeded its deadline.
+    fn check_interrupt(&mut self) -> anyhow::Result<()> {
+        if self
+            .interrupt
+            .is_some_and(|interrupt| interrupt.load(AtomicOrdering::SeqCst))
+        {
+            self.abort();
+            anyhow::bail!(ChannelError::Interrupted);
+        }
+        if let Some((expires_at, deadline)) = self.deadline {
+            if Instant::now() >= expires_at {
+                log::error!("the execution exceeded its deadline of {deadline:?}");
+                self.audit(AuditEvent::DeadlineExceeded {
+                    deadline_ms: u64::try_from(deadline.as_millis()).unwrap_or(u64::MAX),
+                });
+                self.deadline = None;
+                self.abort();
+                anyhow::bail!(ChannelError::DeadlineExceeded(deadline));
+            }
+        }
+        Ok(())
+    }
+
+    /// Tells whether the channel of a given party is polled to check the interrupt flag and the
+    /// deadline. The loop-back channel never waits, so it is not polled.
+    fn polls_interrupt(&self, party_id: usize) -> bool {
+        (self.interrupt.is_some() || self.deadline.is_some()) && party_id != self.id
+    }
+
+    /// Receives a packet from the channel of a given party. If the party can be interrupted, the
+    /// channel is polled with a timeout to check the interrupt flag between the attempts.
+    fn recv_interruptible(&mut self, party_id: usize) -> anyhow::Result<Packet> {
+        if !self.polls_interrupt(party_id) {
+            return self.peer_channels[party_id].recv();
+        }
+        loop {
+            self.check_interrupt()?;
+            match self.peer_channels[party_id].recv_timeout(INTERRUPT_POLL_INTERVAL) {
+                Err(err) if channel::is_recv_timeout(&err) => continue,
+                result => return result,
+            }
+        }
+    }
+
+    /// Flushes the channels during the close handshake, skipping the peers that already closed
+    /// their connection.
+    fn flush_open(&mut self) -> anyhow::Result<()> {
+        for channel in &mut self.peer_channels {
+            match channel.flush() {
+                Err(err) if !channel::is_connection_closed(&err) => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a packet of the close handshake to a given party. Returns `false` if the party
+    /// already closed the connection.
+    fn send_close_packet(&mut self, party_id: usize, kind: u8) -> anyhow::Result<bool> {
+        let packet = Packet::tagged(CLOSE_PROTOCOL_ID, 0, vec![kind]).sent_by(self.id);
+        match self.peer_channels[party_id].send(&packet) {
+            Ok(_) => Ok(true),
+            Err(err) if channel::is_connection_closed(&err) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Receives from a given party until the packet of the close handshake of the given kind
+    /// arrives. The packets of other protocols are kept as pending. Returns `false` if the party
+    /// closed the connection before sending the packet.
+    fn wait_close_packet(&mut self, party_id: usize, kind: u8) -> anyhow::Result<bool> {
+        self.flush_open()?;
+        loop {
+            let packet = match self.peer_channels[party_id].recv() {
+                Ok(packet) => packet,
+                Err(err) if channel::is_connection_closed(&err) => return Ok(false),
+                Err(err) => return Err(err),
+            };
+            self.guard_received(party_id)?;
+            self.check_sequence(party_id, packet.header())?;
+            if packet.header().protocol_id != CLOSE_PROTOCOL_ID {
+                if let Some(packet) = self.filter_session(party_id, packet) {
+                    self.pending[party_id].push_back(packet);
+                }
+            } else if packet.as_slice() == [kind] {
+                return Ok(true);
+            } else if packet.as_slice() == [ABORT] {
+                return Ok(false);
+            } else {
+                anyhow::bail!(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unexpected packet of the close handshake from party {party_id}"),
+                ));
+            }
+        }
+    }
+
+    /// Checks that a packet received from a given peer does not exceed the receive limits. A peer
+    /// that exceeds them is reported as a security event and disconnected: its pending packets
+    /// are discarded and its channel is closed, so it can not make the party spend more memory
+    /// or CPU.
+    fn guard_received(&mut self, party_id: usize) -> Result<(), GuardError> {
+        if party_id == self.id {
+            return Ok(());
+        }
+        let pending_packets = self.pending[party_id].len() + self.deferred[party_id].len();
+        let pending_bytes = self.pending[party_id]
+            .iter()
+            .chain(&self.deferred[party_id])
+            .map(Packet::size)
+            .sum();
+        let result = self
+            .guard
+            .check(party_id, Instant::now(), pending_packets, pending_bytes);
+        if let Err(err) = &result {
+            if !self.guard.is_disconnected(party_id) {
+                log::error!("security event: {err}, so its channel is closed");
+                self.audit(AuditEvent::CheaterIdentified {
+                    party_id,
+                    reason: err.to_string(),
+                });
+                self.guard.disconnect(party_id);
+                self.pending[party_id].clear();
+                self.deferred[party_id].clear();
+                let _ = self.peer_channels[party_id].shutdown();
+                self.report_status(party_id, PeerStatus::Failed);
+            }
+        }
+        result
+    }
+
+    /// Checks the sequence number of a packet received from a given party, recording a failed
+    /// check in the audit log.
+    fn check_sequence(
+        &mut self,
+        party_id: usize,
+        header: &PacketHeader,
+    ) -> Result<(), SequenceError> {
+        let result = self.sequences.check(party_id, header);
+        if let Err(err) = &result {
+            self.audit(AuditEvent::CheckFailed {
+                party_id: Some(party_id),
+                reason: err.to_string(),
+            });
+        }
+        result
+    }
+
+    /// Checks a packet received from a given party. Its sequence number must be higher than the
+    /// one of the previous packet of the party, so packets delivered twice are rejected with a
+    /// [`sequence::SequenceError`], and its sender must be the party itself, so a packet that
+    /// claims another sender is rejected with [`ChannelError::UnexpectedSender`]. If the packet is the FIN of the close handshake, the party
+    /// will not send more packets, so the channel is reported as closed, and if it announces
+    /// that the party aborted, [`ChannelError::PeerAborted`] is returned.
+    fn check_received(&mut self, party_id: usize, packet: Packet) -> anyhow::Result<Packet> {
+        self.check_received_header(party_id, packet.header(), packet.as_slice())?;
+        Ok(packet)
+    }
+
+    /// Works as [`Network::check_received`] for a packet given by its header and its payload.
+    fn check_received_header(
+        &mut self,
+        party_id: usize,
+        header: &PacketHeader,
+        payload: &[u8],
+    ) -> anyhow::Result<()> {
+        self.guard_received(party_id)?;
+        self.check_sequence(party_id, header)?;
+        if usize::from(header.sender) != party_id {
+            let err = ChannelError::UnexpectedSender {
+                party_id,
+                sender: header.sender.into(),
+            };
+            self.audit(AuditEvent::CheckFailed {
+                party_id: Some(party_id),
+                reason: err.to_string(),
+            });
+            anyhow::bail!(err);
+        }
+        if header.protocol_id == CLOSE_PROTOCOL_ID && payload == [ABORT] {
+            self.audit(AuditEvent::PeerAborted { party_id });
+            anyhow::bail!(ChannelError::PeerAborted(party_id));
+        }
+        if header.protocol_id == CLOSE_PROTOCOL_ID {
+            self.fin_received[party_id] = true;
+            anyhow::bail!(ChannelError::ConnectionClosed);
+        }
+        Ok(())
+    }
+
+    /// Sends the packet `packets[i]` to the party `i`. The packet is sent to every party even if
+    /// sending to some of them fails, in which case the errors are returned together as
+    /// [`PeerErrors`]. Returns the total number of bytes sent.
+    pub fn scatter(&mut self, packets: Vec<Packet>) -> anyhow::Result<usize> {
+        if packets.len() != self.peer_channels.len() {
+            anyhow::bail!(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} packets were given to scatter among {} parties",
+                    packets.len(),
+                    self.peer_channels.len()
+                ),
+            ));
+        }
+        let mut bytes_sent = 0;
+        let mut errors = Vec::new();
+        for (party_id, packet) in packets.iter().enumerate() {
+            match self.channel_send(packet, party_id) {
+                Ok(bytes) => bytes_sent += bytes,
+                Err(err) => errors.push((party_id, err)),
+            }
+        }
+        if !errors.is_empty() {
+            anyhow::bail!(PeerErrors(errors));
+        }
+        Ok(bytes_sent)
+    }
+
+    /// Sends the packet to every party and receives one packet from every party. The packet in
+    /// position `i` of the result was sent by the party `i`. The packets travel along the
+    /// [`Topology`] of the network, and in the mesh, the party receives its own packet through
+    /// its loopback channel.
+    pub fn broadcast_and_gather(&mut self, packet: &Packet) -> anyhow::Result<Vec<Packet>> {
+        match self.topology {
+            Topology::Mesh => self.mesh_broadcast_and_gather(packet),
+            Topology::Ring => self.ring_broadcast_and_gather(packet),
+            Topology::Tree => self.tree_broadcast_and_gather(packet),
+        }
+    }
+
+    /// Broadcasts and gathers the packets in the mesh. After each send, the packets that already
+    /// arrived are received, so the peers are not left blocked writing to this party while it is
+    /// sending.
+    fn mesh_broadcast_and_gather(&mut self, packet: &Packet) -> anyhow::Result<Vec<Packet>> {
+        let n_parties = self.peer_channels.len();
+        let mut gathered: Vec<Option<Packet>> = vec![None; n_parties];
+        for party_id in 0..n_parties {
+            self.channel_send(packet, party_id)?;
+            self.gather_ready(&mut gathered)?;
+        }
+        for (party_id, slot) in gathered.iter_mut().enumerate() {
+            if slot.is_none() {
+                *slot = Some(self.recv_from(party_id)?);
+            }
+        }
+        // All the slots were filled by the previous loop.
+        Ok(gathered.into_iter().flatten().collect())
+    }
+
+    /// Broadcasts and gathers the packets in the ring in `n - 1` steps. In every step, the party
+    /// forwards to the next party the packet that it received in the previous step, starting with
+    /// its own, and receives from the previous party the packet of the party before it. The party
+    /// 0 receives before it sends, so the ring is not blocked if every party waits to write.
+    fn ring_broadcast_and_gather(&mut self, packet: &Packet) -> anyhow::Result<Vec<Packet>> {
+        let n_parties = self.peer_channels.len();
+        let next = topology::ring_next(self.id, n_parties);
+        let previous = topology::ring_previous(self.id, n_parties);
+        let mut gathered: Vec<Option<Packet>> = vec![None; n_parties];
+        gathered[self.id] = Some(packet.clone());
+        let mut forwarded = packet.clone();
+        for step in 1..n_parties {
+            let received = if self.id == 0 {
+                let received = self.recv_from(previous)?;
+                self.channel_send(&forwarded, next)?;
+                received
+            } else {
+                self.channel_send(&forwarded, next)?;
+                self.recv_from(previous)?
+            };
+            gathered[(self.id + n_parties - step) % n_parties] = Some(received.clone());
+            forwarded = received;
+        }
+        // The step `s` fills the slot of the party `s` positions before this one.
+        Ok(gathered.into_iter().flatten().collect())
+    }
+
+    /// Broadcasts and gathers the packets in the binary tree rooted at the party 0. Every party
+    /// sends to its parent the packets of its subtree together with its own, and the root sends
+    /// the packets of every party down the tree.
+    fn tree_broadcast_and_gather(&mut self, packet: &Packet) -> anyhow::Result<Vec<Packet>> {
+        let n_parties = self.peer_channels.len();
+        let header = *packet.header();
+        let parent = topology::tree_parent(self.id, 0, n_parties);
+        let children = topology::tree_children(self.id, 0, n_parties);
+        let mut bundle: Vec<(usize, Vec<u8>)> = vec![(self.id, packet.as_slice().to_vec())];
+        for child in &children {
+            bundle.extend(Self::decode_bundle(&self.recv_from(*child)?)?);
+        }
+        if let Some(parent) = parent {
+            let up = Packet::with_header(header, bincode::serialize(&bundle)?);
+            self.channel_send(&up, parent)?;
+            bundle = Self::decode_bundle(&self.recv_from(parent)?)?;
+        }
+        let down = Packet::with_header(header, bincode::serialize(&bundle)?);
+        for child in children {
+            self.channel_send(&down, child)?;
+        }
+
+        let mut gathered: Vec<Option<Packet>> = vec![None; n_parties];
+        for (origin, payload) in bundle {
+            match gathered.get_mut(origin) {
+                Some(slot @ None) => *slot = Some(Packet::with_header(header, payload)),
+                _ => anyhow::bail!(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("the broadcast of the tree has a wrong packet of party {origin}"),
+                )),
+            }
+        }
+        if let Some(origin) = missing_parties(&gathered).first() {
+            anyhow::bail!(Error::new(
+                ErrorKind::InvalidData,
+                format!("the broadcast of the tree lacks the packet of party {origin}"),
+            ));
+        }
+        Ok(gathered.into_iter().flatten().collect())
+    }
+
+    /// Decodes the packets of several parties forwarded together in a tree, paired with the ID
+    /// of the party that sent each of them.
+    fn decode_bundle(packet: &Packet) -> anyhow::Result<Vec<(usize, Vec<u8>)>> {
+        Ok(bincode::deserialize(packet.as_slice())?)
+    }
+
+    /// Broadcasts a message to every party with a consistent echo broadcast, in which the
+    /// parties check that all of them received the same message. Every other party must call
+    /// [`Network::recv_broadcast`] with the ID of this party and the same `instance`. If some
+    /// party detects an equivocation, it aborts the protocol.
+    pub fn broadcast(&mut self, instance: u32, message: Vec<u8>) -> anyhow::Result<()> {
+        broadcast::echo_broadcast(self, self.id, instance, Some(message)).map(|_| ())
+    }
+
+    /// Receives the message that the party `sender` broadcasts with [`Network::broadcast`] in
+    /// the given `instance`, once every party has confirmed that it received the same message.
+    pub fn recv_broadcast(&mut self, sender: usize, instance: u32) -> anyhow::Result<Vec<u8>> {
+        broadcast::echo_broadcast(self, sender, instance, None)
+    }
+
+    /// Sends the packet of the party `root` to every other party along the [`Topology`] of the
+    /// network, and returns it. Only the root gives its packet, which the other parties receive,
+    /// and forward in the ring and the tree.
+    pub fn broadcast_from(
+        &mut self,
+        root: usize,
+        packet: Option<&Packet>,
+    ) -> anyhow::Result<Packet> {
+        let n_parties = self.peer_channels.len();
+        if root >= n_parties {
+            anyhow::bail!(Error::new(
+                ErrorKind::InvalidInput,
+                format!("the root {root} of a broadcast is not one of the {n_parties} parties"),
+            ));
+        }
+        let (parent, children) = match self.topology {
+            Topology::Mesh if self.id == root => {
+                (None, (0..n_parties).filter(|id| *id != root).collect())
+            }
+            Topology::Mesh => (Some(root), Vec::new()),
+            Topology::Ring => {
+                let position = topology::position(self.id, root, n_parties);
+                let next = topology::ring_next(self.id, n_parties);
+                (
+                    (position != 0).then(|| topology::ring_previous(self.id, n_parties)),
+                    if position + 1 < n_parties {
+                        vec![next]
+                    } else {
+                        Vec::new()
+                    },
+                )
+            }
+            Topology::Tree => (
+                topology::tree_parent(self.id, root, n_parties),
+                topology::tree_children(self.id, root, n_parties),
+            ),
+        };
+        let packet = match (parent, packet) {
+            (Some(parent), None) => self.recv_from(parent)?,
+            (None, Some(packet)) => packet.clone(),
+            _ => anyhow::bail!(Error::new(
+                ErrorKind::InvalidInput,
+                format!("only the root {root} of a broadcast gives the packet"),
+            )),
+        };
+        for child in children {
+            self.channel_send(&packet, child)?;
+        }
+        Ok(packet)
+    }
+
+    /// Sends the packet to every party and receives the packet of the same protocol and round
+    /// from every party, waiting at most the given deadline. The packet in position `i` of the
+    /// result was sent by the party `i`, and it is `None` if it did not arrive in time or the
+    /// connection with the party is closed. The packets of earlier rounds of the protocol that
+    /// arrive late are discarded, and the packets of other protocols or later rounds are buffered.
+    /// A deadline of [`Duration::MAX`] waits for every packet.
+    pub fn broadcast_and_gather_within(
+        &mut self,
+        packet: &Packet,
+        deadline: Duration,
+    ) -> anyhow::Result<Vec<Option<Packet>>> {
+        let PacketHeader {
+            protocol_id, round, ..
+        } = *packet.header();
+        let mut classify = |header: &PacketHeader| {
+            Ok(if header.matches(protocol_id, round) {
+                Disposition::Accept
+            } else if header.protocol_id == protocol_id && header.round < round {
+                Disposition::Discard
+            } else {
+                Disposition::Buffer
+            })
+        };
+        let ends_at = Instant::now().checked_add(deadline);
+
+        let n_parties = self.peer_channels.len();
+        let mut gathered: Vec<Option<Packet>> = vec![None; n_parties];
+        let mut closed = vec![false; n_parties];
+        for (party_id, closed) in closed.iter_mut().enumerate() {
+            match self.channel_send(packet, party_id) {
+                Ok(_) => {}
+                Err(err) if channel::is_connection_closed(&err) => {
+                    log::warn!("party {party_id} closed the connection before round {round}");
+                    *closed = true;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        for (party_id, slot) in gathered.iter_mut().enumerate() {
+            let pending = &mut self.pending[party_id];
+            if let Some(position) = pending
+                .iter()
+                .position(|packet| packet.header().matches(protocol_id, round))
+            {
+                *slot = pending.remove(position);
+            }
+        }
+
+        loop {
+            let mut received = false;
+            for party_id in 0..n_parties {
+                if gathered[party_id].is_some() || closed[party_id] {
+                    continue;
+                }
+                match self.channel_try_recv(party_id) {
+                    Ok(Some(packet)) => {
+                        received = true;
+                        gathered[party_id] = self.dispose(party_id, packet, &mut classify)?;
+                    }
+                    Ok(None) => {}
+                    Err(err) if channel::is_connection_closed(&err) => {
+                        log::warn!("party {party_id} closed the connection in round {round}");
+                        closed[party_id] = true;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            let done = gathered
+                .iter()
+                .zip(&closed)
+                .all(|(slot, closed)| slot.is_some() || *closed);
+            if done || ends_at.is_some_and(|ends_at| Instant::now() >= ends_at) {
+                return Ok(gathered);
+            }
+            self.check_interrupt()?;
+            if !received {
+                std::thread::sleep(RECV_ANY_POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// Receives without blocking the next packet of each party whose slot is still empty.
+    fn gather_ready(&mut self, gathered: &mut [Option<Packet>]) -> anyhow::Result<()> {
+        for (party_id, slot) in gathered.iter_mut().enumerate() {
+            if slot.is_some() {
+                continue;
+            }
+            *slot = match self.pending[party_id].pop_front() {
+                Some(packet) => Some(packet),
+                None => self.channel_try_recv(party_id)?,
+            };
+        }
+        Ok(())
+    }
+
+    /// Returns the ID of the party that owns the network.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the number of parties in the network.
+    pub fn n_parties(&self) -> usize {
+        self.peer_channels.len()
+    }
+
+    /// Returns a secret of 32 bytes that the party shares with the party of the given ID under
+    /// the given label, exported from the TLS session of their channel, or `None` if the channel
+    /// is not a TLS channel.
+    pub fn shared_secret(&self, party_id: usize, label: &[u8]) -> anyhow::Result<Option<[u8; 32]>> {
+        let mut secret = [0; 32];
+        let exported = self.peer_channels[party_id].export_keying_material(label, &mut secret)?;
+        Ok(exported.then_some(secret))
+    }
+
+    /// Sends the packet `packets[i]` to the party `i` and receives one packet from each party.
+    /// The packet in position `i` of the result was sent by the party `i`. The exchange with each
+    /// peer is done in ascending order of peer ID, and in each pair the party with the lower ID
+    /// sends first. This way, all the parties go through the exchanges in the same global order,
+    /// so a symmetric round cannot deadlock even if the packets exceed the socket buffers.
+    pub fn exchange(&mut self, packets: Vec<Packet>) -> anyhow::Result<Vec<Packet>> {
+        if packets.len() != self.peer_channels.len() {
+            anyhow::bail!(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} packets were given to exchange with {} parties",
+                    packets.len(),
+                    self.peer_channels.len()
+                ),
+            ));
+        }
+        let mut received = Vec::with_capacity(packets.len());
+        for (party_id, packet) in packets.iter().enumerate() {
+            if party_id < self.id {
+                received.push(self.recv_from(party_id)?);
+                self.channel_send(packet, party_id)?;
+            } else {
+                self.channel_send(packet, party_id)?;
+                received.push(self.recv_from(party_id)?);
+            }
+        }
+        Ok(received)
+    }
+
+    /// Writes the packets buffered in the channels to the connections. Sending buffers the
+    /// packets so the small packets of a round travel together, and the network flushes them
+    /// before waiting to receive, so calling this function is only needed to deliver the packets
+    /// of a round earlier.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        let buffered: Vec<usize> = self.peer_channels.iter().map(|c| c.buffered()).collect();
+        let writing = buffered.iter().filter(|bytes| **bytes > 0).count();
+        if writing <= 1 || buffered.iter().sum::<usize>() < PARALLEL_WRITE_THRESHOLD {
+            for channel in &mut self.peer_channels {
+                channel.flush()?;
+            }
+            return Ok(());
+        }
+        on_every_channel(&mut self.peer_channels, |_, channel| channel.flush())
+            .into_iter()
+            .collect()
+    }
+
+    /// Sends a packet of information to a given party.
+    pub fn send_to(&mut self, packet: &Packet, party_id: usize) -> anyhow::Result<usize> {
+        self.channel_send(packet, party_id)
+    }
+
+    /// Receives a packet from a given party. Packets buffered while waiting for other messages
+    /// are delivered first, in the order in which they arrived.
+    pub fn recv_from(&mut self, party_id: usize) -> anyhow::Result<Packet> {
+        if let Some(packet) = self.pending[party_id].pop_front() {
+            return Ok(packet);
+        }
+        self.channel_recv(party_id)
+    }
+
+    /// Receives a packet from a given party into the given buffer, whose contents are replaced
+    /// by the payload, and returns the header of the packet. Hot loops can reuse the same buffer
+    /// for every packet instead of allocating a new one each time. Packets buffered while
+    /// waiting for other messages are delivered first.
+    pub fn recv_from_into(
+        &mut self,
+        party_id: usize,
+        buffer: &mut Vec<u8>,
+    ) -> anyhow::Result<PacketHeader> {
+        if let Some(packet) = self.pending[party_id].pop_front() {
+            buffer.clear();
+            buffer.extend_from_slice(packet.as_slice());
+            return Ok(*packet.header());
+        }
+        self.channel_recv_into(party_id, buffer)
+    }
+
+    /// Receives a packet from a given party waiting at most the given timeout. If the timeout is
+    /// reached, the function returns [`ChannelError::RecvTimeout`].
+    pub fn recv_from_timeout(
+        &mut self,
+        party_id: usize,
+        timeout: Duration,
+    ) -> anyhow::Result<Packet> {
+        if let Some(packet) = self.pending[party_id].pop_front() {
+            return Ok(packet);
+        }
+        self.channel_recv_timeout(party_id, timeout)
+    }
+
+    /// Receives a packet from a given party if there is one ready, without blocking.
+    pub fn try_recv_from(&mut self, party_id: usize) -> anyhow::Result<Option<Packet>> {
+        if let Some(packet) = self.pending[party_id].pop_front() {
+            return Ok(Some(packet));
+        }
+        self.channel_try_recv(party_id)
+    }
+
+    /// Receives a packet from whichever party has one ready first, returning the ID of the
+    /// sender together with the packet. Buffered packets are delivered before polling the
+    /// channels, and both are visited in a round-robin fashion, starting after the last peer
+    /// served, so that no peer starves.
+    pub fn recv_any(&mut self) -> anyhow::Result<(usize, Packet)> {
+        self.recv_any_matching(|_| Ok(Disposition::Accept))
+    }
+
+    /// Receives from a given party the first packet accepted by `classify`. Buffered packets
+    /// are checked first, and only for acceptance.
+    fn recv_matching<F>(&mut self, party_id: usize, mut classify: F) -> anyhow::Result<Packet>
+    where
+        F: FnMut(&PacketHeader) -> anyhow::Result<Disposition>,
+    {
+        let pending = &mut self.pending[party_id];
+        if let Some(position) = pending
+            .iter()
+            .position(|packet| matches!(classify(packet.header()), Ok(Disposition::Accept)))
+        {
+            // The unwrap is safe because the position was found in the queue.
+            return Ok(pending.remove(position).unwrap());
+        }
+
+        loop {
+            let packet = self.channel_recv(party_id)?;
+            if let Some(packet) = self.dispose(party_id, packet, &mut classify)? {
+                return Ok(packet);
+            }
+        }
+    }
+
+    /// Receives from whichever party is ready first the first packet accepted by `classify`,
+    /// returning the ID of the sender together with the packet. Buffered packets are checked
+    /// first, and only for acceptance.
+    fn recv_any_matching<F>(&mut self, classify: F) -> anyhow::Result<(usize, Packet)>
+    where
+        F: FnMut(&PacketHeader) -> anyhow::Result<Disposition>,
+    {
+        match self.recv_any_matching_until(None, classify)? {
+            Some(received) => Ok(received),
+            None => unreachable!("a receive without time limit only returns with a packet"),
+        }
+    }
+
+    /// Receives like [`Network::recv_any_matching`], but gives up at the instant `until`, if
+    /// any, returning `None` if no packet was accepted by then.
+    fn recv_any_matching_until<F>(
+        &mut self,
+        until: Option<Instant>,
+        mut classify: F,
+    ) -> anyhow::Result<Option<(usize, Packet)>>
+    where
+        F: FnMut(&PacketHeader) -> anyhow::Result<Disposition>,
+    {
+        // The buffered packets are checked from the same rotating party as the channels, so the
+        // peers with low IDs are not always served first.
+        let n_parties = self.peer_channels.len();
+        for offset in 0..n_parties {
+            let party_id = (self.next_poll + offset) % n_parties;
+            let pending = &mut self.pending[party_id];
+            if let Some(position) = pending
+                .iter()
+                .position(|packet| matches!(classify(packet.header()), Ok(Disposition::Accept)))
+            {
+                self.next_poll = (party_id + 1) % n_parties;
+                // The unwrap is safe because the position was found in the queue.
+                return Ok(Some((party_id, pending.remove(position).unwrap())));
+            }
+        }
+
+        // Peers that close their connection are not polled again. This happens, for example,
+        // when a party finishes a protocol before the others.
+        let mut closed = vec![false; n_parties];
+        loop {
+            let mut received = false;
+            for offset in 0..n_parties {
+                let party_id = (self.next_poll + offset) % n_parties;
+                if closed[party_id] {
+                    continue;
+                }
+                match self.channel_try_recv(party_id) {
+                    Ok(Some(packet)) => {
+                        received = true;
+                        if let Some(packet) = self.dispose(party_id, packet, &mut classify)? {
+                            self.next_poll = (party_id + 1) % n_parties;
+                            return Ok(Some((party_id, packet)));
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(err) if channel::is_connection_closed(&err) => {
+                        log::debug!("party {party_id} closed the connection");
+                        closed[party_id] = true;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            if closed.iter().all(|is_closed| *is_closed) {
+                anyhow::bail!(ChannelError::ConnectionClosed);
+            }
+            if until.is_some_and(|until| Instant::now() >= until) {
+                return Ok(None);
+            }
+            self.check_interrupt()?;
+            if !received {
+                std::thread::sleep(RECV_ANY_POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// Applies the disposition given by `classify` to a packet received from a given party.
+    /// Returns the packet if it was accepted.
+    fn dispose<F>(
+        &mut self,
+        party_id: usize,
+        packet: Packet,
+        classify: &mut F,
+    ) -> anyhow::Result<Option<Packet>>
+    where
+        F: FnMut(&PacketHeader) -> anyhow::Result<Disposition>,
+    {
+        let header = *packet.header();
+        match classify(&header)? {
+            Disposition::Accept => return Ok(Some(packet)),
+            Disposition::Buffer => {
+                log::debug!(
+                    "buffering packet from party {party_id} for protocol {} at round {} in sub-channel {}",
+                    header.protocol_id,
+                    header.round,
+                    header.sub_channel,
+                );
+                self.pending[party_id].push_back(packet);
+            }
+            Disposition::Discard => {
+                log::debug!(
+                    "discarding packet from party {party_id} for protocol {} at round {}",
+                    header.protocol_id,
+                    header.round,
+                );
+            }
+        }
+        Ok(None)
+    }
+
+    /// Receives from a given party the packet with the given protocol ID and round. Packets from
+    /// the same party that belong to other protocols or to future rounds are buffered until they
+    /// are requested. If a packet of an earlier round of the same protocol is received, the
+    /// function returns an error.
+    pub fn recv_tagged(
+        &mut self,
+        party_id: usize,
+        protocol_id: u16,
+        round: u32,
+    ) -> anyhow::Result<Packet> {
+        self.recv_matching(party_id, |header| {
+            if header.protocol_id == protocol_id && header.round < round {
+                anyhow::bail!(ChannelError::StalePacket {
+                    protocol_id,
+                    round: header.round,
+                    expected_round: round,
+                });
+            }
+            Ok(Disposition::accept_if(header.matches(protocol_id, round)))
+        })
+    }
+
+    /// Sends a packet to a given party through a logical sub-channel of the connection.
+    pub fn send_on(
+        &mut self,
+        sub_channel: u16,
+        packet: Packet,
+        party_id: usize,
+    ) -> anyhow::Result<usize> {
+        self.send_to(&packet.in_sub_channel(sub_channel), party_id)
+    }
+
+    /// Receives the next packet sent by a given party through a logical sub-channel. Packets of
+    /// other sub-channels are buffered, so each sub-channel keeps its own ordered delivery.
+    pub fn recv_on(&mut self, sub_channel: u16, party_id: usize) -> anyhow::Result<Packet> {
+        self.recv_matching(party_id, |header| {
+            Ok(Disposition::accept_if(header.sub_channel == sub_channel))
+        })
+    }
+
+    /// Sends to a given party the bytes read from `reader` until its end as a stream of chunks
+    /// of at most `chunk_size` bytes, so a large payload never has to be in memory at once. The
+    /// chunk size must not exceed the maximum packet size of the party. Returns the number of
+    /// bytes sent.
+    pub fn send_stream_to(
+        &mut self,
+        reader: &mut dyn Read,
+        chunk_size: usize,
+        party_id: usize,
+    ) -> anyhow::Result<u64> {
+        let mut stream = StreamSender::new(chunk_size)?;
+        while let Some(chunk) = stream.next_chunk(reader)? {
+            self.channel_send(&chunk, party_id)?;
+        }
+        Ok(stream.bytes())
+    }
+
+    /// Receives a stream sent with [`Network::send_stream_to`] by a given party and writes its
+    /// bytes to `writer` as the chunks arrive. Packets of the party that do not belong to the
+    /// stream are buffered. Returns the number of bytes received.
+    pub fn recv_stream_from(
+        &mut self,
+        writer: &mut dyn Write,
+        party_id: usize,
+    ) -> anyhow::Result<u64> {
+        let mut stream = StreamReceiver::default();
+        loop {
+            let chunk = self.recv_matching(party_id, |header| {
+                Ok(Disposition::accept_if(StreamReceiver::is_chunk(header)))
+            })?;
+            if !stream.write_chunk(&chunk, writer)? {
+                return Ok(stream.bytes());
+            }
+        }
+    }
+
+    /// Sends a value to a given party. The value is encoded with the codec of the network and
+    /// tagged with its type, so the receiver can check that it receives a value of the expected
+    /// type.
+    pub fn send_value<T: Serialize>(
+        &mut self,
+        value: &T,
+        party_id: usize,
+    ) -> anyhow::Result<usize> {
+        self.send_to(&encode_value(self.codec, value)?, party_id)
+    }
+
+    /// Receives a value sent with [`Network::send_value`] by a given party. If the party sent a
+    /// value of another type, the function returns [`value::ValueError::TypeMismatch`].
+    pub fn recv_value<T: DeserializeOwned>(&mut self, party_id: usize) -> anyhow::Result<T> {
+        decode_value(self.codec, &self.recv_from(party_id)?)
+    }
+
+    /// Sends the value `values[i]` to the party `i` and receives one value from each party, in
+    /// the same way as [`Network::exchange`].
+    pub fn exchange_values<T>(&mut self, values: &[T]) -> anyhow::Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let codec = self.codec;
+        let packets = values
+            .iter()
+            .map(|value| encode_value(codec, value))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.exchange(packets)?
+            .iter()
+            .map(|packet| decode_value(codec, packet))
+            .collect()
+    }
+
+    /// Sends a value to every party and receives one value from every party, in the same way as
+    /// [`Network::broadcast_and_gather`].
+    pub fn broadcast_and_gather_values<T>(&mut self, value: &T) -> anyhow::Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let codec = self.codec;
+        self.broadcast_and_gather(&encode_value(codec, value)?)?
+            .iter()
+            .map(|packet| decode_value(codec, packet))
+            .collect()
+    }
+}
+
+impl Transport for Network {
+    fn id(&self) -> usize {
+        Network::id(self)
+    }
+
+    fn n_parties(&self) -> usize {
+        Network::n_parties(self)
+    }
+
+    fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    fn shared_secret(&mut self, party_id: usize, label: &[u8]) -> anyhow::Result<Option<[u8; 32]>> {
+        Network::shared_secret(self, party_id, label)
+    }
+
+    fn send_message(&mut self, party_id: usize, message: Vec<u8>) -> anyhow::Result<()> {
+        self.send_to(&Packet::new(message), party_id)?;
+        Ok(())
+    }
+
+    fn recv_message(&mut self, party_id: usize) -> anyhow::Result<Vec<u8>> {
+        Ok(self.recv_from(party_id)?.into_payload())
+    }
+
+    /// Exchanges the values with [`Network::exchange_values`], so they are tagged with their
+    /// type and the round of the network.
+    fn exchange_values<T>(&mut self, values: &[T]) -> anyhow::Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        Network::exchange_values(self, values)
+    }
+
+    /// Broadcasts the value with [`Network::broadcast_and_gather_values`].
+    fn broadcast_and_gather_values<T>(&mut self, value: &T) -> anyhow::Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        Network::broadcast_and_gather_values(self, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        audit::{self, AuditEvent},
+        channel::{Channel, ChannelError, LoopBackChannel},
+        decorator::{Faults, FaultyChannel},
+        guard::{GuardError, ReceiveLimits},
+        local::LocalChannel,
+        progress::PeerStatus,
+        rekey::RekeyPolicy,
+        sequence::SequenceError,
+        topology::Topology,
+        transcript::TranscriptReader,
+        version::{Capabilities, Features, VersionError, WIRE_VERSION},
+        Network, Packet, PeerEndpoint, PeerErrors, PARALLEL_WRITE_THRESHOLD,
+    };
+    use crate::canary::{freed_copies, CANARY};
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+    use zeroize::Zeroize;
+
+    #[test]
+    fn recv_tagged_buffers_future_rounds() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        network.send_to(&Packet::tagged(1, 1, vec![1]), 0).unwrap();
+        network.send_to(&Packet::tagged(1, 0, vec![0]), 0).unwrap();
+
+        let packet = network.recv_tagged(0, 1, 0).unwrap();
+        assert_eq!(packet.as_slice(), &[0]);
+        let packet = network.recv_tagged(0, 1, 1).unwrap();
+        assert_eq!(packet.as_slice(), &[1]);
+    }
+
+    #[test]
+    fn flooding_peers_are_disconnected() {
+        let mut networks = Network::create_local(2);
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        network_0.set_receive_limits(ReceiveLimits {
+            max_pending_packets: Some(4),
+            ..Default::default()
+        });
+        for round in 1..=8 {
+            network_1
+                .send_to(&Packet::tagged(1, round, vec![0; 16]), 0)
+                .unwrap();
+        }
+
+        let err = network_0.recv_tagged(1, 1, 0).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<GuardError>(),
+            Some(&GuardError::TooManyPending {
+                party_id: 1,
+                limit: 4
+            })
+        );
+        let err = network_0.recv_tagged(1, 1, 1).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<GuardError>(),
+            Some(&GuardError::Disconnected(1))
+        );
+    }
+
+    #[test]
+    fn security_events_are_audited() {
+        let path =
+            std::env::temp_dir().join(format!("shami_rs_net_audit_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut networks = Network::create_local(2);
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        network_0.record_audit_log(&path).unwrap();
+        network_0.set_receive_limits(ReceiveLimits {
+            max_pending_packets: Some(1),
+            ..Default::default()
+        });
+        for round in 1..=2 {
+            network_1
+                .send_to(&Packet::tagged(1, round, vec![0]), 0)
+                .unwrap();
+        }
+        network_0.recv_tagged(1, 1, 0).unwrap_err();
+        network_0.abort();
+
+        let events: Vec<AuditEvent> = audit::verify(&path)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                AuditEvent::CheaterIdentified {
+                    party_id: 1,
+                    reason: GuardError::TooManyPending {
+                        party_id: 1,
+                        limit: 1
+                    }
+                    .to_string(),
+                },
+                AuditEvent::Aborted,
+            ]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Channel that counts the rotations of its keys.
+    struct RekeyCounter {
+        inner: Box<dyn Channel>,
+        rotations: Arc<AtomicUsize>,
+    }
+
+    impl Channel for RekeyCounter {
+        fn shutdown(&mut self) -> anyhow::Result<()> {
+            self.inner.shutdown()
+        }
+
+        fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+            self.inner.send(packet)
+        }
+
+        fn recv(&mut self) -> anyhow::Result<Packet> {
+            self.inner.recv()
+        }
+
+        fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+            self.inner.recv_timeout(timeout)
+        }
+
+        fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+            self.inner.try_recv()
+        }
+
+        fn rekey(&mut self) -> anyhow::Result<bool> {
+            self.rotations.fetch_add(1, Ordering::SeqCst);
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn keys_are_rotated_at_round_boundaries() {
+        let mut networks = Network::create_local(2);
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        let rotations = Arc::new(AtomicUsize::new(0));
+        network_0.wrap_channels(|_, inner| {
+            Box::new(RekeyCounter {
+                inner,
+                rotations: rotations.clone(),
+            })
+        });
+        network_0.set_rekey_policy(RekeyPolicy {
+            after_bytes: Some(10),
+            ..Default::default()
+        });
+
+        // The limit is reached in the middle of the first round, but the keys are only rotated
+        // once the next round starts.
+        for _ in 0..2 {
+            network_0.send_to(&Packet::new(vec![0; 8]), 1).unwrap();
+        }
+        assert_eq!(rotations.load(Ordering::SeqCst), 0);
+        network_1.send_to(&Packet::new(vec![1]), 0).unwrap();
+        network_0.recv_from(1).unwrap();
+        network_0.send_to(&Packet::new(vec![0; 8]), 1).unwrap();
+        // The loop-back channel is never rotated.
+        assert_eq!(rotations.load(Ordering::SeqCst), 1);
+        for _ in 0..3 {
+            network_1.recv_from(0).unwrap();
+        }
+    }
+
+    #[test]
+    fn wire_format_is_negotiated() {
+        let handles: Vec<_> = Network::create_local(3)
+            .into_iter()
+            .map(|mut network| {
+                thread::spawn(move || {
+                    network.negotiate(Features::BINCODE).unwrap();
+                    network
+                })
+            })
+            .collect();
+        for handle in handles {
+            let network = handle.join().unwrap();
+            for party_id in 0..3 {
+                let expected = (party_id != network.id()).then_some(Capabilities {
+                    version: WIRE_VERSION,
+                    features: Features::supported(),
+                });
+                assert_eq!(network.peer_capabilities(party_id), expected);
+            }
+        }
+
+        // A peer that requires a feature unknown to the party is rejected at both ends.
+        let mut networks = Network::create_local(2);
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        let handle = thread::spawn(move || {
+            network_1
+                .negotiate(Features::from_bits(1 << 63))
+                .unwrap_err()
+        });
+        let err = network_0.negotiate(Features::BINCODE).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VersionError>(),
+            Some(VersionError::LacksFeatures { party_id: 1, .. })
+        ));
+        let err = handle.join().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<VersionError>(),
+            Some(VersionError::PeerLacksFeatures { party_id: 0, .. })
+        ));
+
+        // A peer that starts sending values without negotiating is rejected too.
+        let mut networks = Network::create_local(2);
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        network_1.send_to(&Packet::new(vec![1]), 0).unwrap();
+        let err = network_0.negotiate(Features::BINCODE).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VersionError>(),
+            Some(VersionError::MissingHello(1))
+        ));
+    }
+
+    #[test]
+    fn sub_channels_are_delivered_independently() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        network.send_on(1, Packet::new(vec![10]), 0).unwrap();
+        network.send_on(2, Packet::new(vec![20]), 0).unwrap();
+        network.send_on(1, Packet::new(vec![11]), 0).unwrap();
+
+        assert_eq!(network.recv_on(2, 0).unwrap().as_slice(), &[20]);
+        assert_eq!(network.recv_on(1, 0).unwrap().as_slice(), &[10]);
+        assert_eq!(network.recv_on(1, 0).unwrap().as_slice(), &[11]);
+    }
+
+    #[test]
+    fn recv_timeout_on_empty_channel() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        assert!(network.try_recv_from(0).unwrap().is_none());
+        let err = network
+            .recv_from_timeout(0, Duration::from_millis(10))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::RecvTimeout(_))
+        ));
+    }
+
+    #[test]
+    fn recv_any_returns_sender() {
+        // The party is the second one, so the packets of its loopback channel come from party 1.
+        let mut network = Network::from_channels(
+            1,
+            vec![
+                Box::new(LoopBackChannel::default()),
+                Box::new(LoopBackChannel::default()),
+            ],
+        );
+        network.send_to(&Packet::new(vec![1]), 1).unwrap();
+        let (party_id, packet) = network.recv_any().unwrap();
+        assert_eq!(party_id, 1);
+        assert_eq!(packet.as_slice(), &[1]);
+    }
+
+    #[test]
+    fn recv_any_serves_every_peer_with_buffered_packets() {
+        let mut network = Network::from_channels(
+            0,
+            (0..3)
+                .map(|_| Box::new(LoopBackChannel::default()) as Box<dyn Channel>)
+                .collect(),
+        );
+        for party_id in 0..3 {
+            for _ in 0..2 {
+                network.pending[party_id].push_back(Packet::new(vec![party_id as u8]));
+            }
+        }
+        let senders: Vec<usize> = (0..6)
+            .map(|_| {
+                let (party_id, packet) = network.recv_any().unwrap();
+                assert_eq!(packet.as_slice(), &[party_id as u8]);
+                party_id
+            })
+            .collect();
+        assert_eq!(senders, [0, 1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn recv_all_within_a_deadline() {
+        let mut networks = Network::create_local(3);
+        let mut network_2 = networks.pop().unwrap();
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        network_0.send_to(&Packet::new(vec![0]), 0).unwrap();
+        network_1.send_to(&Packet::new(vec![1]), 0).unwrap();
+        network_1.flush().unwrap();
+
+        // Party 2 does not send anything, so the reception times out.
+        let err = network_0
+            .recv_all_deadline(Duration::from_millis(20))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::RecvTimeout(_))
+        ));
+
+        // The packets that arrived in time are kept.
+        network_2.send_to(&Packet::new(vec![2]), 0).unwrap();
+        network_2.flush().unwrap();
+        let packets = network_0.recv_all_deadline(Duration::MAX).unwrap();
+        let payloads: Vec<&[u8]> = packets.iter().map(Packet::as_slice).collect();
+        assert_eq!(payloads, [&[0], &[1], &[2]]);
+    }
+
+    #[test]
+    fn transcripts_tag_the_packets_with_the_phase() {
+        let path = std::env::temp_dir().join(format!(
+            "shami_rs_phase_transcript_{}.bin",
+            std::process::id()
+        ));
+        let mut networks = Network::create_local(2);
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        network_0.record_transcript(&path).unwrap();
+        network_0.begin_phase("sharing");
+        network_0
+            .send_to(&Packet::tagged(1, 0, vec![1]), 1)
+            .unwrap();
+        network_0.end_phase();
+        network_0
+            .send_to(&Packet::tagged(1, 1, vec![2]), 1)
+            .unwrap();
+        network_0.flush().unwrap();
+        network_1.recv_from(0).unwrap();
+
+        let records: Vec<_> = TranscriptReader::open(&path)
+            .unwrap()
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+        let phases: Vec<_> = records
+            .iter()
+            .map(|record| record.phase.as_deref())
+            .collect();
+        assert_eq!(phases, [Some("sharing"), None]);
+
+        // The replayed party sends the same packets again.
+        let mut replay = Network::replay(&path).unwrap();
+        replay.send_to(&Packet::tagged(1, 0, vec![3]), 1).unwrap();
+        replay.send_to(&Packet::tagged(1, 1, vec![4]), 1).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn parallel_sends_and_receptions_keep_the_order_of_each_peer() {
+        const N_PARTIES: usize = 4;
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .enumerate()
+            .map(|(id, mut network)| {
+                thread::spawn(move || {
+                    // The large packet is written to the peers in parallel, the small one is not.
+                    let large = vec![id as u8; PARALLEL_WRITE_THRESHOLD + id];
+                    network.send(&Packet::new(large)).unwrap();
+                    network.send(&Packet::new(vec![id as u8])).unwrap();
+                    let large = network.recv().unwrap();
+                    let small = network.recv().unwrap();
+                    (large, small, network.stats().rounds)
+                })
+            })
+            .collect();
+        for handle in handles {
+            let (large, small, rounds) = handle.join().unwrap();
+            for (party_id, (large, small)) in large.iter().zip(&small).enumerate() {
+                assert_eq!(large.size(), PARALLEL_WRITE_THRESHOLD + party_id);
+                assert!(large.as_slice().iter().all(|byte| *byte == party_id as u8));
+                assert_eq!(small.as_slice(), [party_id as u8]);
+            }
+            assert_eq!(rounds, 1);
+        }
+    }
+
+    #[test]
+    fn peer_status_tells_slow_peers_from_gone_ones() {
+        let mut networks = Network::create_local(2);
+        let network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        assert_eq!(network_0.peer_status(0).0, PeerStatus::Local);
+        assert_eq!(network_0.peer_status(1).0, PeerStatus::Connected);
+
+        let err = network_0
+            .recv_from_timeout(1, Duration::from_millis(10))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::RecvTimeout(_))
+        ));
+        drop(network_1);
+        assert!(network_0.recv_from(1).is_err());
+        assert_eq!(network_0.peer_status(1).0, PeerStatus::Failed);
+        assert_eq!(network_0.peer_status(0).0, PeerStatus::Local);
+    }
+
+    #[test]
+    fn reject_packets_of_another_sender() {
+        let (channel, mut peer) = LocalChannel::pair();
+        let mut network = Network::from_channels(
+            0,
+            vec![Box::new(LoopBackChannel::default()), Box::new(channel)],
+        );
+        peer.send(&Packet::new(vec![1]).sent_by(1)).unwrap();
+        assert_eq!(network.recv_from(1).unwrap().as_slice(), &[1]);
+        // The peer claims to be party 0 in the header.
+        peer.send(&Packet::new(vec![2])).unwrap();
+        let err = network.recv_from(1).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::UnexpectedSender {
+                party_id: 1,
+                sender: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn close_waits_for_the_peers() {
+        let mut networks = Network::create_local(2);
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        let peer = thread::spawn(move || {
+            network_1.send_to(&Packet::new(vec![1]), 0).unwrap();
+            thread::sleep(Duration::from_millis(50));
+            let packet = network_1.recv_from(0).unwrap();
+            let closed = network_1.recv_from(0).unwrap_err();
+            assert!(matches!(
+                closed.downcast_ref::<ChannelError>(),
+                Some(ChannelError::ConnectionClosed)
+            ));
+            network_1.close().unwrap();
+            packet
+        });
+
+        network_0.send_to(&Packet::new(vec![0]), 1).unwrap();
+        network_0.close().unwrap();
+        assert_eq!(network_0.recv_from(1).unwrap().as_slice(), &[1]);
+        assert_eq!(peer.join().unwrap().as_slice(), &[0]);
+    }
+
+    #[test]
+    fn interrupt_aborts_the_peers() {
+        static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+        let mut networks = Network::create_local(2);
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        network_0.set_interrupt(&INTERRUPTED);
+        let peer = thread::spawn(move || network_1.recv_from(0).unwrap_err());
+
+        thread::spawn(|| {
+            thread::sleep(Duration::from_millis(50));
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        });
+        let err = network_0.recv_from(1).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::Interrupted)
+        ));
+        let err = peer.join().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::PeerAborted(0))
+        ));
+    }
+
+    #[test]
+    fn deadline_aborts_the_peers() {
+        let path =
+            std::env::temp_dir().join(format!("shami_rs_net_deadline_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut networks = Network::create_local(2);
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        network_0.record_audit_log(&path).unwrap();
+        network_0.set_deadline(Some(Duration::from_millis(50)));
+        assert_eq!(network_0.deadline(), Some(Duration::from_millis(50)));
+        let peer = thread::spawn(move || network_1.recv_from(0).unwrap_err());
+
+        // The peer never sends, so the party would wait forever without the deadline.
+        let err = network_0.recv_from(1).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::DeadlineExceeded(_))
+        ));
+        let err = peer.join().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<ChannelError>(),
+            Some(ChannelError::PeerAborted(0))
+        ));
+        let events: Vec<AuditEvent> = audit::verify(&path)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                AuditEvent::DeadlineExceeded { deadline_ms: 50 },
+                AuditEvent::Aborted
+            ]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn scatter_sends_each_packet_to_its_party() {
+        let mut networks = Network::create_local(3);
+        let packets = (0..3).map(|i| Packet::new(vec![i])).collect();
+        networks[0].scatter(packets).unwrap();
+        for (i, network) in networks.iter_mut().enumerate() {
+            assert_eq!(network.recv_from(0).unwrap().as_slice(), &[i as u8]);
+        }
+        assert!(networks[0].scatter(vec![Packet::empty()]).is_err());
+    }
+
+    #[test]
+    fn scatter_aggregates_peer_errors() {
+        let mut networks = Network::create_local(3);
+        networks.truncate(1);
+        let packets = (0..3).map(|i| Packet::new(vec![i])).collect();
+        let err = networks[0].scatter(packets).unwrap_err();
+        let failed: Vec<usize> = err
+            .downcast_ref::<PeerErrors>()
+            .unwrap()
+            .0
+            .iter()
+            .map(|(party_id, _)| *party_id)
+            .collect();
+        assert_eq!(failed, vec![1, 2]);
+        assert_eq!(networks[0].recv_from(0).unwrap().as_slice(), &[0]);
+    }
+
+    #[test]
+    fn broadcast_and_gather_collects_from_everyone() {
+        let networks = Network::create_local(3);
+        let handles: Vec<_> = networks
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut network)| {
+                thread::spawn(move || {
+                    network
+                        .broadcast_and_gather(&Packet::new(vec![i as u8]))
+                        .unwrap()
+                })
+            })
+            .collect();
+        for handle in handles {
+            let gathered: Vec<u8> = handle
+                .join()
+                .unwrap()
+                .iter()
+                .map(|packet| packet.as_slice()[0])
+                .collect();
+            assert_eq!(gathered, vec![0, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn broadcasts_travel_along_every_topology() {
+        const N_PARTIES: usize = 6;
+        const ROOT: usize = 2;
+
+        for topology in [Topology::Mesh, Topology::Ring, Topology::Tree] {
+            let handles: Vec<_> = Network::create_local(N_PARTIES)
+                .into_iter()
+                .map(|mut network| {
+                    thread::spawn(move || {
+                        network.set_topology(topology);
+                        let before = network.stats().clone();
+                        let id = network.id() as u8;
+                        let gathered = network
+                            .broadcast_and_gather(&Packet::new(vec![id, id]))
+                            .unwrap();
+                        let talked: usize = before
+                            .peers
+                            .iter()
+                            .zip(&network.stats().peers)
+                            .enumerate()
+                            .filter(|(peer, (before, after))| {
+                                *peer != network.id() && after.packets_sent > before.packets_sent
+                            })
+                            .count();
+                        let own = Packet::new(vec![42]);
+                        let root = (network.id() == ROOT).then_some(&own);
+                        let broadcast = network.broadcast_from(ROOT, root).unwrap();
+                        (gathered, broadcast, talked)
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let (gathered, broadcast, talked) = handle.join().unwrap();
+                let gathered: Vec<&[u8]> = gathered.iter().map(Packet::as_slice).collect();
+                let expected: Vec<[u8; 2]> = (0..N_PARTIES as u8).map(|i| [i, i]).collect();
+                assert_eq!(gathered, expected, "{topology:?}");
+                assert_eq!(broadcast.as_slice(), [42], "{topology:?}");
+                // Outside of the mesh, every party only sends to its neighbours while gathering.
+                if topology == Topology::Mesh {
+                    assert_eq!(talked, N_PARTIES - 1);
+                } else {
+                    assert!(talked <= 3, "{topology:?}");
+                }
+            }
+        }
+    }
+
+    /// Creates the networks of `n_parties` connected with Unix socket pairs, whose buffers are
+    /// bounded like the ones of TCP sockets.
+    #[cfg(unix)]
+    fn create_unix(n_parties: usize) -> Vec<Network> {
+        use super::unix::UnixChannel;
+        use std::os::unix::net::UnixStream;
+
+        let mut channels: Vec<Vec<Box<dyn Channel>>> = (0..n_parties).map(|_| Vec::new()).collect();
+        for i in 0..n_parties {
+            channels[i].push(Box::new(LoopBackChannel::default()));
+            for j in i + 1..n_parties {
+                let (stream_i, stream_j) = UnixStream::pair().unwrap();
+                channels[i].push(Box::new(UnixChannel::new(stream_i)));
+                channels[j].push(Box::new(UnixChannel::new(stream_j)));
+            }
+        }
+        channels
+            .into_iter()
+            .enumerate()
+            .map(|(id, channels)| Network::from_channels(id, channels))
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exchange_large_packets() {
+        // The packets exceed the capacity of the socket buffers, so sending to every peer before
+        // receiving would block all the parties.
+        let networks = create_unix(3);
+        let handles: Vec<_> = networks
+            .into_iter()
+            .map(|mut network| {
+                thread::spawn(move || {
+                    let id = network.id() as u8;
+                    let packets = (0..3).map(|i| Packet::new(vec![id * 3 + i; 1 << 20]));
+                    network.exchange(packets.collect()).unwrap()
+                })
+            })
+            .collect();
+        for (id, handle) in handles.into_iter().enumerate() {
+            let received: Vec<u8> = handle
+                .join()
+                .unwrap()
+                .iter()
+                .map(|packet| packet.as_slice()[0])
+                .collect();
+            let expected: Vec<u8> = (0..3).map(|i| i * 3 + id as u8).collect();
+            assert_eq!(received, expected);
+        }
+    }
+
+    #[test]
+    fn packet_slices_share_the_payload() {
+        let packet = Packet::tagged(3, 1, vec![1, 2, 3, 4, 5]);
+        let slice = packet.slice(1..4);
+        assert_eq!(slice.as_slice(), &[2, 3, 4]);
+        assert_eq!(slice.header().protocol_id, 3);
+        assert_eq!(slice.slice(1..3).as_slice(), &[3, 4]);
+        assert_eq!(slice.into_payload(), vec![2, 3, 4]);
+        assert_eq!(packet.into_payload(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn packets_are_wiped_when_dropped() {
+        let payload = || [CANARY.to_le_bytes(), CANARY.to_le_bytes()].concat();
+        let leaked = freed_copies(CANARY, || {
+            let packet = Packet::new(payload());
+            let slice = packet.slice(2..12);
+            drop(packet.clone());
+            drop(packet);
+            drop(slice.into_payload());
+            // The payload that is handed out is wiped by its new owner.
+            Packet::new(payload()).slice(8..16).into_payload().zeroize();
+        });
+        assert_eq!(leaked, 0);
+    }
+
+    #[test]
+    fn queued_packets_are_wiped() {
+        let leaked = freed_copies(CANARY, || {
+            let mut loopback = LoopBackChannel::default();
+            let packet = Packet::new(CANARY.to_le_bytes().to_vec());
+            loopback.send(&packet).unwrap();
+            loopback.send(&packet).unwrap();
+            drop(loopback.recv().unwrap());
+            loopback.shutdown().unwrap();
+
+            // The packets that were never received are wiped when the queue is dropped.
+            let (mut sender, receiver) = LocalChannel::pair();
+            sender.send(&packet).unwrap();
+            drop(sender);
+            drop(receiver);
+        });
+        assert_eq!(leaked, 0);
+    }
+
+    #[test]
+    fn stats_count_traffic() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        network.send_to(&Packet::new(vec![0; 10]), 0).unwrap();
+        network.recv_from(0).unwrap();
+        network.send_to(&Packet::new(vec![0; 5]), 0).unwrap();
+
+        let stats = network.stats();
+        assert_eq!(stats.peers[0].bytes_sent, 15);
+        assert_eq!(stats.peers[0].bytes_received, 10);
+        assert_eq!(stats.peers[0].packets_sent, 2);
+        assert_eq!(stats.rounds, 2);
+    }
+
+    #[test]
+    fn parse_peer_endpoints() {
+        let json = serde_json::json!({
+            "peers": [
+                { "host": "127.0.0.1", "port": 6000 },
+                { "host": "party1.example.com", "port": 7000, "bind": "0.0.0.0:7000" },
+            ]
+        });
+        let peers = PeerEndpoint::parse_all(&json).unwrap();
+        assert_eq!(peers[0].port, 6000);
+        assert_eq!(peers[1].host, "party1.example.com");
+        assert_eq!(peers[1].bind.as_deref(), Some("0.0.0.0:7000"));
+
+        let json = serde_json::json!({
+            "peers": ["10.0.0.1:6000", "party1.example.com:7000", "[::1]:6002"]
+        });
+        let peers = PeerEndpoint::parse_all(&json).unwrap();
+        assert_eq!(peers[1].host, "party1.example.com");
+        assert_eq!(peers[1].port, 7000);
+        assert_eq!(peers[2].host, "::1");
+        assert_eq!(peers[2].address(), "[::1]:6002");
+        for endpoint in ["::1:6000", "host", "host:port", ":6000", "[::1:6000"] {
+            assert!(endpoint.parse::<PeerEndpoint>().is_err(), "{endpoint}");
+        }
+
+        let json = serde_json::json!({
+            "base_port": 5000,
+            "peer_ips": ["127.0.0.1", "::1"],
+        });
+        let peers = PeerEndpoint::parse_all(&json).unwrap();
+        assert_eq!(peers[1].host, "::1");
+        assert_eq!(peers[1].port, 5001);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn recv_into_reuses_the_buffer() {
+        let mut networks = create_unix(2);
+        networks[1]
+            .send_to(&Packet::tagged(2, 5, vec![1, 2, 3]), 0)
+            .unwrap();
+        networks[1].send_to(&Packet::new(vec![4]), 0).unwrap();
+        networks[1].flush().unwrap();
+
+        let mut buffer = Vec::with_capacity(16);
+        let header = networks[0].recv_from_into(1, &mut buffer).unwrap();
+        assert!(header.matches(2, 5));
+        assert_eq!(buffer, vec![1, 2, 3]);
+        let capacity = buffer.capacity();
+        networks[0].recv_from_into(1, &mut buffer).unwrap();
+        assert_eq!(buffer, vec![4]);
+        assert_eq!(buffer.capacity(), capacity);
+        assert_eq!(networks[0].stats().peers[1].bytes_received, 4);
+    }
+
+    #[test]
+    fn streams_skip_other_packets() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        network.send_to(&Packet::new(vec![9]), 0).unwrap();
+        let payload = vec![7; 1000];
+        network
+            .send_stream_to(&mut payload.as_slice(), 300, 0)
+            .unwrap();
+
+        let mut received = Vec::new();
+        assert_eq!(network.recv_stream_from(&mut received, 0).unwrap(), 1000);
+        assert_eq!(received, payload);
+        assert_eq!(network.recv_from(0).unwrap().as_slice(), &[9]);
+    }
+
+    #[test]
+    fn values_are_checked_against_their_type() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        network.send_value(&vec![1u32, 2, 3], 0).unwrap();
+        assert_eq!(network.recv_value::<Vec<u32>>(0).unwrap(), vec![1, 2, 3]);
+
+        network.send_value(&1u32, 0).unwrap();
+        assert!(network.recv_value::<u64>(0).is_err());
+    }
+
+    #[test]
+    fn reject_unexpected_peer_ids() {
+        let accept_ids = |ids: Vec<usize>| {
+            let mut ids = ids.into_iter();
+            move || -> anyhow::Result<(Box<dyn Channel>, usize)> {
+                Ok((Box::new(LoopBackChannel::default()), ids.next().unwrap()))
+            }
+        };
+        let connect =
+            |_| -> anyhow::Result<Box<dyn Channel>> { Ok(Box::new(LoopBackChannel::default())) };
+
+        assert!(Network::connect_all(1, 4, connect, accept_ids(vec![3, 2])).is_ok());
+        for ids in [vec![2, 2], vec![0, 2], vec![1, 2], vec![4, 2]] {
+            assert!(Network::connect_all(1, 4, connect, accept_ids(ids)).is_err());
+        }
+    }
+
+    #[test]
+    fn reject_packets_delivered_twice() {
+        let faults = Faults {
+            duplicate: 1.0,
+            ..Default::default()
+        };
+        let channel = FaultyChannel::new(LoopBackChannel::default(), faults, 0);
+        let mut network = Network::from_channels(0, vec![Box::new(channel)]);
+        network.send_to(&Packet::new(vec![1]), 0).unwrap();
+        assert_eq!(network.recv_from(0).unwrap().as_slice(), &[1]);
+        let err = network.recv_from(0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SequenceError>(),
+            Some(SequenceError::Duplicate {
+                party_id: 0,
+                seq: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn sessions_are_isolated() {
+        let mut networks = Network::create_local(2);
+        let mut network_1 = networks.pop().unwrap();
+        let mut network_0 = networks.pop().unwrap();
+        network_1.send_to(&Packet::new(vec![0]), 0).unwrap();
+        network_1.send_to(&Packet::new(vec![9]), 0).unwrap();
+        network_1.start_session(1).unwrap();
+        network_1.send_to(&Packet::new(vec![1]), 0).unwrap();
+
+        assert_eq!(network_0.recv_from(1).unwrap().as_slice(), &[0]);
+        assert_eq!(
+            network_0.try_recv_from(1).unwrap().unwrap().as_slice(),
+            &[9]
+        );
+        assert!(network_0.try_recv_from(1).unwrap().is_none());
+        network_0.start_session(1).unwrap();
+        assert_eq!(network_0.recv_from(1).unwrap().as_slice(), &[1]);
+        assert!(network_0.start_session(1).is_err());
+
+        network_1.send_to(&Packet::new(vec![2]), 0).unwrap();
+        network_1.start_session(2).unwrap();
+        network_1.send_to(&Packet::new(vec![3]), 0).unwrap();
+        network_0.start_session(2).unwrap();
+        assert_eq!(network_0.recv_from(1).unwrap().as_slice(), &[3]);
+        assert_eq!(network_0.session(), 2);
+    }
+
+    #[test]
+    fn recv_tagged_rejects_stale_rounds() {
+        let mut network = Network::from_channels(0, vec![Box::new(LoopBackChannel::default())]);
+        network.send_to(&Packet::tagged(1, 0, vec![0]), 0).unwrap();
+        assert!(network.recv_tagged(0, 1, 1).is_err());
     }
 }
@@ -0,0 +1,706 @@
+use std::{
+    fs,
+    io::{self, Error, ErrorKind, Read, Write},
+    net::TcpStream,
+    os::fd::{AsRawFd, RawFd},
+    time::Duration,
+};
+
+use aws_lc_rs::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305},
+    agreement::{self, PrivateKey, UnparsedPublicKey, X25519},
+    digest::{self, SHA256},
+    encoding::{AsBigEndian, Curve25519SeedBin},
+    error::Unspecified,
+    hmac,
+};
+use serde_json::Value;
+use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+
+use super::channel::{PacketStream, StreamChannel};
+use crate::hex::decode_hex;
+
+/// Name of the Noise protocol used in the handshake.
+const PROTOCOL_NAME: &[u8] = b"Noise_XX_25519_ChaChaPoly_SHA256";
+
+/// Prologue of the handshake, which binds the handshake to this application.
+const PROLOGUE: &[u8] = b"shami-rs";
+
+/// Size in bytes of the keys and of the output of the hash function.
+const KEY_SIZE: usize = 32;
+
+/// Size in bytes of the authentication tag of the AEAD cipher.
+const TAG_SIZE: usize = 16;
+
+/// Maximum size in bytes of a Noise message.
+const MAX_MESSAGE_SIZE: usize = u16::MAX as usize;
+
+/// Size in bytes of the length prefix of a Noise message in the wire.
+const FRAME_PREFIX_SIZE: usize = 2;
+
+/// Size in bytes of the chunks read from the stream.
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Possible errors that may appear in a Noise channel.
+#[derive(Debug, Error)]
+pub enum NoiseError {
+    /// A key is not a valid X25519 key.
+    #[error("invalid X25519 key")]
+    InvalidKey,
+
+    /// A message could not be decrypted, either because it was tampered with or because the
+    /// parties do not share the same keys.
+    #[error("the message could not be decrypted")]
+    Decryption,
+
+    /// A handshake message does not have the expected size.
+    #[error("the handshake message has {0} bytes, which is not the expected size")]
+    InvalidHandshakeMessage(usize),
+
+    /// The static key of the remote party is not listed in the configuration.
+    #[error("the static key of the remote party is not listed in the configuration")]
+    UnknownStaticKey,
+
+    /// The static key of the remote party belongs to a different party than expected.
+    #[error(
+        "expected to connect with party {expected}, but the static key belongs to party {found}"
+    )]
+    UnexpectedParty { expected: usize, found: usize },
+}
+
+impl From<Unspecified> for NoiseError {
+    fn from(_: Unspecified) -> Self {
+        Self::InvalidKey
+    }
+}
+
+/// Static keys of the parties that use the transport based on the Noise protocol framework, which
+/// is a lighter alternative to TLS. The parties authenticate each other with these keys in a
+/// `Noise_XX_25519_ChaChaPoly_SHA256` handshake, and the packets are encrypted with the resulting
+/// transport keys.
+pub struct NoiseConfig {
+    /// Static private key of the party.
+    private_key: PrivateKey,
+    /// Static public key of each party, indexed by party ID.
+    peer_public_keys: Vec<[u8; KEY_SIZE]>,
+}
+
+impl NoiseConfig {
+    /// Creates the configuration from the static private key of the party and the static public
+    /// keys of all the parties.
+    pub fn new(
+        private_key: &[u8],
+        peer_public_keys: Vec<[u8; KEY_SIZE]>,
+    ) -> Result<Self, NoiseError> {
+        Ok(Self {
+            private_key: PrivateKey::from_private_key(&X25519, private_key)
+                .map_err(|_| NoiseError::InvalidKey)?,
+            peer_public_keys,
+        })
+    }
+
+    /// Reads the configuration from the `noise` object of the network configuration for a network
+    /// with the given number of parties. The object contains the path of a file with the private
+    /// key in hexadecimal in `private_key`, and the public key of each party in hexadecimal in
+    /// `peer_public_keys`.
+    pub(crate) fn from_json(json: &Value, n_parties: usize) -> anyhow::Result<Self> {
+        let private_key_path = json["private_key"].as_str().ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "the path of the Noise private key is not correct",
+        ))?;
+        // Both the contents of the file and the decoded key are wiped when they are dropped.
+        let private_key_hex = Zeroizing::new(fs::read_to_string(private_key_path)?);
+        let private_key = Zeroizing::new(decode_hex(private_key_hex.trim()).ok_or(Error::new(
+            ErrorKind::InvalidData,
+            format!("the Noise private key in {private_key_path} is not correct"),
+        ))?);
+
+        let public_keys_json = json["peer_public_keys"].as_array().ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "the array of Noise public keys is not correct",
+        ))?;
+        let mut peer_public_keys = Vec::new();
+        for public_key in public_keys_json {
+            let public_key = public_key
+                .as_str()
+                .and_then(decode_hex)
+                .and_then(|public_key| public_key.try_into().ok())
+                .ok_or(Error::new(
+                    ErrorKind::InvalidInput,
+                    "the Noise public key of a peer is not correct",
+                ))?;
+            peer_public_keys.push(public_key);
+        }
+        if peer_public_keys.len() != n_parties {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the number of Noise public keys does not match the number of peers",
+            )
+            .into());
+        }
+
+        Ok(Self::new(&private_key, peer_public_keys)?)
+    }
+
+    /// Returns the static public key of the party.
+    fn public_key(&self) -> Result<[u8; KEY_SIZE], NoiseError> {
+        public_key_bytes(&self.private_key)
+    }
+
+    /// Returns the ID of the party that owns the given static public key.
+    fn identify(&self, public_key: &[u8]) -> Result<usize, NoiseError> {
+        self.peer_public_keys
+            .iter()
+            .position(|peer_key| peer_key.as_slice() == public_key)
+            .ok_or(NoiseError::UnknownStaticKey)
+    }
+}
+
+/// Returns the public key associated with an X25519 private key.
+fn public_key_bytes(private_key: &PrivateKey) -> Result<[u8; KEY_SIZE], NoiseError> {
+    let public_key = private_key.compute_public_key()?;
+    public_key
+        .as_ref()
+        .try_into()
+        .map_err(|_| NoiseError::InvalidKey)
+}
+
+/// Generates a new X25519 key pair and returns the private and the public key.
+pub fn generate_keypair() -> Result<([u8; KEY_SIZE], [u8; KEY_SIZE]), NoiseError> {
+    let private_key = PrivateKey::generate(&X25519)?;
+    let seed: Curve25519SeedBin = private_key.as_be_bytes()?;
+    let private_bytes = seed
+        .as_ref()
+        .try_into()
+        .map_err(|_| NoiseError::InvalidKey)?;
+    Ok((private_bytes, public_key_bytes(&private_key)?))
+}
+
+/// Computes the Diffie-Hellman shared secret between a private key and a public key.
+fn dh(private_key: &PrivateKey, public_key: &[u8]) -> Result<Vec<u8>, NoiseError> {
+    agreement::agree(
+        private_key,
+        &UnparsedPublicKey::new(&X25519, public_key),
+        NoiseError::InvalidKey,
+        |secret| Ok(secret.to_vec()),
+    )
+}
+
+/// Computes the HMAC-SHA256 of the concatenation of the given inputs.
+fn hmac_sha256(key: &[u8], inputs: &[&[u8]]) -> [u8; KEY_SIZE] {
+    let mut context = hmac::Context::with_key(&hmac::Key::new(hmac::HMAC_SHA256, key));
+    for input in inputs {
+        context.update(input);
+    }
+    let mut output = [0; KEY_SIZE];
+    output.copy_from_slice(context.sign().as_ref());
+    output
+}
+
+/// Derives two keys from a chaining key and some input key material as defined by the Noise
+/// specification.
+fn hkdf(chaining_key: &[u8], input_key_material: &[u8]) -> ([u8; KEY_SIZE], [u8; KEY_SIZE]) {
+    let temp_key = hmac_sha256(chaining_key, &[input_key_material]);
+    let output1 = hmac_sha256(&temp_key, &[&[1]]);
+    let output2 = hmac_sha256(&temp_key, &[&output1, &[2]]);
+    (output1, output2)
+}
+
+/// State of the encryption in one direction of the communication.
+struct CipherState {
+    /// Key of the cipher, if it was already initialized.
+    key: Option<LessSafeKey>,
+    /// Counter used as the nonce of the next message.
+    nonce: u64,
+}
+
+impl CipherState {
+    /// Creates a cipher state without a key.
+    fn empty() -> Self {
+        Self {
+            key: None,
+            nonce: 0,
+        }
+    }
+
+    /// Creates a cipher state with the given key.
+    fn with_key(key: &[u8; KEY_SIZE]) -> Self {
+        Self {
+            key: Some(LessSafeKey::new(
+                UnboundKey::new(&CHACHA20_POLY1305, key).expect("the key has the correct size"),
+            )),
+            nonce: 0,
+        }
+    }
+
+    /// Returns the nonce of the next message, which contains the counter in little-endian after
+    /// four zero bytes.
+    fn next_nonce(&mut self) -> Nonce {
+        let mut nonce = [0; 12];
+        nonce[4..].copy_from_slice(&self.nonce.to_le_bytes());
+        self.nonce += 1;
+        Nonce::assume_unique_for_key(nonce)
+    }
+
+    /// Replaces the key by the first bytes of the encryption of zeros with the maximum nonce, as
+    /// the `REKEY` function of the Noise specification. The counter of the nonces is kept.
+    fn rekey(&mut self) {
+        if let Some(key) = &self.key {
+            let mut nonce = [0; 12];
+            nonce[4..].copy_from_slice(&u64::MAX.to_le_bytes());
+            let mut in_out = vec![0; KEY_SIZE];
+            key.seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut in_out,
+            )
+            .expect("the key fits in the cipher");
+            let mut new_key = [0; KEY_SIZE];
+            new_key.copy_from_slice(&in_out[..KEY_SIZE]);
+            *self = Self {
+                nonce: self.nonce,
+                ..Self::with_key(&new_key)
+            };
+            new_key.zeroize();
+            in_out.zeroize();
+        }
+    }
+
+    /// Encrypts a plaintext using the associated data. If there is no key, the plaintext is
+    /// returned as it is.
+    fn encrypt_with_ad(&mut self, ad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let mut in_out = plaintext.to_vec();
+        if self.key.is_some() {
+            let nonce = self.next_nonce();
+            if let Some(key) = &self.key {
+                key.seal_in_place_append_tag(nonce, Aad::from(ad), &mut in_out)
+                    .expect("the message fits in the cipher");
+            }
+        }
+        in_out
+    }
+
+    /// Decrypts a ciphertext using the associated data. If there is no key, the ciphertext is
+    /// returned as it is.
+    fn decrypt_with_ad(&mut self, ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let mut in_out = ciphertext.to_vec();
+        if self.key.is_some() {
+            let nonce = self.next_nonce();
+            if let Some(key) = &self.key {
+                let plaintext_size = key
+                    .open_in_place(nonce, Aad::from(ad), &mut in_out)
+                    .map_err(|_| NoiseError::Decryption)?
+                    .len();
+                in_out.truncate(plaintext_size);
+            }
+        }
+        Ok(in_out)
+    }
+}
+
+/// Symmetric state of the handshake.
+struct SymmetricState {
+    /// Chaining key, from which the keys are derived.
+    chaining_key: [u8; KEY_SIZE],
+    /// Hash of all the handshake data sent and received.
+    hash: [u8; KEY_SIZE],
+    /// Cipher used to encrypt the handshake messages.
+    cipher: CipherState,
+}
+
+impl SymmetricState {
+    /// Initializes the symmetric state with the protocol name and the prologue.
+    fn new() -> Self {
+        // The protocol name has the same size as the hash, so it is used directly.
+        let mut hash = [0; KEY_SIZE];
+        hash.copy_from_slice(PROTOCOL_NAME);
+        let mut state = Self {
+            chaining_key: hash,
+            hash,
+            cipher: CipherState::empty(),
+        };
+        state.mix_hash(PROLOGUE);
+        state
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        let mut context = digest::Context::new(&SHA256);
+        context.update(&self.hash);
+        context.update(data);
+        self.hash.copy_from_slice(context.finish().as_ref());
+    }
+
+    fn mix_key(&mut self, input_key_material: &[u8]) {
+        let (chaining_key, key) = hkdf(&self.chaining_key, input_key_material);
+        self.chaining_key = chaining_key;
+        self.cipher = CipherState::with_key(&key);
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let ciphertext = self.cipher.encrypt_with_ad(&self.hash, plaintext);
+        self.mix_hash(&ciphertext);
+        ciphertext
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let plaintext = self.cipher.decrypt_with_ad(&self.hash, ciphertext)?;
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    /// Derives the ciphers of the transport. The first one encrypts the messages of the
+    /// initiator and the second one the messages of the responder.
+    fn split(&self) -> (CipherState, CipherState) {
+        let (initiator_key, responder_key) = hkdf(&self.chaining_key, &[]);
+        (
+            CipherState::with_key(&initiator_key),
+            CipherState::with_key(&responder_key),
+        )
+    }
+}
+
+/// Writes a Noise message prefixed with its length.
+fn write_message(stream: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(FRAME_PREFIX_SIZE + message.len());
+    frame.extend((message.len() as u16).to_be_bytes());
+    frame.extend_from_slice(message);
+    stream.write_all(&frame)
+}
+
+/// Reads a Noise message prefixed with its length.
+fn read_message(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut prefix = [0; FRAME_PREFIX_SIZE];
+    stream.read_exact(&mut prefix)?;
+    let mut message = vec![0; u16::from_be_bytes(prefix) as usize];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
+
+/// Checks that a handshake message has the expected size.
+fn check_message_size(message: &[u8], expected: usize) -> Result<(), NoiseError> {
+    if message.len() != expected {
+        return Err(NoiseError::InvalidHandshakeMessage(message.len()));
+    }
+    Ok(())
+}
+
+/// Runs the handshake as the initiator and returns the ciphers to send and to receive, and the
+/// static public key of the responder.
+fn handshake_initiator<S: Read + Write>(
+    stream: &mut S,
+    config: &NoiseConfig,
+) -> anyhow::Result<(CipherState, CipherState, Vec<u8>)> {
+    let mut state = SymmetricState::new();
+    let ephemeral = PrivateKey::generate(&X25519).map_err(NoiseError::from)?;
+
+    // -> e
+    let ephemeral_public = public_key_bytes(&ephemeral)?;
+    state.mix_hash(&ephemeral_public);
+    let mut message = ephemeral_public.to_vec();
+    message.extend(state.encrypt_and_hash(&[]));
+    write_message(stream, &message)?;
+
+    // <- e, ee, s, es
+    let message = read_message(stream)?;
+    check_message_size(&message, KEY_SIZE + KEY_SIZE + TAG_SIZE + TAG_SIZE)?;
+    let (remote_ephemeral, rest) = message.split_at(KEY_SIZE);
+    state.mix_hash(remote_ephemeral);
+    state.mix_key(&dh(&ephemeral, remote_ephemeral)?);
+    let (encrypted_static, payload) = rest.split_at(KEY_SIZE + TAG_SIZE);
+    let remote_static = state.decrypt_and_hash(encrypted_static)?;
+    state.mix_key(&dh(&ephemeral, &remote_static)?);
+    state.decrypt_and_hash(payload)?;
+
+    // -> s, se
+    let mut message = state.encrypt_and_hash(&config.public_key()?);
+    state.mix_key(&dh(&config.private_key, remote_ephemeral)?);
+    message.extend(state.encrypt_and_hash(&[]));
+    write_message(stream, &message)?;
+
+    let (send_cipher, recv_cipher) = state.split();
+    Ok((send_cipher, recv_cipher, remote_static))
+}
+
+/// Runs the handshake as the responder and returns the ciphers to send and to receive, and the
+/// static public key of the initiator.
+fn handshake_responder<S: Read + Write>(
+    stream: &mut S,
+    config: &NoiseConfig,
+) -> anyhow::Result<(CipherState, CipherState, Vec<u8>)> {
+    let mut state = SymmetricState::new();
+    let ephemeral = PrivateKey::generate(&X25519).map_err(NoiseError::from)?;
+
+    // -> e
+    let message = read_message(stream)?;
+    check_message_size(&message, KEY_SIZE)?;
+    let remote_ephemeral = message;
+    state.mix_hash(&remote_ephemeral);
+    state.decrypt_and_hash(&[])?;
+
+    // <- e, ee, s, es
+    let ephemeral_public = public_key_bytes(&ephemeral)?;
+    state.mix_hash(&ephemeral_public);
+    let mut message = ephemeral_public.to_vec();
+    state.mix_key(&dh(&ephemeral, &remote_ephemeral)?);
+    message.extend(state.encrypt_and_hash(&config.public_key()?));
+    state.mix_key(&dh(&config.private_key, &remote_ephemeral)?);
+    message.extend(state.encrypt_and_hash(&[]));
+    write_message(stream, &message)?;
+
+    // -> s, se
+    let message = read_message(stream)?;
+    check_message_size(&message, KEY_SIZE + TAG_SIZE + TAG_SIZE)?;
+    let (encrypted_static, payload) = message.split_at(KEY_SIZE + TAG_SIZE);
+    let remote_static = state.decrypt_and_hash(encrypted_static)?;
+    state.mix_key(&dh(&ephemeral, &remote_static)?);
+    state.decrypt_and_hash(payload)?;
+
+    let (recv_cipher, send_cipher) = state.split();
+    Ok((send_cipher, recv_cipher, remote_static))
+}
+
+/// TCP stream encrypted with the transport keys of a Noise handshake. The bytes written are split
+/// into as many Noise messages as needed. A message with an empty payload announces that the
+/// sender rotated its key right after it, so the receiver rotates the key with which it decrypts
+/// the following messages.
+pub struct NoiseStream {
+    /// TCP stream connected to the remote party.
+    stream: TcpStream,
+    /// Cipher used to encrypt the messages sent.
+    send_cipher: CipherState,
+    /// Cipher used to decrypt the messages received.
+    recv_cipher: CipherState,
+    /// Encrypted bytes received from the stream that do not form a complete message yet.
+    message_buffer: Vec<u8>,
+    /// Decrypted bytes that were not read yet.
+    plaintext: Vec<u8>,
+}
+
+/// Channel that sends packets to a remote party through a [`NoiseStream`].
+pub type NoiseChannel = StreamChannel<NoiseStream>;
+
+impl NoiseChannel {
+    /// Connects with the party `remote_id` as the initiator of the handshake through a TCP
+    /// stream that is already connected.
+    pub fn connect(
+        mut stream: TcpStream,
+        remote_id: usize,
+        config: &NoiseConfig,
+    ) -> anyhow::Result<Self> {
+        let (send_cipher, recv_cipher, remote_static) = handshake_initiator(&mut stream, config)?;
+        let found = config.identify(&remote_static)?;
+        if found != remote_id {
+            anyhow::bail!(NoiseError::UnexpectedParty {
+                expected: remote_id,
+                found,
+            });
+        }
+        log::info!("established Noise session with party {remote_id}");
+        Ok(Self::new(NoiseStream::new(
+            stream,
+            send_cipher,
+            recv_cipher,
+        )))
+    }
+
+    /// Runs the handshake as the responder through a TCP stream that was accepted from a remote
+    /// party. Returns the channel and the ID of the remote party, which is derived from its static
+    /// key.
+    pub fn accept(mut stream: TcpStream, config: &NoiseConfig) -> anyhow::Result<(Self, usize)> {
+        let socket = stream.peer_addr()?;
+        stream.set_nonblocking(false)?;
+        let (send_cipher, recv_cipher, remote_static) = handshake_responder(&mut stream, config)?;
+        let remote_id = config.identify(&remote_static)?;
+        log::info!("established Noise session with party {remote_id} from {socket:?}");
+        let stream = NoiseStream::new(stream, send_cipher, recv_cipher);
+        Ok((Self::new(stream), remote_id))
+    }
+}
+
+impl NoiseStream {
+    fn new(stream: TcpStream, send_cipher: CipherState, recv_cipher: CipherState) -> Self {
+        Self {
+            stream,
+            send_cipher,
+            recv_cipher,
+            message_buffer: Vec::new(),
+            plaintext: Vec::new(),
+        }
+    }
+
+    /// Decrypts the first message of the message buffer into the plaintext if it is complete.
+    /// Returns whether a message was decrypted.
+    fn decrypt_message(&mut self) -> io::Result<bool> {
+        if self.message_buffer.len() < FRAME_PREFIX_SIZE {
+            return Ok(false);
+        }
+        let size = u16::from_be_bytes([self.message_buffer[0], self.message_buffer[1]]);
+        let end = FRAME_PREFIX_SIZE + size as usize;
+        if self.message_buffer.len() < end {
+            return Ok(false);
+        }
+        let mut plaintext = self
+            .recv_cipher
+            .decrypt_with_ad(&[], &self.message_buffer[FRAME_PREFIX_SIZE..end])
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        if plaintext.is_empty() {
+            self.recv_cipher.rekey();
+        }
+        self.plaintext.extend_from_slice(&plaintext);
+        plaintext.zeroize();
+        self.message_buffer.drain(..end);
+        Ok(true)
+    }
+}
+
+impl Read for NoiseStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if !self.plaintext.is_empty() {
+                let size = buf.len().min(self.plaintext.len());
+                buf[..size].copy_from_slice(&self.plaintext[..size]);
+                self.plaintext[..size].zeroize();
+                self.plaintext.drain(..size);
+                return Ok(size);
+            }
+            if self.decrypt_message()? {
+                continue;
+            }
+            let mut chunk = [0; READ_CHUNK_SIZE];
+            let read_bytes = self.stream.read(&mut chunk)?;
+            if read_bytes == 0 {
+                return Ok(0);
+            }
+            self.message_buffer.extend_from_slice(&chunk[..read_bytes]);
+        }
+    }
+}
+
+impl Write for NoiseStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut frames = Vec::new();
+        for chunk in buf.chunks(MAX_MESSAGE_SIZE - TAG_SIZE) {
+            let message = self.send_cipher.encrypt_with_ad(&[], chunk);
+            write_message(&mut frames, &message)?;
+        }
+        self.stream.write_all(&frames)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl PacketStream for NoiseStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        self.stream.set_nonblocking(nonblocking)
+    }
+
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.stream.shutdown(std::net::Shutdown::Write)?;
+        Ok(())
+    }
+
+    fn raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        let mut frame = Vec::new();
+        write_message(&mut frame, &self.send_cipher.encrypt_with_ad(&[], &[]))?;
+        self.stream.write_all(&frame)?;
+        self.send_cipher.rekey();
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::{generate_keypair, NoiseChannel, NoiseConfig};
+    use crate::net::{channel::Channel, Packet};
+
+    /// Creates the configurations of two parties that know each other's public key.
+    fn configs() -> (NoiseConfig, NoiseConfig) {
+        let (private_0, public_0) = generate_keypair().unwrap();
+        let (private_1, public_1) = generate_keypair().unwrap();
+        (
+            NoiseConfig::new(&private_0, vec![public_0, public_1]).unwrap(),
+            NoiseConfig::new(&private_1, vec![public_0, public_1]).unwrap(),
+        )
+    }
+
+    #[test]
+    fn noise_channel_roundtrip() {
+        let (config_0, config_1) = configs();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let responder = thread::spawn(move || {
+            let (mut channel, remote_id) =
+                NoiseChannel::accept(listener.accept().unwrap().0, &config_0).unwrap();
+            assert_eq!(remote_id, 1);
+            let packet = channel.recv().unwrap();
+            channel.send(&packet).unwrap();
+            channel.flush().unwrap();
+        });
+
+        let stream = std::net::TcpStream::connect(address).unwrap();
+        let mut channel = NoiseChannel::connect(stream, 0, &config_1).unwrap();
+        // The packet is larger than a Noise message, so it is split.
+        let payload: Vec<u8> = (0..200_000).map(|i| i as u8).collect();
+        channel.send(&Packet::new(payload.clone())).unwrap();
+        assert_eq!(channel.recv().unwrap().as_slice(), payload.as_slice());
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn noise_channel_rotates_the_keys() {
+        let (config_0, config_1) = configs();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let responder = thread::spawn(move || {
+            let (mut channel, _) =
+                NoiseChannel::accept(listener.accept().unwrap().0, &config_0).unwrap();
+            for _ in 0..3 {
+                let packet = channel.recv().unwrap();
+                assert!(channel.rekey().unwrap());
+                channel.send(&packet).unwrap();
+            }
+            channel.flush().unwrap();
+        });
+
+        let stream = std::net::TcpStream::connect(address).unwrap();
+        let mut channel = NoiseChannel::connect(stream, 0, &config_1).unwrap();
+        for i in 0..3 {
+            channel.send(&Packet::new(vec![i])).unwrap();
+            // The packets sent before the rotation are decrypted with the previous key.
+            assert!(channel.rekey().unwrap());
+            assert_eq!(channel.recv().unwrap().as_slice(), &[i]);
+        }
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn noise_rejects_unknown_key() {
+        let (config_0, _) = configs();
+        let (config_1, _) = configs();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let responder = thread::spawn(move || {
+            NoiseChannel::accept(listener.accept().unwrap().0, &config_0).is_err()
+        });
+        let stream = std::net::TcpStream::connect(address).unwrap();
+        assert!(NoiseChannel::connect(stream, 0, &config_1).is_err());
+        assert!(responder.join().unwrap());
+    }
+}
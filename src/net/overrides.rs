@@ -0,0 +1,195 @@
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+/// Prefix of the environment variables that override the settings of the network configuration.
+pub const ENV_PREFIX: &str = "SHAMI_CONFIG_";
+
+/// Separator of the levels of a key in the name of an environment variable.
+const ENV_SEPARATOR: &str = "__";
+
+/// Possible errors that may appear while overriding the network configuration.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum OverrideError {
+    /// The override is not given as `KEY=VALUE`.
+    #[error("the override {0} does not have the form KEY=VALUE")]
+    MissingValue(String),
+
+    /// The key is empty or has an empty level.
+    #[error("the key {0} of the override is not correct")]
+    InvalidKey(String),
+
+    /// The key goes through a setting that is not an object nor an array, or through an array
+    /// index that does not exist.
+    #[error("the key {0} of the override does not match the configuration")]
+    KeyMismatch(String),
+}
+
+/// Setting of the network configuration that replaces the one of the file, given by the
+/// environment or the command line so that each node of a deployment can share the same file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Override {
+    /// Levels of the key of the setting. A level that is a number indexes an array.
+    key: Vec<String>,
+    /// Value of the setting.
+    value: Value,
+}
+
+impl Override {
+    /// Creates an override from a key, whose levels are separated by dots, and a value. The value
+    /// is read as JSON, or as a string if it is not valid JSON, so `5000` is a number while
+    /// `127.0.0.1` is a string.
+    pub fn new(key: &str, value: &str) -> Result<Self, OverrideError> {
+        Self::with_levels(key.split('.'), key, value)
+    }
+
+    /// Creates an override from an environment variable, or returns `None` if the name of the
+    /// variable does not start with [`ENV_PREFIX`]. The rest of the name is the key in
+    /// uppercase, with its levels separated by `__`, e.g. `SHAMI_CONFIG_RECONNECT__MAX_ATTEMPTS`.
+    pub fn from_env_var(name: &str, value: &str) -> Option<Result<Self, OverrideError>> {
+        let key = name.strip_prefix(ENV_PREFIX)?.to_lowercase();
+        Some(Self::with_levels(key.split(ENV_SEPARATOR), name, value))
+    }
+
+    /// Creates an override from the levels of its key.
+    fn with_levels<'a>(
+        levels: impl Iterator<Item = &'a str>,
+        key: &str,
+        value: &str,
+    ) -> Result<Self, OverrideError> {
+        let levels: Vec<String> = levels.map(str::to_string).collect();
+        if levels.iter().any(String::is_empty) {
+            return Err(OverrideError::InvalidKey(key.to_string()));
+        }
+        Ok(Self {
+            key: levels,
+            value: serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string())),
+        })
+    }
+
+    /// Replaces the setting in the JSON of a network configuration. The objects in the key that
+    /// are missing are created, but the indexes of the arrays must exist.
+    pub fn apply(&self, json: &mut Value) -> Result<(), OverrideError> {
+        let mismatch = || OverrideError::KeyMismatch(self.key.join("."));
+        let mut current = json;
+        for level in &self.key {
+            if current.is_null() {
+                *current = Value::Object(Map::new());
+            }
+            current = match current {
+                Value::Object(map) => map.entry(level.as_str()).or_insert(Value::Null),
+                Value::Array(array) => level
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| array.get_mut(index))
+                    .ok_or_else(mismatch)?,
+                _ => return Err(mismatch()),
+            };
+        }
+        *current = self.value.clone();
+        Ok(())
+    }
+}
+
+impl FromStr for Override {
+    type Err = OverrideError;
+
+    /// Parses an override given as `KEY=VALUE`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| OverrideError::MissingValue(s.to_string()))?;
+        Self::new(key, value)
+    }
+}
+
+/// Reads the overrides from the given environment variables, ignoring the ones without
+/// [`ENV_PREFIX`]. The overrides are sorted by the name of their variable, so they are applied
+/// in the same order regardless of the order of the environment.
+pub fn from_env(
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<Vec<Override>, OverrideError> {
+    let mut vars: Vec<(String, String)> = vars.into_iter().collect();
+    vars.sort();
+    vars.iter()
+        .filter_map(|(name, value)| Override::from_env_var(name, value))
+        .collect()
+}
+
+/// Applies the overrides to the JSON of a network configuration in order, so a later override
+/// of the same setting wins.
+pub fn apply_all(json: &mut Value, overrides: &[Override]) -> Result<(), OverrideError> {
+    overrides
+        .iter()
+        .try_for_each(|override_| override_.apply(json))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{apply_all, from_env, Override, OverrideError};
+
+    #[test]
+    fn parse_overrides() {
+        let timeout: Override = "timeout=10000".parse().unwrap();
+        assert_eq!(timeout, Override::new("timeout", "10000").unwrap());
+        assert_eq!(
+            "timeout".parse::<Override>(),
+            Err(OverrideError::MissingValue("timeout".to_string()))
+        );
+        assert_eq!(
+            "reconnect..max_attempts=1".parse::<Override>(),
+            Err(OverrideError::InvalidKey(
+                "reconnect..max_attempts".to_string()
+            ))
+        );
+
+        let vars = [
+            ("SHAMI_CONFIG_TIMEOUT", "10000"),
+            ("HOME", "/root"),
+            ("SHAMI_CONFIG_PEER_IPS__1", "10.0.0.2"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        assert_eq!(
+            from_env(vars).unwrap(),
+            vec![
+                Override::new("peer_ips.1", "10.0.0.2").unwrap(),
+                Override::new("timeout", "10000").unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_overrides_in_order() {
+        let mut config = json!({
+            "base_port": 5000,
+            "timeout": 5000,
+            "peer_ips": ["127.0.0.1", "127.0.0.1"],
+        });
+        let overrides = [
+            Override::new("timeout", "10000").unwrap(),
+            Override::new("peer_ips.1", "10.0.0.2").unwrap(),
+            Override::new("reconnect.max_attempts", "5").unwrap(),
+            Override::new("timeout", "20000").unwrap(),
+        ];
+        apply_all(&mut config, &overrides).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "base_port": 5000,
+                "timeout": 20000,
+                "peer_ips": ["127.0.0.1", "10.0.0.2"],
+                "reconnect": { "max_attempts": 5 },
+            })
+        );
+
+        for key in ["peer_ips.2", "peer_ips.x", "timeout.ms"] {
+            assert_eq!(
+                Override::new(key, "1").unwrap().apply(&mut config),
+                Err(OverrideError::KeyMismatch(key.to_string()))
+            );
+        }
+    }
+}
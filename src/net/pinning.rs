@@ -0,0 +1,215 @@
+use std::fmt;
+
+use aws_lc_rs::digest::{self, SHA256};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{self, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    server::danger::{ClientCertVerified, ClientCertVerifier},
+    CertificateError, DigitallySignedStruct, DistinguishedName, SignatureScheme,
+};
+use thiserror::Error;
+
+use crate::hex::decode_hex;
+
+/// Size in bytes of a SHA-256 fingerprint.
+const FINGERPRINT_SIZE: usize = 32;
+
+/// Possible errors that may appear when parsing a fingerprint.
+#[derive(Debug, Error)]
+pub enum FingerprintError {
+    /// The fingerprint does not have the length of a SHA-256 digest.
+    #[error("the fingerprint has {0} bytes, but a SHA-256 fingerprint has 32 bytes")]
+    InvalidLength(usize),
+
+    /// The fingerprint contains characters that are not hexadecimal digits.
+    #[error("the fingerprint is not a hexadecimal string")]
+    InvalidHex,
+}
+
+/// SHA-256 fingerprint of a DER-encoded certificate.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint([u8; FINGERPRINT_SIZE]);
+
+impl Fingerprint {
+    /// Computes the fingerprint of a certificate.
+    pub fn of(cert: &CertificateDer) -> Self {
+        let mut fingerprint = [0; FINGERPRINT_SIZE];
+        fingerprint.copy_from_slice(digest::digest(&SHA256, cert.as_ref()).as_ref());
+        Self(fingerprint)
+    }
+
+    /// Parses a fingerprint written in hexadecimal. The bytes may be separated by colons, as in
+    /// the output of `openssl x509 -fingerprint -sha256`.
+    pub fn parse(hex: &str) -> Result<Self, FingerprintError> {
+        let bytes = decode_hex(&hex.replace(':', "")).ok_or(FingerprintError::InvalidHex)?;
+        let fingerprint = bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| FingerprintError::InvalidLength(bytes.len()))?;
+        Ok(Self(fingerprint))
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex: Vec<String> = self.0.iter().map(|byte| format!("{byte:02X}")).collect();
+        write!(f, "{}", hex.join(":"))
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Verifier that accepts a certificate only if its fingerprint is pinned. It replaces the
+/// verification against a CA in deployments without one, so the validity of the certificate is
+/// given exclusively by its fingerprint. The verifier is used both by the clients and the servers.
+#[derive(Debug)]
+pub struct PinnedCertVerifier {
+    /// Fingerprints of the accepted certificates.
+    fingerprints: Vec<Fingerprint>,
+    /// Provider used to verify the signatures of the handshake.
+    provider: CryptoProvider,
+}
+
+impl PinnedCertVerifier {
+    /// Creates a verifier that accepts the certificates with the given fingerprints.
+    pub fn new(fingerprints: Vec<Fingerprint>) -> Self {
+        Self {
+            fingerprints,
+            provider: crypto::aws_lc_rs::default_provider(),
+        }
+    }
+
+    /// Checks that the fingerprint of the certificate is pinned.
+    fn verify_fingerprint(&self, cert: &CertificateDer) -> Result<(), rustls::Error> {
+        let fingerprint = Fingerprint::of(cert);
+        if self.fingerprints.contains(&fingerprint) {
+            Ok(())
+        } else {
+            log::error!("rejecting certificate with unpinned fingerprint {fingerprint:?}");
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.verify_fingerprint(end_entity)?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+impl ClientCertVerifier for PinnedCertVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.verify_fingerprint(end_entity)?;
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        ServerCertVerifier::verify_tls12_signature(self, message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        ServerCertVerifier::verify_tls13_signature(self, message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        ServerCertVerifier::supported_verify_schemes(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustls::pki_types::CertificateDer;
+
+    use super::{Fingerprint, PinnedCertVerifier};
+
+    #[test]
+    fn parse_fingerprint() {
+        let cert = CertificateDer::from(vec![1, 2, 3]);
+        let fingerprint = Fingerprint::of(&cert);
+        let hex = format!("{fingerprint:?}");
+        assert_eq!(Fingerprint::parse(&hex).unwrap(), fingerprint);
+        assert_eq!(
+            Fingerprint::parse(&hex.replace(':', "")).unwrap(),
+            fingerprint
+        );
+        assert!(Fingerprint::parse("AB:CD").is_err());
+        assert!(Fingerprint::parse(&"ZZ".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn reject_unpinned_certificate() {
+        let pinned = CertificateDer::from(vec![1, 2, 3]);
+        let verifier = PinnedCertVerifier::new(vec![Fingerprint::of(&pinned)]);
+        assert!(verifier.verify_fingerprint(&pinned).is_ok());
+        assert!(verifier
+            .verify_fingerprint(&CertificateDer::from(vec![4, 5, 6]))
+            .is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    os::fd::{AsRawFd, RawFd},
+    time::Duration,
+};
+
+use super::{
+    backoff::Backoff,
+    channel::{self, PacketStream, StreamChannel},
+    PeerEndpoint,
+};
+
+/// Channel that sends packets to a remote party through a TCP stream without TLS. The packets
+/// travel in plain text and the parties are not authenticated, so it is INSECURE and must only be
+/// used to isolate the overhead of the cryptography when benchmarking on trusted local networks.
+pub type PlaintextChannel = StreamChannel<TcpStream>;
+
+impl PacketStream for TcpStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        TcpStream::shutdown(self, Shutdown::Write)?;
+        Ok(())
+    }
+
+    fn raw_fd(&self) -> RawFd {
+        self.as_raw_fd()
+    }
+}
+
+impl PlaintextChannel {
+    /// Connects to the remote party as a client following the backoff policy, as explained in
+    /// [`channel::connect_tcp`]. Once connected, the party sends its ID.
+    pub fn connect(
+        local_id: usize,
+        peer: &PeerEndpoint,
+        backoff: &Backoff,
+    ) -> anyhow::Result<Self> {
+        let mut stream = channel::connect_tcp(local_id, peer, backoff)?;
+        stream.write_all(&local_id.to_le_bytes())?;
+        Ok(Self::new(stream))
+    }
+
+    /// Completes a connection accepted from a remote party. Returns the channel and the ID sent by
+    /// the remote party, which is not authenticated.
+    pub fn accept(mut stream: TcpStream) -> anyhow::Result<(Self, usize)> {
+        let mut id_buffer = [0; (usize::BITS / 8) as usize];
+        stream.read_exact(&mut id_buffer)?;
+        let remote_id = usize::from_le_bytes(id_buffer);
+        log::warn!("accepted an unauthenticated plaintext connection from ID {remote_id}");
+        Ok((Self::new(stream), remote_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread, time::Duration};
+
+    use super::PlaintextChannel;
+    use crate::net::{backoff::Backoff, channel::Channel, Packet, PeerEndpoint};
+
+    #[test]
+    fn plaintext_channel_roundtrip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let peer = PeerEndpoint {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            bind: None,
+            proxy: None,
+        };
+        let client = thread::spawn(move || {
+            let backoff = Backoff::fixed(Duration::from_secs(1), Duration::from_millis(10));
+            let mut channel = PlaintextChannel::connect(2, &peer, &backoff).unwrap();
+            channel.send(&Packet::new(vec![1, 2, 3])).unwrap();
+            channel.recv().unwrap()
+        });
+
+        let (mut channel, remote_id) =
+            PlaintextChannel::accept(listener.accept().unwrap().0).unwrap();
+        assert_eq!(remote_id, 2);
+        let packet = channel.recv().unwrap();
+        channel.send(&packet).unwrap();
+        channel.flush().unwrap();
+        assert_eq!(client.join().unwrap().as_slice(), &[1, 2, 3]);
+    }
+}
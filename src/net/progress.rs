@@ -0,0 +1,364 @@
+use std::{
+    fmt,
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Width in characters of the bar of the rounds completed.
+const BAR_WIDTH: usize = 30;
+
+/// Time between the refreshes of the dashboard.
+const DASHBOARD_REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Status of the connection with a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PeerStatus {
+    /// The party itself, which does not need a connection.
+    Local,
+    /// The connection is established and the party is not waiting for the peer.
+    Connected,
+    /// The party is waiting for a packet of the peer.
+    Waiting,
+    /// The connection was closed at the end of the session.
+    Closed,
+    /// The communication with the peer failed.
+    Failed,
+}
+
+impl PeerStatus {
+    /// Returns the status encoded in an atomic value.
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => PeerStatus::Local,
+            1 => PeerStatus::Connected,
+            2 => PeerStatus::Waiting,
+            3 => PeerStatus::Closed,
+            _ => PeerStatus::Failed,
+        }
+    }
+}
+
+impl fmt::Display for PeerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PeerStatus::Local => "local",
+            PeerStatus::Connected => "connected",
+            PeerStatus::Waiting => "waiting",
+            PeerStatus::Closed => "closed",
+            PeerStatus::Failed => "failed",
+        };
+        f.pad(name)
+    }
+}
+
+/// Progress of the communication with a single peer.
+#[derive(Debug)]
+struct PeerProgress {
+    status: AtomicU8,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+/// Live progress of a party, which the network and the protocol update while a dashboard reads
+/// it from another thread.
+#[derive(Debug)]
+pub struct Progress {
+    /// Phase of the protocol in which the party is.
+    phase: Mutex<String>,
+    /// Number of rounds of the protocol completed.
+    rounds_done: AtomicUsize,
+    /// Total number of rounds of the protocol, or zero if it is not known yet.
+    rounds_total: AtomicUsize,
+    /// Progress of the communication with each peer, indexed by the ID of the peer.
+    peers: Vec<PeerProgress>,
+    /// Time at which the party started.
+    start: Instant,
+}
+
+impl Progress {
+    /// Creates the progress of the party with the given ID, connected to every peer.
+    pub fn new(id: usize, n_parties: usize) -> Self {
+        let peers = (0..n_parties)
+            .map(|party_id| PeerProgress {
+                status: AtomicU8::new(if party_id == id {
+                    PeerStatus::Local as u8
+                } else {
+                    PeerStatus::Connected as u8
+                }),
+                bytes_sent: AtomicU64::new(0),
+                bytes_received: AtomicU64::new(0),
+            })
+            .collect();
+        Self {
+            phase: Mutex::new("connected".to_string()),
+            rounds_done: AtomicUsize::new(0),
+            rounds_total: AtomicUsize::new(0),
+            peers,
+            start: Instant::now(),
+        }
+    }
+
+    /// Sets the phase of the protocol in which the party is.
+    pub fn set_phase(&self, phase: &str) {
+        *self.phase.lock().unwrap_or_else(PoisonError::into_inner) = phase.to_string();
+    }
+
+    /// Sets the number of rounds completed out of the total of the protocol.
+    pub fn set_rounds(&self, done: usize, total: usize) {
+        self.rounds_done.store(done, Ordering::Relaxed);
+        self.rounds_total.store(total, Ordering::Relaxed);
+    }
+
+    /// Sets the status of the connection with a peer. The status of the party itself is kept.
+    pub(crate) fn set_status(&self, party_id: usize, status: PeerStatus) {
+        let peer = &self.peers[party_id];
+        let _ = peer
+            .status
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+                (current != PeerStatus::Local as u8).then_some(status as u8)
+            });
+    }
+
+    /// Records the bytes sent to a peer.
+    pub(crate) fn record_send(&self, party_id: usize, bytes: usize) {
+        self.peers[party_id]
+            .bytes_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records the bytes received from a peer, which means that the party is no longer waiting
+    /// for it.
+    pub(crate) fn record_recv(&self, party_id: usize, bytes: usize) {
+        self.peers[party_id]
+            .bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.set_status(party_id, PeerStatus::Connected);
+    }
+
+    /// Returns the progress at this instant to display it.
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            phase: self
+                .phase
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+            rounds_done: self.rounds_done.load(Ordering::Relaxed),
+            rounds_total: self.rounds_total.load(Ordering::Relaxed),
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| PeerSnapshot {
+                    status: PeerStatus::from_u8(peer.status.load(Ordering::Relaxed)),
+                    bytes_sent: peer.bytes_sent.load(Ordering::Relaxed),
+                    bytes_received: peer.bytes_received.load(Ordering::Relaxed),
+                })
+                .collect(),
+            elapsed: self.start.elapsed(),
+        }
+    }
+}
+
+/// Progress of the communication with a peer at some instant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerSnapshot {
+    /// Status of the connection with the peer.
+    pub status: PeerStatus,
+    /// Number of payload bytes sent to the peer.
+    pub bytes_sent: u64,
+    /// Number of payload bytes received from the peer.
+    pub bytes_received: u64,
+}
+
+/// Progress of a party at some instant. It is displayed as a dashboard with the phase of the
+/// protocol, a bar of the rounds completed, the status and traffic of each peer and the average
+/// throughput.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    /// Phase of the protocol in which the party is.
+    pub phase: String,
+    /// Number of rounds of the protocol completed.
+    pub rounds_done: usize,
+    /// Total number of rounds of the protocol, or zero if it is not known yet.
+    pub rounds_total: usize,
+    /// Progress of the communication with each peer, indexed by the ID of the peer.
+    pub peers: Vec<PeerSnapshot>,
+    /// Time since the party started.
+    pub elapsed: Duration,
+}
+
+impl ProgressSnapshot {
+    /// Returns the number of lines of the dashboard.
+    pub fn n_lines(&self) -> usize {
+        self.peers.len() + 4
+    }
+}
+
+/// Formats a number of bytes with a binary unit.
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{value:.0} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+impl fmt::Display for ProgressSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "phase: {}  (elapsed {:.1} s)",
+            self.phase,
+            self.elapsed.as_secs_f64()
+        )?;
+        match (BAR_WIDTH * self.rounds_done.min(self.rounds_total)).checked_div(self.rounds_total) {
+            None => writeln!(f, "rounds: {}", self.rounds_done)?,
+            Some(filled) => writeln!(
+                f,
+                "rounds: [{}{}] {}/{}",
+                "#".repeat(filled),
+                "-".repeat(BAR_WIDTH - filled),
+                self.rounds_done,
+                self.rounds_total
+            )?,
+        }
+        writeln!(
+            f,
+            "{:>6}  {:<10} {:>12} {:>12}",
+            "peer", "status", "sent", "received"
+        )?;
+        for (party_id, peer) in self.peers.iter().enumerate() {
+            writeln!(
+                f,
+                "{party_id:>6}  {:<10} {:>12} {:>12}",
+                peer.status,
+                format_bytes(peer.bytes_sent as f64),
+                format_bytes(peer.bytes_received as f64)
+            )?;
+        }
+        let seconds = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        let sent: u64 = self.peers.iter().map(|peer| peer.bytes_sent).sum();
+        let received: u64 = self.peers.iter().map(|peer| peer.bytes_received).sum();
+        write!(
+            f,
+            "throughput: {}/s sent, {}/s received",
+            format_bytes(sent as f64 / seconds),
+            format_bytes(received as f64 / seconds)
+        )
+    }
+}
+
+/// Dashboard that shows the progress of the party in the terminal, redrawing it in place from a
+/// background thread. Dropping the dashboard draws the progress one last time.
+pub struct Dashboard {
+    /// Progress shown in the dashboard.
+    progress: Arc<Progress>,
+    /// Sender whose drop stops the thread that draws the dashboard.
+    stop: Option<mpsc::Sender<()>>,
+    /// Thread that draws the dashboard.
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Dashboard {
+    /// Starts the dashboard of a party with the given ID, or returns `None` if the standard error
+    /// is not a terminal. The logs below the warnings are disabled, since they would scroll the
+    /// dashboard away.
+    pub fn start(id: usize, n_parties: usize) -> Option<Self> {
+        if !io::stderr().is_terminal() {
+            log::warn!("the dashboard is disabled since the standard error is not a terminal");
+            return None;
+        }
+        log::set_max_level(log::LevelFilter::Warn);
+        let progress = Arc::new(Progress::new(id, n_parties));
+        let (stop, stopped) = mpsc::channel::<()>();
+        let shown = Arc::clone(&progress);
+        let thread = thread::spawn(move || {
+            let mut n_lines = 0;
+            let mut finished = false;
+            loop {
+                let snapshot = shown.snapshot();
+                let mut stderr = io::stderr().lock();
+                // Go back to the first line of the previous dashboard and clear it.
+                if n_lines > 0 {
+                    let _ = write!(stderr, "\x1b[{n_lines}F\x1b[J");
+                }
+                let _ = writeln!(stderr, "{snapshot}");
+                n_lines = snapshot.n_lines();
+                drop(stderr);
+                if finished {
+                    break;
+                }
+                finished = stopped.recv_timeout(DASHBOARD_REFRESH_INTERVAL)
+                    != Err(RecvTimeoutError::Timeout);
+            }
+        });
+        Some(Self {
+            progress,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the progress shown in the dashboard, which the network must update.
+    pub fn progress(&self) -> &Arc<Progress> {
+        &self.progress
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{PeerStatus, Progress};
+
+    #[test]
+    fn display_the_progress() {
+        let progress = Progress::new(1, 3);
+        progress.set_phase("evaluating the circuit");
+        progress.set_rounds(5, 10);
+        progress.record_send(0, 2048);
+        progress.set_status(2, PeerStatus::Waiting);
+        progress.set_status(1, PeerStatus::Failed);
+        progress.record_recv(0, 100);
+
+        let mut snapshot = progress.snapshot();
+        assert_eq!(snapshot.peers[0].status, PeerStatus::Connected);
+        assert_eq!(snapshot.peers[1].status, PeerStatus::Local);
+        snapshot.elapsed = Duration::from_secs(2);
+        let dashboard = snapshot.to_string();
+        assert_eq!(dashboard.lines().count(), snapshot.n_lines());
+        assert_eq!(
+            dashboard,
+            "phase: evaluating the circuit  (elapsed 2.0 s)\n\
+             rounds: [###############---------------] 5/10\n\
+             \x20 peer  status             sent     received\n\
+             \x20    0  connected       2.0 KiB        100 B\n\
+             \x20    1  local               0 B          0 B\n\
+             \x20    2  waiting             0 B          0 B\n\
+             throughput: 1.0 KiB/s sent, 50 B/s received"
+        );
+    }
+}
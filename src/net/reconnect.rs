@@ -0,0 +1,684 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{Error, ErrorKind},
+    net::{TcpListener, TcpStream},
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+use thiserror::Error;
+
+use super::{
+    channel::{is_connection_dropped, is_timeout, Channel, ChannelError},
+    Packet,
+};
+
+/// Kind of frame that carries a packet of the protocol.
+const DATA_FRAME: u8 = 0;
+/// Kind of frame that acknowledges the packets received so far.
+const ACK_FRAME: u8 = 1;
+/// Kind of frame exchanged after reconnecting to resume the session.
+const RESUME_FRAME: u8 = 2;
+/// Kind of frame that reports a gap in the packets received and asks the remote party to send
+/// them again from the given sequence number.
+const RESEND_FRAME: u8 = 3;
+/// Size in bytes of the prefix that the session adds to the payload of each packet.
+const FRAME_PREFIX_SIZE: usize = 1 + 8;
+/// Number of packets received before acknowledging them to the remote party.
+const ACK_INTERVAL: u64 = 32;
+/// Time to wait between polls of the listener while waiting for a peer to reconnect.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Possible errors that may appear while resuming a session.
+#[derive(Debug, Error)]
+pub enum ReconnectError {
+    /// The connection could not be re-established with the given number of attempts.
+    #[error("could not reconnect after {0} attempts")]
+    AttemptsExhausted(u32),
+
+    /// The frame received does not belong to the session protocol.
+    #[error("invalid session frame: {0}")]
+    InvalidFrame(&'static str),
+
+    /// The remote party expects packets that were already discarded.
+    #[error("the remote party expects packet {expected}, but the oldest packet kept is {oldest}")]
+    LostPackets { expected: u64, oldest: u64 },
+}
+
+/// Policy that governs how a dropped connection is re-established.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts to re-establish the connection.
+    pub max_attempts: u32,
+    /// Time to wait after the first failed attempt.
+    pub interval: Duration,
+    /// Maximum time to wait between two attempts. The wait doubles after every failed attempt
+    /// until it reaches this maximum, so a peer that takes long to come back is not flooded with
+    /// attempts.
+    pub max_interval: Duration,
+}
+
+impl RetryPolicy {
+    /// Reads the policy from the `reconnect` object of the network configuration. Returns `None`
+    /// if the object is not present, in which case the connections are not re-established.
+    pub(crate) fn from_json(json: &Value) -> anyhow::Result<Option<Self>> {
+        if json.is_null() {
+            return Ok(None);
+        }
+        let max_attempts = json["max_attempts"].as_u64().ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "the maximum number of reconnection attempts is not correct",
+        ))?;
+        let interval = json["retry_interval"].as_u64().ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "the reconnection interval is not correct",
+        ))?;
+        let max_interval = match &json["max_retry_interval"] {
+            Value::Null => interval,
+            max_interval => {
+                max_interval
+                    .as_u64()
+                    .filter(|max| *max >= interval)
+                    .ok_or(Error::new(
+                        ErrorKind::InvalidInput,
+                        "the maximum reconnection interval is not correct",
+                    ))?
+            }
+        };
+        Ok(Some(Self {
+            max_attempts: u32::try_from(max_attempts)?,
+            interval: Duration::from_millis(interval),
+            max_interval: Duration::from_millis(max_interval),
+        }))
+    }
+
+    /// Returns the time to wait after the given number of failed attempts.
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempts.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.interval.saturating_mul(factor).min(self.max_interval)
+    }
+}
+
+/// Function that re-establishes the connection with the remote party of a channel.
+pub type Reconnect = Box<dyn FnMut() -> anyhow::Result<Box<dyn Channel>> + Send>;
+
+/// Channel that survives the drop of the underlying connection. When the connection drops, the
+/// channel re-establishes it and both parties tell each other the next packet they expect, so
+/// the packets that were not acknowledged before the drop are sent again. The sent packets are
+/// kept until the remote party acknowledges them, so a packet lost without a drop, which shows
+/// up as a gap in the sequence numbers of the next packet received, is also recovered: the
+/// receiver drops the packets after the gap and asks for the packets from the gap on, which the
+/// sender sends again the next time it reads from the channel. The packets received twice are
+/// dropped, so sending them again is idempotent. The remote party must also use a
+/// [`ReconnectingChannel`].
+pub struct ReconnectingChannel {
+    /// Channel through which the packets are currently sent.
+    inner: Box<dyn Channel>,
+    /// Function that re-establishes the connection.
+    reconnect: Reconnect,
+    /// Policy that governs the reconnection.
+    policy: RetryPolicy,
+    /// Packets sent that the remote party has not acknowledged yet, with their session sequence
+    /// numbers.
+    unacked: VecDeque<(u64, Packet)>,
+    /// Session sequence number of the next packet to be sent.
+    send_seq: u64,
+    /// Session sequence number of the next packet to be received.
+    recv_seq: u64,
+    /// Number of packets received since the last acknowledgement was sent.
+    recv_since_ack: u64,
+    /// Sequence number from which the packets were requested again after a gap, until it is
+    /// received.
+    requested: Option<u64>,
+    /// Maximum packet size set in the channel, which is set again in the re-established ones.
+    max_packet_size: Option<usize>,
+}
+
+impl ReconnectingChannel {
+    /// Creates a channel that uses `reconnect` to replace `inner` when its connection drops.
+    pub fn new(inner: Box<dyn Channel>, reconnect: Reconnect, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            reconnect,
+            policy,
+            unacked: VecDeque::new(),
+            send_seq: 0,
+            recv_seq: 0,
+            recv_since_ack: 0,
+            requested: None,
+            max_packet_size: None,
+        }
+    }
+
+    /// Builds a session frame with the given kind and sequence number that carries `packet`.
+    fn frame(kind: u8, seq: u64, packet: &Packet) -> Packet {
+        let mut payload = Vec::with_capacity(FRAME_PREFIX_SIZE + packet.size());
+        payload.push(kind);
+        payload.extend_from_slice(&seq.to_le_bytes());
+        payload.extend_from_slice(packet.as_slice());
+        Packet::with_header(*packet.header(), payload)
+    }
+
+    /// Splits a session frame into its kind, its sequence number and the packet it carries.
+    fn unframe(frame: Packet) -> anyhow::Result<(u8, u64, Packet)> {
+        let payload = frame.as_slice();
+        if payload.len() < FRAME_PREFIX_SIZE {
+            anyhow::bail!(ReconnectError::InvalidFrame("the frame is too short"));
+        }
+        let mut seq_bytes = [0; 8];
+        seq_bytes.copy_from_slice(&payload[1..FRAME_PREFIX_SIZE]);
+        let seq = u64::from_le_bytes(seq_bytes);
+        let kind = payload[0];
+        let mut packet = frame.slice(FRAME_PREFIX_SIZE..frame.size());
+        packet.header.seq = seq;
+        Ok((kind, seq, packet))
+    }
+
+    /// Discards the sent packets that the remote party acknowledged.
+    fn acknowledge(&mut self, next_expected: u64) {
+        while matches!(self.unacked.front(), Some((seq, _)) if *seq < next_expected) {
+            self.unacked.pop_front();
+        }
+    }
+
+    /// Processes a frame received from the remote party. Returns the packet it carries if the
+    /// frame contains data that was not received before.
+    fn handle_frame(&mut self, frame: Packet) -> anyhow::Result<Option<Packet>> {
+        let (kind, seq, packet) = Self::unframe(frame)?;
+        match kind {
+            DATA_FRAME if seq < self.recv_seq => {
+                log::debug!("dropping replayed packet with session sequence number {seq}");
+                Ok(None)
+            }
+            DATA_FRAME if seq > self.recv_seq => {
+                // The packets after the gap are sent again together with the missing ones, so
+                // they are only requested once.
+                if self.requested != Some(self.recv_seq) {
+                    log::warn!(
+                        "packets {} to {} were lost, asking the remote party to send them again",
+                        self.recv_seq,
+                        seq - 1
+                    );
+                    self.requested = Some(self.recv_seq);
+                    self.send_control(RESEND_FRAME, self.recv_seq)?;
+                }
+                Ok(None)
+            }
+            DATA_FRAME => {
+                self.requested = None;
+                self.recv_seq += 1;
+                self.recv_since_ack += 1;
+                if self.recv_since_ack >= ACK_INTERVAL {
+                    self.send_ack()?;
+                }
+                Ok(Some(packet))
+            }
+            ACK_FRAME => {
+                self.acknowledge(seq);
+                Ok(None)
+            }
+            RESEND_FRAME => match self.resend_from(seq) {
+                // The packets are sent again when the session is resumed.
+                Err(err) if is_connection_dropped(&err) => Ok(None),
+                Err(err) => Err(err),
+                Ok(()) => {
+                    log::info!(
+                        "sent again {} packets from {seq} after a gap",
+                        self.unacked.len()
+                    );
+                    Ok(None)
+                }
+            },
+            RESUME_FRAME => anyhow::bail!(ReconnectError::InvalidFrame(
+                "resume frame outside of a reconnection"
+            )),
+            _ => anyhow::bail!(ReconnectError::InvalidFrame("unknown frame kind")),
+        }
+    }
+
+    /// Acknowledges the packets received so far.
+    fn send_ack(&mut self) -> anyhow::Result<()> {
+        self.recv_since_ack = 0;
+        self.send_control(ACK_FRAME, self.recv_seq)
+    }
+
+    /// Sends a frame of the given kind without a packet, such as an acknowledgement. A dropped
+    /// connection is ignored here, as it is detected by the next operation over the channel.
+    fn send_control(&mut self, kind: u8, seq: u64) -> anyhow::Result<()> {
+        match self
+            .inner
+            .send(&Self::frame(kind, seq, &Packet::empty()))
+            .and_then(|_| self.inner.flush())
+        {
+            Err(err) if !is_connection_dropped(&err) => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Re-establishes the connection following the retry policy and resumes the session.
+    fn resume(&mut self, cause: anyhow::Error) -> anyhow::Result<()> {
+        log::warn!("connection dropped, trying to reconnect: {cause}");
+        for attempt in 1..=self.policy.max_attempts {
+            match (self.reconnect)().and_then(|mut inner| {
+                if let Some(max_packet_size) = self.max_packet_size {
+                    inner.set_max_packet_size(max_packet_size);
+                }
+                self.inner = inner;
+                self.resume_session()
+            }) {
+                Ok(()) => {
+                    log::info!("connection re-established after {attempt} attempts");
+                    return Ok(());
+                }
+                Err(err) if is_connection_dropped(&err) || is_timeout_error(&err) => {
+                    log::warn!("reconnection attempt {attempt} failed: {err}");
+                    std::thread::sleep(self.policy.delay(attempt));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        anyhow::bail!(ReconnectError::AttemptsExhausted(self.policy.max_attempts))
+    }
+
+    /// Exchanges with the remote party the next packet each one expects, and sends again the
+    /// packets that the remote party did not receive.
+    fn resume_session(&mut self) -> anyhow::Result<()> {
+        self.inner
+            .send(&Self::frame(RESUME_FRAME, self.recv_seq, &Packet::empty()))?;
+        let (kind, next_expected, _) = Self::unframe(self.inner.recv()?)?;
+        if kind != RESUME_FRAME {
+            anyhow::bail!(ReconnectError::InvalidFrame("expected a resume frame"));
+        }
+        self.resend_from(next_expected)?;
+        self.recv_since_ack = 0;
+        // The packets requested before the drop are sent again as part of the resumption.
+        self.requested = None;
+        log::info!("session resumed, {} packets sent again", self.unacked.len());
+        Ok(())
+    }
+
+    /// Sends again the packets from the one that the remote party expects next, which must not
+    /// have been discarded, after discarding the ones before it.
+    fn resend_from(&mut self, next_expected: u64) -> anyhow::Result<()> {
+        self.acknowledge(next_expected);
+        let oldest = self.unacked.front().map_or(self.send_seq, |(seq, _)| *seq);
+        if oldest != next_expected {
+            anyhow::bail!(ReconnectError::LostPackets {
+                expected: next_expected,
+                oldest,
+            });
+        }
+        for (_, frame) in &self.unacked {
+            self.inner.send(frame)?;
+        }
+        self.inner.flush()
+    }
+}
+
+/// Checks whether an error was produced because an operation timed out.
+fn is_timeout_error(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<ChannelError>(),
+        Some(ChannelError::Timeout | ChannelError::RecvTimeout(_))
+    )
+}
+
+impl Channel for ReconnectingChannel {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.inner.shutdown()
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.max_packet_size = Some(max_packet_size);
+        self.inner.set_max_packet_size(max_packet_size);
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        self.inner.rekey()
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        self.inner.export_keying_material(label, output)
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        match self.inner.flush() {
+            Err(err) if is_connection_dropped(&err) => self.resume(err),
+            result => result,
+        }
+    }
+
+    fn buffered(&self) -> usize {
+        self.inner.buffered()
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        let frame = Self::frame(DATA_FRAME, self.send_seq, packet);
+        self.unacked.push_back((self.send_seq, frame.clone()));
+        self.send_seq += 1;
+        match self.inner.send(&frame) {
+            Ok(_) => {}
+            // The packet is sent again when the session is resumed.
+            Err(err) if is_connection_dropped(&err) => self.resume(err)?,
+            Err(err) => return Err(err),
+        }
+        Ok(packet.size())
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        loop {
+            match self.inner.recv() {
+                Ok(frame) => {
+                    if let Some(packet) = self.handle_frame(frame)? {
+                        return Ok(packet);
+                    }
+                }
+                Err(err) if is_connection_dropped(&err) => self.resume(err)?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                anyhow::bail!(ChannelError::RecvTimeout(timeout));
+            }
+            match self.inner.recv_timeout(remaining) {
+                Ok(frame) => {
+                    if let Some(packet) = self.handle_frame(frame)? {
+                        return Ok(packet);
+                    }
+                }
+                Err(err) if is_connection_dropped(&err) => self.resume(err)?,
+                Err(err) if is_timeout_error(&err) => {
+                    anyhow::bail!(ChannelError::RecvTimeout(timeout))
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        loop {
+            match self.inner.try_recv() {
+                Ok(Some(frame)) => {
+                    if let Some(packet) = self.handle_frame(frame)? {
+                        return Ok(Some(packet));
+                    }
+                }
+                Ok(None) => return Ok(None),
+                Err(err) if is_connection_dropped(&err) => self.resume(err)?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Function that completes a connection accepted from a peer. Returns the channel and the ID of
+/// the peer.
+pub type AcceptHandshake =
+    Box<dyn Fn(TcpStream) -> anyhow::Result<(Box<dyn Channel>, usize)> + Send>;
+
+/// Listener shared by the channels that wait for their peers to reconnect. Connections from
+/// peers other than the one being waited for are kept until their channel asks for them.
+pub struct Acceptor {
+    /// Listener in which the peers reconnect.
+    listener: TcpListener,
+    /// Handshake run over each accepted connection.
+    handshake: AcceptHandshake,
+    /// Maximum time to wait for a peer to reconnect.
+    timeout: Duration,
+    /// Channels re-established by peers whose channel has not asked for them yet.
+    stash: HashMap<usize, Box<dyn Channel>>,
+}
+
+impl Acceptor {
+    /// Creates an acceptor that completes the connections in `listener` with `handshake`.
+    pub fn new(listener: TcpListener, handshake: AcceptHandshake, timeout: Duration) -> Self {
+        Self {
+            listener,
+            handshake,
+            timeout,
+            stash: HashMap::new(),
+        }
+    }
+
+    /// Waits until the peer `remote_id` reconnects, and returns the new channel with it.
+    pub fn accept_from(&mut self, remote_id: usize) -> anyhow::Result<Box<dyn Channel>> {
+        let start_time = Instant::now();
+        self.listener.set_nonblocking(true)?;
+        loop {
+            if let Some(channel) = self.stash.remove(&remote_id) {
+                return Ok(channel);
+            }
+            match self.listener.accept() {
+                Ok((stream, _)) => match (self.handshake)(stream) {
+                    Ok((channel, id)) => {
+                        self.stash.insert(id, channel);
+                    }
+                    Err(err) => log::warn!("rejecting reconnection: {err}"),
+                },
+                Err(err) if is_timeout(&err) => {
+                    if start_time.elapsed() > self.timeout {
+                        log::error!("timeout reached, peer {remote_id} did not reconnect");
+                        anyhow::bail!(ChannelError::Timeout)
+                    }
+                    std::thread::sleep(ACCEPT_POLL_INTERVAL);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use serde_json::json;
+
+    use super::{Reconnect, ReconnectingChannel, RetryPolicy};
+    use crate::net::{channel::Channel, local::LocalChannel, Packet};
+
+    /// Channel whose connection drops, for both ends, once the shared flag is set.
+    struct DroppingChannel {
+        inner: LocalChannel,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl DroppingChannel {
+        fn check(&self) -> anyhow::Result<()> {
+            if self.dropped.load(Ordering::SeqCst) {
+                Err(io::Error::from(io::ErrorKind::ConnectionReset).into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl Channel for DroppingChannel {
+        fn shutdown(&mut self) -> anyhow::Result<()> {
+            self.inner.shutdown()
+        }
+
+        fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+            self.check()?;
+            self.inner.send(packet)
+        }
+
+        fn recv(&mut self) -> anyhow::Result<Packet> {
+            loop {
+                self.check()?;
+                if let Some(packet) = self.inner.try_recv()? {
+                    return Ok(packet);
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+            self.check()?;
+            self.inner.recv_timeout(timeout)
+        }
+
+        fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+            self.check()?;
+            self.inner.try_recv()
+        }
+    }
+
+    /// Channel that loses the packet sent in the given position without dropping the connection.
+    struct LossyChannel {
+        inner: LocalChannel,
+        lost: usize,
+        sent: usize,
+    }
+
+    impl Channel for LossyChannel {
+        fn shutdown(&mut self) -> anyhow::Result<()> {
+            self.inner.shutdown()
+        }
+
+        fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+            self.sent += 1;
+            if self.sent - 1 == self.lost {
+                return Ok(packet.size());
+            }
+            self.inner.send(packet)
+        }
+
+        fn recv(&mut self) -> anyhow::Result<Packet> {
+            self.inner.recv()
+        }
+
+        fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+            self.inner.recv_timeout(timeout)
+        }
+
+        fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+            self.inner.try_recv()
+        }
+    }
+
+    /// Returns a function that yields the given channel in the first reconnection.
+    fn reconnect_once(channel: LocalChannel) -> Reconnect {
+        let mut channel = Some(channel);
+        Box::new(move || Ok(Box::new(channel.take().expect("single reconnection"))))
+    }
+
+    #[test]
+    fn parse_retry_policy() {
+        let policy = RetryPolicy::from_json(&json!({"max_attempts": 3, "retry_interval": 20}))
+            .unwrap()
+            .unwrap();
+        assert_eq!(policy.max_attempts, 3);
+        assert_eq!(policy.interval, Duration::from_millis(20));
+        assert_eq!(policy.delay(3), Duration::from_millis(20));
+        assert!(RetryPolicy::from_json(&json!(null)).unwrap().is_none());
+        assert!(RetryPolicy::from_json(&json!({"max_attempts": 3})).is_err());
+
+        let policy = RetryPolicy::from_json(
+            &json!({"max_attempts": 5, "retry_interval": 20, "max_retry_interval": 50}),
+        )
+        .unwrap()
+        .unwrap();
+        let delays: Vec<u128> = (1..=4).map(|n| policy.delay(n).as_millis()).collect();
+        assert_eq!(delays, [20, 40, 50, 50]);
+        assert!(RetryPolicy::from_json(
+            &json!({"max_attempts": 5, "retry_interval": 20, "max_retry_interval": 10})
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn resume_after_dropped_connection() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            interval: Duration::from_millis(10),
+            max_interval: Duration::from_millis(10),
+        };
+        let dropped = Arc::new(AtomicBool::new(false));
+        let (first_a, first_b) = LocalChannel::pair();
+        let (second_a, second_b) = LocalChannel::pair();
+        let mut channel_a = ReconnectingChannel::new(
+            Box::new(DroppingChannel {
+                inner: first_a,
+                dropped: dropped.clone(),
+            }),
+            reconnect_once(second_a),
+            policy,
+        );
+        let mut channel_b = ReconnectingChannel::new(
+            Box::new(DroppingChannel {
+                inner: first_b,
+                dropped: dropped.clone(),
+            }),
+            reconnect_once(second_b),
+            policy,
+        );
+
+        let receiver = thread::spawn(move || {
+            let received: Vec<u8> = (0..100)
+                .map(|_| channel_b.recv().unwrap().as_slice()[0])
+                .collect();
+            channel_b.send(&Packet::new(vec![1])).unwrap();
+            received
+        });
+        for i in 0..100 {
+            if i == 50 {
+                dropped.store(true, Ordering::SeqCst);
+            }
+            channel_a.send(&Packet::new(vec![i])).unwrap();
+        }
+        assert_eq!(channel_a.recv().unwrap().as_slice(), &[1]);
+        assert_eq!(receiver.join().unwrap(), (0..100).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn lost_packets_are_sent_again_after_a_gap() {
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            interval: Duration::from_millis(10),
+            max_interval: Duration::from_millis(10),
+        };
+        let never = || -> Reconnect { Box::new(|| anyhow::bail!("the connection never drops")) };
+        let (inner_a, inner_b) = LocalChannel::pair();
+        let mut channel_a = ReconnectingChannel::new(
+            Box::new(LossyChannel {
+                inner: inner_a,
+                lost: 5,
+                sent: 0,
+            }),
+            never(),
+            policy,
+        );
+        let mut channel_b = ReconnectingChannel::new(Box::new(inner_b), never(), policy);
+
+        let receiver = thread::spawn(move || {
+            let received: Vec<u8> = (0..10)
+                .map(|_| channel_b.recv().unwrap().as_slice()[0])
+                .collect();
+            channel_b.send(&Packet::new(vec![1])).unwrap();
+            received
+        });
+        for i in 0..10 {
+            channel_a.send(&Packet::new(vec![i])).unwrap();
+        }
+        // The sender handles the request of the receiver while it waits for its answer.
+        assert_eq!(channel_a.recv().unwrap().as_slice(), &[1]);
+        assert_eq!(receiver.join().unwrap(), (0..10).collect::<Vec<u8>>());
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// Policy that decides when the keys that protect the channel of a peer are rotated, which
+/// limits the traffic protected under a single key in long-running sessions. The keys are only
+/// rotated when the party starts a new round of communication, so no packet of the party to the
+/// peer is in flight. If no limit is given, the keys are never rotated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RekeyPolicy {
+    /// Number of payload bytes sent to a peer after which the keys of its channel are rotated.
+    #[serde(default)]
+    pub after_bytes: Option<u64>,
+    /// Number of seconds after which the keys of the channel of a peer are rotated.
+    #[serde(default)]
+    pub after_seconds: Option<u64>,
+}
+
+/// Keeps track of the traffic sent to each peer since the keys of its channel were rotated, and
+/// tells when they must be rotated again according to a [`RekeyPolicy`].
+#[derive(Debug, Clone)]
+pub struct RekeySchedule {
+    /// Policy that decides when the keys are rotated.
+    policy: RekeyPolicy,
+    /// Instant of the last rotation of the keys of each peer and bytes sent to it since then.
+    since_rotation: Vec<(Instant, u64)>,
+}
+
+impl RekeySchedule {
+    /// Creates a schedule that never rotates the keys of the peers of a network of `n_parties`.
+    pub fn new(n_parties: usize) -> Self {
+        Self {
+            policy: RekeyPolicy::default(),
+            since_rotation: vec![(Instant::now(), 0); n_parties],
+        }
+    }
+
+    /// Sets the policy that decides when the keys are rotated.
+    pub fn set_policy(&mut self, policy: RekeyPolicy) {
+        self.policy = policy;
+    }
+
+    /// Returns the policy that decides when the keys are rotated.
+    pub fn policy(&self) -> &RekeyPolicy {
+        &self.policy
+    }
+
+    /// Records the payload bytes sent to a peer.
+    pub fn record_send(&mut self, party_id: usize, bytes: usize) {
+        self.since_rotation[party_id].1 += bytes as u64;
+    }
+
+    /// Tells whether the keys of the channel of a peer must be rotated at the given instant.
+    pub fn is_due(&self, party_id: usize, now: Instant) -> bool {
+        let (rotated_at, bytes) = self.since_rotation[party_id];
+        self.policy.after_bytes.is_some_and(|limit| bytes >= limit)
+            || self.policy.after_seconds.is_some_and(|seconds| {
+                now.duration_since(rotated_at) >= Duration::from_secs(seconds)
+            })
+    }
+
+    /// Records that the keys of the channel of a peer were rotated at the given instant.
+    pub fn rotated(&mut self, party_id: usize, now: Instant) {
+        self.since_rotation[party_id] = (now, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{RekeyPolicy, RekeySchedule};
+
+    #[test]
+    fn rotate_after_the_limits() {
+        let mut schedule = RekeySchedule::new(2);
+        let now = Instant::now();
+        schedule.record_send(0, 1000);
+        assert!(!schedule.is_due(0, now + Duration::from_secs(3600)));
+
+        schedule.set_policy(RekeyPolicy {
+            after_bytes: Some(100),
+            after_seconds: Some(60),
+        });
+        assert!(schedule.is_due(0, now));
+        assert!(!schedule.is_due(1, now));
+        assert!(schedule.is_due(1, now + Duration::from_secs(60)));
+
+        schedule.rotated(0, now);
+        assert!(!schedule.is_due(0, now));
+        schedule.record_send(0, 99);
+        assert!(!schedule.is_due(0, now));
+        schedule.record_send(0, 1);
+        assert!(schedule.is_due(0, now));
+    }
+}
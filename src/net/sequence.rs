@@ -0,0 +1,103 @@
+use thiserror::Error;
+
+use super::PacketHeader;
+
+/// Possible errors that may appear when a channel delivers a packet that was already delivered.
+#[derive(Debug, Error)]
+pub enum SequenceError {
+    /// The packet has the same sequence number as the last packet delivered by the channel.
+    #[error("party {party_id} delivered again the packet with sequence number {seq}")]
+    Duplicate { party_id: usize, seq: u64 },
+
+    /// The packet has a lower sequence number than the last packet delivered by the channel.
+    #[error(
+        "party {party_id} delivered the packet with sequence number {received} after the packet {last}"
+    )]
+    Regression {
+        party_id: usize,
+        last: u64,
+        received: u64,
+    },
+}
+
+/// Tracks the sequence numbers of the packets delivered by the channel of each party, so that a
+/// packet delivered twice, e.g. by a faulty reconnection, is detected instead of being processed
+/// again. The sequence numbers of each channel must increase monotonically.
+#[derive(Debug, Clone)]
+pub struct SequenceTracker {
+    /// Sequence number of the last packet delivered by the channel of each party.
+    last: Vec<Option<u64>>,
+}
+
+impl SequenceTracker {
+    /// Creates a tracker for the channels of `n_parties`, none of which delivered packets yet.
+    pub fn new(n_parties: usize) -> Self {
+        Self {
+            last: vec![None; n_parties],
+        }
+    }
+
+    /// Checks that the sequence number of a packet delivered by the channel of a given party is
+    /// higher than the one of the previous packet. A jump in the sequence numbers is logged, as
+    /// it means that some packets were lost, but it is not rejected.
+    pub fn check(&mut self, party_id: usize, header: &PacketHeader) -> Result<(), SequenceError> {
+        let seq = header.seq;
+        match self.last[party_id] {
+            Some(last) if seq == last => return Err(SequenceError::Duplicate { party_id, seq }),
+            Some(last) if seq < last => {
+                return Err(SequenceError::Regression {
+                    party_id,
+                    last,
+                    received: seq,
+                })
+            }
+            Some(last) if seq > last + 1 => log::warn!(
+                "party {party_id} skipped {} packets before the sequence number {seq}",
+                seq - last - 1
+            ),
+            None if seq > 0 => log::warn!(
+                "party {party_id} skipped {seq} packets before the sequence number {seq}"
+            ),
+            _ => {}
+        }
+        self.last[party_id] = Some(seq);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SequenceError, SequenceTracker};
+    use crate::net::PacketHeader;
+
+    fn header(seq: u64) -> PacketHeader {
+        PacketHeader {
+            seq,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reject_duplicates_and_regressions() {
+        let mut tracker = SequenceTracker::new(2);
+        tracker.check(0, &header(0)).unwrap();
+        tracker.check(0, &header(1)).unwrap();
+        tracker.check(1, &header(0)).unwrap();
+        assert!(matches!(
+            tracker.check(0, &header(1)),
+            Err(SequenceError::Duplicate {
+                party_id: 0,
+                seq: 1
+            })
+        ));
+        tracker.check(0, &header(4)).unwrap();
+        assert!(matches!(
+            tracker.check(0, &header(2)),
+            Err(SequenceError::Regression {
+                party_id: 0,
+                last: 4,
+                received: 2
+            })
+        ));
+    }
+}
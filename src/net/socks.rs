@@ -0,0 +1,299 @@
+use std::{
+    io::{Read, Write},
+    net::{IpAddr, TcpStream},
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::channel;
+
+/// Version of the SOCKS protocol.
+const SOCKS_VERSION: u8 = 5;
+/// Authentication method that does not require authentication.
+const METHOD_NO_AUTH: u8 = 0x00;
+/// Authentication method with a username and a password (RFC 1929).
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+/// Version of the username and password authentication.
+const AUTH_VERSION: u8 = 1;
+/// Command that opens a TCP connection with the target.
+const COMMAND_CONNECT: u8 = 1;
+/// Address type of an IPv4 address.
+const ADDRESS_IPV4: u8 = 1;
+/// Address type of a domain name, which is resolved by the proxy.
+const ADDRESS_DOMAIN: u8 = 3;
+/// Address type of an IPv6 address.
+const ADDRESS_IPV6: u8 = 4;
+
+/// Possible errors that may appear while connecting through a SOCKS5 proxy.
+#[derive(Debug, Error)]
+pub enum ProxyError {
+    /// The proxy answered with a version different from SOCKS5.
+    #[error("the proxy answered with the SOCKS version {0}")]
+    UnsupportedVersion(u8),
+
+    /// The proxy does not accept any of the offered authentication methods.
+    #[error("the proxy does not accept any of the offered authentication methods")]
+    NoAcceptableMethod,
+
+    /// The proxy rejected the username and password.
+    #[error("the proxy rejected the username and password")]
+    AuthenticationFailed,
+
+    /// The host name or the credentials do not fit in the fields of the protocol.
+    #[error("the {0} is longer than 255 bytes")]
+    FieldTooLong(&'static str),
+
+    /// The proxy could not connect to the target.
+    #[error("the proxy could not connect to the target: {}", reply_message(*.0))]
+    ConnectFailed(u8),
+
+    /// The proxy answered with an unknown address type.
+    #[error("the proxy answered with the unknown address type {0}")]
+    UnknownAddressType(u8),
+}
+
+/// Returns the meaning of a reply code of a SOCKS5 proxy.
+fn reply_message(reply: u8) -> &'static str {
+    match reply {
+        1 => "general failure",
+        2 => "connection not allowed by the ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+/// SOCKS5 proxy through which the outbound connections are opened, e.g. to reach the peers
+/// through Tor or through the egress proxy of a corporate network.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Socks5Proxy {
+    /// Host of the proxy.
+    pub host: String,
+    /// Port in which the proxy listens.
+    pub port: u16,
+    /// Username to authenticate with the proxy, if it requires authentication.
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Password to authenticate with the proxy, if it requires authentication.
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl Socks5Proxy {
+    /// Opens a TCP connection with the target through the proxy. Host names are sent to the
+    /// proxy without resolving them, so the proxy resolves them.
+    pub fn connect(&self, target_host: &str, target_port: u16) -> anyhow::Result<TcpStream> {
+        let mut stream = TcpStream::connect(channel::resolve_host(&self.host, self.port)?)?;
+        self.authenticate(&mut stream)?;
+
+        let target_host = target_host.trim_start_matches('[').trim_end_matches(']');
+        let mut request = vec![SOCKS_VERSION, COMMAND_CONNECT, 0];
+        match target_host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(ADDRESS_IPV4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(ADDRESS_IPV6);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                request.push(ADDRESS_DOMAIN);
+                push_field(&mut request, target_host, "host name")?;
+            }
+        }
+        request.extend_from_slice(&target_port.to_be_bytes());
+        stream.write_all(&request)?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply)?;
+        check_version(reply[0])?;
+        if reply[1] != 0 {
+            anyhow::bail!(ProxyError::ConnectFailed(reply[1]));
+        }
+        // The reply ends with the address bound by the proxy, which is not needed.
+        let address_len = match reply[3] {
+            ADDRESS_IPV4 => 4,
+            ADDRESS_IPV6 => 16,
+            ADDRESS_DOMAIN => {
+                let mut len = [0; 1];
+                stream.read_exact(&mut len)?;
+                len[0] as usize
+            }
+            address_type => anyhow::bail!(ProxyError::UnknownAddressType(address_type)),
+        };
+        let mut bound_address = vec![0; address_len + 2];
+        stream.read_exact(&mut bound_address)?;
+
+        log::debug!(
+            "connected to {target_host}:{target_port} through the proxy {}:{}",
+            self.host,
+            self.port
+        );
+        Ok(stream)
+    }
+
+    /// Negotiates the authentication method with the proxy and authenticates if the proxy
+    /// requires a username and a password.
+    fn authenticate(&self, stream: &mut TcpStream) -> anyhow::Result<()> {
+        let credentials = self.username.as_ref().zip(self.password.as_ref());
+        let greeting: &[u8] = match credentials {
+            Some(_) => &[SOCKS_VERSION, 2, METHOD_NO_AUTH, METHOD_USERNAME_PASSWORD],
+            None => &[SOCKS_VERSION, 1, METHOD_NO_AUTH],
+        };
+        stream.write_all(greeting)?;
+
+        let mut choice = [0; 2];
+        stream.read_exact(&mut choice)?;
+        check_version(choice[0])?;
+        match (choice[1], credentials) {
+            (METHOD_NO_AUTH, _) => Ok(()),
+            (METHOD_USERNAME_PASSWORD, Some((username, password))) => {
+                let mut request = vec![AUTH_VERSION];
+                push_field(&mut request, username, "username")?;
+                push_field(&mut request, password, "password")?;
+                stream.write_all(&request)?;
+
+                let mut status = [0; 2];
+                stream.read_exact(&mut status)?;
+                if status[1] != 0 {
+                    anyhow::bail!(ProxyError::AuthenticationFailed);
+                }
+                Ok(())
+            }
+            _ => Err(ProxyError::NoAcceptableMethod.into()),
+        }
+    }
+}
+
+/// Checks that the proxy answered with the SOCKS5 version.
+fn check_version(version: u8) -> Result<(), ProxyError> {
+    if version != SOCKS_VERSION {
+        return Err(ProxyError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+/// Appends a field prefixed with its length in one byte.
+fn push_field(buffer: &mut Vec<u8>, field: &str, name: &'static str) -> Result<(), ProxyError> {
+    let len = u8::try_from(field.len()).map_err(|_| ProxyError::FieldTooLong(name))?;
+    buffer.push(len);
+    buffer.extend_from_slice(field.as_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::{ProxyError, Socks5Proxy};
+
+    /// Runs a single connection of a SOCKS5 proxy that expects the given credentials, and then
+    /// echoes the bytes of the client. Returns the host and port requested by the client.
+    fn serve_proxy(listener: TcpListener, credentials: Option<(&str, &str)>) -> (String, u16) {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut greeting = [0; 2];
+        stream.read_exact(&mut greeting).unwrap();
+        let mut methods = vec![0; greeting[1] as usize];
+        stream.read_exact(&mut methods).unwrap();
+        match credentials {
+            Some((username, password)) => {
+                assert!(methods.contains(&2));
+                stream.write_all(&[5, 2]).unwrap();
+                let mut auth = [0; 2];
+                stream.read_exact(&mut auth).unwrap();
+                let mut received_username = vec![0; auth[1] as usize];
+                stream.read_exact(&mut received_username).unwrap();
+                let mut password_len = [0; 1];
+                stream.read_exact(&mut password_len).unwrap();
+                let mut received_password = vec![0; password_len[0] as usize];
+                stream.read_exact(&mut received_password).unwrap();
+                let accepted = received_username == username.as_bytes()
+                    && received_password == password.as_bytes();
+                stream.write_all(&[1, u8::from(!accepted)]).unwrap();
+                if !accepted {
+                    return (String::new(), 0);
+                }
+            }
+            None => stream.write_all(&[5, 0]).unwrap(),
+        }
+
+        let mut request = [0; 5];
+        stream.read_exact(&mut request).unwrap();
+        assert_eq!(request[..4], [5, 1, 0, 3]);
+        let mut host = vec![0; request[4] as usize];
+        stream.read_exact(&mut host).unwrap();
+        let mut port = [0; 2];
+        stream.read_exact(&mut port).unwrap();
+        stream.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0]).unwrap();
+
+        let mut payload = [0; 3];
+        stream.read_exact(&mut payload).unwrap();
+        stream.write_all(&payload).unwrap();
+        (String::from_utf8(host).unwrap(), u16::from_be_bytes(port))
+    }
+
+    fn proxy(listener: &TcpListener, credentials: Option<(&str, &str)>) -> Socks5Proxy {
+        Socks5Proxy {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            username: credentials.map(|(username, _)| username.to_string()),
+            password: credentials.map(|(_, password)| password.to_string()),
+        }
+    }
+
+    #[test]
+    fn connect_through_the_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = proxy(&listener, None);
+        let server = thread::spawn(move || serve_proxy(listener, None));
+
+        let mut stream = proxy.connect("party1.example.com", 5001).unwrap();
+        stream.write_all(&[1, 2, 3]).unwrap();
+        let mut echo = [0; 3];
+        stream.read_exact(&mut echo).unwrap();
+        assert_eq!(echo, [1, 2, 3]);
+        assert_eq!(
+            server.join().unwrap(),
+            ("party1.example.com".to_string(), 5001)
+        );
+    }
+
+    #[test]
+    fn authenticate_with_the_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_ok = proxy(&listener, Some(("alice", "secret")));
+        let server = thread::spawn(move || {
+            let listener_clone = listener.try_clone().unwrap();
+            let first = serve_proxy(listener_clone, Some(("alice", "secret")));
+            let second = serve_proxy(listener, Some(("alice", "secret")));
+            (first, second)
+        });
+
+        let mut stream = proxy_ok.connect("party2", 5002).unwrap();
+        stream.write_all(&[4, 5, 6]).unwrap();
+        let mut echo = [0; 3];
+        stream.read_exact(&mut echo).unwrap();
+
+        let proxy_wrong = Socks5Proxy {
+            password: Some("wrong".to_string()),
+            ..proxy_ok
+        };
+        let err = proxy_wrong.connect("party2", 5002).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ProxyError>(),
+            Some(ProxyError::AuthenticationFailed)
+        ));
+        let (first, _) = server.join().unwrap();
+        assert_eq!(first, ("party2".to_string(), 5002));
+    }
+}
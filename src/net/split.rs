@@ -0,0 +1,522 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::net::UnixStream,
+    },
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use super::{
+    channel::{Channel, ChannelError, Readiness, Signal},
+    Packet,
+};
+
+/// Interval at which the background thread of a split channel polls a channel of unknown
+/// readiness, see [`Readiness::Polled`], for received packets.
+const WORKER_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Error of a send done by the background thread, kept until the sender reports it.
+type SendFailure = Arc<Mutex<Option<anyhow::Error>>>;
+
+/// Wakes the background thread of a split channel when a packet is queued to be sent or one of
+/// the halves is dropped.
+#[derive(Clone)]
+pub(crate) enum Doorbell {
+    /// Writes a byte to the socket that the thread polls together with the socket of the channel.
+    Socket(Arc<UnixStream>),
+    /// Raises the signal for which the thread waits.
+    Signal(Arc<Signal>),
+}
+
+impl Doorbell {
+    /// Wakes the background thread.
+    pub(crate) fn ring(&self) {
+        match self {
+            // A full socket already has bytes that wake the thread, so the error is ignored.
+            Doorbell::Socket(socket) => drop((&**socket).write(&[0])),
+            Doorbell::Signal(signal) => signal.raise(),
+        }
+    }
+}
+
+/// Event for which the background thread of a split channel waits when there is nothing to do.
+enum Waiter {
+    /// Waits until the socket of the channel or the socket of the doorbell is readable.
+    Poll {
+        /// File descriptor of the socket of the channel.
+        fd: RawFd,
+        /// Socket in which the doorbell writes.
+        bell: UnixStream,
+    },
+    /// Waits until the signal is raised, by the doorbell or by the remote party, or at most the
+    /// interval, if there is one, while receiving.
+    Signal {
+        /// Signal for which the thread waits.
+        signal: Arc<Signal>,
+        /// Maximum time to wait before polling the channel again.
+        interval: Option<Duration>,
+    },
+}
+
+impl Waiter {
+    /// Returns the waiter and the doorbell of a channel with the given readiness.
+    fn new(readiness: Readiness) -> (Self, Doorbell) {
+        let (signal, interval) = match readiness {
+            Readiness::Fd(fd) => match UnixStream::pair().and_then(|(ringer, bell)| {
+                ringer.set_nonblocking(true)?;
+                bell.set_nonblocking(true)?;
+                Ok((ringer, bell))
+            }) {
+                Ok((ringer, bell)) => {
+                    return (
+                        Waiter::Poll { fd, bell },
+                        Doorbell::Socket(Arc::new(ringer)),
+                    )
+                }
+                Err(err) => {
+                    log::warn!("polling the split channel, since its doorbell failed: {err}");
+                    (Arc::default(), Some(WORKER_POLL_INTERVAL))
+                }
+            },
+            Readiness::Signal(signal) => (signal, None),
+            Readiness::Sends => (Arc::default(), None),
+            Readiness::Polled => (Arc::default(), Some(WORKER_POLL_INTERVAL)),
+        };
+        let doorbell = Doorbell::Signal(signal.clone());
+        (Waiter::Signal { signal, interval }, doorbell)
+    }
+
+    /// Waits until there may be something to do: a packet to receive, if `receiving`, or a ring
+    /// of the doorbell.
+    fn wait(&mut self, receiving: bool) -> io::Result<()> {
+        match self {
+            Waiter::Poll { fd, bell } => {
+                let mut fds = [*fd, bell.as_raw_fd()].map(|fd| libc::pollfd {
+                    fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                });
+                let fds = if receiving {
+                    &mut fds[..]
+                } else {
+                    &mut fds[1..]
+                };
+                // SAFETY: the file descriptors are valid and the length is the one of the slice.
+                let result = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+                if result < 0 {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                // The rings are consumed, so the next wait blocks until a new one.
+                let mut rings = [0; 64];
+                loop {
+                    match bell.read(&mut rings) {
+                        Ok(0) => return Ok(()),
+                        Ok(_) => continue,
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+            Waiter::Signal { signal, interval } => {
+                signal.wait(if receiving { *interval } else { None });
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Sending half of a split channel. The packets are queued and sent by the background thread
+/// that owns the channel, so sending never waits for the receiving half.
+pub struct ChannelSender {
+    /// Queue of packets to be sent by the background thread, which is only `None` once dropped.
+    outgoing: Option<Sender<Packet>>,
+    /// Error produced by the last failed send of the background thread.
+    failure: SendFailure,
+    /// Doorbell that wakes the background thread.
+    doorbell: Doorbell,
+}
+
+impl ChannelSender {
+    /// Queues a packet to be sent through the channel. An error produced while sending a
+    /// previous packet is returned by the next call.
+    pub fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        if let Some(err) = self
+            .failure
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            return Err(err);
+        }
+        self.outgoing
+            .as_ref()
+            .ok_or(ChannelError::ConnectionClosed)?
+            .send(packet.clone())
+            .map_err(|_| ChannelError::ConnectionClosed)?;
+        self.doorbell.ring();
+        Ok(packet.size())
+    }
+}
+
+impl ChannelSender {
+    /// Returns the doorbell that wakes the background thread, which the receiver rings once it
+    /// is dropped.
+    pub(crate) fn doorbell(&self) -> Doorbell {
+        self.doorbell.clone()
+    }
+}
+
+impl Drop for ChannelSender {
+    fn drop(&mut self) {
+        // The queue is closed before ringing, so the background thread notices it once it wakes.
+        self.outgoing = None;
+        self.doorbell.ring();
+    }
+}
+
+/// Receiving half of a split channel. The packets are received by the background thread that
+/// owns the channel as soon as they arrive.
+pub struct ChannelReceiver {
+    /// Queue of packets received by the background thread.
+    incoming: Receiver<anyhow::Result<Packet>>,
+    /// Flag that tells the background thread that the receiving half was dropped.
+    dropped: Arc<AtomicBool>,
+    /// Doorbell that wakes the background thread.
+    doorbell: Doorbell,
+}
+
+impl Drop for ChannelReceiver {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::Relaxed);
+        self.doorbell.ring();
+    }
+}
+
+impl ChannelReceiver {
+    /// Receives a packet from the channel.
+    pub fn recv(&mut self) -> anyhow::Result<Packet> {
+        self.incoming
+            .recv()
+            .map_err(|_| ChannelError::ConnectionClosed)?
+    }
+
+    /// Receives a packet from the channel waiting at most the given timeout. If the timeout is
+    /// reached, the function returns [`ChannelError::RecvTimeout`].
+    pub fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        self.incoming
+            .recv_timeout(timeout)
+            .map_err(|err| match err {
+                RecvTimeoutError::Timeout => ChannelError::RecvTimeout(timeout),
+                RecvTimeoutError::Disconnected => ChannelError::ConnectionClosed,
+            })?
+    }
+
+    /// Receives a packet from the channel if there is one ready, without blocking.
+    pub fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        match self.incoming.try_recv() {
+            Ok(result) => result.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(ChannelError::ConnectionClosed.into()),
+        }
+    }
+}
+
+/// Splits a channel into independent sending and receiving halves, which can be used at the
+/// same time from different threads. The channel is moved to a background thread that sends
+/// the queued packets and receives the incoming ones, sleeping until the channel is ready or a
+/// packet is queued, as told by [`Channel::readiness`]. The thread shuts the channel down and
+/// finishes once both halves are dropped.
+pub fn split(channel: Box<dyn Channel>) -> (ChannelSender, ChannelReceiver, JoinHandle<()>) {
+    let (incoming_sender, incoming_receiver) = mpsc::channel();
+    let dropped = Arc::new(AtomicBool::new(false));
+    let (sender, worker) = spawn_worker(
+        channel,
+        move |result| incoming_sender.send(result).is_ok(),
+        dropped.clone(),
+    );
+    let doorbell = sender.doorbell();
+    (
+        sender,
+        ChannelReceiver {
+            incoming: incoming_receiver,
+            dropped,
+            doorbell,
+        },
+        worker,
+    )
+}
+
+/// Moves a channel to a background thread that sends the packets queued in the returned sender
+/// and hands every received packet, or the error that ends the reception, to `deliver`, which
+/// returns whether the receiver still takes packets. The thread shuts the channel down and
+/// finishes once the sender is dropped and the receiver is dropped, as told by `deliver` or by
+/// the flag `receiver_dropped`, after which the receiver must ring the doorbell of the sender.
+pub(crate) fn spawn_worker<D>(
+    channel: Box<dyn Channel>,
+    deliver: D,
+    receiver_dropped: Arc<AtomicBool>,
+) -> (ChannelSender, JoinHandle<()>)
+where
+    D: FnMut(anyhow::Result<Packet>) -> bool + Send + 'static,
+{
+    let (outgoing_sender, outgoing_receiver) = mpsc::channel();
+    let failure = SendFailure::default();
+    let worker_failure = failure.clone();
+    let (waiter, doorbell) = Waiter::new(channel.readiness());
+    let worker = thread::spawn(move || {
+        run_worker(
+            channel,
+            outgoing_receiver,
+            deliver,
+            receiver_dropped,
+            worker_failure,
+            waiter,
+        )
+    });
+    (
+        ChannelSender {
+            outgoing: Some(outgoing_sender),
+            failure,
+            doorbell,
+        },
+        worker,
+    )
+}
+
+/// Services a split channel, alternating between sending the queued packets and receiving the
+/// packets that are ready, and waiting with the waiter when there is neither.
+fn run_worker(
+    mut channel: Box<dyn Channel>,
+    outgoing: Receiver<Packet>,
+    mut deliver: impl FnMut(anyhow::Result<Packet>) -> bool,
+    receiver_dropped: Arc<AtomicBool>,
+    failure: SendFailure,
+    mut waiter: Waiter,
+) {
+    let mut sending = true;
+    let mut receiving = true;
+    loop {
+        receiving &= !receiver_dropped.load(Ordering::Relaxed);
+        while receiving {
+            match channel.try_recv() {
+                Ok(Some(packet)) => receiving = deliver(Ok(packet)),
+                Ok(None) => break,
+                Err(err) => {
+                    deliver(Err(err));
+                    receiving = false;
+                }
+            }
+        }
+
+        if sending {
+            // The packets queued meanwhile are sent together before flushing the channel.
+            let mut packets = Vec::new();
+            loop {
+                match outgoing.try_recv() {
+                    Ok(packet) => packets.push(packet),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        sending = false;
+                        break;
+                    }
+                }
+            }
+            if !packets.is_empty() {
+                let result = packets
+                    .iter()
+                    .try_for_each(|packet| channel.send(packet).map(|_| ()))
+                    .and_then(|()| channel.flush());
+                if let Err(err) = result {
+                    log::error!("error sending a packet from the split channel: {err}");
+                    *failure.lock().unwrap_or_else(PoisonError::into_inner) = Some(err);
+                    sending = false;
+                }
+                // The packets sent may be received by the channel itself, as in a loopback.
+                continue;
+            }
+        }
+
+        if !sending && !receiving {
+            break;
+        }
+        if let Err(err) = waiter.wait(receiving) {
+            log::error!("error waiting for the split channel: {err}");
+            break;
+        }
+    }
+    if let Err(err) = channel.shutdown() {
+        log::warn!("error closing the split channel: {err}");
+    }
+}
+
+/// Sending half of a split network.
+pub struct NetworkSender {
+    /// Sending halves of the channels with each peer.
+    senders: Vec<ChannelSender>,
+    /// Session of the network in which the packets are sent.
+    session: u32,
+}
+
+impl NetworkSender {
+    /// Sends a packet of information to a given party.
+    pub fn send_to(&mut self, packet: &Packet, party_id: usize) -> anyhow::Result<usize> {
+        self.senders[party_id].send(&packet.clone().in_session(self.session))
+    }
+
+    /// Send a packet to every party in the network.
+    pub fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        let packet = packet.clone().in_session(self.session);
+        let mut bytes_sent = 0;
+        for sender in &mut self.senders {
+            bytes_sent = sender.send(&packet)?;
+        }
+        Ok(bytes_sent)
+    }
+}
+
+/// Receiving half of a split network.
+pub struct NetworkReceiver {
+    /// Receiving halves of the channels with each peer.
+    receivers: Vec<ChannelReceiver>,
+    /// Packets received from each peer before the network was split that were not delivered.
+    pending: Vec<VecDeque<Packet>>,
+    /// Background threads that service the channels.
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl NetworkReceiver {
+    /// Receives a packet from a given party. Packets received before the network was split are
+    /// delivered first.
+    pub fn recv_from(&mut self, party_id: usize) -> anyhow::Result<Packet> {
+        if let Some(packet) = self.pending[party_id].pop_front() {
+            return Ok(packet);
+        }
+        self.receivers[party_id].recv()
+    }
+
+    /// Receives a packet from a given party waiting at most the given timeout. If the timeout is
+    /// reached, the function returns [`ChannelError::RecvTimeout`].
+    pub fn recv_from_timeout(
+        &mut self,
+        party_id: usize,
+        timeout: Duration,
+    ) -> anyhow::Result<Packet> {
+        if let Some(packet) = self.pending[party_id].pop_front() {
+            return Ok(packet);
+        }
+        self.receivers[party_id].recv_timeout(timeout)
+    }
+
+    /// Waits until the background threads finish, which happens once the sending half of the
+    /// network is dropped.
+    pub fn join(mut self) {
+        self.receivers.clear();
+        for worker in self.workers {
+            if worker.join().is_err() {
+                log::error!("a thread of the split network panicked");
+            }
+        }
+    }
+}
+
+/// Splits the channels of a network into a sending and a receiving half.
+pub(crate) fn split_network(
+    channels: Vec<Box<dyn Channel>>,
+    pending: Vec<VecDeque<Packet>>,
+    session: u32,
+) -> (NetworkSender, NetworkReceiver) {
+    let mut senders = Vec::with_capacity(channels.len());
+    let mut receivers = Vec::with_capacity(channels.len());
+    let mut workers = Vec::with_capacity(channels.len());
+    for channel in channels {
+        let (sender, receiver, worker) = split(channel);
+        senders.push(sender);
+        receivers.push(receiver);
+        workers.push(worker);
+    }
+    (
+        NetworkSender { senders, session },
+        NetworkReceiver {
+            receivers,
+            pending,
+            workers,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixStream, thread};
+
+    use super::split;
+    use crate::net::{local::LocalChannel, unix::UnixChannel, Network, Packet};
+
+    #[test]
+    fn split_channel_is_full_duplex() {
+        let (channel_a, channel_b) = LocalChannel::pair();
+        let (mut sender_a, mut receiver_a, worker_a) = split(Box::new(channel_a));
+        let (mut sender_b, mut receiver_b, worker_b) = split(Box::new(channel_b));
+
+        let sending = thread::spawn(move || {
+            for i in 0..100 {
+                sender_a.send(&Packet::new(vec![i])).unwrap();
+            }
+        });
+        for i in 0..100 {
+            sender_b.send(&Packet::new(vec![i])).unwrap();
+            assert_eq!(receiver_b.recv().unwrap().as_slice(), &[i]);
+            assert_eq!(receiver_a.recv().unwrap().as_slice(), &[i]);
+        }
+        sending.join().unwrap();
+        drop((sender_b, receiver_a, receiver_b));
+        worker_a.join().unwrap();
+        worker_b.join().unwrap();
+    }
+
+    #[test]
+    fn split_socket_channels_wake_when_the_socket_is_ready() {
+        let (stream_a, stream_b) = UnixStream::pair().unwrap();
+        let (mut sender_a, mut receiver_a, worker_a) = split(Box::new(UnixChannel::new(stream_a)));
+        let (mut sender_b, mut receiver_b, worker_b) = split(Box::new(UnixChannel::new(stream_b)));
+        for i in 0..100 {
+            sender_a.send(&Packet::new(vec![i])).unwrap();
+            assert_eq!(receiver_b.recv().unwrap().as_slice(), &[i]);
+            sender_b.send(&Packet::new(vec![i])).unwrap();
+            assert_eq!(receiver_a.recv().unwrap().as_slice(), &[i]);
+        }
+        // Dropping the halves of one channel closes its socket, which wakes the other thread.
+        drop((sender_a, receiver_a));
+        worker_a.join().unwrap();
+        assert!(receiver_b.recv().is_err());
+        drop((sender_b, receiver_b));
+        worker_b.join().unwrap();
+    }
+
+    #[test]
+    fn split_network_delivers_pending_packets_first() {
+        let mut network = Network::create_local(1).pop().unwrap();
+        network.send_to(&Packet::new(vec![1]), 0).unwrap();
+        network.send_to(&Packet::new(vec![2]), 0).unwrap();
+        network.send_on(1, Packet::new(vec![3]), 0).unwrap();
+        assert_eq!(network.recv_on(1, 0).unwrap().as_slice(), &[3]);
+
+        let (mut sender, mut receiver) = network.split();
+        sender.send_to(&Packet::new(vec![4]), 0).unwrap();
+        for i in [1, 2, 4] {
+            assert_eq!(receiver.recv_from(0).unwrap().as_slice(), &[i]);
+        }
+        drop(sender);
+        receiver.join();
+    }
+}
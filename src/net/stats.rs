@@ -0,0 +1,280 @@
+use std::{fs, path::Path, time::Duration};
+
+use serde::{Serialize, Serializer};
+
+/// Traffic statistics of the communication with a single peer.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PeerStats {
+    /// Number of payload bytes sent to the peer.
+    pub bytes_sent: u64,
+    /// Number of payload bytes received from the peer.
+    pub bytes_received: u64,
+    /// Number of packets sent to the peer.
+    pub packets_sent: u64,
+    /// Number of packets received from the peer.
+    pub packets_received: u64,
+    /// Wall-clock time spent sending packets to the peer.
+    pub send_time: Duration,
+    /// Wall-clock time spent receiving packets from the peer.
+    pub recv_time: Duration,
+}
+
+/// Traffic statistics of a network.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NetworkStats {
+    /// Statistics for each peer, indexed by the ID of the peer.
+    pub peers: Vec<PeerStats>,
+    /// Number of communication rounds. A new round starts every time the party sends a packet
+    /// after having received one.
+    pub rounds: u64,
+    /// Whether the last operation in the network was a reception.
+    #[serde(skip)]
+    last_was_recv: bool,
+}
+
+impl NetworkStats {
+    /// Creates empty statistics for a network with the given number of parties.
+    pub fn new(n_parties: usize) -> Self {
+        Self {
+            peers: vec![PeerStats::default(); n_parties],
+            rounds: 0,
+            last_was_recv: false,
+        }
+    }
+
+    /// Tells whether the next packet sent starts a new round, i.e. the party already received
+    /// the packets of the previous one.
+    pub(crate) fn starts_round(&self) -> bool {
+        self.rounds == 0 || self.last_was_recv
+    }
+
+    /// Records a packet sent to a peer.
+    pub(crate) fn record_send(&mut self, party_id: usize, bytes: usize, elapsed: Duration) {
+        if self.starts_round() {
+            self.rounds += 1;
+        }
+        self.last_was_recv = false;
+
+        let peer = &mut self.peers[party_id];
+        peer.bytes_sent += bytes as u64;
+        peer.packets_sent += 1;
+        peer.send_time += elapsed;
+    }
+
+    /// Records the time spent waiting for a packet from a peer and the size of the packet, if
+    /// one was received.
+    pub(crate) fn record_recv(&mut self, party_id: usize, bytes: Option<usize>, elapsed: Duration) {
+        let peer = &mut self.peers[party_id];
+        peer.recv_time += elapsed;
+        if let Some(bytes) = bytes {
+            self.last_was_recv = true;
+            peer.bytes_received += bytes as u64;
+            peer.packets_received += 1;
+        }
+    }
+
+    /// Returns the total number of payload bytes sent to all the peers.
+    pub fn total_bytes_sent(&self) -> u64 {
+        self.peers.iter().map(|peer| peer.bytes_sent).sum()
+    }
+
+    /// Returns the total number of payload bytes received from all the peers.
+    pub fn total_bytes_received(&self) -> u64 {
+        self.peers.iter().map(|peer| peer.bytes_received).sum()
+    }
+
+    /// Returns the total number of packets sent to all the peers.
+    pub fn total_packets_sent(&self) -> u64 {
+        self.peers.iter().map(|peer| peer.packets_sent).sum()
+    }
+}
+
+/// Traffic of a phase of a protocol, e.g. the sharing of the inputs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PhaseStats {
+    /// Name of the phase.
+    pub name: String,
+    /// Wall-clock time of the phase, serialized in milliseconds.
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub elapsed: Duration,
+    /// Number of communication rounds of the phase.
+    pub rounds: u64,
+    /// Payload bytes sent to each peer during the phase, indexed by the ID of the peer.
+    pub bytes_sent: Vec<u64>,
+    /// Payload bytes received from each peer during the phase, indexed by the ID of the peer.
+    pub bytes_received: Vec<u64>,
+    /// Packets sent to each peer during the phase, indexed by the ID of the peer.
+    pub packets_sent: Vec<u64>,
+    /// Packets received from each peer during the phase, indexed by the ID of the peer.
+    pub packets_received: Vec<u64>,
+}
+
+/// Serializes a duration as a number of milliseconds, as in the CSV files.
+fn serialize_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+impl PhaseStats {
+    /// Computes the traffic of a phase from the statistics of the network at its start and at
+    /// its end.
+    pub fn between(
+        name: &str,
+        elapsed: Duration,
+        start: &NetworkStats,
+        end: &NetworkStats,
+    ) -> Self {
+        let peers = start.peers.iter().zip(&end.peers);
+        Self {
+            name: name.to_string(),
+            elapsed,
+            rounds: end.rounds.saturating_sub(start.rounds),
+            bytes_sent: peers
+                .clone()
+                .map(|(start, end)| end.bytes_sent.saturating_sub(start.bytes_sent))
+                .collect(),
+            bytes_received: peers
+                .clone()
+                .map(|(start, end)| end.bytes_received.saturating_sub(start.bytes_received))
+                .collect(),
+            packets_sent: peers
+                .clone()
+                .map(|(start, end)| end.packets_sent.saturating_sub(start.packets_sent))
+                .collect(),
+            packets_received: peers
+                .map(|(start, end)| end.packets_received.saturating_sub(start.packets_received))
+                .collect(),
+        }
+    }
+}
+
+/// Writes the traffic of the phases of a party as CSV, with a row per phase. Besides the totals,
+/// there are columns with the bytes sent to and received from each peer, so the rows of every
+/// party can be merged in a spreadsheet.
+pub fn phases_to_csv(party: usize, phases: &[PhaseStats]) -> String {
+    let n_peers = phases.first().map_or(0, |phase| phase.bytes_sent.len());
+    let mut csv = String::from("party,phase,duration_ms,rounds,bytes_sent,bytes_received");
+    for peer in 0..n_peers {
+        csv.push_str(&format!(",bytes_sent_{peer},bytes_received_{peer}"));
+    }
+    csv.push('\n');
+    for phase in phases {
+        csv.push_str(&format!(
+            "{party},{},{:.3},{},{},{}",
+            phase.name.replace(',', ";"),
+            phase.elapsed.as_secs_f64() * 1000.0,
+            phase.rounds,
+            phase.bytes_sent.iter().sum::<u64>(),
+            phase.bytes_received.iter().sum::<u64>(),
+        ));
+        for (sent, received) in phase.bytes_sent.iter().zip(&phase.bytes_received) {
+            csv.push_str(&format!(",{sent},{received}"));
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Summary of the traffic of the phases of a party, as written by [`phases_to_json`].
+#[derive(Serialize)]
+struct PhasesSummary<'a> {
+    /// ID of the party.
+    party: usize,
+    /// Total payload bytes sent over all the phases.
+    bytes_sent: u64,
+    /// Total payload bytes received over all the phases.
+    bytes_received: u64,
+    /// Total rounds of communication over all the phases.
+    rounds: u64,
+    /// Traffic of each phase.
+    phases: &'a [PhaseStats],
+}
+
+/// Writes the traffic of the phases of a party as a JSON document with the totals of the party
+/// and an object per phase, which holds the bytes and the packets sent to and received from each
+/// peer.
+pub fn phases_to_json(party: usize, phases: &[PhaseStats]) -> String {
+    let summary = PhasesSummary {
+        party,
+        bytes_sent: phases.iter().flat_map(|phase| &phase.bytes_sent).sum(),
+        bytes_received: phases.iter().flat_map(|phase| &phase.bytes_received).sum(),
+        rounds: phases.iter().map(|phase| phase.rounds).sum(),
+        phases,
+    };
+    serde_json::to_string_pretty(&summary).expect("the phases serialize to JSON")
+}
+
+/// Writes the traffic of the phases of a party to a file, as JSON if it has the `.json`
+/// extension, or as CSV otherwise.
+pub fn write_phases(path: &Path, party: usize, phases: &[PhaseStats]) -> anyhow::Result<()> {
+    let metrics = if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        phases_to_json(party, phases)
+    } else {
+        phases_to_csv(party, phases)
+    };
+    fs::write(path, metrics)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use super::{phases_to_csv, phases_to_json, write_phases, NetworkStats, PhaseStats};
+
+    #[test]
+    fn write_phases_as_csv_and_json() {
+        let mut start = NetworkStats::new(2);
+        start.record_send(1, 10, Duration::ZERO);
+        let mut end = start.clone();
+        end.record_recv(1, Some(4), Duration::ZERO);
+        end.record_send(1, 6, Duration::ZERO);
+        end.record_send(0, 3, Duration::ZERO);
+
+        let phase = PhaseStats::between("sharing", Duration::from_micros(1500), &start, &end);
+        assert_eq!(phase.rounds, 1);
+        assert_eq!(phase.bytes_sent, vec![3, 6]);
+        assert_eq!(phase.bytes_received, vec![0, 4]);
+        assert_eq!(phase.packets_sent, vec![1, 1]);
+        assert_eq!(phase.packets_received, vec![0, 1]);
+        let json: serde_json::Value =
+            serde_json::from_str(&phases_to_json(1, std::slice::from_ref(&phase))).unwrap();
+        assert_eq!(json["party"], 1);
+        assert_eq!(json["bytes_sent"], 9);
+        assert_eq!(json["phases"][0]["name"], "sharing");
+        assert_eq!(json["phases"][0]["duration_ms"], 1.5);
+        assert_eq!(json["phases"][0]["packets_received"][1], 1);
+        assert_eq!(
+            phases_to_csv(1, &[phase]),
+            "party,phase,duration_ms,rounds,bytes_sent,bytes_received,bytes_sent_0,\
+             bytes_received_0,bytes_sent_1,bytes_received_1\n\
+             1,sharing,1.500,1,9,4,3,0,6,4\n"
+        );
+    }
+
+    #[test]
+    fn phases_are_written_in_the_format_of_the_extension() {
+        let stats = NetworkStats::new(1);
+        let phases = [PhaseStats::between(
+            "opening",
+            Duration::ZERO,
+            &stats,
+            &stats,
+        )];
+        let dir = std::env::temp_dir();
+        for (extension, expected) in [
+            ("json", phases_to_json(0, &phases)),
+            ("csv", phases_to_csv(0, &phases)),
+        ] {
+            let path = dir.join(format!(
+                "shami_rs_metrics_{}.{extension}",
+                std::process::id()
+            ));
+            write_phases(&path, 0, &phases).unwrap();
+            assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
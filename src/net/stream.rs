@@ -0,0 +1,168 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use thiserror::Error;
+
+use super::{Packet, PacketHeader};
+
+/// Protocol ID used to tag the chunks of a stream.
+pub const STREAM_PROTOCOL_ID: u16 = u16::MAX - 1;
+
+/// Possible errors that may appear while receiving a stream.
+#[derive(Debug, Error)]
+pub enum StreamError {
+    /// The packet received is not the next chunk of the stream.
+    #[error(
+        "expected the chunk {expected} of a stream, but received a packet for protocol {protocol_id} at round {round}"
+    )]
+    UnexpectedPacket {
+        expected: u32,
+        protocol_id: u16,
+        round: u32,
+    },
+
+    /// The stream has more chunks than the ones that can be numbered.
+    #[error("the stream has too many chunks")]
+    TooManyChunks,
+}
+
+/// Splits the bytes read from a reader into the chunks of a stream. Each chunk is a packet with
+/// at most the chunk size bytes, tagged with [`STREAM_PROTOCOL_ID`] and with its position in the
+/// stream as round. The stream ends with an empty chunk.
+pub struct StreamSender {
+    /// Maximum size in bytes of a chunk.
+    chunk_size: usize,
+    /// Position of the next chunk in the stream.
+    index: u32,
+    /// Number of bytes read so far.
+    bytes: u64,
+    /// Whether the chunk that ends the stream was already produced.
+    finished: bool,
+}
+
+impl StreamSender {
+    /// Creates a sender that splits the stream into chunks of at most `chunk_size` bytes.
+    pub fn new(chunk_size: usize) -> anyhow::Result<Self> {
+        if chunk_size == 0 {
+            return Err(
+                io::Error::new(ErrorKind::InvalidInput, "the chunk size is not correct").into(),
+            );
+        }
+        Ok(Self {
+            chunk_size,
+            index: 0,
+            bytes: 0,
+            finished: false,
+        })
+    }
+
+    /// Reads the next chunk of the stream from the reader. Returns `None` once the chunk that
+    /// ends the stream was produced.
+    pub fn next_chunk(&mut self, reader: &mut dyn Read) -> anyhow::Result<Option<Packet>> {
+        if self.finished {
+            return Ok(None);
+        }
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        reader
+            .take(self.chunk_size as u64)
+            .read_to_end(&mut chunk)?;
+        self.finished = chunk.is_empty();
+        self.bytes += chunk.len() as u64;
+        let packet = Packet::tagged(STREAM_PROTOCOL_ID, self.index, chunk);
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or(StreamError::TooManyChunks)?;
+        Ok(Some(packet))
+    }
+
+    /// Returns the number of bytes read so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+/// Reassembles the chunks produced by a [`StreamSender`], writing their bytes to a writer.
+#[derive(Default)]
+pub struct StreamReceiver {
+    /// Position of the next chunk in the stream.
+    index: u32,
+    /// Number of bytes written so far.
+    bytes: u64,
+}
+
+impl StreamReceiver {
+    /// Checks whether a packet may be a chunk of a stream.
+    pub fn is_chunk(header: &PacketHeader) -> bool {
+        header.protocol_id == STREAM_PROTOCOL_ID
+    }
+
+    /// Writes the bytes of the next chunk of the stream to the writer. Returns `false` if the
+    /// chunk ends the stream.
+    pub fn write_chunk(&mut self, packet: &Packet, writer: &mut dyn Write) -> anyhow::Result<bool> {
+        let header = packet.header();
+        if !Self::is_chunk(header) || header.round != self.index {
+            anyhow::bail!(StreamError::UnexpectedPacket {
+                expected: self.index,
+                protocol_id: header.protocol_id,
+                round: header.round,
+            });
+        }
+        self.index = self.index.wrapping_add(1);
+        if packet.size() == 0 {
+            writer.flush()?;
+            return Ok(false);
+        }
+        writer.write_all(packet.as_slice())?;
+        self.bytes += packet.size() as u64;
+        Ok(true)
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StreamError, StreamReceiver, StreamSender};
+    use crate::net::{
+        channel::{Channel, LoopBackChannel},
+        Packet,
+    };
+
+    #[test]
+    fn stream_is_split_and_reassembled() {
+        let payload: Vec<u8> = (0..10_000).map(|i| i as u8).collect();
+        let mut channel = LoopBackChannel::default();
+        let sent = channel.send_stream(&mut payload.as_slice(), 4096).unwrap();
+        assert_eq!(sent, payload.len() as u64);
+
+        let mut received = Vec::new();
+        let bytes = channel.recv_stream(&mut received).unwrap();
+        assert_eq!(bytes, payload.len() as u64);
+        assert_eq!(received, payload);
+    }
+
+    #[test]
+    fn reject_chunks_out_of_place() {
+        let mut sender = StreamSender::new(2).unwrap();
+        let mut reader: &[u8] = &[1, 2, 3];
+        let first = sender.next_chunk(&mut reader).unwrap().unwrap();
+        let second = sender.next_chunk(&mut reader).unwrap().unwrap();
+        assert_eq!(second.as_slice(), &[3]);
+
+        let mut receiver = StreamReceiver::default();
+        let mut sink = Vec::new();
+        let err = receiver.write_chunk(&second, &mut sink).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StreamError>(),
+            Some(StreamError::UnexpectedPacket { expected: 0, .. })
+        ));
+        assert!(receiver.write_chunk(&first, &mut sink).unwrap());
+        assert!(receiver
+            .write_chunk(&Packet::new(vec![0]), &mut sink)
+            .is_err());
+        assert!(StreamSender::new(0).is_err());
+    }
+}
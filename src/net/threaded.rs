@@ -0,0 +1,324 @@
+use std::{
+    collections::VecDeque,
+    future::{poll_fn, Future},
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, JoinHandle, Thread},
+};
+
+use super::{
+    channel::{Channel, ChannelError},
+    split::{spawn_worker, ChannelSender, Doorbell},
+    Packet,
+};
+
+/// Packets received by the background thread of an [`ThreadedChannel`] that were not awaited yet,
+/// together with the waker of the task that awaits the next one.
+#[derive(Default)]
+struct Inbox {
+    /// Packets received, or the error that ended the reception.
+    packets: VecDeque<anyhow::Result<Packet>>,
+    /// Waker of the task that awaits a packet.
+    waker: Option<Waker>,
+}
+
+/// Channel whose receives are futures, which are woken by the background thread that services
+/// the channel once a packet arrives. The thread sleeps until the channel is ready, as told by
+/// [`Channel::readiness`], or a packet is queued to be sent. The sockets are still blocking ones
+/// owned by the thread, not registered with the reactor of an asynchronous runtime, so every
+/// channel takes a thread. The receives from different peers are awaited at the same time
+/// instead of blocking one after the other. The sends are queued, as in the halves of a split
+/// channel, so they never wait for the peer.
+pub struct ThreadedChannel {
+    /// Sending half of the channel.
+    sender: ChannelSender,
+    /// Packets received by the background thread.
+    inbox: Arc<Mutex<Inbox>>,
+    /// Flag that tells the background thread that the channel was dropped.
+    dropped: Arc<AtomicBool>,
+    /// Doorbell that wakes the background thread once the channel is dropped.
+    doorbell: Doorbell,
+    /// Background thread that services the channel.
+    worker: JoinHandle<()>,
+}
+
+impl ThreadedChannel {
+    /// Moves the channel to a background thread and returns the interface whose receives are
+    /// futures. The thread shuts the channel down and finishes once the interface is dropped.
+    pub fn new(channel: Box<dyn Channel>) -> Self {
+        let inbox = Arc::new(Mutex::new(Inbox::default()));
+        let dropped = Arc::new(AtomicBool::new(false));
+        let worker_inbox = inbox.clone();
+        let worker_dropped = dropped.clone();
+        let deliver = move |result| {
+            let mut inbox = worker_inbox.lock().unwrap_or_else(PoisonError::into_inner);
+            inbox.packets.push_back(result);
+            if let Some(waker) = inbox.waker.take() {
+                waker.wake();
+            }
+            !worker_dropped.load(Ordering::Relaxed)
+        };
+        let (sender, worker) = spawn_worker(channel, deliver, dropped.clone());
+        let doorbell = sender.doorbell();
+        Self {
+            sender,
+            inbox,
+            dropped,
+            doorbell,
+            worker,
+        }
+    }
+
+    /// Queues a packet to be sent through the channel. An error produced while sending a
+    /// previous packet is returned by the next call.
+    pub async fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        self.sender.send(packet)
+    }
+
+    /// Receives a packet from the channel.
+    pub async fn recv(&mut self) -> anyhow::Result<Packet> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Polls for the next packet of the channel, registering the waker of the context to be
+    /// woken once it arrives.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<anyhow::Result<Packet>> {
+        let mut inbox = self.inbox.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(result) = inbox.packets.pop_front() {
+            return Poll::Ready(result);
+        }
+        // The thread delivers every packet before it finishes, so a finished thread with no
+        // packets left means that the channel is closed.
+        if self.worker.is_finished() {
+            return Poll::Ready(Err(ChannelError::ConnectionClosed.into()));
+        }
+        inbox.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for ThreadedChannel {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::Relaxed);
+        self.doorbell.ring();
+    }
+}
+
+/// Network whose sends and receives are futures, which lets a party await the packets of every
+/// peer at the same time instead of blocking on each peer in turn. The futures are completed by
+/// a thread per channel rather than by the sockets of a runtime, so they are awaited with
+/// [`block_on`] or with any executor that only relies on the wakers. It is obtained from a connected [`super::Network`] with [`super::Network::into_threaded`], which
+/// keeps the blocking interface for the protocols of the crate. As the halves of a split
+/// network, it does not collect traffic statistics or isolate the sessions.
+pub struct ThreadedNetwork {
+    /// ID of the party.
+    id: usize,
+    /// Channels with each peer.
+    channels: Vec<ThreadedChannel>,
+    /// Packets received from each peer before the conversion that were not delivered.
+    pending: Vec<VecDeque<Packet>>,
+    /// Session in which the packets are sent.
+    session: u32,
+}
+
+impl ThreadedNetwork {
+    /// Returns the ID of the party.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the number of parties, including this one.
+    pub fn n_parties(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Returns the channel with a given party, e.g. to await it together with other futures.
+    pub fn channel(&mut self, party_id: usize) -> &mut ThreadedChannel {
+        &mut self.channels[party_id]
+    }
+
+    /// Sends a packet of information to a given party.
+    pub async fn send_to(&mut self, packet: &Packet, party_id: usize) -> anyhow::Result<usize> {
+        let packet = packet.clone().in_session(self.session).sent_by(self.id);
+        self.channels[party_id].send(&packet).await
+    }
+
+    /// Send a packet to every party in the network.
+    pub async fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        let packet = packet.clone().in_session(self.session).sent_by(self.id);
+        let mut bytes_sent = 0;
+        for channel in &mut self.channels {
+            bytes_sent = channel.send(&packet).await?;
+        }
+        Ok(bytes_sent)
+    }
+
+    /// Receives a packet from a given party. Packets received before the conversion are
+    /// delivered first.
+    pub async fn recv_from(&mut self, party_id: usize) -> anyhow::Result<Packet> {
+        if let Some(packet) = self.pending[party_id].pop_front() {
+            return Ok(packet);
+        }
+        self.channels[party_id].recv().await
+    }
+
+    /// Receives a packet from every party, including this one, awaiting all of them at the same
+    /// time. The packets are returned in the order of the party IDs.
+    pub async fn recv_from_all(&mut self) -> anyhow::Result<Vec<Packet>> {
+        let mut packets: Vec<Option<Packet>> =
+            self.pending.iter_mut().map(VecDeque::pop_front).collect();
+        poll_fn(|cx| {
+            for (slot, channel) in packets.iter_mut().zip(&mut self.channels) {
+                if slot.is_none() {
+                    if let Poll::Ready(result) = channel.poll_recv(cx) {
+                        *slot = Some(result?);
+                    }
+                }
+            }
+            if packets.iter().all(Option::is_some) {
+                Poll::Ready(anyhow::Ok(()))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await?;
+        Ok(packets.into_iter().flatten().collect())
+    }
+
+    /// Receives the first packet that arrives from any party, and returns it together with the
+    /// ID of the party that sent it.
+    pub async fn recv_any(&mut self) -> anyhow::Result<(usize, Packet)> {
+        if let Some((party_id, packet)) = self
+            .pending
+            .iter_mut()
+            .enumerate()
+            .find_map(|(party_id, pending)| Some((party_id, pending.pop_front()?)))
+        {
+            return Ok((party_id, packet));
+        }
+        poll_fn(|cx| {
+            for (party_id, channel) in self.channels.iter_mut().enumerate() {
+                if let Poll::Ready(result) = channel.poll_recv(cx) {
+                    return Poll::Ready(result.map(|packet| (party_id, packet)));
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// Converts the channels of a network into a threaded network.
+pub(crate) fn threaded_network(
+    id: usize,
+    channels: Vec<Box<dyn Channel>>,
+    pending: Vec<VecDeque<Packet>>,
+    session: u32,
+) -> ThreadedNetwork {
+    ThreadedNetwork {
+        id,
+        channels: channels.into_iter().map(ThreadedChannel::new).collect(),
+        pending,
+        session,
+    }
+}
+
+/// Waker that resumes a thread parked by [`block_on`].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs a future to completion in the current thread, parking it while the future is pending,
+/// so that blocking code can await the threaded network.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{iter, sync::mpsc, thread};
+
+    use super::{block_on, ThreadedChannel};
+    use crate::net::{local::LocalChannel, Network, Packet};
+
+    #[test]
+    fn peers_are_awaited_at_the_same_time() {
+        const N_PARTIES: usize = 4;
+
+        // Each party sends once the next one has sent, so the packets arrive in reverse order of
+        // the IDs of the parties.
+        let (turns, waits): (Vec<_>, Vec<_>) = (0..N_PARTIES).map(|_| mpsc::channel()).unzip();
+        let previous_turns = iter::once(None).chain(turns.into_iter().map(Some));
+        let handles: Vec<_> = Network::create_local(N_PARTIES)
+            .into_iter()
+            .zip(waits.into_iter().zip(previous_turns))
+            .map(|(network, (wait, previous_turn))| {
+                thread::spawn(move || {
+                    let mut network = network.into_threaded();
+                    let id = network.id() as u8;
+                    block_on(async {
+                        if usize::from(id) + 1 < N_PARTIES {
+                            wait.recv().unwrap();
+                        }
+                        network.send(&Packet::new(vec![id])).await.unwrap();
+                        if let Some(previous_turn) = previous_turn {
+                            previous_turn.send(()).unwrap();
+                        }
+                        let packets = network.recv_from_all().await.unwrap();
+                        let values: Vec<u8> = packets.iter().map(|p| p.as_slice()[0]).collect();
+                        assert_eq!(values, [0, 1, 2, 3]);
+
+                        network.send_to(&Packet::new(vec![id]), 0).await.unwrap();
+                        if id == 0 {
+                            let mut senders: Vec<usize> = Vec::new();
+                            for _ in 0..N_PARTIES {
+                                let (party, packet) = network.recv_any().await.unwrap();
+                                assert_eq!(packet.as_slice(), &[party as u8]);
+                                senders.push(party);
+                            }
+                            senders.sort();
+                            assert_eq!(senders, [0, 1, 2, 3]);
+                            network.send(&Packet::new(vec![0])).await.unwrap();
+                        }
+                        // The parties wait for the first one before closing their channels, whose
+                        // closure would fail its receives.
+                        network.recv_from(0).await.unwrap();
+                    });
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn closed_channels_fail_the_receive() {
+        let (channel_a, channel_b) = LocalChannel::pair();
+        let mut channel = ThreadedChannel::new(Box::new(channel_a));
+        let peer = thread::spawn(move || {
+            let mut peer = ThreadedChannel::new(Box::new(channel_b));
+            // The packet queued is sent before the channel is closed.
+            block_on(peer.send(&Packet::new(vec![7]))).unwrap();
+        });
+        assert_eq!(block_on(channel.recv()).unwrap().as_slice(), &[7]);
+        peer.join().unwrap();
+        assert!(block_on(channel.recv()).is_err());
+    }
+}
@@ -0,0 +1,415 @@
+use std::{
+    io::{Error, ErrorKind},
+    sync::Arc,
+};
+
+use rustls::{
+    client::Resumption,
+    crypto::{aws_lc_rs, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::{NoServerSessionStorage, WebPkiClientVerifier},
+    version, ClientConfig, RootCertStore, ServerConfig, SupportedProtocolVersion,
+};
+use serde_json::Value;
+
+use super::{
+    identity::PeerIdentities,
+    pinning::{Fingerprint, PinnedCertVerifier},
+};
+
+/// Certificates and keys used to secure the connections between parties with mutual TLS.
+pub struct TlsConfig<'a> {
+    /// Root of trust certificates used to verify the certificates of the peers.
+    root_cert_store: RootCertStore,
+    /// Certificates presented to the peers, both as a server and as a client.
+    server_cert: Vec<CertificateDer<'a>>,
+    /// Private key associated with the presented certificate.
+    priv_key: PrivateKeyDer<'a>,
+    /// Identities used to derive the ID of a peer from its certificate.
+    pub(crate) identities: PeerIdentities,
+    /// Tunable parameters of the TLS connections.
+    options: TlsOptions,
+}
+
+/// Tunable parameters of the TLS connections, for deployments with compliance requirements.
+#[derive(Debug, Clone)]
+pub struct TlsOptions {
+    /// Cryptographic provider restricted to the allowed cipher suites.
+    provider: Arc<CryptoProvider>,
+    /// Allowed versions of the TLS protocol.
+    versions: Vec<&'static SupportedProtocolVersion>,
+    /// Protocols offered and accepted in the ALPN extension, in order of preference.
+    alpn_protocols: Vec<Vec<u8>>,
+    /// Whether the sessions can be resumed in later connections.
+    session_resumption: bool,
+}
+
+impl Default for TlsOptions {
+    fn default() -> Self {
+        Self {
+            provider: Arc::new(aws_lc_rs::default_provider()),
+            versions: rustls::DEFAULT_VERSIONS.to_vec(),
+            alpn_protocols: Vec::new(),
+            session_resumption: true,
+        }
+    }
+}
+
+impl TlsOptions {
+    /// Reads the parameters from the `tls` object of the network configuration. The parameters
+    /// that are not present keep the defaults of rustls.
+    pub(crate) fn from_json(json: &Value) -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        if json.is_null() {
+            return Ok(options);
+        }
+        if !json.is_object() {
+            return Err(
+                Error::new(ErrorKind::InvalidInput, "the TLS options are not correct").into(),
+            );
+        }
+
+        if !json["versions"].is_null() {
+            options.versions = parse_strings(&json["versions"], "TLS versions")?
+                .iter()
+                .map(|name| match name.as_str() {
+                    "1.2" => Ok(&version::TLS12),
+                    "1.3" => Ok(&version::TLS13),
+                    _ => Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("the TLS version {name} is not supported"),
+                    )),
+                })
+                .collect::<Result<_, _>>()?;
+        }
+
+        if !json["cipher_suites"].is_null() {
+            let names = parse_strings(&json["cipher_suites"], "cipher suites")?;
+            let mut provider = aws_lc_rs::default_provider();
+            let available = std::mem::take(&mut provider.cipher_suites);
+            for name in names {
+                let suite = available
+                    .iter()
+                    .find(|suite| format!("{:?}", suite.suite()) == name)
+                    .ok_or(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("the cipher suite {name} is not supported"),
+                    ))?;
+                provider.cipher_suites.push(*suite);
+            }
+            options.provider = Arc::new(provider);
+        }
+
+        if !json["alpn"].is_null() {
+            options.alpn_protocols = parse_strings(&json["alpn"], "ALPN protocols")?
+                .into_iter()
+                .map(String::into_bytes)
+                .collect();
+        }
+
+        if !json["session_resumption"].is_null() {
+            options.session_resumption = json["session_resumption"].as_bool().ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                "the session resumption option is not correct",
+            ))?;
+        }
+        Ok(options)
+    }
+
+    /// Applies the parameters that are not set by the builder to a client configuration.
+    fn apply_to_client(&self, client_conf: &mut ClientConfig) {
+        client_conf.alpn_protocols = self.alpn_protocols.clone();
+        if !self.session_resumption {
+            client_conf.resumption = Resumption::disabled();
+        }
+    }
+
+    /// Applies the parameters that are not set by the builder to a server configuration.
+    fn apply_to_server(&self, server_conf: &mut ServerConfig) {
+        server_conf.alpn_protocols = self.alpn_protocols.clone();
+        if !self.session_resumption {
+            server_conf.session_storage = Arc::new(NoServerSessionStorage {});
+            server_conf.send_tls13_tickets = 0;
+        }
+    }
+}
+
+/// Parses a non-empty array of strings of the configuration.
+fn parse_strings(json: &Value, name: &str) -> anyhow::Result<Vec<String>> {
+    let strings: Option<Vec<String>> = json.as_array().and_then(|array| {
+        array
+            .iter()
+            .map(|value| value.as_str().map(str::to_string))
+            .collect()
+    });
+    match strings {
+        Some(strings) if !strings.is_empty() => Ok(strings),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("the list of {name} is not correct"),
+        )
+        .into()),
+    }
+}
+
+impl TlsConfig<'static> {
+    /// Reads the TLS configuration from the JSON of the network configuration for a network with
+    /// the given number of parties.
+    pub(crate) fn from_json(json: &Value, n_parties: usize) -> anyhow::Result<Self> {
+        // Get the certificate chain presented to the peers and its private key.
+        let server_cert_path = json["server_cert"].as_str().ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "the path of the server certificate is not correct",
+        ))?;
+        let server_cert = load_certs(server_cert_path)?;
+        let priv_key_path = json["priv_key"].as_str().ok_or(Error::new(
+            ErrorKind::InvalidInput,
+            "the path of the private key is not correct",
+        ))?;
+        let priv_key = PrivateKeyDer::from_pem_file(priv_key_path).map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("the private key in {priv_key_path} is not correct: {err}"),
+            )
+        })?;
+
+        // Get trusted certificates. They can be given as a list of files, as a single bundle
+        // file, or both.
+        let mut trusted_certs = Vec::new();
+        if !json["trusted_certs"].is_null() {
+            let trusted_certs_json = json["trusted_certs"].as_array().ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                "the array of trusted certificates is not correct",
+            ))?;
+            for trusted_cert in trusted_certs_json {
+                let trusted_cert_path = trusted_cert.as_str().ok_or(Error::new(
+                    ErrorKind::InvalidInput,
+                    "the path of a trusted certificate is not correct",
+                ))?;
+                trusted_certs.extend(load_certs(trusted_cert_path)?);
+            }
+        }
+        if !json["ca_bundle"].is_null() {
+            let ca_bundle_path = json["ca_bundle"].as_str().ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                "the path of the CA bundle is not correct",
+            ))?;
+            trusted_certs.extend(load_certs(ca_bundle_path)?);
+        }
+
+        // Get the certificate of each peer, if they are given. The certificates are trusted as
+        // well, which allows the peers to use self-signed certificates.
+        let identities = match json["peer_certs"].as_array() {
+            Some(peer_certs_json) => {
+                let mut peer_certs = Vec::new();
+                for peer_cert in peer_certs_json {
+                    let peer_cert_path = peer_cert.as_str().ok_or(Error::new(
+                        ErrorKind::InvalidInput,
+                        "the certificate path of a peer is not correct",
+                    ))?;
+                    let peer_cert = load_certs(peer_cert_path)?.swap_remove(0);
+                    peer_certs.push(peer_cert);
+                }
+                if peer_certs.len() != n_parties {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "the number of peer certificates does not match the number of peers",
+                    )
+                    .into());
+                }
+                trusted_certs.extend(peer_certs.iter().cloned());
+                PeerIdentities::from_certs(peer_certs)
+            }
+            None => PeerIdentities::from_subject_names(),
+        };
+
+        // Get the pinned fingerprint of the certificate of each peer, if they are given. In that
+        // case, the certificates are verified by their fingerprint instead of by a CA.
+        let identities = match json["peer_fingerprints"].as_array() {
+            Some(fingerprints_json) => {
+                if !json["peer_certs"].is_null() {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "the peer certificates and the peer fingerprints can not be used together",
+                    )
+                    .into());
+                }
+                let mut fingerprints = Vec::new();
+                for fingerprint in fingerprints_json {
+                    let fingerprint = fingerprint.as_str().ok_or(Error::new(
+                        ErrorKind::InvalidInput,
+                        "the fingerprint of a peer is not correct",
+                    ))?;
+                    fingerprints.push(Fingerprint::parse(fingerprint)?);
+                }
+                if fingerprints.len() != n_parties {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "the number of peer fingerprints does not match the number of peers",
+                    )
+                    .into());
+                }
+                PeerIdentities::from_fingerprints(fingerprints)
+            }
+            None => identities,
+        };
+
+        let mut root_cert_store = RootCertStore::empty();
+        let (certs_added, certs_ignored) = root_cert_store.add_parsable_certificates(trusted_certs);
+        log::info!("added {certs_added} certificates, ignored {certs_ignored} certificates to the root certificate store");
+
+        // The public web PKI can be trusted as well, for deployments with publicly issued
+        // certificates.
+        if json["webpki_roots"].as_bool().unwrap_or(false) {
+            root_cert_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            log::info!("added the web PKI roots to the root certificate store");
+        }
+        // With a coordinator, the certificates of the peers are trusted once the roster is
+        // received.
+        if root_cert_store.is_empty()
+            && identities.pinned_fingerprints().is_none()
+            && json["coordinator"].is_null()
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "there are no trusted certificates to verify the peers",
+            )
+            .into());
+        }
+
+        Ok(Self {
+            root_cert_store,
+            server_cert,
+            priv_key,
+            identities,
+            options: TlsOptions::from_json(&json["tls"])?,
+        })
+    }
+
+    /// Configures mutual TLS: both sides of a connection present their certificate and verify
+    /// the certificate of the other side, either against the root certificate store or against
+    /// the pinned fingerprints.
+    pub(crate) fn configure(&self) -> anyhow::Result<(ClientConfig, ServerConfig)> {
+        let options = &self.options;
+        let client_builder = ClientConfig::builder_with_provider(options.provider.clone())
+            .with_protocol_versions(&options.versions)?;
+        let server_builder = ServerConfig::builder_with_provider(options.provider.clone())
+            .with_protocol_versions(&options.versions)?;
+
+        let (mut client_conf, mut server_conf) =
+            if let Some(fingerprints) = self.identities.pinned_fingerprints() {
+                let verifier = Arc::new(PinnedCertVerifier::new(fingerprints.to_vec()));
+                let client_conf = client_builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(verifier.clone())
+                    .with_client_auth_cert(self.server_cert.clone(), self.priv_key.clone_key())?;
+                let server_conf = server_builder
+                    .with_client_cert_verifier(verifier)
+                    .with_single_cert(self.server_cert.clone(), self.priv_key.clone_key())?;
+                (client_conf, server_conf)
+            } else {
+                // Configure the client TLS
+                let client_conf = client_builder
+                    .with_root_certificates(self.root_cert_store.clone())
+                    .with_client_auth_cert(self.server_cert.clone(), self.priv_key.clone_key())?;
+
+                let client_verifier = WebPkiClientVerifier::builder_with_provider(
+                    Arc::new(self.root_cert_store.clone()),
+                    options.provider.clone(),
+                )
+                .build()?;
+                let server_conf = server_builder
+                    .with_client_cert_verifier(client_verifier)
+                    .with_single_cert(self.server_cert.clone(), self.priv_key.clone_key())?;
+                (client_conf, server_conf)
+            };
+        options.apply_to_client(&mut client_conf);
+        options.apply_to_server(&mut server_conf);
+
+        Ok((client_conf, server_conf))
+    }
+}
+
+impl<'a> TlsConfig<'a> {
+    /// Returns the certificate presented to the peers.
+    pub(crate) fn own_cert(&self) -> &CertificateDer<'a> {
+        &self.server_cert[0]
+    }
+
+    /// Trusts the certificate of each peer, indexed by party ID, and identifies the peers by
+    /// these certificates.
+    pub(crate) fn trust_peer_certs(&mut self, peer_certs: Vec<CertificateDer<'static>>) {
+        let (certs_added, certs_ignored) = self
+            .root_cert_store
+            .add_parsable_certificates(peer_certs.iter().cloned());
+        log::info!("added {certs_added} peer certificates, ignored {certs_ignored} peer certificates to the root certificate store");
+        self.identities = PeerIdentities::from_certs(peer_certs);
+    }
+}
+
+/// Loads the certificates in a PEM file. The file must contain at least one certificate.
+fn load_certs(path: &str) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("the certificates in {path} are not correct: {err}"),
+            )
+        })?;
+    if certs.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("there are no certificates in {path}"),
+        )
+        .into());
+    }
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use rustls::version;
+    use serde_json::json;
+
+    use super::{load_certs, TlsOptions};
+
+    #[test]
+    fn parse_tls_options() {
+        let options = TlsOptions::from_json(&json!({
+            "versions": ["1.3"],
+            "cipher_suites": ["TLS13_AES_256_GCM_SHA384", "TLS13_CHACHA20_POLY1305_SHA256"],
+            "alpn": ["shami/1"],
+            "session_resumption": false
+        }))
+        .unwrap();
+        assert_eq!(options.versions, vec![&version::TLS13]);
+        assert_eq!(options.provider.cipher_suites.len(), 2);
+        assert_eq!(options.alpn_protocols, vec![b"shami/1".to_vec()]);
+        assert!(!options.session_resumption);
+
+        let defaults = TlsOptions::from_json(&serde_json::Value::Null).unwrap();
+        assert!(defaults.session_resumption);
+        assert_eq!(defaults.versions.len(), 2);
+
+        for invalid in [
+            json!({"versions": ["1.1"]}),
+            json!({"versions": []}),
+            json!({"cipher_suites": ["TLS_NULL_WITH_NULL_NULL"]}),
+            json!({"alpn": "shami/1"}),
+            json!({"session_resumption": "no"}),
+        ] {
+            assert!(TlsOptions::from_json(&invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn load_certs_rejects_missing_or_empty_files() {
+        assert!(load_certs("./does_not_exist.crt").is_err());
+
+        let path = std::env::temp_dir().join("shami_rs_empty_cert.crt");
+        std::fs::write(&path, "").unwrap();
+        assert!(load_certs(path.to_str().unwrap()).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use serde::Deserialize;
+
+/// Pattern in which the packets of the broadcasts travel among the parties. In the mesh, every
+/// party sends its packet to every other party, so a party sends `n - 1` copies of every
+/// broadcast and every connection carries traffic. In the ring and the tree, the parties forward
+/// the packets of the others, so every party only talks to its neighbours and the root of a
+/// broadcast sends one or two copies. A party that forwards a packet could alter it, so these
+/// topologies rely on the forwarding parties, unlike the mesh, in which every packet travels
+/// through the authenticated channel of its sender.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Topology {
+    /// Every party sends its packets directly to every other party.
+    #[default]
+    Mesh,
+    /// The parties form a ring, in which every party receives from the previous party and
+    /// forwards to the next one.
+    Ring,
+    /// The parties form a binary tree, in which the packets travel up to the root and down to
+    /// the leaves.
+    Tree,
+}
+
+/// Returns the party that follows the given one in the ring.
+pub(crate) fn ring_next(id: usize, n_parties: usize) -> usize {
+    (id + 1) % n_parties
+}
+
+/// Returns the party that precedes the given one in the ring.
+pub(crate) fn ring_previous(id: usize, n_parties: usize) -> usize {
+    (id + n_parties - 1) % n_parties
+}
+
+/// Returns the position of a party in the ring or the tree rooted at `root`.
+pub(crate) fn position(id: usize, root: usize, n_parties: usize) -> usize {
+    (id + n_parties - root) % n_parties
+}
+
+/// Returns the parent of a party in the binary tree rooted at `root`, where the party at the
+/// position `p` has the children at the positions `2p + 1` and `2p + 2`, or `None` for the root.
+pub(crate) fn tree_parent(id: usize, root: usize, n_parties: usize) -> Option<usize> {
+    match position(id, root, n_parties) {
+        0 => None,
+        p => Some(((p - 1) / 2 + root) % n_parties),
+    }
+}
+
+/// Returns the children of a party in the binary tree rooted at `root`.
+pub(crate) fn tree_children(id: usize, root: usize, n_parties: usize) -> Vec<usize> {
+    let p = position(id, root, n_parties);
+    [2 * p + 1, 2 * p + 2]
+        .into_iter()
+        .filter(|child| *child < n_parties)
+        .map(|child| (child + root) % n_parties)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ring_next, ring_previous, tree_children, tree_parent, Topology};
+
+    #[test]
+    fn trees_span_every_party() {
+        for n_parties in 1..10 {
+            for root in 0..n_parties {
+                for id in 0..n_parties {
+                    for child in tree_children(id, root, n_parties) {
+                        assert_eq!(tree_parent(child, root, n_parties), Some(id));
+                    }
+                    // Every party reaches the root by going up.
+                    let mut ancestor = id;
+                    for _ in 0..n_parties {
+                        ancestor = tree_parent(ancestor, root, n_parties).unwrap_or(ancestor);
+                    }
+                    assert_eq!(ancestor, root);
+                }
+                assert_eq!(tree_parent(root, root, n_parties), None);
+            }
+            assert_eq!(ring_previous(ring_next(0, n_parties), n_parties), 0);
+        }
+        assert_eq!(tree_children(2, 2, 5), [3, 4]);
+        assert_eq!(tree_children(3, 2, 5), [0, 1]);
+        assert_eq!(
+            serde_json::from_str::<Topology>(r#""tree""#).unwrap(),
+            Topology::Tree
+        );
+        assert!(serde_json::from_str::<Topology>(r#""star""#).is_err());
+    }
+}
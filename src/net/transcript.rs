@@ -0,0 +1,532 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use thiserror::Error;
+
+use super::{
+    channel::{Channel, ChannelError, Readiness},
+    Packet, PacketHeader,
+};
+
+/// Bytes at the beginning of every transcript file.
+const TRANSCRIPT_MAGIC: &[u8; 4] = b"SHTR";
+/// Version of the format of the transcript files.
+const TRANSCRIPT_VERSION: u8 = 4;
+/// Size in bytes of the beginning of a transcript file: the magic bytes, the version and the ID
+/// of the party that recorded it.
+const TRANSCRIPT_PREFIX_SIZE: usize = TRANSCRIPT_MAGIC.len() + 1 + 4;
+/// Size in bytes of the fields of a record that precede the phase and the payload: the
+/// direction, the peer ID, the timestamp, the packet header, the payload length and the phase
+/// length.
+const RECORD_PREFIX_SIZE: usize = 1 + 4 + 8 + PacketHeader::SIZE + 4 + 2;
+
+/// Possible errors that may appear while reading a transcript.
+#[derive(Debug, Error)]
+pub enum TranscriptError {
+    /// The file does not start with the bytes of a transcript.
+    #[error("the file is not a transcript")]
+    NotATranscript,
+
+    /// The transcript was written with an unknown version of the format.
+    #[error("unsupported transcript version {0}, expected version {TRANSCRIPT_VERSION}")]
+    UnsupportedVersion(u8),
+
+    /// A record has an unknown direction.
+    #[error("invalid direction {0} in a transcript record")]
+    InvalidDirection(u8),
+
+    /// The protocol sent a packet that was not sent when the transcript was recorded.
+    #[error("the execution diverged from the transcript: {0}")]
+    Diverged(String),
+}
+
+/// Direction in which a recorded packet travelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The party sent the packet to the peer.
+    Sent,
+    /// The party received the packet from the peer.
+    Received,
+}
+
+/// Packet recorded in a transcript.
+#[derive(Debug, Clone)]
+pub struct Record {
+    /// Direction in which the packet travelled.
+    pub direction: Direction,
+    /// ID of the peer that received or sent the packet.
+    pub peer_id: usize,
+    /// Time elapsed since the UNIX epoch when the packet was recorded.
+    pub timestamp: Duration,
+    /// Phase of the protocol in progress when the packet was recorded, as started with
+    /// [`Network::begin_phase`](super::Network::begin_phase), if any.
+    pub phase: Option<String>,
+    /// Packet that was sent or received.
+    pub packet: Packet,
+}
+
+/// Writes the records of the traffic of a party to a transcript file. The file starts with the
+/// magic bytes `SHTR`, the version of the format and the ID of the party, followed by the
+/// records. Each record contains the direction, the peer ID, the timestamp in microseconds, the
+/// header of the packet, the lengths of the payload and of the name of the phase, the name of
+/// the phase, which is empty outside the phases, and the payload, where the integers are
+/// big-endian.
+pub struct TranscriptWriter {
+    /// File in which the records are written.
+    writer: BufWriter<File>,
+    /// Phase of the protocol in progress, with which the packets are tagged.
+    phase: Option<String>,
+}
+
+impl TranscriptWriter {
+    /// Creates the transcript file of the party with the given ID, replacing the file if it
+    /// exists.
+    pub fn create(path: &Path, local_id: usize) -> anyhow::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(TRANSCRIPT_MAGIC)?;
+        writer.write_all(&[TRANSCRIPT_VERSION])?;
+        writer.write_all(&u32::try_from(local_id)?.to_be_bytes())?;
+        Ok(Self {
+            writer,
+            phase: None,
+        })
+    }
+
+    /// Returns the phase of the protocol in progress.
+    pub fn phase(&self) -> Option<&str> {
+        self.phase.as_deref()
+    }
+
+    /// Sets the phase of the protocol in progress, with which the packets recorded from now on
+    /// are tagged.
+    pub fn set_phase(&mut self, phase: Option<&str>) {
+        self.phase = phase.map(String::from);
+    }
+
+    /// Appends a record to the transcript.
+    pub fn write(&mut self, record: &Record) -> anyhow::Result<()> {
+        let payload = record.packet.as_slice();
+        let phase = record.phase.as_deref().unwrap_or_default().as_bytes();
+        let mut bytes = Vec::with_capacity(RECORD_PREFIX_SIZE + phase.len() + payload.len());
+        bytes.push(match record.direction {
+            Direction::Sent => 0,
+            Direction::Received => 1,
+        });
+        bytes.extend_from_slice(&u32::try_from(record.peer_id)?.to_be_bytes());
+        let micros = u64::try_from(record.timestamp.as_micros())?;
+        bytes.extend_from_slice(&micros.to_be_bytes());
+        bytes.extend(bincode::serialize(record.packet.header())?);
+        bytes.extend_from_slice(&u32::try_from(payload.len())?.to_be_bytes());
+        bytes.extend_from_slice(&u16::try_from(phase.len())?.to_be_bytes());
+        bytes.extend_from_slice(phase);
+        bytes.extend_from_slice(payload);
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Writes the buffered records to the file.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// Transcript shared by the channels of a network.
+pub type SharedTranscript = Arc<Mutex<TranscriptWriter>>;
+
+/// Reads the records of a transcript written by a [`TranscriptWriter`].
+pub struct TranscriptReader<R> {
+    /// Source of the bytes of the transcript.
+    reader: R,
+    /// ID of the party that recorded the transcript.
+    local_id: usize,
+}
+
+impl TranscriptReader<BufReader<File>> {
+    /// Opens a transcript file.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> TranscriptReader<R> {
+    /// Creates a reader of the transcript in `reader`, checking that it starts with a supported
+    /// version of the format.
+    pub fn new(mut reader: R) -> anyhow::Result<Self> {
+        let mut prefix = [0; TRANSCRIPT_PREFIX_SIZE];
+        reader.read_exact(&mut prefix)?;
+        if &prefix[..TRANSCRIPT_MAGIC.len()] != TRANSCRIPT_MAGIC {
+            anyhow::bail!(TranscriptError::NotATranscript);
+        }
+        let version = prefix[TRANSCRIPT_MAGIC.len()];
+        if version != TRANSCRIPT_VERSION {
+            anyhow::bail!(TranscriptError::UnsupportedVersion(version));
+        }
+        let mut id_bytes = [0; 4];
+        id_bytes.copy_from_slice(&prefix[TRANSCRIPT_MAGIC.len() + 1..]);
+        Ok(Self {
+            reader,
+            local_id: u32::from_be_bytes(id_bytes) as usize,
+        })
+    }
+
+    /// Returns the ID of the party that recorded the transcript.
+    pub fn local_id(&self) -> usize {
+        self.local_id
+    }
+
+    /// Reads the next record of the transcript. Returns `None` at the end of the transcript.
+    pub fn read_record(&mut self) -> anyhow::Result<Option<Record>> {
+        let mut prefix = [0; RECORD_PREFIX_SIZE];
+        match self.reader.read_exact(&mut prefix[..1]) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        self.reader.read_exact(&mut prefix[1..])?;
+
+        let direction = match prefix[0] {
+            0 => Direction::Sent,
+            1 => Direction::Received,
+            direction => anyhow::bail!(TranscriptError::InvalidDirection(direction)),
+        };
+        let mut peer_id = [0; 4];
+        peer_id.copy_from_slice(&prefix[1..5]);
+        let mut micros = [0; 8];
+        micros.copy_from_slice(&prefix[5..13]);
+        let header_end = 13 + PacketHeader::SIZE;
+        let header: PacketHeader = bincode::deserialize(&prefix[13..header_end])?;
+        let mut payload_len = [0; 4];
+        payload_len.copy_from_slice(&prefix[header_end..header_end + 4]);
+        let mut phase_len = [0; 2];
+        phase_len.copy_from_slice(&prefix[header_end + 4..]);
+
+        let mut phase = vec![0; u16::from_be_bytes(phase_len) as usize];
+        self.reader.read_exact(&mut phase)?;
+        let mut payload = vec![0; u32::from_be_bytes(payload_len) as usize];
+        self.reader.read_exact(&mut payload)?;
+        Ok(Some(Record {
+            direction,
+            peer_id: u32::from_be_bytes(peer_id) as usize,
+            timestamp: Duration::from_micros(u64::from_be_bytes(micros)),
+            phase: (!phase.is_empty())
+                .then(|| String::from_utf8(phase))
+                .transpose()?,
+            packet: Packet::with_header(header, payload),
+        }))
+    }
+}
+
+impl<R: Read> Iterator for TranscriptReader<R> {
+    type Item = anyhow::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Channel that records every packet sent and received through another channel in a
+/// transcript, so that the traffic of a failed execution can be inspected offline.
+pub struct RecordingChannel<C> {
+    /// Channel that actually transmits the packets.
+    inner: C,
+    /// ID of the peer at the other side of the channel.
+    peer_id: usize,
+    /// Transcript in which the packets are recorded.
+    transcript: SharedTranscript,
+}
+
+impl<C: Channel> RecordingChannel<C> {
+    /// Creates a channel that records the traffic of `inner` with the peer `peer_id`.
+    pub fn new(inner: C, peer_id: usize, transcript: SharedTranscript) -> Self {
+        Self {
+            inner,
+            peer_id,
+            transcript,
+        }
+    }
+
+    /// Appends a packet to the transcript. A failure to record is logged instead of returned,
+    /// so that recording never changes the outcome of the protocol.
+    fn record(&self, direction: Direction, packet: &Packet) {
+        let mut transcript = self
+            .transcript
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let record = Record {
+            direction,
+            peer_id: self.peer_id,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            phase: transcript.phase().map(String::from),
+            packet: packet.clone(),
+        };
+        let result = transcript.write(&record);
+        if let Err(err) = result {
+            log::warn!("error recording a packet of peer {}: {err}", self.peer_id);
+        }
+    }
+
+    /// Writes the buffered records to the transcript file.
+    fn flush_transcript(&self) {
+        let result = self
+            .transcript
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .flush();
+        if let Err(err) = result {
+            log::warn!("error writing the transcript: {err}");
+        }
+    }
+}
+
+impl<C: Channel> Channel for RecordingChannel<C> {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.flush_transcript();
+        self.inner.shutdown()
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.flush_transcript();
+        self.inner.flush()
+    }
+
+    fn buffered(&self) -> usize {
+        self.inner.buffered()
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.inner.set_max_packet_size(max_packet_size)
+    }
+
+    fn readiness(&self) -> Readiness {
+        self.inner.readiness()
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        self.inner.rekey()
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        self.inner.export_keying_material(label, output)
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        let bytes = self.inner.send(packet)?;
+        self.record(Direction::Sent, packet);
+        Ok(bytes)
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        let packet = self.inner.recv()?;
+        self.record(Direction::Received, &packet);
+        Ok(packet)
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        let packet = self.inner.recv_timeout(timeout)?;
+        self.record(Direction::Received, &packet);
+        Ok(packet)
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        let packet = self.inner.try_recv()?;
+        if let Some(packet) = &packet {
+            self.record(Direction::Received, packet);
+        }
+        Ok(packet)
+    }
+}
+
+/// Channel that replays the traffic with a peer recorded in a transcript in place of the real
+/// network. The received packets are the ones recorded from the peer, in the same order. The
+/// packets sent must have the same headers as the recorded ones, such that a divergence from the
+/// recorded execution is detected, but their payloads are not compared because they may depend on
+/// randomness.
+pub struct ReplayChannel {
+    /// ID of the peer whose traffic is replayed.
+    peer_id: usize,
+    /// Packets that were received from the peer, in order.
+    received: VecDeque<Packet>,
+    /// Packets that were sent to the peer, in order.
+    sent: VecDeque<Packet>,
+}
+
+impl ReplayChannel {
+    /// Creates a channel that replays the records of the traffic with the peer `peer_id`.
+    pub fn new(peer_id: usize, records: impl IntoIterator<Item = Record>) -> Self {
+        let mut received = VecDeque::new();
+        let mut sent = VecDeque::new();
+        for record in records {
+            if record.peer_id != peer_id {
+                continue;
+            }
+            match record.direction {
+                Direction::Received => received.push_back(record.packet),
+                Direction::Sent => sent.push_back(record.packet),
+            }
+        }
+        Self {
+            peer_id,
+            received,
+            sent,
+        }
+    }
+
+    /// Loads the transcript at the given path and creates a replay channel for each peer,
+    /// indexed by peer ID. Returns the ID of the party that recorded the transcript and the
+    /// channels.
+    pub fn load_all(path: &Path) -> anyhow::Result<(usize, Vec<Self>)> {
+        let reader = TranscriptReader::open(path)?;
+        let local_id = reader.local_id();
+        let records: Vec<Record> = reader.collect::<anyhow::Result<_>>()?;
+        let n_parties = records
+            .iter()
+            .map(|record| record.peer_id + 1)
+            .chain([local_id + 1])
+            .max()
+            .unwrap_or_default();
+        let channels = (0..n_parties)
+            .map(|peer_id| Self::new(peer_id, records.iter().cloned()))
+            .collect();
+        Ok((local_id, channels))
+    }
+
+    /// Returns the next recorded packet received from the peer.
+    fn next_received(&mut self) -> anyhow::Result<Packet> {
+        self.received.pop_front().ok_or_else(|| {
+            log::warn!(
+                "the transcript has no more packets from peer {}",
+                self.peer_id
+            );
+            ChannelError::ConnectionClosed.into()
+        })
+    }
+}
+
+impl Channel for ReplayChannel {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        let expected = self.sent.pop_front().ok_or_else(|| {
+            TranscriptError::Diverged(format!("unexpected packet sent to peer {}", self.peer_id))
+        })?;
+        let (header, expected_header) = (packet.header(), expected.header());
+        if header.session != expected_header.session
+            || header.protocol_id != expected_header.protocol_id
+            || header.round != expected_header.round
+            || header.sub_channel != expected_header.sub_channel
+        {
+            anyhow::bail!(TranscriptError::Diverged(format!(
+                "packet sent to peer {} has the header {header:?}, but the recorded one has {expected_header:?}",
+                self.peer_id
+            )));
+        }
+        Ok(packet.size())
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        self.next_received()
+    }
+
+    fn recv_timeout(&mut self, _timeout: Duration) -> anyhow::Result<Packet> {
+        self.next_received()
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        Ok(self.received.pop_front())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{
+        Direction, RecordingChannel, ReplayChannel, TranscriptError, TranscriptReader,
+        TranscriptWriter,
+    };
+    use crate::net::{channel::Channel, local::LocalChannel, Packet};
+
+    #[test]
+    fn recorded_traffic_can_be_read_back() {
+        let path =
+            std::env::temp_dir().join(format!("shami_rs_transcript_{}.bin", std::process::id()));
+        let transcript = Arc::new(Mutex::new(TranscriptWriter::create(&path, 3).unwrap()));
+        let (channel_a, mut channel_b) = LocalChannel::pair();
+        let mut channel_a = RecordingChannel::new(channel_a, 5, transcript.clone());
+
+        transcript.lock().unwrap().set_phase(Some("offline"));
+        channel_a
+            .send(&Packet::tagged(7, 2, vec![1, 2, 3]))
+            .unwrap();
+        transcript.lock().unwrap().set_phase(None);
+        channel_b.send(&Packet::new(vec![4, 5])).unwrap();
+        assert_eq!(channel_b.recv().unwrap().as_slice(), &[1, 2, 3]);
+        assert_eq!(channel_a.recv().unwrap().as_slice(), &[4, 5]);
+        channel_a.flush().unwrap();
+
+        let reader = TranscriptReader::open(&path).unwrap();
+        assert_eq!(reader.local_id(), 3);
+        let records: Vec<_> = reader.collect::<anyhow::Result<_>>().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, Direction::Sent);
+        assert_eq!(records[0].peer_id, 5);
+        assert!(records[0].packet.header().matches(7, 2));
+        assert_eq!(records[0].packet.as_slice(), &[1, 2, 3]);
+        assert_eq!(records[0].phase.as_deref(), Some("offline"));
+        assert_eq!(records[1].direction, Direction::Received);
+        assert_eq!(records[1].phase, None);
+        assert_eq!(records[1].packet.as_slice(), &[4, 5]);
+        assert!(records[0].timestamp <= records[1].timestamp);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn replay_recorded_traffic() {
+        let path = std::env::temp_dir().join(format!("shami_rs_replay_{}.bin", std::process::id()));
+        let transcript = Arc::new(Mutex::new(TranscriptWriter::create(&path, 0).unwrap()));
+        let (channel_a, mut channel_b) = LocalChannel::pair();
+        let mut channel_a = RecordingChannel::new(channel_a, 1, transcript);
+        channel_b.send(&Packet::tagged(1, 0, vec![9])).unwrap();
+        channel_a.recv().unwrap();
+        channel_a.send(&Packet::tagged(1, 1, vec![8])).unwrap();
+        channel_a.flush().unwrap();
+
+        let (local_id, mut channels) = ReplayChannel::load_all(&path).unwrap();
+        assert_eq!(local_id, 0);
+        assert_eq!(channels.len(), 2);
+        let mut replay = channels.pop().unwrap();
+        let packet = replay.recv().unwrap();
+        assert!(packet.header().matches(1, 0));
+        assert_eq!(packet.as_slice(), &[9]);
+        // The payload of a sent packet may differ, but its header must match.
+        replay.send(&Packet::tagged(1, 1, vec![7])).unwrap();
+        assert!(replay.try_recv().unwrap().is_none());
+
+        let (_, mut channels) = ReplayChannel::load_all(&path).unwrap();
+        let err = channels[1]
+            .send(&Packet::tagged(2, 0, vec![8]))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TranscriptError>(),
+            Some(TranscriptError::Diverged(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reject_files_that_are_not_transcripts() {
+        assert!(TranscriptReader::new(&b"NOPE\x02\0\0\0\0"[..]).is_err());
+        assert!(TranscriptReader::new(&b"SHTR\x03\0\0\0\0"[..]).is_err());
+        assert!(TranscriptReader::new(&b"SHTR\x04\0\0\0\x02"[..]).is_ok());
+    }
+}
@@ -0,0 +1,614 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    io,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use super::{
+    backoff::Backoff,
+    channel::{
+        self, decode_packet, decode_packet_into, encode_packet, is_timeout, Channel, ChannelError,
+        RecvBuffer,
+    },
+    Packet, PacketHeader, PeerEndpoint,
+};
+
+/// Maximum number of bytes of an encoded packet carried by a single datagram, chosen so that the
+/// datagrams fit in the MTU of common links without IP fragmentation.
+const MAX_FRAGMENT_SIZE: usize = 1200;
+/// Size in bytes of the header of a datagram: the kind and the sequence number.
+const DATAGRAM_HEADER_SIZE: usize = 1 + 8;
+/// Maximum number of datagrams sent that may wait for their acknowledgement.
+const WINDOW_SIZE: u64 = 64;
+/// Time after which a datagram that was not acknowledged is sent again. The time doubles with
+/// each retransmission of the same datagram, up to [`MAX_RETRANSMISSION_TIMEOUT`].
+const RETRANSMISSION_TIMEOUT: Duration = Duration::from_millis(100);
+/// Maximum time between two retransmissions of the same datagram.
+const MAX_RETRANSMISSION_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Datagram sent by a client to start the handshake. It carries the ID of the client.
+const KIND_HELLO: u8 = 0;
+/// Datagram that answers a hello from the socket dedicated to the client. It carries the ID of
+/// the server.
+const KIND_HELLO_ACK: u8 = 1;
+/// Datagram with a fragment of the encoded packets.
+const KIND_DATA: u8 = 2;
+/// Datagram that acknowledges every datagram with a lower sequence number.
+const KIND_ACK: u8 = 3;
+/// Datagram that announces that the sender will not send more data.
+const KIND_CLOSE: u8 = 4;
+
+/// Possible errors that may appear in a UDP channel.
+#[derive(Debug, Error)]
+pub enum UdpError {
+    /// The datagram received does not follow the format of the channel.
+    #[error("the datagram received is not correct: {0}")]
+    InvalidDatagram(&'static str),
+
+    /// The remote party did not acknowledge the datagrams sent within the timeout.
+    #[error("the remote party did not answer in {0:?}")]
+    Unresponsive(Duration),
+
+    /// The remote party answered the handshake with a different ID than the expected one.
+    #[error("expected the party {expected} to answer the handshake, but party {received} did")]
+    UnexpectedPeer { expected: usize, received: usize },
+}
+
+/// Datagram that was sent and is waiting for its acknowledgement.
+struct InFlight {
+    /// Sequence number of the datagram.
+    seq: u64,
+    /// Kind of the datagram.
+    kind: u8,
+    /// Bytes of the datagram, including the header.
+    datagram: Vec<u8>,
+    /// Instant at which the datagram was sent for the last time.
+    sent_at: Instant,
+    /// Number of times that the datagram was sent again.
+    retransmissions: u32,
+}
+
+impl InFlight {
+    /// Returns the time after which the datagram must be sent again.
+    fn retransmission_timeout(&self) -> Duration {
+        RETRANSMISSION_TIMEOUT
+            .saturating_mul(1 << self.retransmissions.min(4))
+            .min(MAX_RETRANSMISSION_TIMEOUT)
+    }
+}
+
+/// Encodes a datagram with the given kind, sequence number and payload.
+fn encode_datagram(kind: u8, seq: u64, payload: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(DATAGRAM_HEADER_SIZE + payload.len());
+    datagram.push(kind);
+    datagram.extend_from_slice(&seq.to_be_bytes());
+    datagram.extend_from_slice(payload);
+    datagram
+}
+
+/// Splits a datagram into its kind, sequence number and payload.
+fn decode_datagram(datagram: &[u8]) -> Result<(u8, u64, &[u8]), UdpError> {
+    if datagram.len() < DATAGRAM_HEADER_SIZE {
+        return Err(UdpError::InvalidDatagram("the header is incomplete"));
+    }
+    let (header, payload) = datagram.split_at(DATAGRAM_HEADER_SIZE);
+    // The unwrap is safe because the slice has exactly the size of a `u64`.
+    let seq = u64::from_be_bytes(header[1..].try_into().unwrap());
+    Ok((header[0], seq, payload))
+}
+
+/// Decodes the ID carried by the datagrams of the handshake.
+fn decode_id(payload: &[u8]) -> Result<usize, UdpError> {
+    let id = payload
+        .try_into()
+        .map(u64::from_be_bytes)
+        .map_err(|_| UdpError::InvalidDatagram("the ID of the handshake is not correct"))?;
+    usize::try_from(id).map_err(|_| UdpError::InvalidDatagram("the ID is out of range"))
+}
+
+/// EXPERIMENTAL channel that sends packets to a remote party through UDP. The encoded packets are
+/// split into datagrams numbered in sequence, which the receiver acknowledges and reorders, and
+/// the datagrams that are not acknowledged in time are sent again, so the packets arrive in order
+/// even through lossy links. The datagrams are neither encrypted nor authenticated, so the channel
+/// is INSECURE and must only be used in trusted networks.
+pub struct UdpChannel {
+    /// Socket connected to the socket of the remote party.
+    socket: UdpSocket,
+    /// Time without hearing from the remote party after which the channel fails while datagrams
+    /// wait for their acknowledgement.
+    timeout: Duration,
+    /// Answer of the handshake, which the server sends again until the client sends a datagram,
+    /// together with the instant at which it was sent for the last time.
+    hello_ack: Option<(Vec<u8>, Instant)>,
+    /// Instant at which the last datagram of the remote party arrived.
+    last_heard: Instant,
+    /// Sequence number of the next datagram to be sent.
+    next_datagram: u64,
+    /// Datagrams sent that were not acknowledged yet, in order.
+    in_flight: VecDeque<InFlight>,
+    /// Sequence number of the next datagram expected from the remote party.
+    recv_next: u64,
+    /// Datagrams received ahead of the expected one, indexed by sequence number.
+    out_of_order: BTreeMap<u64, (u8, Vec<u8>)>,
+    /// Bytes received in order that do not form a complete packet yet.
+    recv_buffer: RecvBuffer,
+    /// Sequence number of the next packet to be sent.
+    send_seq: u64,
+    /// Sequence number of the next packet to be received.
+    recv_seq: u64,
+    /// Whether the remote party announced that it will not send more data.
+    closed: bool,
+}
+
+impl UdpChannel {
+    /// Creates a new channel from a socket connected to the socket of the remote party.
+    fn new(socket: UdpSocket, timeout: Duration, hello_ack: Option<Vec<u8>>) -> Self {
+        let now = Instant::now();
+        Self {
+            socket,
+            timeout,
+            hello_ack: hello_ack.map(|datagram| (datagram, now)),
+            last_heard: now,
+            next_datagram: 0,
+            in_flight: VecDeque::new(),
+            recv_next: 0,
+            out_of_order: BTreeMap::new(),
+            recv_buffer: RecvBuffer::default(),
+            send_seq: 0,
+            recv_seq: 0,
+            closed: false,
+        }
+    }
+
+    /// Connects to the remote party as a client. The client sends its ID to the listener of the
+    /// remote party, which answers with its ID from a socket dedicated to the client. The hello
+    /// is sent again following the backoff policy until the answer arrives.
+    pub fn connect(
+        local_id: usize,
+        remote_id: usize,
+        peer: &PeerEndpoint,
+        backoff: &Backoff,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        let server_address = channel::resolve_host(&peer.host, peer.port)?;
+        let local_address: SocketAddr = if server_address.is_ipv4() {
+            "0.0.0.0:0".parse()?
+        } else {
+            "[::]:0".parse()?
+        };
+        let socket = UdpSocket::bind(local_address)?;
+        socket.set_read_timeout(Some(RETRANSMISSION_TIMEOUT))?;
+        let hello = encode_datagram(KIND_HELLO, 0, &(local_id as u64).to_be_bytes());
+
+        log::info!(
+            "trying to connect as a client to {}:{} through UDP",
+            peer.host,
+            peer.port
+        );
+        let mut attempts = backoff.start();
+        let mut buffer = [0; DATAGRAM_HEADER_SIZE + 8];
+        socket.send_to(&hello, server_address)?;
+        loop {
+            match socket.recv_from(&mut buffer) {
+                Ok((size, address)) if address.ip() == server_address.ip() => {
+                    if let Ok((KIND_HELLO_ACK, _, payload)) = decode_datagram(&buffer[..size]) {
+                        let received = decode_id(payload)?;
+                        if received != remote_id {
+                            anyhow::bail!(UdpError::UnexpectedPeer {
+                                expected: remote_id,
+                                received,
+                            });
+                        }
+                        socket.connect(address)?;
+                        log::info!("connected successfully with {address} through UDP");
+                        return Ok(Self::new(socket, timeout, None));
+                    }
+                }
+                Ok(_) => continue,
+                Err(err) if is_timeout(&err) || err.kind() == io::ErrorKind::ConnectionRefused => {
+                    if !attempts.wait() {
+                        log::error!(
+                            "server not answering from ID {local_id} to server {}:{} after {} attempts",
+                            peer.host,
+                            peer.port,
+                            attempts.count()
+                        );
+                        anyhow::bail!(attempts.error())
+                    }
+                    socket.send_to(&hello, server_address)?;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Sends a datagram that does not need to be acknowledged. The datagrams refused because the
+    /// socket of the remote party is not ready are left to the retransmissions.
+    fn send_datagram(&self, datagram: &[u8]) -> io::Result<()> {
+        match self.socket.send(datagram) {
+            Err(err) if err.kind() != io::ErrorKind::ConnectionRefused => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends a datagram that must be acknowledged, waiting for the acknowledgements of previous
+    /// datagrams if the window is full.
+    fn send_reliable(&mut self, kind: u8, payload: &[u8]) -> anyhow::Result<()> {
+        while self.in_flight.len() as u64 >= WINDOW_SIZE {
+            self.poll(RETRANSMISSION_TIMEOUT)?;
+        }
+        let seq = self.next_datagram;
+        self.next_datagram += 1;
+        let datagram = encode_datagram(kind, seq, payload);
+        self.send_datagram(&datagram)?;
+        self.in_flight.push_back(InFlight {
+            seq,
+            kind,
+            datagram,
+            sent_at: Instant::now(),
+            retransmissions: 0,
+        });
+        Ok(())
+    }
+
+    /// Waits up to the given time for a datagram of the remote party and processes it. Then, it
+    /// sends again the datagrams whose acknowledgement is overdue.
+    fn poll(&mut self, wait: Duration) -> anyhow::Result<()> {
+        self.socket.set_read_timeout(Some(wait))?;
+        let mut buffer = [0; DATAGRAM_HEADER_SIZE + MAX_FRAGMENT_SIZE];
+        match self.socket.recv(&mut buffer) {
+            Ok(size) => self.process(&buffer[..size])?,
+            Err(err) if is_timeout(&err) || err.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(err) => return Err(err.into()),
+        }
+        self.retransmit()
+    }
+
+    /// Processes every datagram that is already waiting in the socket without blocking.
+    fn drain(&mut self) -> anyhow::Result<()> {
+        self.socket.set_nonblocking(true)?;
+        let mut buffer = [0; DATAGRAM_HEADER_SIZE + MAX_FRAGMENT_SIZE];
+        let result = loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(size) => {
+                    if let Err(err) = self.process(&buffer[..size]) {
+                        break Err(err);
+                    }
+                }
+                Err(err) if is_timeout(&err) || err.kind() == io::ErrorKind::ConnectionRefused => {
+                    break Ok(())
+                }
+                Err(err) => break Err(err.into()),
+            }
+        };
+        self.socket.set_nonblocking(false)?;
+        result?;
+        self.retransmit()
+    }
+
+    /// Processes a datagram of the remote party.
+    fn process(&mut self, datagram: &[u8]) -> anyhow::Result<()> {
+        let (kind, seq, payload) = decode_datagram(datagram)?;
+        self.last_heard = Instant::now();
+        self.hello_ack = None;
+        match kind {
+            KIND_DATA | KIND_CLOSE => {
+                if seq >= self.recv_next && seq < self.recv_next + WINDOW_SIZE {
+                    self.out_of_order.insert(seq, (kind, payload.to_vec()));
+                }
+                while let Some((kind, payload)) = self.out_of_order.remove(&self.recv_next) {
+                    self.recv_next += 1;
+                    if kind == KIND_CLOSE {
+                        self.closed = true;
+                    } else {
+                        self.recv_buffer.extend_from_slice(&payload);
+                    }
+                }
+                // Duplicated datagrams are acknowledged again, as the previous acknowledgement
+                // may have been lost.
+                self.send_datagram(&encode_datagram(KIND_ACK, self.recv_next, &[]))?;
+            }
+            KIND_ACK => {
+                while self.in_flight.front().is_some_and(|sent| sent.seq < seq) {
+                    self.in_flight.pop_front();
+                }
+            }
+            // The client may receive the answer of the handshake more than once.
+            KIND_HELLO_ACK => {}
+            _ => anyhow::bail!(UdpError::InvalidDatagram("the kind is unknown")),
+        }
+        Ok(())
+    }
+
+    /// Sends again the datagrams whose acknowledgement is overdue, and the answer of the
+    /// handshake if the client did not send any datagram yet.
+    fn retransmit(&mut self) -> anyhow::Result<()> {
+        if let Some((hello_ack, sent_at)) = &mut self.hello_ack {
+            if sent_at.elapsed() >= RETRANSMISSION_TIMEOUT {
+                *sent_at = Instant::now();
+                let hello_ack = hello_ack.clone();
+                self.send_datagram(&hello_ack)?;
+            }
+        }
+        if self.in_flight.is_empty() {
+            return Ok(());
+        }
+        if self.last_heard.elapsed() > self.timeout {
+            anyhow::bail!(UdpError::Unresponsive(self.timeout));
+        }
+        for index in 0..self.in_flight.len() {
+            let sent = &self.in_flight[index];
+            if sent.sent_at.elapsed() >= sent.retransmission_timeout() {
+                log::debug!("sending again the datagram {}", sent.seq);
+                self.send_datagram(&sent.datagram)?;
+                let sent = &mut self.in_flight[index];
+                sent.sent_at = Instant::now();
+                sent.retransmissions += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the error of a receive that can not complete because the remote party closed the
+    /// channel.
+    fn closed_error() -> anyhow::Error {
+        io::Error::new(io::ErrorKind::UnexpectedEof, ChannelError::ConnectionClosed).into()
+    }
+}
+
+impl Channel for UdpChannel {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.send_reliable(KIND_CLOSE, &[])?;
+        // Once the remote party also closed, only the acknowledgement of the close may be missing,
+        // which the remote party does not need to send if it already dropped its socket.
+        while !(self.in_flight.is_empty()
+            || self.closed && self.in_flight.iter().all(|sent| sent.kind == KIND_CLOSE))
+        {
+            match self.poll(RETRANSMISSION_TIMEOUT) {
+                Ok(()) => {}
+                Err(err) if matches!(err.downcast_ref(), Some(UdpError::Unresponsive(_))) => {
+                    log::warn!("the remote party did not acknowledge the close: {err}");
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        log::info!("channel successfully closed");
+        Ok(())
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        let bytes = encode_packet(packet, &mut self.send_seq)?;
+        for fragment in bytes.chunks(MAX_FRAGMENT_SIZE) {
+            self.send_reliable(KIND_DATA, fragment)?;
+        }
+        Ok(packet.size())
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.recv_buffer.set_max_packet_size(max_packet_size);
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        loop {
+            if let Some(packet) = decode_packet(&mut self.recv_buffer, &mut self.recv_seq)? {
+                return Ok(packet);
+            }
+            if self.closed {
+                return Err(Self::closed_error());
+            }
+            self.poll(RETRANSMISSION_TIMEOUT)?;
+        }
+    }
+
+    fn recv_into(&mut self, buffer: &mut Vec<u8>) -> anyhow::Result<PacketHeader> {
+        loop {
+            if let Some(header) =
+                decode_packet_into(&mut self.recv_buffer, &mut self.recv_seq, buffer)?
+            {
+                return Ok(header);
+            }
+            if self.closed {
+                return Err(Self::closed_error());
+            }
+            self.poll(RETRANSMISSION_TIMEOUT)?;
+        }
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(packet) = decode_packet(&mut self.recv_buffer, &mut self.recv_seq)? {
+                return Ok(packet);
+            }
+            if self.closed {
+                return Err(Self::closed_error());
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(ChannelError::RecvTimeout(timeout).into());
+            }
+            self.poll(remaining.min(RETRANSMISSION_TIMEOUT))?;
+        }
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        if let Some(packet) = decode_packet(&mut self.recv_buffer, &mut self.recv_seq)? {
+            return Ok(Some(packet));
+        }
+        self.drain()?;
+        match decode_packet(&mut self.recv_buffer, &mut self.recv_seq)? {
+            None if self.closed => Err(Self::closed_error()),
+            packet => Ok(packet),
+        }
+    }
+}
+
+/// Socket in which a party waits for the handshakes of the clients of the UDP transport. Each
+/// client is answered from a new socket dedicated to it, which becomes its channel.
+pub struct UdpListener {
+    /// Socket that receives the handshakes.
+    socket: UdpSocket,
+    /// ID of the party.
+    local_id: usize,
+    /// Time without hearing from a remote party after which its channel fails.
+    timeout: Duration,
+    /// Sockets dedicated to the clients accepted so far, indexed by the address of the client, so
+    /// that a hello sent again is answered instead of creating another channel.
+    accepted: HashMap<SocketAddr, UdpSocket>,
+}
+
+impl UdpListener {
+    /// Creates a listener bound to the given address.
+    pub fn bind(address: SocketAddr, local_id: usize, timeout: Duration) -> anyhow::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(address)?,
+            local_id,
+            timeout,
+            accepted: HashMap::new(),
+        })
+    }
+
+    /// Returns the address at which the listener receives the handshakes.
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Waits for the handshake of a new client. Returns the channel and the ID sent by the
+    /// client, which is not authenticated.
+    pub fn accept(&mut self) -> anyhow::Result<(UdpChannel, usize)> {
+        let mut buffer = [0; DATAGRAM_HEADER_SIZE + 8];
+        loop {
+            let (size, address) = self.socket.recv_from(&mut buffer)?;
+            let Ok((KIND_HELLO, _, payload)) = decode_datagram(&buffer[..size]) else {
+                log::debug!("ignoring a datagram from {address} that is not a hello");
+                continue;
+            };
+            let remote_id = match decode_id(payload) {
+                Ok(remote_id) => remote_id,
+                Err(err) => {
+                    log::warn!("invalid hello from {address}: {err}");
+                    continue;
+                }
+            };
+            let hello_ack =
+                encode_datagram(KIND_HELLO_ACK, 0, &(self.local_id as u64).to_be_bytes());
+            if let Some(socket) = self.accepted.get(&address) {
+                let _ = socket.send(&hello_ack);
+                continue;
+            }
+
+            let socket = UdpSocket::bind(SocketAddr::new(self.socket.local_addr()?.ip(), 0))?;
+            socket.connect(address)?;
+            socket.send(&hello_ack)?;
+            self.accepted.insert(address, socket.try_clone()?);
+            log::warn!("accepted an unauthenticated UDP channel from ID {remote_id}");
+            return Ok((
+                UdpChannel::new(socket, self.timeout, Some(hello_ack)),
+                remote_id,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::UdpSocket,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{encode_datagram, UdpChannel, UdpError, UdpListener, KIND_DATA};
+    use crate::net::{backoff::Backoff, channel::Channel, Packet, PeerEndpoint};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Connects two channels through the loop-back interface.
+    fn channel_pair() -> (UdpChannel, UdpChannel) {
+        let mut listener = UdpListener::bind("127.0.0.1:0".parse().unwrap(), 0, TIMEOUT).unwrap();
+        let peer = PeerEndpoint {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            bind: None,
+            proxy: None,
+        };
+        let client = thread::spawn(move || {
+            let backoff = Backoff::fixed(TIMEOUT, Duration::from_millis(10));
+            UdpChannel::connect(1, 0, &peer, &backoff, TIMEOUT).unwrap()
+        });
+        let (server, remote_id) = listener.accept().unwrap();
+        assert_eq!(remote_id, 1);
+        (server, client.join().unwrap())
+    }
+
+    #[test]
+    fn udp_channel_roundtrip() {
+        let (mut server, mut client) = channel_pair();
+        // The packets span many datagrams, more than the ones that fit in the window.
+        let payload: Vec<u8> = (0..200_000).map(|i| i as u8).collect();
+        let expected = payload.clone();
+        let sender = thread::spawn(move || {
+            client.send(&Packet::new(vec![1, 2, 3])).unwrap();
+            client.send(&Packet::new(payload)).unwrap();
+            client.shutdown().unwrap();
+        });
+
+        assert_eq!(server.recv().unwrap().as_slice(), &[1, 2, 3]);
+        assert_eq!(server.recv().unwrap().as_slice(), expected.as_slice());
+        let err = server.recv().unwrap_err();
+        assert!(crate::net::channel::is_connection_closed(&err));
+        server.shutdown().unwrap();
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn datagrams_are_reordered_and_deduplicated() {
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(peer.local_addr().unwrap()).unwrap();
+        let mut channel = UdpChannel::new(socket, TIMEOUT, None);
+
+        let bytes = crate::net::channel::encode_packet(&Packet::new(vec![7; 10]), &mut 0).unwrap();
+        let (first, second) = bytes.split_at(bytes.len() / 2);
+        channel
+            .process(&encode_datagram(KIND_DATA, 1, second))
+            .unwrap();
+        assert!(channel.try_recv().unwrap().is_none());
+        channel
+            .process(&encode_datagram(KIND_DATA, 0, first))
+            .unwrap();
+        channel
+            .process(&encode_datagram(KIND_DATA, 1, second))
+            .unwrap();
+        assert_eq!(channel.try_recv().unwrap().unwrap().as_slice(), &[7; 10]);
+        assert!(channel.try_recv().unwrap().is_none());
+
+        let err = channel.process(&[9; 3]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<UdpError>(),
+            Some(UdpError::InvalidDatagram(_))
+        ));
+    }
+
+    #[test]
+    fn fail_when_the_remote_party_does_not_answer() {
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(peer.local_addr().unwrap()).unwrap();
+        let timeout = Duration::from_millis(300);
+        let mut channel = UdpChannel::new(socket, timeout, None);
+        channel.send(&Packet::new(vec![1])).unwrap();
+
+        let start = Instant::now();
+        let err = channel.recv().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<UdpError>(),
+            Some(UdpError::Unresponsive(_))
+        ));
+        assert!(start.elapsed() >= timeout);
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    io::{self, Read, Write},
+    net::Shutdown,
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    time::Duration,
+};
+
+use super::{
+    backoff::Backoff,
+    channel::{PacketStream, StreamChannel},
+};
+
+/// Channel that sends packets to a party running in the same host through a Unix domain socket.
+/// The packets travel in plain text, so the socket files must be protected with the permissions
+/// of the file system.
+pub type UnixChannel = StreamChannel<UnixStream>;
+
+impl PacketStream for UnixStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        UnixStream::set_nonblocking(self, nonblocking)
+    }
+
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        UnixStream::shutdown(self, Shutdown::Write)?;
+        Ok(())
+    }
+
+    fn raw_fd(&self) -> RawFd {
+        self.as_raw_fd()
+    }
+}
+
+impl UnixChannel {
+    /// Connects to the socket of the remote party, trying repeatedly following the backoff policy.
+    /// Once connected, the party sends its ID.
+    pub fn connect(local_id: usize, path: &Path, backoff: &Backoff) -> anyhow::Result<Self> {
+        let mut attempts = backoff.start();
+        log::info!("trying to connect to the socket {path:?}");
+        let mut stream = loop {
+            match UnixStream::connect(path) {
+                Ok(stream) => break stream,
+                Err(err) => {
+                    if !attempts.wait() {
+                        log::error!(
+                            "socket {path:?} not listening from ID {local_id} after {} attempts: {err}",
+                            attempts.count()
+                        );
+                        anyhow::bail!(attempts.error())
+                    }
+                }
+            }
+        };
+        stream.write_all(&local_id.to_le_bytes())?;
+        log::info!("connected successfully with the socket {path:?}");
+        Ok(Self::new(stream))
+    }
+
+    /// Accepts a connection in the listener. Returns the channel and the ID sent by the remote
+    /// party.
+    pub fn accept(listener: &UnixListener) -> anyhow::Result<(Self, usize)> {
+        let (mut stream, _) = listener.accept()?;
+        let mut id_buffer = [0; (usize::BITS / 8) as usize];
+        stream.read_exact(&mut id_buffer)?;
+        let remote_id = usize::from_le_bytes(id_buffer);
+        log::info!("accepted connection in the socket from ID {remote_id}");
+        Ok((Self::new(stream), remote_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixListener, thread, time::Duration};
+
+    use super::UnixChannel;
+    use crate::net::{backoff::Backoff, channel::Channel, Packet};
+
+    #[test]
+    fn unix_channel_roundtrip() {
+        let path = std::env::temp_dir().join(format!("shami_rs_test_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let client_path = path.clone();
+        let client = thread::spawn(move || {
+            let backoff = Backoff::fixed(Duration::from_secs(1), Duration::from_millis(10));
+            let mut channel = UnixChannel::connect(1, &client_path, &backoff).unwrap();
+            channel.send(&Packet::new(vec![1, 2, 3])).unwrap();
+            channel.recv().unwrap()
+        });
+
+        let (mut channel, remote_id) = UnixChannel::accept(&listener).unwrap();
+        assert_eq!(remote_id, 1);
+        let packet = channel.recv().unwrap();
+        assert!(channel.try_recv().unwrap().is_none());
+        channel.send(&packet).unwrap();
+        channel.flush().unwrap();
+        assert_eq!(client.join().unwrap().as_slice(), &[1, 2, 3]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+use std::any::type_name;
+
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use super::Packet;
+use crate::{
+    codec::{Codec, WireCodec},
+    math::{mersenne127::Mersenne127, mersenne61::Mersenne61, p128::P128, FiniteField},
+};
+
+/// Version of the header and the encoding of the values. It is the first byte of every value, so
+/// a change of the header is detected before the rest of it is read.
+const FORMAT_VERSION: u8 = 1;
+
+/// Size in bytes of the name of the field in the header, padded with zeros.
+const FIELD_NAME_SIZE: usize = 4;
+
+/// Size in bytes of the type tag that precedes the encoded value.
+const TYPE_TAG_SIZE: usize = 8;
+
+/// Size in bytes of the header of an encoded value: the version of the format, the ID of the
+/// codec, the name of the field and the type tag.
+const HEADER_SIZE: usize = 1 + 1 + FIELD_NAME_SIZE + TYPE_TAG_SIZE;
+
+/// Possible errors that may appear while decoding a typed value.
+#[derive(Debug, Error)]
+pub enum ValueError {
+    /// The packet is too short to contain the header of the value.
+    #[error("the packet has {0} bytes, which is not enough for the header of a value")]
+    MissingTypeTag(usize),
+
+    /// The value was encoded with another version of the format.
+    #[error("the value was encoded with version {found} of the format, but the party uses version {expected}")]
+    VersionMismatch { expected: u8, found: u8 },
+
+    /// The value holds elements of another field than the expected one.
+    #[error("field mismatch: the value holds elements of {found}, but the party uses {expected}")]
+    FieldMismatch { expected: String, found: String },
+
+    /// The packet contains a value of a different type than the expected one.
+    #[error("the packet does not contain a value of type {expected}")]
+    TypeMismatch { expected: &'static str },
+
+    /// The value was encoded with a different codec than the one of the party.
+    #[error("the value was encoded with the codec {found}, but the party uses {expected}")]
+    CodecMismatch { expected: Codec, found: String },
+}
+
+/// Returns the tag that identifies the type `T` in the packets. It is the 64-bit FNV-1a hash of
+/// the name of the type, so both parties must be compiled from the same definition of the type.
+pub fn type_tag<T: ?Sized>() -> u64 {
+    type_name::<T>()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Returns the name of the field whose elements the type `T` holds, e.g. `M61` for the shares of
+/// [`Mersenne61`], or `None` if the type does not mention any field.
+pub fn field_of<T: ?Sized>() -> Option<&'static str> {
+    let name = type_name::<T>();
+    [
+        (type_name::<Mersenne61>(), Mersenne61::NAME),
+        (type_name::<Mersenne127>(), Mersenne127::NAME),
+        (type_name::<P128>(), P128::NAME),
+    ]
+    .into_iter()
+    .find(|(field, _)| name.contains(field))
+    .map(|(_, field_name)| field_name)
+}
+
+/// Encodes the name of a field in the header, where the lack of a field is all zeros.
+fn encode_field(field: Option<&str>) -> [u8; FIELD_NAME_SIZE] {
+    let mut bytes = [0; FIELD_NAME_SIZE];
+    if let Some(field) = field {
+        bytes[..field.len()].copy_from_slice(field.as_bytes());
+    }
+    bytes
+}
+
+/// Describes the name of a field encoded in a header, for the errors.
+fn describe_field(bytes: &[u8]) -> String {
+    let name: Vec<u8> = bytes
+        .iter()
+        .copied()
+        .take_while(|byte| *byte != 0)
+        .collect();
+    if name.is_empty() {
+        "no field".to_string()
+    } else {
+        String::from_utf8_lossy(&name).into_owned()
+    }
+}
+
+/// Encodes a value in a packet. The payload is a header with the version of the format, the ID of
+/// the codec, the name of the field of the value and the tag of its type, followed by the value
+/// encoded with the codec.
+pub fn encode_value<T: Serialize>(codec: Codec, value: &T) -> anyhow::Result<Packet> {
+    let encoded = codec.encode(value)?;
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + encoded.len());
+    bytes.push(FORMAT_VERSION);
+    bytes.push(codec.id());
+    bytes.extend_from_slice(&encode_field(field_of::<T>()));
+    bytes.extend_from_slice(&type_tag::<T>().to_be_bytes());
+    bytes.extend_from_slice(&encoded);
+    Ok(Packet::new(bytes))
+}
+
+/// Decodes a value encoded with [`encode_value`], checking that the packet contains a value of
+/// the type `T` encoded with the given codec and the same version of the format. The header is
+/// checked from its start, so a value of another field fails with [`ValueError::FieldMismatch`]
+/// instead of the less telling [`ValueError::TypeMismatch`].
+pub fn decode_value<T: DeserializeOwned>(codec: Codec, packet: &Packet) -> anyhow::Result<T> {
+    let payload = packet.as_slice();
+    if payload.len() < HEADER_SIZE {
+        anyhow::bail!(ValueError::MissingTypeTag(payload.len()));
+    }
+    let (header, encoded) = payload.split_at(HEADER_SIZE);
+    if header[0] != FORMAT_VERSION {
+        anyhow::bail!(ValueError::VersionMismatch {
+            expected: FORMAT_VERSION,
+            found: header[0],
+        });
+    }
+    if header[1] != codec.id() {
+        anyhow::bail!(ValueError::CodecMismatch {
+            expected: codec,
+            found: Codec::from_id(header[1]).map_or_else(
+                || format!("with ID {}", header[1]),
+                |found| found.to_string()
+            ),
+        });
+    }
+    let (field, tag) = header[2..].split_at(FIELD_NAME_SIZE);
+    let expected_field = encode_field(field_of::<T>());
+    if field != expected_field {
+        anyhow::bail!(ValueError::FieldMismatch {
+            expected: describe_field(&expected_field),
+            found: describe_field(field),
+        });
+    }
+    // The unwrap is safe because the tag has exactly the size of a `u64`.
+    if u64::from_be_bytes(tag.try_into().unwrap()) != type_tag::<T>() {
+        anyhow::bail!(ValueError::TypeMismatch {
+            expected: type_name::<T>(),
+        });
+    }
+    codec.decode(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_value, encode_value, field_of, ValueError, FIELD_NAME_SIZE};
+    use crate::{
+        codec::Codec,
+        math::{mersenne127::Mersenne127, mersenne61::Mersenne61, p128::P128, FiniteField},
+        mpc::share::ShamirShare,
+        net::Packet,
+    };
+
+    #[test]
+    fn values_roundtrip() {
+        let value = (7u64, vec![String::from("share")]);
+        for codec in [Codec::Bincode, Codec::Postcard, Codec::Protobuf] {
+            let packet = encode_value(codec, &value).unwrap();
+            assert_eq!(
+                decode_value::<(u64, Vec<String>)>(codec, &packet).unwrap(),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn reject_values_of_other_codecs() {
+        let packet = encode_value(Codec::Postcard, &7u64).unwrap();
+        let err = decode_value::<u64>(Codec::Protobuf, &packet).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ValueError>(),
+            Some(ValueError::CodecMismatch {
+                expected: Codec::Protobuf,
+                ..
+            })
+        ));
+        assert!(err.to_string().contains("postcard"));
+    }
+
+    #[test]
+    fn reject_values_of_other_types() {
+        let packet = encode_value(Codec::default(), &7u64).unwrap();
+        let err = decode_value::<i64>(Codec::default(), &packet).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ValueError>(),
+            Some(ValueError::TypeMismatch { .. })
+        ));
+
+        let err = decode_value::<u64>(Codec::default(), &Packet::new(vec![1, 2])).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ValueError>(),
+            Some(ValueError::MissingTypeTag(2))
+        ));
+    }
+
+    #[test]
+    fn reject_values_of_other_fields_and_versions() {
+        for name in [Mersenne61::NAME, Mersenne127::NAME, P128::NAME] {
+            assert!(name.is_ascii() && name.len() <= FIELD_NAME_SIZE);
+        }
+        assert_eq!(field_of::<Vec<ShamirShare<Mersenne127>>>(), Some("M127"));
+        assert_eq!(field_of::<u64>(), None);
+
+        let share = ShamirShare::new(Mersenne127::from(7), 1);
+        let packet = encode_value(Codec::default(), &share).unwrap();
+        let err = decode_value::<ShamirShare<Mersenne61>>(Codec::default(), &packet).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ValueError>(),
+            Some(ValueError::FieldMismatch { expected, found }) if expected == "M61" && found == "M127"
+        ));
+        assert!(err.to_string().starts_with("field mismatch"));
+        let err = decode_value::<u64>(Codec::default(), &packet).unwrap_err();
+        assert!(err.to_string().contains("no field"));
+
+        let mut bytes = packet.into_payload();
+        bytes[0] += 1;
+        let err = decode_value::<ShamirShare<Mersenne127>>(Codec::default(), &Packet::new(bytes))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ValueError>(),
+            Some(ValueError::VersionMismatch {
+                expected: 1,
+                found: 2
+            })
+        ));
+    }
+}
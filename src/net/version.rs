@@ -0,0 +1,333 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::codec::Codec;
+
+use super::rekey::RekeyPolicy;
+
+/// Version of the wire format spoken by this build. It increases with every change of the
+/// framing, the encoding of the values or the sub-protocols that older builds can not follow.
+pub const WIRE_VERSION: u16 = 3;
+
+/// Oldest version of the wire format that this build still speaks.
+pub const MIN_WIRE_VERSION: u16 = 3;
+
+/// Protocol ID reserved for the packets of the negotiation.
+pub(crate) const HELLO_PROTOCOL_ID: u16 = u16::MAX - 1;
+
+/// Size in bytes of the hello of this version. Later versions may append fields, which are
+/// ignored by this one.
+const HELLO_SIZE: usize = 20;
+
+/// Possible errors that may appear while negotiating the wire format with a peer.
+#[derive(Debug, Error)]
+pub enum VersionError {
+    /// The hello of the peer is too short.
+    #[error(
+        "the hello of party {party_id} has {size} bytes, but at least {HELLO_SIZE} are needed"
+    )]
+    MalformedHello { party_id: usize, size: usize },
+
+    /// The peer sent another packet before its hello, so it does not negotiate the wire format.
+    #[error("party {0} did not start with a hello, so it does not negotiate the wire format")]
+    MissingHello(usize),
+
+    /// The ranges of versions of the wire format of both parties do not overlap.
+    #[error("party {party_id} speaks the versions {peer_min} to {peer_max} of the wire format, but this party speaks {own_min} to {own_max}")]
+    IncompatibleVersion {
+        party_id: usize,
+        own_min: u16,
+        own_max: u16,
+        peer_min: u16,
+        peer_max: u16,
+    },
+
+    /// The peer does not support some features that the party requires.
+    #[error("party {party_id} does not support the required features {missing}")]
+    PeerLacksFeatures { party_id: usize, missing: Features },
+
+    /// The peer requires some features that the party does not support.
+    #[error("party {party_id} requires the features {missing}, which this party does not support")]
+    LacksFeatures { party_id: usize, missing: Features },
+}
+
+/// Set of optional features of the wire format, one per bit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Features(u64);
+
+impl Features {
+    /// Values encoded with bincode.
+    pub const BINCODE: Self = Self(1 << 0);
+    /// Values encoded with postcard.
+    pub const POSTCARD: Self = Self(1 << 1);
+    /// Values encoded with the wire format of Protocol Buffers.
+    pub const PROTOBUF: Self = Self(1 << 2);
+    /// Rotation of the keys of the channels announced in band.
+    pub const REKEY: Self = Self(1 << 3);
+
+    /// Names of the known features, in order of bit.
+    const NAMES: [(Self, &'static str); 4] = [
+        (Self::BINCODE, "bincode"),
+        (Self::POSTCARD, "postcard"),
+        (Self::PROTOBUF, "protobuf"),
+        (Self::REKEY, "rekey"),
+    ];
+
+    /// Returns the empty set of features.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the features supported by this build.
+    pub const fn supported() -> Self {
+        Self(Self::BINCODE.0 | Self::POSTCARD.0 | Self::PROTOBUF.0 | Self::REKEY.0)
+    }
+
+    /// Returns the set of features with the given bits, which may include unknown features.
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the bits of the set of features.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns the feature of the encoding of a codec.
+    pub fn codec(codec: Codec) -> Self {
+        match codec {
+            Codec::Bincode => Self::BINCODE,
+            Codec::Postcard => Self::POSTCARD,
+            Codec::Protobuf => Self::PROTOBUF,
+        }
+    }
+
+    /// Returns the features that a party needs from its peers to run with the given codec and
+    /// rekey policy.
+    pub fn required(codec: Codec, rekey: &RekeyPolicy) -> Self {
+        let rotates = rekey.after_bytes.is_some() || rekey.after_seconds.is_some();
+        Self::codec(codec).union(if rotates { Self::REKEY } else { Self::empty() })
+    }
+
+    /// Tells whether the set is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Tells whether every feature of `other` is in the set.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the features that are in either set.
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Returns the features that are in both sets.
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// Returns the features of the set that are not in `other`.
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<String> = Self::NAMES
+            .iter()
+            .filter(|(feature, _)| self.contains(*feature))
+            .map(|(_, name)| name.to_string())
+            .collect();
+        let unknown = Self::NAMES
+            .iter()
+            .fold(*self, |rest, (feature, _)| rest.difference(*feature));
+        if !unknown.is_empty() {
+            names.push(format!("{:#x}", unknown.0));
+        }
+        write!(f, "{{{}}}", names.join(", "))
+    }
+}
+
+/// Version and features that a party agreed with a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Highest version of the wire format spoken by both parties.
+    pub version: u16,
+    /// Features supported by both parties.
+    pub features: Features,
+}
+
+/// First packet that a party sends to each peer once the connection is secured. It announces the
+/// versions of the wire format that the party speaks, the features that it supports and the ones
+/// that it requires from the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hello {
+    /// Highest version of the wire format spoken by the party.
+    pub version: u16,
+    /// Lowest version of the wire format spoken by the party.
+    pub min_version: u16,
+    /// Features supported by the party.
+    pub supported: Features,
+    /// Features that the peers of the party must support.
+    pub required: Features,
+}
+
+impl Hello {
+    /// Creates the hello of this build with the given required features.
+    pub fn new(required: Features) -> Self {
+        Self {
+            version: WIRE_VERSION,
+            min_version: MIN_WIRE_VERSION,
+            supported: Features::supported(),
+            required,
+        }
+    }
+
+    /// Encodes the hello in little-endian order, independently of the codec of the values, which
+    /// is not agreed yet.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HELLO_SIZE);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&self.min_version.to_le_bytes());
+        bytes.extend_from_slice(&self.supported.0.to_le_bytes());
+        bytes.extend_from_slice(&self.required.0.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes the hello sent by the party with the given ID. The bytes after the fields of this
+    /// version are ignored.
+    pub fn decode(party_id: usize, bytes: &[u8]) -> Result<Self, VersionError> {
+        if bytes.len() < HELLO_SIZE {
+            return Err(VersionError::MalformedHello {
+                party_id,
+                size: bytes.len(),
+            });
+        }
+        // The unwraps are safe because the slices have the size of the integers.
+        let u16_at = |i: usize| u16::from_le_bytes(bytes[i..i + 2].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        Ok(Self {
+            version: u16_at(0),
+            min_version: u16_at(2),
+            supported: Features(u64_at(4)),
+            required: Features(u64_at(12)),
+        })
+    }
+
+    /// Agrees with the hello of the party with the given ID on the highest version spoken by both
+    /// and the features supported by both. Both parties reach the same outcome, so an
+    /// incompatible peer is rejected by both ends of the connection.
+    pub fn negotiate(&self, party_id: usize, peer: &Hello) -> Result<Capabilities, VersionError> {
+        let version = self.version.min(peer.version);
+        if version < self.min_version.max(peer.min_version) {
+            return Err(VersionError::IncompatibleVersion {
+                party_id,
+                own_min: self.min_version,
+                own_max: self.version,
+                peer_min: peer.min_version,
+                peer_max: peer.version,
+            });
+        }
+        let missing = self.required.difference(peer.supported);
+        if !missing.is_empty() {
+            return Err(VersionError::PeerLacksFeatures { party_id, missing });
+        }
+        let missing = peer.required.difference(self.supported);
+        if !missing.is_empty() {
+            return Err(VersionError::LacksFeatures { party_id, missing });
+        }
+        Ok(Capabilities {
+            version,
+            features: self.supported.intersection(peer.supported),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Features, Hello, VersionError, HELLO_SIZE, WIRE_VERSION};
+    use crate::{codec::Codec, net::rekey::RekeyPolicy};
+
+    #[test]
+    fn hellos_roundtrip_and_ignore_later_fields() {
+        let hello = Hello::new(Features::POSTCARD);
+        let mut bytes = hello.encode();
+        assert_eq!(bytes.len(), HELLO_SIZE);
+        assert_eq!(Hello::decode(1, &bytes).unwrap(), hello);
+        bytes.extend_from_slice(&[0xff; 6]);
+        assert_eq!(Hello::decode(1, &bytes).unwrap(), hello);
+        assert!(matches!(
+            Hello::decode(1, &bytes[..HELLO_SIZE - 1]),
+            Err(VersionError::MalformedHello {
+                party_id: 1,
+                size: 19
+            })
+        ));
+    }
+
+    #[test]
+    fn newer_peers_fall_back_to_a_common_version() {
+        let own = Hello::new(Features::BINCODE);
+        let newer = Hello {
+            version: WIRE_VERSION + 2,
+            min_version: WIRE_VERSION,
+            supported: Features::supported().union(Features::from_bits(1 << 40)),
+            required: Features::BINCODE,
+        };
+        let capabilities = own.negotiate(1, &newer).unwrap();
+        assert_eq!(capabilities.version, WIRE_VERSION);
+        assert_eq!(capabilities.features, Features::supported());
+        assert_eq!(newer.negotiate(0, &own).unwrap(), capabilities);
+
+        let too_new = Hello {
+            min_version: WIRE_VERSION + 1,
+            ..newer
+        };
+        let err = own.negotiate(1, &too_new).unwrap_err();
+        assert!(matches!(err, VersionError::IncompatibleVersion { .. }));
+        assert!(matches!(
+            too_new.negotiate(0, &own),
+            Err(VersionError::IncompatibleVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn peers_without_the_required_features_are_rejected() {
+        let rekey = RekeyPolicy {
+            after_bytes: Some(1 << 20),
+            after_seconds: None,
+        };
+        let required = Features::required(Codec::Protobuf, &rekey);
+        assert_eq!(required, Features::PROTOBUF.union(Features::REKEY));
+        let own = Hello::new(required);
+        let old = Hello {
+            supported: Features::BINCODE,
+            required: Features::BINCODE,
+            ..own
+        };
+        let err = own.negotiate(2, &old).unwrap_err();
+        assert!(matches!(
+            err,
+            VersionError::PeerLacksFeatures { party_id: 2, missing } if missing == required
+        ));
+        assert!(err.to_string().contains("{protobuf, rekey}"));
+        let err = old.negotiate(0, &own).unwrap_err();
+        assert!(matches!(
+            err,
+            VersionError::LacksFeatures { party_id: 0, .. }
+        ));
+
+        let unknown = Hello {
+            required: Features::from_bits(1 << 63),
+            ..own
+        };
+        let err = own.negotiate(1, &unknown).unwrap_err();
+        assert!(err.to_string().contains("{0x8000000000000000}"));
+    }
+}
@@ -0,0 +1,558 @@
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    ops::{Deref, DerefMut},
+    os::fd::AsRawFd,
+    time::{Duration, Instant},
+};
+
+use aws_lc_rs::digest::{self, SHA1_FOR_LEGACY_USE_ONLY};
+use rand::{rngs::StdRng, CryptoRng, Rng, RngCore, SeedableRng};
+use rustls::{ConnectionCommon, SideData, StreamOwned};
+use thiserror::Error;
+
+use super::{
+    channel::{
+        decode_packet, encode_packet, export_tls_keying_material, is_timeout, refresh_tls_keys,
+        Channel, ChannelError, Readiness, RecvBuffer,
+    },
+    Packet,
+};
+
+/// GUID used to compute the `Sec-WebSocket-Accept` header, as defined in RFC 6455.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Path requested in the opening handshake.
+const WEBSOCKET_PATH: &str = "/shami";
+
+/// Maximum size in bytes of the HTTP headers of the opening handshake.
+const MAX_HANDSHAKE_SIZE: usize = 8192;
+
+/// Size in bytes of the chunks read from the stream.
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Opcodes of the WebSocket frames.
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Possible errors that may appear in a WebSocket channel.
+#[derive(Debug, Error)]
+pub enum WebSocketError {
+    /// The opening handshake does not follow RFC 6455.
+    #[error("invalid WebSocket handshake: {0}")]
+    InvalidHandshake(String),
+
+    /// A frame does not follow RFC 6455 or is not supported.
+    #[error("invalid WebSocket frame: {0}")]
+    InvalidFrame(&'static str),
+
+    /// The first message of the connection does not contain the ID of the remote party.
+    #[error("the first WebSocket message does not contain the ID of the party")]
+    MissingId,
+}
+
+/// Stream over which the WebSocket messages travel. It gives access to the underlying TCP socket
+/// to configure the timeouts.
+pub trait WebSocketStream: Read + Write + Send {
+    /// Returns the underlying TCP socket.
+    fn socket(&self) -> &TcpStream;
+    /// Rotates the keys of the stream, if it is encrypted. Returns whether they were rotated.
+    fn refresh_keys(&mut self) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+    /// Exports keying material from the session of the stream, if it is encrypted. Returns
+    /// whether it was exported.
+    fn export_keying_material(&self, _label: &[u8], _output: &mut [u8]) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+}
+
+impl WebSocketStream for TcpStream {
+    fn socket(&self) -> &TcpStream {
+        self
+    }
+}
+
+impl<C, S> WebSocketStream for StreamOwned<C, TcpStream>
+where
+    C: Send + DerefMut + Deref<Target = ConnectionCommon<S>>,
+    S: SideData,
+{
+    fn socket(&self) -> &TcpStream {
+        &self.sock
+    }
+
+    fn refresh_keys(&mut self) -> anyhow::Result<bool> {
+        refresh_tls_keys(self)
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        export_tls_keying_material(&self.conn, label, output)
+    }
+}
+
+/// Encodes bytes in base64 with padding.
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let block = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(block >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Computes the value of the `Sec-WebSocket-Accept` header for a `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> String {
+    let mut context = digest::Context::new(&SHA1_FOR_LEGACY_USE_ONLY);
+    context.update(key.as_bytes());
+    context.update(WEBSOCKET_GUID.as_bytes());
+    encode_base64(context.finish().as_ref())
+}
+
+/// Reads the HTTP headers of the opening handshake. The stream is read byte by byte so that no
+/// WebSocket frame that follows the headers is consumed.
+fn read_http_headers(stream: &mut impl Read) -> anyhow::Result<String> {
+    let mut headers = Vec::new();
+    let mut byte = [0];
+    while !headers.ends_with(b"\r\n\r\n") {
+        if headers.len() > MAX_HANDSHAKE_SIZE {
+            anyhow::bail!(WebSocketError::InvalidHandshake(
+                "the headers are too large".to_string()
+            ));
+        }
+        stream.read_exact(&mut byte)?;
+        headers.push(byte[0]);
+    }
+    Ok(String::from_utf8(headers)
+        .map_err(|_| WebSocketError::InvalidHandshake("the headers are not UTF-8".to_string()))?)
+}
+
+/// Returns the value of an HTTP header, ignoring the case of its name.
+fn header_value<'a>(headers: &'a str, name: &str) -> Option<&'a str> {
+    headers.lines().find_map(|line| {
+        let (header, value) = line.split_once(':')?;
+        header
+            .trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+/// Decoded header of a WebSocket frame.
+struct FrameHeader {
+    /// Whether the frame is the last one of a message.
+    fin: bool,
+    /// Opcode of the frame.
+    opcode: u8,
+    /// Masking key of the payload, if the payload is masked.
+    mask: Option<[u8; 4]>,
+    /// Size in bytes of the payload.
+    payload_size: usize,
+    /// Size in bytes of the header.
+    header_size: usize,
+}
+
+impl FrameHeader {
+    /// Decodes the header of the frame at the beginning of the buffer, if the buffer contains the
+    /// whole header.
+    fn decode(buffer: &[u8]) -> Option<Self> {
+        if buffer.len() < 2 {
+            return None;
+        }
+        let fin = buffer[0] & 0x80 != 0;
+        let opcode = buffer[0] & 0x0F;
+        let masked = buffer[1] & 0x80 != 0;
+        let (payload_size, mut header_size) = match buffer[1] & 0x7F {
+            126 => (
+                u16::from_be_bytes(buffer.get(2..4)?.try_into().ok()?) as usize,
+                4,
+            ),
+            127 => (
+                u64::from_be_bytes(buffer.get(2..10)?.try_into().ok()?) as usize,
+                10,
+            ),
+            size => (size as usize, 2),
+        };
+        let mask = if masked {
+            let mask = buffer.get(header_size..header_size + 4)?.try_into().ok()?;
+            header_size += 4;
+            Some(mask)
+        } else {
+            None
+        };
+        Some(Self {
+            fin,
+            opcode,
+            mask,
+            payload_size,
+            header_size,
+        })
+    }
+}
+
+/// Encodes a WebSocket frame with the given opcode and payload. The payload is masked if a
+/// masking key is given, as required for the frames sent by a client.
+fn encode_frame(opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(14 + payload.len());
+    frame.push(0x80 | opcode);
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        size if size < 126 => frame.push(mask_bit | size as u8),
+        size if size <= u16::MAX as usize => {
+            frame.push(mask_bit | 126);
+            frame.extend((size as u16).to_be_bytes());
+        }
+        size => {
+            frame.push(mask_bit | 127);
+            frame.extend((size as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            frame.extend(mask);
+            frame.extend(
+                payload
+                    .iter()
+                    .enumerate()
+                    .map(|(i, byte)| byte ^ mask[i % 4]),
+            );
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    frame
+}
+
+/// Channel that sends packets to a remote party as binary WebSocket messages, which allows
+/// parties running in a browser or behind restrictive firewalls to participate. Each message
+/// contains a packet with its length prefix and its header. The first message sent by the client
+/// contains its party ID.
+pub struct WebSocketChannel<S> {
+    /// Stream connected to the remote party.
+    stream: S,
+    /// Generator of the masks of the frames, which only the client side of the connection masks.
+    mask_rng: Option<Box<dyn RngCore + Send>>,
+    /// Bytes received from the stream that do not form a complete frame yet.
+    frame_buffer: Vec<u8>,
+    /// Payload of the fragments received of a message that is not complete yet.
+    fragments: Vec<u8>,
+    /// Payloads of the complete messages received, which do not form a complete packet yet.
+    recv_buffer: RecvBuffer,
+    /// Sequence number of the next packet to be sent.
+    send_seq: u64,
+    /// Sequence number of the next packet to be received.
+    recv_seq: u64,
+}
+
+impl<S: WebSocketStream> WebSocketChannel<S> {
+    /// Performs the opening handshake as a client through a connected stream and sends the ID of
+    /// the party in the first message.
+    pub fn connect(stream: S, host: &str, local_id: usize) -> anyhow::Result<Self> {
+        Self::connect_with_rng(stream, host, local_id, StdRng::from_entropy())
+    }
+
+    /// Works as [`WebSocketChannel::connect`], drawing the key of the handshake and the masks of
+    /// the frames from the given generator, e.g. a seeded one to reproduce the traffic in tests.
+    pub fn connect_with_rng<R>(
+        mut stream: S,
+        host: &str,
+        local_id: usize,
+        mut rng: R,
+    ) -> anyhow::Result<Self>
+    where
+        R: RngCore + CryptoRng + Send + 'static,
+    {
+        let key = encode_base64(&rng.gen::<[u8; 16]>());
+        let request = format!(
+            "GET {WEBSOCKET_PATH} HTTP/1.1\r\nHost: {host}\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Key: {key}\r\nSec-WebSocket-Version: 13\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes())?;
+
+        let response = read_http_headers(&mut stream)?;
+        let status = response.lines().next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("101") {
+            anyhow::bail!(WebSocketError::InvalidHandshake(format!(
+                "unexpected response {status}"
+            )));
+        }
+        if header_value(&response, "Sec-WebSocket-Accept") != Some(&accept_key(&key)) {
+            anyhow::bail!(WebSocketError::InvalidHandshake(
+                "wrong Sec-WebSocket-Accept header".to_string()
+            ));
+        }
+
+        let mut channel = Self::new(stream, Some(Box::new(rng)));
+        channel.write_message(OPCODE_BINARY, &local_id.to_le_bytes())?;
+        log::info!("established WebSocket connection as a client");
+        Ok(channel)
+    }
+
+    /// Performs the opening handshake as a server through a connected stream. Returns the
+    /// channel and the ID claimed by the remote party in its first message.
+    pub fn accept(mut stream: S) -> anyhow::Result<(Self, usize)> {
+        let request = read_http_headers(&mut stream)?;
+        let is_upgrade = header_value(&request, "Upgrade")
+            .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+        let key = header_value(&request, "Sec-WebSocket-Key");
+        let key = match (is_upgrade, key) {
+            (true, Some(key)) => key,
+            _ => {
+                stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+                anyhow::bail!(WebSocketError::InvalidHandshake(
+                    "the request is not a WebSocket upgrade".to_string()
+                ));
+            }
+        };
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(key)
+        );
+        stream.write_all(response.as_bytes())?;
+
+        let mut channel = Self::new(stream, None);
+        let id_message = channel.read_message()?;
+        let remote_id = id_message
+            .try_into()
+            .map(usize::from_le_bytes)
+            .map_err(|_| WebSocketError::MissingId)?;
+        log::info!("established WebSocket connection as a server with claimed ID {remote_id}");
+        Ok((channel, remote_id))
+    }
+
+    fn new(stream: S, mask_rng: Option<Box<dyn RngCore + Send>>) -> Self {
+        Self {
+            stream,
+            mask_rng,
+            frame_buffer: Vec::new(),
+            fragments: Vec::new(),
+            recv_buffer: RecvBuffer::default(),
+            send_seq: 0,
+            recv_seq: 0,
+        }
+    }
+
+    /// Writes a message in a single frame.
+    fn write_message(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mask = self.mask_rng.as_mut().map(|rng| rng.gen());
+        self.stream
+            .write_all(&encode_frame(opcode, payload, mask))?;
+        self.stream.flush()
+    }
+
+    /// Reads from the stream until a complete data message is received and returns its payload.
+    fn read_message(&mut self) -> anyhow::Result<Vec<u8>> {
+        loop {
+            if let Some(message) = self.take_message()? {
+                return Ok(message);
+            }
+            self.fill_buffer()?;
+        }
+    }
+
+    /// Processes the complete frames in the frame buffer until a complete data message is found.
+    /// Control frames are answered as required by RFC 6455.
+    fn take_message(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        while let Some(header) = FrameHeader::decode(&self.frame_buffer) {
+            let message_size = self.fragments.len().saturating_add(header.payload_size);
+            if message_size > self.recv_buffer.max_encoded_size() {
+                anyhow::bail!(ChannelError::PacketExceedsLimit {
+                    size: message_size,
+                    limit: self.recv_buffer.max_encoded_size(),
+                });
+            }
+            let frame_end = header.header_size + header.payload_size;
+            if self.frame_buffer.len() < frame_end {
+                break;
+            }
+            let mut payload = self.frame_buffer[header.header_size..frame_end].to_vec();
+            self.frame_buffer.drain(..frame_end);
+            if let Some(mask) = header.mask {
+                payload
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(i, byte)| *byte ^= mask[i % 4]);
+            }
+
+            match header.opcode {
+                OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    self.fragments.extend(payload);
+                    if header.fin {
+                        return Ok(Some(std::mem::take(&mut self.fragments)));
+                    }
+                }
+                OPCODE_PING => self.write_message(OPCODE_PONG, &payload)?,
+                OPCODE_PONG => {}
+                OPCODE_CLOSE => anyhow::bail!(ChannelError::ConnectionClosed),
+                _ => anyhow::bail!(WebSocketError::InvalidFrame("unsupported opcode")),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Extracts a packet from the received messages if they contain a complete one.
+    fn take_buffered_packet(&mut self) -> anyhow::Result<Option<Packet>> {
+        // Messages are taken one at a time, so that the packets received before a close frame
+        // are delivered.
+        loop {
+            if let Some(packet) = decode_packet(&mut self.recv_buffer, &mut self.recv_seq)? {
+                return Ok(Some(packet));
+            }
+            match self.take_message()? {
+                Some(message) => self.recv_buffer.extend_from_slice(&message),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Reads the available bytes from the stream into the frame buffer.
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        let read_bytes = self.stream.read(&mut chunk)?;
+        if read_bytes == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                ChannelError::ConnectionClosed,
+            ));
+        }
+        self.frame_buffer.extend_from_slice(&chunk[..read_bytes]);
+        Ok(())
+    }
+}
+
+impl<S: WebSocketStream> Channel for WebSocketChannel<S> {
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.write_message(OPCODE_CLOSE, &[])?;
+        log::info!("channel successfully closed");
+        Ok(())
+    }
+
+    fn send(&mut self, packet: &Packet) -> anyhow::Result<usize> {
+        let bytes = encode_packet(packet, &mut self.send_seq)?;
+        self.write_message(OPCODE_BINARY, &bytes)?;
+        Ok(packet.size())
+    }
+
+    fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.recv_buffer.set_max_packet_size(max_packet_size);
+    }
+
+    fn readiness(&self) -> Readiness {
+        Readiness::Fd(self.stream.socket().as_raw_fd())
+    }
+
+    fn rekey(&mut self) -> anyhow::Result<bool> {
+        self.stream.refresh_keys()
+    }
+
+    fn export_keying_material(&self, label: &[u8], output: &mut [u8]) -> anyhow::Result<bool> {
+        self.stream.export_keying_material(label, output)
+    }
+
+    fn recv(&mut self) -> anyhow::Result<Packet> {
+        loop {
+            if let Some(packet) = self.take_buffered_packet()? {
+                return Ok(packet);
+            }
+            self.fill_buffer()?;
+        }
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> anyhow::Result<Packet> {
+        let deadline = Instant::now() + timeout;
+        let result = loop {
+            if let Some(packet) = self.take_buffered_packet()? {
+                break Ok(packet);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break Err(ChannelError::RecvTimeout(timeout).into());
+            }
+            self.stream.socket().set_read_timeout(Some(remaining))?;
+            match self.fill_buffer() {
+                Ok(()) => continue,
+                Err(err) if is_timeout(&err) => {
+                    break Err(ChannelError::RecvTimeout(timeout).into())
+                }
+                Err(err) => break Err(err.into()),
+            }
+        };
+        self.stream.socket().set_read_timeout(None)?;
+        result
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<Option<Packet>> {
+        if let Some(packet) = self.take_buffered_packet()? {
+            return Ok(Some(packet));
+        }
+        self.stream.socket().set_nonblocking(true)?;
+        let result = loop {
+            match self.fill_buffer() {
+                Ok(()) => continue,
+                Err(err) if is_timeout(&err) => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        };
+        self.stream.socket().set_nonblocking(false)?;
+        result?;
+        self.take_buffered_packet()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{accept_key, encode_base64, WebSocketChannel};
+    use crate::net::{channel::Channel, Packet};
+
+    #[test]
+    fn handshake_accept_key() {
+        // Example from RFC 6455.
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(encode_base64(b"ab"), "YWI=");
+    }
+
+    #[test]
+    fn websocket_channel_roundtrip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let (mut channel, remote_id) = WebSocketChannel::accept(stream).unwrap();
+            assert_eq!(remote_id, 3);
+            let packet = channel.recv().unwrap();
+            channel.send(&packet).unwrap();
+        });
+
+        let stream = TcpStream::connect(address).unwrap();
+        let mut channel =
+            WebSocketChannel::connect_with_rng(stream, "localhost", 3, StdRng::seed_from_u64(3))
+                .unwrap();
+        let payload: Vec<u8> = (0..70_000).map(|i| i as u8).collect();
+        channel.send(&Packet::new(payload.clone())).unwrap();
+        assert_eq!(channel.recv().unwrap().as_slice(), payload.as_slice());
+        server.join().unwrap();
+    }
+}
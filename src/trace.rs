@@ -0,0 +1,139 @@
+#[cfg(feature = "native")]
+use std::time::Instant;
+use std::{
+    cell::{Cell, RefCell},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::Serialize;
+
+/// Target of the log records that open and close the spans.
+pub const SPAN_TARGET: &str = "shami_rs::span";
+
+/// Source of the IDs of the spans, which are unique across threads.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Spans open in the current thread, from the outermost to the innermost.
+    static SPANS: RefCell<Vec<OpenSpan>> = const { RefCell::new(Vec::new()) };
+    /// Duration in microseconds of the span that is being closed, while its last record is
+    /// logged.
+    static CLOSING: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Span registered as open in a thread.
+#[derive(Debug)]
+struct OpenSpan {
+    /// ID of the span.
+    id: u64,
+    /// Name of the span.
+    name: String,
+    /// Instant at which the span was opened. The clock is only read in native builds, since
+    /// WebAssembly has no monotonic clock without the help of the host.
+    #[cfg(feature = "native")]
+    start: Instant,
+}
+
+/// Spans in which a log record is emitted, which allows to build traces of the execution of
+/// every party from their logs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SpanContext {
+    /// Names of the open spans from the outermost to the innermost, separated by semicolons as in
+    /// the folded stacks from which flame graphs are drawn.
+    pub span: String,
+    /// Duration of the span in microseconds, only in the record that closes it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_us: Option<u64>,
+}
+
+/// Span of the execution of a thread, e.g. a phase of a protocol, which stays open until it is
+/// dropped. The spans opened while it is open are nested in it. Opening and closing a span logs a
+/// record with the target [`SPAN_TARGET`] at the debug level, and the record that closes it has
+/// its duration.
+#[derive(Debug)]
+#[must_use = "the span is closed as soon as it is dropped"]
+pub struct Span {
+    /// ID of the span.
+    id: u64,
+}
+
+/// Opens a span with the given name in the current thread.
+pub fn span(name: impl Into<String>) -> Span {
+    let name = name.into();
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    SPANS.with_borrow_mut(|spans| {
+        spans.push(OpenSpan {
+            id,
+            name: name.clone(),
+            #[cfg(feature = "native")]
+            start: Instant::now(),
+        })
+    });
+    log::debug!(target: SPAN_TARGET, "entering {name}");
+    Span { id }
+}
+
+/// Returns the spans open in the current thread, or `None` if there are none.
+pub fn current() -> Option<SpanContext> {
+    SPANS.with_borrow(|spans| {
+        let names: Vec<&str> = spans.iter().map(|span| span.name.as_str()).collect();
+        (!names.is_empty()).then(|| SpanContext {
+            span: names.join(";"),
+            elapsed_us: CLOSING.get(),
+        })
+    })
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        // A span that is dropped in another thread than the one in which it was opened, e.g.
+        // together with a network moved to another thread, is not found and closes silently.
+        let Some(position) =
+            SPANS.with_borrow(|spans| spans.iter().rposition(|span| span.id == self.id))
+        else {
+            return;
+        };
+        let name = SPANS.with_borrow(|spans| spans[position].name.clone());
+        #[cfg(feature = "native")]
+        {
+            let start = SPANS.with_borrow(|spans| spans[position].start);
+            CLOSING.set(Some(
+                u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX),
+            ));
+        }
+        log::debug!(target: SPAN_TARGET, "leaving {name}");
+        CLOSING.set(None);
+        // The spans nested in this one that are still open are closed with it.
+        SPANS.with_borrow_mut(|spans| spans.truncate(position));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::{current, span};
+
+    #[test]
+    fn spans_nest_per_thread() {
+        assert_eq!(current(), None);
+        let outer = span("setup");
+        {
+            let _inner = span("sharing");
+            assert_eq!(current().unwrap().span, "setup;sharing");
+            thread::spawn(|| assert_eq!(current(), None))
+                .join()
+                .unwrap();
+        }
+        let context = current().unwrap();
+        assert_eq!(context.span, "setup");
+        assert_eq!(context.elapsed_us, None);
+
+        // A span closes the spans nested in it that are still open.
+        let inner = span("opening");
+        drop(outer);
+        assert_eq!(current(), None);
+        drop(inner);
+        assert_eq!(current(), None);
+    }
+}
@@ -0,0 +1,264 @@
+use serde::{de::DeserializeOwned, Serialize};
+use zeroize::Zeroizing;
+
+use crate::codec::{Codec, WireCodec};
+
+/// Medium through which a party exchanges messages with the other parties of a protocol. The
+/// protocols of [`crate::mpc`] only need this trait, so a party that can not open TCP
+/// connections, such as a browser running the library compiled to WebAssembly, takes part in them
+/// by implementing it over WebSockets or WebRTC data channels. The messages between two parties
+/// must be delivered reliably and in the order in which they were sent.
+///
+/// The values are encoded with the codec of [`Transport::codec`], one message per value, and the
+/// buffers of the messages are wiped once the values are decoded. The implementation over TCP
+/// and TLS of the library is [`crate::net::Network`], which adds the framing, the rounds and the
+/// checks of its own packets.
+pub trait Transport {
+    /// Returns the ID of the party.
+    fn id(&self) -> usize;
+
+    /// Returns the number of parties of the protocol, including this one.
+    fn n_parties(&self) -> usize;
+
+    /// Returns the codec with which the values are encoded, which is bincode unless the transport
+    /// chooses another one.
+    fn codec(&self) -> Codec {
+        Codec::default()
+    }
+
+    /// Returns a secret of 32 bytes that the party shares with the party of the given ID under
+    /// the given label, derived from the session of their channel without any message, or `None`
+    /// if the transport can not derive one.
+    fn shared_secret(
+        &mut self,
+        _party_id: usize,
+        _label: &[u8],
+    ) -> anyhow::Result<Option<[u8; 32]>> {
+        Ok(None)
+    }
+
+    /// Sends a message to the party with the given ID, which is never the party itself.
+    fn send_message(&mut self, party_id: usize, message: Vec<u8>) -> anyhow::Result<()>;
+
+    /// Receives the next message of the party with the given ID, waiting until it arrives.
+    fn recv_message(&mut self, party_id: usize) -> anyhow::Result<Vec<u8>>;
+
+    /// Sends the value `values[i]` to the party `i` and receives one value from each party, in
+    /// order of ID. The value of the party itself is kept instead of sent. Every message is sent
+    /// before any is received, so the sends must not wait for the other parties to receive.
+    fn exchange_values<T>(&mut self, values: &[T]) -> anyhow::Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+        Self: Sized,
+    {
+        if values.len() != self.n_parties() {
+            anyhow::bail!(
+                "there are {} values for {} parties",
+                values.len(),
+                self.n_parties()
+            );
+        }
+        for (party_id, value) in values.iter().enumerate() {
+            if party_id != self.id() {
+                self.send_message(party_id, self.codec().encode(value)?)?;
+            }
+        }
+        // The own value goes through the same encoding, so it does not need to be cloned.
+        let own = Zeroizing::new(self.codec().encode(&values[self.id()])?);
+        (0..self.n_parties())
+            .map(|party_id| {
+                let message = if party_id == self.id() {
+                    own.clone()
+                } else {
+                    Zeroizing::new(self.recv_message(party_id)?)
+                };
+                self.codec().decode(&message)
+            })
+            .collect()
+    }
+
+    /// Sends a value to every party and receives one value from every party, in order of ID.
+    fn broadcast_and_gather_values<T>(&mut self, value: &T) -> anyhow::Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+        Self: Sized,
+    {
+        let message = Zeroizing::new(self.codec().encode(value)?);
+        let id = self.id();
+        for party_id in (0..self.n_parties()).filter(|party_id| *party_id != id) {
+            self.send_message(party_id, message.to_vec())?;
+        }
+        (0..self.n_parties())
+            .map(|party_id| {
+                let message = if party_id == self.id() {
+                    message.clone()
+                } else {
+                    Zeroizing::new(self.recv_message(party_id)?)
+                };
+                self.codec().decode(&message)
+            })
+            .collect()
+    }
+}
+
+/// A transport that is borrowed mutably is a transport too, so that a protocol can take it for a
+/// while, e.g. in a [`crate::mpc::engine::Engine`], and give it back afterwards.
+impl<N: Transport> Transport for &mut N {
+    fn id(&self) -> usize {
+        (**self).id()
+    }
+
+    fn n_parties(&self) -> usize {
+        (**self).n_parties()
+    }
+
+    fn codec(&self) -> Codec {
+        (**self).codec()
+    }
+
+    fn shared_secret(&mut self, party_id: usize, label: &[u8]) -> anyhow::Result<Option<[u8; 32]>> {
+        (**self).shared_secret(party_id, label)
+    }
+
+    fn send_message(&mut self, party_id: usize, message: Vec<u8>) -> anyhow::Result<()> {
+        (**self).send_message(party_id, message)
+    }
+
+    fn recv_message(&mut self, party_id: usize) -> anyhow::Result<Vec<u8>> {
+        (**self).recv_message(party_id)
+    }
+
+    fn exchange_values<T>(&mut self, values: &[T]) -> anyhow::Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        (**self).exchange_values(values)
+    }
+
+    fn broadcast_and_gather_values<T>(&mut self, value: &T) -> anyhow::Result<Vec<T>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        (**self).broadcast_and_gather_values(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc::{self, Receiver, Sender},
+        thread,
+    };
+
+    use rand::thread_rng;
+
+    use super::Transport;
+    use crate::{
+        codec::Codec,
+        math::mersenne61::Mersenne61,
+        mpc::{
+            engine::Engine, expression::Expression, reconstruct_secret, share::ShamirShare,
+            share_inputs,
+        },
+    };
+
+    /// Transport over in-memory queues, as a stand-in for the WebSockets of a browser.
+    struct QueueTransport {
+        id: usize,
+        codec: Codec,
+        senders: Vec<Sender<Vec<u8>>>,
+        receivers: Vec<Receiver<Vec<u8>>>,
+    }
+
+    impl QueueTransport {
+        /// Creates the transports of the given number of parties, connected with each other.
+        fn create(n_parties: usize, codec: Codec) -> Vec<Self> {
+            let mut senders: Vec<Vec<Sender<Vec<u8>>>> = vec![Vec::new(); n_parties];
+            let mut receivers: Vec<Vec<Receiver<Vec<u8>>>> =
+                (0..n_parties).map(|_| Vec::new()).collect();
+            // The queue `(from, to)` carries the messages of party `from` to party `to`.
+            for sent_bys in &mut senders {
+                for to_receivers in &mut receivers {
+                    let (sender, receiver) = mpsc::channel();
+                    sent_bys.push(sender);
+                    to_receivers.push(receiver);
+                }
+            }
+            senders
+                .into_iter()
+                .zip(receivers)
+                .enumerate()
+                .map(|(id, (senders, receivers))| Self {
+                    id,
+                    codec,
+                    senders,
+                    receivers,
+                })
+                .collect()
+        }
+    }
+
+    impl Transport for QueueTransport {
+        fn id(&self) -> usize {
+            self.id
+        }
+
+        fn n_parties(&self) -> usize {
+            self.senders.len()
+        }
+
+        fn codec(&self) -> Codec {
+            self.codec
+        }
+
+        fn send_message(&mut self, party_id: usize, message: Vec<u8>) -> anyhow::Result<()> {
+            Ok(self.senders[party_id].send(message)?)
+        }
+
+        fn recv_message(&mut self, party_id: usize) -> anyhow::Result<Vec<u8>> {
+            Ok(self.receivers[party_id].recv()?)
+        }
+    }
+
+    #[test]
+    fn protocols_run_over_any_transport() {
+        const THRESHOLD: usize = 1;
+        let inputs = [3, 5, 7];
+        let expression: Expression = "x0 * x1 * x2 + x1".parse().unwrap();
+        let handles: Vec<_> = [Codec::Bincode, Codec::Postcard, Codec::Protobuf]
+            .into_iter()
+            .flat_map(|codec| {
+                QueueTransport::create(inputs.len(), codec)
+                    .into_iter()
+                    .zip(inputs)
+            })
+            .map(|(mut transport, input)| {
+                let expression = expression.clone();
+                thread::spawn(move || {
+                    let mut rng = thread_rng();
+                    let own_shares = share_inputs(
+                        &[Mersenne61::from(input)],
+                        transport.n_parties(),
+                        THRESHOLD,
+                        &mut rng,
+                    );
+                    let shares: Vec<Vec<ShamirShare<Mersenne61>>> =
+                        transport.exchange_values(&own_shares).unwrap();
+                    let output = expression
+                        .evaluate(
+                            &shares,
+                            THRESHOLD,
+                            &mut rng,
+                            &mut Engine::new(&mut transport),
+                        )
+                        .unwrap();
+                    let outputs = transport.broadcast_and_gather_values(&output).unwrap();
+                    reconstruct_secret(outputs)
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Mersenne61::from(3 * 5 * 7 + 5));
+        }
+    }
+}